@@ -5,7 +5,7 @@
 ///
 /// [1]: https://github.com/rust-lang/cargo/issues/3544
 fn main() {
-    // Generate `ngx_os` and `ngx_feature` cfg values
+    // Generate `ngx_os`, `ngx_feature` and nginx version cfg values
 
     // Specify acceptable values for `ngx_feature`
     println!("cargo::rerun-if-env-changed=DEP_NGINX_FEATURES_CHECK");
@@ -33,25 +33,32 @@ fn main() {
         println!("cargo::rustc-cfg=ngx_os=\"{os}\"");
     }
 
-    // Generate cfg values for version checks
-    const VERSION_CHECKS: &[(u64, &str)] = &[
-        //
-        (1_021_001, "nginx1_21_1"),
-        (1_025_001, "nginx1_25_1"),
-    ];
-    VERSION_CHECKS.iter().for_each(|check| println!("cargo::rustc-check-cfg=cfg({})", check.1));
+    // Generate cfg values for version checks, e.g. `nginx1_25_1`
 
-    println!("cargo::rerun-if-env-changed=DEP_NGINX_VERSION_NUMBER");
-    if let Ok(version) = std::env::var("DEP_NGINX_VERSION_NUMBER") {
-        let version: u64 = version.parse().unwrap();
-
-        for check in VERSION_CHECKS {
-            if version >= check.0 {
-                println!("cargo::rustc-cfg={}", check.1);
-            }
+    // Specify acceptable version cfgs
+    println!("cargo::rerun-if-env-changed=DEP_NGINX_VERSIONS_CHECK");
+    if let Ok(versions) = std::env::var("DEP_NGINX_VERSIONS_CHECK") {
+        println!("cargo::rustc-check-cfg=cfg({versions})");
+    }
+    // Read version cfgs matched by nginx-sys and pass to the compiler.
+    println!("cargo::rerun-if-env-changed=DEP_NGINX_VERSIONS");
+    if let Ok(versions) = std::env::var("DEP_NGINX_VERSIONS") {
+        for version in versions.split(',').map(str::trim).filter(|x| !x.is_empty()) {
+            println!("cargo::rustc-cfg={version}");
         }
     }
 
+    // Expose build metadata to the module code, see `ngx::ngx_module_build_info!`.
+    // The variable only applies to the crate using this buildscript.
+    println!("cargo::rerun-if-env-changed=DEP_NGINX_VERSION");
+    println!("cargo::rerun-if-env-changed=PROFILE");
+    println!(
+        "cargo::rustc-env=NGX_RUST_MODULE_BUILD=nginx/{} {} {}",
+        std::env::var("DEP_NGINX_VERSION").unwrap_or("unknown".to_string()),
+        std::env::var("TARGET").unwrap_or_default(),
+        std::env::var("PROFILE").unwrap_or_default(),
+    );
+
     // Pass build directory to the tests
     println!("cargo::rerun-if-env-changed=DEP_NGINX_BUILD_DIR");
     if let Ok(build_dir) = std::env::var("DEP_NGINX_BUILD_DIR") {
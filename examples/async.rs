@@ -1,9 +1,7 @@
 extern crate alloc;
 
 use alloc::sync::Arc;
-use core::ffi::{c_char, c_void};
 use core::mem;
-use core::ptr;
 use core::sync::atomic::{AtomicBool, AtomicPtr, Ordering};
 use core::time::Duration;
 use std::sync::OnceLock;
@@ -11,12 +9,11 @@ use std::time::Instant;
 
 use ngx::core::Status;
 use ngx::ffi::{
-    NGX_CONF_TAKE1, NGX_HTTP_LOC_CONF, NGX_HTTP_LOC_CONF_OFFSET, NGX_HTTP_MODULE, NGX_LOG_EMERG,
-    ngx_command_t, ngx_conf_t, ngx_connection_t, ngx_event_t, ngx_http_module_t, ngx_int_t,
-    ngx_module_t, ngx_post_event, ngx_posted_events, ngx_posted_next_events, ngx_str_t, ngx_uint_t,
+    NGX_HTTP_LOC_CONF, NGX_HTTP_LOC_CONF_OFFSET, ngx_conf_t, ngx_connection_t, ngx_event_t,
+    ngx_int_t, ngx_module_t, ngx_post_event, ngx_posted_events, ngx_posted_next_events,
 };
 use ngx::http::{self, HttpModule, HttpModuleLocationConf, HttpRequestHandler, MergeConfigError};
-use ngx::{ngx_conf_log_error, ngx_log_debug_http, ngx_string};
+use ngx::ngx_log_debug_http;
 use tokio::runtime::Runtime;
 
 struct Module;
@@ -44,43 +41,22 @@ unsafe impl HttpModuleLocationConf for Module {
     type LocationConf = ModuleConfig;
 }
 
-static mut NGX_HTTP_ASYNC_COMMANDS: [ngx_command_t; 2] = [
-    ngx_command_t {
-        name: ngx_string!("async"),
-        type_: (NGX_HTTP_LOC_CONF | NGX_CONF_TAKE1) as ngx_uint_t,
-        set: Some(ngx_http_async_commands_set_enable),
-        conf: NGX_HTTP_LOC_CONF_OFFSET,
-        offset: 0,
-        post: ptr::null_mut(),
-    },
-    ngx_command_t::empty(),
-];
-
-static NGX_HTTP_ASYNC_MODULE_CTX: ngx_http_module_t = ngx_http_module_t {
-    preconfiguration: Some(Module::preconfiguration),
-    postconfiguration: Some(Module::postconfiguration),
-    create_main_conf: None,
-    init_main_conf: None,
-    create_srv_conf: None,
-    merge_srv_conf: None,
-    create_loc_conf: Some(Module::create_loc_conf),
-    merge_loc_conf: Some(Module::merge_loc_conf),
-};
+ngx::ngx_commands! {
+    static mut NGX_HTTP_ASYNC_COMMANDS = [
+        "async" (NGX_HTTP_LOC_CONF, NGX_HTTP_LOC_CONF_OFFSET) =>
+            fn(_cf, conf: &mut ModuleConfig, enable: bool) -> Result<(), &'static str> {
+                conf.enable = enable;
+                Ok(())
+            },
+    ];
+}
 
-// Generate the `ngx_modules` table with exported modules.
-// This feature is required to build a 'cdylib' dynamic module outside of the NGINX buildsystem.
-#[cfg(feature = "export-modules")]
-ngx::ngx_modules!(ngx_http_async_module);
-
-#[used]
-#[allow(non_upper_case_globals)]
-#[cfg_attr(not(feature = "export-modules"), unsafe(no_mangle))]
-pub static mut ngx_http_async_module: ngx_module_t = ngx_module_t {
-    ctx: &raw const NGX_HTTP_ASYNC_MODULE_CTX as _,
-    commands: unsafe { &raw mut NGX_HTTP_ASYNC_COMMANDS[0] },
-    type_: NGX_HTTP_MODULE as _,
-    ..ngx_module_t::default()
-};
+ngx::ngx_http_module! {
+    pub static mut ngx_http_async_module = Module {
+        commands: NGX_HTTP_ASYNC_COMMANDS,
+        conf: [loc],
+    }
+}
 
 impl http::Merge for ModuleConfig {
     fn merge(&mut self, prev: &ModuleConfig) -> Result<(), MergeConfigError> {
@@ -165,8 +141,8 @@ impl HttpRequestHandler for AsyncAccessHandler {
 
         let ctx = unsafe { &mut *ctx };
         ctx.event.handler = Some(check_async_work_done);
-        ctx.event.data = request.connection().cast();
-        ctx.event.log = unsafe { (*request.connection()).log };
+        ctx.event.data = request.connection().as_ptr().cast();
+        ctx.event.log = request.log();
         unsafe { ngx_post_event(&raw mut ctx.event, &raw mut ngx_posted_next_events) };
 
         // Request is no longer needed and can be converted to something movable to the async block
@@ -194,35 +170,6 @@ impl HttpRequestHandler for AsyncAccessHandler {
     }
 }
 
-extern "C" fn ngx_http_async_commands_set_enable(
-    cf: *mut ngx_conf_t,
-    _cmd: *mut ngx_command_t,
-    conf: *mut c_void,
-) -> *mut c_char {
-    unsafe {
-        let conf = &mut *(conf as *mut ModuleConfig);
-        let args: &[ngx_str_t] = (*(*cf).args).as_slice();
-        let val = match args[1].to_str() {
-            Ok(s) => s,
-            Err(_) => {
-                ngx_conf_log_error!(NGX_LOG_EMERG, cf, "`async` argument is not utf-8 encoded");
-                return ngx::core::NGX_CONF_ERROR;
-            }
-        };
-
-        // set default value optionally
-        conf.enable = false;
-
-        if val.eq_ignore_ascii_case("on") {
-            conf.enable = true;
-        } else if val.eq_ignore_ascii_case("off") {
-            conf.enable = false;
-        }
-    };
-
-    ngx::core::NGX_CONF_OK
-}
-
 fn ngx_http_async_runtime() -> &'static Runtime {
     // Should not be called from the master process
     assert_ne!(unsafe { ngx::ffi::ngx_process }, ngx::ffi::NGX_PROCESS_MASTER as _);
@@ -4,9 +4,8 @@ use core::ptr;
 use http::HeaderMap;
 use ngx::core::Status;
 use ngx::ffi::{
-    NGX_CONF_TAKE1, NGX_HTTP_LOC_CONF, NGX_HTTP_LOC_CONF_OFFSET, NGX_HTTP_MODULE,
-    NGX_HTTP_SRV_CONF, NGX_LOG_EMERG, ngx_command_t, ngx_conf_t, ngx_http_module_t, ngx_int_t,
-    ngx_module_t, ngx_str_t, ngx_uint_t,
+    NGX_CONF_TAKE1, NGX_HTTP_LOC_CONF, NGX_HTTP_LOC_CONF_OFFSET, NGX_HTTP_SRV_CONF, NGX_LOG_EMERG,
+    ngx_command_t, ngx_conf_t, ngx_int_t, ngx_module_t, ngx_str_t, ngx_uint_t,
 };
 use ngx::http::*;
 use ngx::{ngx_conf_log_error, ngx_log_debug_http, ngx_string};
@@ -84,31 +83,12 @@ static mut NGX_HTTP_AWSSIGV4_COMMANDS: [ngx_command_t; 6] = [
     ngx_command_t::empty(),
 ];
 
-static NGX_HTTP_AWSSIGV4_MODULE_CTX: ngx_http_module_t = ngx_http_module_t {
-    preconfiguration: Some(Module::preconfiguration),
-    postconfiguration: Some(Module::postconfiguration),
-    create_main_conf: None,
-    init_main_conf: None,
-    create_srv_conf: None,
-    merge_srv_conf: None,
-    create_loc_conf: Some(Module::create_loc_conf),
-    merge_loc_conf: Some(Module::merge_loc_conf),
-};
-
-// Generate the `ngx_modules` table with exported modules.
-// This feature is required to build a 'cdylib' dynamic module outside of the NGINX buildsystem.
-#[cfg(feature = "export-modules")]
-ngx::ngx_modules!(ngx_http_awssigv4_module);
-
-#[used]
-#[allow(non_upper_case_globals)]
-#[cfg_attr(not(feature = "export-modules"), unsafe(no_mangle))]
-pub static mut ngx_http_awssigv4_module: ngx_module_t = ngx_module_t {
-    ctx: &raw const NGX_HTTP_AWSSIGV4_MODULE_CTX as _,
-    commands: unsafe { &raw mut NGX_HTTP_AWSSIGV4_COMMANDS[0] },
-    type_: NGX_HTTP_MODULE as _,
-    ..ngx_module_t::default()
-};
+ngx::ngx_http_module! {
+    pub static mut ngx_http_awssigv4_module = Module {
+        commands: NGX_HTTP_AWSSIGV4_COMMANDS,
+        conf: [loc],
+    }
+}
 
 impl Merge for ModuleConfig {
     fn merge(&mut self, prev: &ModuleConfig) -> Result<(), MergeConfigError> {
@@ -276,8 +256,9 @@ impl HttpRequestHandler for AwsSigV4HeaderHandler {
             // Iterate over requests headers_in and copy into HeaderMap
             // Copy only headers that will be used to sign the request
             let mut headers = HeaderMap::new();
-            for (name, value) in request.headers_in_iterator() {
-                if let Ok(name) = name.to_str() {
+            for h in request.headers_in_iterator() {
+                let value = h.value();
+                if let Ok(name) = h.key().to_str() {
                     if name.to_lowercase() == "host" {
                         let Ok(value) = http::HeaderValue::from_bytes(value.as_bytes()) else {
                             return Status::NGX_DECLINED;
@@ -312,11 +293,11 @@ impl HttpRequestHandler for AwsSigV4HeaderHandler {
         request.add_header_in("authorization", signature.as_str());
         request.add_header_in("X-Amz-Date", datetime_now.as_str());
 
-        for (name, value) in request.headers_out_iterator() {
-            ngx_log_debug_http!(request, "headers_out {name}: {value}",);
+        for h in request.headers_out_iterator() {
+            ngx_log_debug_http!(request, "headers_out {h}");
         }
-        for (name, value) in request.headers_in_iterator() {
-            ngx_log_debug_http!(request, "headers_in  {name}: {value}",);
+        for h in request.headers_in_iterator() {
+            ngx_log_debug_http!(request, "headers_in  {h}");
         }
 
         Status::NGX_OK
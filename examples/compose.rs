@@ -0,0 +1,140 @@
+/*
+ * A content handler composing the response from the output of subrequests.
+ *
+ * The reference usage of `OutputSequencer`: the `compose` directive lists the URIs, and the
+ * response is made of a line with the response of each subrequest, between the `begin` and `end`
+ * lines written by the handler, e.g.
+ *
+ *     location /page {
+ *         compose /header /body;
+ *     }
+ */
+use core::ffi::{c_char, c_void};
+use core::ptr;
+
+use ngx::core::{NGX_CONF_ERROR, NGX_CONF_OK};
+use ngx::http::{self, OutputSequencer};
+use ngx::prelude::*;
+
+struct Module;
+
+impl HttpModule for Module {
+    fn module() -> &'static ngx_module_t {
+        unsafe { &*::core::ptr::addr_of!(ngx_http_compose_module) }
+    }
+
+    unsafe extern "C" fn postconfiguration(cf: *mut ngx_conf_t) -> ngx_int_t {
+        // SAFETY: this function is called with non-NULL cf always
+        let cf = unsafe { &mut *cf };
+        http::add_phase_handler::<ComposeHandler>(cf)
+            .map_or(Status::NGX_ERROR, |_| Status::NGX_OK)
+            .into()
+    }
+}
+
+#[derive(Debug, Default)]
+struct LocationConf {
+    uris: Vec<String>,
+}
+
+impl Merge for LocationConf {
+    fn merge(&mut self, prev: &LocationConf) -> Result<(), MergeConfigError> {
+        if self.uris.is_empty() {
+            self.uris = prev.uris.clone();
+        }
+        Ok(())
+    }
+}
+
+unsafe impl HttpModuleLocationConf for Module {
+    type LocationConf = LocationConf;
+}
+
+static mut NGX_HTTP_COMPOSE_COMMANDS: [ngx_command_t; 2] = [
+    ngx_command_t {
+        name: ngx_string!("compose"),
+        type_: (NGX_HTTP_LOC_CONF | NGX_CONF_1MORE) as ngx_uint_t,
+        set: Some(ngx_http_compose_set),
+        conf: NGX_HTTP_LOC_CONF_OFFSET,
+        offset: 0,
+        post: ptr::null_mut(),
+    },
+    ngx_command_t::empty(),
+];
+
+ngx_http_module! {
+    pub static mut ngx_http_compose_module = Module {
+        commands: NGX_HTTP_COMPOSE_COMMANDS,
+        conf: [loc],
+    }
+}
+
+extern "C" fn ngx_http_compose_set(
+    cf: *mut ngx_conf_t,
+    _cmd: *mut ngx_command_t,
+    conf: *mut c_void,
+) -> *mut c_char {
+    // SAFETY: this function is called with non-NULL cf and conf always
+    let cf = unsafe { &mut *cf };
+    let conf = unsafe { &mut *(conf as *mut LocationConf) };
+    let args: &[ngx_str_t] = unsafe { (*cf.args).as_slice() };
+
+    if !conf.uris.is_empty() {
+        ngx_conf_log_error!(NGX_LOG_EMERG, cf, "\"compose\" directive is duplicate");
+        return NGX_CONF_ERROR;
+    }
+
+    for arg in &args[1..] {
+        // SAFETY: the arguments are allocated from the configuration pool.
+        let Ok(uri) = unsafe { NgxStr::from_ngx_str(*arg) }.to_str() else {
+            ngx_conf_log_error!(NGX_LOG_EMERG, cf, "invalid URI \"{arg}\"");
+            return NGX_CONF_ERROR;
+        };
+        conf.uris.push(uri.to_owned());
+    }
+
+    NGX_CONF_OK
+}
+
+struct ComposeHandler;
+
+impl HttpRequestHandler for ComposeHandler {
+    const PHASE: HttpPhase = HttpPhase::Content;
+    type Output = Status;
+
+    fn handler(request: &mut Request) -> Self::Output {
+        let Some(lc) = Module::location_conf(request) else {
+            return Status::NGX_DECLINED;
+        };
+        if lc.uris.is_empty() {
+            return Status::NGX_DECLINED;
+        }
+
+        compose(request, &lc.uris).unwrap_or_else(|rc| rc)
+    }
+}
+
+fn compose(request: &mut Request, uris: &[String]) -> Result<Status, Status> {
+    let rc = request.discard_request_body();
+    if rc != Status::NGX_OK {
+        return Ok(rc);
+    }
+
+    request.set_status(HTTPStatus::OK);
+    let rc = request.send_header();
+    if rc == Status::NGX_ERROR || rc > Status::NGX_OK || request.header_only() {
+        return Ok(rc);
+    }
+
+    let mut out = OutputSequencer::new(request);
+    out.write_static("begin\n")?;
+
+    for uri in uris {
+        out.include(uri, None, false)?;
+        ngx_log_debug_http!(out.request(), "compose: subrequest \"{uri}\"");
+        out.write_static("\n")?;
+    }
+
+    out.write_static("end\n")?;
+    Ok(out.finish())
+}
@@ -1,13 +1,12 @@
-use core::ffi::{c_char, c_void};
 use core::ptr;
 
 use ngx::core::Status;
 use ngx::ffi::{
-    NGX_CONF_TAKE1, NGX_HTTP_LOC_CONF, NGX_HTTP_LOC_CONF_OFFSET, NGX_HTTP_MODULE, NGX_LOG_EMERG,
-    ngx_command_t, ngx_conf_t, ngx_http_module_t, ngx_int_t, ngx_module_t, ngx_str_t, ngx_uint_t,
+    NGX_CONF_TAKE1, NGX_HTTP_LOC_CONF, NGX_HTTP_LOC_CONF_OFFSET, ngx_command_t, ngx_conf_t,
+    ngx_int_t, ngx_module_t, ngx_uint_t,
 };
-use ngx::http::{self, HttpModule, HttpModuleLocationConf, HttpRequestHandler, MergeConfigError};
-use ngx::{ngx_conf_log_error, ngx_log_debug_http, ngx_string};
+use ngx::http::{self, EnableFlag, HttpModule, HttpModuleLocationConf, HttpRequestHandler};
+use ngx::{ngx_log_debug_http, ngx_string};
 
 struct Module;
 
@@ -25,20 +24,15 @@ impl http::HttpModule for Module {
     }
 }
 
-#[derive(Debug, Default)]
-struct ModuleConfig {
-    enable: bool,
-}
-
 unsafe impl HttpModuleLocationConf for Module {
-    type LocationConf = ModuleConfig;
+    type LocationConf = EnableFlag;
 }
 
 static mut NGX_HTTP_CURL_COMMANDS: [ngx_command_t; 2] = [
     ngx_command_t {
         name: ngx_string!("curl"),
         type_: (NGX_HTTP_LOC_CONF | NGX_CONF_TAKE1) as ngx_uint_t,
-        set: Some(ngx_http_curl_commands_set_enable),
+        set: Some(http::enable_flag_slot),
         conf: NGX_HTTP_LOC_CONF_OFFSET,
         offset: 0,
         post: ptr::null_mut(),
@@ -46,38 +40,10 @@ static mut NGX_HTTP_CURL_COMMANDS: [ngx_command_t; 2] = [
     ngx_command_t::empty(),
 ];
 
-static NGX_HTTP_CURL_MODULE_CTX: ngx_http_module_t = ngx_http_module_t {
-    preconfiguration: Some(Module::preconfiguration),
-    postconfiguration: Some(Module::postconfiguration),
-    create_main_conf: None,
-    init_main_conf: None,
-    create_srv_conf: None,
-    merge_srv_conf: None,
-    create_loc_conf: Some(Module::create_loc_conf),
-    merge_loc_conf: Some(Module::merge_loc_conf),
-};
-
-// Generate the `ngx_modules` table with exported modules.
-// This feature is required to build a 'cdylib' dynamic module outside of the NGINX buildsystem.
-#[cfg(feature = "export-modules")]
-ngx::ngx_modules!(ngx_http_curl_module);
-
-#[used]
-#[allow(non_upper_case_globals)]
-#[cfg_attr(not(feature = "export-modules"), unsafe(no_mangle))]
-pub static mut ngx_http_curl_module: ngx_module_t = ngx_module_t {
-    ctx: &raw const NGX_HTTP_CURL_MODULE_CTX as _,
-    commands: unsafe { &raw mut NGX_HTTP_CURL_COMMANDS[0] },
-    type_: NGX_HTTP_MODULE as _,
-    ..ngx_module_t::default()
-};
-
-impl http::Merge for ModuleConfig {
-    fn merge(&mut self, prev: &ModuleConfig) -> Result<(), MergeConfigError> {
-        if prev.enable {
-            self.enable = true;
-        };
-        Ok(())
+ngx::ngx_http_module! {
+    pub static mut ngx_http_curl_module = Module {
+        commands: NGX_HTTP_CURL_COMMANDS,
+        conf: [loc],
     }
 }
 
@@ -90,9 +56,9 @@ impl HttpRequestHandler for CurlRequestHandler {
     fn handler(request: &mut http::Request) -> Self::Output {
         let co = Module::location_conf(request).expect("module config is none");
 
-        ngx_log_debug_http!(request, "curl module enabled: {}", co.enable);
+        ngx_log_debug_http!(request, "curl module enabled: {}", co.is_enabled());
 
-        match co.enable {
+        match co.is_enabled() {
             true => {
                 if request.user_agent().is_some_and(|ua| ua.as_bytes().starts_with(b"curl")) {
                     http::HTTPStatus::FORBIDDEN.into()
@@ -104,33 +70,3 @@ impl HttpRequestHandler for CurlRequestHandler {
         }
     }
 }
-
-extern "C" fn ngx_http_curl_commands_set_enable(
-    cf: *mut ngx_conf_t,
-    _cmd: *mut ngx_command_t,
-    conf: *mut c_void,
-) -> *mut c_char {
-    unsafe {
-        let conf = &mut *(conf as *mut ModuleConfig);
-        let args: &[ngx_str_t] = (*(*cf).args).as_slice();
-
-        let val = match args[1].to_str() {
-            Ok(s) => s,
-            Err(_) => {
-                ngx_conf_log_error!(NGX_LOG_EMERG, cf, "`curl` argument is not utf-8 encoded");
-                return ngx::core::NGX_CONF_ERROR;
-            }
-        };
-
-        // set default value optionally
-        conf.enable = false;
-
-        if val.len() == 2 && val.eq_ignore_ascii_case("on") {
-            conf.enable = true;
-        } else if val.len() == 3 && val.eq_ignore_ascii_case("off") {
-            conf.enable = false;
-        }
-    };
-
-    ngx::core::NGX_CONF_OK
-}
@@ -1,18 +1,14 @@
-use core::ffi::{c_int, c_void};
-use core::mem;
-use core::ptr::{self, NonNull};
+use core::ffi::c_void;
+use core::ptr::NonNull;
 
 use ngx::core::{Pool, Status};
 use ngx::ffi::{
-    INET_ADDRSTRLEN, NGX_HTTP_MODULE, in_port_t, ngx_conf_t, ngx_connection_local_sockaddr,
-    ngx_http_add_variable, ngx_http_module_t, ngx_http_variable_t, ngx_inet_get_port, ngx_int_t,
-    ngx_module_t, ngx_sock_ntop, ngx_str_t, ngx_variable_value_t, sockaddr, sockaddr_storage,
+    in_port_t, ngx_conf_t, ngx_http_variable_t, ngx_int_t, ngx_module_t, ngx_str_t,
+    ngx_variable_value_t,
 };
 use ngx::http::{self, HttpModule};
 use ngx::{http_variable_get, ngx_log_debug_http, ngx_string};
 
-const IPV4_STRLEN: usize = INET_ADDRSTRLEN as usize;
-
 #[derive(Debug, Default)]
 struct NgxHttpOrigDstCtx {
     orig_dst_addr: ngx_str_t,
@@ -74,31 +70,10 @@ impl NgxHttpOrigDstCtx {
     }
 }
 
-static NGX_HTTP_ORIG_DST_MODULE_CTX: ngx_http_module_t = ngx_http_module_t {
-    preconfiguration: Some(Module::preconfiguration),
-    postconfiguration: Some(Module::postconfiguration),
-    create_main_conf: None,
-    init_main_conf: None,
-    create_srv_conf: None,
-    merge_srv_conf: None,
-    create_loc_conf: None,
-    merge_loc_conf: None,
-};
-
-// Generate the `ngx_modules` table with exported modules.
-// This feature is required to build a 'cdylib' dynamic module outside of the NGINX buildsystem.
-#[cfg(feature = "export-modules")]
-ngx::ngx_modules!(ngx_http_orig_dst_module);
-
-#[used]
-#[allow(non_upper_case_globals)]
-#[cfg_attr(not(feature = "export-modules"), unsafe(no_mangle))]
-pub static mut ngx_http_orig_dst_module: ngx_module_t = ngx_module_t {
-    ctx: &raw const NGX_HTTP_ORIG_DST_MODULE_CTX as _,
-    commands: ptr::null_mut(),
-    type_: NGX_HTTP_MODULE as _,
-    ..ngx_module_t::default()
-};
+ngx::ngx_http_module! {
+    pub static mut ngx_http_orig_dst_module = Module {
+    }
+}
 
 static mut NGX_HTTP_ORIG_DST_VARS: [ngx_http_variable_t; 2] = [
     // ngx_str_t name
@@ -125,60 +100,16 @@ static mut NGX_HTTP_ORIG_DST_VARS: [ngx_http_variable_t; 2] = [
     },
 ];
 
-unsafe fn ngx_get_origdst(request: &mut http::Request) -> Result<(String, in_port_t), Status> {
+fn ngx_get_origdst(request: &mut http::Request) -> Result<(String, in_port_t), Status> {
     let c = request.connection();
 
-    if unsafe { (*c).type_ } != libc::SOCK_STREAM {
-        ngx_log_debug_http!(request, "httporigdst: connection is not type SOCK_STREAM");
-        return Err(Status::NGX_DECLINED);
-    }
-
-    if unsafe { ngx_connection_local_sockaddr(c, ptr::null_mut(), 0) } != Status::NGX_OK.into() {
-        ngx_log_debug_http!(request, "httporigdst: no local sockaddr from connection");
-        return Err(Status::NGX_ERROR);
-    }
-
-    let level: c_int;
-    let optname: c_int;
-    match unsafe { (*(*c).local_sockaddr).sa_family } as i32 {
-        libc::AF_INET => {
-            level = libc::SOL_IP;
-            optname = libc::SO_ORIGINAL_DST;
-        }
-        _ => {
-            ngx_log_debug_http!(request, "httporigdst: only support IPv4");
-            return Err(Status::NGX_DECLINED);
+    match c.original_dst() {
+        Ok(addr) => Ok((addr.ip().to_string(), addr.port())),
+        Err(e) => {
+            ngx_log_debug_http!(request, "httporigdst: original destination is not available");
+            Err(e)
         }
     }
-
-    let mut addr: sockaddr_storage = unsafe { mem::zeroed() };
-    let mut addrlen: libc::socklen_t = mem::size_of_val(&addr) as libc::socklen_t;
-    let rc = unsafe {
-        libc::getsockopt((*c).fd, level, optname, (&raw mut addr).cast(), &raw mut addrlen)
-    };
-    if rc == -1 {
-        ngx_log_debug_http!(request, "httporigdst: getsockopt failed");
-        return Err(Status::NGX_DECLINED);
-    }
-    let mut ip: Vec<u8> = vec![0; IPV4_STRLEN];
-    let e = unsafe {
-        ngx_sock_ntop(
-            (&raw mut addr).cast(),
-            mem::size_of::<sockaddr>() as u32,
-            ip.as_mut_ptr(),
-            IPV4_STRLEN,
-            0,
-        )
-    };
-    if e == 0 {
-        ngx_log_debug_http!(request, "httporigdst: ngx_sock_ntop failed to convert sockaddr");
-        return Err(Status::NGX_ERROR);
-    }
-    ip.truncate(e);
-
-    let port = unsafe { ngx_inet_get_port((&raw mut addr).cast()) };
-
-    Ok((String::from_utf8(ip).unwrap(), port))
 }
 
 http_variable_get!(
@@ -196,7 +127,7 @@ http_variable_get!(
         //   set context
         // bind address
         ngx_log_debug_http!(request, "httporigdst: context not found, getting address");
-        let r = unsafe { ngx_get_origdst(request) };
+        let r = ngx_get_origdst(request);
         match r {
             Err(e) => {
                 return e;
@@ -235,7 +166,7 @@ http_variable_get!(
         //   set context
         // bind port
         ngx_log_debug_http!(request, "httporigdst: context not found, getting address");
-        let r = unsafe { ngx_get_origdst(request) };
+        let r = ngx_get_origdst(request);
         match r {
             Err(e) => {
                 return e;
@@ -268,16 +199,10 @@ impl HttpModule for Module {
 
     // static ngx_int_t ngx_http_orig_dst_add_variables(ngx_conf_t *cf)
     unsafe extern "C" fn preconfiguration(cf: *mut ngx_conf_t) -> ngx_int_t {
-        for mut v in unsafe { NGX_HTTP_ORIG_DST_VARS } {
-            let var = NonNull::new(unsafe { ngx_http_add_variable(cf, &raw mut v.name, v.flags) });
-            if var.is_none() {
-                return Status::NGX_ERROR.into();
-            }
-            let mut var = var.unwrap();
-            let var = unsafe { var.as_mut() };
-            var.get_handler = v.get_handler;
-            var.data = v.data;
-        }
-        Status::NGX_OK.into()
+        // SAFETY: this function is called with non-NULL cf always
+        let cf = unsafe { &mut *cf };
+        http::register_variables(cf, unsafe { &*&raw const NGX_HTTP_ORIG_DST_VARS })
+            .map_or(Status::NGX_ERROR, |_| Status::NGX_OK)
+            .into()
     }
 }
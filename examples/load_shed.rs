@@ -0,0 +1,139 @@
+use core::cell::RefCell;
+use core::ptr;
+
+use ngx::core::{LoadSampler, worker_cpu_affinity, worker_index};
+use ngx::ffi::ngx_conf_set_num_slot;
+use ngx::http;
+use ngx::prelude::*;
+
+struct Module;
+
+impl HttpModule for Module {
+    fn module() -> &'static ngx_module_t {
+        unsafe { &*::core::ptr::addr_of!(ngx_http_load_shed_module) }
+    }
+
+    unsafe extern "C" fn postconfiguration(cf: *mut ngx_conf_t) -> ngx_int_t {
+        // SAFETY: this function is called with non-NULL cf always
+        let cf = unsafe { &mut *cf };
+        http::add_phase_handler::<LoadShedHandler>(cf)
+            .map_or(Status::NGX_ERROR, |_| Status::NGX_OK)
+            .into()
+    }
+}
+
+#[derive(Debug)]
+struct LocationConf {
+    /// Percentage of the worker connections in use above which the requests are rejected.
+    threshold: ngx_int_t,
+}
+
+impl Default for LocationConf {
+    fn default() -> Self {
+        // NGX_CONF_UNSET
+        Self { threshold: -1 }
+    }
+}
+
+impl Merge for LocationConf {
+    fn merge(&mut self, prev: &LocationConf) -> Result<(), MergeConfigError> {
+        if self.threshold.is_unset() {
+            self.threshold = prev.threshold;
+        }
+        Ok(())
+    }
+}
+
+unsafe impl HttpModuleLocationConf for Module {
+    type LocationConf = LocationConf;
+}
+
+static mut NGX_HTTP_LOAD_SHED_COMMANDS: [ngx_command_t; 2] = [
+    ngx_command_t {
+        name: ngx_string!("load_shed_threshold"),
+        type_: (NGX_HTTP_MAIN_CONF | NGX_HTTP_SRV_CONF | NGX_HTTP_LOC_CONF | NGX_CONF_TAKE1)
+            as ngx_uint_t,
+        set: Some(ngx_conf_set_num_slot),
+        conf: NGX_HTTP_LOC_CONF_OFFSET,
+        offset: core::mem::offset_of!(LocationConf, threshold),
+        post: ptr::null_mut(),
+    },
+    ngx_command_t::empty(),
+];
+
+ngx_http_module! {
+    pub static mut ngx_http_load_shed_module = Module {
+        commands: NGX_HTTP_LOAD_SHED_COMMANDS,
+        conf: [loc],
+        lifecycle: Module,
+    }
+}
+
+thread_local! {
+    static SAMPLER: RefCell<LoadSampler> = const { RefCell::new(LoadSampler::new()) };
+}
+
+impl ProcessLifecycle for Module {
+    type Error = &'static str;
+
+    fn init_process(cycle: &mut ngx_cycle_t) -> Result<(), Self::Error> {
+        let Some(worker) = worker_index() else {
+            return Ok(());
+        };
+
+        match worker_cpu_affinity(worker) {
+            Some(cpus) => {
+                ngx_log_error!(
+                    NGX_LOG_NOTICE,
+                    cycle.log,
+                    "load_shed: worker {worker} on cpus {cpus:?}"
+                );
+            }
+            None => {
+                ngx_log_error!(
+                    NGX_LOG_NOTICE,
+                    cycle.log,
+                    "load_shed: worker {worker} is not bound"
+                );
+            }
+        }
+
+        Ok(())
+    }
+}
+
+struct LoadShedHandler;
+
+impl HttpRequestHandler for LoadShedHandler {
+    const PHASE: HttpPhase = HttpPhase::Preaccess;
+    type Output = Status;
+
+    fn handler(request: &mut Request) -> Self::Output {
+        SAMPLER.with_borrow_mut(|sampler| sampler.count_request());
+
+        let Some(lc) = Module::location_conf(request) else {
+            return Status::NGX_DECLINED;
+        };
+        if lc.threshold < 0 {
+            return Status::NGX_DECLINED;
+        }
+
+        let load = SAMPLER.with_borrow_mut(|sampler| sampler.sample());
+        let usage = (load.connection_usage() * 100.0) as ngx_int_t;
+
+        ngx_log_debug_http!(
+            request,
+            "load_shed: connections {}/{}, {:?} r/s in worker, {:?} r/s total",
+            load.active_connections,
+            load.max_connections,
+            load.worker_requests_per_sec,
+            load.total_requests_per_sec
+        );
+
+        if usage >= lc.threshold {
+            return HTTPStatus::SERVICE_UNAVAILABLE.into();
+        }
+
+        Status::NGX_DECLINED
+    }
+}
@@ -0,0 +1,143 @@
+/*
+ * Tasks stored in the request pool.
+ *
+ * The handle of a task spawned for a request is allocated from the request pool, so the task is
+ * cancelled when the request is finalized, unless it was detached with `Task::detach_on_drop`.
+ * The `pool_task` directive selects the behavior of the location:
+ *
+ *     location /spawn {
+ *         pool_task spawn;     # the task is cancelled with the request
+ *     }
+ *
+ *     location /detach {
+ *         pool_task detach;    # the task keeps running after the request
+ *     }
+ *
+ *     location /status {
+ *         pool_task status;    # reports the number of the dropped and completed tasks
+ *     }
+ */
+use core::cell::Cell;
+use core::time::Duration;
+
+use ngx::async_::{sleep, spawn_for};
+use ngx::http;
+use ngx::prelude::*;
+
+struct Module;
+
+impl HttpModule for Module {
+    fn module() -> &'static ngx_module_t {
+        unsafe { &*::core::ptr::addr_of!(ngx_http_pool_task_module) }
+    }
+
+    unsafe extern "C" fn postconfiguration(cf: *mut ngx_conf_t) -> ngx_int_t {
+        // SAFETY: this function is called with non-NULL cf always
+        let cf = unsafe { &mut *cf };
+        http::add_phase_handler::<PoolTaskHandler>(cf)
+            .map_or(Status::NGX_ERROR, |_| Status::NGX_OK)
+            .into()
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum Mode {
+    #[default]
+    Off,
+    Spawn,
+    Detach,
+    Status,
+}
+
+#[derive(Debug, Default)]
+struct LocationConf {
+    mode: Mode,
+}
+
+impl Merge for LocationConf {
+    fn merge(&mut self, prev: &LocationConf) -> Result<(), MergeConfigError> {
+        if self.mode == Mode::Off {
+            self.mode = prev.mode;
+        }
+        Ok(())
+    }
+}
+
+unsafe impl HttpModuleLocationConf for Module {
+    type LocationConf = LocationConf;
+}
+
+ngx_commands! {
+    static mut NGX_HTTP_POOL_TASK_COMMANDS = [
+        "pool_task" (NGX_HTTP_LOC_CONF, NGX_HTTP_LOC_CONF_OFFSET) =>
+            fn(_cf, conf: &mut LocationConf, mode: &str) -> Result<(), &'static str> {
+                conf.mode = match mode {
+                    "spawn" => Mode::Spawn,
+                    "detach" => Mode::Detach,
+                    "status" => Mode::Status,
+                    _ => return Err("it must be \"spawn\", \"detach\" or \"status\""),
+                };
+                Ok(())
+            },
+    ];
+}
+
+ngx_http_module! {
+    pub static mut ngx_http_pool_task_module = Module {
+        commands: NGX_HTTP_POOL_TASK_COMMANDS,
+        conf: [loc],
+    }
+}
+
+thread_local! {
+    static DROPPED: Cell<usize> = const { Cell::new(0) };
+    static COMPLETED: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Counts the future as dropped, whether it was completed or cancelled.
+struct DropCounter;
+
+impl Drop for DropCounter {
+    fn drop(&mut self) {
+        DROPPED.set(DROPPED.get() + 1);
+    }
+}
+
+struct PoolTaskHandler;
+
+impl HttpRequestHandler for PoolTaskHandler {
+    const PHASE: HttpPhase = HttpPhase::Content;
+    type Output = Status;
+
+    fn handler(request: &mut Request) -> Self::Output {
+        let Some(lc) = Module::location_conf(request) else {
+            return Status::NGX_DECLINED;
+        };
+
+        let delay = match lc.mode {
+            Mode::Off => return Status::NGX_DECLINED,
+            Mode::Status => {
+                let body = format!("dropped: {}, completed: {}\n", DROPPED.get(), COMPLETED.get());
+                return request.send_response(HTTPStatus::OK, b"text/plain", body.as_bytes());
+            }
+            // Far beyond the lifetime of the request.
+            Mode::Spawn => Duration::from_secs(3600),
+            Mode::Detach => Duration::from_millis(100),
+        };
+
+        let mut task = spawn_for(request, async move {
+            let _counter = DropCounter;
+            sleep(delay).await;
+            COMPLETED.set(COMPLETED.get() + 1);
+        });
+        task.detach_on_drop(lc.mode == Mode::Detach);
+
+        // The handle is dropped with the request pool.
+        if request.pool().allocate(task).is_null() {
+            return Status::NGX_ERROR;
+        }
+
+        ngx_log_debug_http!(request, "pool_task: spawned {:?} task", lc.mode);
+        request.send_response(HTTPStatus::OK, b"text/plain", b"spawned\n")
+    }
+}
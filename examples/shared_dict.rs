@@ -1,19 +1,26 @@
 #![no_std]
+#[cfg(feature = "std")]
+extern crate std;
+
 use core::ffi::{c_char, c_void};
-use core::mem;
-use core::ptr::{self, NonNull};
+use core::ptr;
+use core::time::Duration;
 
 use nginx_sys::{
-    NGX_CONF_TAKE2, NGX_HTTP_DELETE, NGX_HTTP_MAIN_CONF, NGX_HTTP_MAIN_CONF_OFFSET,
-    NGX_HTTP_MODULE, NGX_HTTP_VAR_CHANGEABLE, NGX_HTTP_VAR_NOCACHEABLE, NGX_LOG_EMERG,
-    ngx_command_t, ngx_conf_t, ngx_http_add_variable, ngx_http_compile_complex_value_t,
-    ngx_http_complex_value, ngx_http_complex_value_t, ngx_http_module_t, ngx_http_request_t,
-    ngx_http_variable_t, ngx_http_variable_value_t, ngx_int_t, ngx_module_t, ngx_parse_size,
-    ngx_shared_memory_add, ngx_shm_zone_t, ngx_str_t, ngx_uint_t,
+    NGX_CONF_TAKE23, NGX_HTTP_MAIN_CONF, NGX_HTTP_MAIN_CONF_OFFSET, NGX_HTTP_MODULE,
+    NGX_HTTP_VAR_CHANGEABLE, NGX_HTTP_VAR_NOCACHEABLE, NGX_LOG_EMERG, ngx_command_t, ngx_conf_t,
+    ngx_http_module_t, ngx_http_request_t, ngx_http_variable_t, ngx_http_variable_value_t,
+    ngx_int_t, ngx_module_t, ngx_parse_size, ngx_str_t, ngx_uint_t,
 };
-use ngx::collections::RbTreeMap;
-use ngx::core::{NGX_CONF_ERROR, NGX_CONF_OK, NgxStr, NgxString, Pool, SlabPool, Status};
-use ngx::http::{HttpModule, HttpModuleMainConf};
+#[cfg(feature = "std")]
+use nginx_sys::{NGX_LOG_NOTICE, NGX_LOG_WARN, NGX_OK, ngx_conf_full_name, ngx_cycle, ngx_cycle_t};
+use ngx::core::{IntBuffer, NGX_CONF_ERROR, NGX_CONF_OK, NgxSec, NgxString, Pool, Status};
+#[cfg(feature = "std")]
+use ngx::core::{ZoneSnapshotReader, ZoneSnapshotWriter};
+use ngx::http::{self, HttpModule, HttpModuleMainConf};
+use ngx::kv::{self, KvStore, SharedKv, SharedKvZone};
+#[cfg(feature = "std")]
+use ngx::ngx_log_error;
 use ngx::{ngx_conf_log_error, ngx_log_debug, ngx_string};
 
 struct HttpSharedDictModule;
@@ -24,17 +31,11 @@ impl HttpModule for HttpSharedDictModule {
     }
 
     unsafe extern "C" fn preconfiguration(cf: *mut ngx_conf_t) -> ngx_int_t {
-        for mut v in unsafe { NGX_HTTP_SHARED_DICT_VARS } {
-            let var = NonNull::new(unsafe { ngx_http_add_variable(cf, &raw mut v.name, v.flags) });
-            if var.is_none() {
-                return Status::NGX_ERROR.into();
-            }
-            let var = unsafe { var.unwrap().as_mut() };
-            var.get_handler = v.get_handler;
-            var.set_handler = v.set_handler;
-            var.data = v.data;
-        }
-        Status::NGX_OK.into()
+        // SAFETY: this function is called with non-NULL cf always
+        let cf = unsafe { &mut *cf };
+        http::register_variables(cf, unsafe { &*&raw const NGX_HTTP_SHARED_DICT_VARS })
+            .map_or(Status::NGX_ERROR, |_| Status::NGX_OK)
+            .into()
     }
 }
 
@@ -45,7 +46,7 @@ unsafe impl HttpModuleMainConf for HttpSharedDictModule {
 static mut NGX_HTTP_SHARED_DICT_COMMANDS: [ngx_command_t; 3] = [
     ngx_command_t {
         name: ngx_string!("shared_dict_zone"),
-        type_: (NGX_HTTP_MAIN_CONF | NGX_CONF_TAKE2) as ngx_uint_t,
+        type_: (NGX_HTTP_MAIN_CONF | NGX_CONF_TAKE23) as ngx_uint_t,
         set: Some(ngx_http_shared_dict_add_zone),
         conf: NGX_HTTP_MAIN_CONF_OFFSET,
         offset: 0,
@@ -53,7 +54,7 @@ static mut NGX_HTTP_SHARED_DICT_COMMANDS: [ngx_command_t; 3] = [
     },
     ngx_command_t {
         name: ngx_string!("shared_dict"),
-        type_: (NGX_HTTP_MAIN_CONF | NGX_CONF_TAKE2) as ngx_uint_t,
+        type_: (NGX_HTTP_MAIN_CONF | NGX_CONF_TAKE23) as ngx_uint_t,
         set: Some(ngx_http_shared_dict_add_variable),
         conf: NGX_HTTP_MAIN_CONF_OFFSET,
         offset: 0,
@@ -94,19 +95,38 @@ pub static mut ngx_http_shared_dict_module: ngx_module_t = ngx_module_t {
     ctx: &raw const NGX_HTTP_SHARED_DICT_MODULE_CTX as _,
     commands: unsafe { &raw mut NGX_HTTP_SHARED_DICT_COMMANDS[0] },
     type_: NGX_HTTP_MODULE as _,
+    #[cfg(feature = "std")]
+    exit_master: Some(ngx_http_shared_dict_exit_master),
     ..ngx_module_t::default()
 };
 
-type SharedData = ngx::sync::RwLock<RbTreeMap<NgxString<SlabPool>, NgxString<SlabPool>, SlabPool>>;
+/// Version of the snapshot contents: string keys and values.
+#[cfg(feature = "std")]
+const SNAPSHOT_VERSION: u32 = 1;
 
 #[derive(Debug)]
 struct SharedDictMainConfig {
-    shm_zone: *mut ngx_shm_zone_t,
+    zone: Option<SharedKvZone>,
+    persist: ngx_str_t,
 }
 
 impl Default for SharedDictMainConfig {
     fn default() -> Self {
-        Self { shm_zone: ptr::null_mut() }
+        Self { zone: None, persist: ngx_str_t::empty() }
+    }
+}
+
+impl SharedDictMainConfig {
+    #[cfg(feature = "std")]
+    fn persist_path(&self) -> Option<&str> {
+        if self.persist.is_empty() {
+            return None;
+        }
+        core::str::from_utf8(self.persist.as_bytes()).ok()
+    }
+
+    fn shared(&self) -> Result<&SharedKv, Status> {
+        self.zone.as_ref().and_then(|zone| zone.store().ok()).ok_or(Status::NGX_ERROR)
     }
 }
 
@@ -121,223 +141,168 @@ extern "C" fn ngx_http_shared_dict_add_zone(
         unsafe { conf.cast::<SharedDictMainConfig>().as_mut().expect("shared dict main config") };
 
     // SAFETY:
-    // - `cf.args` is guaranteed to be a pointer to an array with 3 elements (NGX_CONF_TAKE2).
+    // - `cf.args` is guaranteed to be a pointer to an array with 3 or 4 elements
+    //   (NGX_CONF_TAKE23).
     // - The pointers are well-aligned by construction method (`ngx_palloc`).
     debug_assert!(!cf.args.is_null() && unsafe { (*cf.args).nelts >= 3 });
     let args = unsafe { (*cf.args).as_slice_mut() };
 
-    let mut name: ngx_str_t = args[1];
+    let name: ngx_str_t = args[1];
     let size = unsafe { ngx_parse_size(&raw mut args[2]) };
     if size == -1 {
         return NGX_CONF_ERROR;
     }
 
-    smcf.shm_zone = unsafe {
-        ngx_shared_memory_add(
-            cf,
-            &raw mut name,
-            size as usize,
-            (&raw mut ngx_http_shared_dict_module).cast(),
-        )
-    };
-
-    let Some(shm_zone) = (unsafe { smcf.shm_zone.as_mut() }) else {
+    #[cfg(not(feature = "std"))]
+    if let Some(arg) = args.get(3) {
+        // The snapshot file I/O requires std.
+        ngx_conf_log_error!(NGX_LOG_EMERG, cf, "invalid parameter \"{arg}\"");
         return NGX_CONF_ERROR;
+    }
+
+    #[cfg(feature = "std")]
+    if let Some(arg) = args.get(3) {
+        let Some(path) = arg.as_bytes().strip_prefix(b"persist=") else {
+            ngx_conf_log_error!(NGX_LOG_EMERG, cf, "invalid parameter \"{arg}\"");
+            return NGX_CONF_ERROR;
+        };
+
+        if path.is_empty() || core::str::from_utf8(path).is_err() {
+            ngx_conf_log_error!(NGX_LOG_EMERG, cf, "invalid persist path \"{arg}\"");
+            return NGX_CONF_ERROR;
+        }
+
+        // Resolve a relative path against the prefix, as nginx does for the other data paths.
+        let mut path = ngx_str_t { len: path.len(), data: path.as_ptr().cast_mut() };
+        if unsafe { ngx_conf_full_name(cf.cycle, &raw mut path, 0) } != NGX_OK as ngx_int_t {
+            return NGX_CONF_ERROR;
+        }
+
+        smcf.persist = path;
+    }
+
+    let persist = smcf.persist;
+    let init = move |store: &mut KvStore| {
+        // The path is validated when parsing the directive.
+        if let (false, Ok(path)) = (persist.is_empty(), persist.to_str()) {
+            ngx_http_shared_dict_load(store, path);
+        }
     };
 
-    shm_zone.init = Some(ngx_http_shared_dict_zone_init);
-    shm_zone.data = ptr::from_mut(smcf).cast();
+    let module = HttpSharedDictModule::module();
+    match SharedKvZone::add_with(cf, &name, size as usize, module, init) {
+        Ok(zone) => smcf.zone = Some(zone),
+        Err(_) => return NGX_CONF_ERROR,
+    }
 
     NGX_CONF_OK
 }
 
-fn ngx_http_shared_dict_get_shared(shm_zone: &mut ngx_shm_zone_t) -> Result<&SharedData, Status> {
-    let mut alloc = unsafe { SlabPool::from_shm_zone(shm_zone) }.ok_or(Status::NGX_ERROR)?;
+#[cfg(not(feature = "std"))]
+fn ngx_http_shared_dict_load(_store: &mut KvStore, _path: &str) {}
 
-    if alloc.as_mut().data.is_null() {
-        let shared: RbTreeMap<NgxString<SlabPool>, NgxString<SlabPool>, SlabPool> =
-            RbTreeMap::try_new_in(alloc.clone()).map_err(|_| Status::NGX_ERROR)?;
+#[cfg(feature = "std")]
+fn ngx_http_shared_dict_load(store: &mut KvStore, path: &str) {
+    let log = unsafe { (*ngx_cycle).log };
 
-        let shared = ngx::sync::RwLock::new(shared);
+    let mut reader = match ZoneSnapshotReader::open(path, SNAPSHOT_VERSION) {
+        Ok(Some(reader)) => reader,
+        Ok(None) => return,
+        Err(err) => {
+            ngx_log_error!(NGX_LOG_WARN, log, "shared dict: cannot read \"{path}\": {err}");
+            return;
+        }
+    };
+
+    let mut entries = 0;
+
+    for (key, value) in reader.by_ref() {
+        // Do not evict the loaded entries to make room for the rest of an oversized snapshot.
+        if store.try_set(key, value, None).is_err() {
+            ngx_log_error!(NGX_LOG_WARN, log, "shared dict: zone is full, \"{path}\" is truncated");
+            break;
+        }
+        entries += 1;
+    }
 
-        alloc.as_mut().data = ngx::allocator::allocate(shared, &alloc)
-            .map_err(|_| Status::NGX_ERROR)?
-            .as_ptr()
-            .cast();
+    if reader.is_corrupted() {
+        ngx_log_error!(NGX_LOG_WARN, log, "shared dict: \"{path}\" is damaged");
     }
 
-    unsafe { alloc.as_ref().data.cast::<SharedData>().as_ref().ok_or(Status::NGX_ERROR) }
+    ngx_log_error!(NGX_LOG_NOTICE, log, "shared dict: loaded {entries} entries from \"{path}\"");
 }
 
-extern "C" fn ngx_http_shared_dict_zone_init(
-    shm_zone: *mut ngx_shm_zone_t,
-    _data: *mut c_void,
-) -> ngx_int_t {
-    let shm_zone = unsafe { &mut *shm_zone };
+#[cfg(feature = "std")]
+extern "C" fn ngx_http_shared_dict_exit_master(cycle: *mut ngx_cycle_t) {
+    let cycle = unsafe { &*cycle };
+
+    let Some(smcf) = HttpSharedDictModule::main_conf(cycle) else {
+        return;
+    };
+
+    let (Some(path), Ok(shared)) = (smcf.persist_path(), smcf.shared()) else {
+        return;
+    };
 
-    match ngx_http_shared_dict_get_shared(shm_zone) {
-        Err(e) => e.into(),
-        Ok(_) => Status::NGX_OK.into(),
+    let save = || -> std::io::Result<()> {
+        let mut writer = ZoneSnapshotWriter::create(path, SNAPSHOT_VERSION)?;
+        for (key, value) in shared.read().iter() {
+            writer.write_entry(key.as_bytes(), value.as_bytes())?;
+        }
+        writer.commit()
+    };
+
+    if let Err(err) = save() {
+        ngx_log_error!(NGX_LOG_WARN, cycle.log, "shared dict: cannot save \"{path}\": {err}");
     }
 }
 
 extern "C" fn ngx_http_shared_dict_add_variable(
     cf: *mut ngx_conf_t,
     _cmd: *mut ngx_command_t,
-    _conf: *mut c_void,
+    conf: *mut c_void,
 ) -> *mut c_char {
     // SAFETY: configuration handlers always receive a valid `cf` pointer.
     let cf = unsafe { cf.as_mut().unwrap() };
-    let pool = unsafe { Pool::from_ngx_pool(cf.pool) };
-
-    let key = pool.calloc_type::<ngx_http_complex_value_t>();
-    if key.is_null() {
-        return NGX_CONF_ERROR;
-    }
+    let smcf =
+        unsafe { conf.cast::<SharedDictMainConfig>().as_mut().expect("shared dict main config") };
 
     // SAFETY:
-    // - `cf.args` is guaranteed to be a pointer to an array with 3 elements (NGX_CONF_TAKE2).
+    // - `cf.args` is guaranteed to be a pointer to an array with 3 or 4 elements
+    //   (NGX_CONF_TAKE23).
     // - The pointers are well-aligned by construction method (`ngx_palloc`).
     debug_assert!(!cf.args.is_null() && unsafe { (*cf.args).nelts >= 3 });
     let args = unsafe { (*cf.args).as_slice_mut() };
 
-    let mut ccv: ngx_http_compile_complex_value_t = unsafe { mem::zeroed() };
-    ccv.cf = cf;
-    ccv.value = &raw mut args[1];
-    ccv.complex_value = key;
-
-    if unsafe { nginx_sys::ngx_http_compile_complex_value(&raw mut ccv) } != Status::NGX_OK.into() {
-        return NGX_CONF_ERROR;
-    }
-
-    let mut name = args[2];
-
-    if name.as_bytes()[0] != b'$' {
-        ngx_conf_log_error!(NGX_LOG_EMERG, cf, "invalid variable name \"{name}\"");
+    let Some(zone) = smcf.zone else {
+        ngx_conf_log_error!(NGX_LOG_EMERG, cf, "\"shared_dict_zone\" is not defined");
         return NGX_CONF_ERROR;
-    }
-
-    name.data = unsafe { name.data.add(1) };
-    name.len -= 1;
-
-    let var = unsafe {
-        ngx_http_add_variable(
-            cf,
-            &raw mut name,
-            (NGX_HTTP_VAR_CHANGEABLE | NGX_HTTP_VAR_NOCACHEABLE) as ngx_uint_t,
-        )
     };
-    if var.is_null() {
-        return NGX_CONF_ERROR;
-    }
 
-    unsafe {
-        (*var).get_handler = Some(ngx_http_shared_dict_get_variable);
-        (*var).set_handler = Some(ngx_http_shared_dict_set_variable);
-        (*var).data = key as usize;
-    }
+    let mut ttl = None;
 
-    NGX_CONF_OK
-}
-
-extern "C" fn ngx_http_shared_dict_get_variable(
-    r: *mut ngx_http_request_t,
-    v: *mut ngx_http_variable_value_t,
-    data: usize,
-) -> ngx_int_t {
-    let r = unsafe { &mut *r };
-    let v = unsafe { &mut *v };
-    let smcf = HttpSharedDictModule::main_conf_mut(r).expect("shared dict main config");
-
-    let mut key = ngx_str_t::empty();
-    if unsafe { ngx_http_complex_value(r, data as _, &raw mut key) } != Status::NGX_OK.into() {
-        return Status::NGX_ERROR.into();
-    }
-
-    let key = unsafe { NgxStr::from_ngx_str(key) };
-
-    let Ok(shared) = ngx_http_shared_dict_get_shared(unsafe { &mut *smcf.shm_zone }) else {
-        return Status::NGX_ERROR.into();
-    };
-
-    let value =
-        shared.read().get(key).and_then(|x| unsafe { ngx_str_t::from_bytes(r.pool, x.as_bytes()) });
-
-    ngx_log_debug!(
-        unsafe { (*r.connection).log },
-        "shared dict: get \"{}\" -> {:?} w:{} p:{}",
-        key,
-        value.as_ref().map(|x| unsafe { NgxStr::from_ngx_str(*x) }),
-        unsafe { nginx_sys::ngx_worker },
-        unsafe { nginx_sys::ngx_pid },
-    );
-
-    let Some(value) = value else {
-        v.set_not_found(1);
-        return Status::NGX_ERROR.into();
-    };
-
-    v.data = value.data;
-    v.set_len(value.len as _);
-
-    v.set_valid(1);
-    v.set_no_cacheable(0);
-    v.set_not_found(0);
-
-    Status::NGX_OK.into()
-}
-
-extern "C" fn ngx_http_shared_dict_set_variable(
-    r: *mut ngx_http_request_t,
-    v: *mut ngx_http_variable_value_t,
-    data: usize,
-) {
-    let r = unsafe { &mut *r };
-    let v = unsafe { &mut *v };
-    let smcf = HttpSharedDictModule::main_conf_mut(r).expect("shared dict main config");
-    let mut key = ngx_str_t::empty();
+    if let Some(arg) = args.get(3) {
+        let Some(value) = arg.as_bytes().strip_prefix(b"ttl=") else {
+            ngx_conf_log_error!(NGX_LOG_EMERG, cf, "invalid parameter \"{arg}\"");
+            return NGX_CONF_ERROR;
+        };
 
-    if unsafe { ngx_http_complex_value(r, data as _, &raw mut key) } != Status::NGX_OK.into() {
-        return;
+        match NgxSec::parse(value).map(Duration::try_from) {
+            Ok(Ok(value)) if !value.is_zero() => ttl = Some(value),
+            _ => {
+                ngx_conf_log_error!(NGX_LOG_EMERG, cf, "invalid ttl \"{arg}\"");
+                return NGX_CONF_ERROR;
+            }
+        }
     }
 
-    let Ok(shared) = ngx_http_shared_dict_get_shared(unsafe { &mut *smcf.shm_zone }) else {
-        return;
+    let Ok(key) = http::ConfComplexValue::compile(cf, &args[1]) else {
+        return NGX_CONF_ERROR;
     };
 
-    if r.method == NGX_HTTP_DELETE as _ {
-        let key = unsafe { NgxStr::from_ngx_str(key) };
-
-        ngx_log_debug!(
-            unsafe { (*r.connection).log },
-            "shared dict: delete \"{}\" w:{} p:{}",
-            key,
-            unsafe { nginx_sys::ngx_worker },
-            unsafe { nginx_sys::ngx_pid },
-        );
-
-        let _ = shared.write().remove(key);
-    } else {
-        let alloc = unsafe { SlabPool::from_shm_zone(&*smcf.shm_zone).expect("slab pool") };
-
-        let Ok(key) = NgxString::try_from_bytes_in(key.as_bytes(), alloc.clone()) else {
-            return;
-        };
-
-        let Ok(value) = NgxString::try_from_bytes_in(v.as_bytes(), alloc.clone()) else {
-            return;
-        };
-
-        ngx_log_debug!(
-            unsafe { (*r.connection).log },
-            "shared dict: set \"{}\" -> \"{}\" w:{} p:{}",
-            key,
-            value,
-            unsafe { nginx_sys::ngx_worker },
-            unsafe { nginx_sys::ngx_pid },
-        );
-
-        let _ = shared.write().try_insert(key, value);
+    match kv::add_variable(cf, zone, key, &args[2], ttl) {
+        Ok(()) => NGX_CONF_OK,
+        Err(_) => NGX_CONF_ERROR,
     }
 }
 
@@ -346,8 +311,6 @@ extern "C" fn ngx_http_shared_dict_get_entries(
     v: *mut ngx_http_variable_value_t,
     _data: usize,
 ) -> ngx_int_t {
-    use core::fmt::Write;
-
     let r = unsafe { &mut *r };
     let v = unsafe { &mut *v };
     let pool = unsafe { Pool::from_ngx_pool(r.pool) };
@@ -355,7 +318,7 @@ extern "C" fn ngx_http_shared_dict_get_entries(
 
     ngx_log_debug!(unsafe { (*r.connection).log }, "shared dict: get all entries");
 
-    let Ok(shared) = ngx_http_shared_dict_get_shared(unsafe { &mut *smcf.shm_zone }) else {
+    let Ok(shared) = smcf.shared() else {
         return Status::NGX_ERROR.into();
     };
 
@@ -367,7 +330,7 @@ extern "C" fn ngx_http_shared_dict_get_entries(
         let mut values: usize = 0;
 
         for (key, value) in dict.iter() {
-            len += key.len() + value.len() + b" = ; ".len();
+            len += key.as_bytes().len() + value.as_bytes().len() + b" = ; ".len();
             values += 1;
         }
 
@@ -377,14 +340,15 @@ extern "C" fn ngx_http_shared_dict_get_entries(
             return Status::NGX_ERROR.into();
         }
 
-        if write!(str, "{values}; ").is_err() {
-            return Status::NGX_ERROR.into();
-        }
+        let mut buf = IntBuffer::new();
+        let _ = str.append_within_capacity(buf.format(values));
+        let _ = str.append_within_capacity(b"; ");
 
         for (key, value) in dict.iter() {
-            if write!(str, "{key} = {value}; ").is_err() {
-                return Status::NGX_ERROR.into();
-            }
+            let _ = str.append_within_capacity(key);
+            let _ = str.append_within_capacity(b" = ");
+            let _ = str.append_within_capacity(value);
+            let _ = str.append_within_capacity(b"; ");
         }
     }
 
@@ -411,15 +375,9 @@ extern "C" fn ngx_http_shared_dict_set_entries(
 
     ngx_log_debug!(unsafe { (*r.connection).log }, "shared dict: clear");
 
-    let Ok(shared) = ngx_http_shared_dict_get_shared(unsafe { &mut *smcf.shm_zone }) else {
-        return;
-    };
-
-    let Ok(tree) = RbTreeMap::try_new_in(shared.read().allocator().clone()) else {
+    let Ok(shared) = smcf.shared() else {
         return;
     };
 
-    // This would check both .clear() and the drop implementation
-    *shared.write() = tree;
-    // shared.write().clear()
+    shared.write().clear()
 }
@@ -0,0 +1,116 @@
+/*
+ * A content handler running a synthetic request that creates a subrequest.
+ *
+ * The reference usage of `SyntheticRequestBuilder`: the `synthetic_subrequest` directive sets the
+ * URI of the subrequest created by the content handler of the synthetic request, and the response
+ * reports the status of the subrequest, e.g.
+ *
+ *     location /run {
+ *         synthetic_subrequest /sub;
+ *     }
+ *
+ *     location /sub {
+ *         return 200 "sub\n";
+ *     }
+ *
+ * The synthetic request handler returns `NGX_DONE` and leaves the subrequest running: once the
+ * subrequest is finalized, nginx posts the synthetic request and calls its write event handler.
+ */
+use core::cell::Cell;
+
+use ngx::http::{self, SubrequestFlags, SyntheticRequestBuilder};
+use ngx::prelude::*;
+
+struct Module;
+
+impl HttpModule for Module {
+    fn module() -> &'static ngx_module_t {
+        unsafe { &*::core::ptr::addr_of!(ngx_http_synthetic_module) }
+    }
+
+    unsafe extern "C" fn postconfiguration(cf: *mut ngx_conf_t) -> ngx_int_t {
+        // SAFETY: this function is called with non-NULL cf always
+        let cf = unsafe { &mut *cf };
+        http::add_phase_handler::<SyntheticHandler>(cf)
+            .map_or(Status::NGX_ERROR, |_| Status::NGX_OK)
+            .into()
+    }
+}
+
+#[derive(Debug, Default)]
+struct LocationConf {
+    uri: Option<String>,
+}
+
+impl Merge for LocationConf {
+    fn merge(&mut self, prev: &LocationConf) -> Result<(), MergeConfigError> {
+        if self.uri.is_none() {
+            self.uri = prev.uri.clone();
+        }
+        Ok(())
+    }
+}
+
+unsafe impl HttpModuleLocationConf for Module {
+    type LocationConf = LocationConf;
+}
+
+ngx_commands! {
+    static mut NGX_HTTP_SYNTHETIC_COMMANDS = [
+        "synthetic_subrequest" (NGX_HTTP_LOC_CONF, NGX_HTTP_LOC_CONF_OFFSET) =>
+            fn(_cf, conf: &mut LocationConf, uri: &str) -> Result<(), &'static str> {
+                conf.uri = Some(uri.to_owned());
+                Ok(())
+            },
+    ];
+}
+
+ngx_http_module! {
+    pub static mut ngx_http_synthetic_module = Module {
+        commands: NGX_HTTP_SYNTHETIC_COMMANDS,
+        conf: [loc],
+    }
+}
+
+thread_local! {
+    static SUBREQUEST_STATUS: Cell<usize> = const { Cell::new(0) };
+}
+
+struct SyntheticHandler;
+
+impl HttpRequestHandler for SyntheticHandler {
+    const PHASE: HttpPhase = HttpPhase::Content;
+    type Output = Status;
+
+    fn handler(request: &mut Request) -> Self::Output {
+        let Some(uri) = Module::location_conf(request).and_then(|lc| lc.uri.clone()) else {
+            return Status::NGX_DECLINED;
+        };
+
+        let mut sr = match SyntheticRequestBuilder::new("/synthetic").build() {
+            Ok(sr) => sr,
+            Err(rc) => return rc,
+        };
+
+        SUBREQUEST_STATUS.set(0);
+
+        let rc = sr.run(|r| {
+            let flags = SubrequestFlags::new().waited(true);
+            match r.subrequest_with(&uri, None, flags, |sr, _| {
+                SUBREQUEST_STATUS.set(sr.status().0 as usize);
+            }) {
+                // The subrequest completes after the handler returns.
+                Ok(_) => Status::NGX_DONE,
+                Err(rc) => rc,
+            }
+        });
+
+        ngx_log_debug_http!(request, "synthetic: handler returned {rc:?}");
+
+        // The subrequest is run with the posted requests of the synthetic request.
+        let body = format!("subrequest: {}\n", SUBREQUEST_STATUS.get());
+        drop(sr);
+
+        request.send_response(HTTPStatus::OK, b"text/plain", body.as_bytes())
+    }
+}
@@ -0,0 +1,88 @@
+#!/usr/bin/perl
+
+# (C) Nginx, Inc
+
+# Tests for ngx-rust example modules.
+
+###############################################################################
+
+use warnings;
+use strict;
+
+use Test::More;
+
+BEGIN { use FindBin; chdir($FindBin::Bin); }
+
+use lib 'lib';
+use Test::Nginx;
+
+###############################################################################
+
+select STDERR; $| = 1;
+select STDOUT; $| = 1;
+
+my $t = Test::Nginx->new()->has(qw/http proxy/)->plan(3)
+	->write_file_expand('nginx.conf', <<"EOF");
+
+%%TEST_GLOBALS%%
+
+daemon off;
+
+events {
+}
+
+http {
+    %%TEST_GLOBALS_HTTP%%
+
+    server {
+        listen       127.0.0.1:8080;
+        server_name  localhost;
+
+        location /local {
+            compose /a /b;
+        }
+
+        location /proxied {
+            compose /slow /b;
+        }
+
+        location /nested {
+            compose /local /b;
+        }
+
+        location /a {
+            return 200 "A";
+        }
+
+        location /b {
+            return 200 "B";
+        }
+
+        location /slow {
+            proxy_pass http://127.0.0.1:8081/a;
+        }
+    }
+
+    server {
+        listen       127.0.0.1:8081;
+        server_name  localhost;
+
+        location /a {
+            return 200 "A";
+        }
+    }
+}
+
+EOF
+
+$t->run();
+
+###############################################################################
+
+like(http_get('/local'), qr/\x0d\x0a\x0d\x0abegin\nA\nB\nend\n$/, 'compose');
+like(http_get('/proxied'), qr/\x0d\x0a\x0d\x0abegin\nA\nB\nend\n$/,
+	'compose postponed');
+like(http_get('/nested'), qr/\x0d\x0a\x0d\x0abegin\nbegin\nA\nB\nend\n\nB\nend\n$/,
+	'compose nested');
+
+###############################################################################
@@ -0,0 +1,90 @@
+#!/usr/bin/perl
+
+# (C) Nginx, Inc
+
+# Tests for ngx-rust example modules.
+
+###############################################################################
+
+use warnings;
+use strict;
+
+use Test::More;
+
+BEGIN { use FindBin; chdir($FindBin::Bin); }
+
+use lib 'lib';
+use Test::Nginx;
+
+###############################################################################
+
+select STDERR; $| = 1;
+select STDOUT; $| = 1;
+
+my $t = Test::Nginx->new()->has(qw/http/)->plan(4)
+	->write_file_expand('nginx.conf', <<'EOF');
+
+%%TEST_GLOBALS%%
+
+daemon off;
+
+events {
+}
+
+http {
+    %%TEST_GLOBALS_HTTP%%
+
+    server {
+        listen       127.0.0.1:8080;
+        server_name  localhost;
+
+        location /spawn {
+            pool_task spawn;
+        }
+
+        location /detach {
+            pool_task detach;
+        }
+
+        location /status {
+            pool_task status;
+        }
+    }
+}
+
+EOF
+
+$t->run();
+
+###############################################################################
+
+like(http_get('/spawn'), qr/spawned/, 'spawn');
+
+# the task is cancelled when the request pool is destroyed
+
+like(wait_status(qr/dropped: 1, completed: 0/), qr/dropped: 1, completed: 0/,
+	'cancelled with request');
+
+like(http_get('/detach'), qr/spawned/, 'spawn detached');
+
+# the detached task completes after the request
+
+like(wait_status(qr/dropped: 2, completed: 1/), qr/dropped: 2, completed: 1/,
+	'completed after request');
+
+###############################################################################
+
+sub wait_status {
+	my ($like) = @_;
+	my $r;
+
+	for (1 .. 20) {
+		$r = http_get('/status');
+		last if $r =~ $like;
+		select undef, undef, undef, 0.1;
+	}
+
+	return $r;
+}
+
+###############################################################################
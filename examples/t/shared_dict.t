@@ -21,7 +21,7 @@ use Test::Nginx;
 select STDERR; $| = 1;
 select STDOUT; $| = 1;
 
-my $t = Test::Nginx->new()->has(qw/http rewrite/)->plan(12)
+my $t = Test::Nginx->new()->has(qw/http rewrite/)->plan(17)
 	->write_file_expand('nginx.conf', <<'EOF');
 
 %%TEST_GLOBALS%%
@@ -38,6 +38,7 @@ http {
 
     shared_dict_zone z 64k;
     shared_dict $arg_key $foo;
+    shared_dict $arg_key $short ttl=1s;
 
     server {
         listen       127.0.0.1:8080;
@@ -52,6 +53,12 @@ http {
             return 200;
         }
 
+        location /set_short/ {
+            add_header X-Process $pid;
+            set $short $arg_value;
+            return 200;
+        }
+
         location /entries/ {
             add_header X-Process $pid;
             return 200 $shared_dict_entries;
@@ -95,6 +102,30 @@ like(http_get('/clear/'), qr/200 OK/, 'clear');
 
 like(http_get('/entries/'), qr/^0; $/ms, 'get entries - clear');
 
+# expiration
+
+like(http_get('/set_short/?key=tmp&value=temporary'), qr/200 OK/, 'set expiring');
+like(http_get('/?key=tmp'), qr/X-Value: temporary/i, 'check expiring');
+
+select undef, undef, undef, 2.5;
+
+unlike(http_get('/?key=tmp'), qr/X-Value:/i, 'check expired');
+
+# eviction of the least recently used entries
+
+http_get('/set/?key=old&value=old');
+http_get('/set/?key=hot&value=hot');
+
+my $value = 'x' x 1000;
+
+for my $n (1 .. 100) {
+	http_get("/set/?key=k$n&value=$value");
+	http_get('/?key=hot');
+}
+
+like(http_get('/?key=hot'), qr/X-Value: hot/i, 'recently used kept');
+unlike(http_get('/?key=old'), qr/X-Value:/i, 'least recently used evicted');
+
 ###############################################################################
 
 sub check {
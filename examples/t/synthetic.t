@@ -0,0 +1,67 @@
+#!/usr/bin/perl
+
+# (C) Nginx, Inc
+
+# Tests for ngx-rust example modules.
+
+###############################################################################
+
+use warnings;
+use strict;
+
+use Test::More;
+
+BEGIN { use FindBin; chdir($FindBin::Bin); }
+
+use lib 'lib';
+use Test::Nginx;
+
+###############################################################################
+
+select STDERR; $| = 1;
+select STDOUT; $| = 1;
+
+my $t = Test::Nginx->new()->has(qw/http rewrite/)->plan(3)
+	->write_file_expand('nginx.conf', <<'EOF');
+
+%%TEST_GLOBALS%%
+
+daemon off;
+
+events {
+}
+
+http {
+    %%TEST_GLOBALS_HTTP%%
+
+    server {
+        listen       127.0.0.1:8080;
+        server_name  localhost;
+
+        location /run {
+            synthetic_subrequest /sub;
+        }
+
+        location /missing {
+            synthetic_subrequest /nonexistent;
+        }
+
+        location /sub {
+            return 200 "sub\n";
+        }
+    }
+}
+
+EOF
+
+$t->run();
+
+###############################################################################
+
+# the synthetic request is posted and resumed when the subrequest completes
+
+like(http_get('/run'), qr/subrequest: 200/, 'synthetic subrequest');
+like(http_get('/missing'), qr/subrequest: 404/, 'synthetic subrequest 404');
+like(http_get('/run'), qr/subrequest: 200/, 'synthetic subrequest again');
+
+###############################################################################
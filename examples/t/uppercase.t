@@ -0,0 +1,79 @@
+#!/usr/bin/perl
+
+# (C) Nginx, Inc
+
+# Tests for ngx-rust example modules.
+
+###############################################################################
+
+use warnings;
+use strict;
+
+use Test::More;
+
+BEGIN { use FindBin; chdir($FindBin::Bin); }
+
+use lib 'lib';
+use Test::Nginx;
+
+###############################################################################
+
+select STDERR; $| = 1;
+select STDOUT; $| = 1;
+
+my $t = Test::Nginx->new()->has(qw/http/)->plan(4)
+	->write_file_expand('nginx.conf', <<'EOF');
+
+%%TEST_GLOBALS%%
+
+daemon off;
+
+events {
+}
+
+http {
+    %%TEST_GLOBALS_HTTP%%
+
+    server {
+        listen       127.0.0.1:8080;
+        server_name  localhost;
+
+        sendfile off;
+        output_buffers 2 4k;
+
+        location / {
+            uppercase on;
+            error_log %%TESTDIR%%/uppercase.log info;
+        }
+
+        location /plain/ {
+            alias %%TESTDIR%%/;
+        }
+    }
+}
+
+EOF
+
+$t->write_file('small.html', 'hello');
+$t->write_file('large.html', 'x' x 65536);
+$t->run();
+
+###############################################################################
+
+like(http_get('/small.html'), qr/\x0d\x0a\x0d\x0aHELLO$/, 'uppercase');
+like(http_get('/plain/small.html'), qr/\x0d\x0a\x0d\x0ahello$/, 'disabled');
+
+like(http_get('/large.html'), qr/\x0d\x0a\x0d\x0aX{65536}$/, 'uppercase large');
+
+$t->stop();
+
+# the 64 parts of the large response reuse the sent buffers
+
+my @counts = $t->read_file('uppercase.log')
+	=~ /uppercase: (\d+) buffers allocated, (\d+) reused/g;
+my ($allocated, $reused) = map { $_ // 0 } @counts[-2, -1];
+
+ok($reused > 0 && $allocated + $reused == 64, 'reused buffers')
+	or diag("allocated: $allocated, reused: $reused");
+
+###############################################################################
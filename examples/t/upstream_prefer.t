@@ -0,0 +1,101 @@
+#!/usr/bin/perl
+
+# (C) Nginx, Inc
+
+# Tests for ngx-rust example modules.
+
+###############################################################################
+
+use warnings;
+use strict;
+
+use Test::More;
+
+BEGIN { use FindBin; chdir($FindBin::Bin); }
+
+use lib 'lib';
+use Test::Nginx;
+
+###############################################################################
+
+select STDERR; $| = 1;
+select STDOUT; $| = 1;
+
+my $t = Test::Nginx->new()->has(qw/http proxy/)->plan(5)
+	->write_file_expand('nginx.conf', <<"EOF");
+
+%%TEST_GLOBALS%%
+
+daemon off;
+
+events {
+}
+
+http {
+    %%TEST_GLOBALS_HTTP%%
+
+    upstream u {
+        server 127.0.0.1:8081;
+        server 127.0.0.1:8082;
+        prefer_header X-Backend;
+    }
+
+    upstream down {
+        server 127.0.0.1:8081;
+        server 127.0.0.1:8082 down;
+        prefer_header X-Backend;
+    }
+
+    server {
+        listen       127.0.0.1:8080;
+        server_name  localhost;
+
+        location / {
+            proxy_pass http://u;
+        }
+
+        location /down {
+            proxy_pass http://down;
+        }
+    }
+
+    server {
+        listen       127.0.0.1:8081;
+        listen       127.0.0.1:8082;
+        server_name  localhost;
+
+        location / {
+            return 200 "port $server_port";
+        }
+    }
+}
+
+EOF
+
+$t->run();
+
+###############################################################################
+
+like(get('/', '127.0.0.1:8082'), qr/port 8082/, 'preferred peer');
+like(get('/', '127.0.0.1:8082'), qr/port 8082/, 'preferred peer again');
+like(get('/', '127.0.0.1:8081'), qr/port 8081/, 'other preferred peer');
+
+my $ports = join ' ', map { get('/') =~ /port (\d+)/ } 1 .. 4;
+like($ports, qr/^(8081 8082|8082 8081) \1$/, 'round robin without header');
+
+like(get('/down', '127.0.0.1:8082'), qr/port 8081/, 'preferred peer down');
+
+###############################################################################
+
+sub get {
+	my ($uri, $backend) = @_;
+	my $header = defined $backend ? "X-Backend: $backend" . CRLF : '';
+
+	return http(<<EOF);
+GET $uri HTTP/1.0
+Host: localhost
+${header}
+EOF
+}
+
+###############################################################################
@@ -0,0 +1,213 @@
+/*
+ * A body filter converting the response to upper case.
+ *
+ * The reference usage of `BufferChains`: the converted data is copied to the buffers owned by the
+ * module, which are reused once sent instead of allocating new ones for each part of the body.
+ * The numbers of the allocated and the reused buffers are logged at the `info` level when the
+ * response is complete, e.g.
+ *
+ *     location / {
+ *         uppercase on;
+ *         sendfile off;
+ *     }
+ *
+ * The data of the file buffers is passed as is, hence `sendfile off`.
+ */
+use core::ptr;
+use core::slice;
+
+use ngx::core::{Buffer, BufferChains};
+use ngx::ffi::{ngx_alloc_chain_link, ngx_buf_t, ngx_chain_t};
+use ngx::http::{self, BodyFilter, Chain, NextBodyFilter};
+use ngx::prelude::*;
+
+/// Size of the buffers owned by the filter.
+const BUFFER_SIZE: usize = 1024;
+
+struct Module;
+
+impl HttpModule for Module {
+    fn module() -> &'static ngx_module_t {
+        unsafe { &*::core::ptr::addr_of!(ngx_http_uppercase_filter_module) }
+    }
+
+    unsafe extern "C" fn postconfiguration(_cf: *mut ngx_conf_t) -> ngx_int_t {
+        http::install_body_filter::<UppercaseFilter>();
+        Status::NGX_OK.into()
+    }
+}
+
+#[derive(Debug, Default)]
+struct LocationConf {
+    enable: bool,
+}
+
+impl Merge for LocationConf {
+    fn merge(&mut self, prev: &LocationConf) -> Result<(), MergeConfigError> {
+        if prev.enable {
+            self.enable = true;
+        }
+        Ok(())
+    }
+}
+
+unsafe impl HttpModuleLocationConf for Module {
+    type LocationConf = LocationConf;
+}
+
+ngx_commands! {
+    static mut NGX_HTTP_UPPERCASE_COMMANDS = [
+        "uppercase" (NGX_HTTP_LOC_CONF, NGX_HTTP_LOC_CONF_OFFSET) =>
+            fn(_cf, conf: &mut LocationConf, enable: bool) -> Result<(), &'static str> {
+                conf.enable = enable;
+                Ok(())
+            },
+    ];
+}
+
+ngx_http_module! {
+    pub static mut ngx_http_uppercase_filter_module = Module {
+        commands: NGX_HTTP_UPPERCASE_COMMANDS,
+        conf: [loc],
+    }
+}
+
+struct FilterCtx {
+    chains: BufferChains,
+    allocated: usize,
+    reused: usize,
+}
+
+type Ctx = RequestContext<Module, FilterCtx>;
+
+static NEXT: NextBodyFilter = NextBodyFilter::new();
+
+struct UppercaseFilter;
+
+impl BodyFilter for UppercaseFilter {
+    type Output = Status;
+
+    fn next() -> &'static NextBodyFilter {
+        &NEXT
+    }
+
+    fn filter(r: &mut Request, chain: Chain<'_>) -> Status {
+        if chain.is_empty() || !Module::location_conf(r).is_some_and(|lc| lc.enable) {
+            return NEXT.call(r, chain);
+        }
+
+        if Ctx::get_current(r).is_none() {
+            let chains = BufferChains::new(Module::module());
+            let ctx = FilterCtx { chains, allocated: 0, reused: 0 };
+            if Ctx::set_current(r, ctx).is_err() {
+                return Status::NGX_ERROR;
+            }
+        }
+
+        let pool = r.pool();
+        let last = chain.has_last_buf();
+
+        let Some(ctx) = Ctx::get_current_mut(r) else {
+            return Status::NGX_ERROR;
+        };
+        let Some(mut out) = uppercase(&pool, ctx, chain) else {
+            return Status::NGX_ERROR;
+        };
+
+        // SAFETY: the chain is allocated from the request pool.
+        let rc = NEXT.call(r, unsafe { Chain::from_raw(out) });
+
+        let Some(ctx) = Ctx::get_current_mut(r) else {
+            return Status::NGX_ERROR;
+        };
+        // SAFETY: the chain is allocated from the request pool.
+        unsafe { ctx.chains.update(&pool, &mut out) };
+
+        if last {
+            let (allocated, reused) = (ctx.allocated, ctx.reused);
+            ngx_log_error!(
+                NGX_LOG_INFO,
+                r.log(),
+                "uppercase: {allocated} buffers allocated, {reused} reused"
+            );
+        }
+
+        rc
+    }
+}
+
+/// Copies the data of the chain in upper case to the buffers of the filter, and returns the new
+/// chain.
+///
+/// The buffers without data in memory, e.g. the special buffers, are passed as is.
+fn uppercase(pool: &Pool, ctx: &mut FilterCtx, chain: Chain<'_>) -> Option<*mut ngx_chain_t> {
+    let mut out: *mut ngx_chain_t = ptr::null_mut();
+    let mut ll = &raw mut out;
+
+    for mut b in chain {
+        if b.as_bytes().is_empty() {
+            let cl = unsafe { ngx_alloc_chain_link(pool.as_ptr()) };
+            if cl.is_null() {
+                return None;
+            }
+            unsafe {
+                (*cl).buf = b.as_ngx_buf_mut();
+                (*cl).next = ptr::null_mut();
+                *ll = cl;
+                ll = &raw mut (*cl).next;
+            }
+            continue;
+        }
+
+        let mut copy: *mut ngx_buf_t = ptr::null_mut();
+
+        for chunk in b.as_bytes().chunks(BUFFER_SIZE) {
+            let cl = ctx.chains.get_free_buf(pool)?.as_ptr();
+            // SAFETY: the free chain links have a buffer, either reused or allocated empty.
+            let buf = unsafe { &mut *(*cl).buf };
+
+            if buf.start.is_null() {
+                let data = pool.alloc(BUFFER_SIZE).cast::<u8>();
+                if data.is_null() {
+                    return None;
+                }
+                buf.start = data;
+                buf.end = unsafe { data.add(BUFFER_SIZE) };
+                buf.set_temporary(1);
+                ctx.allocated += 1;
+            } else {
+                ctx.reused += 1;
+            }
+
+            // SAFETY: the buffer memory is `BUFFER_SIZE` bytes long.
+            let data = unsafe { slice::from_raw_parts_mut(buf.start, chunk.len()) };
+            data.copy_from_slice(chunk);
+            data.make_ascii_uppercase();
+
+            buf.pos = buf.start;
+            buf.last = unsafe { buf.start.add(chunk.len()) };
+            // A reused buffer keeps the flags of the previous use.
+            buf.set_flush(0);
+            buf.set_last_buf(0);
+            buf.set_last_in_chain(0);
+            copy = buf;
+
+            unsafe {
+                *ll = cl;
+                ll = &raw mut (*cl).next;
+            }
+        }
+
+        // The flags of the original buffer go with the last copy of its data.
+        unsafe {
+            let src = &*b.as_ngx_buf();
+            (*copy).set_flush(src.flush());
+            (*copy).set_last_buf(src.last_buf());
+            (*copy).set_last_in_chain(src.last_in_chain());
+        }
+
+        b.consume();
+    }
+
+    Some(out)
+}
@@ -12,12 +12,11 @@ use core::ptr;
 
 use ngx::core::{Pool, Status};
 use ngx::ffi::{
-    NGX_CONF_NOARGS, NGX_CONF_TAKE1, NGX_CONF_UNSET, NGX_ERROR, NGX_HTTP_MODULE,
-    NGX_HTTP_SRV_CONF_OFFSET, NGX_HTTP_UPS_CONF, NGX_LOG_EMERG, ngx_atoi, ngx_command_t,
-    ngx_conf_t, ngx_connection_t, ngx_event_free_peer_pt, ngx_event_get_peer_pt, ngx_http_module_t,
-    ngx_http_upstream_init_peer_pt, ngx_http_upstream_init_pt, ngx_http_upstream_init_round_robin,
-    ngx_http_upstream_srv_conf_t, ngx_http_upstream_t, ngx_int_t, ngx_module_t,
-    ngx_peer_connection_t, ngx_str_t, ngx_uint_t,
+    NGX_CONF_NOARGS, NGX_CONF_TAKE1, NGX_CONF_UNSET, NGX_ERROR, NGX_HTTP_SRV_CONF_OFFSET,
+    NGX_HTTP_UPS_CONF, NGX_LOG_EMERG, ngx_atoi, ngx_command_t, ngx_conf_t, ngx_connection_t,
+    ngx_event_free_peer_pt, ngx_event_get_peer_pt, ngx_http_upstream_init_peer_pt,
+    ngx_http_upstream_init_pt, ngx_http_upstream_init_round_robin, ngx_http_upstream_srv_conf_t,
+    ngx_http_upstream_t, ngx_int_t, ngx_module_t, ngx_peer_connection_t, ngx_str_t, ngx_uint_t,
 };
 use ngx::http::{HttpModule, Merge, MergeConfigError, Request};
 use ngx::http::{HttpModuleServerConf, NgxHttpUpstreamModule};
@@ -71,17 +70,6 @@ impl Default for UpstreamPeerData {
     }
 }
 
-static NGX_HTTP_UPSTREAM_CUSTOM_CTX: ngx_http_module_t = ngx_http_module_t {
-    preconfiguration: Some(Module::preconfiguration),
-    postconfiguration: Some(Module::postconfiguration),
-    create_main_conf: None,
-    init_main_conf: None,
-    create_srv_conf: Some(Module::create_srv_conf),
-    merge_srv_conf: Some(Module::merge_srv_conf),
-    create_loc_conf: None,
-    merge_loc_conf: None,
-};
-
 static mut NGX_HTTP_UPSTREAM_CUSTOM_COMMANDS: [ngx_command_t; 2] = [
     ngx_command_t {
         name: ngx_string!("custom"),
@@ -94,20 +82,12 @@ static mut NGX_HTTP_UPSTREAM_CUSTOM_COMMANDS: [ngx_command_t; 2] = [
     ngx_command_t::empty(),
 ];
 
-// Generate the `ngx_modules` table with exported modules.
-// This feature is required to build a 'cdylib' dynamic module outside of the NGINX buildsystem.
-#[cfg(feature = "export-modules")]
-ngx::ngx_modules!(ngx_http_upstream_custom_module);
-
-#[used]
-#[allow(non_upper_case_globals)]
-#[cfg_attr(not(feature = "export-modules"), unsafe(no_mangle))]
-pub static mut ngx_http_upstream_custom_module: ngx_module_t = ngx_module_t {
-    ctx: &raw const NGX_HTTP_UPSTREAM_CUSTOM_CTX as _,
-    commands: unsafe { &raw mut NGX_HTTP_UPSTREAM_CUSTOM_COMMANDS[0] },
-    type_: NGX_HTTP_MODULE as _,
-    ..ngx_module_t::default()
-};
+ngx::ngx_http_module! {
+    pub static mut ngx_http_upstream_custom_module = Module {
+        commands: NGX_HTTP_UPSTREAM_CUSTOM_COMMANDS,
+        conf: [srv],
+    }
+}
 
 // http_upstream_init_custom_peer
 // The module's custom peer.init callback. On HTTP request the peer upstream get and free callbacks
@@ -144,7 +124,7 @@ http_upstream_init_peer_pt!(
             (*hcpd).conf = Some(hccf);
             (*hcpd).upstream = maybe_upstream;
             (*hcpd).data = (*upstream_ptr).peer.data;
-            (*hcpd).client_connection = Some(request.connection());
+            (*hcpd).client_connection = Some(request.connection().as_ptr());
             (*hcpd).original_get_peer = (*upstream_ptr).peer.get;
             (*hcpd).original_free_peer = (*upstream_ptr).peer.free;
 
@@ -0,0 +1,189 @@
+/*
+ * A load balancer preferring the peer designated by a request header.
+ *
+ * The reference implementation of the `UpstreamPeer` trait: the peers of the upstream block are
+ * balanced with the round-robin algorithm unless the configured request header names one of the
+ * peers, e.g. `X-Backend: 127.0.0.1:8082`. If the designated peer is unavailable to the
+ * round-robin algorithm (down, failed within `fail_timeout` or at the `max_conns` limit) or fails,
+ * the next attempts are made with the round-robin algorithm.
+ */
+use core::ffi::{c_char, c_void};
+use core::ptr;
+
+use ngx::core::Status;
+use ngx::ffi::{
+    NGX_CONF_TAKE1, NGX_HTTP_SRV_CONF_OFFSET, NGX_HTTP_UPS_CONF, ngx_command_t, ngx_conf_t,
+    ngx_http_upstream_rr_peer_t, ngx_http_upstream_srv_conf_t, ngx_module_t, ngx_peer_connection_t,
+    ngx_str_t, ngx_time, ngx_uint_t,
+};
+use ngx::http::{
+    HttpModule, HttpModuleServerConf, Merge, MergeConfigError, NgxHttpUpstreamModule, Request,
+    RoundRobinPeers, UpstreamPeer, set_upstream_peer,
+};
+use ngx::{ngx_log_debug_http, ngx_log_debug_mask, ngx_string};
+
+#[derive(Debug)]
+struct SrvConfig {
+    header: ngx_str_t,
+}
+
+impl Default for SrvConfig {
+    fn default() -> Self {
+        SrvConfig { header: ngx_str_t::empty() }
+    }
+}
+
+impl Merge for SrvConfig {
+    fn merge(&mut self, _prev: &SrvConfig) -> Result<(), MergeConfigError> {
+        Ok(())
+    }
+}
+
+struct Module;
+
+impl HttpModule for Module {
+    fn module() -> &'static ngx_module_t {
+        unsafe { &*::core::ptr::addr_of!(ngx_http_upstream_prefer_module) }
+    }
+}
+
+unsafe impl HttpModuleServerConf for Module {
+    type ServerConf = SrvConfig;
+}
+
+static mut NGX_HTTP_UPSTREAM_PREFER_COMMANDS: [ngx_command_t; 2] = [
+    ngx_command_t {
+        name: ngx_string!("prefer_header"),
+        type_: (NGX_HTTP_UPS_CONF | NGX_CONF_TAKE1) as ngx_uint_t,
+        set: Some(ngx_http_upstream_prefer_header),
+        conf: NGX_HTTP_SRV_CONF_OFFSET,
+        offset: 0,
+        post: ptr::null_mut(),
+    },
+    ngx_command_t::empty(),
+];
+
+ngx::ngx_http_module! {
+    pub static mut ngx_http_upstream_prefer_module = Module {
+        commands: NGX_HTTP_UPSTREAM_PREFER_COMMANDS,
+        conf: [srv],
+    }
+}
+
+/// Per-request balancer state.
+struct PreferPeer {
+    /// Value of the header, cleared after the first attempt.
+    ///
+    /// The value is allocated from the request pool and remains valid while the request exists.
+    preferred: Option<ngx_str_t>,
+}
+
+impl UpstreamPeer for PreferPeer {
+    fn init(r: &mut Request, us: &ngx_http_upstream_srv_conf_t) -> Result<Self, Status> {
+        let conf = Module::server_conf(us).ok_or(Status::NGX_ERROR)?;
+        let header = conf.header.as_bytes();
+
+        let preferred = r.headers_in().get(header).map(|h| h.as_ngx_table_elt().value);
+
+        if let Some(value) = preferred {
+            ngx_log_debug_http!(r, "upstream prefer: \"{value}\"");
+        }
+
+        Ok(PreferPeer { preferred })
+    }
+
+    fn get(&mut self, pc: &mut ngx_peer_connection_t, rr: &mut RoundRobinPeers) -> Status {
+        let Some(value) = self.preferred.take() else {
+            return rr.get(pc);
+        };
+
+        if let Some(peer) = find_peer(rr, value.as_bytes()) {
+            ngx_log_debug_mask!(DebugMask::Http, pc.log, "upstream prefer: selected \"{value}\"");
+
+            // SAFETY: the peer belongs to the round-robin peers of the request.
+            let peer = unsafe { &mut *peer };
+            peer.conns += 1;
+
+            pc.sockaddr = peer.sockaddr;
+            pc.socklen = peer.socklen;
+            pc.name = &raw mut peer.name;
+
+            rr.data().current = peer;
+            return Status::NGX_OK;
+        }
+
+        rr.get(pc)
+    }
+}
+
+/// Finds an available peer by its address or by the name in the `server` directive, and marks
+/// it as tried.
+///
+/// The availability checks mirror `ngx_http_upstream_get_peer()`.
+fn find_peer(rr: &mut RoundRobinPeers, name: &[u8]) -> Option<*mut ngx_http_upstream_rr_peer_t> {
+    let rrp = rr.data();
+    // SAFETY: the peers are initialized by the round-robin balancer.
+    let peers = unsafe { &*rrp.peers };
+
+    // The peers in a shared zone require locking; leave them to the round-robin balancer.
+    if !peers.shpool.is_null() {
+        return None;
+    }
+
+    let now = ngx_time();
+    let bits = 8 * size_of::<usize>();
+    let mut p = peers.peer;
+    let mut n = 0;
+
+    while let Some(peer) = unsafe { p.as_mut() } {
+        if peer.name.as_bytes() == name || peer.server.as_bytes() == name {
+            let tried = unsafe { &mut *rrp.tried.add(n / bits) };
+            let bit = 1 << (n % bits);
+
+            if peer.down != 0 || *tried & bit != 0 {
+                return None;
+            }
+
+            if peer.max_fails != 0
+                && peer.fails >= peer.max_fails
+                && now - peer.checked <= peer.fail_timeout
+            {
+                return None;
+            }
+
+            if peer.max_conns != 0 && peer.conns >= peer.max_conns {
+                return None;
+            }
+
+            if now - peer.checked > peer.fail_timeout {
+                peer.checked = now;
+            }
+
+            *tried |= bit;
+            return Some(p);
+        }
+
+        p = peer.next;
+        n += 1;
+    }
+
+    None
+}
+
+extern "C" fn ngx_http_upstream_prefer_header(
+    cf: *mut ngx_conf_t,
+    _cmd: *mut ngx_command_t,
+    conf: *mut c_void,
+) -> *mut c_char {
+    // SAFETY: this function is called with non-NULL cf and conf always
+    let cf = unsafe { &mut *cf };
+    let conf = unsafe { &mut *(conf as *mut SrvConfig) };
+    let args: &[ngx_str_t] = unsafe { (*cf.args).as_slice() };
+
+    conf.header = args[1];
+
+    let uscf = NgxHttpUpstreamModule::server_conf_mut(cf).expect("http upstream srv conf");
+    set_upstream_peer::<PreferPeer>(uscf);
+
+    ngx::core::NGX_CONF_OK
+}
@@ -0,0 +1,211 @@
+//! Fuzzing checks for the parsers handling untrusted input.
+//!
+//! Each check accepts arbitrary bytes and verifies that the parser neither panics nor reads out of
+//! bounds, and that feeding the input in several pieces gives the same result as feeding it at
+//! once. The first bytes of the input select the split points and the allocation budget, so that
+//! the fuzzer also explores the buffering and the allocation failure paths.
+//!
+//! The checks are shared by the `cargo fuzz` targets and the seed tests below, which can be run
+//! under Miri:
+//!
+//! ```sh
+//! cargo +nightly miri test --no-default-features
+//! ```
+use core::alloc::Layout;
+use core::cell::Cell;
+use core::ptr::NonNull;
+
+use ngx::allocator::{AllocError, Allocator, Global};
+use ngx::http::multipart::{
+    ContentDisposition, Event, Multipart, MultipartParser, multipart_boundary,
+};
+use ngx::http::negotiate::Accept;
+use ngx::parse::{ParseError, Resumable};
+
+/// Allocator failing after the specified number of allocations.
+pub struct FailingAlloc {
+    budget: Cell<usize>,
+}
+
+impl FailingAlloc {
+    /// Creates an allocator permitting `budget` allocations.
+    pub fn new(budget: usize) -> Self {
+        Self { budget: Cell::new(budget) }
+    }
+}
+
+unsafe impl Allocator for FailingAlloc {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        let budget = self.budget.get().checked_sub(1).ok_or(AllocError)?;
+        self.budget.set(budget);
+        Global.allocate(layout)
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        unsafe { Global.deallocate(ptr, layout) }
+    }
+}
+
+/// Splits the control prefix from the fuzzer input.
+///
+/// Returns the split position within the remaining data and the allocation budget.
+fn control(data: &[u8]) -> Option<(usize, usize, &[u8])> {
+    let (&[split, budget], data) = data.split_first_chunk::<2>()?;
+    let split = if data.is_empty() { 0 } else { split as usize % (data.len() + 1) };
+    Some((split, budget as usize, data))
+}
+
+/// Appends a normalized multipart event to the transcript.
+///
+/// Data events are merged, as their boundaries depend on the input buffers.
+fn record(out: &mut Vec<u8>, last_data: &mut bool, event: Event<'_>) {
+    let data = matches!(event, Event::Data(_));
+    match event {
+        Event::PartStart => out.extend_from_slice(b"[start]"),
+        Event::Header { name, value } => {
+            out.extend_from_slice(b"[header]");
+            out.extend_from_slice(name);
+            out.push(b':');
+            out.extend_from_slice(value);
+        }
+        Event::HeadersEnd => out.extend_from_slice(b"[headers end]"),
+        Event::Data(x) => {
+            if !*last_data {
+                out.extend_from_slice(b"[data]");
+            }
+            out.extend_from_slice(x);
+        }
+        Event::PartEnd => out.extend_from_slice(b"[end]"),
+        Event::End => out.extend_from_slice(b"[done]"),
+    }
+    *last_data = data;
+}
+
+fn run_multipart<A: Allocator + Clone>(
+    boundary: &[u8],
+    pieces: &[&[u8]],
+    limit: usize,
+    alloc: A,
+) -> Result<Vec<u8>, ParseError<ngx::http::multipart::MultipartError>> {
+    let mut out = Vec::new();
+    let mut last_data = false;
+    let mut parser: Multipart<'_, A> =
+        Resumable::new_in(MultipartParser::new(boundary), limit, alloc);
+
+    for piece in pieces {
+        parser.feed(piece, |ev| record(&mut out, &mut last_data, ev))?;
+    }
+    parser.finish()?;
+    Ok(out)
+}
+
+/// Multipart body parser with the resumable driver.
+///
+/// The input is `boundary CRLF body`.
+pub fn multipart(data: &[u8]) {
+    let Some((split, budget, data)) = control(data) else {
+        return;
+    };
+    let Some(n) = data.windows(2).position(|x| x == b"\r\n") else {
+        return;
+    };
+    let (boundary, body) = (&data[..n], &data[n + 2..]);
+    if boundary.is_empty() {
+        return;
+    }
+    let split = split.min(body.len());
+    let limit = body.len() + 1;
+
+    let whole = run_multipart(boundary, &[body], limit, Global);
+    let parts = run_multipart(boundary, &[&body[..split], &body[split..]], limit, Global);
+
+    match (&whole, &parts) {
+        (Ok(a), Ok(b)) => assert_eq!(a, b),
+        (Err(ParseError::Invalid(a, _)), Err(ParseError::Invalid(b, _))) => assert_eq!(a, b),
+        (Err(a), Err(b)) => assert_eq!(a, b),
+        (a, b) => panic!("results differ: {a:?} != {b:?}"),
+    }
+
+    // Small buffer limit and allocation failures must be reported as errors.
+    let alloc = FailingAlloc::new(budget % 4);
+    let _ = run_multipart(boundary, &[&body[..split], &body[split..]], budget, &alloc);
+}
+
+/// Multipart header values: `Content-Type` boundary and `Content-Disposition`.
+pub fn multipart_headers(data: &[u8]) {
+    if let Some(boundary) = multipart_boundary(data) {
+        assert!(!boundary.is_empty() && boundary.len() <= 70);
+    }
+
+    if let Some(cd) = ContentDisposition::parse(data) {
+        assert!(!cd.kind.is_empty());
+    }
+}
+
+/// `Accept` and `Accept-Language` header values.
+pub fn accept(data: &[u8]) {
+    let Some((_, budget, data)) = control(data) else {
+        return;
+    };
+
+    let mut types = Accept::media_types_in(Global);
+    types.add(data).expect("allocation");
+    for offered in ["text/html", "application/json", "*/*", ""] {
+        assert!(types.quality(offered) <= 1000);
+    }
+
+    let mut langs = Accept::languages_in(Global);
+    langs.add(data).expect("allocation");
+    for offered in ["en", "en-US", "de", ""] {
+        assert!(langs.quality(offered) <= 1000);
+    }
+
+    let alloc = FailingAlloc::new(budget % 4);
+    let _ = Accept::media_types_in(&alloc).add(data);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_control(data: &[u8]) -> impl Iterator<Item = Vec<u8>> + '_ {
+        (0..=data.len().min(255)).map(|split| {
+            let mut v = vec![split as u8, 2];
+            v.extend_from_slice(data);
+            v
+        })
+    }
+
+    #[test]
+    fn test_multipart_seeds() {
+        for seed in [
+            &b"xyz\r\n--xyz\r\nContent-Disposition: form-data; name=\"a\"\r\n\r\n1\r\n--xyz--\r\n"
+                [..],
+            b"b\r\npreamble\r\n--b\r\n\r\ndata\r\n--b\r\nX: y\r\n\r\n\r\n--b--",
+            b"b\r\n--b\r\ninvalid header\r\n\r\n",
+            b"b\r\n--bx\r\n",
+        ] {
+            for input in with_control(seed) {
+                multipart(&input);
+            }
+        }
+    }
+
+    #[test]
+    fn test_header_seeds() {
+        for seed in [
+            &b"multipart/form-data; boundary=\"abc\""[..],
+            b"multipart/form-data; boundary=",
+            b"form-data; name=\"field\"; filename=\"a;b.txt\"",
+            b"; name=x",
+        ] {
+            multipart_headers(seed);
+        }
+
+        for seed in [&b"text/*;q=0.5, text/html, */*;q=0.1"[..], b"en-US,en;q=0.9,*;q=", b";;,"] {
+            for input in with_control(seed) {
+                accept(&input);
+            }
+        }
+    }
+}
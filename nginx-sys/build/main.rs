@@ -20,6 +20,7 @@ const NGX_CONF_FEATURES: &[&str] = &[
     "api",
     "compat",
     "debug",
+    "have_deferred_accept",
     "have_devpoll",
     "have_epollexclusive",
     "have_epollrdhup",
@@ -74,6 +75,18 @@ const NGX_CONF_FEATURES: &[&str] = &[
 const NGX_CONF_OS: &[&str] =
     &["darwin", "freebsd", "gnu_hurd", "hpux", "linux", "solaris", "tru64", "win32"];
 
+/// The nginx versions introducing the API changes that require conditional compilation.
+///
+/// The cfg names of the versions not newer than the nginx source will be exposed to the
+/// buildscripts of _direct_ dependents of this crate as `DEP_NGINX_VERSIONS` environment variable.
+/// The list of recognized names will be exported as `DEP_NGINX_VERSIONS_CHECK`.
+const NGX_VERSION_CHECKS: &[(u64, &str)] = &[
+    //
+    (1_021_001, "nginx1_21_1"),
+    (1_023_000, "nginx1_23_0"),
+    (1_025_001, "nginx1_25_1"),
+];
+
 type BoxError = Box<dyn StdError>;
 
 /// Function invoked when `cargo build` is executed.
@@ -347,6 +360,7 @@ pub fn print_cargo_metadata<T: AsRef<Path>>(
 
     let mut ngx_features: Vec<String> = vec![];
     let mut ngx_os = String::new();
+    let mut ngx_version_number: u64 = 0;
 
     let expanded = expand_definitions(includes, defines)?;
     for line in String::from_utf8(expanded)?.lines() {
@@ -365,6 +379,7 @@ pub fn print_cargo_metadata<T: AsRef<Path>>(
             println!("cargo::metadata=version={}", unquote(value));
         } else if name == "nginx_version_number" {
             println!("cargo::metadata=version_number={value}");
+            ngx_version_number = value.parse()?;
         } else if NGX_CONF_OS.contains(&name.as_str()) {
             ngx_os = name;
         } else if NGX_CONF_FEATURES.contains(&name.as_str()) && value != "0" {
@@ -410,6 +425,18 @@ pub fn print_cargo_metadata<T: AsRef<Path>>(
     println!("cargo::metadata=os={ngx_os}");
     println!("cargo::rustc-cfg=ngx_os=\"{ngx_os}\"");
 
+    // A list of all recognized version cfgs to be passed to rustc-check-cfg.
+    let values = NGX_VERSION_CHECKS.iter().map(|x| x.1).collect::<Vec<_>>().join(",");
+    println!("cargo::metadata=versions_check={values}");
+    // A list of version cfgs satisfied by the nginx source we're using
+    let values = NGX_VERSION_CHECKS
+        .iter()
+        .filter(|x| ngx_version_number >= x.0)
+        .map(|x| x.1)
+        .collect::<Vec<_>>()
+        .join(",");
+    println!("cargo::metadata=versions={values}");
+
     Ok(())
 }
 
@@ -3,6 +3,10 @@
 #include <ngx_event.h>
 #include <ngx_event_connect.h>
 
+#if (NGX_THREADS)
+#include <ngx_thread_pool.h>
+#endif
+
 /* __has_include was a compiler-specific extension until C23,
  * but it's safe to assume that bindgen supports it via libclang.
  */
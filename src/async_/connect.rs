@@ -0,0 +1,120 @@
+use core::fmt;
+use core::future::poll_fn;
+use core::ptr;
+use core::task::{Poll, Waker};
+use core::time::Duration;
+
+use nginx_sys::{ngx_connection_t, ngx_err_t, ngx_event_t, ngx_msec_t};
+
+use crate::core::{ConnectState, PeerConnection, Status, add_timer, del_timer};
+
+/// Error returned by [`connect`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConnectError {
+    /// The connection was not established within the timeout.
+    TimedOut,
+    /// The connection failed with the socket error code, e.g. `ECONNREFUSED`.
+    Os(ngx_err_t),
+    /// The connection could not be started; see [`PeerConnection::connect`].
+    Status(Status),
+}
+
+impl fmt::Display for ConnectError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TimedOut => f.write_str("connection timed out"),
+            Self::Os(err) => write!(f, "connection failed: error {err}"),
+            Self::Status(rc) => write!(f, "connection failed: {rc:?}"),
+        }
+    }
+}
+
+impl core::error::Error for ConnectError {}
+
+/// Connects `peer`, waiting at most `timeout` for the connection to be established.
+///
+/// The event handlers and the data of the connection are reset once the future completes, and
+/// should be set again for further I/O.
+///
+/// ```no_run
+/// # use core::time::Duration;
+/// # use ngx::async_::{ConnectError, connect};
+/// # use ngx::core::PeerConnection;
+/// async fn notify(mut peer: PeerConnection) -> Result<(), ConnectError> {
+///     connect(&mut peer, Duration::from_secs(5)).await?;
+///     // ...
+///     Ok(())
+/// }
+/// ```
+pub async fn connect(peer: &mut PeerConnection, timeout: Duration) -> Result<(), ConnectError> {
+    if peer.connect().map_err(ConnectError::Status)? == ConnectState::Connected {
+        return Ok(());
+    }
+
+    // The waker slot is pinned in the future state while the handlers are set.
+    let mut waker: Option<Waker> = None;
+    let slot: *mut Option<Waker> = &raw mut waker;
+    let mut guard = HandlerGuard(peer);
+    guard.0.set_handlers(slot.cast(), Some(connect_handler), Some(connect_handler));
+
+    let Some(c) = guard.0.connection() else {
+        return Err(ConnectError::Status(Status::NGX_ERROR));
+    };
+    let c: *mut ngx_connection_t = c.as_ptr();
+    // SAFETY: the connection is open while connecting.
+    let (rev, wev) = unsafe { ((*c).read, (*c).write) };
+    let msec = timeout.as_millis().min(ngx_msec_t::MAX as u128) as ngx_msec_t;
+    // SAFETY: the write event is valid while the connection is open.
+    unsafe { add_timer(wev, msec) };
+
+    poll_fn(|cx| {
+        // SAFETY: the events are valid while the connection is open.
+        unsafe {
+            if (*wev).timedout() != 0 {
+                return Poll::Ready(Err(ConnectError::TimedOut));
+            }
+            if (*wev).ready() == 0 && (*rev).ready() == 0 {
+                // SAFETY: the slot is only accessed by the handlers in the same thread.
+                match &mut *slot {
+                    Some(w) => w.clone_from(cx.waker()),
+                    None => *slot = Some(cx.waker().clone()),
+                }
+                return Poll::Pending;
+            }
+        }
+        Poll::Ready(guard.0.check_connected().map_err(ConnectError::Os))
+    })
+    .await
+}
+
+/// Resets the handlers of the connection set for [`connect`], as the waker slot does not outlive
+/// the future.
+struct HandlerGuard<'a>(&'a mut PeerConnection);
+
+impl Drop for HandlerGuard<'_> {
+    fn drop(&mut self) {
+        let Some(c) = self.0.connection() else {
+            return;
+        };
+        let c: *mut ngx_connection_t = c.as_ptr();
+        // SAFETY: the write event is valid while the connection is open.
+        unsafe {
+            if (*(*c).write).timer_set() != 0 {
+                del_timer((*c).write);
+            }
+        }
+        self.0.set_handlers(ptr::null_mut(), Some(empty_handler), Some(empty_handler));
+    }
+}
+
+unsafe extern "C" fn connect_handler(ev: *mut ngx_event_t) {
+    // SAFETY: the event data is the connection, and the connection data is the waker slot set in
+    // `connect`.
+    let c = unsafe { (*ev).data.cast::<ngx_connection_t>() };
+    let waker = unsafe { &mut *(*c).data.cast::<Option<Waker>>() };
+    if let Some(waker) = waker.take() {
+        waker.wake();
+    }
+}
+
+unsafe extern "C" fn empty_handler(_ev: *mut ngx_event_t) {}
@@ -1,8 +1,19 @@
 //! Async runtime and set of utilities on top of the NGINX event loop.
-pub use self::sleep::{Sleep, sleep};
-pub use self::spawn::{Task, spawn};
+pub use self::connect::{ConnectError, connect};
+#[cfg(feature = "task-registry")]
+pub use self::registry::{TaskInfo, live_tasks, log_live_tasks};
+pub use self::sleep::{Interval, Sleep, interval, sleep};
+#[cfg(ngx_feature = "http")]
+pub use self::spawn::spawn_for;
+pub use self::spawn::{RuntimeStats, Task, TaskOwner, runtime_stats, spawn, spawn_in};
 
+pub mod net;
 pub mod resolver;
+pub mod sync;
 
+mod connect;
+
+#[cfg(feature = "task-registry")]
+mod registry;
 mod sleep;
 mod spawn;
@@ -0,0 +1,223 @@
+//! Asynchronous TCP and UDP sockets on top of the NGINX event loop.
+//!
+//! The sockets are non-blocking nginx connections: the I/O operations return [`Poll::Pending`]
+//! instead of blocking, and the task is woken by the connection event handlers once the socket
+//! is ready. This allows the modules to talk to external services, e.g. to send a webhook or
+//! to query an authentication daemon, without blocking the worker process.
+//!
+//! ```no_run
+//! # use core::time::Duration;
+//! # use ngx::async_::net::TcpStream;
+//! async fn ping() -> Result<(), ngx::core::Status> {
+//!     let addr = "127.0.0.1:9000".parse().unwrap();
+//!     let mut stream = TcpStream::connect(addr, Duration::from_secs(5))
+//!         .await
+//!         .map_err(|_| ngx::core::Status::NGX_ERROR)?;
+//!     stream.write_all(b"PING\r\n").await?;
+//!
+//!     let mut buf = [0u8; 64];
+//!     let n = stream.read(&mut buf).await?;
+//!     // ...
+//!     Ok(())
+//! }
+//! ```
+use alloc::boxed::Box;
+use core::future::poll_fn;
+use core::net::SocketAddr;
+use core::task::{Context, Poll, Waker};
+use core::time::Duration;
+
+use nginx_sys::{ngx_connection_t, ngx_event_t};
+
+use crate::async_::{ConnectError, connect};
+use crate::core::{PeerConnection, SocketType, Status};
+use crate::log::ngx_cycle_log;
+
+/// Connection with the wakers of the tasks waiting for the I/O.
+///
+/// The value is boxed to keep the address stored in the connection data stable.
+struct Socket {
+    peer: PeerConnection,
+    read: Option<Waker>,
+    write: Option<Waker>,
+}
+
+impl Socket {
+    async fn connect(
+        addr: SocketAddr,
+        ty: SocketType,
+        timeout: Duration,
+    ) -> Result<Box<Self>, ConnectError> {
+        let mut peer = PeerConnection::new(addr, ngx_cycle_log());
+        peer.set_socket_type(ty);
+        connect(&mut peer, timeout).await?;
+
+        let mut this = Box::new(Self { peer, read: None, write: None });
+        let data: *mut Self = &raw mut *this;
+        this.peer.set_handlers(data.cast(), Some(read_handler), Some(write_handler));
+        Ok(this)
+    }
+
+    fn poll_read(&mut self, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<Result<usize, Status>> {
+        match self.peer.recv(buf) {
+            Err(Status::NGX_AGAIN) => {
+                register(&mut self.read, cx);
+                Poll::Pending
+            }
+            x => Poll::Ready(x),
+        }
+    }
+
+    fn poll_write(&mut self, cx: &mut Context<'_>, buf: &[u8]) -> Poll<Result<usize, Status>> {
+        match self.peer.send(buf) {
+            Err(Status::NGX_AGAIN) => {
+                register(&mut self.write, cx);
+                Poll::Pending
+            }
+            x => Poll::Ready(x),
+        }
+    }
+
+    fn peer_addr(&self) -> SocketAddr {
+        self.peer.addr()
+    }
+
+    fn local_addr(&mut self) -> Option<SocketAddr> {
+        self.peer.connection_mut()?.local_addr()
+    }
+}
+
+fn register(slot: &mut Option<Waker>, cx: &mut Context<'_>) {
+    match slot {
+        Some(w) => w.clone_from(cx.waker()),
+        None => *slot = Some(cx.waker().clone()),
+    }
+}
+
+unsafe extern "C" fn read_handler(ev: *mut ngx_event_t) {
+    // SAFETY: the event data is the connection, and the connection data is the boxed socket.
+    let socket = unsafe { &mut *(*(*ev).data.cast::<ngx_connection_t>()).data.cast::<Socket>() };
+    if let Some(waker) = socket.read.take() {
+        waker.wake();
+    }
+}
+
+unsafe extern "C" fn write_handler(ev: *mut ngx_event_t) {
+    // SAFETY: the event data is the connection, and the connection data is the boxed socket.
+    let socket = unsafe { &mut *(*(*ev).data.cast::<ngx_connection_t>()).data.cast::<Socket>() };
+    if let Some(waker) = socket.write.take() {
+        waker.wake();
+    }
+}
+
+/// TCP connection to a remote host.
+///
+/// The connection is closed when the value is dropped.
+pub struct TcpStream(Box<Socket>);
+
+impl TcpStream {
+    /// Opens a TCP connection to `addr`, waiting at most `timeout` for the connection to be
+    /// established.
+    pub async fn connect(addr: SocketAddr, timeout: Duration) -> Result<Self, ConnectError> {
+        Socket::connect(addr, SocketType::Stream, timeout).await.map(Self)
+    }
+
+    /// Attempts to read data into `buf`.
+    ///
+    /// Returns `Ok(0)` once the remote host closed the connection.
+    pub fn poll_read(
+        &mut self,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<Result<usize, Status>> {
+        self.0.poll_read(cx, buf)
+    }
+
+    /// Attempts to write data from `buf`, and returns the number of bytes written.
+    pub fn poll_write(&mut self, cx: &mut Context<'_>, buf: &[u8]) -> Poll<Result<usize, Status>> {
+        self.0.poll_write(cx, buf)
+    }
+
+    /// Reads data into `buf`, and returns the number of bytes read.
+    pub async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Status> {
+        poll_fn(|cx| self.poll_read(cx, buf)).await
+    }
+
+    /// Writes data from `buf`, and returns the number of bytes written.
+    pub async fn write(&mut self, buf: &[u8]) -> Result<usize, Status> {
+        poll_fn(|cx| self.poll_write(cx, buf)).await
+    }
+
+    /// Writes all the data from `buf`.
+    pub async fn write_all(&mut self, mut buf: &[u8]) -> Result<(), Status> {
+        while !buf.is_empty() {
+            let n = self.write(buf).await?;
+            buf = &buf[n..];
+        }
+        Ok(())
+    }
+
+    /// Returns the address of the remote host.
+    pub fn peer_addr(&self) -> SocketAddr {
+        self.0.peer_addr()
+    }
+
+    /// Returns the local address of the connection.
+    pub fn local_addr(&mut self) -> Option<SocketAddr> {
+        self.0.local_addr()
+    }
+
+    /// Returns the underlying connection.
+    pub fn peer_connection(&mut self) -> &mut PeerConnection {
+        &mut self.0.peer
+    }
+}
+
+/// UDP socket connected to a remote host.
+///
+/// The socket only sends datagrams to and receives datagrams from the connected address.
+pub struct UdpSocket(Box<Socket>);
+
+impl UdpSocket {
+    /// Creates a UDP socket connected to `addr`.
+    pub async fn connect(addr: SocketAddr) -> Result<Self, ConnectError> {
+        // Connecting a datagram socket only sets the remote address, and does not wait.
+        Socket::connect(addr, SocketType::Datagram, Duration::ZERO).await.map(Self)
+    }
+
+    /// Attempts to receive a datagram into `buf`, and returns the size of the datagram.
+    ///
+    /// The rest of a datagram larger than `buf` is discarded.
+    pub fn poll_recv(
+        &mut self,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<Result<usize, Status>> {
+        self.0.poll_read(cx, buf)
+    }
+
+    /// Attempts to send `buf` as a single datagram.
+    pub fn poll_send(&mut self, cx: &mut Context<'_>, buf: &[u8]) -> Poll<Result<usize, Status>> {
+        self.0.poll_write(cx, buf)
+    }
+
+    /// Receives a datagram into `buf`, and returns the size of the datagram.
+    pub async fn recv(&mut self, buf: &mut [u8]) -> Result<usize, Status> {
+        poll_fn(|cx| self.poll_recv(cx, buf)).await
+    }
+
+    /// Sends `buf` as a single datagram.
+    pub async fn send(&mut self, buf: &[u8]) -> Result<usize, Status> {
+        poll_fn(|cx| self.poll_send(cx, buf)).await
+    }
+
+    /// Returns the address of the remote host.
+    pub fn peer_addr(&self) -> SocketAddr {
+        self.0.peer_addr()
+    }
+
+    /// Returns the local address of the socket.
+    pub fn local_addr(&mut self) -> Option<SocketAddr> {
+        self.0.local_addr()
+    }
+}
@@ -0,0 +1,112 @@
+//! Registry of the live tasks, for diagnosing leaked futures.
+//!
+//! With the `task-registry` feature, each task spawned with [`spawn`](super::spawn) or
+//! [`spawn_for`](super::spawn_for) is recorded until the future completes or is dropped. A task
+//! that stays in the registry after its request is finalized usually indicates a leaked future,
+//! e.g. a detached task waiting for an event that never happens, which keeps the request memory
+//! alive.
+//!
+//! The registry can be exposed from a debug endpoint with [`live_tasks`], or logged on worker
+//! shutdown with [`log_live_tasks`] from the module's `exit_process` handler.
+use alloc::vec::Vec;
+use core::cell::UnsafeCell;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::core::current_msec;
+use crate::ffi::{NGX_LOG_NOTICE, ngx_log_t, ngx_msec_t};
+use crate::ngx_log_error;
+
+use super::spawn::TaskOwner;
+
+/// Information about a live task.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TaskInfo {
+    /// Task identifier, unique within the worker process.
+    pub id: usize,
+    /// Request the task was spawned for, if any.
+    pub owner: Option<TaskOwner>,
+    /// Type name of the spawned future.
+    pub name: &'static str,
+    /// Time the task was spawned, in the milliseconds of `ngx_current_msec`.
+    pub spawned: ngx_msec_t,
+}
+
+struct Registry(UnsafeCell<Vec<TaskInfo>>);
+
+// SAFETY: the tasks are only spawned and dropped in the main thread of a worker process.
+unsafe impl Sync for Registry {}
+
+static REGISTRY: Registry = Registry(UnsafeCell::new(Vec::new()));
+static NEXT_ID: AtomicUsize = AtomicUsize::new(1);
+
+fn tasks() -> &'static mut Vec<TaskInfo> {
+    // SAFETY: see `unsafe impl Sync`, and the references are not held across calls.
+    unsafe { &mut *REGISTRY.0.get() }
+}
+
+/// Records a new task and returns its identifier.
+///
+/// The task is not recorded if the registry cannot grow; the task still runs, but is missing
+/// from [`live_tasks`].
+pub(crate) fn register(owner: Option<TaskOwner>, name: &'static str) -> usize {
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    let spawned = current_msec();
+    let tasks = tasks();
+    if tasks.try_reserve(1).is_ok() {
+        tasks.push(TaskInfo { id, owner, name, spawned });
+    }
+    id
+}
+
+/// Removes a completed or dropped task.
+pub(crate) fn unregister(id: usize) {
+    let tasks = tasks();
+    if let Some(pos) = tasks.iter().position(|x| x.id == id) {
+        tasks.swap_remove(pos);
+    }
+}
+
+/// Returns a snapshot of the live tasks in the current worker process.
+pub fn live_tasks() -> Vec<TaskInfo> {
+    tasks().clone()
+}
+
+/// Logs the live tasks at the `notice` level.
+///
+/// ```no_run
+/// # use ngx::ffi::ngx_cycle_t;
+/// unsafe extern "C" fn exit_process(cycle: *mut ngx_cycle_t) {
+///     ngx::async_::log_live_tasks(unsafe { (*cycle).log });
+/// }
+/// ```
+pub fn log_live_tasks(log: *mut ngx_log_t) {
+    let now = current_msec();
+
+    for task in tasks().iter() {
+        let age = now.wrapping_sub(task.spawned);
+        match task.owner {
+            Some(owner) => {
+                ngx_log_error!(
+                    NGX_LOG_NOTICE,
+                    log,
+                    "async: live task #{} \"{}\", *{} request {}, age {}ms",
+                    task.id,
+                    task.name,
+                    owner.connection,
+                    owner.request,
+                    age
+                );
+            }
+            None => {
+                ngx_log_error!(
+                    NGX_LOG_NOTICE,
+                    log,
+                    "async: live task #{} \"{}\", age {}ms",
+                    task.id,
+                    task.name,
+                    age
+                );
+            }
+        }
+    }
+}
@@ -7,9 +7,12 @@
 //!
 //! See <https://nginx.org/en/docs/http/ngx_http_core_module.html#resolver>.
 
+use alloc::boxed::Box;
 use alloc::string::{String, ToString};
+use alloc::vec;
 use core::ffi::c_void;
 use core::fmt;
+use core::net::SocketAddr;
 use core::num::NonZero;
 use core::pin::Pin;
 use core::ptr::NonNull;
@@ -21,12 +24,11 @@ use nginx_sys::{
 };
 
 use crate::{
-    allocator::Box,
     collections::Vec,
-    core::{Pool, Status},
+    core::{Pool, Status, sockaddr_to_socket_addr},
     ffi::{
-        ngx_addr_t, ngx_msec_t, ngx_resolve_name, ngx_resolve_start, ngx_resolver_ctx_t,
-        ngx_resolver_t, ngx_str_t,
+        ngx_addr_t, ngx_msec_t, ngx_resolve_name, ngx_resolve_start, ngx_resolver_addr_t,
+        ngx_resolver_ctx_t, ngx_resolver_t, ngx_str_t,
     },
 };
 
@@ -120,63 +122,114 @@ impl Resolver {
         Self { resolver, timeout }
     }
 
-    /// Resolve a name into a set of addresses.
-    pub async fn resolve_name(&self, name: &ngx_str_t, pool: &Pool) -> Res {
-        let mut resolver = Resolution::new(name, &ngx_str_t::empty(), self, pool)?;
-        resolver.as_mut().await
+    /// Creates a new `Resolver` from the `resolver` and `resolver_timeout` directives of the
+    /// request location.
+    ///
+    /// Returns `None` if no resolver is configured.
+    #[cfg(ngx_feature = "http")]
+    pub fn from_request(r: &crate::http::Request) -> Option<Self> {
+        use crate::http::{HttpModuleLocationConf, NgxHttpCoreModule};
+
+        let clcf = NgxHttpCoreModule::location_conf(r)?;
+        let resolver = NonNull::new(clcf.resolver)?;
+        // SAFETY: the resolver is allocated from the configuration pool.
+        if unsafe { resolver.as_ref() }.connections.nelts == 0 {
+            return None;
+        }
+        Some(Self::from_resolver(resolver, clcf.resolver_timeout))
+    }
+
+    /// Resolve a host name into a set of addresses, with the port 0.
+    ///
+    /// The resolution is limited by the resolver timeout, and dropping the future cancels the
+    /// resolution. The future does not borrow any pool: to cancel the resolution when the request
+    /// is terminated, spawn the task with [`spawn_in`](crate::async_::spawn_in) on the request
+    /// pool, or keep the [`Task`](crate::async_::Task) handle in the request pool.
+    pub async fn resolve_name(&self, name: &str) -> Result<vec::Vec<SocketAddr>, Error> {
+        self.lookup_host(name, 0).await
+    }
+
+    /// Resolve a host name into a set of socket addresses with the specified port.
+    ///
+    /// See [`Self::resolve_name`].
+    pub async fn lookup_host(&self, name: &str, port: u16) -> Result<vec::Vec<SocketAddr>, Error> {
+        self.resolve(name.as_bytes(), b"", |ctx| ctx.into_socket_addrs(port)).await
+    }
+
+    /// Resolve a name into a set of addresses allocated from the pool.
+    pub async fn resolve_name_in(&self, name: &ngx_str_t, pool: &Pool) -> Res {
+        self.resolve(name.as_bytes(), b"", |ctx| ctx.into_result(pool)).await
     }
 
     /// Resolve a service into a set of addresses.
     pub async fn resolve_service(&self, name: &ngx_str_t, service: &ngx_str_t, pool: &Pool) -> Res {
-        let mut resolver = Resolution::new(name, service, self, pool)?;
-        resolver.as_mut().await
+        self.resolve(name.as_bytes(), service.as_bytes(), |ctx| ctx.into_result(pool)).await
+    }
+
+    async fn resolve<T>(
+        &self,
+        name: &[u8],
+        service: &[u8],
+        f: impl FnOnce(ResolverCtx) -> Result<T, Error>,
+    ) -> Result<T, Error> {
+        let mut resolution = Resolution::new(name, service, self)?;
+        // The completed ctx refers to the name owned by the resolution, which must outlive it.
+        let ctx = (&mut *resolution).await?;
+        f(ctx)
     }
 }
 
-struct Resolution<'a> {
-    // Storage for the result of the resolution `Res`. Populated by the
-    // callback handler, and taken by the Future::poll impl.
-    complete: Option<Res>,
+/// State of a pending resolution.
+///
+/// Allocated from the global heap, so that the resolver can keep a pointer to it regardless of
+/// the pools that the caller uses. Completes with the resolver context holding the results.
+struct Resolution {
+    // Set by the callback handler when the resolution is completed.
+    done: bool,
     // Storage for a pending Waker. Populated by the Future::poll impl,
     // and taken by the callback handler.
     waker: Option<Waker>,
-    // Pool used for allocating `Vec<ngx_addr_t>` contents in `Res`. Read by
-    // the callback handler.
-    pool: &'a Pool,
-    // Owned pointer to the ngx_resolver_ctx_t.
+    // Owned pointer to the ngx_resolver_ctx_t. Dropping it cancels the pending resolution, and
+    // must happen before the name and the service are freed.
     ctx: Option<ResolverCtx>,
+    // Copies of the name and the service referenced by the context, as the resolver modifies
+    // them in place.
+    name: vec::Vec<u8>,
+    service: vec::Vec<u8>,
 }
 
-impl<'a> Resolution<'a> {
-    pub fn new(
-        name: &ngx_str_t,
-        service: &ngx_str_t,
-        resolver: &Resolver,
-        pool: &'a Pool,
-    ) -> Result<Pin<Box<Self, Pool>>, Error> {
-        // Create a pinned Resolution on the Pool, so that we can make
-        // a stable pointer to the Resolution struct.
-        let mut this =
-            Box::pin_in(Resolution { complete: None, waker: None, pool, ctx: None }, pool.clone());
+impl Resolution {
+    fn new(name: &[u8], service: &[u8], resolver: &Resolver) -> Result<Box<Self>, Error> {
+        let copy = |data: &[u8]| {
+            let mut v = vec::Vec::new();
+            v.try_reserve_exact(data.len()).map_err(|_| Error::AllocationFailed)?;
+            v.extend_from_slice(data);
+            Ok::<_, Error>(v)
+        };
+
+        // Box the Resolution, so that we can make a stable pointer to it.
+        let mut this = Box::new(Resolution {
+            done: false,
+            waker: None,
+            ctx: None,
+            name: copy(name)?,
+            service: copy(service)?,
+        });
 
         // Set up the ctx with everything the resolver needs to resolve a
         // name, and the handler callback which is called on completion.
         let mut ctx = ResolverCtx::new(resolver.resolver)?;
-        ctx.name = *name;
-        ctx.service = *service;
+        ctx.name = ngx_str_t { len: this.name.len(), data: this.name.as_mut_ptr() };
+        ctx.service = ngx_str_t { len: this.service.len(), data: this.service.as_mut_ptr() };
         ctx.timeout = resolver.timeout;
         ctx.set_cancelable(1);
         ctx.handler = Some(Self::handler);
-
-        {
-            // Safety: Self::handler, Future::poll, and Drop::drop will have
-            // access to &mut Resolution. Nginx is single-threaded and we are
-            // assured only one of those is on the stack at a time, except if
-            // Self::handler wakes a task which polls or drops the Future,
-            // which it only does after use of &mut Resolution is complete.
-            let ptr: &mut Resolution = unsafe { Pin::into_inner_unchecked(this.as_mut()) };
-            ctx.data = ptr as *mut Resolution as *mut c_void;
-        }
+        // Safety: Self::handler, Future::poll, and Drop::drop will have
+        // access to &mut Resolution. Nginx is single-threaded and we are
+        // assured only one of those is on the stack at a time, except if
+        // Self::handler wakes a task which polls or drops the Future,
+        // which it only does after use of &mut Resolution is complete.
+        ctx.data = core::ptr::from_mut::<Resolution>(&mut this).cast::<c_void>();
 
         // Neither ownership nor borrows are tracked for this pointer,
         // and ctxp will not be used after the ctx destruction.
@@ -204,9 +257,7 @@ impl<'a> Resolution<'a> {
         let mut data = unsafe { NonNull::new_unchecked((*ctx).data as *mut Resolution) };
         let this: &mut Resolution = unsafe { data.as_mut() };
 
-        if let Some(ctx) = this.ctx.take() {
-            this.complete = Some(ctx.into_result(this.pool));
-        }
+        this.done = true;
 
         // Wake last, after all use of &mut Resolution, because wake may
         // poll Resolution future on current stack.
@@ -216,28 +267,27 @@ impl<'a> Resolution<'a> {
     }
 }
 
-impl core::future::Future for Resolution<'_> {
-    type Output = Result<Vec<ngx_addr_t, Pool>, Error>;
+impl core::future::Future for Resolution {
+    type Output = Result<ResolverCtx, Error>;
 
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         // Resolution is Unpin, so we can use it as just a &mut Resolution
         let this: &mut Resolution = self.get_mut();
 
-        // The handler populates this.complete, and we consume it here:
-        match this.complete.take() {
-            Some(res) => Poll::Ready(res),
+        // The handler marks the resolution as done, and we take the results here:
+        if this.done {
+            return Poll::Ready(this.ctx.take().ok_or(Error::Internal));
+        }
+
+        // If the handler has not yet fired, populate the waker field,
+        // which the handler will consume:
+        match &mut this.waker {
             None => {
-                // If the handler has not yet fired, populate the waker field,
-                // which the handler will consume:
-                match &mut this.waker {
-                    None => {
-                        this.waker = Some(cx.waker().clone());
-                    }
-                    Some(w) => w.clone_from(cx.waker()),
-                }
-                Poll::Pending
+                this.waker = Some(cx.waker().clone());
             }
+            Some(w) => w.clone_from(cx.waker()),
         }
+        Poll::Pending
     }
 }
 
@@ -286,33 +336,56 @@ impl ResolverCtx {
     /// Result<Vec<ngx_addr_t, Pool>, Error>, where the Vec and the internals
     /// of the ngx_addr_t are allocated on the given Pool
     pub fn into_result(self, pool: &Pool) -> Result<Vec<ngx_addr_t, Pool>, Error> {
-        if let Some(e) = NonZero::new(self.state) {
-            return Err(Error::Resolver(ResolverError::from(e), self.name.to_string()));
-        }
-        if self.addrs.is_null() {
-            Err(Error::AllocationFailed)?;
-        }
+        let addrs = self.addrs()?;
 
         let mut out = Vec::new_in(pool.clone());
+        out.try_reserve_exact(addrs.len()).map_err(|_| Error::AllocationFailed)?;
+
+        for addr in addrs {
+            out.push(copy_resolved_addr(addr, pool)?);
+        }
+
+        Ok(out)
+    }
+
+    /// Take the results in a ctx and convert them to socket addresses with the
+    /// specified port.
+    pub fn into_socket_addrs(self, port: u16) -> Result<vec::Vec<SocketAddr>, Error> {
+        let addrs = self.addrs()?;
 
-        if self.naddrs > 0 {
-            out.try_reserve_exact(self.naddrs).map_err(|_| Error::AllocationFailed)?;
+        let mut out = vec::Vec::new();
+        out.try_reserve_exact(addrs.len()).map_err(|_| Error::AllocationFailed)?;
 
-            for addr in unsafe { core::slice::from_raw_parts(self.addrs, self.naddrs) } {
-                out.push(copy_resolved_addr(addr, pool)?);
+        for addr in addrs {
+            // SAFETY: the resolver only returns AF_INET and AF_INET6 addresses.
+            if let Some(mut sa) = unsafe { sockaddr_to_socket_addr(addr.sockaddr) } {
+                sa.set_port(port);
+                out.push(sa);
             }
         }
 
         Ok(out)
     }
+
+    /// Returns the resolved addresses of a completed ctx, or the resolution error.
+    fn addrs(&self) -> Result<&[ngx_resolver_addr_t], Error> {
+        if let Some(e) = NonZero::new(self.state) {
+            return Err(Error::Resolver(ResolverError::from(e), self.name.to_string()));
+        }
+        if self.addrs.is_null() {
+            Err(Error::AllocationFailed)?;
+        }
+        if self.naddrs == 0 {
+            return Ok(&[]);
+        }
+        // SAFETY: the resolver sets `addrs` to an array of `naddrs` addresses.
+        Ok(unsafe { core::slice::from_raw_parts(self.addrs, self.naddrs) })
+    }
 }
 
 /// Take the contents of an ngx_resolver_addr_t and make an owned copy as
 /// an ngx_addr_t, using the Pool for allocation of the internals.
-fn copy_resolved_addr(
-    addr: &nginx_sys::ngx_resolver_addr_t,
-    pool: &Pool,
-) -> Result<ngx_addr_t, Error> {
+fn copy_resolved_addr(addr: &ngx_resolver_addr_t, pool: &Pool) -> Result<ngx_addr_t, Error> {
     let sockaddr = pool.alloc(addr.socklen as usize) as *mut nginx_sys::sockaddr;
     if sockaddr.is_null() {
         Err(Error::AllocationFailed)?;
@@ -5,12 +5,13 @@ use core::ptr::NonNull;
 use core::task::{self, Poll};
 use core::time::Duration;
 
-use nginx_sys::{ngx_add_timer, ngx_del_timer, ngx_event_t, ngx_log_t, ngx_msec_int_t, ngx_msec_t};
+use nginx_sys::{ngx_event_t, ngx_log_t, ngx_msec_int_t, ngx_msec_t};
 use pin_project_lite::pin_project;
 
+use crate::core::{add_timer, del_timer};
 use crate::{ngx_container_of, ngx_log_debug};
 
-/// Maximum duration that can be achieved using [ngx_add_timer].
+/// Maximum duration that can be achieved using `ngx_add_timer`.
 const NGX_TIMER_DURATION_MAX: Duration = Duration::from_millis(ngx_msec_int_t::MAX as _);
 
 /// Puts the current task to sleep for at least the specified amount of time.
@@ -71,6 +72,62 @@ impl Future for Sleep {
     }
 }
 
+/// Creates a new [Interval] yielding every `period`, using the global logger for debug output.
+///
+/// The first tick completes after one period.
+#[inline]
+pub fn interval(period: Duration) -> Interval {
+    Interval::new(period, crate::log::ngx_cycle_log())
+}
+
+pin_project! {
+/// Recurring timer returned by [interval].
+///
+/// The timer is rearmed when a tick is consumed, so the ticks are never closer than `period`, but
+/// may drift if the task is not polled in time. The pending timer is cancelled when the interval
+/// is dropped.
+pub struct Interval {
+    #[pin]
+    timer: TimerEvent,
+    period: Duration,
+}
+}
+
+impl Interval {
+    /// Creates a new Interval with the specified period and logger for debug messages.
+    pub fn new(period: Duration, log: NonNull<ngx_log_t>) -> Self {
+        let timer = TimerEvent::new(log);
+        ngx_log_debug!(timer.event.log, "async: interval {period:?}");
+        Interval { timer, period }
+    }
+
+    /// Returns the period of the interval.
+    pub fn period(&self) -> Duration {
+        self.period
+    }
+
+    /// Waits until the next tick.
+    pub async fn tick(self: Pin<&mut Self>) {
+        let mut this = self;
+        core::future::poll_fn(|cx| this.as_mut().poll_tick(cx)).await
+    }
+
+    /// Polls for the next tick.
+    ///
+    /// Returns [Poll::Ready] once per period, arming the timer for the next one.
+    pub fn poll_tick(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<()> {
+        let msec = self.period.min(NGX_TIMER_DURATION_MAX).as_millis() as ngx_msec_t;
+        let mut this = self.project();
+        match this.timer.as_mut().poll_sleep(msec, cx) {
+            Poll::Ready(()) => {
+                this.timer.event.set_timedout(0); // rearm on the next poll
+                Poll::Ready(())
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
 struct TimerEvent {
     event: ngx_event_t,
     waker: Option<task::Waker>,
@@ -111,7 +168,7 @@ impl TimerEvent {
             }
             Poll::Pending
         } else {
-            unsafe { ngx_add_timer(&raw mut self.event, duration) };
+            unsafe { add_timer(&raw mut self.event, duration) };
             self.waker = Some(context.waker().clone());
             Poll::Pending
         }
@@ -129,7 +186,7 @@ impl TimerEvent {
 impl Drop for TimerEvent {
     fn drop(&mut self) {
         if self.event.timer_set() != 0 {
-            unsafe { ngx_del_timer(&raw mut self.event) };
+            unsafe { del_timer(&raw mut self.event) };
         }
     }
 }
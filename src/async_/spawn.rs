@@ -1,20 +1,27 @@
 use alloc::collections::vec_deque::VecDeque;
-use core::cell::UnsafeCell;
+use alloc::rc::Rc;
+use core::cell::{Cell, UnsafeCell};
+use core::fmt;
 use core::future::Future;
-use core::mem;
+use core::mem::{self, ManuallyDrop};
+use core::pin::Pin;
 use core::ptr::{self, NonNull};
+use core::sync::atomic::{AtomicUsize, Ordering};
+use core::task::{Context, Poll, Waker};
 
-pub use async_task::Task;
 use async_task::{Runnable, ScheduleInfo, WithInfo};
-use nginx_sys::{
-    ngx_del_timer, ngx_delete_posted_event, ngx_event_t, ngx_post_event, ngx_posted_next_events,
-};
+use nginx_sys::{ngx_delete_posted_event, ngx_event_t, ngx_post_event, ngx_posted_next_events};
 
+use crate::allocator::AllocError;
+use crate::core::{Pool, del_timer};
 use crate::log::ngx_cycle_log;
 use crate::{ngx_container_of, ngx_log_debug};
 
 static SCHEDULER: Scheduler = Scheduler::new();
 
+static TASKS_SPAWNED: AtomicUsize = AtomicUsize::new(0);
+static TASKS_ACTIVE: AtomicUsize = AtomicUsize::new(0);
+
 struct Scheduler(UnsafeCell<SchedulerInner>);
 
 // SAFETY: Scheduler must only be used from the main thread of a worker process.
@@ -32,6 +39,12 @@ impl Scheduler {
         let inner = unsafe { &mut *UnsafeCell::raw_get(&raw const self.0) };
         inner.send(runnable)
     }
+
+    pub fn queued(&self) -> usize {
+        // SAFETY: see `schedule`.
+        let inner = unsafe { &*UnsafeCell::raw_get(&raw const self.0) };
+        inner.queue.len()
+    }
 }
 
 #[repr(C)]
@@ -113,7 +126,7 @@ impl Drop for SchedulerInner {
         }
 
         if self.event.timer_set() != 0 {
-            unsafe { ngx_del_timer(&raw mut self.event) };
+            unsafe { del_timer(&raw mut self.event) };
         }
     }
 }
@@ -130,17 +143,381 @@ fn schedule(runnable: Runnable, _info: ScheduleInfo) {
     SCHEDULER.schedule(runnable);
 }
 
+/// A spawned task.
+///
+/// The task can be awaited to obtain the output of the future.
+///
+/// Dropping the task handle cancels the task, unless [`Task::detach_on_drop`] was enabled. This
+/// includes the handles stored in the memory allocated with [`Pool::allocate`]: the task is
+/// cancelled when the pool is destroyed, e.g. when the request is finalized while the task is
+/// still pending. A cancelled task will not be polled again, and its future is dropped on the next
+/// iteration of the event loop.
+///
+/// [`Pool::allocate`]: crate::core::Pool::allocate
+pub struct Task<T> {
+    task: Option<async_task::Task<T>>,
+    detach_on_drop: bool,
+}
+
+impl<T> Task<T> {
+    fn new(task: async_task::Task<T>) -> Self {
+        Self { task: Some(task), detach_on_drop: false }
+    }
+
+    /// Controls whether the task continues running in the background when the handle is dropped.
+    ///
+    /// By default, dropping the handle cancels the task.
+    pub fn detach_on_drop(&mut self, detach: bool) {
+        self.detach_on_drop = detach;
+    }
+
+    /// Detaches the task to let it keep running in the background.
+    pub fn detach(mut self) {
+        if let Some(task) = self.task.take() {
+            task.detach();
+        }
+    }
+
+    /// Cancels the task and waits for it to stop running.
+    ///
+    /// Returns the output of the task if it was completed before it could be cancelled.
+    pub async fn cancel(mut self) -> Option<T> {
+        self.task.take()?.cancel().await
+    }
+
+    /// Returns `true` if the task has completed or was cancelled.
+    pub fn is_finished(&self) -> bool {
+        self.task.as_ref().is_none_or(|task| task.is_finished())
+    }
+}
+
+impl<T> Future for Task<T> {
+    type Output = T;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match self.task.as_mut() {
+            Some(task) => Pin::new(task).poll(cx),
+            None => Poll::Pending,
+        }
+    }
+}
+
+impl<T> Drop for Task<T> {
+    fn drop(&mut self) {
+        if let Some(task) = self.task.take() {
+            if self.detach_on_drop {
+                task.detach();
+            }
+            // Dropping `async_task::Task` cancels the task.
+        }
+    }
+}
+
+impl<T> fmt::Debug for Task<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Task")
+            .field("finished", &self.is_finished())
+            .field("detach_on_drop", &self.detach_on_drop)
+            .finish()
+    }
+}
+
 /// Creates a new task running on the NGINX event loop.
 pub fn spawn<F, T>(future: F) -> Task<T>
+where
+    F: Future<Output = T> + 'static,
+    T: 'static,
+{
+    spawn_owned(None, future)
+}
+
+/// Creates a new task running on the NGINX event loop, on behalf of the request `r`.
+///
+/// The task is not bound to the request lifetime and must be cancelled or completed before the
+/// request is finalized, see [`Task`]. With the `task-registry` feature, the task is recorded with
+/// the connection and request numbers of `r`, and can be found in
+/// [`live_tasks`](crate::async_::live_tasks) if it outlives the request.
+#[cfg(ngx_feature = "http")]
+pub fn spawn_for<F, T>(r: &crate::http::Request, future: F) -> Task<T>
+where
+    F: Future<Output = T> + 'static,
+    T: 'static,
+{
+    spawn_owned(Some(TaskOwner::from_request(r)), future)
+}
+
+fn spawn_owned<F, T>(owner: Option<TaskOwner>, future: F) -> Task<T>
 where
     F: Future<Output = T> + 'static,
     T: 'static,
 {
     ngx_log_debug!(ngx_cycle_log().as_ptr(), "async: spawning new task");
+    let guard = ActiveTask::new(owner, core::any::type_name::<F>());
+    let future = async move {
+        let _guard = guard;
+        future.await
+    };
     let scheduler = WithInfo(schedule);
     // Safety: single threaded embedding takes care of send/sync requirements for future and
     // scheduler. Future and scheduler are both 'static.
     let (runnable, task) = unsafe { async_task::spawn_unchecked(future, scheduler) };
     runnable.schedule();
-    task
+    Task::new(task)
+}
+
+/// Creates a new task running on the NGINX event loop, with the future allocated from `pool`.
+///
+/// The future is stored in the pool memory instead of the global heap, which ties its lifetime to
+/// the pool: when the pool is destroyed, e.g. when the request is finalized, the future is dropped
+/// and the task completes with `None`. Otherwise, the task completes with the output of the
+/// future, and the future is dropped as soon as it is completed or the task is cancelled. Only a
+/// small task header remains allocated from the global heap.
+///
+/// # Safety
+///
+/// The pool must not be destroyed while the future is being polled: the future must not, for
+/// example, finalize the request if spawned in the request pool. Use [`spawn`] for such futures.
+///
+/// ```no_run
+/// # use ngx::async_::{sleep, spawn_in};
+/// # use ngx::http::Request;
+/// # fn handler(r: &mut Request) -> Result<(), ngx::allocator::AllocError> {
+/// let pool = r.pool();
+/// let task = unsafe {
+///     spawn_in(&pool, async {
+///         sleep(core::time::Duration::from_millis(100)).await;
+///     })?
+/// };
+/// task.detach();
+/// # Ok(())
+/// # }
+/// ```
+pub unsafe fn spawn_in<F, T>(pool: &Pool, future: F) -> Result<Task<Option<T>>, AllocError>
+where
+    F: Future<Output = T> + 'static,
+    T: 'static,
+{
+    let shared = Rc::new(PoolFutureState { alive: Cell::new(true), waker: Cell::new(None) });
+    let cell = pool.allocate(PoolFutureCell { future: ManuallyDrop::new(future), shared });
+    let cell = NonNull::new(cell).ok_or(AllocError)?;
+    // SAFETY: the cell is allocated above and is valid until the pool is destroyed.
+    let shared = unsafe { cell.as_ref() }.shared.clone();
+    Ok(spawn(PoolFuture { cell, shared }))
+}
+
+/// State of a pool-allocated future shared between the pool and the task.
+struct PoolFutureState {
+    /// The future is not yet completed or dropped.
+    alive: Cell<bool>,
+    waker: Cell<Option<Waker>>,
+}
+
+/// Pool-allocated future, dropped with the pool.
+struct PoolFutureCell<F> {
+    future: ManuallyDrop<F>,
+    shared: Rc<PoolFutureState>,
+}
+
+impl<F> PoolFutureCell<F> {
+    /// Drops the future, if not already dropped.
+    fn drop_future(&mut self) {
+        if self.shared.alive.replace(false) {
+            // SAFETY: the future is dropped only once, as guarded by `alive`.
+            unsafe { ManuallyDrop::drop(&mut self.future) };
+        }
+    }
+}
+
+impl<F> Drop for PoolFutureCell<F> {
+    fn drop(&mut self) {
+        self.drop_future();
+        // Let the task complete with `None`.
+        if let Some(waker) = self.shared.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+/// Task future referencing a [`PoolFutureCell`].
+struct PoolFuture<F> {
+    cell: NonNull<PoolFutureCell<F>>,
+    shared: Rc<PoolFutureState>,
+}
+
+impl<F: Future> Future for PoolFuture<F> {
+    type Output = Option<F::Output>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if !self.shared.alive.get() {
+            return Poll::Ready(None);
+        }
+
+        // SAFETY: the cell is valid while the future is alive, and the future is never moved out
+        // of the pool memory.
+        let cell = unsafe { &mut *self.cell.as_ptr() };
+        let future = unsafe { Pin::new_unchecked(&mut *cell.future) };
+
+        match future.poll(cx) {
+            Poll::Ready(output) => {
+                cell.drop_future();
+                self.shared.waker.take();
+                Poll::Ready(Some(output))
+            }
+            Poll::Pending => {
+                self.shared.waker.set(Some(cx.waker().clone()));
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl<F> Drop for PoolFuture<F> {
+    fn drop(&mut self) {
+        if self.shared.alive.get() {
+            // SAFETY: the cell is valid while the future is alive.
+            unsafe { (*self.cell.as_ptr()).drop_future() };
+        }
+    }
+}
+
+/// Request a task was spawned for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TaskOwner {
+    /// Connection number, as in the `*N` prefix of the error log messages.
+    pub connection: usize,
+    /// Number of requests received on the connection when the task was spawned.
+    pub request: usize,
+}
+
+impl TaskOwner {
+    /// Returns the owner identifying the request `r`.
+    #[cfg(ngx_feature = "http")]
+    pub fn from_request(r: &crate::http::Request) -> Self {
+        // SAFETY: the connection is valid for the lifetime of the request.
+        let c = unsafe { &*r.as_ref().connection };
+        Self { connection: c.number as usize, request: c.requests as usize }
+    }
+}
+
+/// Counts the task as active until the future is completed or dropped.
+struct ActiveTask {
+    #[cfg(feature = "task-registry")]
+    id: usize,
+}
+
+impl ActiveTask {
+    #[cfg_attr(not(feature = "task-registry"), allow(unused_variables))]
+    fn new(owner: Option<TaskOwner>, name: &'static str) -> Self {
+        TASKS_SPAWNED.fetch_add(1, Ordering::Relaxed);
+        TASKS_ACTIVE.fetch_add(1, Ordering::Relaxed);
+        Self {
+            #[cfg(feature = "task-registry")]
+            id: super::registry::register(owner, name),
+        }
+    }
+}
+
+impl Drop for ActiveTask {
+    fn drop(&mut self) {
+        TASKS_ACTIVE.fetch_sub(1, Ordering::Relaxed);
+        #[cfg(feature = "task-registry")]
+        super::registry::unregister(self.id);
+    }
+}
+
+/// Statistics of the async runtime in the current process.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RuntimeStats {
+    /// Number of tasks spawned since the process start.
+    pub spawned: usize,
+    /// Number of tasks not yet completed or cancelled.
+    pub active: usize,
+    /// Number of task wakeups waiting for the next event loop iteration.
+    pub queued: usize,
+}
+
+/// Returns the statistics of the async runtime.
+pub fn runtime_stats() -> RuntimeStats {
+    RuntimeStats {
+        spawned: TASKS_SPAWNED.load(Ordering::Relaxed),
+        active: TASKS_ACTIVE.load(Ordering::Relaxed),
+        queued: SCHEDULER.queued(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use alloc::rc::Rc;
+    use alloc::vec::Vec;
+    use core::cell::{Cell, RefCell};
+    use std::thread_local;
+
+    use super::*;
+
+    thread_local! {
+        static QUEUE: RefCell<Vec<Runnable>> = const { RefCell::new(Vec::new()) };
+    }
+
+    fn spawn_local<F>(future: F) -> Task<F::Output>
+    where
+        F: Future + 'static,
+    {
+        let schedule = |runnable| QUEUE.with(|q| q.borrow_mut().push(runnable));
+        let (runnable, task) = unsafe { async_task::spawn_unchecked(future, schedule) };
+        runnable.schedule();
+        Task::new(task)
+    }
+
+    fn run_pending() {
+        while let Some(runnable) = QUEUE.with(|q| q.borrow_mut().pop()) {
+            runnable.run();
+        }
+    }
+
+    struct DropFlag(Rc<Cell<bool>>);
+
+    impl Drop for DropFlag {
+        fn drop(&mut self) {
+            self.0.set(true);
+        }
+    }
+
+    #[test]
+    fn test_drop_cancels() {
+        let polled = Rc::new(Cell::new(false));
+        let dropped = Rc::new(Cell::new(false));
+
+        let task = spawn_local({
+            let polled = polled.clone();
+            let flag = DropFlag(dropped.clone());
+            async move {
+                let _flag = flag;
+                polled.set(true);
+            }
+        });
+
+        drop(task);
+        run_pending();
+
+        assert!(!polled.get());
+        assert!(dropped.get());
+    }
+
+    #[test]
+    fn test_detach_on_drop() {
+        let polled = Rc::new(Cell::new(false));
+
+        let mut task = spawn_local({
+            let polled = polled.clone();
+            async move { polled.set(true) }
+        });
+        task.detach_on_drop(true);
+
+        drop(task);
+        run_pending();
+
+        assert!(polled.get());
+    }
 }
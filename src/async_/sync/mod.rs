@@ -0,0 +1,44 @@
+//! Synchronization primitives for the tasks running on the NGINX event loop.
+//!
+//! The primitives hand values from the event handlers and C callbacks, e.g. the
+//! `post_subrequest` or the resolver handlers, to the tasks awaiting them. The tasks are woken
+//! through the async runtime: the wakeup is deferred with `ngx_post_event` and the task is
+//! polled on the current iteration of the event loop, never from within the callback.
+//!
+//! The primitives are not thread-safe and must be used from the main thread of a worker
+//! process.
+use alloc::rc::Rc;
+use core::cell::RefCell;
+use core::ffi::c_void;
+use core::task::{Context, Waker};
+
+pub mod mpsc;
+pub mod oneshot;
+pub mod singleflight;
+
+fn register(slot: &mut Option<Waker>, cx: &Context<'_>) {
+    match slot {
+        Some(w) => w.clone_from(cx.waker()),
+        None => *slot = Some(cx.waker().clone()),
+    }
+}
+
+fn wake(slot: &mut Option<Waker>) {
+    if let Some(waker) = slot.take() {
+        waker.wake();
+    }
+}
+
+/// Converts a shared state into a pointer suitable for the `data` argument of a C callback.
+fn into_raw<T>(inner: Rc<RefCell<T>>) -> *mut c_void {
+    Rc::into_raw(inner).cast_mut().cast()
+}
+
+/// Restores a shared state converted with [`into_raw`].
+///
+/// # Safety
+///
+/// The pointer must be obtained from [`into_raw`] with the same `T`, and must not be used again.
+unsafe fn from_raw<T>(ptr: *mut c_void) -> Rc<RefCell<T>> {
+    unsafe { Rc::from_raw(ptr.cast::<RefCell<T>>()) }
+}
@@ -0,0 +1,397 @@
+//! Bounded multi-producer, single-consumer channel.
+//!
+//! ```no_run
+//! # use ngx::async_::{spawn, sync::mpsc};
+//! async fn consume() {
+//!     let (tx, mut rx) = mpsc::channel::<u32>(16);
+//!
+//!     spawn(async move {
+//!         for i in 0..100 {
+//!             if tx.send(i).await.is_err() {
+//!                 break;
+//!             }
+//!         }
+//!     })
+//!     .detach();
+//!
+//!     while let Some(i) = rx.recv().await {
+//!         // ...
+//!     }
+//! }
+//! ```
+use alloc::collections::vec_deque::VecDeque;
+use alloc::rc::Rc;
+use core::cell::RefCell;
+use core::fmt;
+use core::future::poll_fn;
+use core::pin::Pin;
+use core::task::{Context, Poll, Waker};
+
+/// Error returned by [`Sender::send`] when the receiver is dropped.
+///
+/// The error contains the value that was not sent.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SendError<T>(pub T);
+
+impl<T> fmt::Display for SendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("channel closed")
+    }
+}
+
+impl<T: fmt::Debug> core::error::Error for SendError<T> {}
+
+/// Error returned by [`Sender::try_send`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TrySendError<T> {
+    /// The channel is full.
+    Full(T),
+    /// The receiver is dropped.
+    Closed(T),
+}
+
+impl<T> TrySendError<T> {
+    /// Returns the value that was not sent.
+    pub fn into_inner(self) -> T {
+        match self {
+            Self::Full(value) | Self::Closed(value) => value,
+        }
+    }
+}
+
+impl<T> fmt::Display for TrySendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Full(_) => f.write_str("channel full"),
+            Self::Closed(_) => f.write_str("channel closed"),
+        }
+    }
+}
+
+impl<T: fmt::Debug> core::error::Error for TrySendError<T> {}
+
+struct Inner<T> {
+    queue: VecDeque<T>,
+    capacity: usize,
+    senders: usize,
+    receiver: bool,
+    recv_waker: Option<Waker>,
+    /// Wakers of the pending sends, with the identifiers of the sends.
+    send_wakers: VecDeque<(usize, Waker)>,
+    next_send_id: usize,
+}
+
+impl<T> Inner<T> {
+    fn wake_sender(&mut self) {
+        if let Some((_, waker)) = self.send_wakers.pop_front() {
+            waker.wake();
+        }
+    }
+}
+
+/// Registration of a pending [`Sender::send`] in the queue of the waiting senders.
+///
+/// Dropping a cancelled send removes its waker, or passes the wakeup on to the next sender if the
+/// send was already woken, so that the space in the channel is not left unnoticed.
+struct SendWaiter<'a, T> {
+    inner: &'a RefCell<Inner<T>>,
+    id: Option<usize>,
+}
+
+impl<T> SendWaiter<'_, T> {
+    fn register(&mut self, inner: &mut Inner<T>, waker: &Waker) {
+        if let Some(id) = self.id {
+            if let Some((_, w)) = inner.send_wakers.iter_mut().find(|(x, _)| *x == id) {
+                if !w.will_wake(waker) {
+                    *w = waker.clone();
+                }
+                return;
+            }
+        }
+
+        let id = inner.next_send_id;
+        inner.next_send_id = id.wrapping_add(1);
+        inner.send_wakers.push_back((id, waker.clone()));
+        self.id = Some(id);
+    }
+
+    /// Removes the waker of the completed send.
+    fn finish(&mut self) {
+        if let Some(id) = self.id.take() {
+            self.inner.borrow_mut().send_wakers.retain(|(x, _)| *x != id);
+        }
+    }
+}
+
+impl<T> Drop for SendWaiter<'_, T> {
+    fn drop(&mut self) {
+        let Some(id) = self.id else {
+            return;
+        };
+        let mut inner = self.inner.borrow_mut();
+        match inner.send_wakers.iter().position(|(x, _)| *x == id) {
+            Some(i) => {
+                inner.send_wakers.remove(i);
+            }
+            None => inner.wake_sender(),
+        }
+    }
+}
+
+/// Creates a channel buffering up to `capacity` values.
+///
+/// # Panics
+///
+/// Panics if `capacity` is zero.
+pub fn channel<T>(capacity: usize) -> (Sender<T>, Receiver<T>) {
+    assert!(capacity > 0, "channel capacity must be positive");
+    let inner = Rc::new(RefCell::new(Inner {
+        queue: VecDeque::with_capacity(capacity),
+        capacity,
+        senders: 1,
+        receiver: true,
+        recv_waker: None,
+        send_wakers: VecDeque::new(),
+        next_send_id: 0,
+    }));
+    (Sender(inner.clone()), Receiver(inner))
+}
+
+/// Sending half of a [`channel`].
+///
+/// The sender can be cloned to send from several tasks or callbacks.
+pub struct Sender<T>(Rc<RefCell<Inner<T>>>);
+
+impl<T> Sender<T> {
+    /// Attempts to send the value without waiting.
+    pub fn try_send(&self, value: T) -> Result<(), TrySendError<T>> {
+        let mut inner = self.0.borrow_mut();
+        if !inner.receiver {
+            return Err(TrySendError::Closed(value));
+        }
+        if inner.queue.len() >= inner.capacity {
+            return Err(TrySendError::Full(value));
+        }
+        inner.queue.push_back(value);
+        super::wake(&mut inner.recv_waker);
+        Ok(())
+    }
+
+    /// Sends the value, waiting for the space in the channel.
+    ///
+    /// The waiting sends are woken in the order of arrival. Returns the value back if the receiver
+    /// is dropped.
+    pub async fn send(&self, value: T) -> Result<(), SendError<T>> {
+        let mut value = Some(value);
+        let mut waiter = SendWaiter { inner: &self.0, id: None };
+        poll_fn(|cx| {
+            let v = value.take().expect("polled after completion");
+            let rc = match self.try_send(v) {
+                Ok(()) => Ok(()),
+                Err(TrySendError::Closed(v)) => Err(SendError(v)),
+                Err(TrySendError::Full(v)) => {
+                    value = Some(v);
+                    waiter.register(&mut self.0.borrow_mut(), cx.waker());
+                    return Poll::Pending;
+                }
+            };
+            waiter.finish();
+            Poll::Ready(rc)
+        })
+        .await
+    }
+
+    /// Returns `true` if the receiver is dropped.
+    pub fn is_closed(&self) -> bool {
+        !self.0.borrow().receiver
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        self.0.borrow_mut().senders += 1;
+        Self(self.0.clone())
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let mut inner = self.0.borrow_mut();
+        inner.senders -= 1;
+        if inner.senders == 0 {
+            super::wake(&mut inner.recv_waker);
+        }
+    }
+}
+
+/// Receiving half of a [`channel`].
+pub struct Receiver<T>(Rc<RefCell<Inner<T>>>);
+
+impl<T> Receiver<T> {
+    /// Attempts to receive a value without waiting.
+    pub fn try_recv(&mut self) -> Option<T> {
+        let mut inner = self.0.borrow_mut();
+        let value = inner.queue.pop_front()?;
+        inner.wake_sender();
+        Some(value)
+    }
+
+    /// Polls for the next value.
+    ///
+    /// Returns `Poll::Ready(None)` once all the senders are dropped and the channel is empty.
+    pub fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        if let Some(value) = self.try_recv() {
+            return Poll::Ready(Some(value));
+        }
+        let mut inner = self.0.borrow_mut();
+        if inner.senders == 0 {
+            return Poll::Ready(None);
+        }
+        super::register(&mut inner.recv_waker, cx);
+        Poll::Pending
+    }
+
+    /// Receives the next value.
+    ///
+    /// Returns `None` once all the senders are dropped and the channel is empty.
+    pub async fn recv(&mut self) -> Option<T> {
+        poll_fn(|cx| self.poll_recv(cx)).await
+    }
+
+    /// Returns the number of values in the channel.
+    pub fn len(&self) -> usize {
+        self.0.borrow().queue.len()
+    }
+
+    /// Returns `true` if the channel is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<T> futures_core::Stream for Receiver<T> {
+    type Item = T;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        self.get_mut().poll_recv(cx)
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        let mut inner = self.0.borrow_mut();
+        inner.receiver = false;
+        inner.recv_waker = None;
+        // Let the waiting senders observe the closed channel.
+        for (_, waker) in inner.send_wakers.drain(..) {
+            waker.wake();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::boxed::Box;
+    use alloc::sync::Arc;
+    use alloc::task::Wake;
+    use core::future::Future;
+    use core::pin::pin;
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    fn poll<F: Future>(f: Pin<&mut F>) -> Poll<F::Output> {
+        f.poll(&mut Context::from_waker(Waker::noop()))
+    }
+
+    struct CountingWaker(AtomicUsize);
+
+    impl Wake for CountingWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn counting_waker() -> (Arc<CountingWaker>, Waker) {
+        let counter = Arc::new(CountingWaker(AtomicUsize::new(0)));
+        (counter.clone(), Waker::from(counter))
+    }
+
+    #[test]
+    fn test_try_send() {
+        let (tx, mut rx) = channel(2);
+        assert_eq!(tx.try_send(1), Ok(()));
+        assert_eq!(tx.try_send(2), Ok(()));
+        assert_eq!(tx.try_send(3), Err(TrySendError::Full(3)));
+        assert_eq!(rx.len(), 2);
+        assert_eq!(rx.try_recv(), Some(1));
+        assert_eq!(rx.try_recv(), Some(2));
+        assert_eq!(rx.try_recv(), None);
+    }
+
+    #[test]
+    fn test_send_waits() {
+        let (tx, mut rx) = channel(1);
+        assert_eq!(tx.try_send(1), Ok(()));
+
+        let mut send = pin!(tx.send(2));
+        assert_eq!(poll(send.as_mut()), Poll::Pending);
+        assert_eq!(rx.try_recv(), Some(1));
+        assert_eq!(poll(send.as_mut()), Poll::Ready(Ok(())));
+        assert_eq!(rx.try_recv(), Some(2));
+    }
+
+    #[test]
+    fn test_send_cancelled() {
+        let (tx, mut rx) = channel(1);
+        assert_eq!(tx.try_send(0), Ok(()));
+
+        let (a, waker_a) = counting_waker();
+        let (b, waker_b) = counting_waker();
+        let mut cx_b = Context::from_waker(&waker_b);
+
+        // the cancelled send is removed from the queue
+        let mut send_a = Box::pin(tx.send(1));
+        assert!(send_a.as_mut().poll(&mut Context::from_waker(&waker_a)).is_pending());
+        let mut send_b = pin!(tx.send(2));
+        assert!(send_b.as_mut().poll(&mut cx_b).is_pending());
+        drop(send_a);
+
+        assert_eq!(rx.try_recv(), Some(0));
+        assert_eq!(a.0.load(Ordering::Relaxed), 0);
+        assert_eq!(b.0.load(Ordering::Relaxed), 1);
+        assert_eq!(send_b.as_mut().poll(&mut cx_b), Poll::Ready(Ok(())));
+
+        // the wakeup of the cancelled send is passed on
+        let mut send_a = Box::pin(tx.send(3));
+        assert!(send_a.as_mut().poll(&mut Context::from_waker(&waker_a)).is_pending());
+        let mut send_b = pin!(tx.send(4));
+        assert!(send_b.as_mut().poll(&mut cx_b).is_pending());
+
+        assert_eq!(rx.try_recv(), Some(2));
+        assert_eq!(a.0.load(Ordering::Relaxed), 1);
+        drop(send_a);
+        assert_eq!(b.0.load(Ordering::Relaxed), 2);
+        assert_eq!(send_b.as_mut().poll(&mut cx_b), Poll::Ready(Ok(())));
+        assert_eq!(rx.try_recv(), Some(4));
+        assert!(tx.0.borrow().send_wakers.is_empty());
+    }
+
+    #[test]
+    fn test_closed() {
+        let (tx, mut rx) = channel(1);
+        let tx2 = tx.clone();
+        assert_eq!(tx.try_send(1), Ok(()));
+        drop(tx);
+        drop(tx2);
+
+        let mut cx = Context::from_waker(Waker::noop());
+        assert_eq!(rx.poll_recv(&mut cx), Poll::Ready(Some(1)));
+        assert_eq!(rx.poll_recv(&mut cx), Poll::Ready(None));
+
+        let (tx, rx) = channel(1);
+        drop(rx);
+        assert!(tx.is_closed());
+        assert_eq!(tx.try_send(1), Err(TrySendError::Closed(1)));
+    }
+}
@@ -0,0 +1,190 @@
+//! Channel for sending a single value to a task.
+//!
+//! ```no_run
+//! # use core::ffi::c_void;
+//! # use ngx::async_::sync::oneshot;
+//! # use ngx::ffi::{ngx_http_request_t, ngx_int_t};
+//! unsafe extern "C" fn post_subrequest(
+//!     r: *mut ngx_http_request_t,
+//!     data: *mut c_void,
+//!     rc: ngx_int_t,
+//! ) -> ngx_int_t {
+//!     // SAFETY: `data` is the sender converted with `into_raw` below.
+//!     let tx = unsafe { oneshot::Sender::<ngx_int_t>::from_raw(data) };
+//!     let _ = tx.send(rc);
+//!     rc
+//! }
+//!
+//! async fn wait() {
+//!     let (tx, rx) = oneshot::channel::<ngx_int_t>();
+//!     let data = tx.into_raw();
+//!     // ... pass `post_subrequest` and `data` to the subrequest
+//!     let rc = rx.await;
+//! }
+//! ```
+use alloc::rc::Rc;
+use core::cell::RefCell;
+use core::ffi::c_void;
+use core::fmt;
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll, Waker};
+
+/// Error returned by the [`Receiver`] when the [`Sender`] is dropped without sending a value.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RecvError;
+
+impl fmt::Display for RecvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("channel closed")
+    }
+}
+
+impl core::error::Error for RecvError {}
+
+struct Inner<T> {
+    value: Option<T>,
+    waker: Option<Waker>,
+    sender: bool,
+    receiver: bool,
+}
+
+/// Creates a channel for a single value.
+pub fn channel<T>() -> (Sender<T>, Receiver<T>) {
+    let inner =
+        Rc::new(RefCell::new(Inner { value: None, waker: None, sender: true, receiver: true }));
+    (Sender(inner.clone()), Receiver(inner))
+}
+
+/// Sending half of a [`channel`].
+pub struct Sender<T>(Rc<RefCell<Inner<T>>>);
+
+impl<T> Sender<T> {
+    /// Sends the value, waking the receiving task.
+    ///
+    /// Returns the value back if the receiver is dropped.
+    pub fn send(self, value: T) -> Result<(), T> {
+        let mut inner = self.0.borrow_mut();
+        if !inner.receiver {
+            return Err(value);
+        }
+        inner.value = Some(value);
+        super::wake(&mut inner.waker);
+        Ok(())
+    }
+
+    /// Returns `true` if the receiver is dropped.
+    pub fn is_closed(&self) -> bool {
+        !self.0.borrow().receiver
+    }
+
+    /// Converts the sender into a pointer, e.g. for the `data` argument of a C callback.
+    ///
+    /// The pointer must be converted back with [`Sender::from_raw`] exactly once, or the
+    /// receiver will wait forever.
+    pub fn into_raw(self) -> *mut c_void {
+        let this = core::mem::ManuallyDrop::new(self);
+        // SAFETY: the value is not dropped, and the Rc is moved out exactly once.
+        super::into_raw(unsafe { core::ptr::read(&this.0) })
+    }
+
+    /// Restores a sender converted with [`Sender::into_raw`].
+    ///
+    /// # Safety
+    ///
+    /// The pointer must be obtained from [`Sender::into_raw`] for the same `T`, and must not be
+    /// used again.
+    pub unsafe fn from_raw(ptr: *mut c_void) -> Self {
+        Self(unsafe { super::from_raw(ptr) })
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let mut inner = self.0.borrow_mut();
+        inner.sender = false;
+        super::wake(&mut inner.waker);
+    }
+}
+
+/// Receiving half of a [`channel`].
+///
+/// The receiver is a future resolving to the sent value.
+pub struct Receiver<T>(Rc<RefCell<Inner<T>>>);
+
+impl<T> Receiver<T> {
+    /// Returns the value if it is already sent.
+    pub fn try_recv(&mut self) -> Option<T> {
+        self.0.borrow_mut().value.take()
+    }
+}
+
+impl<T> Future for Receiver<T> {
+    type Output = Result<T, RecvError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut inner = self.0.borrow_mut();
+        if let Some(value) = inner.value.take() {
+            return Poll::Ready(Ok(value));
+        }
+        if !inner.sender {
+            return Poll::Ready(Err(RecvError));
+        }
+        super::register(&mut inner.waker, cx);
+        Poll::Pending
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        let mut inner = self.0.borrow_mut();
+        inner.receiver = false;
+        inner.waker = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::pin::pin;
+
+    use super::*;
+
+    fn poll<F: Future>(f: Pin<&mut F>) -> Poll<F::Output> {
+        f.poll(&mut Context::from_waker(Waker::noop()))
+    }
+
+    #[test]
+    fn test_send() {
+        let (tx, rx) = channel();
+        let mut rx = pin!(rx);
+        assert_eq!(poll(rx.as_mut()), Poll::Pending);
+        assert_eq!(tx.send(1), Ok(()));
+        assert_eq!(poll(rx.as_mut()), Poll::Ready(Ok(1)));
+    }
+
+    #[test]
+    fn test_sender_dropped() {
+        let (tx, rx) = channel::<u32>();
+        let mut rx = pin!(rx);
+        assert_eq!(poll(rx.as_mut()), Poll::Pending);
+        drop(tx);
+        assert_eq!(poll(rx.as_mut()), Poll::Ready(Err(RecvError)));
+    }
+
+    #[test]
+    fn test_receiver_dropped() {
+        let (tx, rx) = channel();
+        drop(rx);
+        assert!(tx.is_closed());
+        assert_eq!(tx.send(1), Err(1));
+    }
+
+    #[test]
+    fn test_raw() {
+        let (tx, mut rx) = channel();
+        let ptr = tx.into_raw();
+        let tx = unsafe { Sender::from_raw(ptr) };
+        assert_eq!(tx.send(1), Ok(()));
+        assert_eq!(rx.try_recv(), Some(1));
+    }
+}
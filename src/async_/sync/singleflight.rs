@@ -0,0 +1,213 @@
+//! Coalescing of the concurrent operations with the same key.
+//!
+//! A [`Group`] runs one operation per key at a time: the first task calling [`Group::run`] for a
+//! key starts the operation, and the tasks calling it for the same key while the operation is in
+//! flight wait for its result instead of starting their own. This is the usual way to avoid the
+//! thundering herd on a cache miss, when many requests need the same value from an upstream or a
+//! slow computation.
+//!
+//! ```no_run
+//! # extern crate alloc;
+//! # use alloc::rc::Rc;
+//! # use ngx::async_::sync::singleflight::Group;
+//! # async fn fetch(key: &str) -> Result<Rc<[u8]>, u16> { Ok(Rc::from(&b""[..])) }
+//! type Fills = Group<String, Result<Rc<[u8]>, u16>>;
+//!
+//! // The group is shared by the tasks of the worker process, e.g. created in `init_process`.
+//! async fn cache_fill(fills: &Fills, key: String) -> Result<Rc<[u8]>, u16> {
+//!     fills.run(key.clone(), || async move { fetch(&key).await }).await
+//! }
+//! ```
+//!
+//! The result is cloned for each waiting task, so large values should be shared, e.g. with
+//! [`Rc`](alloc::rc::Rc).
+//!
+//! If the task running the operation is dropped before completion, e.g. when the client closes
+//! the connection, one of the waiting tasks starts the operation again with its own closure.
+//!
+//! The group only coalesces the operations within a worker process. The workers can avoid the
+//! duplicate work by storing the results in a shared zone and checking it before calling
+//! [`Group::run`].
+use alloc::collections::btree_map::BTreeMap;
+use alloc::rc::Rc;
+use alloc::vec::Vec;
+use core::borrow::Borrow;
+use core::cell::RefCell;
+use core::future::{Future, poll_fn};
+use core::task::{Poll, Waker};
+
+enum Call<V> {
+    /// The operation is in flight, with the tasks waiting for the result.
+    Running(Vec<Waker>),
+    /// The operation has completed.
+    Done(V),
+    /// The task running the operation was dropped.
+    Abandoned,
+}
+
+type CallRef<V> = Rc<RefCell<Call<V>>>;
+
+/// Set of the in-flight operations, keyed by `K` and producing `V`.
+pub struct Group<K, V> {
+    calls: RefCell<BTreeMap<K, CallRef<V>>>,
+}
+
+impl<K, V> Default for Group<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V> Group<K, V> {
+    /// Creates an empty group.
+    pub const fn new() -> Self {
+        Self { calls: RefCell::new(BTreeMap::new()) }
+    }
+
+    /// Returns the number of the in-flight operations.
+    pub fn len(&self) -> usize {
+        self.calls.borrow().len()
+    }
+
+    /// Returns `true` if no operation is in flight.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<K: Ord + Clone, V: Clone> Group<K, V> {
+    /// Returns `true` if an operation for the `key` is in flight.
+    pub fn in_flight<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.calls.borrow().contains_key(key)
+    }
+
+    /// Runs the operation returned by `f` for the `key`, or waits for the result of the operation
+    /// already in flight for the same key.
+    ///
+    /// The closure is only called if the task starts the operation.
+    pub async fn run<F, Fut>(&self, key: K, f: F) -> V
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = V>,
+    {
+        loop {
+            let call = self.calls.borrow().get(&key).cloned();
+
+            if let Some(call) = call {
+                if let Some(value) = wait(&call).await {
+                    return value;
+                }
+                // The running task was dropped; retry and possibly start the operation.
+                continue;
+            }
+
+            let call = Rc::new(RefCell::new(Call::Running(Vec::new())));
+            self.calls.borrow_mut().insert(key.clone(), call.clone());
+
+            let mut guard = Leader { group: self, key: &key, call: &call, done: false };
+            let value = f().await;
+            guard.complete(Call::Done(value.clone()));
+            return value;
+        }
+    }
+}
+
+/// Waits for the call to complete, returning `None` if it was abandoned.
+async fn wait<V: Clone>(call: &RefCell<Call<V>>) -> Option<V> {
+    poll_fn(|cx| match &mut *call.borrow_mut() {
+        Call::Running(wakers) => {
+            if !wakers.iter().any(|w| w.will_wake(cx.waker())) {
+                wakers.push(cx.waker().clone());
+            }
+            Poll::Pending
+        }
+        Call::Done(value) => Poll::Ready(Some(value.clone())),
+        Call::Abandoned => Poll::Ready(None),
+    })
+    .await
+}
+
+/// Completes the call when the operation finishes or the running task is dropped.
+struct Leader<'a, K: Ord, V> {
+    group: &'a Group<K, V>,
+    key: &'a K,
+    call: &'a CallRef<V>,
+    done: bool,
+}
+
+impl<K: Ord, V> Leader<'_, K, V> {
+    fn complete(&mut self, state: Call<V>) {
+        self.done = true;
+
+        let mut calls = self.group.calls.borrow_mut();
+        if calls.get(self.key).is_some_and(|c| Rc::ptr_eq(c, self.call)) {
+            calls.remove(self.key);
+        }
+        drop(calls);
+
+        let prev = core::mem::replace(&mut *self.call.borrow_mut(), state);
+        if let Call::Running(wakers) = prev {
+            wakers.into_iter().for_each(Waker::wake);
+        }
+    }
+}
+
+impl<K: Ord, V> Drop for Leader<'_, K, V> {
+    fn drop(&mut self) {
+        if !self.done {
+            self.complete(Call::Abandoned);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::pin::{Pin, pin};
+    use core::task::Context;
+
+    use super::super::oneshot;
+    use super::*;
+
+    fn poll<F: Future>(f: Pin<&mut F>) -> Poll<F::Output> {
+        f.poll(&mut Context::from_waker(Waker::noop()))
+    }
+
+    #[test]
+    fn test_coalesce() {
+        let group = Group::<u32, u32>::new();
+        let (tx, rx) = oneshot::channel();
+
+        let mut first = pin!(group.run(1, || async move { rx.await.unwrap() }));
+        let mut second = pin!(group.run(1, || async { 0 }));
+        let mut other = pin!(group.run(2, || async { 20 }));
+
+        assert_eq!(poll(first.as_mut()), Poll::Pending);
+        assert_eq!(poll(second.as_mut()), Poll::Pending);
+        assert!(group.in_flight(&1));
+        assert_eq!(poll(other.as_mut()), Poll::Ready(20));
+        assert_eq!(group.len(), 1);
+
+        tx.send(10).unwrap();
+        assert_eq!(poll(first.as_mut()), Poll::Ready(10));
+        assert_eq!(poll(second.as_mut()), Poll::Ready(10));
+        assert!(group.is_empty());
+    }
+
+    #[test]
+    fn test_abandoned() {
+        let group = Group::<u32, u32>::new();
+
+        let mut first = alloc::boxed::Box::pin(group.run(1, core::future::pending));
+        let mut second = pin!(group.run(1, || async { 2 }));
+
+        assert_eq!(poll(first.as_mut()), Poll::Pending);
+        assert_eq!(poll(second.as_mut()), Poll::Pending);
+        drop(first);
+        assert_eq!(poll(second.as_mut()), Poll::Ready(2));
+        assert!(group.is_empty());
+    }
+}
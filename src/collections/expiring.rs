@@ -0,0 +1,181 @@
+//! Map with per-entry expiration.
+//!
+//! The [ExpiringMap] stores a deadline with each entry, based on the cached nginx time, and is
+//! suitable for rate limiting state or session caches in shared zones.
+use core::borrow;
+use core::hash::Hash;
+use core::time::Duration;
+
+use crate::allocator::{AllocError, Allocator};
+use crate::collections::rbtree::{MapIter, RbTreeMap};
+use crate::core::time;
+use crate::ffi::time_t;
+
+/// Value with the expiration deadline.
+#[derive(Debug)]
+struct Expiring<V> {
+    deadline: time_t,
+    value: V,
+}
+
+impl<V> Expiring<V> {
+    fn is_live(&self, now: time_t) -> bool {
+        self.deadline > now
+    }
+}
+
+/// A [RbTreeMap] with the entries expiring after the time-to-live specified on insertion.
+///
+/// The deadlines are calculated from `ngx_time()`, so the resolution is one second. Expired entries
+/// are never returned: they are removed lazily when accessed with a mutable reference, and can be
+/// removed in bulk with [sweep](Self::sweep), e.g. from a [Timer](crate::core::Timer):
+///
+/// ```no_run
+/// # use core::time::Duration;
+/// # use ngx::collections::ExpiringMap;
+/// # use ngx::core::{SharedZone, Timer};
+/// # use ngx::sync::RwLock;
+/// # type Map = RwLock<ExpiringMap<u64, u64, ngx::core::SlabPool>>;
+/// # fn start(zone: SharedZone<Map>) {
+/// let timer = Timer::new(ngx::log::ngx_cycle_log(), move || {
+///     if let Some(map) = zone.get() {
+///         map.write().sweep();
+///     }
+///     Some(Duration::from_secs(10))
+/// });
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct ExpiringMap<K, V, A>
+where
+    A: Allocator,
+{
+    map: RbTreeMap<K, Expiring<V>, A>,
+}
+
+impl<K, V, A> ExpiringMap<K, V, A>
+where
+    A: Allocator,
+{
+    /// Returns a reference to the underlying allocator.
+    pub fn allocator(&self) -> &A {
+        self.map.allocator()
+    }
+
+    /// Returns true if the map contains no entries, including the expired ones.
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// Returns an iterator over the entries that are not expired.
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> + '_ {
+        let now = time();
+        let iter: MapIter<'_, K, Expiring<V>> = self.map.iter();
+        iter.filter(move |(_, v)| v.is_live(now)).map(|(k, v)| (k, &v.value))
+    }
+
+    /// Clears the map, removing all entries.
+    pub fn clear(&mut self) {
+        self.map.clear()
+    }
+
+    /// Removes the expired entries, returning the number of removed entries.
+    pub fn sweep(&mut self) -> usize {
+        let now = time();
+        let mut removed = 0;
+        self.map.retain(|_, v| {
+            let live = v.is_live(now);
+            removed += usize::from(!live);
+            live
+        });
+        removed
+    }
+}
+
+impl<K, V, A> ExpiringMap<K, V, A>
+where
+    A: Allocator,
+    K: Hash + Ord,
+{
+    /// Attempts to create a new map with the specified allocator.
+    pub fn try_new_in(alloc: A) -> Result<Self, AllocError> {
+        Ok(Self { map: RbTreeMap::try_new_in(alloc)? })
+    }
+
+    /// Returns a reference to the value corresponding to the key, if not expired.
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: borrow::Borrow<Q>,
+        Q: Hash + Ord + ?Sized,
+    {
+        let entry = self.map.get(key)?;
+        entry.is_live(time()).then_some(&entry.value)
+    }
+
+    /// Returns a mutable reference to the value corresponding to the key, removing the entry if
+    /// expired.
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: borrow::Borrow<Q>,
+        Q: Hash + Ord + ?Sized,
+    {
+        if !self.map.get(key)?.is_live(time()) {
+            self.map.remove(key);
+            return None;
+        }
+        self.map.get_mut(key).map(|x| &mut x.value)
+    }
+
+    /// Returns the remaining time to live of the entry, if not expired.
+    pub fn ttl<Q>(&self, key: &Q) -> Option<Duration>
+    where
+        K: borrow::Borrow<Q>,
+        Q: Hash + Ord + ?Sized,
+    {
+        let entry = self.map.get(key)?;
+        let left = entry.deadline - time();
+        (left > 0).then(|| Duration::from_secs(left as u64))
+    }
+
+    /// Attempts to insert an entry expiring after `ttl`, replacing the existing one.
+    ///
+    /// The time to live is rounded up to whole seconds.
+    pub fn try_insert(&mut self, key: K, value: V, ttl: Duration) -> Result<&mut V, AllocError> {
+        let deadline = deadline(time(), ttl);
+        let entry = self.map.try_insert(key, Expiring { deadline, value })?;
+        Ok(&mut entry.value)
+    }
+
+    /// Extends the expiration of an existing entry to `ttl` from now.
+    ///
+    /// Returns `false` if the entry does not exist or has already expired.
+    pub fn touch<Q>(&mut self, key: &Q, ttl: Duration) -> bool
+    where
+        K: borrow::Borrow<Q>,
+        Q: Hash + Ord + ?Sized,
+    {
+        let now = time();
+        match self.map.get_mut(key) {
+            Some(entry) if entry.is_live(now) => {
+                entry.deadline = deadline(now, ttl);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Removes a key from the map, returning the value if the entry was not expired.
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: borrow::Borrow<Q>,
+        Q: Hash + Ord + ?Sized,
+    {
+        let entry = self.map.remove(key)?;
+        entry.is_live(time()).then_some(entry.value)
+    }
+}
+
+pub(crate) fn deadline(now: time_t, ttl: Duration) -> time_t {
+    let secs = ttl.as_secs() + u64::from(ttl.subsec_nanos() > 0);
+    now.saturating_add(time_t::try_from(secs).unwrap_or(time_t::MAX))
+}
@@ -9,8 +9,12 @@ pub use allocator_api2::{
     vec, // reexport both the module and the macro
     vec::Vec,
 };
+pub use expiring::ExpiringMap;
+pub use observed::{MapObserver, ObservedMap};
 pub use queue::Queue;
 pub use rbtree::RbTreeMap;
 
+pub mod expiring;
+pub mod observed;
 pub mod queue;
 pub mod rbtree;
@@ -0,0 +1,154 @@
+//! Map wrapper reporting the mutations to an observer.
+//!
+//! The [ObservedMap] allows a module to follow the changes of a shared zone, e.g. to replicate
+//! them to another instance or to write them to a log before the zone is lost.
+use core::borrow;
+use core::hash::Hash;
+
+use crate::allocator::{AllocError, Allocator};
+use crate::collections::rbtree::{MapIter, RbTreeMap};
+
+/// Observer for the mutations of an [ObservedMap].
+///
+/// The methods are invoked after the corresponding change is applied, in the context of the
+/// mutating call; when the map is in a shared zone, that is with the zone lock held. All the
+/// methods do nothing by default.
+pub trait MapObserver<K, V> {
+    /// Called when `key` is inserted or its value is replaced or modified.
+    fn inserted(&mut self, key: &K, value: &V) {
+        let _ = (key, value);
+    }
+
+    /// Called when `key` is removed.
+    fn removed(&mut self, key: &K, value: &V) {
+        let _ = (key, value);
+    }
+
+    /// Called when all the entries are removed.
+    fn cleared(&mut self) {}
+}
+
+impl<K, V> MapObserver<K, V> for () {}
+
+/// A [RbTreeMap] that reports each mutation to a [MapObserver].
+///
+/// Only the read access to the underlying map is exposed, so the observer sees every change.
+#[derive(Debug)]
+pub struct ObservedMap<K, V, A, O>
+where
+    A: Allocator,
+{
+    map: RbTreeMap<K, V, A>,
+    observer: O,
+}
+
+impl<K, V, A, O> ObservedMap<K, V, A, O>
+where
+    A: Allocator,
+    O: MapObserver<K, V>,
+{
+    /// Wraps an existing map.
+    pub fn new(map: RbTreeMap<K, V, A>, observer: O) -> Self {
+        Self { map, observer }
+    }
+
+    /// Returns the underlying map.
+    pub fn as_map(&self) -> &RbTreeMap<K, V, A> {
+        &self.map
+    }
+
+    /// Returns a reference to the observer.
+    pub fn observer(&self) -> &O {
+        &self.observer
+    }
+
+    /// Returns a mutable reference to the observer.
+    pub fn observer_mut(&mut self) -> &mut O {
+        &mut self.observer
+    }
+
+    /// Returns the underlying map and the observer.
+    pub fn into_parts(self) -> (RbTreeMap<K, V, A>, O) {
+        (self.map, self.observer)
+    }
+
+    /// Returns true if the map contains no entries.
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// Returns an iterator over the entries of the map.
+    pub fn iter(&self) -> MapIter<'_, K, V> {
+        self.map.iter()
+    }
+
+    /// Clears the map, removing all elements.
+    pub fn clear(&mut self) {
+        self.map.clear();
+        self.observer.cleared();
+    }
+}
+
+impl<K, V, A, O> ObservedMap<K, V, A, O>
+where
+    A: Allocator,
+    K: Hash + Ord,
+    O: MapObserver<K, V>,
+{
+    /// Attempts to create a new map with specified allocator and observer.
+    pub fn try_new_in(alloc: A, observer: O) -> Result<Self, AllocError> {
+        Ok(Self::new(RbTreeMap::try_new_in(alloc)?, observer))
+    }
+
+    /// Returns a reference to the value corresponding to the key.
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: borrow::Borrow<Q>,
+        Q: Hash + Ord + ?Sized,
+    {
+        self.map.get(key)
+    }
+
+    /// Attempts to insert a new element into the map.
+    pub fn try_insert(&mut self, key: K, value: V) -> Result<&V, AllocError> {
+        let (key, value) = self.map.try_insert_entry(key, value)?;
+        self.observer.inserted(key, value);
+        Ok(value)
+    }
+
+    /// Modifies the value corresponding to the key with `f`.
+    ///
+    /// Returns `None` if the key is not in the map.
+    pub fn modify<Q, R>(&mut self, key: &Q, f: impl FnOnce(&mut V) -> R) -> Option<R>
+    where
+        K: borrow::Borrow<Q>,
+        Q: Hash + Ord + ?Sized,
+    {
+        let ret = f(self.map.get_mut(key)?);
+        let (key, value) = self.map.get_key_value(key)?;
+        self.observer.inserted(key, value);
+        Some(ret)
+    }
+
+    /// Removes a key from the map, returning the value at the key if the key was previously in the
+    /// map.
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: borrow::Borrow<Q>,
+        Q: Hash + Ord + ?Sized,
+    {
+        self.remove_entry(key).map(|(_, v)| v)
+    }
+
+    /// Removes a key from the map, returning the stored key and value if the key was previously in
+    /// the map.
+    pub fn remove_entry<Q>(&mut self, key: &Q) -> Option<(K, V)>
+    where
+        K: borrow::Borrow<Q>,
+        Q: Hash + Ord + ?Sized,
+    {
+        let (key, value) = self.map.remove_entry(key)?;
+        self.observer.removed(&key, &value);
+        Some((key, value))
+    }
+}
@@ -283,6 +283,30 @@ where
         self.tree.is_empty()
     }
 
+    /// Retains only the entries for which `f` returns `true`.
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&K, &mut V) -> bool,
+    {
+        // SAFETY: the iterator keeps a pointer to the next node, so the current one can be removed.
+        let iter = unsafe { NgxRbTreeIter::new(NonNull::from(&self.tree.inner)) };
+        let layout = Layout::new::<MapEntry<K, V>>();
+
+        for node in iter {
+            unsafe {
+                let mut data = MapEntry::<K, V>::from_rbtree_node(node);
+                let entry = data.as_mut();
+                if f(&entry.key, &mut entry.value) {
+                    continue;
+                }
+
+                ngx_rbtree_delete(&raw mut self.tree.inner, &raw mut entry.node);
+                ptr::drop_in_place(data.as_mut());
+                self.allocator().deallocate(data.cast(), layout)
+            }
+        }
+    }
+
     /// Returns an iterator over the entries of the tree.
     #[inline]
     pub fn iter(&self) -> MapIter<'_, K, V> {
@@ -326,6 +350,18 @@ where
         self.lookup(key).map(|x| unsafe { &x.as_ref().value })
     }
 
+    /// Returns the stored key and a reference to the value corresponding to the key.
+    pub fn get_key_value<Q>(&self, key: &Q) -> Option<(&K, &V)>
+    where
+        K: borrow::Borrow<Q>,
+        Q: Hash + Ord + ?Sized,
+    {
+        self.lookup(key).map(|x| unsafe {
+            let x = x.as_ref();
+            (&x.key, &x.value)
+        })
+    }
+
     /// Returns a mutable reference to the value corresponding to the key.
     pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
     where
@@ -352,7 +388,26 @@ where
         K: borrow::Borrow<Q>,
         Q: Hash + Ord + ?Sized,
     {
-        let mut node = self.lookup(key)?;
+        let node = self.lookup(key)?;
+        // SAFETY: the node is found in this tree.
+        Some(unsafe { self.remove_node(node) })
+    }
+
+    /// Removes the entry with the value at `value` from the tree, returning the stored key and
+    /// value.
+    ///
+    /// # Safety
+    ///
+    /// `value` must point to a value stored in this tree, e.g. obtained from [`Self::get_mut`].
+    pub(crate) unsafe fn remove_value(&mut self, value: NonNull<V>) -> (K, V) {
+        // SAFETY: the value is a field of a `MapEntry` stored in this tree.
+        unsafe {
+            let node = value.byte_sub(mem::offset_of!(MapEntry<K, V>, value)).cast();
+            self.remove_node(node)
+        }
+    }
+
+    unsafe fn remove_node(&mut self, mut node: NonNull<MapEntry<K, V>>) -> (K, V) {
         unsafe {
             self.tree.remove(node.as_mut());
 
@@ -361,12 +416,21 @@ where
             // dropping it.
             let copy = node.as_ptr().read();
             self.allocator().deallocate(node.cast(), layout);
-            Some(copy.into_kv())
+            copy.into_kv()
         }
     }
 
     /// Attempts to insert a new element into the tree.
     pub fn try_insert(&mut self, key: K, value: V) -> Result<&mut V, AllocError> {
+        self.try_insert_entry(key, value).map(|(_, v)| v)
+    }
+
+    /// Attempts to insert a new element into the tree, returning the stored key and value.
+    pub(crate) fn try_insert_entry(
+        &mut self,
+        key: K,
+        value: V,
+    ) -> Result<(&K, &mut V), AllocError> {
         let mut node = if let Some(mut node) = self.lookup(&key) {
             unsafe { node.as_mut().value = value };
             node
@@ -377,7 +441,8 @@ where
             node
         };
 
-        Ok(unsafe { &mut node.as_mut().value })
+        let node = unsafe { node.as_mut() };
+        Ok((&node.key, &mut node.value))
     }
 
     extern "C" fn insert(
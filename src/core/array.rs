@@ -0,0 +1,146 @@
+use core::marker::PhantomData;
+use core::ops::{Deref, DerefMut};
+use core::ptr::NonNull;
+use core::{fmt, mem};
+
+use crate::allocator::AllocError;
+use crate::core::Pool;
+use crate::ffi::{ngx_array_create, ngx_array_push, ngx_array_t};
+
+/// Typed view of an [`ngx_array_t`] with elements of type `T`.
+///
+/// The array stores the elements in memory allocated from the pool of the array, and grows by
+/// reallocating the storage from the same pool. The elements are never dropped, thus adding the
+/// elements requires `T: Copy`.
+///
+/// See <https://nginx.org/en/docs/dev/development_guide.html#array>.
+#[repr(transparent)]
+pub struct NgxArray<T> {
+    raw: ngx_array_t,
+    _marker: PhantomData<T>,
+}
+
+impl<T> NgxArray<T> {
+    /// Creates an array in the `pool` with the initial capacity of `n` elements.
+    ///
+    /// The array is valid for the lifetime of the pool.
+    pub fn create(pool: &Pool, n: usize) -> Result<NonNull<Self>, AllocError> {
+        let n = n.max(1);
+        let raw = unsafe { ngx_array_create(pool.as_ptr(), n, mem::size_of::<T>()) };
+        NonNull::new(raw.cast()).ok_or(AllocError)
+    }
+
+    /// Creates a typed reference to an existing array.
+    ///
+    /// # Safety
+    ///
+    /// The array must be initialized and contain elements of type `T`.
+    pub unsafe fn from_ngx_array(raw: &ngx_array_t) -> &Self {
+        debug_assert_eq!(raw.size, mem::size_of::<T>(), "NgxArray: element size mismatch");
+        // SAFETY: the type is a transparent wrapper over `ngx_array_t`.
+        unsafe { &*core::ptr::from_ref(raw).cast() }
+    }
+
+    /// Creates a typed mutable reference to an existing array.
+    ///
+    /// # Safety
+    ///
+    /// The array must be initialized and contain elements of type `T`.
+    pub unsafe fn from_ngx_array_mut(raw: &mut ngx_array_t) -> &mut Self {
+        debug_assert_eq!(raw.size, mem::size_of::<T>(), "NgxArray: element size mismatch");
+        // SAFETY: the type is a transparent wrapper over `ngx_array_t`.
+        unsafe { &mut *core::ptr::from_mut(raw).cast() }
+    }
+
+    /// Returns a reference to the underlying [`ngx_array_t`].
+    pub fn as_ngx_array(&self) -> &ngx_array_t {
+        &self.raw
+    }
+
+    /// Returns the pool used for the array storage.
+    pub fn pool(&self) -> Pool {
+        // SAFETY: the array is created with a valid pool.
+        unsafe { Pool::from_ngx_pool(self.raw.pool) }
+    }
+
+    /// Returns the number of elements the array can hold without reallocating.
+    pub fn capacity(&self) -> usize {
+        self.raw.nalloc
+    }
+
+    /// Returns the elements as a slice.
+    pub fn as_slice(&self) -> &[T] {
+        // SAFETY: the array is initialized and contains elements of type `T`.
+        unsafe { self.raw.as_slice() }
+    }
+
+    /// Returns the elements as a mutable slice.
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        // SAFETY: the array is initialized and contains elements of type `T`.
+        unsafe { self.raw.as_slice_mut() }
+    }
+}
+
+impl<T: Copy> NgxArray<T> {
+    /// Appends an element to the array, returning a reference to the stored element.
+    ///
+    /// The storage is reallocated from the array pool when the capacity is exceeded; the old
+    /// storage is only released if it was the last allocation in the pool.
+    pub fn push(&mut self, value: T) -> Result<&mut T, AllocError> {
+        let elt = unsafe { ngx_array_push(&raw mut self.raw) }.cast::<T>();
+        if elt.is_null() {
+            return Err(AllocError);
+        }
+        // SAFETY: `elt` points to the uninitialized storage for an element of type `T`.
+        unsafe {
+            elt.write(value);
+            Ok(&mut *elt)
+        }
+    }
+
+    /// Appends all the elements of the slice to the array.
+    pub fn extend_from_slice(&mut self, values: &[T]) -> Result<(), AllocError> {
+        for value in values {
+            self.push(*value)?;
+        }
+        Ok(())
+    }
+}
+
+impl<T> Deref for NgxArray<T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        self.as_slice()
+    }
+}
+
+impl<T> DerefMut for NgxArray<T> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        self.as_mut_slice()
+    }
+}
+
+impl<'a, T> IntoIterator for &'a NgxArray<T> {
+    type Item = &'a T;
+    type IntoIter = core::slice::Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.as_slice().iter()
+    }
+}
+
+impl<'a, T> IntoIterator for &'a mut NgxArray<T> {
+    type Item = &'a mut T;
+    type IntoIter = core::slice::IterMut<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.as_mut_slice().iter_mut()
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for NgxArray<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.as_slice()).finish()
+    }
+}
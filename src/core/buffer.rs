@@ -1,5 +1,8 @@
+use core::ptr::{self, NonNull};
 use core::slice;
 
+use crate::allocator::AllocError;
+use crate::core::Pool;
 use crate::ffi::*;
 
 /// The `Buffer` trait provides methods for working with an nginx buffer (`ngx_buf_t`).
@@ -57,6 +60,100 @@ pub trait Buffer {
             (*buf).set_last_in_chain(if last { 1 } else { 0 });
         }
     }
+
+    /// Returns the tag of the buffer, identifying the module that owns the buffer.
+    fn tag(&self) -> ngx_buf_tag_t {
+        unsafe { (*self.as_ngx_buf()).tag }
+    }
+
+    /// Sets the tag of the buffer.
+    ///
+    /// Buffers allocated by a filter module should be tagged with [`buf_tag`] of the module, so
+    /// they can be reused after being sent with [`BufferChains::update`].
+    fn set_tag(&mut self, tag: ngx_buf_tag_t) {
+        unsafe { (*self.as_ngx_buf_mut()).tag = tag };
+    }
+
+    /// Returns `true` if the buffer is tagged with `tag`.
+    fn has_tag(&self, tag: ngx_buf_tag_t) -> bool {
+        self.tag() == tag
+    }
+}
+
+/// Returns the buffer tag for a module.
+///
+/// nginx modules use the address of the module structure as the tag of the buffers they own.
+pub fn buf_tag(module: &'static ngx_module_t) -> ngx_buf_tag_t {
+    ptr::from_ref(module).cast_mut().cast()
+}
+
+/// Free and busy buffer chains of a module producing output, e.g. a body filter.
+///
+/// The buffers are taken from the free chain or allocated with [`BufferChains::get_free_buf`],
+/// passed to the next filter, and then returned to the free chain with [`BufferChains::update`]
+/// when completely sent. Only the buffers with the module tag are reused; the buffers of the
+/// other modules passing through the output chain are left untouched.
+///
+/// See <https://nginx.org/en/docs/dev/development_guide.html#http_body_buffers_reuse>
+#[derive(Debug)]
+pub struct BufferChains {
+    free: *mut ngx_chain_t,
+    busy: *mut ngx_chain_t,
+    tag: ngx_buf_tag_t,
+}
+
+impl BufferChains {
+    /// Creates empty chains for the buffers tagged with [`buf_tag`] of the module.
+    pub fn new(module: &'static ngx_module_t) -> Self {
+        Self::with_tag(buf_tag(module))
+    }
+
+    /// Creates empty chains for the buffers with the specified tag.
+    pub fn with_tag(tag: ngx_buf_tag_t) -> Self {
+        Self { free: ptr::null_mut(), busy: ptr::null_mut(), tag }
+    }
+
+    /// Returns the tag of the owned buffers.
+    pub fn tag(&self) -> ngx_buf_tag_t {
+        self.tag
+    }
+
+    /// Returns `true` if some of the owned buffers are not sent yet.
+    pub fn is_busy(&self) -> bool {
+        !self.busy.is_null()
+    }
+
+    /// Returns a chain link with a buffer from the free chain, or a new empty buffer.
+    ///
+    /// The buffer is tagged with the tag of the chains. A reused buffer keeps its memory, a new
+    /// buffer has no memory attached.
+    pub fn get_free_buf(&mut self, pool: &Pool) -> Option<NonNull<ngx_chain_t>> {
+        // SAFETY: the free chain contains the links allocated from a pool that outlives `self`.
+        let cl = unsafe { ngx_chain_get_free_buf(pool.as_ptr(), &raw mut self.free) };
+        let cl = NonNull::new(cl)?;
+        unsafe { (*(*cl.as_ptr()).buf).tag = self.tag };
+        Some(cl)
+    }
+
+    /// Moves the sent buffers with the tag of the chains from `out` and the busy chain to the
+    /// free chain.
+    ///
+    /// Should be called after passing `out` to the next filter, with the same `pool`.
+    ///
+    /// # Safety
+    ///
+    /// `out` must be NULL or a valid chain allocated from `pool`.
+    pub unsafe fn update(&mut self, pool: &Pool, out: &mut *mut ngx_chain_t) {
+        unsafe {
+            ngx_chain_update_chains(
+                pool.as_ptr(),
+                &raw mut self.free,
+                &raw mut self.busy,
+                out,
+                self.tag,
+            )
+        };
+    }
 }
 
 /// The `MutableBuffer` trait extends the `Buffer` trait and provides methods for working with a
@@ -127,3 +224,117 @@ impl Buffer for MemoryBuffer {
         self.0
     }
 }
+
+/// Writer appending data to a chain of temporary buffers allocated from a pool.
+///
+/// A new buffer of `chunk_size` bytes, or larger for a longer write, is added when the current
+/// one is full. The data is never moved once written, so the writer does not need to know the
+/// total length in advance.
+pub struct ChainWriter {
+    pool: Pool,
+    chunk_size: usize,
+    head: *mut ngx_chain_t,
+    tail: *mut ngx_chain_t,
+    len: usize,
+}
+
+impl ChainWriter {
+    /// Creates an empty writer.
+    pub fn new(pool: Pool, chunk_size: usize) -> Self {
+        Self {
+            pool,
+            chunk_size: chunk_size.max(1),
+            head: ptr::null_mut(),
+            tail: ptr::null_mut(),
+            len: 0,
+        }
+    }
+
+    /// Returns the total length of the written data.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if nothing was written.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Appends `data` to the chain.
+    pub fn write_bytes(&mut self, mut data: &[u8]) -> Result<(), AllocError> {
+        while !data.is_empty() {
+            let b = match unsafe { self.tail.as_ref() } {
+                Some(cl) if unsafe { (*cl.buf).end > (*cl.buf).last } => cl.buf,
+                _ => self.add_buf(data.len())?,
+            };
+
+            // SAFETY: `b` is a temporary buffer allocated by the writer.
+            unsafe {
+                let n = data.len().min((*b).end.offset_from((*b).last) as usize);
+                ptr::copy_nonoverlapping(data.as_ptr(), (*b).last, n);
+                (*b).last = (*b).last.add(n);
+                self.len += n;
+                data = &data[n..];
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the chain of the written buffers, or NULL if nothing was written.
+    ///
+    /// With `last_buf`, the last buffer is marked as the end of the response body; if nothing was
+    /// written, the chain contains a single empty buffer with the flag.
+    pub fn finish(mut self, last_buf: bool) -> Result<*mut ngx_chain_t, AllocError> {
+        if last_buf {
+            if self.tail.is_null() {
+                self.add_buf(0)?;
+            }
+            // SAFETY: the tail link is allocated by the writer.
+            unsafe {
+                let b = (*self.tail).buf;
+                (*b).set_last_buf(1);
+                (*b).set_last_in_chain(1);
+                if (*b).pos == (*b).last {
+                    (*b).set_sync(1);
+                }
+            }
+        }
+
+        Ok(self.head)
+    }
+
+    fn add_buf(&mut self, size: usize) -> Result<*mut ngx_buf_t, AllocError> {
+        let b = if size == 0 {
+            self.pool.calloc_type::<ngx_buf_t>()
+        } else {
+            // SAFETY: the pool is valid for the lifetime of the writer.
+            unsafe { ngx_create_temp_buf(self.pool.as_ptr(), size.max(self.chunk_size)) }
+        };
+        if b.is_null() {
+            return Err(AllocError);
+        }
+
+        let cl = self.pool.alloc_type::<ngx_chain_t>();
+        if cl.is_null() {
+            return Err(AllocError);
+        }
+
+        unsafe {
+            cl.write(ngx_chain_t { buf: b, next: ptr::null_mut() });
+            match self.tail.as_mut() {
+                Some(tail) => tail.next = cl,
+                None => self.head = cl,
+            }
+        }
+        self.tail = cl;
+
+        Ok(b)
+    }
+}
+
+impl core::fmt::Write for ChainWriter {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        self.write_bytes(s.as_bytes()).map_err(|_| core::fmt::Error)
+    }
+}
@@ -0,0 +1,105 @@
+use core::fmt;
+use core::ops::{Deref, DerefMut};
+
+use crate::ffi::ngx_cacheline_size;
+
+/// Returns the size of the CPU cache line, as detected by nginx on startup.
+///
+/// The value is determined by `ngx_cpuinfo` on x86 and defaults to the `NGX_CPU_CACHE_LINE` of
+/// the build otherwise. It is the alignment used by nginx for its own shared memory structures,
+/// e.g. the hash tables of the shared zones.
+#[inline]
+pub fn cacheline_size() -> usize {
+    // SAFETY: the value is set once at startup and never modified.
+    unsafe { ngx_cacheline_size }
+}
+
+/// Pads and aligns a value to the size of a cache line.
+///
+/// Counters and locks updated by different worker processes should not share a cache line, or
+/// every update invalidates the line in the caches of the other CPUs (false sharing). Wrapping
+/// each of them in `CachePadded` places them on separate cache lines:
+///
+/// ```
+/// # use core::sync::atomic::AtomicU64;
+/// # use ngx::core::CachePadded;
+/// #[repr(C)]
+/// struct Counters {
+///     requests: CachePadded<AtomicU64>,
+///     errors: CachePadded<AtomicU64>,
+/// }
+/// ```
+///
+/// The alignment is a compile-time constant and covers the cache line size of the common
+/// platforms: 128 bytes on x86_64 and aarch64, where the adjacent cache lines are prefetched in
+/// pairs, and 64 bytes otherwise. The shared memory allocators of the crate honor the alignment.
+#[derive(Clone, Copy, Default, Hash, PartialEq, Eq)]
+#[cfg_attr(any(target_arch = "x86_64", target_arch = "aarch64"), repr(align(128)))]
+#[cfg_attr(not(any(target_arch = "x86_64", target_arch = "aarch64")), repr(align(64)))]
+pub struct CachePadded<T> {
+    value: T,
+}
+
+impl<T> CachePadded<T> {
+    /// Wraps the value.
+    pub const fn new(value: T) -> Self {
+        Self { value }
+    }
+
+    /// Returns the wrapped value.
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+}
+
+impl<T> Deref for CachePadded<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T> DerefMut for CachePadded<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+}
+
+impl<T> From<T> for CachePadded<T> {
+    fn from(value: T) -> Self {
+        Self::new(value)
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for CachePadded<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("CachePadded").field(&self.value).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::mem;
+
+    use super::*;
+
+    #[test]
+    fn test_cache_padded() {
+        #[repr(C)]
+        struct Counters {
+            a: CachePadded<u64>,
+            b: CachePadded<u64>,
+        }
+
+        let align = mem::align_of::<CachePadded<u64>>();
+        assert!(align >= 64);
+        assert_eq!(mem::size_of::<CachePadded<u8>>(), align);
+        let c = Counters { a: CachePadded::new(1), b: CachePadded::new(2) };
+        assert_eq!((&raw const c.b).addr() - (&raw const c.a).addr(), align);
+
+        let mut x = CachePadded::new(1u64);
+        *x += 1;
+        assert_eq!(x.into_inner(), 2);
+    }
+}
@@ -0,0 +1,603 @@
+//! Compact binary encoding for the values stored in shared memory.
+//!
+//! Implements a subset of [CBOR] sufficient for small structured values: integers, booleans,
+//! null, byte and text strings, arrays and maps of definite length. Unlike [`NgxString`]
+//! values, the encoded data can hold arbitrary binary fields, and the encoded size is computed
+//! before the allocation, so the value is stored in a single exact-sized [`SlabPool`]
+//! allocation.
+//!
+//! The types implement [`Encode`] and [`Decode`] by writing and reading their fields in order,
+//! usually as an array:
+//!
+//! ```
+//! use ngx::core::cbor::{self, Decode, Decoder, Encode, Encoder, Error, Sink};
+//!
+//! #[derive(Debug, PartialEq)]
+//! struct Entry<'a> {
+//!     hits: u64,
+//!     banned: bool,
+//!     key: &'a [u8],
+//! }
+//!
+//! impl Encode for Entry<'_> {
+//!     fn encode<S: Sink>(&self, e: &mut Encoder<S>) -> Result<(), Error> {
+//!         e.array(3)?;
+//!         self.hits.encode(e)?;
+//!         self.banned.encode(e)?;
+//!         self.key.encode(e)
+//!     }
+//! }
+//!
+//! impl<'a> Decode<'a> for Entry<'a> {
+//!     fn decode(d: &mut Decoder<'a>) -> Result<Self, Error> {
+//!         d.array_len(3)?;
+//!         Ok(Self { hits: d.decode()?, banned: d.decode()?, key: d.decode()? })
+//!     }
+//! }
+//!
+//! let entry = Entry { hits: 10, banned: false, key: b"\x00\x01" };
+//! let data = cbor::to_vec_in(&entry, ngx::allocator::Global).unwrap();
+//! assert_eq!(cbor::from_slice::<Entry>(&data).unwrap(), entry);
+//! ```
+//!
+//! [CBOR]: https://www.rfc-editor.org/rfc/rfc8949
+//! [`NgxString`]: crate::core::NgxString
+//! [`SlabPool`]: crate::core::SlabPool
+use core::fmt;
+
+use crate::allocator::{AllocError, Allocator};
+use crate::collections::Vec;
+
+const MAJOR_UINT: u8 = 0;
+const MAJOR_NINT: u8 = 1;
+const MAJOR_BYTES: u8 = 2;
+const MAJOR_TEXT: u8 = 3;
+const MAJOR_ARRAY: u8 = 4;
+const MAJOR_MAP: u8 = 5;
+const MAJOR_SIMPLE: u8 = 7;
+
+const FALSE: u8 = 0xf4;
+const TRUE: u8 = 0xf5;
+const NULL: u8 = 0xf6;
+
+/// Encoding or decoding error.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// Memory allocation failed.
+    Alloc,
+    /// Unexpected end of the input.
+    Eof,
+    /// Unexpected type of the item.
+    Type,
+    /// The value is out of range of the target type, or the length does not match.
+    Range,
+    /// Invalid UTF-8 in a text string.
+    Utf8,
+    /// Unsupported encoding, e.g. an indefinite length item.
+    Unsupported,
+    /// Trailing data after the value.
+    Trailing,
+    /// The items are nested deeper than [`Decoder::MAX_DEPTH`].
+    Depth,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Error::Alloc => "memory allocation failed",
+            Error::Eof => "unexpected end of data",
+            Error::Type => "unexpected item type",
+            Error::Range => "value out of range",
+            Error::Utf8 => "invalid utf-8 text",
+            Error::Unsupported => "unsupported encoding",
+            Error::Trailing => "trailing data",
+            Error::Depth => "nesting too deep",
+        })
+    }
+}
+
+impl core::error::Error for Error {}
+
+impl From<AllocError> for Error {
+    fn from(_: AllocError) -> Self {
+        Error::Alloc
+    }
+}
+
+/// Destination of an [`Encoder`].
+pub trait Sink {
+    /// Appends the data to the output.
+    fn write(&mut self, data: &[u8]) -> Result<(), Error>;
+}
+
+impl<A: Allocator> Sink for Vec<u8, A> {
+    fn write(&mut self, data: &[u8]) -> Result<(), Error> {
+        self.try_reserve(data.len()).map_err(|_| Error::Alloc)?;
+        self.extend_from_slice(data);
+        Ok(())
+    }
+}
+
+/// Sink computing the encoded length.
+#[derive(Debug, Default)]
+pub struct SizeCounter(pub usize);
+
+impl Sink for SizeCounter {
+    fn write(&mut self, data: &[u8]) -> Result<(), Error> {
+        self.0 += data.len();
+        Ok(())
+    }
+}
+
+/// Item encoder.
+pub struct Encoder<S>(S);
+
+impl<S: Sink> Encoder<S> {
+    /// Creates an encoder for the sink.
+    pub fn new(sink: S) -> Self {
+        Self(sink)
+    }
+
+    /// Returns the sink.
+    pub fn into_inner(self) -> S {
+        self.0
+    }
+
+    fn head(&mut self, major: u8, value: u64) -> Result<(), Error> {
+        let major = major << 5;
+        match value {
+            0..24 => self.0.write(&[major | value as u8]),
+            24..0x100 => self.0.write(&[major | 24, value as u8]),
+            0x100..0x10000 => {
+                self.0.write(&[major | 25])?;
+                self.0.write(&(value as u16).to_be_bytes())
+            }
+            0x10000..0x1_0000_0000 => {
+                self.0.write(&[major | 26])?;
+                self.0.write(&(value as u32).to_be_bytes())
+            }
+            _ => {
+                self.0.write(&[major | 27])?;
+                self.0.write(&value.to_be_bytes())
+            }
+        }
+    }
+
+    /// Writes an unsigned integer.
+    pub fn uint(&mut self, value: u64) -> Result<(), Error> {
+        self.head(MAJOR_UINT, value)
+    }
+
+    /// Writes a signed integer.
+    pub fn int(&mut self, value: i64) -> Result<(), Error> {
+        if value < 0 {
+            self.head(MAJOR_NINT, !(value as u64))
+        } else {
+            self.head(MAJOR_UINT, value as u64)
+        }
+    }
+
+    /// Writes a byte string.
+    pub fn bytes(&mut self, value: &[u8]) -> Result<(), Error> {
+        self.head(MAJOR_BYTES, value.len() as u64)?;
+        self.0.write(value)
+    }
+
+    /// Writes a text string.
+    pub fn text(&mut self, value: &str) -> Result<(), Error> {
+        self.head(MAJOR_TEXT, value.len() as u64)?;
+        self.0.write(value.as_bytes())
+    }
+
+    /// Writes a boolean.
+    pub fn bool(&mut self, value: bool) -> Result<(), Error> {
+        self.0.write(&[if value { TRUE } else { FALSE }])
+    }
+
+    /// Writes a null.
+    pub fn null(&mut self) -> Result<(), Error> {
+        self.0.write(&[NULL])
+    }
+
+    /// Starts an array of `len` items.
+    pub fn array(&mut self, len: usize) -> Result<(), Error> {
+        self.head(MAJOR_ARRAY, len as u64)
+    }
+
+    /// Starts a map of `len` key-value pairs.
+    pub fn map(&mut self, len: usize) -> Result<(), Error> {
+        self.head(MAJOR_MAP, len as u64)
+    }
+}
+
+/// Item decoder, borrowing the strings from the input.
+pub struct Decoder<'a>(&'a [u8]);
+
+impl<'a> Decoder<'a> {
+    /// Maximum nesting of the arrays and maps skipped with [`Self::skip`].
+    pub const MAX_DEPTH: usize = 32;
+
+    /// Creates a decoder for the input.
+    pub fn new(data: &'a [u8]) -> Self {
+        Self(data)
+    }
+
+    /// Returns the remaining input.
+    pub fn remaining(&self) -> &'a [u8] {
+        self.0
+    }
+
+    /// Decodes a value.
+    pub fn decode<T: Decode<'a>>(&mut self) -> Result<T, Error> {
+        T::decode(self)
+    }
+
+    fn take(&mut self, n: usize) -> Result<&'a [u8], Error> {
+        if self.0.len() < n {
+            return Err(Error::Eof);
+        }
+        let (head, rest) = self.0.split_at(n);
+        self.0 = rest;
+        Ok(head)
+    }
+
+    fn peek(&self) -> Result<u8, Error> {
+        self.0.first().copied().ok_or(Error::Eof)
+    }
+
+    fn head(&mut self, major: u8) -> Result<u64, Error> {
+        let b = self.peek()?;
+        if b >> 5 != major {
+            return Err(Error::Type);
+        }
+        self.0 = &self.0[1..];
+
+        let arg = |d: &mut Self, n: usize| -> Result<u64, Error> {
+            Ok(d.take(n)?.iter().fold(0u64, |acc, &x| (acc << 8) | u64::from(x)))
+        };
+
+        match b & 0x1f {
+            x @ 0..24 => Ok(u64::from(x)),
+            24 => arg(self, 1),
+            25 => arg(self, 2),
+            26 => arg(self, 4),
+            27 => arg(self, 8),
+            _ => Err(Error::Unsupported),
+        }
+    }
+
+    fn len(&mut self, major: u8) -> Result<usize, Error> {
+        usize::try_from(self.head(major)?).map_err(|_| Error::Range)
+    }
+
+    /// Reads an unsigned integer.
+    pub fn uint(&mut self) -> Result<u64, Error> {
+        self.head(MAJOR_UINT)
+    }
+
+    /// Reads a signed integer.
+    pub fn int(&mut self) -> Result<i64, Error> {
+        if self.peek()? >> 5 == MAJOR_NINT {
+            let v = i64::try_from(self.head(MAJOR_NINT)?).map_err(|_| Error::Range)?;
+            return Ok(!v);
+        }
+        i64::try_from(self.head(MAJOR_UINT)?).map_err(|_| Error::Range)
+    }
+
+    /// Reads a byte string.
+    pub fn bytes(&mut self) -> Result<&'a [u8], Error> {
+        let len = self.len(MAJOR_BYTES)?;
+        self.take(len)
+    }
+
+    /// Reads a text string.
+    pub fn text(&mut self) -> Result<&'a str, Error> {
+        let len = self.len(MAJOR_TEXT)?;
+        core::str::from_utf8(self.take(len)?).map_err(|_| Error::Utf8)
+    }
+
+    /// Reads a boolean.
+    pub fn bool(&mut self) -> Result<bool, Error> {
+        let value = match self.peek()? {
+            FALSE => false,
+            TRUE => true,
+            _ => return Err(Error::Type),
+        };
+        self.0 = &self.0[1..];
+        Ok(value)
+    }
+
+    /// Reads a null if present, and returns `true` in this case.
+    pub fn null(&mut self) -> Result<bool, Error> {
+        if self.peek()? == NULL {
+            self.0 = &self.0[1..];
+            return Ok(true);
+        }
+        Ok(false)
+    }
+
+    /// Reads the length of an array.
+    pub fn array(&mut self) -> Result<usize, Error> {
+        self.len(MAJOR_ARRAY)
+    }
+
+    /// Reads the length of an array and checks that it is equal to `len`.
+    pub fn array_len(&mut self, len: usize) -> Result<(), Error> {
+        if self.array()? != len {
+            return Err(Error::Range);
+        }
+        Ok(())
+    }
+
+    /// Reads the number of the key-value pairs of a map.
+    pub fn map(&mut self) -> Result<usize, Error> {
+        self.len(MAJOR_MAP)
+    }
+
+    /// Skips an item, including the nested items.
+    ///
+    /// The items nested deeper than [`Self::MAX_DEPTH`] are rejected, so that untrusted input
+    /// cannot exhaust the stack.
+    pub fn skip(&mut self) -> Result<(), Error> {
+        self.skip_nested(0)
+    }
+
+    fn skip_nested(&mut self, depth: usize) -> Result<(), Error> {
+        let b = self.peek()?;
+        match b >> 5 {
+            MAJOR_UINT | MAJOR_NINT => {
+                self.head(b >> 5)?;
+            }
+            MAJOR_BYTES | MAJOR_TEXT => {
+                let len = self.len(b >> 5)?;
+                self.take(len)?;
+            }
+            MAJOR_ARRAY | MAJOR_MAP if depth == Self::MAX_DEPTH => return Err(Error::Depth),
+            MAJOR_ARRAY => {
+                for _ in 0..self.array()? {
+                    self.skip_nested(depth + 1)?;
+                }
+            }
+            MAJOR_MAP => {
+                for _ in 0..self.map()? {
+                    self.skip_nested(depth + 1)?;
+                    self.skip_nested(depth + 1)?;
+                }
+            }
+            MAJOR_SIMPLE if matches!(b, FALSE | TRUE | NULL) => self.0 = &self.0[1..],
+            _ => return Err(Error::Unsupported),
+        }
+        Ok(())
+    }
+}
+
+/// Type that can be encoded.
+pub trait Encode {
+    /// Writes the value to the encoder.
+    fn encode<S: Sink>(&self, e: &mut Encoder<S>) -> Result<(), Error>;
+}
+
+/// Type that can be decoded, possibly borrowing from the input.
+pub trait Decode<'a>: Sized {
+    /// Reads the value from the decoder.
+    fn decode(d: &mut Decoder<'a>) -> Result<Self, Error>;
+}
+
+macro_rules! impl_uint {
+    ($($t:ty),+) => {$(
+        impl Encode for $t {
+            fn encode<S: Sink>(&self, e: &mut Encoder<S>) -> Result<(), Error> {
+                e.uint(*self as u64)
+            }
+        }
+
+        impl Decode<'_> for $t {
+            fn decode(d: &mut Decoder<'_>) -> Result<Self, Error> {
+                <$t>::try_from(d.uint()?).map_err(|_| Error::Range)
+            }
+        }
+    )+};
+}
+
+macro_rules! impl_int {
+    ($($t:ty),+) => {$(
+        impl Encode for $t {
+            fn encode<S: Sink>(&self, e: &mut Encoder<S>) -> Result<(), Error> {
+                e.int(*self as i64)
+            }
+        }
+
+        impl Decode<'_> for $t {
+            fn decode(d: &mut Decoder<'_>) -> Result<Self, Error> {
+                <$t>::try_from(d.int()?).map_err(|_| Error::Range)
+            }
+        }
+    )+};
+}
+
+impl_uint!(u8, u16, u32, u64, usize);
+impl_int!(i8, i16, i32, i64, isize);
+
+impl Encode for bool {
+    fn encode<S: Sink>(&self, e: &mut Encoder<S>) -> Result<(), Error> {
+        e.bool(*self)
+    }
+}
+
+impl Decode<'_> for bool {
+    fn decode(d: &mut Decoder<'_>) -> Result<Self, Error> {
+        d.bool()
+    }
+}
+
+impl Encode for [u8] {
+    fn encode<S: Sink>(&self, e: &mut Encoder<S>) -> Result<(), Error> {
+        e.bytes(self)
+    }
+}
+
+impl<'a> Decode<'a> for &'a [u8] {
+    fn decode(d: &mut Decoder<'a>) -> Result<Self, Error> {
+        d.bytes()
+    }
+}
+
+impl Encode for str {
+    fn encode<S: Sink>(&self, e: &mut Encoder<S>) -> Result<(), Error> {
+        e.text(self)
+    }
+}
+
+impl<'a> Decode<'a> for &'a str {
+    fn decode(d: &mut Decoder<'a>) -> Result<Self, Error> {
+        d.text()
+    }
+}
+
+impl<T: Encode + ?Sized> Encode for &T {
+    fn encode<S: Sink>(&self, e: &mut Encoder<S>) -> Result<(), Error> {
+        (**self).encode(e)
+    }
+}
+
+impl<T: Encode> Encode for Option<T> {
+    fn encode<S: Sink>(&self, e: &mut Encoder<S>) -> Result<(), Error> {
+        match self {
+            Some(x) => x.encode(e),
+            None => e.null(),
+        }
+    }
+}
+
+impl<'a, T: Decode<'a>> Decode<'a> for Option<T> {
+    fn decode(d: &mut Decoder<'a>) -> Result<Self, Error> {
+        if d.null()? {
+            return Ok(None);
+        }
+        T::decode(d).map(Some)
+    }
+}
+
+/// Returns the encoded size of the value.
+pub fn encoded_len<T: Encode + ?Sized>(value: &T) -> Result<usize, Error> {
+    let mut e = Encoder::new(SizeCounter(0));
+    value.encode(&mut e)?;
+    Ok(e.into_inner().0)
+}
+
+/// Encodes the value into an exact-sized vector allocated with `alloc`, e.g. a
+/// [`SlabPool`](crate::core::SlabPool).
+pub fn to_vec_in<T: Encode + ?Sized, A: Allocator>(
+    value: &T,
+    alloc: A,
+) -> Result<Vec<u8, A>, Error> {
+    let len = encoded_len(value)?;
+
+    let mut out = Vec::new_in(alloc);
+    out.try_reserve_exact(len).map_err(|_| Error::Alloc)?;
+
+    let mut e = Encoder::new(out);
+    value.encode(&mut e)?;
+    Ok(e.into_inner())
+}
+
+/// Decodes a value from the data, which must contain exactly one encoded value.
+pub fn from_slice<'a, T: Decode<'a>>(data: &'a [u8]) -> Result<T, Error> {
+    let mut d = Decoder::new(data);
+    let value = T::decode(&mut d)?;
+    if !d.remaining().is_empty() {
+        return Err(Error::Trailing);
+    }
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::allocator::Global;
+
+    use super::*;
+
+    fn roundtrip<T>(value: T, expected: &[u8])
+    where
+        T: Encode + for<'a> Decode<'a> + PartialEq + fmt::Debug,
+    {
+        let data = to_vec_in(&value, Global).unwrap();
+        assert_eq!(&data[..], expected);
+        assert_eq!(encoded_len(&value).unwrap(), expected.len());
+        assert_eq!(from_slice::<T>(&data).unwrap(), value);
+    }
+
+    #[test]
+    fn test_integers() {
+        roundtrip(0u8, &[0x00]);
+        roundtrip(23u32, &[0x17]);
+        roundtrip(24u32, &[0x18, 0x18]);
+        roundtrip(1000u64, &[0x19, 0x03, 0xe8]);
+        roundtrip(1_000_000u64, &[0x1a, 0x00, 0x0f, 0x42, 0x40]);
+        roundtrip(u64::MAX, &[0x1b, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]);
+        roundtrip(-1i32, &[0x20]);
+        roundtrip(-1000i64, &[0x39, 0x03, 0xe7]);
+        roundtrip(i64::MIN, &[0x3b, 0x7f, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]);
+        roundtrip(Some(true), &[0xf5]);
+        roundtrip(None::<bool>, &[0xf6]);
+
+        assert_eq!(from_slice::<u8>(&[0x19, 0x01, 0x00]), Err(Error::Range));
+        assert_eq!(from_slice::<u8>(&[0x20]), Err(Error::Type));
+        assert_eq!(from_slice::<u8>(&[0x19, 0x01]), Err(Error::Eof));
+        assert_eq!(from_slice::<u8>(&[0x01, 0x01]), Err(Error::Trailing));
+    }
+
+    #[test]
+    fn test_strings() {
+        let mut e = Encoder::new(Vec::new_in(Global));
+        e.array(2).unwrap();
+        e.bytes(b"\x00\xff").unwrap();
+        b"".encode(&mut e).unwrap();
+        let data = e.into_inner();
+        assert_eq!(&data[..], &[0x82, 0x42, 0x00, 0xff, 0x40]);
+
+        let mut d = Decoder::new(&data);
+        assert_eq!(d.array(), Ok(2));
+        assert_eq!(d.bytes(), Ok(&b"\x00\xff"[..]));
+        assert_eq!(d.bytes(), Ok(&b""[..]));
+
+        let data = to_vec_in("héllo", Global).unwrap();
+        assert_eq!(from_slice::<&str>(&data), Ok("héllo"));
+        assert_eq!(from_slice::<&str>(&[0x61, 0xff]), Err(Error::Utf8));
+    }
+
+    #[test]
+    fn test_skip() {
+        let mut e = Encoder::new(Vec::new_in(Global));
+        e.map(2).unwrap();
+        e.text("a").unwrap();
+        e.array(2).unwrap();
+        e.int(-5).unwrap();
+        e.null().unwrap();
+        e.text("b").unwrap();
+        e.uint(7).unwrap();
+        let data = e.into_inner();
+
+        let mut d = Decoder::new(&data);
+        assert_eq!(d.map(), Ok(2));
+        assert_eq!(d.text(), Ok("a"));
+        d.skip().unwrap();
+        assert_eq!(d.text(), Ok("b"));
+        assert_eq!(d.uint(), Ok(7));
+        assert!(d.remaining().is_empty());
+
+        // arrays of one item nested up to the limit, with an integer inside
+        let mut data = Vec::new_in(Global);
+        data.resize(Decoder::MAX_DEPTH, 0x81);
+        data.push(0x00);
+        let mut d = Decoder::new(&data);
+        d.skip().unwrap();
+        assert!(d.remaining().is_empty());
+
+        data.insert(0, 0x81);
+        assert_eq!(Decoder::new(&data).skip(), Err(Error::Depth));
+
+        let data = [0xa1; 100_000];
+        assert_eq!(Decoder::new(&data).skip(), Err(Error::Depth));
+    }
+}
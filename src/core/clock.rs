@@ -0,0 +1,71 @@
+//! Time and timer sources of the crate.
+//!
+//! The cached time and the timers of the event loop are replaced with the mock clock of
+//! [`testing`](crate::testing) on the threads where a test enabled it with
+//! [`testing::enable`](crate::testing::enable). Everywhere else the functions read the nginx time
+//! and use the timers of the event loop, even if the `testing` feature is enabled.
+use crate::ffi::{ngx_event_t, ngx_msec_t, time_t};
+
+/// Returns the cached time in seconds since the Unix epoch.
+#[inline]
+pub(crate) fn time() -> time_t {
+    #[cfg(any(test, feature = "testing"))]
+    if let Some(t) = crate::testing::mock_time() {
+        return t;
+    }
+    crate::ffi::ngx_time()
+}
+
+/// Returns the cached monotonic time in milliseconds.
+#[inline]
+pub(crate) fn current_msec() -> ngx_msec_t {
+    #[cfg(any(test, feature = "testing"))]
+    if let Some(msec) = crate::testing::mock_current_msec() {
+        return msec;
+    }
+    // SAFETY: `ngx_current_msec` is only updated by the main thread.
+    unsafe { crate::ffi::ngx_current_msec }
+}
+
+/// Returns the cached wall clock time since the Unix epoch.
+#[inline]
+pub(crate) fn timeofday() -> core::time::Duration {
+    #[cfg(any(test, feature = "testing"))]
+    if let Some(now) = crate::testing::mock_now() {
+        return now;
+    }
+    let tp = crate::ffi::ngx_timeofday();
+    core::time::Duration::new(tp.sec as u64, tp.msec as u32 * 1_000_000)
+}
+
+/// Schedules the timer of the event.
+///
+/// # Safety
+///
+/// Same as `ngx_add_timer`: the event must stay valid until the timer fires or is deleted.
+#[inline]
+pub(crate) unsafe fn add_timer(ev: *mut ngx_event_t, msec: ngx_msec_t) {
+    #[cfg(any(test, feature = "testing"))]
+    // SAFETY: the event is valid, as required by the caller.
+    if unsafe { crate::testing::mock_add_timer(ev, msec) } {
+        return;
+    }
+    // SAFETY: the event is valid, as required by the caller.
+    unsafe { crate::ffi::ngx_add_timer(ev, msec) }
+}
+
+/// Removes the timer of the event.
+///
+/// # Safety
+///
+/// Same as `ngx_del_timer`: the event must be valid and its timer must be set.
+#[inline]
+pub(crate) unsafe fn del_timer(ev: *mut ngx_event_t) {
+    #[cfg(any(test, feature = "testing"))]
+    // SAFETY: the event is valid, as required by the caller.
+    if unsafe { crate::testing::mock_del_timer(ev) } {
+        return;
+    }
+    // SAFETY: the event is valid, as required by the caller.
+    unsafe { crate::ffi::ngx_del_timer(ev) }
+}
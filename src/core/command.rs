@@ -0,0 +1,260 @@
+use core::fmt;
+use core::str::FromStr;
+
+use crate::core::{NgxMsec, NgxSec, NgxSize, NgxStr};
+use crate::ffi::{
+    NGX_CONF_NOARGS, NGX_CONF_TAKE1, NGX_CONF_TAKE2, NGX_CONF_TAKE3, NGX_CONF_TAKE4,
+    NGX_CONF_TAKE5, NGX_CONF_TAKE6, NGX_CONF_TAKE7, ngx_str_t, ngx_uint_t,
+};
+
+/// Error returned when a directive argument cannot be converted with [`ConfArg`].
+///
+/// The reason is appended to the standard nginx message, e.g.
+/// `invalid value "yes" in "foo" directive, it must be "on" or "off"`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ConfArgError(pub &'static str);
+
+impl fmt::Display for ConfArgError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.0)
+    }
+}
+
+/// Trait for the types of the directive arguments accepted by [`ngx_commands!`].
+pub trait ConfArg<'a>: Sized {
+    /// Converts a directive argument.
+    fn from_conf_arg(arg: &'a ngx_str_t) -> Result<Self, ConfArgError>;
+}
+
+impl<'a> ConfArg<'a> for &'a NgxStr {
+    fn from_conf_arg(arg: &'a ngx_str_t) -> Result<Self, ConfArgError> {
+        // SAFETY: the arguments are allocated from the configuration pool.
+        Ok(unsafe { NgxStr::from_ngx_str(*arg) })
+    }
+}
+
+impl<'a> ConfArg<'a> for &'a [u8] {
+    fn from_conf_arg(arg: &'a ngx_str_t) -> Result<Self, ConfArgError> {
+        Ok(arg.as_bytes())
+    }
+}
+
+impl<'a> ConfArg<'a> for &'a str {
+    fn from_conf_arg(arg: &'a ngx_str_t) -> Result<Self, ConfArgError> {
+        core::str::from_utf8(arg.as_bytes()).map_err(|_| ConfArgError("it must be valid UTF-8"))
+    }
+}
+
+impl ConfArg<'_> for ngx_str_t {
+    fn from_conf_arg(arg: &ngx_str_t) -> Result<Self, ConfArgError> {
+        Ok(*arg)
+    }
+}
+
+impl ConfArg<'_> for bool {
+    fn from_conf_arg(arg: &ngx_str_t) -> Result<Self, ConfArgError> {
+        parse_flag(arg.as_bytes()).ok_or(ConfArgError("it must be \"on\" or \"off\""))
+    }
+}
+
+impl ConfArg<'_> for usize {
+    fn from_conf_arg(arg: &ngx_str_t) -> Result<Self, ConfArgError> {
+        let value = arg.as_bytes();
+        // `FromStr` accepts a leading `+`, unlike `ngx_atoi`.
+        if value.first() == Some(&b'+') {
+            return Err(ConfArgError("it must be a number"));
+        }
+        core::str::from_utf8(value)
+            .ok()
+            .and_then(|x| usize::from_str(x).ok())
+            .ok_or(ConfArgError("it must be a number"))
+    }
+}
+
+impl ConfArg<'_> for NgxSize {
+    fn from_conf_arg(arg: &ngx_str_t) -> Result<Self, ConfArgError> {
+        Self::parse(arg.as_bytes()).map_err(|_| ConfArgError("it must be a size"))
+    }
+}
+
+impl ConfArg<'_> for NgxMsec {
+    fn from_conf_arg(arg: &ngx_str_t) -> Result<Self, ConfArgError> {
+        Self::parse(arg.as_bytes()).map_err(|_| ConfArgError("it must be a time interval"))
+    }
+}
+
+impl ConfArg<'_> for NgxSec {
+    fn from_conf_arg(arg: &ngx_str_t) -> Result<Self, ConfArgError> {
+        Self::parse(arg.as_bytes()).map_err(|_| ConfArgError("it must be a time interval"))
+    }
+}
+
+/// Parses an `on`/`off` flag, ignoring ASCII case as nginx does.
+pub(crate) fn parse_flag(value: &[u8]) -> Option<bool> {
+    if value.eq_ignore_ascii_case(b"on") {
+        Some(true)
+    } else if value.eq_ignore_ascii_case(b"off") {
+        Some(false)
+    } else {
+        None
+    }
+}
+
+/// Returns the `NGX_CONF_NOARGS` or `NGX_CONF_TAKEn` flag for a directive with `n` arguments.
+///
+/// Fails to compile in a constant context if `n` exceeds the maximum of 7 arguments.
+pub const fn conf_take(n: usize) -> ngx_uint_t {
+    const TAKE: [u32; 8] = [
+        NGX_CONF_NOARGS,
+        NGX_CONF_TAKE1,
+        NGX_CONF_TAKE2,
+        NGX_CONF_TAKE3,
+        NGX_CONF_TAKE4,
+        NGX_CONF_TAKE5,
+        NGX_CONF_TAKE6,
+        NGX_CONF_TAKE7,
+    ];
+
+    assert!(n < TAKE.len(), "a directive can take at most 7 arguments");
+    TAKE[n] as ngx_uint_t
+}
+
+/// Defines a static array of configuration directives with typed handlers.
+///
+/// Each directive is specified with the name, the contexts, the configuration offset, and a
+/// handler function receiving the configuration and the arguments converted with [`ConfArg`].
+/// The number of arguments, `NGX_CONF_TAKEn`, is derived from the handler signature, and the
+/// terminating empty element is appended automatically.
+///
+/// An argument that cannot be converted is reported with the standard nginx message, and errors
+/// returned from the handler are logged at `NGX_LOG_EMERG` level.
+///
+/// ```no_run
+/// # use ngx::core::NgxMsec;
+/// # use ngx::ffi::{NGX_HTTP_LOC_CONF, NGX_HTTP_LOC_CONF_OFFSET, NGX_HTTP_SRV_CONF};
+/// struct LocConf {
+///     enable: bool,
+///     timeout: NgxMsec,
+/// }
+///
+/// ngx::ngx_commands! {
+///     static mut NGX_HTTP_FOO_COMMANDS = [
+///         "foo" (NGX_HTTP_SRV_CONF | NGX_HTTP_LOC_CONF, NGX_HTTP_LOC_CONF_OFFSET) =>
+///             fn(_cf, conf: &mut LocConf, enable: bool) -> Result<(), &'static str> {
+///                 conf.enable = enable;
+///                 Ok(())
+///             },
+///         "foo_timeout" (NGX_HTTP_LOC_CONF, NGX_HTTP_LOC_CONF_OFFSET) =>
+///             fn(_cf, conf: &mut LocConf, timeout: NgxMsec) -> Result<(), &'static str> {
+///                 conf.timeout = timeout;
+///                 Ok(())
+///             },
+///     ];
+/// }
+///
+/// // ngx_module_t { commands: unsafe { &raw mut NGX_HTTP_FOO_COMMANDS[0] }, .. }
+/// ```
+#[macro_export]
+macro_rules! ngx_commands {
+    (
+        $(#[$attr:meta])*
+        $vis:vis static mut $name:ident = [
+            $(
+                $directive:literal ($type:expr, $conf:expr) =>
+                    fn($cf:ident, $cnf:ident: &mut $conf_ty:ty $(, $arg:ident: $arg_ty:ty)* $(,)?)
+                        -> $ret:ty $body:block
+            ),* $(,)?
+        ];
+    ) => {
+        $(#[$attr])*
+        $vis static mut $name: [$crate::ffi::ngx_command_t; <[()]>::len(&[$( { stringify!($directive); } ),*]) + 1] = [
+            $(
+                $crate::ffi::ngx_command_t {
+                    name: $crate::ngx_string!($directive),
+                    type_: ($type) as $crate::ffi::ngx_uint_t
+                        | $crate::core::conf_take(<[()]>::len(&[$( { stringify!($arg); } ),*])),
+                    set: Some({
+                        unsafe extern "C" fn handler(
+                            cf: *mut $crate::ffi::ngx_conf_t,
+                            _cmd: *mut $crate::ffi::ngx_command_t,
+                            conf: *mut ::core::ffi::c_void,
+                        ) -> *mut ::core::ffi::c_char {
+                            fn typed(
+                                $cf: &mut $crate::ffi::ngx_conf_t,
+                                $cnf: &mut $conf_ty,
+                                $( $arg: $arg_ty, )*
+                            ) -> $ret $body
+
+                            // SAFETY: nginx passes valid pointers to the directive handlers, and
+                            // `cf.args` contains the directive name and the arguments, as many as
+                            // specified with NGX_CONF_TAKEn.
+                            let args: &[$crate::ffi::ngx_str_t] = unsafe { (*(*cf).args).as_slice() };
+                            let cf = unsafe { &mut *cf };
+                            let conf = unsafe { &mut *conf.cast::<$conf_ty>() };
+                            #[allow(unused_mut, unused_variables)]
+                            let mut it = args.iter().skip(1);
+
+                            $(
+                                let $arg: $arg_ty = {
+                                    let raw = it.next().expect("directive argument");
+                                    match $crate::core::ConfArg::from_conf_arg(raw) {
+                                        Ok(value) => value,
+                                        Err(err) => {
+                                            $crate::ngx_conf_log_error!(
+                                                $crate::ffi::NGX_LOG_EMERG,
+                                                cf,
+                                                "invalid value \"{}\" in \"{}\" directive, {}",
+                                                raw,
+                                                $directive,
+                                                err
+                                            );
+                                            return $crate::core::NGX_CONF_ERROR;
+                                        }
+                                    }
+                                };
+                            )*
+
+                            match typed(cf, conf, $( $arg, )*) {
+                                Ok(()) => $crate::core::NGX_CONF_OK,
+                                Err(err) => {
+                                    $crate::ngx_conf_log_error!(
+                                        $crate::ffi::NGX_LOG_EMERG,
+                                        cf,
+                                        "{}",
+                                        err
+                                    );
+                                    $crate::core::NGX_CONF_ERROR
+                                }
+                            }
+                        }
+                        handler as unsafe extern "C" fn(_, _, _) -> _
+                    }),
+                    conf: $conf,
+                    offset: 0,
+                    post: ::core::ptr::null_mut(),
+                },
+            )*
+            $crate::ffi::ngx_command_t::empty(),
+        ];
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_flag() {
+        assert_eq!(parse_flag(b"on"), Some(true));
+        assert_eq!(parse_flag(b"OFF"), Some(false));
+        assert_eq!(parse_flag(b"yes"), None);
+        assert_eq!(parse_flag(b""), None);
+    }
+
+    #[test]
+    fn test_conf_take() {
+        assert_eq!(conf_take(0), NGX_CONF_NOARGS as ngx_uint_t);
+        assert_eq!(conf_take(2), NGX_CONF_TAKE2 as ngx_uint_t);
+        assert_eq!(conf_take(7), NGX_CONF_TAKE7 as ngx_uint_t);
+    }
+}
@@ -1,5 +1,9 @@
+use core::error;
+use core::fmt;
 use core::ptr::NonNull;
 
+use crate::allocator::AllocError;
+use crate::core::Status;
 use crate::ffi::{ngx_core_conf_t, ngx_module_t};
 
 /// Trait for core-style modules.
@@ -84,6 +88,77 @@ unsafe impl CoreModuleMainConf for NgxCoreModule {
     type MainConf = ngx_core_conf_t;
 }
 
+/// MergeConfigError - configuration cannot be merged with levels above.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum MergeConfigError {
+    /// No value provided for configuration argument
+    NoValue,
+    /// Memory allocation failed while merging the values
+    NoMemory,
+}
+
+impl error::Error for MergeConfigError {}
+
+impl fmt::Display for MergeConfigError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MergeConfigError::NoValue => "no value".fmt(fmt),
+            MergeConfigError::NoMemory => "no memory".fmt(fmt),
+        }
+    }
+}
+
+impl From<AllocError> for MergeConfigError {
+    fn from(_: AllocError) -> Self {
+        MergeConfigError::NoMemory
+    }
+}
+
+/// ConfError - a directive argument cannot be compiled.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConfError {
+    /// Memory allocation failed.
+    NoMemory,
+    /// The value is invalid; the reason is logged by nginx.
+    InvalidValue,
+}
+
+impl error::Error for ConfError {}
+
+impl fmt::Display for ConfError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfError::NoMemory => "no memory".fmt(fmt),
+            ConfError::InvalidValue => "invalid value".fmt(fmt),
+        }
+    }
+}
+
+impl From<ConfError> for Status {
+    fn from(_: ConfError) -> Self {
+        Status::NGX_ERROR
+    }
+}
+
+/// The `Merge` trait provides a method for merging configuration down through each level.
+///
+/// A module configuration should implement this trait for setting its configuration throughout
+/// each level.
+pub trait Merge {
+    /// Module merge function.
+    ///
+    /// # Returns
+    /// Result, Ok on success or MergeConfigError on failure.
+    fn merge(&mut self, prev: &Self) -> Result<(), MergeConfigError>;
+}
+
+impl Merge for () {
+    fn merge(&mut self, _prev: &Self) -> Result<(), MergeConfigError> {
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     extern crate alloc;
@@ -0,0 +1,210 @@
+use core::ptr;
+
+use crate::core::{ConfArg, NgxMsec, NgxSec, NgxSize, NgxStr, Status};
+use crate::ffi::{NGX_LOG_EMERG, ngx_conf_t, ngx_str_t};
+use crate::ngx_conf_log_error;
+
+/// Trait for the enumerations accepted as a directive argument, similar to `ngx_conf_enum_t`.
+///
+/// ```
+/// # use ngx::core::ConfEnum;
+/// #[derive(Clone, Copy)]
+/// enum Mode {
+///     Strict,
+///     Relaxed,
+/// }
+///
+/// impl ConfEnum for Mode {
+///     const VALUES: &'static [(&'static str, Self)] =
+///         &[("strict", Mode::Strict), ("relaxed", Mode::Relaxed)];
+/// }
+/// ```
+pub trait ConfEnum: Copy + 'static {
+    /// Names of the values, compared ignoring ASCII case.
+    const VALUES: &'static [(&'static str, Self)];
+}
+
+/// Arguments of the directive currently processed by the configuration parser.
+///
+/// The typed accessors log the errors with the directive name at `NGX_LOG_EMERG` level, using the
+/// same messages as the nginx slot functions, and return `NGX_ERROR`. The directive handler
+/// should then return `NGX_CONF_ERROR`.
+///
+/// The arguments are indexed from 0, not including the directive name.
+///
+/// ```no_run
+/// # use core::ffi::{c_char, c_void};
+/// # use ngx::core::{ConfArgs, NGX_CONF_ERROR, NGX_CONF_OK};
+/// # use ngx::ffi::{ngx_command_t, ngx_conf_t};
+/// # struct ModuleConfig { enable: bool }
+/// unsafe extern "C" fn set_enable(
+///     cf: *mut ngx_conf_t,
+///     _cmd: *mut ngx_command_t,
+///     conf: *mut c_void,
+/// ) -> *mut c_char {
+///     let conf = unsafe { &mut *conf.cast::<ModuleConfig>() };
+///     let args = unsafe { ConfArgs::new(&*cf) };
+///
+///     match args.get_flag(0) {
+///         Ok(enable) => conf.enable = enable,
+///         Err(_) => return NGX_CONF_ERROR,
+///     }
+///     NGX_CONF_OK
+/// }
+/// ```
+#[derive(Clone, Copy)]
+pub struct ConfArgs<'a> {
+    cf: &'a ngx_conf_t,
+    args: &'a [ngx_str_t],
+}
+
+impl<'a> ConfArgs<'a> {
+    /// Creates a view of the arguments of the current directive.
+    ///
+    /// # Safety
+    ///
+    /// Must be called from a directive handler, while `cf.args` contains the tokens of the
+    /// directive.
+    pub unsafe fn new(cf: &'a ngx_conf_t) -> Self {
+        // SAFETY: the caller ensures that `cf.args` is a valid array of strings.
+        let args: &[ngx_str_t] = unsafe { (*cf.args).as_slice() };
+        Self { cf, args }
+    }
+
+    /// Returns the directive name.
+    pub fn name(&self) -> &'a NgxStr {
+        // SAFETY: the tokens are allocated from the configuration pool.
+        self.args.first().map_or(NgxStr::from_bytes(b""), |x| unsafe { NgxStr::from_ngx_str(*x) })
+    }
+
+    /// Returns the number of arguments, not including the directive name.
+    pub fn len(&self) -> usize {
+        self.args.len().saturating_sub(1)
+    }
+
+    /// Returns `true` if the directive has no arguments.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the arguments as raw strings.
+    pub fn as_slice(&self) -> &'a [ngx_str_t] {
+        self.args.get(1..).unwrap_or_default()
+    }
+
+    /// Returns the argument `i` as a raw string, logging an error if it is missing.
+    pub fn get_raw(&self, i: usize) -> Result<&'a ngx_str_t, Status> {
+        self.as_slice().get(i).ok_or_else(|| {
+            let name = self.name();
+            ngx_conf_log_error!(
+                NGX_LOG_EMERG,
+                self.cf_ptr(),
+                "invalid number of arguments in \"{name}\" directive"
+            );
+            Status::NGX_ERROR
+        })
+    }
+
+    /// Converts the argument `i` with [`ConfArg`].
+    pub fn get<T: ConfArg<'a>>(&self, i: usize) -> Result<T, Status> {
+        let arg = self.get_raw(i)?;
+        T::from_conf_arg(arg).map_err(|err| self.invalid(i, err.0))
+    }
+
+    /// Returns the argument `i` as a UTF-8 string.
+    pub fn get_str(&self, i: usize) -> Result<&'a str, Status> {
+        self.get(i)
+    }
+
+    /// Returns the argument `i` parsed as an `on` or `off` flag.
+    pub fn get_flag(&self, i: usize) -> Result<bool, Status> {
+        self.get(i)
+    }
+
+    /// Returns the argument `i` parsed as a size, e.g. `10m`.
+    pub fn get_size(&self, i: usize) -> Result<NgxSize, Status> {
+        self.get(i)
+    }
+
+    /// Returns the argument `i` parsed as a time interval in milliseconds, e.g. `1s500ms`.
+    pub fn get_msec(&self, i: usize) -> Result<NgxMsec, Status> {
+        self.get(i)
+    }
+
+    /// Returns the argument `i` parsed as a time interval in seconds, e.g. `1h`.
+    pub fn get_sec(&self, i: usize) -> Result<NgxSec, Status> {
+        self.get(i)
+    }
+
+    /// Returns the argument `i` parsed as one of the values of `T`.
+    pub fn get_enum<T: ConfEnum>(&self, i: usize) -> Result<T, Status> {
+        let arg = self.get_raw(i)?.as_bytes();
+        match T::VALUES.iter().find(|(name, _)| name.as_bytes().eq_ignore_ascii_case(arg)) {
+            Some((_, value)) => Ok(*value),
+            None => {
+                let name = self.name();
+                ngx_conf_log_error!(
+                    NGX_LOG_EMERG,
+                    self.cf_ptr(),
+                    "invalid value \"{}\" in \"{name}\" directive, it must be {}",
+                    NgxStr::from_bytes(arg),
+                    EnumValues(T::VALUES)
+                );
+                Err(Status::NGX_ERROR)
+            }
+        }
+    }
+
+    /// Logs an invalid value of the argument `i` with the `reason`, and returns `NGX_ERROR`.
+    ///
+    /// The message follows the nginx format: `invalid value "..." in "..." directive, <reason>`.
+    pub fn invalid(&self, i: usize, reason: &str) -> Status {
+        let name = self.name();
+        let value = self.as_slice().get(i).copied().unwrap_or(ngx_str_t::empty());
+        ngx_conf_log_error!(
+            NGX_LOG_EMERG,
+            self.cf_ptr(),
+            "invalid value \"{value}\" in \"{name}\" directive, {reason}"
+        );
+        Status::NGX_ERROR
+    }
+
+    fn cf_ptr(&self) -> *mut ngx_conf_t {
+        // The logging functions do not modify the configuration.
+        ptr::from_ref(self.cf).cast_mut()
+    }
+}
+
+/// Formats the enumeration values as `"a", "b" or "c"`.
+struct EnumValues<T>(&'static [(&'static str, T)]);
+
+impl<T> core::fmt::Display for EnumValues<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let n = self.0.len();
+        for (i, (name, _)) in self.0.iter().enumerate() {
+            match i {
+                0 => {}
+                _ if i + 1 == n => f.write_str(" or ")?,
+                _ => f.write_str(", ")?,
+            }
+            write!(f, "\"{name}\"")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use std::string::ToString;
+
+    use super::EnumValues;
+
+    #[test]
+    fn test_enum_values() {
+        let fmt = |values: &'static [(&'static str, u8)]| EnumValues(values).to_string();
+        assert_eq!(fmt(&[("a", 0)]), r#""a""#);
+        assert_eq!(fmt(&[("a", 0), ("b", 1)]), r#""a" or "b""#);
+        assert_eq!(fmt(&[("a", 0), ("b", 1), ("c", 2)]), r#""a", "b" or "c""#);
+    }
+}
@@ -0,0 +1,199 @@
+use core::ffi::c_void;
+use core::fmt;
+use core::mem;
+use core::ptr;
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+
+use crate::core::{NgxStr, Status, conf_file_position};
+use crate::ffi::{
+    NGX_LOG_EMERG, NGX_LOG_NOTICE, ngx_conf_t, ngx_cycle_t, ngx_int_t, ngx_log_t,
+    ngx_pool_cleanup_add,
+};
+use crate::{ngx_conf_log_error, ngx_log_error};
+
+/// When a check registered with [`conf_check`] is run.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CheckMode {
+    /// The check is run in the directive handler.
+    #[default]
+    Immediate,
+    /// The check is run by [`conf_check_handler`] once the configuration block is parsed.
+    Deferred,
+}
+
+/// Context of a configuration check registered with [`conf_check`].
+pub struct ConfCheck<'a> {
+    cycle: &'a mut ngx_cycle_t,
+    log: *mut ngx_log_t,
+    file: &'a [u8],
+    line: usize,
+}
+
+impl ConfCheck<'_> {
+    /// Returns the configuration cycle being loaded.
+    pub fn cycle(&mut self) -> &mut ngx_cycle_t {
+        self.cycle
+    }
+
+    /// Returns the name of the configuration file with the checked directive.
+    pub fn file(&self) -> &NgxStr {
+        NgxStr::from_bytes(self.file)
+    }
+
+    /// Returns the line of the checked directive.
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    /// Logs the progress of a long check, e.g. the number of the parsed rules, with the location
+    /// of the directive.
+    pub fn progress(&self, args: fmt::Arguments<'_>) {
+        ngx_log_error!(NGX_LOG_NOTICE, self.log, "{} in {}:{}", args, self.file(), self.line);
+    }
+
+    fn error(&self, err: &dyn fmt::Display) {
+        ngx_log_error!(NGX_LOG_EMERG, self.log, "{} in {}:{}", err, self.file(), self.line);
+    }
+}
+
+/// Registers a check of a directive against the external resources, e.g. a file that must exist
+/// or a large ruleset that must be parsed.
+///
+/// With [`CheckMode::Immediate`] the check is run right away, and the error is logged with the
+/// location of the directive as usual. With [`CheckMode::Deferred`] the check is recorded in the
+/// cycle pool with the location of the directive and is run by [`conf_check_handler`], which
+/// must be set as the `postconfiguration` handler of the module. The deferred checks are run in
+/// the order of registration once the `http` or `stream` block is parsed and merged, so that the
+/// checks of a huge configuration do not slow down the parser and a syntax error within the block
+/// is reported without waiting for them. All the pending checks are run, and each failure is
+/// logged with the location of the directive before the configuration is rejected. On reload,
+/// nginx keeps running with the old configuration.
+///
+/// A check that is not run because the configuration fails to load earlier is dropped with the
+/// cycle pool.
+///
+/// ```no_run
+/// # use ngx::core::{CheckMode, ConfCheck, Status, conf_check};
+/// # use ngx::ffi::ngx_conf_t;
+/// # fn parse_rules(path: &[u8]) -> Result<Vec<u32>, &'static str> { unimplemented!() }
+/// # fn f(cf: &mut ngx_conf_t, path: Vec<u8>) -> Result<(), Status> {
+/// // in the handler of the `rules` directive
+/// conf_check(cf, CheckMode::Deferred, move |check: &mut ConfCheck| {
+///     let rules = parse_rules(&path)?;
+///     check.progress(format_args!("loaded {} rules", rules.len()));
+///     Ok::<_, &str>(())
+/// })
+/// # }
+///
+/// // ngx_http_module_t {
+/// //     postconfiguration: Some(ngx::core::conf_check_handler),
+/// //     ..
+/// // }
+/// ```
+pub fn conf_check<F, E>(cf: &mut ngx_conf_t, mode: CheckMode, check: F) -> Result<(), Status>
+where
+    F: FnOnce(&mut ConfCheck<'_>) -> Result<(), E> + 'static,
+    E: fmt::Display,
+{
+    let (file, line) = match conf_file_position(cf) {
+        Some((file, line)) => (file.as_bytes(), line),
+        None => (&b""[..], 0),
+    };
+
+    if mode == CheckMode::Immediate {
+        // SAFETY: the cycle is valid in a directive handler.
+        let cycle = unsafe { &mut *cf.cycle };
+        let mut ctx = ConfCheck { cycle, log: cf.log, file, line };
+        if let Err(err) = check(&mut ctx) {
+            ngx_conf_log_error!(NGX_LOG_EMERG, cf, "{}", err);
+            return Err(Status::NGX_ERROR);
+        }
+        return Ok(());
+    }
+
+    let mut name = Vec::new();
+    name.try_reserve_exact(file.len()).map_err(|_| Status::NGX_ERROR)?;
+    name.extend_from_slice(file);
+
+    let record = Record {
+        check: Some(Box::new(move |ctx: &mut ConfCheck<'_>| {
+            check(ctx).map_err(|err| ctx.error(&err))
+        })),
+        file: name,
+        line,
+    };
+
+    // SAFETY: the cycle pool is valid in a directive handler.
+    let cln = unsafe { ngx_pool_cleanup_add((*cf.cycle).pool, mem::size_of::<Record>()) };
+    let Some(cln) = (unsafe { cln.as_mut() }) else {
+        return Err(Status::NGX_ERROR);
+    };
+    // SAFETY: the cleanup data is allocated above with the size of `Record` and the alignment of
+    // the pool.
+    unsafe { cln.data.cast::<Record>().write(record) };
+    cln.handler = Some(conf_check_cleanup);
+
+    Ok(())
+}
+
+/// `postconfiguration` handler running the checks deferred with [`conf_check`].
+///
+/// The handler runs the pending checks registered so far, and can be set for several HTTP and
+/// stream modules of a crate: each check is run once. A module with its own `postconfiguration`
+/// handler calls this function from it.
+///
+/// # Safety
+///
+/// Must only be called by nginx as the `postconfiguration` handler of a module, or with the
+/// configuration passed to such a handler.
+pub unsafe extern "C" fn conf_check_handler(cf: *mut ngx_conf_t) -> ngx_int_t {
+    // SAFETY: nginx calls the handler with the configuration being parsed.
+    let cf = unsafe { &mut *cf };
+    // SAFETY: the cycle is valid while the configuration is parsed.
+    let cycle = unsafe { &mut *cf.cycle };
+
+    // The cleanups are in the reverse order of registration.
+    let mut records: Vec<*mut Record> = Vec::new();
+    // SAFETY: the cleanup list is owned by the cycle pool.
+    let mut cln = unsafe { (*cycle.pool).cleanup };
+    while let Some(c) = unsafe { cln.as_ref() } {
+        if c.handler == Some(conf_check_cleanup as unsafe extern "C" fn(_)) {
+            if records.try_reserve(1).is_err() {
+                return Status::NGX_ERROR.into();
+            }
+            records.push(c.data.cast());
+        }
+        cln = c.next;
+    }
+
+    let log = cf.log;
+    let mut failed = false;
+
+    for record in records.into_iter().rev() {
+        // SAFETY: the record is initialized by `conf_check` and lives until the pool is destroyed.
+        let record = unsafe { &mut *record };
+        let Some(check) = record.check.take() else {
+            continue;
+        };
+        let mut ctx = ConfCheck { cycle: &mut *cycle, log, file: &record.file, line: record.line };
+        failed |= check(&mut ctx).is_err();
+    }
+
+    if failed { Status::NGX_ERROR.into() } else { Status::NGX_OK.into() }
+}
+
+type CheckFn = dyn FnOnce(&mut ConfCheck<'_>) -> Result<(), ()>;
+
+struct Record {
+    check: Option<Box<CheckFn>>,
+    file: Vec<u8>,
+    line: usize,
+}
+
+/// Drops a deferred check with the cycle pool.
+unsafe extern "C" fn conf_check_cleanup(data: *mut c_void) {
+    // SAFETY: the data is the record written by `conf_check`.
+    unsafe { ptr::drop_in_place(data.cast::<Record>()) };
+}
@@ -0,0 +1,84 @@
+use core::ffi::{c_char, c_void};
+use core::fmt;
+
+use crate::core::{NGX_CONF_ERROR, NGX_CONF_OK, NgxStr, Status};
+use crate::ffi::{
+    NGX_LOG_EMERG, NGX_OK, ngx_command_t, ngx_conf_full_name, ngx_conf_parse, ngx_conf_t,
+    ngx_int_t, ngx_str_t,
+};
+use crate::ngx_conf_log_error;
+
+/// Parses an auxiliary configuration file with the nginx configuration tokenizer.
+///
+/// The `handler` is invoked for each simple directive in the file with the list of the directive
+/// tokens, including the directive name. Blocks are not allowed in the file and will be rejected
+/// by the parser.
+///
+/// Errors returned from the `handler` are logged at `NGX_LOG_EMERG` level with the name of the
+/// file and the line number, consistent with the errors in the main configuration. The relative
+/// `path` is resolved against the configuration prefix, as done for the `include` directive.
+///
+/// This function should be called from a directive handler. The previous state of `cf` is restored
+/// before returning.
+pub fn parse_conf_file<F, E>(
+    cf: &mut ngx_conf_t,
+    path: &ngx_str_t,
+    mut handler: F,
+) -> Result<(), Status>
+where
+    F: FnMut(&mut ngx_conf_t, &[ngx_str_t]) -> Result<(), E>,
+    E: fmt::Display,
+{
+    let mut file = *path;
+    if unsafe { ngx_conf_full_name(cf.cycle, &raw mut file, 1) } != NGX_OK as ngx_int_t {
+        return Err(Status::NGX_ERROR);
+    }
+
+    let saved = *cf;
+
+    cf.handler = Some(conf_file_handler::<F, E>);
+    cf.handler_conf = (&raw mut handler).cast();
+
+    let rv = unsafe { ngx_conf_parse(cf, &raw mut file) };
+
+    *cf = saved;
+
+    if rv == NGX_CONF_OK { Ok(()) } else { Err(Status::NGX_ERROR) }
+}
+
+/// Returns the name of the configuration file and the line currently processed by the parser.
+pub fn conf_file_position(cf: &ngx_conf_t) -> Option<(&NgxStr, usize)> {
+    // SAFETY: `conf_file` is either NULL or points to a valid file description owned by the
+    // parser.
+    let conf_file = unsafe { cf.conf_file.as_ref()? };
+    // SAFETY: the file name is allocated from the configuration pool.
+    let name = unsafe { NgxStr::from_ngx_str(conf_file.file.name) };
+    Some((name, conf_file.line))
+}
+
+unsafe extern "C" fn conf_file_handler<F, E>(
+    cf: *mut ngx_conf_t,
+    _dummy: *mut ngx_command_t,
+    conf: *mut c_void,
+) -> *mut c_char
+where
+    F: FnMut(&mut ngx_conf_t, &[ngx_str_t]) -> Result<(), E>,
+    E: fmt::Display,
+{
+    // SAFETY: the handler is installed by parse_conf_file with `handler_conf` pointing to `F`,
+    // which outlives the ngx_conf_parse call.
+    let handler = unsafe { &mut *conf.cast::<F>() };
+    let cf = unsafe { &mut *cf };
+
+    // SAFETY: `cf.args` contains the tokens of the current directive and is not modified
+    // until the handler returns.
+    let args: &[ngx_str_t] = unsafe { (*cf.args).as_slice() };
+
+    match handler(cf, args) {
+        Ok(()) => NGX_CONF_OK,
+        Err(err) => {
+            ngx_conf_log_error!(NGX_LOG_EMERG, cf, "{err}");
+            NGX_CONF_ERROR
+        }
+    }
+}
@@ -0,0 +1,139 @@
+use core::ffi::{c_char, c_void};
+use core::{ptr, slice};
+
+use crate::allocator::AllocError;
+use crate::collections::Vec;
+use crate::core::{ConfUnset, NGX_CONF_ERROR, NGX_CONF_OK, Pool};
+use crate::ffi::{ngx_command_t, ngx_conf_t, ngx_str_t};
+
+/// Merge strategy for [`ConfArgsList`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ListMerge {
+    /// Use the values from the previous level only if the directive was not specified on the
+    /// current level, as done by nginx for `allow`/`deny`, `add_header` and similar directives.
+    #[default]
+    Override,
+    /// Append the values of the current level to the values from the previous level.
+    Concat,
+}
+
+/// Arguments of a directive that may be specified multiple times on the same level.
+///
+/// Each occurrence of the directive adds an entry with its arguments, excluding the directive name.
+/// The entries and the copies of the argument lists are allocated from the configuration pool.
+///
+/// Use [`conf_args_list_slot`] as the directive handler to fill a field of this type in the module
+/// configuration.
+#[derive(Debug, Default)]
+pub struct ConfArgsList {
+    entries: Option<Vec<&'static [ngx_str_t], Pool>>,
+}
+
+impl ConfArgsList {
+    /// Returns `true` if the directive was not specified.
+    pub fn is_unset(&self) -> bool {
+        self.entries.is_none()
+    }
+
+    /// Returns the number of directive occurrences.
+    pub fn len(&self) -> usize {
+        self.entries.as_ref().map_or(0, |x| x.len())
+    }
+
+    /// Returns `true` if there are no directive occurrences.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the arguments of the `index`-th directive occurrence.
+    pub fn get(&self, index: usize) -> Option<&[ngx_str_t]> {
+        self.entries.as_ref()?.get(index).copied()
+    }
+
+    /// Returns an iterator over the arguments of each directive occurrence.
+    pub fn iter(&self) -> impl Iterator<Item = &[ngx_str_t]> {
+        self.entries.iter().flat_map(|x| x.iter().copied())
+    }
+
+    /// Adds an entry with a copy of `args`, allocated from `pool`.
+    pub fn push(&mut self, pool: &Pool, args: &[ngx_str_t]) -> Result<(), AllocError> {
+        let copy = if args.is_empty() {
+            &[]
+        } else {
+            let p = pool.alloc(core::mem::size_of_val(args)).cast::<ngx_str_t>();
+            if p.is_null() {
+                return Err(AllocError);
+            }
+            // SAFETY: `p` is a new allocation large enough to hold `args`.
+            unsafe {
+                ptr::copy_nonoverlapping(args.as_ptr(), p, args.len());
+                slice::from_raw_parts(p, args.len())
+            }
+        };
+
+        let entries = self.entries.get_or_insert_with(|| Vec::new_in(pool.clone()));
+        entries.try_reserve(1).map_err(|_| AllocError)?;
+        entries.push(copy);
+        Ok(())
+    }
+
+    /// Merges the entries with the values from the previous configuration level.
+    pub fn merge_with(&mut self, prev: &Self, mode: ListMerge) -> Result<(), AllocError> {
+        let Some(prev_entries) = prev.entries.as_ref() else {
+            return Ok(());
+        };
+
+        let current = match (self.entries.as_deref(), mode) {
+            (None, _) => &[][..],
+            (Some(_), ListMerge::Override) => return Ok(()),
+            (Some(entries), ListMerge::Concat) => entries,
+        };
+
+        let mut entries = Vec::new_in(prev_entries.allocator().clone());
+        entries.try_reserve_exact(prev_entries.len() + current.len()).map_err(|_| AllocError)?;
+        entries.extend_from_slice(prev_entries);
+        entries.extend_from_slice(current);
+        self.entries = Some(entries);
+
+        Ok(())
+    }
+}
+
+impl ConfUnset for ConfArgsList {
+    fn is_unset(&self) -> bool {
+        ConfArgsList::is_unset(self)
+    }
+}
+
+impl crate::core::Merge for ConfArgsList {
+    fn merge(&mut self, prev: &Self) -> Result<(), crate::core::MergeConfigError> {
+        Ok(self.merge_with(prev, ListMerge::Override)?)
+    }
+}
+
+/// Directive handler appending the arguments to a [`ConfArgsList`] field of the configuration.
+///
+/// The field is located at `offset` of the command, as with the nginx slot functions.
+///
+/// # Safety
+///
+/// The field at `cmd.offset` in `conf` must have the type [`ConfArgsList`].
+pub unsafe extern "C" fn conf_args_list_slot(
+    cf: *mut ngx_conf_t,
+    cmd: *mut ngx_command_t,
+    conf: *mut c_void,
+) -> *mut c_char {
+    // SAFETY: nginx passes valid pointers to the directive handlers.
+    let (cf, cmd) = unsafe { (&mut *cf, &*cmd) };
+    let field = unsafe { &mut *conf.byte_add(cmd.offset).cast::<ConfArgsList>() };
+
+    // SAFETY: `cf.args` contains the directive name and arguments.
+    let args: &[ngx_str_t] = unsafe { (*cf.args).as_slice() };
+    // SAFETY: the configuration pool is valid for the duration of the configuration parsing.
+    let pool = unsafe { Pool::from_ngx_pool(cf.pool) };
+
+    match field.push(&pool, &args[1..]) {
+        Ok(()) => NGX_CONF_OK,
+        Err(_) => NGX_CONF_ERROR,
+    }
+}
@@ -0,0 +1,73 @@
+use crate::ffi::{ngx_int_t, ngx_uint_t};
+
+/// Configuration values with a distinct "unset" state.
+///
+/// Module configurations are created with all the fields unset and the values are assigned by
+/// the directive handlers. A directive handler may check that the field is still unset to reject
+/// duplicate directives, similar to the following check in nginx modules:
+///
+/// ```c
+/// if (conf->x != NGX_CONF_UNSET) {
+///     return "is duplicate";
+/// }
+/// ```
+///
+/// # Example
+///
+/// ```
+/// # use core::ffi::{c_char, c_void};
+/// # use ngx::core::{ConfUnset, NGX_CONF_DUPLICATE, NGX_CONF_OK};
+/// # use ngx::ffi::{ngx_command_t, ngx_conf_t};
+/// struct ModuleConf {
+///     enabled: ngx::ffi::ngx_flag_t,
+/// }
+///
+/// unsafe extern "C" fn set_enabled(
+///     _cf: *mut ngx_conf_t,
+///     _cmd: *mut ngx_command_t,
+///     conf: *mut c_void,
+/// ) -> *mut c_char {
+///     let conf = unsafe { &mut *conf.cast::<ModuleConf>() };
+///     if conf.enabled.is_set() {
+///         return NGX_CONF_DUPLICATE;
+///     }
+///     conf.enabled = 1;
+///     NGX_CONF_OK
+/// }
+/// ```
+pub trait ConfUnset {
+    /// Returns `true` if the value is not set.
+    fn is_unset(&self) -> bool;
+
+    /// Returns `true` if the value is already set.
+    fn is_set(&self) -> bool {
+        !self.is_unset()
+    }
+}
+
+/// `NGX_CONF_UNSET`, also used for `ngx_flag_t`.
+impl ConfUnset for ngx_int_t {
+    fn is_unset(&self) -> bool {
+        *self == -1
+    }
+}
+
+/// `NGX_CONF_UNSET_UINT`, `NGX_CONF_UNSET_SIZE` and `NGX_CONF_UNSET_MSEC`.
+impl ConfUnset for ngx_uint_t {
+    fn is_unset(&self) -> bool {
+        *self == ngx_uint_t::MAX
+    }
+}
+
+/// `NGX_CONF_UNSET_PTR`.
+impl<T> ConfUnset for *mut T {
+    fn is_unset(&self) -> bool {
+        self.addr() == usize::MAX
+    }
+}
+
+impl<T: ConfUnset> ConfUnset for Option<T> {
+    fn is_unset(&self) -> bool {
+        self.as_ref().is_none_or(T::is_unset)
+    }
+}
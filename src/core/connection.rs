@@ -0,0 +1,477 @@
+use core::net::{Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6};
+use core::{mem, ptr};
+
+use crate::ffi::{
+    AF_INET, AF_INET6, NGX_OK, SOCK_DGRAM, SOCK_STREAM, getsockname, ngx_connection_local_sockaddr,
+    ngx_connection_t, ngx_int_t, ngx_listening_t, ngx_log_t, ngx_reusable_connection, ngx_socket_t,
+    ngx_uint_t, sockaddr, sockaddr_in, sockaddr_in6, sockaddr_storage, socklen_t,
+};
+
+/// Type of a connection socket.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SocketType {
+    /// `SOCK_STREAM`, e.g. TCP or a stream UNIX-domain socket.
+    Stream,
+    /// `SOCK_DGRAM`, e.g. UDP.
+    Datagram,
+    /// Other socket types.
+    Other(i32),
+}
+
+/// Wrapper struct for an [`ngx_connection_t`] pointer.
+///
+/// [`ngx_connection_t`]: https://nginx.org/en/docs/dev/development_guide.html#connection
+#[repr(transparent)]
+pub struct Connection(ngx_connection_t);
+
+impl AsRef<ngx_connection_t> for Connection {
+    fn as_ref(&self) -> &ngx_connection_t {
+        &self.0
+    }
+}
+
+impl AsMut<ngx_connection_t> for Connection {
+    fn as_mut(&mut self) -> &mut ngx_connection_t {
+        &mut self.0
+    }
+}
+
+impl Connection {
+    /// Creates a [`Connection`] from an [`ngx_connection_t`] pointer.
+    ///
+    /// # Safety
+    ///
+    /// The caller must provide a valid non-null pointer to an `ngx_connection_t`.
+    pub unsafe fn from_ptr<'a>(c: *const ngx_connection_t) -> &'a Self {
+        unsafe { &*c.cast::<Self>() }
+    }
+
+    /// Creates a mutable [`Connection`] from an [`ngx_connection_t`] pointer.
+    ///
+    /// # Safety
+    ///
+    /// The caller must provide a valid non-null pointer to an `ngx_connection_t`.
+    pub unsafe fn from_ptr_mut<'a>(c: *mut ngx_connection_t) -> &'a mut Self {
+        unsafe { &mut *c.cast::<Self>() }
+    }
+
+    /// Returns a raw pointer to the connection.
+    pub fn as_ptr(&self) -> *mut ngx_connection_t {
+        ptr::from_ref(&self.0).cast_mut()
+    }
+
+    /// Returns the socket descriptor of the connection.
+    pub fn fd(&self) -> ngx_socket_t {
+        self.0.fd
+    }
+
+    /// Returns the connection number, as in the `*N` prefix of the error log messages.
+    pub fn number(&self) -> ngx_uint_t {
+        self.0.number as _
+    }
+
+    /// Returns the type of the connection socket.
+    pub fn socket_type(&self) -> SocketType {
+        match self.0.type_ {
+            x if x == SOCK_STREAM as i32 => SocketType::Stream,
+            x if x == SOCK_DGRAM as i32 => SocketType::Datagram,
+            x => SocketType::Other(x),
+        }
+    }
+
+    /// Returns the log of the connection.
+    pub fn log(&self) -> *mut ngx_log_t {
+        self.0.log
+    }
+
+    /// Returns the address of the remote peer.
+    ///
+    /// Returns `None` for the address families other than `AF_INET` and `AF_INET6`, e.g. for a
+    /// UNIX-domain socket.
+    pub fn remote_addr(&self) -> Option<SocketAddr> {
+        if self.0.sockaddr.is_null() {
+            return None;
+        }
+        // SAFETY: `sockaddr` is either NULL or a valid address of the connection.
+        unsafe { sockaddr_to_socket_addr(self.0.sockaddr) }
+    }
+
+    /// Returns the local address of the connection.
+    ///
+    /// The address of a connection accepted on a wildcard listening socket is obtained with
+    /// `getsockname()` on the first call. Returns `None` on failure, or for the address families
+    /// other than `AF_INET` and `AF_INET6`.
+    pub fn local_addr(&mut self) -> Option<SocketAddr> {
+        // The outgoing connections created with `ngx_event_connect_peer` have no pool to store
+        // the address in.
+        if self.0.local_sockaddr.is_null() && self.0.pool.is_null() {
+            return socket_local_addr(self.0.fd);
+        }
+        // SAFETY: the connection is valid, and the address is stored in the connection pool.
+        let rc = unsafe { ngx_connection_local_sockaddr(&raw mut self.0, ptr::null_mut(), 0) };
+        if rc != NGX_OK as ngx_int_t || self.0.local_sockaddr.is_null() {
+            return None;
+        }
+        // SAFETY: `local_sockaddr` is set by ngx_connection_local_sockaddr on success.
+        unsafe { sockaddr_to_socket_addr(self.0.local_sockaddr) }
+    }
+
+    /// Returns the flags of the data buffered in the output filters, e.g.
+    /// `NGX_HTTP_SSI_BUFFERED` or `NGX_LOWLEVEL_BUFFERED`.
+    pub fn buffered(&self) -> ngx_uint_t {
+        self.0.buffered() as _
+    }
+
+    /// Returns `true` if any output data is buffered.
+    pub fn is_buffered(&self) -> bool {
+        self.0.buffered() != 0
+    }
+
+    /// Adds the connection to the queue of the reusable connections, or removes it.
+    ///
+    /// An idle connection kept across requests, e.g. a keepalive connection, should be marked as
+    /// reusable while it waits for data. When nginx runs out of free connections, it closes the
+    /// least recently used reusable connections: the `close` flag is set, see
+    /// [`Connection::is_closing`], and the read event handler is called. The handler must then
+    /// close the connection.
+    ///
+    /// The connection must be removed from the queue before it is closed or used again.
+    pub fn set_reusable(&mut self, reusable: bool) {
+        // SAFETY: the connection is valid.
+        unsafe { ngx_reusable_connection(&raw mut self.0, reusable as ngx_uint_t) }
+    }
+
+    /// Returns `true` if the connection is in the queue of the reusable connections.
+    pub fn is_reusable(&self) -> bool {
+        self.0.reusable() != 0
+    }
+
+    /// Marks the connection as idle, so that it is closed on a graceful shutdown of the worker
+    /// process.
+    ///
+    /// As for the reusable connections, the `close` flag is set and the read event handler is
+    /// called.
+    pub fn set_idle(&mut self, idle: bool) {
+        self.0.set_idle(idle as _);
+    }
+
+    /// Returns `true` if the connection is marked as idle.
+    pub fn is_idle(&self) -> bool {
+        self.0.idle() != 0
+    }
+
+    /// Returns the listening socket that accepted the connection.
+    pub fn listening(&self) -> Option<&ngx_listening_t> {
+        // SAFETY: the listening sockets are valid for the lifetime of the cycle.
+        unsafe { self.0.listening.as_ref() }
+    }
+
+    /// Returns the data read from the connection and not yet processed, e.g. the beginning of
+    /// the request received with the connection when the deferred accept is used.
+    ///
+    /// The buffer is allocated by the protocol handler, on the first read from the connection.
+    pub fn buffer(&self) -> Option<&[u8]> {
+        // SAFETY: `buffer` is either NULL or allocated from the connection pool.
+        let b = unsafe { self.0.buffer.as_ref()? };
+        if b.pos.is_null() {
+            return None;
+        }
+        // SAFETY: the unprocessed data is between `pos` and `last`.
+        let len = unsafe { b.last.offset_from(b.pos) } as usize;
+        Some(unsafe { core::slice::from_raw_parts(b.pos, len) })
+    }
+
+    /// Returns `true` if nginx requested to close the connection, e.g. to reuse it for a new
+    /// client connection or on a graceful shutdown.
+    pub fn is_closing(&self) -> bool {
+        self.0.close() != 0
+    }
+}
+
+/// Converts a socket address of the `AF_INET` or `AF_INET6` family into [`SocketAddr`].
+///
+/// # Safety
+///
+/// `p` must point to a valid socket address of the family specified in `sa_family`.
+pub(crate) unsafe fn sockaddr_to_socket_addr(p: *const sockaddr) -> Option<SocketAddr> {
+    match u32::from(unsafe { (*p).sa_family }) {
+        AF_INET => {
+            let sin = unsafe { &*p.cast::<sockaddr_in>() };
+            let ip = Ipv4Addr::from(u32::from_be(sin.sin_addr.s_addr));
+            Some(SocketAddrV4::new(ip, u16::from_be(sin.sin_port)).into())
+        }
+        AF_INET6 => {
+            let sin6 = unsafe { &*p.cast::<sockaddr_in6>() };
+            // SAFETY: `in6_addr` is a union of byte arrays of the same size.
+            let octets: [u8; 16] = unsafe { mem::transmute(sin6.sin6_addr) };
+            let ip = Ipv6Addr::from(octets);
+            let port = u16::from_be(sin6.sin6_port);
+            Some(
+                SocketAddrV6::new(ip, port, u32::from_be(sin6.sin6_flowinfo), sin6.sin6_scope_id)
+                    .into(),
+            )
+        }
+        _ => None,
+    }
+}
+
+/// Returns the local address of the socket with `getsockname()`.
+pub(crate) fn socket_local_addr(fd: ngx_socket_t) -> Option<SocketAddr> {
+    // SAFETY: an all-zero value is a valid sockaddr_storage.
+    let mut addr: sockaddr_storage = unsafe { mem::zeroed() };
+    let mut len = mem::size_of_val(&addr) as socklen_t;
+    // SAFETY: the buffer is large enough for any address family.
+    if unsafe { getsockname(fd, (&raw mut addr).cast(), &raw mut len) } == -1 {
+        return None;
+    }
+    // SAFETY: the address family determines the layout of the returned address.
+    unsafe { sockaddr_to_socket_addr((&raw const addr).cast()) }
+}
+
+/// Converts [`SocketAddr`] into a socket address of the `AF_INET` or `AF_INET6` family.
+///
+/// Returns the length of the address written to `out`.
+pub(crate) fn socket_addr_to_sockaddr(addr: &SocketAddr, out: &mut sockaddr_storage) -> socklen_t {
+    // SAFETY: an all-zero value is a valid sockaddr_storage.
+    *out = unsafe { mem::zeroed() };
+
+    match addr {
+        SocketAddr::V4(addr) => {
+            // SAFETY: sockaddr_storage is large enough and suitably aligned for sockaddr_in.
+            let sin = unsafe { &mut *ptr::from_mut(out).cast::<sockaddr_in>() };
+            sin.sin_family = AF_INET as _;
+            sin.sin_port = addr.port().to_be();
+            sin.sin_addr.s_addr = u32::from(*addr.ip()).to_be();
+            mem::size_of::<sockaddr_in>() as socklen_t
+        }
+        SocketAddr::V6(addr) => {
+            // SAFETY: sockaddr_storage is large enough and suitably aligned for sockaddr_in6.
+            let sin6 = unsafe { &mut *ptr::from_mut(out).cast::<sockaddr_in6>() };
+            sin6.sin6_family = AF_INET6 as _;
+            sin6.sin6_port = addr.port().to_be();
+            sin6.sin6_flowinfo = addr.flowinfo().to_be();
+            sin6.sin6_scope_id = addr.scope_id();
+            // SAFETY: `in6_addr` is a union of byte arrays of the same size.
+            sin6.sin6_addr = unsafe { mem::transmute::<[u8; 16], _>(addr.ip().octets()) };
+            mem::size_of::<sockaddr_in6>() as socklen_t
+        }
+    }
+}
+
+#[cfg(ngx_os = "linux")]
+mod original_dst {
+    use core::mem;
+    use core::net::SocketAddr;
+
+    use crate::core::Status;
+    use crate::ffi::{
+        AF_INET, AF_INET6, NGX_OK, SOCK_STREAM, getsockopt, ngx_connection_local_sockaddr,
+        ngx_int_t, sockaddr_storage, socklen_t,
+    };
+
+    use super::{Connection, sockaddr_to_socket_addr};
+
+    // <linux/netfilter_ipv4.h> and <linux/netfilter_ipv6/ip6_tables.h>
+    const SOL_IP: i32 = 0;
+    const SOL_IPV6: i32 = 41;
+    const SO_ORIGINAL_DST: i32 = 80;
+    const IP6T_SO_ORIGINAL_DST: i32 = 80;
+
+    impl Connection {
+        /// Returns the original destination address of a connection redirected with the
+        /// netfilter `REDIRECT` or `DNAT` targets.
+        ///
+        /// Returns [`Status::NGX_DECLINED`] if the connection is not a TCP connection over IPv4 or
+        /// IPv6, or the original destination is not available.
+        pub fn original_dst(&mut self) -> Result<SocketAddr, Status> {
+            if self.0.type_ != SOCK_STREAM as i32 {
+                return Err(Status::NGX_DECLINED);
+            }
+
+            let c = &raw mut self.0;
+            if unsafe { ngx_connection_local_sockaddr(c, core::ptr::null_mut(), 0) }
+                != NGX_OK as ngx_int_t
+            {
+                return Err(Status::NGX_ERROR);
+            }
+
+            // SAFETY: `local_sockaddr` is set by ngx_connection_local_sockaddr on success.
+            let (level, optname) = match u32::from(unsafe { (*self.0.local_sockaddr).sa_family }) {
+                AF_INET => (SOL_IP, SO_ORIGINAL_DST),
+                AF_INET6 => (SOL_IPV6, IP6T_SO_ORIGINAL_DST),
+                _ => return Err(Status::NGX_DECLINED),
+            };
+
+            // SAFETY: an all-zero value is a valid sockaddr_storage.
+            let mut addr: sockaddr_storage = unsafe { mem::zeroed() };
+            let mut len = mem::size_of_val(&addr) as socklen_t;
+
+            let rc = unsafe {
+                getsockopt(self.0.fd, level, optname, (&raw mut addr).cast(), &raw mut len)
+            };
+            if rc == -1 {
+                return Err(Status::NGX_DECLINED);
+            }
+
+            // SAFETY: the address family determines the layout of the returned address.
+            unsafe { sockaddr_to_socket_addr((&raw const addr).cast()) }.ok_or(Status::NGX_DECLINED)
+        }
+    }
+}
+
+#[cfg(ngx_feature = "have_deferred_accept")]
+mod deferred_accept {
+    use super::Connection;
+
+    impl Connection {
+        /// Returns `true` if the connection was accepted on a listening socket with the deferred
+        /// accept, i.e. the `deferred` parameter of the `listen` directive on Linux, or the
+        /// `accept_filter` parameter on FreeBSD.
+        ///
+        /// Such connections are only accepted once the data is received, and the read event of
+        /// the connection is ready.
+        pub fn is_deferred_accept(&self) -> bool {
+            self.listening().is_some_and(|ls| {
+                #[cfg(ngx_os = "freebsd")]
+                if !ls.accept_filter.is_null() {
+                    return true;
+                }
+                ls.deferred_accept() != 0
+            })
+        }
+
+        /// Returns the name of the accept filter of the listening socket, e.g. `dataready` or
+        /// `httpready`.
+        #[cfg(ngx_os = "freebsd")]
+        pub fn accept_filter(&self) -> Option<&core::ffi::CStr> {
+            let ls = self.listening()?;
+            if ls.accept_filter.is_null() {
+                return None;
+            }
+            // SAFETY: the filter name is a null-terminated string allocated from the cycle pool.
+            Some(unsafe { core::ffi::CStr::from_ptr(ls.accept_filter) })
+        }
+    }
+}
+
+#[cfg(ngx_feature = "ssl")]
+mod ssl {
+    use core::ffi::{c_uchar, c_uint};
+    use core::{ptr, slice};
+
+    use crate::core::FeatureDisabled;
+    use crate::ffi::{SSL_get0_alpn_selected, ngx_ssl_connection_t};
+
+    use super::Connection;
+
+    impl Connection {
+        /// Returns the TLS state of the connection, if TLS is used.
+        pub fn ssl_connection(&self) -> Option<&ngx_ssl_connection_t> {
+            // SAFETY: `ssl` is either NULL or allocated from the connection pool.
+            unsafe { self.0.ssl.as_ref() }
+        }
+
+        /// Returns the application protocol negotiated with ALPN, e.g. `h2` or `http/1.1`.
+        ///
+        /// Returns `None` if TLS is not used, or if no protocol was negotiated.
+        pub fn alpn_protocol(&self) -> Result<Option<&[u8]>, FeatureDisabled> {
+            let Some(ssl) = self.ssl_connection() else {
+                return Ok(None);
+            };
+
+            let mut data: *const c_uchar = ptr::null();
+            let mut len: c_uint = 0;
+            unsafe { SSL_get0_alpn_selected(ssl.connection, &raw mut data, &raw mut len) };
+
+            if data.is_null() || len == 0 {
+                return Ok(None);
+            }
+
+            // SAFETY: the protocol name is owned by the SSL object.
+            Ok(Some(unsafe { slice::from_raw_parts(data, len as usize) }))
+        }
+
+        /// Returns `true` if the data is currently received as TLS 1.3 early data (0-RTT).
+        ///
+        /// Early data can be replayed by an attacker; non-idempotent requests received in early
+        /// data should be rejected with the `425 Too Early` status.
+        pub fn in_early_data(&self) -> Result<bool, FeatureDisabled> {
+            Ok(self.ssl_connection().is_some_and(|ssl| ssl.in_early_data() != 0))
+        }
+    }
+}
+
+#[cfg(all(feature = "feature-stubs", not(ngx_feature = "ssl")))]
+mod ssl_stubs {
+    use crate::core::{Feature, FeatureDisabled};
+
+    use super::Connection;
+
+    // Stubs for nginx builds without TLS support.
+    impl Connection {
+        /// Returns the application protocol negotiated with ALPN.
+        ///
+        /// Always fails: nginx was built without TLS support.
+        pub fn alpn_protocol(&self) -> Result<Option<&[u8]>, FeatureDisabled> {
+            Err(FeatureDisabled(Feature::Ssl))
+        }
+
+        /// Returns `true` if the data is currently received as TLS 1.3 early data.
+        ///
+        /// Always fails: nginx was built without TLS support.
+        pub fn in_early_data(&self) -> Result<bool, FeatureDisabled> {
+            Err(FeatureDisabled(Feature::Ssl))
+        }
+    }
+}
+
+#[cfg(ngx_feature = "quic")]
+impl Connection {
+    /// Returns `true` if the connection is a QUIC stream or a QUIC connection.
+    pub fn is_quic(&self) -> Result<bool, crate::core::FeatureDisabled> {
+        Ok(!self.0.quic.is_null())
+    }
+}
+
+#[cfg(all(feature = "feature-stubs", not(ngx_feature = "quic")))]
+impl Connection {
+    /// Returns `true` if the connection is a QUIC stream or a QUIC connection.
+    ///
+    /// Always fails: nginx was built without QUIC support.
+    pub fn is_quic(&self) -> Result<bool, crate::core::FeatureDisabled> {
+        Err(crate::core::FeatureDisabled(crate::core::Feature::Quic))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use std::net::{TcpListener, UdpSocket};
+    use std::os::fd::AsRawFd;
+
+    use super::*;
+
+    #[test]
+    fn test_socket_local_addr() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = socket_local_addr(listener.as_raw_fd());
+        assert_eq!(addr, Some(listener.local_addr().unwrap()));
+
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket.connect(listener.local_addr().unwrap()).unwrap();
+        assert_eq!(socket_local_addr(socket.as_raw_fd()), Some(socket.local_addr().unwrap()));
+
+        assert_eq!(socket_local_addr(-1), None);
+    }
+
+    #[test]
+    fn test_sockaddr_roundtrip() {
+        for addr in ["127.0.0.1:8080", "[::1]:443"] {
+            let addr: SocketAddr = addr.parse().unwrap();
+            // SAFETY: an all-zero value is a valid sockaddr_storage.
+            let mut out: sockaddr_storage = unsafe { mem::zeroed() };
+            socket_addr_to_sockaddr(&addr, &mut out);
+            assert_eq!(unsafe { sockaddr_to_socket_addr((&raw const out).cast()) }, Some(addr));
+        }
+    }
+}
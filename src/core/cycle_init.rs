@@ -0,0 +1,121 @@
+use core::any::TypeId;
+use core::ffi::c_void;
+use core::fmt;
+use core::mem;
+
+use crate::core::{Pool, Status};
+use crate::ffi::{NGX_LOG_EMERG, ngx_cycle_t, ngx_int_t, ngx_pool_cleanup_add};
+use crate::ngx_log_error;
+
+/// Initialization shared by the modules of a crate, run once per configuration cycle.
+///
+/// A crate shipping several cooperating modules implements the trait on a marker type and sets
+/// the `init_module` field of each module to [`cycle_init_handler`] for this type. nginx calls the
+/// `init_module` handlers once the configuration is parsed, after the `postconfiguration` handlers
+/// of all modules, and the [`CycleInit::init_cycle`] method is run for the first module only. The
+/// configuration of each module is available via the cycle, e.g. with
+/// [`HttpModuleMainConf::main_conf`](crate::http::HttpModuleMainConf::main_conf), and can be
+/// linked with the configuration of the other modules.
+///
+/// The initialization is run in the master process, or in the single process, for each loaded
+/// configuration. A failure is logged and is fatal: nginx treats an `init_module` error as
+/// unrecoverable and exits, even if the configuration is being reloaded by a running master
+/// process. The method should therefore only link the configurations; the directives are
+/// validated in the `postconfiguration` handlers, where an error just rejects the new
+/// configuration and keeps the old one.
+///
+/// ```no_run
+/// # use ngx::core::CycleInit;
+/// # use ngx::ffi::{ngx_cycle_t, ngx_module_t};
+/// # use ngx::http::{HttpModule, HttpModuleMainConf};
+/// # struct Cache;
+/// # impl HttpModule for Cache { fn module() -> &'static ngx_module_t { unimplemented!() } }
+/// # unsafe impl HttpModuleMainConf for Cache { type MainConf = CacheConf; }
+/// # struct Purge;
+/// # impl HttpModule for Purge { fn module() -> &'static ngx_module_t { unimplemented!() } }
+/// # unsafe impl HttpModuleMainConf for Purge { type MainConf = PurgeConf; }
+/// # struct CacheConf { zone: Option<usize> }
+/// # struct PurgeConf { cache_zone: Option<usize> }
+/// struct Modules;
+///
+/// impl CycleInit for Modules {
+///     type Error = core::convert::Infallible;
+///
+///     fn init_cycle(cycle: &mut ngx_cycle_t) -> Result<(), Self::Error> {
+///         // the modules are not configured without the `http` block
+///         let (Some(cache), Some(purge)) = (Cache::main_conf(cycle), Purge::main_conf_mut(cycle))
+///         else {
+///             return Ok(());
+///         };
+///         // `purge` without a cache zone is rejected in the postconfiguration handler
+///         purge.cache_zone = cache.zone;
+///         Ok(())
+///     }
+/// }
+///
+/// // ngx_module_t {
+/// //     init_module: Some(ngx::core::cycle_init_handler::<Modules>),
+/// //     ..
+/// // }
+/// ```
+pub trait CycleInit: 'static {
+    /// Error returned by the initialization.
+    type Error: fmt::Display;
+
+    /// Initializes the modules for the new configuration cycle.
+    ///
+    /// An error is fatal for the nginx process, see the [trait documentation](CycleInit).
+    fn init_cycle(cycle: &mut ngx_cycle_t) -> Result<(), Self::Error>;
+}
+
+/// `init_module` handler running [`CycleInit::init_cycle`] once per configuration cycle.
+///
+/// # Safety
+///
+/// Must only be called by nginx as the `init_module` handler of a module.
+pub unsafe extern "C" fn cycle_init_handler<T: CycleInit>(cycle: *mut ngx_cycle_t) -> ngx_int_t {
+    // SAFETY: nginx calls the handler with the cycle being initialized.
+    let cycle = unsafe { &mut *cycle };
+    // SAFETY: the cycle pool is valid while the cycle is alive.
+    let pool = unsafe { Pool::from_ngx_pool(cycle.pool) };
+
+    let id = TypeId::of::<T>();
+    if is_initialized(&pool, id) {
+        return Status::NGX_OK.into();
+    }
+
+    if let Err(err) = T::init_cycle(cycle) {
+        ngx_log_error!(NGX_LOG_EMERG, cycle.log, "{}", err);
+        return Status::NGX_ERROR.into();
+    }
+
+    // The handler is called again for each module of the crate, record that the initialization
+    // is done for this cycle.
+    // SAFETY: the cycle pool is valid while the cycle is alive.
+    let cln = unsafe { ngx_pool_cleanup_add(pool.as_ptr(), mem::size_of::<TypeId>()) };
+    let Some(cln) = (unsafe { cln.as_mut() }) else {
+        return Status::NGX_ERROR.into();
+    };
+    // SAFETY: the cleanup data is allocated above with the size of `TypeId`.
+    unsafe { cln.data.cast::<TypeId>().write_unaligned(id) };
+    cln.handler = Some(cycle_init_cleanup);
+
+    Status::NGX_OK.into()
+}
+
+fn is_initialized(pool: &Pool, id: TypeId) -> bool {
+    // SAFETY: the cleanup list is owned by the pool.
+    let mut cln = unsafe { (*pool.as_ptr()).cleanup };
+    while let Some(c) = unsafe { cln.as_ref() } {
+        if c.handler == Some(cycle_init_cleanup as unsafe extern "C" fn(_))
+            && unsafe { c.data.cast::<TypeId>().read_unaligned() } == id
+        {
+            return true;
+        }
+        cln = c.next;
+    }
+    false
+}
+
+/// Marks the cycle pool cleanup recording a completed initialization.
+unsafe extern "C" fn cycle_init_cleanup(_data: *mut c_void) {}
@@ -0,0 +1,290 @@
+use core::error;
+use core::fmt::{self, Write};
+use core::slice;
+
+use crate::core::{LenCounter, Pool, SliceWriter, Status};
+use crate::ffi::ngx_int_t;
+
+/// Error with a chain of context messages.
+///
+/// The messages are allocated from a memory pool, usually the request or the configuration
+/// pool, and the error cannot outlive the pool. Each helper in the call stack can add a message
+/// describing the operation in progress with [`NgxError::context`] or [`ResultExt::context`],
+/// and the caller receives the whole chain as a single error, e.g.
+/// `"subrequest to /auth failed: failed to allocate buffer"`.
+///
+/// The root cause is never lost on allocation failures: a context message that cannot be stored
+/// is replaced with a placeholder or omitted.
+#[derive(Clone, Copy)]
+pub struct NgxError<'p> {
+    status: ngx_int_t,
+    message: Option<&'p str>,
+    source: Option<&'p Frame<'p>>,
+}
+
+#[derive(Clone, Copy)]
+struct Frame<'p> {
+    message: Option<&'p str>,
+    source: Option<&'p Frame<'p>>,
+}
+
+const NO_MEMORY: &str = "<no memory for error context>";
+
+impl<'p> NgxError<'p> {
+    /// Creates an error with a static message.
+    pub const fn new(status: Status, message: &'p str) -> Self {
+        Self { status: status.0, message: Some(message), source: None }
+    }
+
+    /// Creates an error with a message formatted into the pool.
+    pub fn from_display(pool: &'p Pool, status: Status, message: impl fmt::Display) -> Self {
+        let message = pool_format(pool, format_args!("{message}")).unwrap_or(NO_MEMORY);
+        Self { status: status.0, message: Some(message), source: None }
+    }
+
+    /// Adds a context message to the error.
+    ///
+    /// The new message becomes the outermost one, and the status code is preserved.
+    pub fn context(self, pool: &'p Pool, context: impl fmt::Display) -> Self {
+        let message = pool_format(pool, format_args!("{context}")).unwrap_or(NO_MEMORY);
+
+        if self.message.is_none() && self.source.is_none() {
+            return Self { message: Some(message), ..self };
+        }
+
+        let frame = pool.alloc_type::<Frame<'p>>();
+        if frame.is_null() {
+            // Keep the original error rather than losing the root cause.
+            return self;
+        }
+
+        // SAFETY: `frame` is a fresh allocation from the pool, valid for `'p`.
+        let frame = unsafe {
+            frame.write(Frame { message: self.message, source: self.source });
+            &*frame
+        };
+
+        Self { status: self.status, message: Some(message), source: Some(frame) }
+    }
+
+    /// Returns the status code of the error.
+    pub fn status(&self) -> Status {
+        Status(self.status)
+    }
+
+    /// Returns the outermost message.
+    pub fn message(&self) -> Option<&'p str> {
+        self.message
+    }
+
+    /// Returns an iterator over the messages, from the outermost to the root cause.
+    pub fn chain(&self) -> Chain<'p> {
+        Chain { message: self.message, source: self.source, first: true }
+    }
+}
+
+impl From<Status> for NgxError<'_> {
+    fn from(status: Status) -> Self {
+        Self { status: status.0, message: None, source: None }
+    }
+}
+
+impl error::Error for NgxError<'_> {}
+
+impl fmt::Debug for NgxError<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NgxError")
+            .field("status", &self.status)
+            .field("chain", &DebugChain(self.chain()))
+            .finish()
+    }
+}
+
+impl fmt::Display for NgxError<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut chain = self.chain();
+        match chain.next() {
+            Some(message) => f.write_str(message)?,
+            None => return write!(f, "error {}", self.status),
+        }
+        for message in chain {
+            f.write_str(": ")?;
+            f.write_str(message)?;
+        }
+        Ok(())
+    }
+}
+
+/// Iterator over the messages of an [`NgxError`].
+#[derive(Clone)]
+pub struct Chain<'p> {
+    message: Option<&'p str>,
+    source: Option<&'p Frame<'p>>,
+    first: bool,
+}
+
+impl<'p> Iterator for Chain<'p> {
+    type Item = &'p str;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.first {
+                self.first = false;
+            } else {
+                let frame = self.source?;
+                self.message = frame.message;
+                self.source = frame.source;
+            }
+
+            if let Some(message) = self.message.take() {
+                return Some(message);
+            }
+        }
+    }
+}
+
+struct DebugChain<'p>(Chain<'p>);
+
+impl fmt::Debug for DebugChain<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.0.clone()).finish()
+    }
+}
+
+/// Extension trait for adding context to the errors.
+pub trait ResultExt<'p, T> {
+    /// Adds a context message to the error.
+    fn context(self, pool: &'p Pool, context: impl fmt::Display) -> Result<T, NgxError<'p>>;
+
+    /// Adds a lazily evaluated context message to the error.
+    fn with_context<C, F>(self, pool: &'p Pool, f: F) -> Result<T, NgxError<'p>>
+    where
+        C: fmt::Display,
+        F: FnOnce() -> C;
+}
+
+impl<'p, T, E> ResultExt<'p, T> for Result<T, E>
+where
+    E: Into<NgxError<'p>>,
+{
+    fn context(self, pool: &'p Pool, context: impl fmt::Display) -> Result<T, NgxError<'p>> {
+        self.map_err(|err| err.into().context(pool, context))
+    }
+
+    fn with_context<C, F>(self, pool: &'p Pool, f: F) -> Result<T, NgxError<'p>>
+    where
+        C: fmt::Display,
+        F: FnOnce() -> C,
+    {
+        self.map_err(|err| err.into().context(pool, f()))
+    }
+}
+
+impl<'p, T> ResultExt<'p, T> for Option<T> {
+    fn context(self, pool: &'p Pool, context: impl fmt::Display) -> Result<T, NgxError<'p>> {
+        self.ok_or_else(|| NgxError::from_display(pool, Status::NGX_ERROR, context))
+    }
+
+    fn with_context<C, F>(self, pool: &'p Pool, f: F) -> Result<T, NgxError<'p>>
+    where
+        C: fmt::Display,
+        F: FnOnce() -> C,
+    {
+        self.ok_or_else(|| NgxError::from_display(pool, Status::NGX_ERROR, f()))
+    }
+}
+
+/// Formats the arguments into a string allocated from the pool.
+fn pool_format<'p>(pool: &'p Pool, args: fmt::Arguments<'_>) -> Option<&'p str> {
+    if let Some(s) = args.as_str() {
+        // Avoid copying the static strings.
+        return Some(s);
+    }
+
+    let mut counter = LenCounter(0);
+    counter.write_fmt(args).ok()?;
+
+    let data = pool.alloc_unaligned(counter.0).cast::<u8>();
+    if data.is_null() {
+        return None;
+    }
+
+    // SAFETY: `data` is a fresh allocation of `counter.0` bytes, valid for `'p`.
+    let buf = unsafe { slice::from_raw_parts_mut(data, counter.0) };
+    let mut writer = SliceWriter::new(&mut *buf);
+    // A Display implementation may produce a longer output on the second call.
+    writer.write_fmt(args).ok()?;
+    let len = writer.pos;
+
+    let buf: &'p [u8] = buf;
+    core::str::from_utf8(&buf[..len]).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use std::format;
+    use std::string::ToString;
+    use std::vec::Vec;
+
+    use super::*;
+
+    #[test]
+    fn test_chain() {
+        // The frames as `context` allocates them from the pool.
+        let root = Frame { message: Some("failed to allocate buffer"), source: None };
+        let frame = Frame { message: Some("subrequest to /auth failed"), source: Some(&root) };
+        let err = NgxError {
+            status: Status::NGX_DECLINED.0,
+            message: Some("access check"),
+            source: Some(&frame),
+        };
+
+        assert_eq!(err.status(), Status::NGX_DECLINED);
+        assert_eq!(err.message(), Some("access check"));
+        assert_eq!(
+            err.chain().collect::<Vec<_>>(),
+            ["access check", "subrequest to /auth failed", "failed to allocate buffer"]
+        );
+        assert_eq!(
+            err.to_string(),
+            "access check: subrequest to /auth failed: failed to allocate buffer"
+        );
+        assert_eq!(
+            format!("{err:?}"),
+            "NgxError { status: -5, chain: [\"access check\", \
+             \"subrequest to /auth failed\", \"failed to allocate buffer\"] }"
+        );
+    }
+
+    #[test]
+    fn test_chain_skips_empty() {
+        // The frames without a message are skipped.
+        let root = Frame { message: None, source: None };
+        let err = NgxError {
+            status: Status::NGX_ERROR.0,
+            message: Some("reading body"),
+            source: Some(&root),
+        };
+        assert_eq!(err.chain().collect::<Vec<_>>(), ["reading body"]);
+        assert_eq!(err.to_string(), "reading body");
+
+        let err = NgxError { status: Status::NGX_ERROR.0, message: None, source: Some(&root) };
+        assert_eq!(err.chain().count(), 0);
+        assert_eq!(err.to_string(), "error -1");
+    }
+
+    #[test]
+    fn test_from_status() {
+        let err = NgxError::from(Status::NGX_AGAIN);
+        assert_eq!(err.status(), Status::NGX_AGAIN);
+        assert_eq!(err.message(), None);
+        assert_eq!(err.chain().count(), 0);
+        assert_eq!(err.to_string(), "error -2");
+
+        let err = NgxError::new(Status::NGX_ERROR, "no memory");
+        assert_eq!(err.chain().collect::<Vec<_>>(), ["no memory"]);
+        assert_eq!(err.to_string(), "no memory");
+    }
+}
@@ -0,0 +1,82 @@
+use core::ffi::{CStr, c_void};
+use core::ptr::NonNull;
+
+use crate::core::{CoreModuleConfExt, NgxMsec};
+use crate::ffi::{
+    ngx_accept_mutex_held, ngx_event_conf_t, ngx_event_core_module, ngx_events_module,
+    ngx_use_accept_mutex,
+};
+
+/// Auxiliary structure to access `ngx_event_core_module` configuration.
+pub struct NgxEventCoreModule;
+
+impl NgxEventCoreModule {
+    /// Returns the configuration of the `events` block.
+    pub fn conf(o: &impl CoreModuleConfExt) -> Option<&'static EventCoreConf> {
+        // SAFETY: the configuration slot of `ngx_events_module` points to a pointer to the array
+        // of the event module configurations, indexed by `ctx_index` (`void ***`), same as in
+        // `ngx_event_get_conf`.
+        unsafe {
+            let module = &*core::ptr::addr_of!(ngx_events_module);
+            let ctx = o.core_main_conf_unchecked::<*mut *mut c_void>(module)?;
+            let confs = NonNull::new(*ctx.as_ptr())?;
+            let index = (*core::ptr::addr_of!(ngx_event_core_module)).ctx_index;
+            let conf = *confs.as_ptr().add(index);
+            Some(NonNull::new(conf)?.cast::<EventCoreConf>().as_ref())
+        }
+    }
+}
+
+/// Wrapper for the [`ngx_event_conf_t`] event core module configuration.
+#[repr(transparent)]
+pub struct EventCoreConf(ngx_event_conf_t);
+
+impl AsRef<ngx_event_conf_t> for EventCoreConf {
+    fn as_ref(&self) -> &ngx_event_conf_t {
+        &self.0
+    }
+}
+
+impl EventCoreConf {
+    /// Returns the `worker_connections` value.
+    pub fn worker_connections(&self) -> usize {
+        self.0.connections as usize
+    }
+
+    /// Returns `true` if `multi_accept` is enabled.
+    pub fn multi_accept(&self) -> bool {
+        self.0.multi_accept != 0
+    }
+
+    /// Returns `true` if `accept_mutex` is enabled in the configuration.
+    pub fn accept_mutex(&self) -> bool {
+        self.0.accept_mutex != 0
+    }
+
+    /// Returns the `accept_mutex_delay` value.
+    pub fn accept_mutex_delay(&self) -> NgxMsec {
+        NgxMsec(self.0.accept_mutex_delay)
+    }
+
+    /// Returns the name of the connection processing method, e.g. `epoll` or `kqueue`.
+    pub fn event_method(&self) -> Option<&'static CStr> {
+        if self.0.name.is_null() {
+            return None;
+        }
+        // SAFETY: `name` points to the static name of the selected event module.
+        Some(unsafe { CStr::from_ptr(self.0.name.cast()) })
+    }
+}
+
+/// Returns `true` if the worker processes use the accept mutex.
+///
+/// The accept mutex may be disabled at runtime even if enabled in the configuration, e.g. with a
+/// single worker process or when the listening sockets use `reuseport`.
+pub fn accept_mutex_in_use() -> bool {
+    unsafe { ngx_use_accept_mutex != 0 }
+}
+
+/// Returns `true` if the current worker process holds the accept mutex.
+pub fn accept_mutex_held() -> bool {
+    unsafe { ngx_accept_mutex_held != 0 }
+}
@@ -0,0 +1,84 @@
+use core::error;
+use core::fmt;
+
+/// Optional features of the nginx build.
+///
+/// The availability of the features is determined at compile time from the nginx configuration,
+/// and the corresponding APIs are only compiled if the feature is enabled. With the
+/// `feature-stubs` cargo feature, some of these APIs are also available when the feature is
+/// disabled and fail with [`FeatureDisabled`].
+///
+/// Modules targeting different nginx builds can use [`Feature::require`] to reject the
+/// configuration that depends on a missing feature with a descriptive error.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Feature {
+    /// TLS support in the core, required by all the `ssl` modules.
+    Ssl,
+    /// `ngx_http_ssl_module`.
+    HttpSsl,
+    /// `ngx_http_v2_module`.
+    HttpV2,
+    /// `ngx_http_v3_module`.
+    HttpV3,
+    /// QUIC transport.
+    Quic,
+    /// Thread pools.
+    Threads,
+}
+
+impl Feature {
+    /// Returns the name of the feature, as used in the `ngx_feature` cfg.
+    pub const fn name(self) -> &'static str {
+        match self {
+            Feature::Ssl => "ssl",
+            Feature::HttpSsl => "http_ssl",
+            Feature::HttpV2 => "http_v2",
+            Feature::HttpV3 => "http_v3",
+            Feature::Quic => "quic",
+            Feature::Threads => "threads",
+        }
+    }
+
+    /// Returns `true` if nginx was built with the feature.
+    pub const fn is_enabled(self) -> bool {
+        match self {
+            Feature::Ssl => cfg!(ngx_feature = "ssl"),
+            Feature::HttpSsl => cfg!(ngx_feature = "http_ssl"),
+            Feature::HttpV2 => cfg!(ngx_feature = "http_v2"),
+            Feature::HttpV3 => cfg!(ngx_feature = "http_v3"),
+            Feature::Quic => cfg!(ngx_feature = "quic"),
+            Feature::Threads => cfg!(ngx_feature = "threads"),
+        }
+    }
+
+    /// Returns an error if nginx was built without the feature.
+    pub const fn require(self) -> Result<(), FeatureDisabled> {
+        if self.is_enabled() { Ok(()) } else { Err(FeatureDisabled(self)) }
+    }
+}
+
+impl fmt::Display for Feature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Error returned when the required feature is not available in the nginx build.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FeatureDisabled(pub Feature);
+
+impl FeatureDisabled {
+    /// Returns the missing feature.
+    pub fn feature(&self) -> Feature {
+        self.0
+    }
+}
+
+impl error::Error for FeatureDisabled {}
+
+impl fmt::Display for FeatureDisabled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "nginx was built without \"{}\" support", self.0)
+    }
+}
@@ -0,0 +1,103 @@
+use core::ffi::c_void;
+use core::fmt;
+use core::ptr;
+use core::sync::atomic::{AtomicPtr, AtomicUsize, Ordering};
+
+use crate::ffi::{ngx_conf_t, ngx_cycle_t, ngx_pool_cleanup_add};
+
+static GENERATION: AtomicUsize = AtomicUsize::new(0);
+static CYCLE: AtomicPtr<ngx_cycle_t> = AtomicPtr::new(ptr::null_mut());
+
+/// Configuration generation, incremented for each loaded configuration.
+///
+/// The generation allows to invalidate the state derived from a configuration, e.g. cache entries
+/// in a shared memory zone that survives reloads, without keeping and comparing pointers to the
+/// configuration structures. The worker processes inherit the generation of the configuration
+/// they were started with.
+///
+/// The generation is advanced with [`Generation::advance`], which must be called during the
+/// configuration parsing by at least one module, e.g. from the `postconfiguration` handler.
+/// Multiple calls for the same configuration cycle advance the generation once, and the
+/// generation of a configuration that failed to load is discarded.
+///
+/// ```no_run
+/// # use ngx::core::Generation;
+/// struct Entry {
+///     generation: Generation,
+///     // ...
+/// }
+///
+/// fn is_stale(entry: &Entry) -> bool {
+///     entry.generation != Generation::current()
+/// }
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Generation(usize);
+
+/// State restored if the configuration cycle is destroyed while it is the last advanced one.
+#[derive(Clone, Copy)]
+struct Previous {
+    /// Cycle that advanced the generation.
+    advanced: *mut ngx_cycle_t,
+    /// Previously advanced cycle and its generation.
+    cycle: *mut ngx_cycle_t,
+    generation: usize,
+}
+
+impl Generation {
+    /// Returns the generation of the current configuration.
+    ///
+    /// Returns the zero generation before the first call to [`Generation::advance`].
+    pub fn current() -> Self {
+        Self(GENERATION.load(Ordering::Relaxed))
+    }
+
+    /// Returns the numeric value of the generation.
+    pub fn get(&self) -> usize {
+        self.0
+    }
+
+    /// Advances the generation for the configuration being parsed, and returns the new generation.
+    ///
+    /// Must be called from a configuration handler.
+    pub fn advance(cf: &ngx_conf_t) -> Self {
+        let cycle = cf.cycle;
+        let prev = CYCLE.load(Ordering::Relaxed);
+        if prev == cycle {
+            return Self::current();
+        }
+
+        let cln = unsafe { ngx_pool_cleanup_add(cf.pool, core::mem::size_of::<Previous>()) };
+        // The generation is still advanced if the cleanup cannot be added; it is only used to
+        // restore the generation after a failed reload.
+        if let Some(cln) = unsafe { cln.as_mut() } {
+            let generation = GENERATION.load(Ordering::Relaxed);
+            let previous = Previous { advanced: cycle, cycle: prev, generation };
+            unsafe { ptr::write(cln.data.cast::<Previous>(), previous) };
+            cln.handler = Some(restore_generation);
+        }
+
+        CYCLE.store(cycle, Ordering::Relaxed);
+        Self(GENERATION.fetch_add(1, Ordering::Relaxed) + 1)
+    }
+}
+
+impl fmt::Display for Generation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+/// Restores the previous generation if the destroyed cycle is the last one advanced, i.e. the
+/// configuration failed to load.
+///
+/// The cleanup is registered in the cycle pool, which is destroyed either on failure, when the
+/// old configuration remains current, or after a newer configuration is loaded.
+unsafe extern "C" fn restore_generation(data: *mut c_void) {
+    let previous = unsafe { &*data.cast::<Previous>() };
+
+    if CYCLE.load(Ordering::Relaxed) == previous.advanced {
+        GENERATION.store(previous.generation, Ordering::Relaxed);
+        CYCLE.store(previous.cycle, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,383 @@
+use core::fmt;
+
+use crate::allocator::AllocError;
+use crate::core::{ChainWriter, NgxStr};
+
+/// Maximum nesting depth of the objects and arrays.
+pub const JSON_MAX_DEPTH: usize = 64;
+
+/// Destination of a [`JsonWriter`].
+pub trait JsonSink {
+    /// Appends the data to the output.
+    fn write_bytes(&mut self, data: &[u8]) -> Result<(), AllocError>;
+}
+
+impl JsonSink for ChainWriter {
+    fn write_bytes(&mut self, data: &[u8]) -> Result<(), AllocError> {
+        ChainWriter::write_bytes(self, data)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<A: crate::allocator::Allocator> JsonSink for crate::collections::Vec<u8, A> {
+    fn write_bytes(&mut self, data: &[u8]) -> Result<(), AllocError> {
+        self.try_reserve(data.len()).map_err(|_| AllocError)?;
+        self.extend_from_slice(data);
+        Ok(())
+    }
+}
+
+/// Error writing a JSON document.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum JsonError {
+    /// Memory allocation failed.
+    Alloc,
+    /// The nesting depth exceeds [`JSON_MAX_DEPTH`].
+    TooDeep,
+    /// Mismatched end of an object or array, or a value without a key in an object.
+    Unbalanced,
+}
+
+impl fmt::Display for JsonError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            JsonError::Alloc => "memory allocation failed",
+            JsonError::TooDeep => "nesting too deep",
+            JsonError::Unbalanced => "unbalanced document",
+        })
+    }
+}
+
+impl core::error::Error for JsonError {}
+
+impl From<AllocError> for JsonError {
+    fn from(_: AllocError) -> Self {
+        JsonError::Alloc
+    }
+}
+
+/// Streaming JSON encoder.
+///
+/// The values are written directly to the sink, usually a [`ChainWriter`] producing the buffers
+/// for the response body, without building the document in memory. The writer inserts the
+/// separators and checks the nesting of the objects and arrays.
+///
+/// # Example
+///
+/// ```no_run
+/// # use ngx::core::{ChainWriter, JsonError, JsonWriter, Pool};
+/// # fn status(pool: Pool, connections: u64) -> Result<*mut ngx::ffi::ngx_chain_t, JsonError> {
+/// let mut json = JsonWriter::new(ChainWriter::new(pool, 4096));
+/// json.begin_object()?;
+/// json.key("connections")?;
+/// json.uint(connections)?;
+/// json.key("zones")?;
+/// json.begin_array()?;
+/// json.string("one")?;
+/// json.end_array()?;
+/// json.end_object()?;
+/// Ok(json.into_inner()?.finish(true)?)
+/// # }
+/// ```
+pub struct JsonWriter<W> {
+    sink: W,
+    /// Bit per nesting level: set for objects.
+    objects: u64,
+    /// Bit per nesting level: set if the level already has a member or element.
+    nonempty: u64,
+    depth: usize,
+    after_key: bool,
+}
+
+impl<W: JsonSink> JsonWriter<W> {
+    /// Creates a writer for the sink.
+    pub fn new(sink: W) -> Self {
+        Self { sink, objects: 0, nonempty: 0, depth: 0, after_key: false }
+    }
+
+    /// Returns the sink, checking that all the objects and arrays are closed.
+    pub fn into_inner(self) -> Result<W, JsonError> {
+        if self.depth != 0 || self.after_key {
+            return Err(JsonError::Unbalanced);
+        }
+        Ok(self.sink)
+    }
+
+    /// Starts an object.
+    pub fn begin_object(&mut self) -> Result<(), JsonError> {
+        self.begin(true)
+    }
+
+    /// Ends the current object.
+    pub fn end_object(&mut self) -> Result<(), JsonError> {
+        self.end(true)
+    }
+
+    /// Starts an array.
+    pub fn begin_array(&mut self) -> Result<(), JsonError> {
+        self.begin(false)
+    }
+
+    /// Ends the current array.
+    pub fn end_array(&mut self) -> Result<(), JsonError> {
+        self.end(false)
+    }
+
+    /// Writes an object member name.
+    pub fn key(&mut self, key: &str) -> Result<(), JsonError> {
+        self.key_bytes(key.as_bytes())
+    }
+
+    /// Writes an object member name from bytes, escaped with [`json_escape`].
+    pub fn key_bytes(&mut self, key: &[u8]) -> Result<(), JsonError> {
+        if self.depth == 0 || !self.in_object() || self.after_key {
+            return Err(JsonError::Unbalanced);
+        }
+        self.separator()?;
+        self.quoted(key)?;
+        self.sink.write_bytes(b":")?;
+        self.after_key = true;
+        Ok(())
+    }
+
+    /// Writes a string value.
+    pub fn string(&mut self, value: &str) -> Result<(), JsonError> {
+        self.bytes(value.as_bytes())
+    }
+
+    /// Writes a string value from bytes, escaped with [`json_escape`].
+    pub fn bytes(&mut self, value: &[u8]) -> Result<(), JsonError> {
+        self.value()?;
+        self.quoted(value)
+    }
+
+    /// Writes a string value from an nginx string.
+    pub fn ngx_str(&mut self, value: &NgxStr) -> Result<(), JsonError> {
+        self.bytes(value.as_bytes())
+    }
+
+    /// Writes an unsigned integer value.
+    pub fn uint(&mut self, value: u64) -> Result<(), JsonError> {
+        self.value()?;
+        let mut buf = [0u8; 20];
+        let s = format_u64(value, &mut buf);
+        Ok(self.sink.write_bytes(s)?)
+    }
+
+    /// Writes a signed integer value.
+    pub fn int(&mut self, value: i64) -> Result<(), JsonError> {
+        if value >= 0 {
+            return self.uint(value as u64);
+        }
+        self.value()?;
+        let mut buf = [0u8; 20];
+        let s = format_u64(value.unsigned_abs(), &mut buf);
+        self.sink.write_bytes(b"-")?;
+        Ok(self.sink.write_bytes(s)?)
+    }
+
+    /// Writes a boolean value.
+    pub fn bool(&mut self, value: bool) -> Result<(), JsonError> {
+        self.value()?;
+        Ok(self.sink.write_bytes(if value { b"true" } else { b"false" })?)
+    }
+
+    /// Writes a `null` value.
+    pub fn null(&mut self) -> Result<(), JsonError> {
+        self.value()?;
+        Ok(self.sink.write_bytes(b"null")?)
+    }
+
+    /// Writes a preformatted JSON value as is.
+    pub fn raw(&mut self, value: &[u8]) -> Result<(), JsonError> {
+        self.value()?;
+        Ok(self.sink.write_bytes(value)?)
+    }
+
+    fn in_object(&self) -> bool {
+        self.objects & (1 << (self.depth - 1)) != 0
+    }
+
+    fn separator(&mut self) -> Result<(), JsonError> {
+        if self.depth == 0 {
+            return Ok(());
+        }
+        let bit = 1 << (self.depth - 1);
+        if self.nonempty & bit != 0 {
+            self.sink.write_bytes(b",")?;
+        }
+        self.nonempty |= bit;
+        Ok(())
+    }
+
+    fn value(&mut self) -> Result<(), JsonError> {
+        if self.after_key {
+            self.after_key = false;
+            return Ok(());
+        }
+        if self.depth > 0 && self.in_object() {
+            return Err(JsonError::Unbalanced);
+        }
+        self.separator()
+    }
+
+    fn begin(&mut self, object: bool) -> Result<(), JsonError> {
+        if self.depth == JSON_MAX_DEPTH {
+            return Err(JsonError::TooDeep);
+        }
+        self.value()?;
+        self.sink.write_bytes(if object { b"{" } else { b"[" })?;
+
+        let bit = 1 << self.depth;
+        self.depth += 1;
+        self.nonempty &= !bit;
+        if object {
+            self.objects |= bit;
+        } else {
+            self.objects &= !bit;
+        }
+        Ok(())
+    }
+
+    fn end(&mut self, object: bool) -> Result<(), JsonError> {
+        if self.depth == 0 || self.in_object() != object || self.after_key {
+            return Err(JsonError::Unbalanced);
+        }
+        self.depth -= 1;
+        Ok(self.sink.write_bytes(if object { b"}" } else { b"]" })?)
+    }
+
+    fn quoted(&mut self, value: &[u8]) -> Result<(), JsonError> {
+        self.sink.write_bytes(b"\"")?;
+        json_escape(value, |x| self.sink.write_bytes(x))?;
+        Ok(self.sink.write_bytes(b"\"")?)
+    }
+}
+
+/// Escapes `value` for a JSON string, passing the parts to `write`.
+///
+/// As in nginx, the quotation mark, the backslash and the control characters are escaped, and
+/// the other bytes are written as is, so the output is valid UTF-8 only for valid UTF-8 input.
+pub fn json_escape<E>(
+    value: &[u8],
+    mut write: impl FnMut(&[u8]) -> Result<(), E>,
+) -> Result<(), E> {
+    const HEX: &[u8; 16] = b"0123456789abcdef";
+
+    let mut unicode = *b"\\u00XX";
+    let mut start = 0;
+    for (i, &ch) in value.iter().enumerate() {
+        let esc: &[u8] = match ch {
+            b'"' => b"\\\"",
+            b'\\' => b"\\\\",
+            b'\n' => b"\\n",
+            b'\r' => b"\\r",
+            b'\t' => b"\\t",
+            0x08 => b"\\b",
+            0x0c => b"\\f",
+            0..0x20 => {
+                unicode[4] = HEX[(ch >> 4) as usize];
+                unicode[5] = HEX[(ch & 0xf) as usize];
+                &unicode
+            }
+            _ => continue,
+        };
+        write(&value[start..i])?;
+        write(esc)?;
+        start = i + 1;
+    }
+    write(&value[start..])
+}
+
+/// Returns the length of `value` escaped with [`json_escape`].
+pub fn json_escaped_len(value: &[u8]) -> usize {
+    let mut len = 0;
+    let _ = json_escape::<()>(value, |x| {
+        len += x.len();
+        Ok(())
+    });
+    len
+}
+
+fn format_u64(mut value: u64, buf: &mut [u8; 20]) -> &[u8] {
+    let mut pos = buf.len();
+    loop {
+        pos -= 1;
+        buf[pos] = b'0' + (value % 10) as u8;
+        value /= 10;
+        if value == 0 {
+            break;
+        }
+    }
+    &buf[pos..]
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use std::vec::Vec;
+
+    use super::*;
+
+    impl JsonSink for Vec<u8> {
+        fn write_bytes(&mut self, data: &[u8]) -> Result<(), AllocError> {
+            self.extend_from_slice(data);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_json_writer() -> Result<(), JsonError> {
+        let mut json = JsonWriter::new(Vec::new());
+        json.begin_object()?;
+        json.key("a")?;
+        json.uint(0)?;
+        json.key("b")?;
+        json.begin_array()?;
+        json.int(-12)?;
+        json.bool(true)?;
+        json.null()?;
+        json.begin_object()?;
+        json.end_object()?;
+        json.begin_array()?;
+        json.end_array()?;
+        json.end_array()?;
+        json.key_bytes(b"c\"")?;
+        json.bytes(b"x\ny\x01\\")?;
+        json.key("d")?;
+        json.uint(u64::MAX)?;
+        json.end_object()?;
+
+        let out = json.into_inner()?;
+        assert_eq!(
+            core::str::from_utf8(&out).unwrap(),
+            r#"{"a":0,"b":[-12,true,null,{},[]],"c\"":"x\ny\u0001\\","d":18446744073709551615}"#
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_json_errors() {
+        let mut json = JsonWriter::new(Vec::new());
+        assert_eq!(json.key("a"), Err(JsonError::Unbalanced));
+        json.begin_object().unwrap();
+        assert_eq!(json.uint(1), Err(JsonError::Unbalanced));
+        assert_eq!(json.end_array(), Err(JsonError::Unbalanced));
+        json.key("a").unwrap();
+        assert_eq!(json.end_object(), Err(JsonError::Unbalanced));
+
+        let mut json = JsonWriter::new(Vec::new());
+        for _ in 0..JSON_MAX_DEPTH {
+            json.begin_array().unwrap();
+        }
+        assert_eq!(json.begin_array(), Err(JsonError::TooDeep));
+        assert!(json.into_inner().is_err());
+    }
+
+    #[test]
+    fn test_json_escaped_len() {
+        assert_eq!(json_escaped_len(b"abc"), 3);
+        assert_eq!(json_escaped_len(b"a\"\x1f"), 9);
+    }
+}
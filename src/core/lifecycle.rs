@@ -0,0 +1,100 @@
+use core::fmt;
+
+use crate::core::Status;
+use crate::ffi::{NGX_LOG_EMERG, ngx_cycle_t, ngx_int_t, ngx_log_t, ngx_module_t};
+use crate::ngx_log_error;
+
+/// Hooks run by nginx when the master and the worker processes start and exit.
+///
+/// The hooks are wired to the `init_master`, `init_process`, `exit_process` and `exit_master`
+/// fields of the module with [`with_process_lifecycle`], and all have a no-op default. The
+/// `init_process` hook is the place to start the background work of a worker process, e.g. a
+/// recurring [`Timer`](crate::core::Timer) or a task spawned with
+/// [`spawn`](crate::async_::spawn).
+///
+/// ```no_run
+/// # use ngx::core::{ProcessLifecycle, with_process_lifecycle};
+/// # use ngx::ffi::{ngx_cycle_t, ngx_module_t};
+/// struct Module;
+///
+/// impl ProcessLifecycle for Module {
+///     type Error = &'static str;
+///
+///     fn init_process(cycle: &mut ngx_cycle_t) -> Result<(), Self::Error> {
+///         // start the background work
+///         Ok(())
+///     }
+/// }
+///
+/// #[used]
+/// #[allow(non_upper_case_globals)]
+/// pub static mut ngx_foo_module: ngx_module_t = with_process_lifecycle::<Module>(ngx_module_t {
+///     // ctx, commands, type_
+///     ..ngx_module_t::default()
+/// });
+/// ```
+pub trait ProcessLifecycle: 'static {
+    /// Error returned by the initialization hooks.
+    type Error: fmt::Display;
+
+    /// Initializes the master process.
+    ///
+    /// Note that nginx does not currently call the `init_master` handlers of the modules.
+    fn init_master(_log: &mut ngx_log_t) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    /// Initializes a worker process, or the single process.
+    ///
+    /// A failure is logged, and the worker process exits.
+    fn init_process(_cycle: &mut ngx_cycle_t) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    /// Runs on the exit of a worker process, or the single process.
+    fn exit_process(_cycle: &mut ngx_cycle_t) {}
+
+    /// Runs on the exit of the master process.
+    fn exit_master(_cycle: &mut ngx_cycle_t) {}
+}
+
+/// Sets the process lifecycle handlers of `module` to the [`ProcessLifecycle`] hooks of `T`.
+pub const fn with_process_lifecycle<T: ProcessLifecycle>(mut module: ngx_module_t) -> ngx_module_t {
+    module.init_master = Some(init_master_handler::<T>);
+    module.init_process = Some(init_process_handler::<T>);
+    module.exit_process = Some(exit_process_handler::<T>);
+    module.exit_master = Some(exit_master_handler::<T>);
+    module
+}
+
+unsafe extern "C" fn init_master_handler<T: ProcessLifecycle>(log: *mut ngx_log_t) -> ngx_int_t {
+    // SAFETY: nginx calls the handler with a valid log.
+    let log = unsafe { &mut *log };
+    if let Err(err) = T::init_master(log) {
+        ngx_log_error!(NGX_LOG_EMERG, log, "{}", err);
+        return Status::NGX_ERROR.into();
+    }
+    Status::NGX_OK.into()
+}
+
+unsafe extern "C" fn init_process_handler<T: ProcessLifecycle>(
+    cycle: *mut ngx_cycle_t,
+) -> ngx_int_t {
+    // SAFETY: nginx calls the handler with the current cycle.
+    let cycle = unsafe { &mut *cycle };
+    if let Err(err) = T::init_process(cycle) {
+        ngx_log_error!(NGX_LOG_EMERG, cycle.log, "{}", err);
+        return Status::NGX_ERROR.into();
+    }
+    Status::NGX_OK.into()
+}
+
+unsafe extern "C" fn exit_process_handler<T: ProcessLifecycle>(cycle: *mut ngx_cycle_t) {
+    // SAFETY: nginx calls the handler with the current cycle.
+    T::exit_process(unsafe { &mut *cycle })
+}
+
+unsafe extern "C" fn exit_master_handler<T: ProcessLifecycle>(cycle: *mut ngx_cycle_t) {
+    // SAFETY: nginx calls the handler with the current cycle.
+    T::exit_master(unsafe { &mut *cycle })
+}
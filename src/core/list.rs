@@ -0,0 +1,224 @@
+use core::marker::PhantomData;
+use core::ptr::{self, NonNull};
+use core::{fmt, mem, slice};
+
+use crate::allocator::AllocError;
+use crate::core::Pool;
+use crate::ffi::{NGX_OK, ngx_int_t, ngx_list_init, ngx_list_part_t, ngx_list_push, ngx_list_t};
+
+/// Typed view of an [`ngx_list_t`] with elements of type `T`, e.g. the request headers.
+///
+/// Unlike [`NgxArray`](crate::core::NgxArray), the list allocates a new part when full and never
+/// moves the elements, so the references to the elements remain valid. The elements are never
+/// dropped, thus adding the elements requires `T: Copy`.
+///
+/// See <https://nginx.org/en/docs/dev/development_guide.html#list>.
+#[repr(transparent)]
+pub struct NgxList<T> {
+    raw: ngx_list_t,
+    _marker: PhantomData<T>,
+}
+
+impl<T> NgxList<T> {
+    /// Creates a list in the `pool` with `n` elements per part.
+    ///
+    /// The list is valid for the lifetime of the pool.
+    pub fn create(pool: &Pool, n: usize) -> Result<NonNull<Self>, AllocError> {
+        let list = pool.alloc_type::<ngx_list_t>();
+        if list.is_null() {
+            return Err(AllocError);
+        }
+        // SAFETY: `list` is allocated above and `pool` is a valid pool.
+        let rc = unsafe { ngx_list_init(list, pool.as_ptr(), n.max(1), mem::size_of::<T>()) };
+        if rc != NGX_OK as ngx_int_t {
+            return Err(AllocError);
+        }
+        // SAFETY: `list` is non-null and initialized above.
+        Ok(unsafe { NonNull::new_unchecked(list.cast()) })
+    }
+
+    /// Creates a typed reference to an existing list.
+    ///
+    /// # Safety
+    ///
+    /// The list must be initialized and contain elements of type `T`.
+    pub unsafe fn from_ngx_list(raw: &ngx_list_t) -> &Self {
+        debug_assert_eq!(raw.size, mem::size_of::<T>(), "NgxList: element size mismatch");
+        // SAFETY: the type is a transparent wrapper over `ngx_list_t`.
+        unsafe { &*ptr::from_ref(raw).cast() }
+    }
+
+    /// Creates a typed mutable reference to an existing list.
+    ///
+    /// # Safety
+    ///
+    /// The list must be initialized and contain elements of type `T`.
+    pub unsafe fn from_ngx_list_mut(raw: &mut ngx_list_t) -> &mut Self {
+        debug_assert_eq!(raw.size, mem::size_of::<T>(), "NgxList: element size mismatch");
+        // SAFETY: the type is a transparent wrapper over `ngx_list_t`.
+        unsafe { &mut *ptr::from_mut(raw).cast() }
+    }
+
+    /// Returns a reference to the underlying [`ngx_list_t`].
+    pub fn as_ngx_list(&self) -> &ngx_list_t {
+        &self.raw
+    }
+
+    /// Returns the pool used for the list storage.
+    pub fn pool(&self) -> Pool {
+        // SAFETY: the list is created with a valid pool.
+        unsafe { Pool::from_ngx_pool(self.raw.pool) }
+    }
+
+    /// Returns `true` if the list contains no elements.
+    pub fn is_empty(&self) -> bool {
+        // The elements are appended to the last part, so the first part is only empty if the list
+        // is empty.
+        self.raw.part.nelts == 0
+    }
+
+    /// Returns the number of elements in the list.
+    ///
+    /// The operation walks all the parts of the list.
+    pub fn len(&self) -> usize {
+        self.iter_parts().map(|x| x.len()).sum()
+    }
+
+    /// Returns an iterator over the elements.
+    pub fn iter(&self) -> ListIter<'_, T> {
+        ListIter { part: &raw const self.raw.part, i: 0, _marker: PhantomData }
+    }
+
+    /// Returns an iterator over the mutable references to the elements.
+    pub fn iter_mut(&mut self) -> ListIterMut<'_, T> {
+        ListIterMut { part: &raw mut self.raw.part, i: 0, _marker: PhantomData }
+    }
+
+    fn iter_parts(&self) -> impl Iterator<Item = &[T]> + '_ {
+        let mut part: *const ngx_list_part_t = &self.raw.part;
+        core::iter::from_fn(move || {
+            // SAFETY: the parts are valid for the lifetime of the list.
+            let p = unsafe { part.as_ref()? };
+            part = p.next;
+            Some(unsafe { part_slice(p) })
+        })
+    }
+}
+
+impl<T: Copy> NgxList<T> {
+    /// Appends an element to the list, returning a reference to the stored element.
+    pub fn push(&mut self, value: T) -> Result<&mut T, AllocError> {
+        let elt = unsafe { ngx_list_push(&raw mut self.raw) }.cast::<T>();
+        if elt.is_null() {
+            return Err(AllocError);
+        }
+        // SAFETY: `elt` points to the uninitialized storage for an element of type `T`.
+        unsafe {
+            elt.write(value);
+            Ok(&mut *elt)
+        }
+    }
+}
+
+/// Returns the elements of a list part.
+///
+/// # Safety
+///
+/// The part must belong to an initialized list with elements of type `T`.
+unsafe fn part_slice<T>(part: &ngx_list_part_t) -> &[T] {
+    if part.nelts == 0 {
+        &[]
+    } else {
+        unsafe { slice::from_raw_parts(part.elts.cast(), part.nelts) }
+    }
+}
+
+impl<'a, T> IntoIterator for &'a NgxList<T> {
+    type Item = &'a T;
+    type IntoIter = ListIter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a, T> IntoIterator for &'a mut NgxList<T> {
+    type Item = &'a mut T;
+    type IntoIter = ListIterMut<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for NgxList<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+/// Iterator over the elements of an [`NgxList`].
+pub struct ListIter<'a, T> {
+    part: *const ngx_list_part_t,
+    i: usize,
+    _marker: PhantomData<&'a T>,
+}
+
+impl<'a, T> ListIter<'a, T> {
+    /// Creates an iterator over the elements of a raw list.
+    ///
+    /// # Safety
+    ///
+    /// The list must be initialized and contain elements of type `T`.
+    pub unsafe fn from_ngx_list(raw: &'a ngx_list_t) -> Self {
+        Self { part: &raw.part, i: 0, _marker: PhantomData }
+    }
+}
+
+impl<'a, T> Iterator for ListIter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // SAFETY: the parts are valid for the lifetime of the list.
+        let mut part = unsafe { self.part.as_ref()? };
+        while self.i >= part.nelts {
+            self.part = part.next;
+            part = unsafe { self.part.as_ref()? };
+            self.i = 0;
+        }
+        let elt = unsafe { &*part.elts.cast::<T>().add(self.i) };
+        self.i += 1;
+        Some(elt)
+    }
+}
+
+impl<T> Clone for ListIter<'_, T> {
+    fn clone(&self) -> Self {
+        Self { part: self.part, i: self.i, _marker: PhantomData }
+    }
+}
+
+/// Mutable iterator over the elements of an [`NgxList`].
+pub struct ListIterMut<'a, T> {
+    part: *mut ngx_list_part_t,
+    i: usize,
+    _marker: PhantomData<&'a mut T>,
+}
+
+impl<'a, T> Iterator for ListIterMut<'a, T> {
+    type Item = &'a mut T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // SAFETY: the parts are valid for the lifetime of the list, and each element is returned
+        // once.
+        let mut part = unsafe { self.part.as_mut()? };
+        while self.i >= part.nelts {
+            self.part = part.next;
+            part = unsafe { self.part.as_mut()? };
+            self.i = 0;
+        }
+        let elt = unsafe { &mut *part.elts.cast::<T>().add(self.i) };
+        self.i += 1;
+        Some(elt)
+    }
+}
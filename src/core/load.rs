@@ -0,0 +1,161 @@
+use core::ffi::c_ulong;
+use core::mem;
+
+use crate::ffi::{ngx_cpuset_t, ngx_cycle, ngx_get_cpu_affinity, ngx_msec_t, ngx_uint_t};
+
+/// Set of CPUs, as configured with the `worker_cpu_affinity` directive.
+#[derive(Clone, Copy)]
+pub struct CpuSet(ngx_cpuset_t);
+
+impl CpuSet {
+    const WORDS: usize = mem::size_of::<ngx_cpuset_t>() / mem::size_of::<c_ulong>();
+
+    fn words(&self) -> &[c_ulong] {
+        // SAFETY: the CPU set is a bit array of machine words on all supported systems.
+        unsafe { core::slice::from_raw_parts((&raw const self.0).cast(), Self::WORDS) }
+    }
+
+    /// Returns `true` if the set contains the CPU.
+    pub fn contains(&self, cpu: usize) -> bool {
+        let bits = c_ulong::BITS as usize;
+        self.words().get(cpu / bits).is_some_and(|w| w & (1 << (cpu % bits)) != 0)
+    }
+
+    /// Returns the number of CPUs in the set.
+    pub fn len(&self) -> usize {
+        self.words().iter().map(|w| w.count_ones() as usize).sum()
+    }
+
+    /// Returns `true` if the set is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns an iterator over the CPUs in the set.
+    pub fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..Self::WORDS * c_ulong::BITS as usize).filter(|&cpu| self.contains(cpu))
+    }
+}
+
+impl core::fmt::Debug for CpuSet {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+/// Returns the CPU affinity of the worker process `worker`, as configured with the
+/// `worker_cpu_affinity` directive, including the `auto` mode.
+///
+/// Returns `None` if the directive is not set.
+pub fn worker_cpu_affinity(worker: ngx_uint_t) -> Option<CpuSet> {
+    // SAFETY: the function only reads the configuration of the current cycle.
+    let set = unsafe { ngx_get_cpu_affinity(worker).as_ref()? };
+    Some(CpuSet(*set))
+}
+
+/// Load of the current worker process, see [`LoadSampler`].
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct LoadSample {
+    /// Number of connections in use in the current worker process, including the connections to
+    /// the upstream servers.
+    pub active_connections: usize,
+    /// Maximum number of connections of the worker process, `worker_connections`.
+    pub max_connections: usize,
+    /// Rate of the requests per second in the current worker process, counted with
+    /// [`LoadSampler::count_request`].
+    ///
+    /// The rate is `None` on the first sample.
+    pub worker_requests_per_sec: Option<f64>,
+    /// Rate of the client requests per second, for all the worker processes.
+    ///
+    /// Requires the `stub_status` module. The rate is `None` on the first sample.
+    pub total_requests_per_sec: Option<f64>,
+}
+
+impl LoadSample {
+    /// Returns the ratio of the used connections of the worker process, from 0 to 1.
+    pub fn connection_usage(&self) -> f64 {
+        if self.max_connections == 0 {
+            return 0.0;
+        }
+        self.active_connections as f64 / self.max_connections as f64
+    }
+}
+
+/// Sampler of the worker process load, for the modules adapting their behavior to the load, e.g.
+/// rejecting expensive requests while the worker is saturated.
+///
+/// The worker request rate is computed from the requests counted with
+/// [`LoadSampler::count_request`], so the sampler is expected to be kept per worker process, e.g.
+/// in a `thread_local!`. The total request rate is computed from the counters of the
+/// `stub_status` module, stored in the shared memory. Both rates are measured between two
+/// consecutive samples.
+#[derive(Debug, Default)]
+pub struct LoadSampler {
+    requests: u64,
+    /// Time, worker requests and total requests of the previous sample.
+    last: Option<(ngx_msec_t, u64, Option<u64>)>,
+    worker_rate: Option<f64>,
+    total_rate: Option<f64>,
+}
+
+impl LoadSampler {
+    /// Creates a new sampler.
+    pub const fn new() -> Self {
+        Self { requests: 0, last: None, worker_rate: None, total_rate: None }
+    }
+
+    /// Counts a request processed by the current worker process.
+    pub fn count_request(&mut self) {
+        self.requests += 1;
+    }
+
+    /// Takes a sample of the load.
+    ///
+    /// The request rates are updated at most once per second, and the previous values are
+    /// returned otherwise.
+    pub fn sample(&mut self) -> LoadSample {
+        // SAFETY: the cycle is valid in a worker process.
+        let cycle = unsafe { &*ngx_cycle };
+
+        let now = super::current_msec();
+        let total = total_requests();
+
+        match self.last {
+            Some((time, requests, prev_total)) => {
+                let elapsed = now.wrapping_sub(time);
+                if elapsed >= 1000 {
+                    let per_sec = |delta: u64| delta as f64 * 1000.0 / elapsed as f64;
+                    self.worker_rate = Some(per_sec(self.requests - requests));
+                    self.total_rate =
+                        total.zip(prev_total).map(|(t, prev)| per_sec(t.saturating_sub(prev)));
+                    self.last = Some((now, self.requests, total));
+                }
+            }
+            None => self.last = Some((now, self.requests, total)),
+        }
+
+        LoadSample {
+            active_connections: (cycle.connection_n - cycle.free_connection_n) as usize,
+            max_connections: cycle.connection_n as usize,
+            worker_requests_per_sec: self.worker_rate,
+            total_requests_per_sec: self.total_rate,
+        }
+    }
+}
+
+#[cfg(ngx_feature = "stat_stub")]
+fn total_requests() -> Option<u64> {
+    // SAFETY: the counter is allocated in the shared memory before the worker processes start.
+    let p = unsafe { crate::ffi::ngx_stat_requests };
+    if p.is_null() {
+        return None;
+    }
+    // SAFETY: the counter is updated atomically.
+    Some(unsafe { core::ptr::read_volatile(p) } as u64)
+}
+
+#[cfg(not(ngx_feature = "stat_stub"))]
+fn total_requests() -> Option<u64> {
+    None
+}
@@ -1,16 +1,92 @@
+mod array;
 mod buffer;
+mod cacheline;
+#[cfg(feature = "cbor")]
+pub mod cbor;
+mod clock;
+mod command;
 mod conf;
+mod conf_args;
+#[cfg(feature = "alloc")]
+mod conf_check;
+mod conf_file;
+#[cfg(feature = "alloc")]
+mod conf_list;
+mod conf_unset;
+mod connection;
+mod cycle_init;
+mod error;
+mod event;
+mod feature;
+mod generation;
+mod json;
+mod lifecycle;
+mod list;
+mod load;
+mod number;
+mod peer;
+mod pem;
+#[cfg(feature = "std")]
+mod persist;
 mod pool;
+mod process;
+mod proctitle;
+mod shared_zone;
 pub mod slab;
+mod sockopt;
 mod status;
+pub mod str;
 mod string;
+#[cfg(any(ngx_feature = "threads", feature = "feature-stubs"))]
+mod thread_pool;
+#[cfg(feature = "alloc")]
+mod timer;
+mod units;
+mod version;
 
+pub use array::NgxArray;
 pub use buffer::*;
+pub use cacheline::{CachePadded, cacheline_size};
+pub(crate) use clock::{add_timer, current_msec, del_timer, time, timeofday};
+pub use command::{ConfArg, ConfArgError, conf_take};
 pub use conf::*;
+pub use conf_args::{ConfArgs, ConfEnum};
+#[cfg(feature = "alloc")]
+pub use conf_check::{CheckMode, ConfCheck, conf_check, conf_check_handler};
+pub use conf_file::*;
+#[cfg(feature = "alloc")]
+pub use conf_list::*;
+pub use conf_unset::*;
+pub use connection::*;
+pub use cycle_init::{CycleInit, cycle_init_handler};
+pub use error::*;
+pub use event::*;
+pub use feature::*;
+pub use generation::Generation;
+pub use json::*;
+pub use lifecycle::{ProcessLifecycle, with_process_lifecycle};
+pub use list::{ListIter, ListIterMut, NgxList};
+pub use load::{CpuSet, LoadSample, LoadSampler, worker_cpu_affinity};
+pub use number::{FloatBuffer, IntBuffer, Integer, MAX_FLOAT_PRECISION};
+pub(crate) use number::{LenCounter, SliceWriter};
+pub use peer::{ConnectState, PeerConnection};
+pub(crate) use pem::pem_to_der;
+#[cfg(feature = "std")]
+pub use persist::*;
 pub use pool::*;
+pub use process::*;
+pub use proctitle::{ProcessTitleGuard, set_process_title_suffix};
+pub use shared_zone::SharedZone;
 pub use slab::SlabPool;
+pub use sockopt::*;
 pub use status::*;
 pub use string::*;
+#[cfg(any(ngx_feature = "threads", feature = "feature-stubs"))]
+pub use thread_pool::ThreadPool;
+#[cfg(feature = "alloc")]
+pub use timer::Timer;
+pub use units::*;
+pub use version::*;
 
 /// Gets an outer object pointer from a pointer to one of its fields.
 /// While there is no corresponding C macro, the pattern is common in the NGINX source.
@@ -0,0 +1,282 @@
+//! Locale-independent formatting of numbers without `core::fmt`.
+//!
+//! The formatting machinery of `core::fmt` is relatively expensive for the short strings produced
+//! on the hot paths, e.g. in the variable handlers or when setting a response header. The buffers
+//! here format a number on the stack, and the result can be copied to the request pool or passed
+//! to a function accepting a string:
+//!
+//! ```
+//! # use ngx::core::IntBuffer;
+//! let mut buf = IntBuffer::new();
+//! assert_eq!(buf.format(-1234i32), "-1234");
+//! ```
+use core::{fmt, str};
+
+/// Maximum length of a formatted 64-bit integer: `-9223372036854775808` or `18446744073709551615`.
+const INT_MAX_LEN: usize = 20;
+
+/// Maximum precision accepted by [`FloatBuffer::format_fixed`].
+pub const MAX_FLOAT_PRECISION: usize = 9;
+
+/// Maximum length of a formatted `f64`: the sign, 309 digits of the integer part, the decimal
+/// point and the fractional part.
+const FLOAT_MAX_LEN: usize = 1 + 309 + 1 + MAX_FLOAT_PRECISION;
+
+const DIGITS: &[u8; 200] = b"\
+    0001020304050607080910111213141516171819\
+    2021222324252627282930313233343536373839\
+    4041424344454647484950515253545556575859\
+    6061626364656667686970717273747576777879\
+    8081828384858687888990919293949596979899";
+
+mod private {
+    pub trait Sealed {}
+}
+
+/// Integer types supported by [`IntBuffer`].
+pub trait Integer: Copy + private::Sealed {
+    #[doc(hidden)]
+    fn write_to(self, buf: &mut [u8; INT_MAX_LEN]) -> usize;
+}
+
+macro_rules! impl_unsigned {
+    ($($t:ty),*) => {$(
+        impl private::Sealed for $t {}
+
+        impl Integer for $t {
+            #[inline]
+            fn write_to(self, buf: &mut [u8; INT_MAX_LEN]) -> usize {
+                write_u64(self as u64, buf)
+            }
+        }
+    )*};
+}
+
+macro_rules! impl_signed {
+    ($($t:ty),*) => {$(
+        impl private::Sealed for $t {}
+
+        impl Integer for $t {
+            #[inline]
+            fn write_to(self, buf: &mut [u8; INT_MAX_LEN]) -> usize {
+                let mut pos = write_u64(self.unsigned_abs() as u64, buf);
+                if self < 0 {
+                    pos -= 1;
+                    buf[pos] = b'-';
+                }
+                pos
+            }
+        }
+    )*};
+}
+
+impl_unsigned!(u8, u16, u32, u64, usize);
+impl_signed!(i8, i16, i32, i64, isize);
+
+/// Writes the decimal digits of `n` at the end of `buf` and returns the start position.
+fn write_u64(mut n: u64, buf: &mut [u8]) -> usize {
+    let mut pos = buf.len();
+
+    while n >= 100 {
+        let d = (n % 100) as usize * 2;
+        n /= 100;
+        pos -= 2;
+        buf[pos..pos + 2].copy_from_slice(&DIGITS[d..d + 2]);
+    }
+
+    if n >= 10 {
+        let d = n as usize * 2;
+        pos -= 2;
+        buf[pos..pos + 2].copy_from_slice(&DIGITS[d..d + 2]);
+    } else {
+        pos -= 1;
+        buf[pos] = b'0' + n as u8;
+    }
+
+    pos
+}
+
+/// Stack buffer for formatting an integer in decimal.
+#[derive(Clone, Copy)]
+pub struct IntBuffer {
+    buf: [u8; INT_MAX_LEN],
+}
+
+impl IntBuffer {
+    /// Creates a buffer.
+    pub const fn new() -> Self {
+        Self { buf: [0; INT_MAX_LEN] }
+    }
+
+    /// Formats `n` into the buffer and returns the string.
+    pub fn format<T: Integer>(&mut self, n: T) -> &str {
+        let pos = n.write_to(&mut self.buf);
+        // SAFETY: the buffer contains ASCII digits and the sign.
+        unsafe { str::from_utf8_unchecked(&self.buf[pos..]) }
+    }
+}
+
+impl Default for IntBuffer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Stack buffer for formatting a floating point number with a fixed number of decimal places.
+#[derive(Clone, Copy)]
+pub struct FloatBuffer {
+    buf: [u8; FLOAT_MAX_LEN],
+}
+
+impl FloatBuffer {
+    /// Creates a buffer.
+    pub const fn new() -> Self {
+        Self { buf: [0; FLOAT_MAX_LEN] }
+    }
+
+    /// Formats `f` with `precision` decimal places, as with `%.Nf` in `ngx_sprintf`, and returns
+    /// the string.
+    ///
+    /// The value is rounded half away from zero. The precision is limited to
+    /// [`MAX_FLOAT_PRECISION`]. Non-finite values are formatted as `NaN`, `inf` and `-inf`.
+    ///
+    /// ```
+    /// # use ngx::core::FloatBuffer;
+    /// let mut buf = FloatBuffer::new();
+    /// assert_eq!(buf.format_fixed(0.1234, 3), "0.123");
+    /// assert_eq!(buf.format_fixed(-2.5, 0), "-3");
+    /// ```
+    pub fn format_fixed(&mut self, f: f64, precision: usize) -> &str {
+        let precision = precision.min(MAX_FLOAT_PRECISION);
+
+        if f.is_nan() {
+            return "NaN";
+        }
+        if f.is_infinite() {
+            return if f > 0.0 { "inf" } else { "-inf" };
+        }
+
+        let scale = 10u64.pow(precision as u32);
+        let scaled = f.abs() * scale as f64 + 0.5;
+
+        let len = if scaled < u64::MAX as f64 {
+            let n = scaled as u64;
+            self.write_fixed(f.is_sign_negative() && n != 0, n / scale, n % scale, precision)
+        } else {
+            // The integer part does not fit into u64, and the value has no fractional part.
+            let mut w = SliceWriter::new(&mut self.buf);
+            let _ = fmt::Write::write_fmt(&mut w, format_args!("{f:.precision$}"));
+            w.pos
+        };
+
+        // SAFETY: the buffer contains ASCII digits, the sign and the decimal point.
+        unsafe { str::from_utf8_unchecked(&self.buf[..len]) }
+    }
+
+    fn write_fixed(&mut self, negative: bool, int: u64, frac: u64, precision: usize) -> usize {
+        let mut digits = [0u8; INT_MAX_LEN];
+        let mut len = 0;
+
+        if negative {
+            self.buf[len] = b'-';
+            len += 1;
+        }
+
+        let pos = write_u64(int, &mut digits);
+        let int = &digits[pos..];
+        self.buf[len..len + int.len()].copy_from_slice(int);
+        len += int.len();
+
+        if precision > 0 {
+            self.buf[len] = b'.';
+            len += 1;
+
+            let frac_start = len;
+            len += precision;
+            self.buf[frac_start..len].fill(b'0');
+            let pos = write_u64(frac, &mut digits);
+            let frac = &digits[pos..];
+            self.buf[len - frac.len()..len].copy_from_slice(frac);
+        }
+
+        len
+    }
+}
+
+impl Default for FloatBuffer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Counts the length of the formatted output.
+pub(crate) struct LenCounter(pub(crate) usize);
+
+impl fmt::Write for LenCounter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.0 += s.len();
+        Ok(())
+    }
+}
+
+/// Writes the formatted output into a slice, failing if the output does not fit.
+pub(crate) struct SliceWriter<'a> {
+    pub(crate) buf: &'a mut [u8],
+    pub(crate) pos: usize,
+}
+
+impl<'a> SliceWriter<'a> {
+    pub(crate) fn new(buf: &'a mut [u8]) -> Self {
+        Self { buf, pos: 0 }
+    }
+}
+
+impl fmt::Write for SliceWriter<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let end = self.pos + s.len();
+        self.buf.get_mut(self.pos..end).ok_or(fmt::Error)?.copy_from_slice(s.as_bytes());
+        self.pos = end;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use std::format;
+
+    use super::*;
+
+    #[test]
+    fn test_format_int() {
+        let mut buf = IntBuffer::new();
+        assert_eq!(buf.format(0u8), "0");
+        assert_eq!(buf.format(7i32), "7");
+        assert_eq!(buf.format(10u16), "10");
+        assert_eq!(buf.format(-99i64), "-99");
+        assert_eq!(buf.format(1234567usize), "1234567");
+        assert_eq!(buf.format(u64::MAX), "18446744073709551615");
+        assert_eq!(buf.format(i64::MIN), "-9223372036854775808");
+        assert_eq!(buf.format(i8::MIN), "-128");
+
+        for n in [1u64, 9, 99, 100, 101, 999, 1000, 65535, 4294967296] {
+            assert_eq!(buf.format(n), format!("{n}"));
+        }
+    }
+
+    #[test]
+    fn test_format_fixed() {
+        let mut buf = FloatBuffer::new();
+        assert_eq!(buf.format_fixed(0.0, 3), "0.000");
+        assert_eq!(buf.format_fixed(1.5, 0), "2");
+        assert_eq!(buf.format_fixed(0.001, 3), "0.001");
+        assert_eq!(buf.format_fixed(12.0625, 2), "12.06");
+        assert_eq!(buf.format_fixed(-0.0001, 3), "0.000");
+        assert_eq!(buf.format_fixed(-1.25, 1), "-1.3");
+        assert_eq!(buf.format_fixed(1.0, 20), "1.000000000");
+        assert_eq!(buf.format_fixed(f64::NAN, 3), "NaN");
+        assert_eq!(buf.format_fixed(f64::NEG_INFINITY, 3), "-inf");
+        assert_eq!(buf.format_fixed(1e300, 2), format!("{:.2}", 1e300));
+        assert_eq!(buf.format_fixed(-f64::MAX, 9), format!("{:.9}", -f64::MAX));
+    }
+}
@@ -0,0 +1,282 @@
+use core::ffi::c_void;
+use core::mem;
+use core::net::SocketAddr;
+use core::ptr::{self, NonNull};
+
+use crate::core::{Connection, SocketType, Status, socket_addr_to_sockaddr};
+use crate::ffi::{
+    NGX_AGAIN, NGX_ERROR, NGX_OK, SO_ERROR, SOCK_DGRAM, SOCK_STREAM, SOL_SOCKET, getsockopt,
+    ngx_close_connection, ngx_connection_log_error_e_NGX_ERROR_ERR, ngx_err_t,
+    ngx_event_connect_peer, ngx_event_get_peer, ngx_event_handler_pt, ngx_handle_read_event,
+    ngx_handle_write_event, ngx_int_t, ngx_log_t, ngx_peer_connection_t, ngx_socket_errno,
+    ngx_str_t, sockaddr_storage, socklen_t,
+};
+
+/// Result of [`PeerConnection::connect`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConnectState {
+    /// The connection is established.
+    Connected,
+    /// The connection is in progress: the write event of the connection is posted once it is
+    /// established or failed, see [`PeerConnection::check_connected`].
+    InProgress,
+}
+
+/// Outgoing connection to a peer, opened outside of the upstream module.
+///
+/// The connection uses the nginx event loop: the read and write event handlers are set with
+/// [`PeerConnection::set_handlers`], and are called when the connection is ready for the I/O.
+/// [`PeerConnection::recv`] and [`PeerConnection::send`] return [`Status::NGX_AGAIN`] when the
+/// operation would block, and arm the corresponding event.
+///
+/// The connection is closed when the value is dropped.
+///
+/// ```no_run
+/// # use core::ptr::NonNull;
+/// # use ngx::core::{ConnectState, PeerConnection, Status};
+/// # use ngx::ffi::{ngx_event_t, ngx_log_t};
+/// unsafe extern "C" fn on_write(ev: *mut ngx_event_t) {
+///     // ...
+/// }
+///
+/// fn start(log: NonNull<ngx_log_t>) -> Result<PeerConnection, Status> {
+///     let mut peer = PeerConnection::new("127.0.0.1:9000".parse().unwrap(), log);
+///     match peer.connect()? {
+///         ConnectState::Connected => { /* send the request */ }
+///         ConnectState::InProgress => {
+///             peer.set_handlers(core::ptr::null_mut(), None, Some(on_write));
+///         }
+///     }
+///     Ok(peer)
+/// }
+/// ```
+pub struct PeerConnection {
+    pc: ngx_peer_connection_t,
+    addr: SocketAddr,
+    sockaddr: sockaddr_storage,
+    name: ngx_str_t,
+}
+
+impl PeerConnection {
+    /// Creates a stream (TCP) connection to `addr`, not yet connected.
+    pub fn new(addr: SocketAddr, log: NonNull<ngx_log_t>) -> Self {
+        // SAFETY: an all-zero value is a valid ngx_peer_connection_t.
+        let mut pc: ngx_peer_connection_t = unsafe { mem::zeroed() };
+        pc.get = Some(ngx_event_get_peer);
+        pc.log = log.as_ptr();
+        pc.set_log_error(ngx_connection_log_error_e_NGX_ERROR_ERR as _);
+        pc.type_ = SOCK_STREAM as _;
+
+        // SAFETY: an all-zero value is a valid sockaddr_storage.
+        let mut sockaddr = unsafe { mem::zeroed() };
+        pc.socklen = socket_addr_to_sockaddr(&addr, &mut sockaddr);
+
+        Self { pc, addr, sockaddr, name: ngx_str_t::default() }
+    }
+
+    /// Sets the type of the socket, [`SocketType::Stream`] or [`SocketType::Datagram`].
+    pub fn set_socket_type(&mut self, ty: SocketType) {
+        self.pc.type_ = match ty {
+            SocketType::Stream => SOCK_STREAM as _,
+            SocketType::Datagram => SOCK_DGRAM as _,
+            SocketType::Other(x) => x,
+        };
+    }
+
+    /// Sets the name of the peer used in the log messages, e.g. `auth.example.com:9000`.
+    ///
+    /// The name must outlive the connection.
+    pub fn set_name(&mut self, name: &'static [u8]) {
+        self.name = ngx_str_t { len: name.len(), data: name.as_ptr().cast_mut() };
+    }
+
+    /// Starts connecting to the peer.
+    ///
+    /// Returns [`Status::NGX_DECLINED`] if the connection failed immediately, e.g. was refused,
+    /// and [`Status::NGX_ERROR`] or [`Status::NGX_BUSY`] on other errors.
+    pub fn connect(&mut self) -> Result<ConnectState, Status> {
+        if !self.pc.connection.is_null() {
+            return Err(Status::NGX_ERROR);
+        }
+
+        // The fields are only used by ngx_event_connect_peer.
+        self.pc.sockaddr = (&raw mut self.sockaddr).cast();
+        self.pc.name = &raw mut self.name;
+
+        // SAFETY: the peer connection is initialized in `new`.
+        let rc = unsafe { ngx_event_connect_peer(&raw mut self.pc) };
+
+        if rc == NGX_OK as ngx_int_t {
+            Ok(ConnectState::Connected)
+        } else if rc == NGX_AGAIN as ngx_int_t {
+            Ok(ConnectState::InProgress)
+        } else {
+            // The connection is closed by ngx_event_connect_peer, except for NGX_ERROR returned
+            // after the socket is created.
+            self.close();
+            Err(Status(rc))
+        }
+    }
+
+    /// Checks the result of a connection in progress, once the write event is posted.
+    ///
+    /// Returns the socket error code on failure.
+    pub fn check_connected(&mut self) -> Result<(), ngx_err_t> {
+        let c = self.connection().ok_or(0)?;
+
+        let mut err: ngx_err_t = 0;
+        let mut len = mem::size_of::<ngx_err_t>() as socklen_t;
+        // SAFETY: the socket is valid while the connection is open.
+        let rc = unsafe {
+            getsockopt(c.fd(), SOL_SOCKET as _, SO_ERROR as _, (&raw mut err).cast(), &raw mut len)
+        };
+        if rc == -1 {
+            return Err(ngx_socket_errno());
+        }
+        if err != 0 {
+            return Err(err);
+        }
+        Ok(())
+    }
+
+    /// Returns the address of the peer.
+    ///
+    /// The address of the outgoing connection is not stored in the `ngx_connection_t`, so
+    /// [`Connection::remote_addr`] returns `None` for it.
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Returns the connection, if connected or connecting.
+    pub fn connection(&self) -> Option<&Connection> {
+        // SAFETY: the connection is either NULL or valid until closed.
+        unsafe { self.pc.connection.as_ref().map(|c| Connection::from_ptr(c)) }
+    }
+
+    /// Returns the mutable connection, if connected or connecting.
+    pub fn connection_mut(&mut self) -> Option<&mut Connection> {
+        // SAFETY: the connection is either NULL or valid until closed.
+        (!self.pc.connection.is_null())
+            .then(|| unsafe { Connection::from_ptr_mut(self.pc.connection) })
+    }
+
+    /// Returns the underlying [`ngx_peer_connection_t`].
+    pub fn as_ngx_peer_connection(&self) -> &ngx_peer_connection_t {
+        &self.pc
+    }
+
+    /// Sets the data of the connection and the handlers of the read and write events.
+    ///
+    /// The event handlers can obtain the data from the `data` field of the connection, itself
+    /// stored in the `data` field of the event.
+    pub fn set_handlers(
+        &mut self,
+        data: *mut c_void,
+        read: ngx_event_handler_pt,
+        write: ngx_event_handler_pt,
+    ) {
+        let Some(c) = self.connection_mut() else {
+            return;
+        };
+        let c = c.as_mut();
+        c.data = data;
+        // SAFETY: the events of an open connection are valid.
+        unsafe {
+            (*c.read).handler = read;
+            (*c.write).handler = write;
+        }
+    }
+
+    /// Receives data from the connection.
+    ///
+    /// Returns `Ok(0)` at the end of the stream, and [`Status::NGX_AGAIN`] if there is no data
+    /// to read. In the latter case, the read event is armed.
+    pub fn recv(&mut self, buf: &mut [u8]) -> Result<usize, Status> {
+        let c = self.pc.connection;
+        if c.is_null() {
+            return Err(Status::NGX_ERROR);
+        }
+
+        // SAFETY: `recv` is set for an open connection.
+        let n = unsafe {
+            let recv = (*c).recv.ok_or(Status::NGX_ERROR)?;
+            recv(c, buf.as_mut_ptr(), buf.len())
+        };
+
+        if n >= 0 {
+            return Ok(n as usize);
+        }
+        if n == NGX_AGAIN as isize {
+            // SAFETY: the read event of an open connection is valid.
+            if unsafe { ngx_handle_read_event((*c).read, 0) } != NGX_OK as ngx_int_t {
+                return Err(Status::NGX_ERROR);
+            }
+            return Err(Status::NGX_AGAIN);
+        }
+        Err(Status::NGX_ERROR)
+    }
+
+    /// Sends data to the connection.
+    ///
+    /// Returns the number of bytes sent, or [`Status::NGX_AGAIN`] if the data cannot be sent
+    /// now. In the latter case, the write event is armed.
+    pub fn send(&mut self, buf: &[u8]) -> Result<usize, Status> {
+        let c = self.pc.connection;
+        if c.is_null() {
+            return Err(Status::NGX_ERROR);
+        }
+
+        // SAFETY: `send` is set for an open connection.
+        let n = unsafe {
+            let send = (*c).send.ok_or(Status::NGX_ERROR)?;
+            send(c, buf.as_ptr().cast_mut(), buf.len())
+        };
+
+        if n > 0 || (n == 0 && buf.is_empty()) {
+            return Ok(n as usize);
+        }
+        if n == NGX_AGAIN as isize || n == 0 {
+            // SAFETY: the write event of an open connection is valid.
+            if unsafe { ngx_handle_write_event((*c).write, 0) } != NGX_OK as ngx_int_t {
+                return Err(Status::NGX_ERROR);
+            }
+            return Err(Status::NGX_AGAIN);
+        }
+        debug_assert_eq!(n, NGX_ERROR as isize);
+        Err(Status::NGX_ERROR)
+    }
+
+    /// Closes the connection, if open.
+    pub fn close(&mut self) {
+        let c = mem::replace(&mut self.pc.connection, ptr::null_mut());
+        if !c.is_null() {
+            // SAFETY: the connection is open and is not referenced after closing.
+            unsafe { ngx_close_connection(c) };
+        }
+    }
+}
+
+impl Drop for PeerConnection {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_peer_addr() {
+        // SAFETY: an all-zero value is a valid ngx_log_t.
+        let mut log: ngx_log_t = unsafe { mem::zeroed() };
+
+        for addr in ["127.0.0.1:9000", "[2001:db8::1]:53"] {
+            let addr: SocketAddr = addr.parse().unwrap();
+            let mut peer = PeerConnection::new(addr, NonNull::from(&mut log));
+            peer.set_socket_type(SocketType::Datagram);
+            assert_eq!(peer.addr(), addr);
+            assert!(peer.connection().is_none());
+        }
+    }
+}
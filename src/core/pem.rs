@@ -0,0 +1,39 @@
+/// Decodes the body of a PEM-encoded certificate into `out`.
+///
+/// Returns the length of the DER encoding. `out` should be at least as large as the PEM input.
+#[cfg_attr(not(any(ngx_feature = "http", ngx_feature = "ssl")), allow(dead_code))]
+pub(crate) fn pem_to_der(pem: &[u8], out: &mut [u8]) -> Option<usize> {
+    const BEGIN: &[u8] = b"-----BEGIN CERTIFICATE-----";
+    const END: &[u8] = b"-----END CERTIFICATE-----";
+
+    let start = pem.windows(BEGIN.len()).position(|x| x == BEGIN)? + BEGIN.len();
+    let body = &pem[start..];
+    let body = &body[..body.windows(END.len()).position(|x| x == END)?];
+
+    let mut acc: u32 = 0;
+    let mut bits = 0;
+    let mut len = 0;
+
+    for &ch in body {
+        let value = match ch {
+            b'A'..=b'Z' => ch - b'A',
+            b'a'..=b'z' => ch - b'a' + 26,
+            b'0'..=b'9' => ch - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            b'=' | b'\r' | b'\n' | b'\t' | b' ' => continue,
+            _ => return None,
+        };
+
+        acc = (acc << 6) | u32::from(value);
+        bits += 6;
+
+        if bits >= 8 {
+            bits -= 8;
+            *out.get_mut(len)? = (acc >> bits) as u8;
+            len += 1;
+        }
+    }
+
+    Some(len)
+}
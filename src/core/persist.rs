@@ -0,0 +1,241 @@
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::vec::Vec;
+
+const MAGIC: &[u8; 8] = b"NGXZONE\0";
+const FORMAT_VERSION: u32 = 1;
+
+/// Writer for a snapshot of the shared zone contents.
+///
+/// The snapshot is a sequence of key-value records, each protected by a checksum, so a damaged
+/// file loses only the records after the damage. The data is written to a temporary file, which
+/// replaces the snapshot on [`ZoneSnapshotWriter::commit`]; an interrupted write never damages
+/// the previous snapshot.
+///
+/// Usually written from the `exit_master` callback of the module on a graceful shutdown, and
+/// loaded with [`ZoneSnapshotReader`] from the zone `init` callback when the zone is created.
+///
+/// The snapshot does not carry the zone contents over a binary upgrade: the new master process
+/// creates its zones and loads the snapshot left by the previous shutdown while the old master
+/// process is still running, and the old one writes its snapshot only when it exits.
+pub struct ZoneSnapshotWriter {
+    file: BufWriter<File>,
+    tmp: PathBuf,
+    path: PathBuf,
+}
+
+impl ZoneSnapshotWriter {
+    /// Starts a snapshot at `path`.
+    ///
+    /// `version` identifies the format of the keys and values; a snapshot with a different
+    /// version is ignored by the reader.
+    pub fn create(path: impl AsRef<Path>, version: u32) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let mut tmp = path.clone().into_os_string();
+        tmp.push(".tmp");
+        let tmp = PathBuf::from(tmp);
+
+        let mut file = BufWriter::new(File::create(&tmp)?);
+        file.write_all(MAGIC)?;
+        file.write_all(&FORMAT_VERSION.to_le_bytes())?;
+        file.write_all(&version.to_le_bytes())?;
+
+        Ok(Self { file, tmp, path })
+    }
+
+    /// Appends a record.
+    pub fn write_entry(&mut self, key: &[u8], value: &[u8]) -> io::Result<()> {
+        let key_len = u32::try_from(key.len()).map_err(|_| io::ErrorKind::InvalidInput)?;
+        let value_len = u32::try_from(value.len()).map_err(|_| io::ErrorKind::InvalidInput)?;
+
+        let mut head = [0u8; 8];
+        head[..4].copy_from_slice(&key_len.to_le_bytes());
+        head[4..].copy_from_slice(&value_len.to_le_bytes());
+
+        let crc = crc32(crc32(crc32(0, &head), key), value);
+
+        self.file.write_all(&head)?;
+        self.file.write_all(key)?;
+        self.file.write_all(value)?;
+        self.file.write_all(&crc.to_le_bytes())
+    }
+
+    /// Completes the snapshot and replaces the previous one.
+    pub fn commit(self) -> io::Result<()> {
+        let file = self.file.into_inner().map_err(|err| err.into_error())?;
+        file.sync_all()?;
+        drop(file);
+        fs::rename(&self.tmp, &self.path)
+    }
+}
+
+/// Reader for a snapshot written with [`ZoneSnapshotWriter`].
+///
+/// Iterates over the records until the end of the file or the first damaged record.
+pub struct ZoneSnapshotReader {
+    file: BufReader<File>,
+    corrupted: bool,
+}
+
+impl ZoneSnapshotReader {
+    /// Opens the snapshot at `path`.
+    ///
+    /// Returns `None` if the file does not exist, is not a snapshot, or has a different
+    /// `version`.
+    pub fn open(path: impl AsRef<Path>, version: u32) -> io::Result<Option<Self>> {
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err),
+        };
+
+        let mut file = BufReader::new(file);
+        let mut head = [0u8; 16];
+        match file.read_exact(&mut head) {
+            Ok(()) => {}
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(err) => return Err(err),
+        }
+
+        if &head[..8] != MAGIC
+            || head[8..12] != FORMAT_VERSION.to_le_bytes()
+            || head[12..] != version.to_le_bytes()
+        {
+            return Ok(None);
+        }
+
+        Ok(Some(Self { file, corrupted: false }))
+    }
+
+    /// Returns `true` if the iteration stopped at a damaged or incomplete record.
+    pub fn is_corrupted(&self) -> bool {
+        self.corrupted
+    }
+
+    fn read_entry(&mut self) -> io::Result<Option<(Vec<u8>, Vec<u8>)>> {
+        let mut head = [0u8; 8];
+        let n = self.file.read(&mut head[..1])?;
+        if n == 0 {
+            return Ok(None);
+        }
+        self.file.read_exact(&mut head[1..])?;
+
+        let key_len = u32::from_le_bytes(head[..4].try_into().unwrap()) as usize;
+        let value_len = u32::from_le_bytes(head[4..].try_into().unwrap()) as usize;
+
+        let mut key = Vec::new();
+        let mut value = Vec::new();
+        (&mut self.file).take(key_len as u64).read_to_end(&mut key)?;
+        (&mut self.file).take(value_len as u64).read_to_end(&mut value)?;
+
+        let mut crc = [0u8; 4];
+        self.file.read_exact(&mut crc)?;
+
+        if key.len() != key_len
+            || value.len() != value_len
+            || u32::from_le_bytes(crc) != crc32(crc32(crc32(0, &head), &key), &value)
+        {
+            return Err(io::ErrorKind::InvalidData.into());
+        }
+
+        Ok(Some((key, value)))
+    }
+}
+
+impl Iterator for ZoneSnapshotReader {
+    type Item = (Vec<u8>, Vec<u8>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.corrupted {
+            return None;
+        }
+        match self.read_entry() {
+            Ok(entry) => entry,
+            Err(_) => {
+                self.corrupted = true;
+                None
+            }
+        }
+    }
+}
+
+/// CRC-32 (IEEE), continuing from `crc`.
+fn crc32(crc: u32, data: &[u8]) -> u32 {
+    let mut crc = !crc;
+    for &b in data {
+        crc ^= u32::from(b);
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xedb8_8320 & (crc & 1).wrapping_neg());
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::OpenOptions;
+    use std::io::{Seek, SeekFrom};
+
+    use super::*;
+
+    fn write(path: &Path, version: u32, entries: &[(&[u8], &[u8])]) {
+        let mut w = ZoneSnapshotWriter::create(path, version).unwrap();
+        for (k, v) in entries {
+            w.write_entry(k, v).unwrap();
+        }
+        w.commit().unwrap();
+    }
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(0, b"123456789"), 0xcbf4_3926);
+        assert_eq!(crc32(crc32(0, b"1234"), b"56789"), 0xcbf4_3926);
+    }
+
+    #[test]
+    fn test_snapshot() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("zone.snapshot");
+
+        assert!(ZoneSnapshotReader::open(&path, 1).unwrap().is_none());
+
+        write(&path, 1, &[(b"a", b"1"), (b"bb", b"\x00\xff"), (b"", b"")]);
+
+        let mut r = ZoneSnapshotReader::open(&path, 1).unwrap().unwrap();
+        let entries: Vec<_> = r.by_ref().collect();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[1], (b"bb".to_vec(), b"\x00\xff".to_vec()));
+        assert!(!r.is_corrupted());
+
+        assert!(ZoneSnapshotReader::open(&path, 2).unwrap().is_none());
+        assert!(!dir.path().join("zone.snapshot.tmp").exists());
+    }
+
+    #[test]
+    fn test_snapshot_corrupted() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("zone.snapshot");
+
+        write(&path, 1, &[(b"a", b"1"), (b"b", b"2")]);
+
+        // damage the value of the second record: header + first record + its header + key
+        let mut file = OpenOptions::new().write(true).open(&path).unwrap();
+        file.seek(SeekFrom::Start(16 + 14 + 8 + 1)).unwrap();
+        file.write_all(b"x").unwrap();
+        drop(file);
+
+        let mut r = ZoneSnapshotReader::open(&path, 1).unwrap().unwrap();
+        let entries: Vec<_> = r.by_ref().collect();
+        assert_eq!(entries, [(b"a".to_vec(), b"1".to_vec())]);
+        assert!(r.is_corrupted());
+
+        // truncated file
+        let len = fs::metadata(&path).unwrap().len();
+        OpenOptions::new().write(true).open(&path).unwrap().set_len(len - 2).unwrap();
+
+        let mut r = ZoneSnapshotReader::open(&path, 1).unwrap().unwrap();
+        assert_eq!(r.by_ref().count(), 1);
+        assert!(r.is_corrupted());
+    }
+}
@@ -5,7 +5,7 @@ use core::ptr::{self, NonNull};
 
 use nginx_sys::{
     NGX_ALIGNMENT, ngx_buf_t, ngx_create_temp_buf, ngx_palloc, ngx_pcalloc, ngx_pfree,
-    ngx_pmemalign, ngx_pnalloc, ngx_pool_cleanup_add, ngx_pool_t,
+    ngx_pmemalign, ngx_pnalloc, ngx_pool_cleanup_add, ngx_pool_t, ngx_str_t,
 };
 
 use crate::allocator::{AllocError, Allocator, dangling_for_layout};
@@ -253,6 +253,20 @@ impl Pool {
         self.calloc(mem::size_of::<T>()) as *mut T
     }
 
+    /// Allocates memory for a type from the pool, aligned to the size of a cache line.
+    ///
+    /// The alignment is the greater of [`cacheline_size`](crate::core::cacheline_size) and the
+    /// alignment of `T`.
+    ///
+    /// Returns a typed pointer to the allocated memory, or a null pointer on failure.
+    pub fn alloc_aligned<T: Copy>(&self) -> *mut T {
+        let align = mem::align_of::<T>().max(super::cacheline_size());
+        Layout::from_size_align(mem::size_of::<T>(), align)
+            .ok()
+            .and_then(|layout| self.allocate(layout).ok())
+            .map_or(ptr::null_mut(), |p| p.as_ptr().cast())
+    }
+
     /// Allocates unaligned memory from the pool of the specified size.
     ///
     /// Returns a raw pointer to the allocated memory.
@@ -260,6 +274,14 @@ impl Pool {
         unsafe { ngx_pnalloc(self.0.as_ptr(), size) }
     }
 
+    /// Copies the string into the pool.
+    ///
+    /// Returns `None` if the allocation fails.
+    pub fn copy_str(&self, s: impl AsRef<[u8]>) -> Option<ngx_str_t> {
+        // SAFETY: the pool is valid, and the string is copied into the pool.
+        unsafe { ngx_str_t::from_bytes(self.as_ptr(), s.as_ref()) }
+    }
+
     /// Allocates unaligned memory for a type from the pool.
     ///
     /// Returns a typed pointer to the allocated memory.
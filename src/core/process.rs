@@ -0,0 +1,104 @@
+use crate::core::Status;
+use crate::ffi::{
+    NGX_PROCESS_HELPER, NGX_PROCESS_MASTER, NGX_PROCESS_SIGNALLER, NGX_PROCESS_WORKER, ngx_parent,
+    ngx_pid, ngx_pid_t, ngx_process, ngx_uint_t, ngx_worker,
+};
+
+/// Role of the current nginx process.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProcessRole {
+    /// Single process mode, `master_process off`.
+    Single,
+    /// Master process.
+    Master,
+    /// Process sending a signal to the running instance, `nginx -s`.
+    Signaller,
+    /// Worker process.
+    Worker,
+    /// Helper process, e.g. the cache manager or the cache loader.
+    Helper,
+}
+
+impl ProcessRole {
+    /// Returns the role of the current process.
+    pub fn current() -> Self {
+        match unsafe { ngx_process } as u32 {
+            NGX_PROCESS_MASTER => Self::Master,
+            NGX_PROCESS_SIGNALLER => Self::Signaller,
+            NGX_PROCESS_WORKER => Self::Worker,
+            NGX_PROCESS_HELPER => Self::Helper,
+            _ => Self::Single,
+        }
+    }
+}
+
+/// Returns the process id of the current process.
+pub fn pid() -> ngx_pid_t {
+    unsafe { ngx_pid }
+}
+
+/// Returns the index of the current worker process, from 0 to `worker_processes - 1`.
+///
+/// Returns `None` if the current process is not a worker process.
+pub fn worker_index() -> Option<ngx_uint_t> {
+    (ProcessRole::current() == ProcessRole::Worker).then(|| unsafe { ngx_worker })
+}
+
+/// Returns the process id of the master process.
+///
+/// Returns `None` in the single process mode and in the signaller process.
+pub fn master_pid() -> Option<ngx_pid_t> {
+    match ProcessRole::current() {
+        ProcessRole::Master => Some(pid()),
+        ProcessRole::Worker | ProcessRole::Helper => Some(unsafe { ngx_parent }),
+        ProcessRole::Single | ProcessRole::Signaller => None,
+    }
+}
+
+/// Control signals understood by the master process.
+///
+/// See <https://nginx.org/en/docs/control.html>.
+#[cfg(not(ngx_os = "win32"))]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Signal {
+    /// Reload the configuration and start new worker processes (`SIGHUP`).
+    Reload,
+    /// Reopen the log files (`SIGUSR1`).
+    Reopen,
+    /// Shut down gracefully (`SIGQUIT`).
+    Quit,
+    /// Shut down fast (`SIGTERM`).
+    Terminate,
+}
+
+#[cfg(not(ngx_os = "win32"))]
+impl Signal {
+    fn signo(self) -> core::ffi::c_int {
+        use crate::ffi::{SIGHUP, SIGQUIT, SIGTERM, SIGUSR1};
+
+        (match self {
+            Signal::Reload => SIGHUP,
+            Signal::Reopen => SIGUSR1,
+            Signal::Quit => SIGQUIT,
+            Signal::Terminate => SIGTERM,
+        }) as _
+    }
+}
+
+/// Sends a control signal to the master process.
+///
+/// The signal affects the whole nginx instance: a reload or a shutdown terminates the current
+/// worker processes, including the one calling this function, after the active requests are
+/// completed. Modules exposing this functionality should restrict the access to it.
+///
+/// Returns [`Status::NGX_DECLINED`] if there is no master process.
+#[cfg(not(ngx_os = "win32"))]
+pub fn signal_master(signal: Signal) -> Result<(), Status> {
+    let pid = master_pid().ok_or(Status::NGX_DECLINED)?;
+
+    if unsafe { crate::ffi::kill(pid, signal.signo()) } == -1 {
+        return Err(Status::NGX_ERROR);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,109 @@
+use core::ffi::c_char;
+
+use crate::core::ProcessRole;
+
+/// Maximum length of the process title, excluding the `nginx: ` prefix added by nginx.
+///
+/// The title is further truncated by nginx to the space of the original command line.
+const TITLE_MAX: usize = 128;
+
+/// Process title with a suffix set by [`set_process_title_suffix`].
+///
+/// The previous title is restored when the value is dropped.
+#[must_use = "the title is restored when the guard is dropped"]
+pub struct ProcessTitleGuard {
+    prev: [u8; TITLE_MAX],
+    prev_len: usize,
+}
+
+/// Appends `suffix` to the title of the current process for the duration of a long operation,
+/// e.g. `nginx: worker process: reloading geo db`, so that the operators can see what the
+/// process is busy with in `ps` or `top`.
+///
+/// The title is restored when the returned guard is dropped; the guards must be dropped in the
+/// reverse order of creation. The title is truncated to fit the space of the original command
+/// line, and the suffix is cut at the first NUL byte.
+///
+/// The title is changed with `ngx_setproctitle` on Linux and with `setproctitle(3)` on FreeBSD,
+/// and the function does nothing on the other platforms.
+///
+/// ```no_run
+/// # use ngx::core::set_process_title_suffix;
+/// let _title = set_process_title_suffix("reloading geo db");
+/// // ... load the database
+/// ```
+pub fn set_process_title_suffix(suffix: &str) -> ProcessTitleGuard {
+    let mut guard = ProcessTitleGuard { prev: [0; TITLE_MAX], prev_len: 0 };
+    let prev = current_title();
+    guard.prev_len = prev.len().min(TITLE_MAX);
+    guard.prev[..guard.prev_len].copy_from_slice(&prev[..guard.prev_len]);
+
+    if guard.prev_len > 0 {
+        let suffix = suffix.as_bytes();
+        let suffix = suffix.split(|&b| b == 0).next().unwrap_or_default();
+        set_title(&[&guard.prev[..guard.prev_len], b": ", suffix]);
+    }
+    guard
+}
+
+impl Drop for ProcessTitleGuard {
+    fn drop(&mut self) {
+        if self.prev_len > 0 {
+            set_title(&[&self.prev[..self.prev_len]]);
+        }
+    }
+}
+
+/// Returns the current title without the `nginx: ` prefix.
+#[cfg(ngx_os = "linux")]
+fn current_title() -> &'static [u8] {
+    // SAFETY: `ngx_os_argv[0]` points to the NUL-terminated title set by `ngx_setproctitle`, or
+    // to the original command line.
+    let title = unsafe { core::ffi::CStr::from_ptr(*crate::ffi::ngx_os_argv) }.to_bytes();
+    match title.strip_prefix(b"nginx: ") {
+        Some(title) => title,
+        None => fallback_title(),
+    }
+}
+
+#[cfg(not(ngx_os = "linux"))]
+fn current_title() -> &'static [u8] {
+    fallback_title()
+}
+
+/// Returns the title set by nginx for the role of the current process.
+fn fallback_title() -> &'static [u8] {
+    match ProcessRole::current() {
+        ProcessRole::Worker => b"worker process",
+        // The titles of the other processes depend on the command line or the process purpose.
+        _ => b"",
+    }
+}
+
+fn set_title(parts: &[&[u8]]) {
+    let mut buf = [0u8; TITLE_MAX + 1];
+    let mut len = 0;
+    for part in parts {
+        let n = part.len().min(TITLE_MAX - len);
+        buf[len..len + n].copy_from_slice(&part[..n]);
+        len += n;
+    }
+    let title = buf.as_mut_ptr().cast::<c_char>();
+
+    // SAFETY: the buffer is NUL-terminated, and nginx copies the title.
+    #[cfg(ngx_os = "linux")]
+    unsafe {
+        crate::ffi::ngx_setproctitle(title)
+    };
+
+    #[cfg(ngx_os = "freebsd")]
+    unsafe {
+        unsafe extern "C" {
+            fn setproctitle(fmt: *const c_char, ...);
+        }
+        setproctitle(c"%s".as_ptr(), title.cast_const())
+    };
+
+    #[cfg(not(any(ngx_os = "linux", ngx_os = "freebsd")))]
+    let _ = title;
+}
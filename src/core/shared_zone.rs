@@ -0,0 +1,165 @@
+//! Typed shared memory zones.
+//!
+//! See <https://nginx.org/en/docs/dev/development_guide.html#shared_memory>.
+use core::ffi::c_void;
+use core::marker::PhantomData;
+use core::ptr::NonNull;
+
+use crate::allocator::{self, AllocError};
+use crate::core::{NgxStr, Pool, SlabPool, Status};
+use crate::ffi::{
+    NGX_LOG_EMERG, ngx_conf_t, ngx_int_t, ngx_module_t, ngx_shared_memory_add, ngx_shm_zone_t,
+    ngx_str_t,
+};
+use crate::ngx_conf_log_error;
+
+/// Shared memory zone holding a value of type `T` allocated from the zone's slab pool.
+///
+/// The zone is registered during the configuration parsing with [`SharedZone::add`], and the
+/// value is built by the init closure when nginx maps the zone. The value is kept across
+/// configuration reloads if the zone is reused with the same name and size, so `T` must not change
+/// between the configurations.
+///
+/// The value is shared between the worker processes and must use appropriate synchronization, e.g.
+/// [`RwLock`](crate::sync::RwLock), for any mutable state. All the allocations should be made
+/// from the [`SlabPool`] of the zone.
+///
+/// # Example
+///
+/// ```no_run
+/// # use ngx::collections::RbTreeMap;
+/// # use ngx::core::{NgxString, SharedZone, SlabPool, Status};
+/// # use ngx::sync::RwLock;
+/// type Dict = RwLock<RbTreeMap<NgxString<SlabPool>, NgxString<SlabPool>, SlabPool>>;
+///
+/// # fn add(cf: &mut ngx::ffi::ngx_conf_t, name: &ngx::ffi::ngx_str_t, module: &ngx::ffi::ngx_module_t) -> Result<(), Status> {
+/// let zone = SharedZone::<Dict>::add(cf, name, 1024 * 1024, module, |alloc| {
+///     Ok(RwLock::new(RbTreeMap::try_new_in(alloc)?))
+/// })?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct SharedZone<T> {
+    zone: NonNull<ngx_shm_zone_t>,
+    _marker: PhantomData<*const T>,
+}
+
+impl<T> Clone for SharedZone<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for SharedZone<T> {}
+
+impl<T> core::fmt::Debug for SharedZone<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("SharedZone").field("name", &self.name()).finish()
+    }
+}
+
+impl<T> SharedZone<T> {
+    /// Registers a shared memory zone `name` of `size` bytes owned by `module`.
+    ///
+    /// `init` is called once the zone is mapped, unless the zone already contains a value from the
+    /// previous configuration. Must be called from a configuration directive handler.
+    ///
+    /// Registering a zone with the same name again returns the existing zone; nginx ensures that
+    /// the zone belongs to the same module, which must use the same `T` for all its zones.
+    pub fn add<F>(
+        cf: &mut ngx_conf_t,
+        name: &ngx_str_t,
+        size: usize,
+        module: &ngx_module_t,
+        init: F,
+    ) -> Result<Self, Status>
+    where
+        F: Fn(SlabPool) -> Result<T, AllocError> + 'static,
+    {
+        let mut name = *name;
+        let tag = core::ptr::from_ref(module).cast_mut().cast();
+        let zone = unsafe { ngx_shared_memory_add(cf, &raw mut name, size, tag) };
+        // nginx logs the reason of failure, e.g. a duplicate zone with a different tag.
+        let mut zone = NonNull::new(zone).ok_or(Status::NGX_ERROR)?;
+        let shm_zone = unsafe { zone.as_mut() };
+
+        if shm_zone.data.is_null() {
+            let pool = unsafe { Pool::from_ngx_pool(cf.pool) };
+            let init = pool.allocate(init);
+            if init.is_null() {
+                return Err(Status::NGX_ERROR);
+            }
+            shm_zone.init = Some(zone_init::<T, F>);
+            shm_zone.data = init.cast();
+        } else if shm_zone.init.is_none() {
+            ngx_conf_log_error!(NGX_LOG_EMERG, cf, "zone \"{name}\" is not initialized");
+            return Err(Status::NGX_ERROR);
+        }
+
+        Ok(Self { zone, _marker: PhantomData })
+    }
+
+    /// Creates a `SharedZone` from a zone registered with [`SharedZone::add`].
+    ///
+    /// # Safety
+    ///
+    /// The zone must be registered with [`SharedZone::add`] with the same type `T`.
+    pub unsafe fn from_shm_zone(zone: NonNull<ngx_shm_zone_t>) -> Self {
+        Self { zone, _marker: PhantomData }
+    }
+
+    /// Returns the zone name.
+    pub fn name(&self) -> &NgxStr {
+        // SAFETY: the zone is allocated from the cycle pool and outlives the configuration.
+        unsafe { NgxStr::from_ngx_str(self.zone.as_ref().shm.name) }
+    }
+
+    /// Returns a reference to the underlying [`ngx_shm_zone_t`].
+    pub fn as_shm_zone(&self) -> &ngx_shm_zone_t {
+        // SAFETY: the zone is allocated from the cycle pool and outlives the configuration.
+        unsafe { self.zone.as_ref() }
+    }
+
+    /// Returns the slab pool of the zone, or `None` if the zone is not mapped yet.
+    pub fn slab_pool(&self) -> Option<SlabPool> {
+        // SAFETY: see `SlabPool::from_shm_zone`; the value is only available while the zone is
+        // mapped.
+        unsafe { SlabPool::from_shm_zone(self.zone.as_ref()) }
+    }
+
+    /// Returns a reference to the shared value, or `None` if the zone is not initialized yet.
+    pub fn get(&self) -> Option<&T> {
+        let pool = self.slab_pool()?;
+        // SAFETY: the data pointer is set by `zone_init` to a value of type `T`.
+        unsafe { pool.as_ref().data.cast::<T>().as_ref() }
+    }
+}
+
+extern "C" fn zone_init<T, F>(shm_zone: *mut ngx_shm_zone_t, _data: *mut c_void) -> ngx_int_t
+where
+    F: Fn(SlabPool) -> Result<T, AllocError>,
+{
+    // SAFETY: nginx calls the handler with a valid mapped zone.
+    let shm_zone = unsafe { &mut *shm_zone };
+    let Some(mut alloc) = (unsafe { SlabPool::from_shm_zone(shm_zone) }) else {
+        return Status::NGX_ERROR.into();
+    };
+
+    // The zone is reused from the previous cycle and already contains the value.
+    if !alloc.as_ref().data.is_null() {
+        return Status::NGX_OK.into();
+    }
+
+    // SAFETY: `data` is set to the init closure in `SharedZone::add`.
+    let init = unsafe { &*shm_zone.data.cast::<F>() };
+
+    let Ok(value) = init(alloc.clone()) else {
+        return Status::NGX_ERROR.into();
+    };
+    let Ok(ptr) = allocator::allocate(value, &alloc) else {
+        return Status::NGX_ERROR.into();
+    };
+    alloc.as_mut().data = ptr.as_ptr().cast();
+
+    Status::NGX_OK.into()
+}
@@ -101,6 +101,23 @@ impl SlabPool {
         Some(Self(ptr))
     }
 
+    /// Allocates memory for a type, aligned to the size of a cache line.
+    ///
+    /// The alignment is the greater of [`cacheline_size`](crate::core::cacheline_size) and the
+    /// alignment of `T`, so that the values updated by different worker processes do not share
+    /// a cache line. The memory is released with [`Allocator::deallocate`] and the same layout,
+    /// see [`SlabPool::aligned_layout`].
+    pub fn alloc_aligned<T>(&self) -> Result<NonNull<T>, AllocError> {
+        let layout = Self::aligned_layout::<T>().ok_or(AllocError)?;
+        Ok(self.allocate(layout)?.cast())
+    }
+
+    /// Returns the layout used by [`SlabPool::alloc_aligned`] for `T`.
+    pub fn aligned_layout<T>() -> Option<Layout> {
+        let align = core::mem::align_of::<T>().max(super::cacheline_size());
+        Layout::from_size_align(core::mem::size_of::<T>(), align).ok()
+    }
+
     /// Locks the slab pool mutex.
     #[inline]
     pub fn lock(&self) -> LockedSlabPool {
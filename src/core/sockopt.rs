@@ -0,0 +1,159 @@
+use core::ffi::{c_int, c_void};
+use core::mem;
+
+use crate::core::{Connection, Status};
+use crate::ffi::{
+    AF_INET6, IP_TOS, IPPROTO_IP, IPPROTO_IPV6, IPPROTO_TCP, IPV6_TCLASS, NGX_OK, TCP_NODELAY,
+    getsockopt, ngx_connection_local_sockaddr, ngx_int_t, ngx_socket_t, setsockopt, socklen_t,
+};
+
+/// Snapshot of the `TCP_INFO` socket statistics.
+///
+/// The values are the same as those reported by the `$tcpinfo_*` variables.
+#[cfg(any(ngx_os = "linux", ngx_os = "freebsd"))]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TcpInfo {
+    /// Smoothed round-trip time, in microseconds.
+    pub rtt: u32,
+    /// Round-trip time variance, in microseconds.
+    pub rttvar: u32,
+    /// Congestion window, in segments.
+    pub snd_cwnd: u32,
+    /// Receive space, in bytes.
+    pub rcv_space: u32,
+}
+
+impl Connection {
+    /// Returns `true` if the `TCP_NODELAY` option is enabled on the socket.
+    pub fn tcp_nodelay(&self) -> Result<bool, Status> {
+        let value: c_int = unsafe { get_option(self.fd(), IPPROTO_TCP as _, TCP_NODELAY as _)? };
+        Ok(value != 0)
+    }
+
+    /// Enables or disables the `TCP_NODELAY` option on the socket.
+    ///
+    /// A disabled option is not enabled again by nginx, e.g. for a keepalive connection.
+    pub fn set_tcp_nodelay(&mut self, enable: bool) -> Result<(), Status> {
+        if enable {
+            // Use nginx helper to keep the connection state consistent.
+            let c = &raw mut *self.as_mut();
+            if unsafe { crate::ffi::ngx_tcp_nodelay(c) } != NGX_OK as ngx_int_t {
+                return Err(Status::NGX_ERROR);
+            }
+            return Ok(());
+        }
+
+        let off: c_int = 0;
+        unsafe { set_option(self.fd(), IPPROTO_TCP as _, TCP_NODELAY as _, off)? };
+        self.as_mut()
+            .set_tcp_nodelay(crate::ffi::ngx_connection_tcp_nodelay_e_NGX_TCP_NODELAY_DISABLED);
+        Ok(())
+    }
+
+    /// Returns the type of service (IPv4) or the traffic class (IPv6) of the outgoing packets.
+    pub fn tos(&mut self) -> Result<u8, Status> {
+        let (level, name) = self.tos_option()?;
+        let value: c_int = unsafe { get_option(self.fd(), level, name)? };
+        Ok(value as u8)
+    }
+
+    /// Sets the type of service (IPv4) or the traffic class (IPv6) of the outgoing packets.
+    pub fn set_tos(&mut self, tos: u8) -> Result<(), Status> {
+        let (level, name) = self.tos_option()?;
+        unsafe { set_option(self.fd(), level, name, c_int::from(tos)) }
+    }
+
+    /// Returns the DSCP value, the upper 6 bits of the type of service.
+    pub fn dscp(&mut self) -> Result<u8, Status> {
+        Ok(self.tos()? >> 2)
+    }
+
+    /// Sets the DSCP value, preserving the ECN bits of the type of service.
+    pub fn set_dscp(&mut self, dscp: u8) -> Result<(), Status> {
+        let tos = self.tos()?;
+        self.set_tos((dscp << 2) | (tos & 0x03))
+    }
+
+    /// Returns the `SO_MARK` value of the socket.
+    #[cfg(ngx_os = "linux")]
+    pub fn mark(&self) -> Result<u32, Status> {
+        unsafe { get_option(self.fd(), crate::ffi::SOL_SOCKET as _, crate::ffi::SO_MARK as _) }
+    }
+
+    /// Sets the `SO_MARK` value of the socket.
+    ///
+    /// Requires the `CAP_NET_ADMIN` capability.
+    #[cfg(ngx_os = "linux")]
+    pub fn set_mark(&mut self, mark: u32) -> Result<(), Status> {
+        unsafe {
+            set_option(self.fd(), crate::ffi::SOL_SOCKET as _, crate::ffi::SO_MARK as _, mark)
+        }
+    }
+
+    /// Returns a snapshot of the `TCP_INFO` socket statistics.
+    #[cfg(any(ngx_os = "linux", ngx_os = "freebsd"))]
+    pub fn tcp_info(&self) -> Result<TcpInfo, Status> {
+        let ti: crate::ffi::tcp_info =
+            unsafe { get_option(self.fd(), IPPROTO_TCP as _, crate::ffi::TCP_INFO as _)? };
+
+        Ok(TcpInfo {
+            rtt: ti.tcpi_rtt,
+            rttvar: ti.tcpi_rttvar,
+            snd_cwnd: ti.tcpi_snd_cwnd,
+            rcv_space: ti.tcpi_rcv_space,
+        })
+    }
+
+    fn tos_option(&mut self) -> Result<(c_int, c_int), Status> {
+        let c = &raw mut *self.as_mut();
+        if unsafe { ngx_connection_local_sockaddr(c, core::ptr::null_mut(), 0) }
+            != NGX_OK as ngx_int_t
+        {
+            return Err(Status::NGX_ERROR);
+        }
+
+        // SAFETY: `local_sockaddr` is set by ngx_connection_local_sockaddr on success.
+        if u32::from(unsafe { (*self.as_ref().local_sockaddr).sa_family }) == AF_INET6 {
+            Ok((IPPROTO_IPV6 as _, IPV6_TCLASS as _))
+        } else {
+            Ok((IPPROTO_IP as _, IP_TOS as _))
+        }
+    }
+}
+
+/// Reads a socket option of type `T`.
+///
+/// # Safety
+///
+/// `T` must be the type of the option value and valid for any bit pattern.
+unsafe fn get_option<T>(fd: ngx_socket_t, level: c_int, name: c_int) -> Result<T, Status> {
+    let mut value = mem::MaybeUninit::<T>::zeroed();
+    let mut len = mem::size_of::<T>() as socklen_t;
+
+    if unsafe { getsockopt(fd, level, name, value.as_mut_ptr().cast(), &raw mut len) } == -1 {
+        return Err(Status::NGX_ERROR);
+    }
+
+    Ok(unsafe { value.assume_init() })
+}
+
+/// Sets a socket option of type `T`.
+///
+/// # Safety
+///
+/// `T` must be the type of the option value.
+unsafe fn set_option<T>(
+    fd: ngx_socket_t,
+    level: c_int,
+    name: c_int,
+    value: T,
+) -> Result<(), Status> {
+    let p: *const T = &value;
+    let len = mem::size_of::<T>() as socklen_t;
+
+    if unsafe { setsockopt(fd, level, name, p.cast::<c_void>(), len) } == -1 {
+        return Err(Status::NGX_ERROR);
+    }
+
+    Ok(())
+}
@@ -68,3 +68,5 @@ ngx_codes! {
 pub const NGX_CONF_ERROR: *mut c_char = ptr::null_mut::<c_char>().wrapping_offset(-1);
 /// Configuration handler succeeded.
 pub const NGX_CONF_OK: *mut c_char = ptr::null_mut();
+/// The directive is already specified, see [`ConfUnset`](crate::core::ConfUnset).
+pub const NGX_CONF_DUPLICATE: *mut c_char = c"is duplicate".as_ptr().cast_mut();
@@ -0,0 +1,312 @@
+//! String conversions implemented with the nginx string functions.
+//!
+//! The helpers wrap `ngx_atoi`, `ngx_hextoi`, the base64 and the URI escaping functions, and
+//! `ngx_strlow`, so that a module produces exactly the same results as the nginx code handling
+//! the same input, e.g. when decoding a header or escaping an argument for a redirect.
+//!
+//! The allocating helpers return an [`NgxString`] in the specified allocator, usually the request
+//! [`Pool`](crate::core::Pool):
+//!
+//! ```no_run
+//! # use ngx::core::{NgxStr, Pool};
+//! # use ngx::core::str::{self, Escape};
+//! # fn f(pool: &Pool, arg: &NgxStr) -> Result<(), ngx::collections::TryReserveError> {
+//! let escaped = str::escape_uri(arg, Escape::Args, pool.clone())?;
+//! let decoded = str::decode_base64(b"bmdpbng=", pool.clone());
+//! # Ok(()) }
+//! ```
+//!
+//! See [`IntBuffer`](crate::core::IntBuffer) for formatting the integers.
+#[cfg(feature = "alloc")]
+pub use self::_alloc::*;
+use crate::core::NgxStr;
+use crate::ffi::{
+    NGX_ESCAPE_ARGS, NGX_ESCAPE_HTML, NGX_ESCAPE_MAIL_AUTH, NGX_ESCAPE_MEMCACHED,
+    NGX_ESCAPE_REFRESH, NGX_ESCAPE_URI, NGX_ESCAPE_URI_COMPONENT, NGX_UNESCAPE_REDIRECT,
+    NGX_UNESCAPE_URI, ngx_atoi, ngx_hextoi, ngx_int_t, ngx_strlow, ngx_uint_t,
+};
+
+/// Parses a non-negative decimal integer with `ngx_atoi`.
+///
+/// Returns `None` if the string is empty, contains anything but the digits, or overflows.
+pub fn parse_int(s: impl AsRef<[u8]>) -> Option<ngx_int_t> {
+    let s = s.as_ref();
+    // SAFETY: `ngx_atoi` only reads `s.len()` bytes.
+    let n = unsafe { ngx_atoi(s.as_ptr().cast_mut(), s.len()) };
+    (n >= 0).then_some(n)
+}
+
+/// Parses a non-negative hexadecimal integer with `ngx_hextoi`.
+///
+/// Returns `None` if the string is empty, contains anything but the hex digits, or overflows.
+pub fn parse_hex(s: impl AsRef<[u8]>) -> Option<ngx_int_t> {
+    let s = s.as_ref();
+    // SAFETY: `ngx_hextoi` only reads `s.len()` bytes.
+    let n = unsafe { ngx_hextoi(s.as_ptr().cast_mut(), s.len()) };
+    (n >= 0).then_some(n)
+}
+
+/// Converts the ASCII letters of the string to lowercase in place, with `ngx_strlow`.
+pub fn make_lowercase(s: &mut NgxStr) {
+    let s: &mut [u8] = s.as_mut();
+    let p = s.as_mut_ptr();
+    // SAFETY: `ngx_strlow` converts the bytes one at a time, and supports the same buffer.
+    unsafe { ngx_strlow(p, p, s.len()) };
+}
+
+/// Characters escaped by [`escape_uri`], matching the `NGX_ESCAPE_*` types of `ngx_escape_uri`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Escape {
+    /// Path of a URI.
+    Uri,
+    /// Query string arguments.
+    Args,
+    /// Component of a URI, e.g. a single argument value.
+    UriComponent,
+    /// URI for an HTML attribute.
+    Html,
+    /// URI for the `Refresh` header.
+    Refresh,
+    /// Memcached key.
+    Memcached,
+    /// Mail authentication parameter.
+    MailAuth,
+}
+
+impl Escape {
+    fn as_ngx(self) -> ngx_uint_t {
+        (match self {
+            Self::Uri => NGX_ESCAPE_URI,
+            Self::Args => NGX_ESCAPE_ARGS,
+            Self::UriComponent => NGX_ESCAPE_URI_COMPONENT,
+            Self::Html => NGX_ESCAPE_HTML,
+            Self::Refresh => NGX_ESCAPE_REFRESH,
+            Self::Memcached => NGX_ESCAPE_MEMCACHED,
+            Self::MailAuth => NGX_ESCAPE_MAIL_AUTH,
+        }) as ngx_uint_t
+    }
+}
+
+/// Decoding of [`unescape_uri`], matching the types of `ngx_unescape_uri`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Unescape {
+    /// Decodes all the percent-encoded characters.
+    #[default]
+    All,
+    /// Decodes the path of a URI, stopping after an encoded `?`.
+    Uri,
+    /// Decodes the path of a URI for a redirect, stopping after an encoded `?` and keeping the
+    /// characters up to `%` and the non-ASCII characters encoded.
+    Redirect,
+}
+
+impl Unescape {
+    fn as_ngx(self) -> ngx_uint_t {
+        (match self {
+            Self::All => 0,
+            Self::Uri => NGX_UNESCAPE_URI,
+            Self::Redirect => NGX_UNESCAPE_REDIRECT,
+        }) as ngx_uint_t
+    }
+}
+
+#[cfg(feature = "alloc")]
+mod _alloc {
+    use core::fmt;
+
+    use super::*;
+    use crate::allocator::Allocator;
+    use crate::collections::TryReserveError;
+    use crate::core::NgxString;
+    use crate::ffi::{
+        NGX_OK, ngx_decode_base64, ngx_decode_base64url, ngx_encode_base64, ngx_encode_base64url,
+        ngx_escape_uri, ngx_str_t, ngx_unescape_uri,
+    };
+
+    /// Error returned by the decoding functions.
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    pub enum DecodeError {
+        /// The input is not valid.
+        Invalid,
+        /// Memory allocation failed.
+        Alloc(TryReserveError),
+    }
+
+    impl From<TryReserveError> for DecodeError {
+        fn from(err: TryReserveError) -> Self {
+            Self::Alloc(err)
+        }
+    }
+
+    impl fmt::Display for DecodeError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                Self::Invalid => f.write_str("invalid encoding"),
+                Self::Alloc(err) => err.fmt(f),
+            }
+        }
+    }
+
+    impl core::error::Error for DecodeError {}
+
+    /// Returns a lowercase copy of the string, converted with `ngx_strlow`.
+    pub fn to_lowercase<A>(s: impl AsRef<[u8]>, alloc: A) -> Result<NgxString<A>, TryReserveError>
+    where
+        A: Allocator + Clone,
+    {
+        let s = s.as_ref();
+        // SAFETY: `ngx_strlow` writes exactly `s.len()` bytes.
+        with_buffer(s.len(), alloc, |dst| unsafe {
+            ngx_strlow(dst, s.as_ptr().cast_mut(), s.len());
+            Some(s.len())
+        })
+        .map_err(expect_alloc)
+    }
+
+    /// Encodes the bytes with base64, using `ngx_encode_base64`.
+    pub fn encode_base64<A>(
+        src: impl AsRef<[u8]>,
+        alloc: A,
+    ) -> Result<NgxString<A>, TryReserveError>
+    where
+        A: Allocator + Clone,
+    {
+        let src = src.as_ref();
+        let len = src.len().div_ceil(3) * 4;
+        encode(src, len, alloc, |dst, src| unsafe { ngx_encode_base64(dst, src) })
+    }
+
+    /// Encodes the bytes with base64url without padding, using `ngx_encode_base64url`.
+    pub fn encode_base64url<A>(
+        src: impl AsRef<[u8]>,
+        alloc: A,
+    ) -> Result<NgxString<A>, TryReserveError>
+    where
+        A: Allocator + Clone,
+    {
+        let src = src.as_ref();
+        let len = (src.len() * 4).div_ceil(3);
+        encode(src, len, alloc, |dst, src| unsafe { ngx_encode_base64url(dst, src) })
+    }
+
+    /// Decodes base64 with `ngx_decode_base64`.
+    pub fn decode_base64<A>(src: impl AsRef<[u8]>, alloc: A) -> Result<NgxString<A>, DecodeError>
+    where
+        A: Allocator + Clone,
+    {
+        decode(src.as_ref(), alloc, |dst, src| unsafe { ngx_decode_base64(dst, src) })
+    }
+
+    /// Decodes base64url with `ngx_decode_base64url`.
+    pub fn decode_base64url<A>(src: impl AsRef<[u8]>, alloc: A) -> Result<NgxString<A>, DecodeError>
+    where
+        A: Allocator + Clone,
+    {
+        decode(src.as_ref(), alloc, |dst, src| unsafe { ngx_decode_base64url(dst, src) })
+    }
+
+    /// Percent-encodes the characters of the specified type with `ngx_escape_uri`.
+    pub fn escape_uri<A>(
+        src: impl AsRef<[u8]>,
+        escape: Escape,
+        alloc: A,
+    ) -> Result<NgxString<A>, TryReserveError>
+    where
+        A: Allocator + Clone,
+    {
+        let src = src.as_ref();
+        let p = src.as_ptr().cast_mut();
+        // SAFETY: with a null destination, `ngx_escape_uri` counts the characters to escape.
+        let n = unsafe { ngx_escape_uri(core::ptr::null_mut(), p, src.len(), escape.as_ngx()) };
+        let len = src.len() + 2 * n;
+        // SAFETY: the buffer has space for each escaped character expanded to three bytes.
+        with_buffer(len, alloc, |dst| unsafe {
+            ngx_escape_uri(dst, p, src.len(), escape.as_ngx());
+            Some(len)
+        })
+        .map_err(expect_alloc)
+    }
+
+    /// Decodes the percent-encoded characters with `ngx_unescape_uri`.
+    ///
+    /// Invalid escape sequences are copied as is.
+    pub fn unescape_uri<A>(
+        src: impl AsRef<[u8]>,
+        unescape: Unescape,
+        alloc: A,
+    ) -> Result<NgxString<A>, TryReserveError>
+    where
+        A: Allocator + Clone,
+    {
+        let src = src.as_ref();
+        // SAFETY: the decoded string is never longer than the source.
+        with_buffer(src.len(), alloc, |dst| unsafe {
+            let mut d = dst;
+            let mut s = src.as_ptr().cast_mut();
+            ngx_unescape_uri(&mut d, &mut s, src.len(), unescape.as_ngx());
+            Some(d.offset_from(dst) as usize)
+        })
+        .map_err(expect_alloc)
+    }
+
+    fn encode<A>(
+        src: &[u8],
+        len: usize,
+        alloc: A,
+        f: impl FnOnce(*mut ngx_str_t, *mut ngx_str_t),
+    ) -> Result<NgxString<A>, TryReserveError>
+    where
+        A: Allocator + Clone,
+    {
+        with_buffer(len, alloc, |data| {
+            let mut dst = ngx_str_t { len: 0, data };
+            let mut src = ngx_str_t { len: src.len(), data: src.as_ptr().cast_mut() };
+            f(&mut dst, &mut src);
+            Some(dst.len)
+        })
+        .map_err(expect_alloc)
+    }
+
+    fn decode<A>(
+        src: &[u8],
+        alloc: A,
+        f: impl FnOnce(*mut ngx_str_t, *mut ngx_str_t) -> ngx_int_t,
+    ) -> Result<NgxString<A>, DecodeError>
+    where
+        A: Allocator + Clone,
+    {
+        let len = src.len().div_ceil(4) * 3;
+        with_buffer(len, alloc, |data| {
+            let mut dst = ngx_str_t { len: 0, data };
+            let mut src = ngx_str_t { len: src.len(), data: src.as_ptr().cast_mut() };
+            (f(&mut dst, &mut src) == NGX_OK as ngx_int_t).then_some(dst.len)
+        })
+    }
+
+    /// Allocates a buffer of `len` bytes and fills it with `f`, which returns the length of the
+    /// written data or `None` if the input is invalid.
+    fn with_buffer<A>(
+        len: usize,
+        alloc: A,
+        f: impl FnOnce(*mut u8) -> Option<usize>,
+    ) -> Result<NgxString<A>, DecodeError>
+    where
+        A: Allocator + Clone,
+    {
+        let mut s = NgxString::new_in(alloc);
+        s.try_reserve_exact(len)?;
+        let (ptr, _, cap, alloc) = s.into_raw_parts();
+        let n = f(ptr);
+        // SAFETY: the parts are obtained from `into_raw_parts` above, and `f` initialized the
+        // first `n` bytes of the buffer.
+        let s = unsafe { NgxString::from_raw_parts(ptr, n.unwrap_or(0), cap, alloc) };
+        n.map(|_| s).ok_or(DecodeError::Invalid)
+    }
+
+    fn expect_alloc(err: DecodeError) -> TryReserveError {
+        match err {
+            DecodeError::Alloc(err) => err,
+            DecodeError::Invalid => unreachable!("encoding does not fail"),
+        }
+    }
+}
@@ -2,7 +2,8 @@
 use alloc::{borrow::Cow, string::String};
 use core::cmp;
 use core::fmt;
-use core::str::{self, Utf8Error};
+use core::slice::EscapeAscii;
+use core::str::{self, Utf8Chunks, Utf8Error};
 
 use crate::ffi::{ngx_str_t, u_char};
 
@@ -21,6 +22,19 @@ pub use self::_alloc::NgxString;
 
 /// Representation of a borrowed [Nginx string].
 ///
+/// An nginx string is a sequence of bytes without a guaranteed encoding. The values received
+/// from the clients, such as the request line and the header values, may contain arbitrary bytes,
+/// and the decoded URI path may contain any sequence produced by the percent-decoding. The
+/// conversions to Rust strings are explicit about it:
+///
+///  - [`NgxStr::as_bytes`] is lossless and should be preferred for parsing and comparisons;
+///  - [`NgxStr::to_str`] fails on invalid UTF-8;
+///  - [`NgxStr::to_string_lossy`] and [`NgxStr::utf8_chunks`] replace or expose the invalid
+///    sequences;
+///  - the [`fmt::Display`] implementation writes the invalid bytes as `\xNN` escapes, and
+///    [`NgxStr::escape_ascii`] escapes all the non-printable bytes, e.g. for logging the client
+///    input.
+///
 /// [Nginx string]: https://nginx.org/en/docs/dev/development_guide.html#string_overview
 #[derive(Hash, PartialEq, Eq, PartialOrd, Ord)]
 #[repr(transparent)]
@@ -75,6 +89,22 @@ impl NgxStr {
         String::from_utf8_lossy(self.as_bytes())
     }
 
+    /// Returns an iterator over the valid UTF-8 chunks of the [`NgxStr`] and the invalid bytes
+    /// between them.
+    ///
+    /// See [`slice::utf8_chunks`].
+    pub fn utf8_chunks(&self) -> Utf8Chunks<'_> {
+        self.as_bytes().utf8_chunks()
+    }
+
+    /// Returns an iterator over the bytes of the [`NgxStr`], with the non-printable and non-ASCII
+    /// bytes escaped. The result is safe to write to a log or a response as is.
+    ///
+    /// See [`slice::escape_ascii`].
+    pub fn escape_ascii(&self) -> EscapeAscii<'_> {
+        self.as_bytes().escape_ascii()
+    }
+
     /// Returns `true` if the [`NgxStr`] is empty, otherwise `false`.
     pub fn is_empty(&self) -> bool {
         self.0.is_empty()
@@ -111,6 +141,7 @@ impl Default for &NgxStr {
     }
 }
 
+/// Writes the string, with the invalid UTF-8 sequences escaped as `\xNN`.
 impl fmt::Display for NgxStr {
     #[inline]
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -545,6 +576,15 @@ mod tests {
 
     use super::*;
 
+    #[test]
+    fn test_str_display() {
+        let ns = NgxStr::from_bytes(b"caf\xc3\xa9 \xff\n");
+        assert!(ns.to_str().is_err());
+        assert_eq!(ns.to_string(), "caf\u{e9} \\xff\n");
+        assert_eq!(ns.escape_ascii().to_string(), "caf\\xc3\\xa9 \\xff\\n");
+        assert_eq!(ns.utf8_chunks().map(|c| c.invalid().len()).sum::<usize>(), 1);
+    }
+
     #[test]
     fn test_str_comparisons() {
         let string = "test".to_string();
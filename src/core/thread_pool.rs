@@ -0,0 +1,45 @@
+use crate::core::FeatureDisabled;
+use crate::ffi::{ngx_conf_t, ngx_str_t};
+
+/// Thread pool defined with the `thread_pool` directive.
+///
+/// Requires nginx built with thread pools (`--with-threads`). With the `feature-stubs` cargo
+/// feature, [`ThreadPool::add`] is also available in the other builds, and fails with
+/// [`FeatureDisabled`].
+#[derive(Clone, Copy, Debug)]
+pub struct ThreadPool(Inner);
+
+#[cfg(ngx_feature = "threads")]
+type Inner = core::ptr::NonNull<crate::ffi::ngx_thread_pool_t>;
+
+#[cfg(not(ngx_feature = "threads"))]
+type Inner = core::convert::Infallible;
+
+impl ThreadPool {
+    /// Adds a reference to the thread pool `name` from a directive handler, as with the
+    /// `aio threads=name` directive.
+    ///
+    /// The pool does not need to be defined yet: nginx reports the missing pools after the
+    /// configuration is parsed. Returns `Ok(None)` if the allocation fails.
+    #[cfg(ngx_feature = "threads")]
+    pub fn add(cf: &mut ngx_conf_t, name: &ngx_str_t) -> Result<Option<Self>, FeatureDisabled> {
+        let mut name = *name;
+        // SAFETY: the function is called from a directive handler with a valid configuration.
+        let tp = unsafe { crate::ffi::ngx_thread_pool_add(cf, &raw mut name) };
+        Ok(core::ptr::NonNull::new(tp).map(Self))
+    }
+
+    /// Adds a reference to the thread pool `name` from a directive handler.
+    ///
+    /// Always fails: nginx was built without thread pools.
+    #[cfg(all(feature = "feature-stubs", not(ngx_feature = "threads")))]
+    pub fn add(_cf: &mut ngx_conf_t, _name: &ngx_str_t) -> Result<Option<Self>, FeatureDisabled> {
+        Err(FeatureDisabled(crate::core::Feature::Threads))
+    }
+
+    /// Returns the pointer to the thread pool.
+    #[cfg(ngx_feature = "threads")]
+    pub fn as_ptr(&self) -> *mut crate::ffi::ngx_thread_pool_t {
+        self.0.as_ptr()
+    }
+}
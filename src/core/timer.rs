@@ -0,0 +1,112 @@
+use core::mem;
+use core::pin::Pin;
+use core::ptr::NonNull;
+use core::time::Duration;
+
+use crate::allocator::{AllocError, Box};
+use crate::core::{add_timer, del_timer};
+use crate::ffi::{ngx_event_t, ngx_log_t, ngx_msec_int_t, ngx_msec_t};
+use crate::ngx_container_of;
+
+/// Maximum duration that can be achieved using `ngx_add_timer`.
+const NGX_TIMER_DURATION_MAX: Duration = Duration::from_millis(ngx_msec_int_t::MAX as _);
+
+/// Callback-based timer on the nginx event loop.
+///
+/// The callback is invoked from the event loop when the timer expires, and returns the delay
+/// until the next invocation or `None` to stop. The timer is cancelled when dropped, and does not
+/// delay the graceful shutdown of a worker process.
+///
+/// Unlike [`async_::sleep`](crate::async_::sleep), the timer does not require an async runtime and
+/// can be started from the `init_process` handler of a module, e.g. for periodic cleanup of a
+/// cache.
+///
+/// ```no_run
+/// # use core::time::Duration;
+/// # use ngx::core::Timer;
+/// let mut timer = Timer::new(ngx::log::ngx_cycle_log(), || {
+///     // sweep expired entries
+///     Some(Duration::from_secs(60))
+/// })
+/// .expect("timer");
+/// timer.arm(Duration::from_secs(60));
+/// // keep `timer` alive, e.g. in the module context
+/// ```
+pub struct Timer<F>
+where
+    F: FnMut() -> Option<Duration>,
+{
+    event: ngx_event_t,
+    callback: F,
+}
+
+impl<F> Timer<F>
+where
+    F: FnMut() -> Option<Duration>,
+{
+    /// Creates a new disarmed timer with the specified logger and callback.
+    pub fn new(log: NonNull<ngx_log_t>, callback: F) -> Result<Pin<Box<Self>>, AllocError> {
+        static IDENT: [usize; 4] = [
+            0, 0, 0, 0x54494d52, // TIMR
+        ];
+
+        let mut event: ngx_event_t = unsafe { mem::zeroed() };
+        // The data is only used for `ngx_event_ident` and will not be mutated.
+        event.data = (&raw const IDENT).cast_mut().cast();
+        event.handler = Some(Self::timer_handler);
+        event.log = log.as_ptr();
+        event.set_cancelable(1);
+
+        Box::try_new(Self { event, callback }).map(Box::into_pin)
+    }
+
+    /// Arms the timer to expire after the `delay`, replacing the previous delay.
+    ///
+    /// The delay is truncated to the maximum supported by nginx.
+    pub fn arm(self: &mut Pin<Box<Self>>, delay: Duration) {
+        // SAFETY: the event is not moved out of the pinned allocation.
+        let this = unsafe { self.as_mut().get_unchecked_mut() };
+        this.event.set_timedout(0);
+        unsafe { add_timer(&raw mut this.event, to_msec(delay)) };
+    }
+
+    /// Cancels the timer, if armed.
+    pub fn cancel(self: &mut Pin<Box<Self>>) {
+        // SAFETY: the event is not moved out of the pinned allocation.
+        let this = unsafe { self.as_mut().get_unchecked_mut() };
+        if this.event.timer_set() != 0 {
+            unsafe { del_timer(&raw mut this.event) };
+        }
+    }
+
+    /// Returns `true` if the timer is armed.
+    pub fn is_armed(&self) -> bool {
+        self.event.timer_set() != 0
+    }
+
+    unsafe extern "C" fn timer_handler(ev: *mut ngx_event_t) {
+        let timer = ngx_container_of!(ev, Self, event);
+        // SAFETY: the event is embedded in a pinned `Timer`, which deletes the timer when dropped.
+        let timer = unsafe { &mut *timer };
+
+        if let Some(delay) = (timer.callback)() {
+            timer.event.set_timedout(0);
+            unsafe { add_timer(&raw mut timer.event, to_msec(delay)) };
+        }
+    }
+}
+
+impl<F> Drop for Timer<F>
+where
+    F: FnMut() -> Option<Duration>,
+{
+    fn drop(&mut self) {
+        if self.event.timer_set() != 0 {
+            unsafe { del_timer(&raw mut self.event) };
+        }
+    }
+}
+
+fn to_msec(delay: Duration) -> ngx_msec_t {
+    delay.min(NGX_TIMER_DURATION_MAX).as_millis() as ngx_msec_t
+}
@@ -0,0 +1,528 @@
+use core::error;
+use core::ffi::{c_char, c_void};
+use core::fmt;
+use core::str::FromStr;
+use core::time::Duration;
+
+use crate::core::{ConfUnset, NGX_CONF_DUPLICATE, NGX_CONF_OK};
+use crate::ffi::{ngx_command_t, ngx_conf_t, ngx_msec_t, ngx_str_t, off_t, time_t};
+
+/// Error parsing a value in the nginx unit syntax.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ParseUnitError;
+
+impl error::Error for ParseUnitError {}
+
+impl fmt::Display for ParseUnitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("invalid value")
+    }
+}
+
+/// Time interval in milliseconds.
+///
+/// Layout-compatible with `ngx_msec_t` and can be used with [`ngx_conf_set_msec_slot`].
+///
+/// Parsed from the [time interval syntax], e.g. `500ms`, `30s` or `1h 30m`; a number without a
+/// suffix means seconds.
+///
+/// [`ngx_conf_set_msec_slot`]: crate::ffi::ngx_conf_set_msec_slot
+/// [time interval syntax]: https://nginx.org/en/docs/syntax.html
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(transparent)]
+pub struct NgxMsec(pub ngx_msec_t);
+
+/// Time interval in seconds.
+///
+/// Layout-compatible with `time_t` and can be used with [`ngx_conf_set_sec_slot`].
+///
+/// Parsed from the [time interval syntax], e.g. `30s`, `10m` or `1d 12h`; a number without a
+/// suffix means seconds. Milliseconds are not allowed.
+///
+/// [`ngx_conf_set_sec_slot`]: crate::ffi::ngx_conf_set_sec_slot
+/// [time interval syntax]: https://nginx.org/en/docs/syntax.html
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(transparent)]
+pub struct NgxSec(pub time_t);
+
+/// Size in bytes.
+///
+/// Layout-compatible with `off_t` and can be used with [`ngx_conf_set_off_slot`].
+///
+/// Parsed from the [size syntax] with an optional `k`, `m` or `g` suffix, e.g. `512`, `16k` or
+/// `1g`.
+///
+/// [`ngx_conf_set_off_slot`]: crate::ffi::ngx_conf_set_off_slot
+/// [size syntax]: https://nginx.org/en/docs/syntax.html
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(transparent)]
+pub struct NgxSize(pub off_t);
+
+impl NgxMsec {
+    /// Unset value, equivalent to `NGX_CONF_UNSET_MSEC`.
+    pub const UNSET: Self = Self(ngx_msec_t::MAX);
+
+    /// Returns `true` if the value is not set.
+    pub fn is_unset(&self) -> bool {
+        *self == Self::UNSET
+    }
+
+    /// Parses a time interval from bytes.
+    pub fn parse(value: &[u8]) -> Result<Self, ParseUnitError> {
+        let msec = parse_time(value, false)?;
+        match ngx_msec_t::try_from(msec) {
+            Ok(msec) if msec != ngx_msec_t::MAX => Ok(Self(msec)),
+            _ => Err(ParseUnitError),
+        }
+    }
+}
+
+impl NgxSec {
+    /// Unset value, equivalent to `NGX_CONF_UNSET`.
+    pub const UNSET: Self = Self(-1);
+
+    /// Returns `true` if the value is not set.
+    pub fn is_unset(&self) -> bool {
+        *self == Self::UNSET
+    }
+
+    /// Parses a time interval from bytes.
+    pub fn parse(value: &[u8]) -> Result<Self, ParseUnitError> {
+        let sec = parse_time(value, true)? / 1000;
+        time_t::try_from(sec).map(Self).map_err(|_| ParseUnitError)
+    }
+}
+
+impl NgxSize {
+    /// Unset value, equivalent to `NGX_CONF_UNSET`.
+    pub const UNSET: Self = Self(-1);
+
+    /// Returns `true` if the value is not set.
+    pub fn is_unset(&self) -> bool {
+        *self == Self::UNSET
+    }
+
+    /// Parses a size from bytes.
+    pub fn parse(value: &[u8]) -> Result<Self, ParseUnitError> {
+        let (digits, scale) = match value.split_last() {
+            Some((b'k' | b'K', rest)) => (rest, 1 << 10),
+            Some((b'm' | b'M', rest)) => (rest, 1 << 20),
+            Some((b'g' | b'G', rest)) => (rest, 1 << 30),
+            _ => (value, 1),
+        };
+
+        parse_number(digits)
+            .and_then(|x| x.checked_mul(scale))
+            .and_then(|x| off_t::try_from(x).ok())
+            .map(Self)
+            .ok_or(ParseUnitError)
+    }
+}
+
+/// Parses the time interval syntax into milliseconds, following `ngx_parse_time`.
+///
+/// Units must appear in the descending order, and years and months are only allowed for the
+/// values in seconds.
+fn parse_time(value: &[u8], is_sec: bool) -> Result<u64, ParseUnitError> {
+    const SEC: u64 = 1000;
+    const DAY: u64 = 24 * 60 * 60 * SEC;
+
+    // Units, ordered by the position in the interval; the last entry is a number without a suffix.
+    const UNITS: [(&[u8], u64); 10] = [
+        (b"y", 365 * DAY),
+        (b"M", 30 * DAY),
+        (b"w", 7 * DAY),
+        (b"d", DAY),
+        (b"h", 60 * 60 * SEC),
+        (b"ms", 1),
+        (b"m", 60 * SEC),
+        (b"s", SEC),
+        (b" ", SEC),
+        (b"", SEC),
+    ];
+
+    let mut rest = value;
+    let mut total: u64 = 0;
+    // Position of the last unit; disallow years and months in milliseconds.
+    let mut last = if is_sec { 0 } else { 2 };
+
+    if rest.is_empty() {
+        return Err(ParseUnitError);
+    }
+
+    while !rest.is_empty() {
+        let len = rest.iter().take_while(|x| x.is_ascii_digit()).count();
+        let (digits, tail) = rest.split_at(len);
+        let number = parse_number(digits).ok_or(ParseUnitError)?;
+
+        let index =
+            UNITS.iter().position(|(suffix, _)| tail.starts_with(suffix)).ok_or(ParseUnitError)?;
+
+        // Order of the unit; milliseconds follow seconds, and a number without a suffix is only
+        // allowed before seconds.
+        let order = match index {
+            5 => 8,
+            6 => 6,
+            7 => 7,
+            8 | 9 => 9,
+            x => x + 1,
+        };
+
+        if order <= last || (order == 8 && is_sec) || (order == 9 && last >= 7) {
+            return Err(ParseUnitError);
+        }
+
+        last = order;
+
+        let (suffix, scale) = UNITS[index];
+        total =
+            number.checked_mul(scale).and_then(|x| x.checked_add(total)).ok_or(ParseUnitError)?;
+
+        rest = tail[suffix.len()..].trim_ascii_start();
+    }
+
+    Ok(total)
+}
+
+fn parse_number(digits: &[u8]) -> Option<u64> {
+    if digits.is_empty() {
+        return None;
+    }
+
+    digits.iter().try_fold(0u64, |acc, &x| {
+        if !x.is_ascii_digit() {
+            return None;
+        }
+        acc.checked_mul(10)?.checked_add(u64::from(x - b'0'))
+    })
+}
+
+/// Writes the interval in milliseconds as a sequence of units, e.g. `1h30m` or `1s500ms`.
+fn fmt_time(f: &mut fmt::Formatter<'_>, mut msec: u64) -> fmt::Result {
+    const UNITS: [(&str, u64); 5] =
+        [("d", 86_400_000), ("h", 3_600_000), ("m", 60_000), ("s", 1000), ("ms", 1)];
+
+    if msec == 0 {
+        return f.write_str("0s");
+    }
+
+    for (suffix, scale) in UNITS {
+        if msec >= scale {
+            write!(f, "{}{}", msec / scale, suffix)?;
+            msec %= scale;
+        }
+    }
+
+    Ok(())
+}
+
+macro_rules! impl_from_str {
+    ($($ty:ty),+) => {$(
+        impl FromStr for $ty {
+            type Err = ParseUnitError;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                Self::parse(s.as_bytes())
+            }
+        }
+    )+};
+}
+
+impl_from_str!(NgxMsec, NgxSec, NgxSize);
+
+/// The configurations created with `Default` start with the unset values, as in nginx, so that
+/// the values are merged and the duplicate directives are detected.
+macro_rules! impl_default {
+    ($($ty:ty),+) => {$(
+        impl Default for $ty {
+            fn default() -> Self {
+                Self::UNSET
+            }
+        }
+    )+};
+}
+
+impl_default!(NgxMsec, NgxSec, NgxSize);
+
+impl fmt::Display for NgxMsec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt_time(f, self.0 as u64)
+    }
+}
+
+impl fmt::Display for NgxSec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0 < 0 {
+            return write!(f, "{}", self.0);
+        }
+        fmt_time(f, (self.0 as u64).saturating_mul(1000))
+    }
+}
+
+impl fmt::Display for NgxSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const UNITS: [(&str, off_t); 3] = [("g", 1 << 30), ("m", 1 << 20), ("k", 1 << 10)];
+
+        if self.0 > 0 {
+            for (suffix, scale) in UNITS {
+                if self.0 % scale == 0 {
+                    return write!(f, "{}{}", self.0 / scale, suffix);
+                }
+            }
+        }
+
+        write!(f, "{}", self.0)
+    }
+}
+
+impl From<NgxMsec> for Duration {
+    fn from(value: NgxMsec) -> Self {
+        Duration::from_millis(value.0 as u64)
+    }
+}
+
+impl TryFrom<Duration> for NgxMsec {
+    type Error = ParseUnitError;
+
+    fn try_from(value: Duration) -> Result<Self, Self::Error> {
+        match ngx_msec_t::try_from(value.as_millis()) {
+            Ok(msec) if msec != ngx_msec_t::MAX => Ok(Self(msec)),
+            _ => Err(ParseUnitError),
+        }
+    }
+}
+
+impl TryFrom<NgxSec> for Duration {
+    type Error = ParseUnitError;
+
+    fn try_from(value: NgxSec) -> Result<Self, Self::Error> {
+        u64::try_from(value.0).map(Duration::from_secs).map_err(|_| ParseUnitError)
+    }
+}
+
+impl TryFrom<Duration> for NgxSec {
+    type Error = ParseUnitError;
+
+    fn try_from(value: Duration) -> Result<Self, Self::Error> {
+        time_t::try_from(value.as_secs()).map(Self).map_err(|_| ParseUnitError)
+    }
+}
+
+impl TryFrom<NgxSize> for u64 {
+    type Error = ParseUnitError;
+
+    fn try_from(value: NgxSize) -> Result<Self, Self::Error> {
+        u64::try_from(value.0).map_err(|_| ParseUnitError)
+    }
+}
+
+impl TryFrom<u64> for NgxSize {
+    type Error = ParseUnitError;
+
+    fn try_from(value: u64) -> Result<Self, Self::Error> {
+        off_t::try_from(value).map(Self).map_err(|_| ParseUnitError)
+    }
+}
+
+impl From<NgxSize> for off_t {
+    fn from(value: NgxSize) -> Self {
+        value.0
+    }
+}
+
+macro_rules! impl_conf_slot {
+    ($($(#[$attr:meta])* $name:ident: $ty:ident),+) => {$(
+        impl ConfUnset for $ty {
+            fn is_unset(&self) -> bool {
+                $ty::is_unset(self)
+            }
+        }
+
+        $(#[$attr])*
+        ///
+        /// The field is located at `offset` of the command, as with the nginx slot functions.
+        /// Rejects duplicate directives.
+        ///
+        /// # Safety
+        ///
+        #[doc = concat!("The field at `cmd.offset` in `conf` must have the type [`", stringify!($ty), "`].")]
+        pub unsafe extern "C" fn $name(
+            cf: *mut ngx_conf_t,
+            cmd: *mut ngx_command_t,
+            conf: *mut c_void,
+        ) -> *mut c_char {
+            // SAFETY: nginx passes valid pointers to the directive handlers.
+            let (cf, cmd) = unsafe { (&*cf, &*cmd) };
+            let field = unsafe { &mut *conf.byte_add(cmd.offset).cast::<$ty>() };
+
+            if field.is_set() {
+                return NGX_CONF_DUPLICATE;
+            }
+
+            // SAFETY: `cf.args` contains the directive name and arguments.
+            let args: &[ngx_str_t] = unsafe { (*cf.args).as_slice() };
+
+            match args.get(1).map(|x| $ty::parse(x.as_bytes())) {
+                Some(Ok(value)) => {
+                    *field = value;
+                    NGX_CONF_OK
+                }
+                _ => c"invalid value".as_ptr().cast_mut(),
+            }
+        }
+    )+};
+}
+
+impl_conf_slot!(
+    /// Directive handler parsing a time interval into a [`NgxMsec`] field of the configuration.
+    msec_slot: NgxMsec,
+    /// Directive handler parsing a time interval into a [`NgxSec`] field of the configuration.
+    sec_slot: NgxSec,
+    /// Directive handler parsing a size into a [`NgxSize`] field of the configuration.
+    size_slot: NgxSize
+);
+
+macro_rules! impl_merge {
+    ($($ty:ty),+) => {$(
+        impl crate::core::Merge for $ty {
+            fn merge(&mut self, prev: &Self) -> Result<(), crate::core::MergeConfigError> {
+                if self.is_unset() {
+                    *self = *prev;
+                }
+                Ok(())
+            }
+        }
+    )+};
+}
+
+impl_merge!(NgxMsec, NgxSec, NgxSize);
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use std::string::ToString;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_msec() {
+        let parse = |s: &str| s.parse::<NgxMsec>().map(|x| x.0 as u64);
+
+        assert_eq!(parse("500ms"), Ok(500));
+        assert_eq!(parse("30"), Ok(30_000));
+        assert_eq!(parse("30s"), Ok(30_000));
+        assert_eq!(parse("1h 30m"), Ok(5_400_000));
+        assert_eq!(parse("1m30s500ms"), Ok(90_500));
+        assert_eq!(parse("1w"), Ok(604_800_000));
+        assert_eq!(parse("1m 5"), Ok(65_000));
+
+        assert!(parse("").is_err());
+        assert!(parse("1y").is_err());
+        assert!(parse("1s 1m").is_err());
+        assert!(parse("1s 5").is_err());
+        assert!(parse("5 1s").is_err());
+        assert!(parse("1x").is_err());
+        assert!(parse("s").is_err());
+    }
+
+    #[test]
+    fn test_parse_sec() {
+        let parse = |s: &str| s.parse::<NgxSec>().map(|x| x.0);
+
+        assert_eq!(parse("10m"), Ok(600));
+        assert_eq!(parse("1y 1M"), Ok(365 * 86400 + 30 * 86400));
+        assert_eq!(parse("1d 12h"), Ok(129_600));
+
+        assert!(parse("500ms").is_err());
+        assert!(parse("1M 1y").is_err());
+    }
+
+    #[test]
+    fn test_parse_size() {
+        let parse = |s: &str| s.parse::<NgxSize>().map(|x| x.0);
+
+        assert_eq!(parse("512"), Ok(512));
+        assert_eq!(parse("16k"), Ok(16384));
+        assert_eq!(parse("2M"), Ok(2 << 20));
+        assert_eq!(parse("1g"), Ok(1 << 30));
+
+        assert!(parse("").is_err());
+        assert!(parse("k").is_err());
+        assert!(parse("1t").is_err());
+        assert!(parse("-1").is_err());
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(NgxMsec(90_500).to_string(), "1m30s500ms");
+        assert_eq!(NgxMsec(0).to_string(), "0s");
+        assert_eq!(NgxSec(129_600).to_string(), "1d12h");
+        assert_eq!(NgxSize(16384).to_string(), "16k");
+        assert_eq!(NgxSize(1 << 30).to_string(), "1g");
+        assert_eq!(NgxSize(1000).to_string(), "1000");
+
+        for s in ["1m30s500ms", "1d12h", "45s"] {
+            assert_eq!(s.parse::<NgxMsec>().unwrap().to_string(), s);
+        }
+    }
+
+    #[test]
+    fn test_duration() {
+        assert_eq!(Duration::from(NgxMsec(1500)), Duration::from_millis(1500));
+        assert_eq!(NgxSec::try_from(Duration::from_secs(60)), Ok(NgxSec(60)));
+        assert!(Duration::try_from(NgxSec::UNSET).is_err());
+    }
+
+    #[test]
+    fn test_default() {
+        assert!(NgxMsec::default().is_unset());
+        assert!(NgxSec::default().is_unset());
+        assert!(NgxSize::default().is_unset());
+    }
+
+    #[test]
+    fn test_conf_slot() {
+        use core::ffi::CStr;
+        use core::{mem, ptr};
+
+        use crate::ffi::ngx_array_t;
+
+        #[derive(Default)]
+        struct Conf {
+            timeout: NgxMsec,
+            size: NgxSize,
+        }
+
+        fn set_size(conf: &mut Conf, value: &'static [u8]) -> Option<&'static CStr> {
+            let mut args = [
+                ngx_str_t { len: 4, data: b"size".as_ptr().cast_mut() },
+                ngx_str_t { len: value.len(), data: value.as_ptr().cast_mut() },
+            ];
+            let mut array: ngx_array_t = unsafe { mem::zeroed() };
+            array.elts = args.as_mut_ptr().cast();
+            array.nelts = args.len();
+            array.size = mem::size_of::<ngx_str_t>();
+            array.nalloc = args.len();
+
+            let mut cf: ngx_conf_t = unsafe { mem::zeroed() };
+            cf.args = &mut array;
+            let mut cmd: ngx_command_t = unsafe { mem::zeroed() };
+            cmd.offset = mem::offset_of!(Conf, size);
+
+            let rc = unsafe { size_slot(&mut cf, &mut cmd, ptr::from_mut(conf).cast()) };
+            (!rc.is_null()).then(|| unsafe { CStr::from_ptr(rc) })
+        }
+
+        let mut conf = Conf::default();
+        assert_eq!(set_size(&mut conf, b"16k"), None);
+        assert_eq!(conf.size, NgxSize(16384));
+        assert_eq!(set_size(&mut conf, b"1k"), Some(c"is duplicate"));
+        assert_eq!(conf.size, NgxSize(16384));
+        assert!(conf.timeout.is_unset());
+
+        let mut conf = Conf::default();
+        assert_eq!(set_size(&mut conf, b"1t"), Some(c"invalid value"));
+        assert!(conf.size.is_unset());
+    }
+}
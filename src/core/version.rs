@@ -0,0 +1,73 @@
+use crate::ffi::nginx_version;
+
+/// Version of nginx the module is built against, encoded as `major * 1000000 + minor * 1000 +
+/// patch`.
+///
+/// See [`ngx_version_at_least`](crate::ngx_version_at_least).
+pub const NGX_VERSION_NUMBER: u32 = nginx_version as u32;
+
+/// Encodes an nginx version in the format of [`NGX_VERSION_NUMBER`].
+pub const fn version_number(major: u32, minor: u32, patch: u32) -> u32 {
+    major * 1_000_000 + minor * 1_000 + patch
+}
+
+/// Evaluates to `true` if the module is built against the specified nginx version or newer.
+///
+/// The result is a constant expression and can be used to select the code path for different
+/// nginx versions without the overhead of a runtime check. Both branches must compile with
+/// any supported nginx version.
+///
+/// The API differences affecting the types and the fields of the structures require conditional
+/// compilation with the version cfgs instead. `nginx-sys` exports the cfg names as
+/// `DEP_NGINX_VERSIONS`, and the build script of this crate shows how to pass them to the
+/// compiler. A module with the same build script can then use
+///
+/// ```ignore
+/// #[cfg(nginx1_25_1)]
+/// let http2 = hscf.enable;
+/// ```
+///
+/// See the `nginx-sys` documentation for the list of the versions and the build script example.
+///
+/// # Example
+///
+/// ```
+/// # use ngx::ngx_version_at_least;
+/// const HAS_FEATURE: bool = ngx_version_at_least!(1, 25, 0);
+///
+/// if ngx_version_at_least!(1, 27) {
+///     // ...
+/// }
+/// ```
+#[macro_export]
+macro_rules! ngx_version_at_least {
+    ($major:expr, $minor:expr) => {
+        $crate::ngx_version_at_least!($major, $minor, 0)
+    };
+    ($major:expr, $minor:expr, $patch:expr) => {
+        ($crate::core::NGX_VERSION_NUMBER >= $crate::core::version_number($major, $minor, $patch))
+    };
+}
+
+/// Evaluates to `true` if the module is built against nginx older than the specified version.
+///
+/// See [`ngx_version_at_least`](crate::ngx_version_at_least).
+#[macro_export]
+macro_rules! ngx_version_before {
+    ($($version:expr),+) => {
+        !$crate::ngx_version_at_least!($($version),+)
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_version_number() {
+        assert_eq!(version_number(1, 25, 1), 1_025_001);
+        assert!(ngx_version_at_least!(1, 0));
+        assert!(ngx_version_before!(100, 0, 0));
+        assert_eq!(ngx_version_at_least!(1, 25, 1), NGX_VERSION_NUMBER >= version_number(1, 25, 1));
+    }
+}
@@ -0,0 +1,468 @@
+//! Access control policies.
+//!
+//! A policy is a list of `allow` and `deny` rules with optional conditions, evaluated in order
+//! until the first matching rule, similar to the nginx `access` module. The rules are usually
+//! configured with directives:
+//!
+//! ```text
+//! policy_rule allow 10.0.0.0/8;
+//! policy_rule deny  all method=POST,PUT,DELETE;
+//! policy_rule deny  all header=X-Debug;
+//! policy_rule allow all header=Authorization^Bearer rate=10r/s burst=20;
+//! policy_rule deny  all;
+//! ```
+//!
+//! The conditions of a rule must all match:
+//!
+//! - `addr` or `addr/prefix`, `all` - client address in the CIDR block;
+//! - `method=NAME[,NAME...]` - request method;
+//! - `header=Name` - the request header is present; `header=Name:value` - the header value is
+//!   equal to `value`; `header=Name^prefix` - the header value starts with `prefix`;
+//! - `rate=Nr/s` or `rate=Nr/m` with optional `burst=N` - the rule matches while the requests
+//!   matching the other conditions do not exceed the rate. The rate is tracked per worker process
+//!   for each rule, not per client.
+use core::cell::Cell;
+use core::error;
+use core::fmt;
+use core::net::IpAddr;
+
+use crate::allocator::{AllocError, Allocator};
+use crate::collections::Vec;
+use crate::core::{Pool, sockaddr_to_socket_addr};
+use crate::ffi::ngx_msec_t;
+use crate::http::Request;
+
+/// Action of a matching rule.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    /// Allow the request.
+    Allow,
+    /// Deny the request.
+    Deny,
+}
+
+/// Error parsing a policy rule.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PolicyError {
+    /// The action is not `allow` or `deny`.
+    InvalidAction,
+    /// Invalid address or CIDR block.
+    InvalidAddress,
+    /// Unknown or invalid condition.
+    InvalidCondition,
+    /// Memory allocation failed.
+    Alloc,
+}
+
+impl error::Error for PolicyError {}
+
+impl fmt::Display for PolicyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            PolicyError::InvalidAction => "invalid action",
+            PolicyError::InvalidAddress => "invalid address",
+            PolicyError::InvalidCondition => "invalid condition",
+            PolicyError::Alloc => "memory allocation failed",
+        })
+    }
+}
+
+impl From<AllocError> for PolicyError {
+    fn from(_: AllocError) -> Self {
+        PolicyError::Alloc
+    }
+}
+
+/// CIDR block of IPv4 or IPv6 addresses.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Cidr {
+    addr: IpAddr,
+    prefix: u8,
+}
+
+impl Cidr {
+    /// Parses an address or a CIDR block, e.g. `192.168.1.0/24` or `2001:db8::/32`.
+    ///
+    /// The address bits beyond the prefix length must be zero, as in nginx.
+    pub fn parse(value: &[u8]) -> Result<Self, PolicyError> {
+        let value = core::str::from_utf8(value).map_err(|_| PolicyError::InvalidAddress)?;
+        let (addr, prefix) = match value.split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (value, None),
+        };
+
+        let addr: IpAddr = addr.parse().map_err(|_| PolicyError::InvalidAddress)?;
+        let max = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(x) => x.parse::<u8>().map_err(|_| PolicyError::InvalidAddress)?,
+            None => max,
+        };
+
+        if prefix > max {
+            return Err(PolicyError::InvalidAddress);
+        }
+
+        let cidr = Self { addr, prefix };
+        if cidr.masked(addr) != bits(addr) {
+            return Err(PolicyError::InvalidAddress);
+        }
+        Ok(cidr)
+    }
+
+    /// Returns `true` if the block contains `addr`.
+    ///
+    /// IPv4-mapped IPv6 addresses are matched against the IPv4 blocks.
+    pub fn contains(&self, addr: IpAddr) -> bool {
+        let addr = match addr {
+            IpAddr::V6(v6) => v6.to_ipv4_mapped().map_or(addr, IpAddr::V4),
+            v4 => v4,
+        };
+
+        addr.is_ipv4() == self.addr.is_ipv4() && self.masked(addr) == bits(self.addr)
+    }
+
+    fn masked(&self, addr: IpAddr) -> u128 {
+        let width = if addr.is_ipv4() { 32 } else { 128 };
+        let shift = width - u32::from(self.prefix);
+        let mask = if shift >= 128 { 0 } else { u128::MAX << shift };
+        bits(addr) & mask
+    }
+}
+
+fn bits(addr: IpAddr) -> u128 {
+    match addr {
+        IpAddr::V4(x) => u128::from(x.to_bits()),
+        IpAddr::V6(x) => x.to_bits(),
+    }
+}
+
+/// Matcher for a request header value.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HeaderMatch<'a> {
+    /// The header is present.
+    Present,
+    /// The header value is equal to the string.
+    Equals(&'a [u8]),
+    /// The header value starts with the string.
+    Prefix(&'a [u8]),
+}
+
+impl HeaderMatch<'_> {
+    /// Returns `true` if the header value matches.
+    pub fn matches(&self, value: &[u8]) -> bool {
+        match self {
+            HeaderMatch::Present => true,
+            HeaderMatch::Equals(x) => value == *x,
+            HeaderMatch::Prefix(x) => value.starts_with(x),
+        }
+    }
+}
+
+/// Token bucket rate limiter, tracked in the current process.
+#[derive(Debug)]
+pub struct TokenBucket {
+    /// Rate, in requests per minute.
+    rate: u64,
+    /// Bucket capacity, in [`TokenBucket::UNIT`]s.
+    capacity: u64,
+    tokens: Cell<u64>,
+    last: Cell<ngx_msec_t>,
+}
+
+impl TokenBucket {
+    /// Cost of a request in tokens; the bucket gains `rate` tokens every millisecond.
+    const UNIT: u64 = 60_000;
+
+    /// Creates a bucket allowing `per_minute` requests per minute with bursts of `burst` requests
+    /// above the rate.
+    pub fn new(per_minute: u64, burst: u64) -> Self {
+        let capacity = burst.saturating_add(1).saturating_mul(Self::UNIT);
+        Self { rate: per_minute, capacity, tokens: Cell::new(capacity), last: Cell::new(0) }
+    }
+
+    /// Takes a token from the bucket at the time `now`, in milliseconds.
+    ///
+    /// Returns `false` if the rate is exceeded.
+    pub fn take(&self, now: ngx_msec_t) -> bool {
+        let elapsed = now.wrapping_sub(self.last.get()) as u64;
+        self.last.set(now);
+
+        let tokens =
+            self.tokens.get().saturating_add(elapsed.saturating_mul(self.rate)).min(self.capacity);
+
+        if tokens < Self::UNIT {
+            self.tokens.set(tokens);
+            return false;
+        }
+
+        self.tokens.set(tokens - Self::UNIT);
+        true
+    }
+}
+
+/// Condition of a policy rule.
+#[derive(Debug)]
+pub enum Condition<'a> {
+    /// Client address is in the CIDR block.
+    Address(Cidr),
+    /// Request method is one of the methods.
+    Method(&'a [u8]),
+    /// Request header matches.
+    Header {
+        /// Header name, case-insensitive.
+        name: &'a [u8],
+        /// Value matcher.
+        value: HeaderMatch<'a>,
+    },
+    /// Matching requests do not exceed the rate.
+    Rate(TokenBucket),
+}
+
+/// Policy rule: an action with the conditions that must all match.
+#[derive(Debug)]
+pub struct Rule<'a, A: Allocator = Pool> {
+    action: Action,
+    conditions: Vec<Condition<'a>, A>,
+}
+
+impl<'a, A: Allocator> Rule<'a, A> {
+    /// Parses a rule from the directive arguments, e.g. `["allow", "10.0.0.0/8", "method=GET"]`.
+    pub fn parse_in(args: &[&'a [u8]], alloc: A) -> Result<Self, PolicyError> {
+        let (action, args) = args.split_first().ok_or(PolicyError::InvalidAction)?;
+        let action = match *action {
+            b"allow" => Action::Allow,
+            b"deny" => Action::Deny,
+            _ => return Err(PolicyError::InvalidAction),
+        };
+
+        let (addr, args) = args.split_first().ok_or(PolicyError::InvalidAddress)?;
+
+        let mut conditions = Vec::new_in(alloc);
+        if *addr != b"all" {
+            conditions.try_reserve(1).map_err(|_| AllocError)?;
+            conditions.push(Condition::Address(Cidr::parse(addr)?));
+        }
+
+        let mut rate = None;
+        let mut burst = 0;
+
+        for arg in args {
+            let Some(eq) = arg.iter().position(|&x| x == b'=') else {
+                return Err(PolicyError::InvalidCondition);
+            };
+            let (key, value) = (&arg[..eq], &arg[eq + 1..]);
+
+            let condition = match key {
+                b"method" if !value.is_empty() => Condition::Method(value),
+                b"header" => parse_header(value)?,
+                b"rate" => {
+                    rate = Some(parse_rate(value)?);
+                    continue;
+                }
+                b"burst" => {
+                    burst = parse_number(value)?;
+                    continue;
+                }
+                _ => return Err(PolicyError::InvalidCondition),
+            };
+
+            conditions.try_reserve(1).map_err(|_| AllocError)?;
+            conditions.push(condition);
+        }
+
+        match rate {
+            Some(rate) => {
+                conditions.try_reserve(1).map_err(|_| AllocError)?;
+                conditions.push(Condition::Rate(TokenBucket::new(rate, burst)));
+            }
+            None if burst != 0 => return Err(PolicyError::InvalidCondition),
+            None => {}
+        }
+
+        Ok(Self { action, conditions })
+    }
+
+    /// Returns the action of the rule.
+    pub fn action(&self) -> Action {
+        self.action
+    }
+
+    /// Returns the conditions of the rule.
+    pub fn conditions(&self) -> &[Condition<'a>] {
+        &self.conditions
+    }
+
+    /// Returns `true` if the request matches all the conditions.
+    ///
+    /// The rate condition, if any, is checked last and consumes a token only when the other
+    /// conditions match.
+    pub fn matches(&self, r: &Request, now: ngx_msec_t) -> bool {
+        let mut addr = None;
+
+        self.conditions.iter().all(|condition| match condition {
+            Condition::Address(cidr) => {
+                let ip = *addr.get_or_insert_with(|| client_addr(r));
+                ip.is_some_and(|ip| cidr.contains(ip))
+            }
+            Condition::Method(list) => {
+                let method = r.method();
+                list.split(|&x| x == b',').any(|x| x == method.as_str().as_bytes())
+            }
+            Condition::Header { name, value } => {
+                r.headers_in().get_all(*name).any(|h| value.matches(h.value().as_bytes()))
+            }
+            Condition::Rate(bucket) => bucket.take(now),
+        })
+    }
+}
+
+fn parse_header(value: &[u8]) -> Result<Condition<'_>, PolicyError> {
+    let pos = value.iter().position(|&x| x == b':' || x == b'^');
+    let (name, matcher) = match pos {
+        Some(pos) if value[pos] == b':' => (&value[..pos], HeaderMatch::Equals(&value[pos + 1..])),
+        Some(pos) => (&value[..pos], HeaderMatch::Prefix(&value[pos + 1..])),
+        None => (value, HeaderMatch::Present),
+    };
+
+    if name.is_empty() {
+        return Err(PolicyError::InvalidCondition);
+    }
+
+    Ok(Condition::Header { name, value: matcher })
+}
+
+fn parse_rate(value: &[u8]) -> Result<u64, PolicyError> {
+    let (num, scale) = if let Some(x) = value.strip_suffix(b"r/s") {
+        (x, 60)
+    } else if let Some(x) = value.strip_suffix(b"r/m") {
+        (x, 1)
+    } else {
+        return Err(PolicyError::InvalidCondition);
+    };
+
+    match parse_number(num)?.checked_mul(scale) {
+        Some(rate) if rate != 0 => Ok(rate),
+        _ => Err(PolicyError::InvalidCondition),
+    }
+}
+
+fn parse_number(value: &[u8]) -> Result<u64, PolicyError> {
+    core::str::from_utf8(value)
+        .ok()
+        .and_then(|x| x.parse().ok())
+        .ok_or(PolicyError::InvalidCondition)
+}
+
+fn client_addr(r: &Request) -> Option<IpAddr> {
+    let c = r.as_ref().connection;
+    // SAFETY: the connection and its address are valid for the lifetime of the request.
+    unsafe { sockaddr_to_socket_addr((*c).sockaddr) }.map(|x| x.ip())
+}
+
+/// Ordered list of the access rules.
+#[derive(Debug)]
+pub struct AccessPolicy<'a, A: Allocator + Clone = Pool> {
+    rules: Vec<Rule<'a, A>, A>,
+}
+
+impl<'a, A: Allocator + Clone> AccessPolicy<'a, A> {
+    /// Creates an empty policy.
+    pub fn new_in(alloc: A) -> Self {
+        Self { rules: Vec::new_in(alloc) }
+    }
+
+    /// Parses a rule from the directive arguments and appends it to the policy.
+    pub fn add_rule(&mut self, args: &[&'a [u8]]) -> Result<(), PolicyError> {
+        let rule = Rule::parse_in(args, self.rules.allocator().clone())?;
+        self.rules.try_reserve(1).map_err(|_| AllocError)?;
+        self.rules.push(rule);
+        Ok(())
+    }
+
+    /// Returns the rules of the policy.
+    pub fn rules(&self) -> &[Rule<'a, A>] {
+        &self.rules
+    }
+
+    /// Returns `true` if the policy has no rules.
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Evaluates the policy for the request and returns the action of the first matching rule.
+    ///
+    /// Returns `None` if no rule matches; the caller decides the default action.
+    pub fn evaluate(&self, r: &Request) -> Option<Action> {
+        let now = crate::core::current_msec();
+        self.rules.iter().find(|rule| rule.matches(r, now)).map(Rule::action)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use crate::allocator::Global;
+
+    use super::*;
+
+    #[test]
+    fn test_cidr() {
+        let cidr = Cidr::parse(b"192.168.0.0/16").unwrap();
+        assert!(cidr.contains("192.168.10.1".parse().unwrap()));
+        assert!(cidr.contains("::ffff:192.168.10.1".parse().unwrap()));
+        assert!(!cidr.contains("192.169.0.1".parse().unwrap()));
+        assert!(!cidr.contains("::1".parse().unwrap()));
+
+        let cidr = Cidr::parse(b"2001:db8::/32").unwrap();
+        assert!(cidr.contains("2001:db8::1".parse().unwrap()));
+        assert!(!cidr.contains("2001:db9::1".parse().unwrap()));
+
+        assert!(Cidr::parse(b"0.0.0.0/0").unwrap().contains("10.0.0.1".parse().unwrap()));
+        assert!(Cidr::parse(b"10.0.0.1").unwrap().contains("10.0.0.1".parse().unwrap()));
+
+        assert_eq!(Cidr::parse(b"10.0.0.1/8"), Err(PolicyError::InvalidAddress));
+        assert_eq!(Cidr::parse(b"10.0.0.0/33"), Err(PolicyError::InvalidAddress));
+        assert_eq!(Cidr::parse(b"example.com"), Err(PolicyError::InvalidAddress));
+    }
+
+    #[test]
+    fn test_token_bucket() {
+        let bucket = TokenBucket::new(60, 1);
+        assert!(bucket.take(0));
+        assert!(bucket.take(0));
+        assert!(!bucket.take(0));
+        assert!(!bucket.take(500));
+        assert!(bucket.take(1000));
+        assert!(!bucket.take(1000));
+    }
+
+    #[test]
+    fn test_parse_rule() {
+        let rule = Rule::parse_in(
+            &[b"deny", b"10.0.0.0/8", b"method=POST,PUT", b"header=Authorization^Bearer "],
+            Global,
+        )
+        .unwrap();
+        assert_eq!(rule.action(), Action::Deny);
+        assert_eq!(rule.conditions().len(), 3);
+        assert!(matches!(
+            rule.conditions()[2],
+            Condition::Header { name: b"Authorization", value: HeaderMatch::Prefix(b"Bearer ") }
+        ));
+
+        let rule = Rule::parse_in(&[b"allow", b"all", b"rate=10r/s", b"burst=5"], Global).unwrap();
+        assert!(matches!(rule.conditions(), [Condition::Rate(_)]));
+
+        for args in [
+            &[&b"permit"[..], b"all"][..],
+            &[b"allow"],
+            &[b"allow", b"all", b"unknown=1"],
+            &[b"allow", b"all", b"burst=5"],
+            &[b"allow", b"all", b"rate=0r/s"],
+            &[b"allow", b"all", b"header="],
+        ] {
+            assert!(Rule::parse_in(args, Global).is_err(), "{args:?}");
+        }
+    }
+}
@@ -0,0 +1,177 @@
+use core::ffi::CStr;
+use core::ptr;
+
+use crate::allocator::AllocError;
+use crate::core::{Pool, Status};
+use crate::ffi::{
+    NGX_LOG_EMERG, ngx_conf_t, ngx_http_add_variable, ngx_http_request_t, ngx_int_t, ngx_str_t,
+    ngx_variable_value_t,
+};
+use crate::http::HttpModule;
+use crate::ngx_conf_log_error;
+
+/// Build metadata of a module crate.
+///
+/// Use [`ngx_module_build_info`](crate::ngx_module_build_info) to capture the metadata of the
+/// calling crate at compile time.
+#[derive(Clone, Copy, Debug)]
+pub struct ModuleBuildInfo {
+    /// Name of the crate.
+    pub name: &'static str,
+    /// Version of the crate.
+    pub version: &'static str,
+    /// Free-form build description.
+    ///
+    /// Populated from the `NGX_RUST_MODULE_BUILD` compile-time environment variable of the module
+    /// crate, or from [`ModuleBuildInfo::NGX_BUILD`] if the variable is not set. See
+    /// [`ngx_module_build_info`](crate::ngx_module_build_info).
+    pub build: &'static str,
+}
+
+impl ModuleBuildInfo {
+    /// Build description of the `ngx` crate: the nginx version, the target and the profile.
+    ///
+    /// Set by the buildscript of the `ngx` crate and does not reflect the profile of the module
+    /// crate.
+    pub const NGX_BUILD: &'static str = env!("NGX_RUST_MODULE_BUILD");
+}
+
+/// Captures [`ModuleBuildInfo`] for the crate invoking the macro.
+///
+/// The values are read from the Cargo environment at compile time, thus the macro must be expanded
+/// in the module crate.
+///
+/// The build description is read from the `NGX_RUST_MODULE_BUILD` variable set for the module
+/// crate. The variable is not inherited from the `ngx` crate: the buildscript of the module crate
+/// should set it with `cargo::rustc-env`, as the example buildscript of this crate does, e.g.
+///
+/// ```no_run
+/// // build.rs
+/// println!("cargo::rerun-if-env-changed=DEP_NGINX_VERSION");
+/// println!(
+///     "cargo::rustc-env=NGX_RUST_MODULE_BUILD=nginx/{} {}",
+///     std::env::var("DEP_NGINX_VERSION").unwrap_or("unknown".to_string()),
+///     std::env::var("PROFILE").unwrap_or_default(),
+/// );
+/// ```
+///
+/// `DEP_NGINX_VERSION` is only available with `nginx-sys` in the direct dependencies of the
+/// module crate. Without the variable, the description of the `ngx` build,
+/// [`ModuleBuildInfo::NGX_BUILD`], is used.
+#[macro_export]
+macro_rules! ngx_module_build_info {
+    () => {
+        $crate::http::ModuleBuildInfo {
+            name: env!("CARGO_PKG_NAME"),
+            version: env!("CARGO_PKG_VERSION"),
+            build: match option_env!("NGX_RUST_MODULE_BUILD") {
+                Some(build) => build,
+                None => $crate::http::ModuleBuildInfo::NGX_BUILD,
+            },
+        }
+    };
+}
+
+/// Registers `$<module>_version` and `$<module>_build` variables for an HTTP module.
+///
+/// The `<module>` prefix is derived from the module name by removing the `ngx_http_` prefix and
+/// the `_module` suffix, e.g. `ngx_http_foo_module` will define `$foo_version` and `$foo_build`.
+/// The crate name is used if the module name is not available.
+///
+/// This function must be called from the module's `preconfiguration()` function.
+pub fn add_build_info_variables<M>(
+    cf: &mut ngx_conf_t,
+    info: &'static ModuleBuildInfo,
+) -> Result<(), AllocError>
+where
+    M: HttpModule,
+{
+    // SAFETY: the module name is either NULL or a static nul-terminated string set by
+    // ngx_preinit_modules() or ngx_add_module().
+    let module_name = unsafe { M::module().name.as_ref() }.map(|x| unsafe { CStr::from_ptr(x) });
+    let prefix = match module_name {
+        Some(name) => variable_prefix(name.to_bytes()),
+        None => info.name.as_bytes(),
+    };
+
+    // SAFETY: configuration handlers always receive a valid `cf` pointer with a valid pool.
+    let pool = unsafe { Pool::from_ngx_pool(cf.pool) };
+
+    for (suffix, value) in [(&b"_version"[..], &info.version), (&b"_build"[..], &info.build)] {
+        let mut name = variable_name(&pool, prefix, suffix).ok_or(AllocError)?;
+
+        let var = unsafe { ngx_http_add_variable(cf, &raw mut name, 0).as_mut() };
+        let Some(var) = var else {
+            ngx_conf_log_error!(NGX_LOG_EMERG, cf, "failed to add variable \"{name}\"");
+            return Err(AllocError);
+        };
+
+        var.get_handler = Some(build_info_variable);
+        var.data = ptr::from_ref(value) as usize;
+    }
+
+    Ok(())
+}
+
+/// Strips the conventional `ngx_http_` prefix and `_module` suffix from the module name.
+fn variable_prefix(name: &[u8]) -> &[u8] {
+    let name = name.strip_prefix(b"ngx_http_").unwrap_or(name);
+    let name = name.strip_suffix(b"_module").unwrap_or(name);
+    if name.is_empty() { b"module" } else { name }
+}
+
+/// Concatenates a variable name in the pool memory, replacing characters not allowed in nginx
+/// variable names.
+fn variable_name(pool: &Pool, prefix: &[u8], suffix: &[u8]) -> Option<ngx_str_t> {
+    let len = prefix.len() + suffix.len();
+    let data = pool.alloc_unaligned(len).cast::<u8>();
+    if data.is_null() {
+        return None;
+    }
+
+    // SAFETY: `data` points to `len` bytes of uninitialized memory allocated above.
+    let buf = unsafe {
+        ptr::copy_nonoverlapping(prefix.as_ptr(), data, prefix.len());
+        ptr::copy_nonoverlapping(suffix.as_ptr(), data.add(prefix.len()), suffix.len());
+        core::slice::from_raw_parts_mut(data, len)
+    };
+
+    for b in buf.iter_mut() {
+        if !b.is_ascii_alphanumeric() {
+            *b = b'_';
+        }
+    }
+
+    Some(ngx_str_t { data, len })
+}
+
+unsafe extern "C" fn build_info_variable(
+    _r: *mut ngx_http_request_t,
+    v: *mut ngx_variable_value_t,
+    data: usize,
+) -> ngx_int_t {
+    // SAFETY: `data` is set by add_build_info_variables to a field of a static ModuleBuildInfo.
+    let value: &'static str = unsafe { *(data as *const &'static str) };
+    let v = unsafe { &mut *v };
+
+    v.data = value.as_ptr().cast_mut();
+    v.set_len(value.len() as _);
+    v.set_valid(1);
+    v.set_no_cacheable(0);
+    v.set_not_found(0);
+
+    Status::NGX_OK.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::variable_prefix;
+
+    #[test]
+    fn test_variable_prefix() {
+        assert_eq!(variable_prefix(b"ngx_http_foo_module"), b"foo");
+        assert_eq!(variable_prefix(b"ngx_http_foo_bar_module"), b"foo_bar");
+        assert_eq!(variable_prefix(b"custom"), b"custom");
+        assert_eq!(variable_prefix(b"ngx_http__module"), b"module");
+    }
+}
@@ -0,0 +1,220 @@
+use core::fmt;
+
+use crate::core::{NgxStr, Status, time};
+use crate::ffi::*;
+use crate::http::{Request, compat};
+
+/// Status of a response with respect to the cache, as reported by `$upstream_cache_status`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CacheStatus {
+    /// The response was not found in the cache.
+    Miss,
+    /// The cache was bypassed with `proxy_cache_bypass` or a similar directive.
+    Bypass,
+    /// The cached response has expired and was fetched again.
+    Expired,
+    /// A stale cached response was served.
+    Stale,
+    /// A stale cached response was served while the cache entry is being updated.
+    Updating,
+    /// The expired cached response was revalidated with a conditional request.
+    Revalidated,
+    /// The response was served from the cache.
+    Hit,
+    /// The response was not cached because of `proxy_cache_min_uses` or a similar directive.
+    Scarce,
+}
+
+impl CacheStatus {
+    fn from_ngx(status: ngx_uint_t) -> Option<Self> {
+        let status = match status as u32 {
+            NGX_HTTP_CACHE_MISS => CacheStatus::Miss,
+            NGX_HTTP_CACHE_BYPASS => CacheStatus::Bypass,
+            NGX_HTTP_CACHE_EXPIRED => CacheStatus::Expired,
+            NGX_HTTP_CACHE_STALE => CacheStatus::Stale,
+            NGX_HTTP_CACHE_UPDATING => CacheStatus::Updating,
+            NGX_HTTP_CACHE_REVALIDATED => CacheStatus::Revalidated,
+            NGX_HTTP_CACHE_HIT => CacheStatus::Hit,
+            NGX_HTTP_CACHE_SCARCE => CacheStatus::Scarce,
+            _ => return None,
+        };
+        Some(status)
+    }
+
+    /// Returns the status name, as reported by `$upstream_cache_status`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CacheStatus::Miss => "MISS",
+            CacheStatus::Bypass => "BYPASS",
+            CacheStatus::Expired => "EXPIRED",
+            CacheStatus::Stale => "STALE",
+            CacheStatus::Updating => "UPDATING",
+            CacheStatus::Revalidated => "REVALIDATED",
+            CacheStatus::Hit => "HIT",
+            CacheStatus::Scarce => "SCARCE",
+        }
+    }
+}
+
+impl fmt::Display for CacheStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Cache control for a response produced by an upstream module.
+///
+/// Equivalent to the values of the `X-Accel-Expires` upstream response header.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CacheControl {
+    /// Do not cache the response (`X-Accel-Expires: 0`).
+    NoCache,
+    /// Cache the response for the specified number of seconds (`X-Accel-Expires: <seconds>`).
+    ExpiresIn(time_t),
+    /// Cache the response until the specified time (`X-Accel-Expires: @<time>`).
+    ExpiresAt(time_t),
+}
+
+/// Wrapper for an [`ngx_http_cache_t`] cache entry of a request.
+#[repr(transparent)]
+pub struct HttpCache(ngx_http_cache_t);
+
+impl AsRef<ngx_http_cache_t> for HttpCache {
+    fn as_ref(&self) -> &ngx_http_cache_t {
+        &self.0
+    }
+}
+
+impl AsMut<ngx_http_cache_t> for HttpCache {
+    fn as_mut(&mut self) -> &mut ngx_http_cache_t {
+        &mut self.0
+    }
+}
+
+impl HttpCache {
+    /// Returns the cache key hash.
+    pub fn key(&self) -> &[u8] {
+        &self.0.key
+    }
+
+    /// Returns the cache file name, if already known.
+    pub fn file_name(&self) -> Option<&NgxStr> {
+        if self.0.file.name.len == 0 {
+            return None;
+        }
+        // SAFETY: the file name is allocated from the request pool.
+        Some(unsafe { NgxStr::from_ngx_str(self.0.file.name) })
+    }
+
+    /// Returns the expiration time of the cached response.
+    pub fn valid_sec(&self) -> time_t {
+        self.0.valid_sec
+    }
+
+    /// Sets the expiration time of the cached response.
+    pub fn set_valid_sec(&mut self, time: time_t) {
+        self.0.valid_sec = time;
+    }
+
+    /// Returns the number of seconds a stale response may be served while the entry is being
+    /// updated (`stale-while-revalidate`).
+    pub fn updating_sec(&self) -> time_t {
+        self.0.updating_sec
+    }
+
+    /// Sets the number of seconds a stale response may be served while the entry is being
+    /// updated.
+    pub fn set_updating_sec(&mut self, sec: time_t) {
+        self.0.updating_sec = sec;
+    }
+
+    /// Returns the number of seconds a stale response may be served in case of an error
+    /// (`stale-if-error`).
+    pub fn error_sec(&self) -> time_t {
+        self.0.error_sec
+    }
+
+    /// Sets the number of seconds a stale response may be served in case of an error.
+    pub fn set_error_sec(&mut self, sec: time_t) {
+        self.0.error_sec = sec;
+    }
+
+    /// Returns the `Date` of the cached response.
+    pub fn date(&self) -> time_t {
+        self.0.date
+    }
+
+    /// Returns the `Last-Modified` time of the cached response.
+    pub fn last_modified(&self) -> time_t {
+        self.0.last_modified
+    }
+
+    /// Returns the offset of the response body in the cache file.
+    pub fn body_start(&self) -> usize {
+        self.0.body_start
+    }
+}
+
+impl Request {
+    /// Returns the cache entry of the request, if the request is processed with a cache enabled.
+    pub fn cache(&self) -> Option<&HttpCache> {
+        // SAFETY: `cache` is either NULL or allocated from the request pool.
+        unsafe { self.as_ref().cache.cast::<HttpCache>().as_ref() }
+    }
+
+    /// Returns the mutable cache entry of the request, if the request is processed with a cache
+    /// enabled.
+    pub fn cache_mut(&mut self) -> Option<&mut HttpCache> {
+        // SAFETY: `cache` is either NULL or allocated from the request pool.
+        unsafe { self.as_mut().cache.cast::<HttpCache>().as_mut() }
+    }
+
+    /// Returns the upstream cache status of the request.
+    pub fn upstream_cache_status(&self) -> Option<CacheStatus> {
+        // SAFETY: `upstream` is either NULL or allocated from the request pool.
+        let u = unsafe { self.as_ref().upstream.as_ref()? };
+        CacheStatus::from_ngx(u.cache_status)
+    }
+
+    /// Controls caching of the response received by an upstream module.
+    ///
+    /// Should be called from the `process_header` upstream callback. Follows the processing of the
+    /// `X-Accel-Expires` header by nginx, including the `ignore_headers` setting of the upstream
+    /// configuration.
+    pub fn set_upstream_cache_control(&mut self, control: CacheControl) -> Status {
+        let r = self.as_mut();
+
+        // SAFETY: `upstream` is either NULL or allocated from the request pool.
+        let Some(u) = (unsafe { r.upstream.as_mut() }) else {
+            return Status::NGX_DECLINED;
+        };
+
+        // SAFETY: the upstream configuration is always set for an active upstream.
+        let ignore_headers = unsafe { (*u.conf).ignore_headers };
+        if ignore_headers & (NGX_HTTP_UPSTREAM_IGN_XA_EXPIRES as ngx_uint_t) != 0 {
+            return Status::NGX_OK;
+        }
+
+        // SAFETY: `cache` is either NULL or allocated from the request pool.
+        let Some(cache) = (unsafe { r.cache.as_mut() }) else {
+            return Status::NGX_OK;
+        };
+
+        if u.cacheable() == 0 {
+            return Status::NGX_OK;
+        }
+
+        cache.valid_sec = match control {
+            CacheControl::NoCache => {
+                u.set_cacheable(0);
+                return Status::NGX_OK;
+            }
+            CacheControl::ExpiresIn(sec) => time() + sec,
+            CacheControl::ExpiresAt(time) => time,
+        };
+
+        compat::reset_upstream_cache_flags(&mut u.headers_in);
+
+        Status::NGX_OK
+    }
+}
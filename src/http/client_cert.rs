@@ -0,0 +1,349 @@
+use core::fmt;
+use core::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use core::slice;
+
+use crate::core::{FeatureDisabled, NgxStr, Pool, pem_to_der};
+use crate::ffi::{ngx_encode_base64, ngx_encode_base64url, ngx_str_t};
+use crate::http::Request;
+
+/// Client certificate of a TLS connection.
+///
+/// The certificate is stored in the DER encoding and can be serialized into request headers for
+/// the upstream servers, e.g. as the `Client-Cert` header defined in [RFC 9440].
+///
+/// [RFC 9440]: https://datatracker.ietf.org/doc/html/rfc9440
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ClientCertificate<'a> {
+    der: &'a [u8],
+}
+
+/// Entry of the subject alternative name certificate extension.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SubjectAltName<'a> {
+    /// DNS name.
+    Dns(&'a [u8]),
+    /// Email address.
+    Email(&'a [u8]),
+    /// Uniform resource identifier.
+    Uri(&'a [u8]),
+    /// IP address.
+    Ip(IpAddr),
+    /// Other type of the name, with the context-specific tag and the DER-encoded value.
+    Other(u8, &'a [u8]),
+}
+
+impl<'a> ClientCertificate<'a> {
+    /// Creates a certificate from the DER encoding.
+    pub fn from_der(der: &'a [u8]) -> Self {
+        Self { der }
+    }
+
+    /// Returns the client certificate of the request connection.
+    ///
+    /// The certificate is decoded into the request pool. Returns `None` if the client did not
+    /// present a certificate, the connection does not use TLS, or the certificate cannot be
+    /// decoded. The certificate is not required to be verified; see [`Request::client_verify`].
+    pub fn from_request(r: &'a Request) -> Result<Option<Self>, FeatureDisabled> {
+        let Some(pem) = ssl_variable(r, SslVariable::RawCertificate)? else {
+            return Ok(None);
+        };
+        let pool = r.pool();
+
+        let buf = pool.alloc_unaligned(pem.len()).cast::<u8>();
+        if buf.is_null() {
+            return Ok(None);
+        }
+
+        // SAFETY: the buffer is large enough for the decoded PEM body.
+        let buf = unsafe { slice::from_raw_parts_mut(buf, pem.len()) };
+        let Some(len) = pem_to_der(pem.as_bytes(), buf) else {
+            return Ok(None);
+        };
+
+        Ok(Some(Self { der: &buf[..len] }))
+    }
+
+    /// Returns the DER encoding of the certificate.
+    pub fn der(&self) -> &'a [u8] {
+        self.der
+    }
+
+    /// Returns the certificate encoded with base64url, without padding.
+    pub fn to_base64url<'p>(&self, pool: &'p Pool) -> Option<&'p NgxStr> {
+        encode(pool, self.der, (self.der.len() * 4).div_ceil(3), 0, |dst, src| unsafe {
+            ngx_encode_base64url(dst, src)
+        })
+    }
+
+    /// Returns the certificate formatted as the value of the RFC 9440 `Client-Cert` header: a
+    /// structured field byte sequence with the base64-encoded DER certificate.
+    pub fn to_client_cert_header<'p>(&self, pool: &'p Pool) -> Option<&'p NgxStr> {
+        let len = self.der.len().div_ceil(3) * 4;
+        encode(pool, self.der, len, 1, |dst, src| unsafe { ngx_encode_base64(dst, src) })
+    }
+
+    /// Returns an iterator over the subject alternative names of the certificate.
+    ///
+    /// The iterator is empty if the certificate does not have the extension or cannot be parsed.
+    pub fn subject_alt_names(&self) -> SubjectAltNames<'a> {
+        SubjectAltNames(find_extension(self.der, SUBJECT_ALT_NAME).and_then(|ext| {
+            let (tag, names, _) = read_tlv(ext)?;
+            (tag == TAG_SEQUENCE).then_some(names)
+        }))
+    }
+}
+
+impl fmt::Display for SubjectAltName<'_> {
+    /// Formats the name as in the OpenSSL text output, e.g. `DNS:example.com` or `IP:127.0.0.1`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Dns(x) => write!(f, "DNS:{}", x.escape_ascii()),
+            Self::Email(x) => write!(f, "email:{}", x.escape_ascii()),
+            Self::Uri(x) => write!(f, "URI:{}", x.escape_ascii()),
+            Self::Ip(x) => write!(f, "IP:{x}"),
+            Self::Other(tag, _) => write!(f, "othername:<{tag}>"),
+        }
+    }
+}
+
+/// Iterator over the subject alternative names of a certificate.
+#[derive(Clone, Debug)]
+pub struct SubjectAltNames<'a>(Option<&'a [u8]>);
+
+impl<'a> Iterator for SubjectAltNames<'a> {
+    type Item = SubjectAltName<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let Some((tag, value, rest)) = read_tlv(self.0?) else {
+            self.0 = None;
+            return None;
+        };
+        self.0 = Some(rest);
+
+        let name = match tag {
+            0x81 => SubjectAltName::Email(value),
+            0x82 => SubjectAltName::Dns(value),
+            0x86 => SubjectAltName::Uri(value),
+            0x87 => match <[u8; 4]>::try_from(value) {
+                Ok(ip) => SubjectAltName::Ip(Ipv4Addr::from(ip).into()),
+                Err(_) => match <[u8; 16]>::try_from(value) {
+                    Ok(ip) => SubjectAltName::Ip(Ipv6Addr::from(ip).into()),
+                    Err(_) => SubjectAltName::Other(tag & 0x1f, value),
+                },
+            },
+            _ => SubjectAltName::Other(tag & 0x1f, value),
+        };
+
+        Some(name)
+    }
+}
+
+impl Request {
+    /// Returns the subject DN of the client certificate, as in the `$ssl_client_s_dn` variable.
+    pub fn client_subject_dn(&self) -> Result<Option<&NgxStr>, FeatureDisabled> {
+        ssl_variable(self, SslVariable::SubjectDn)
+    }
+
+    /// Returns the result of the client certificate verification, as in the `$ssl_client_verify`
+    /// variable: `SUCCESS`, `FAILED:reason` or `NONE`.
+    pub fn client_verify(&self) -> Result<Option<&NgxStr>, FeatureDisabled> {
+        ssl_variable(self, SslVariable::ClientVerify)
+    }
+}
+
+#[derive(Clone, Copy)]
+enum SslVariable {
+    RawCertificate,
+    SubjectDn,
+    ClientVerify,
+}
+
+#[cfg(ngx_feature = "http_ssl")]
+fn ssl_variable(r: &Request, var: SslVariable) -> Result<Option<&NgxStr>, FeatureDisabled> {
+    use crate::ffi::{
+        NGX_OK, ngx_connection_t, ngx_int_t, ngx_pool_t, ngx_ssl_get_client_verify,
+        ngx_ssl_get_raw_certificate, ngx_ssl_get_subject_dn,
+    };
+
+    type Getter =
+        unsafe extern "C" fn(*mut ngx_connection_t, *mut ngx_pool_t, *mut ngx_str_t) -> ngx_int_t;
+
+    let get: Getter = match var {
+        SslVariable::RawCertificate => ngx_ssl_get_raw_certificate,
+        SslVariable::SubjectDn => ngx_ssl_get_subject_dn,
+        SslVariable::ClientVerify => ngx_ssl_get_client_verify,
+    };
+
+    let c = r.as_ref().connection;
+
+    // SAFETY: the connection is valid for the lifetime of the request.
+    if unsafe { (*c).ssl.is_null() } {
+        return Ok(None);
+    }
+
+    let mut s = ngx_str_t::default();
+    if unsafe { get(c, r.as_ref().pool, &raw mut s) } != NGX_OK as ngx_int_t || s.len == 0 {
+        return Ok(None);
+    }
+
+    // SAFETY: the value is allocated from the request pool.
+    Ok(Some(unsafe { NgxStr::from_ngx_str(s) }))
+}
+
+/// Stub for nginx builds without `http_ssl`.
+#[cfg(not(ngx_feature = "http_ssl"))]
+fn ssl_variable(_r: &Request, _var: SslVariable) -> Result<Option<&NgxStr>, FeatureDisabled> {
+    Err(FeatureDisabled(crate::core::Feature::HttpSsl))
+}
+
+fn encode<'p>(
+    pool: &'p Pool,
+    src: &[u8],
+    len: usize,
+    quote: usize,
+    f: impl FnOnce(*mut ngx_str_t, *mut ngx_str_t),
+) -> Option<&'p NgxStr> {
+    let data = pool.alloc_unaligned(len + 2 * quote).cast::<u8>();
+    if data.is_null() {
+        return None;
+    }
+
+    let mut dst = ngx_str_t { len: 0, data: unsafe { data.add(quote) } };
+    let mut src = ngx_str_t { len: src.len(), data: src.as_ptr().cast_mut() };
+    f(&raw mut dst, &raw mut src);
+
+    if quote != 0 {
+        // SAFETY: the buffer has space for the delimiters around the encoded value.
+        unsafe {
+            *data = b':';
+            *data.add(dst.len + 1) = b':';
+        }
+    }
+
+    let s = ngx_str_t { len: dst.len + 2 * quote, data };
+    // SAFETY: the value is allocated from `pool`.
+    Some(unsafe { NgxStr::from_ngx_str(s) })
+}
+
+const TAG_SEQUENCE: u8 = 0x30;
+const TAG_OCTET_STRING: u8 = 0x04;
+const TAG_OID: u8 = 0x06;
+const TAG_EXTENSIONS: u8 = 0xa3;
+
+/// `id-ce-subjectAltName`, 2.5.29.17.
+const SUBJECT_ALT_NAME: &[u8] = &[0x55, 0x1d, 0x11];
+
+/// Reads a DER-encoded element and returns the tag, the value and the remaining input.
+fn read_tlv(input: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let (&tag, input) = input.split_first()?;
+    let (&len, mut input) = input.split_first()?;
+
+    let len = if len < 0x80 {
+        usize::from(len)
+    } else {
+        let n = usize::from(len & 0x7f);
+        if n == 0 || n > core::mem::size_of::<usize>() || n > input.len() {
+            return None;
+        }
+        let (bytes, rest) = input.split_at(n);
+        input = rest;
+        bytes.iter().fold(0usize, |acc, &x| (acc << 8) | usize::from(x))
+    };
+
+    if len > input.len() {
+        return None;
+    }
+
+    let (value, rest) = input.split_at(len);
+    Some((tag, value, rest))
+}
+
+/// Returns the value of the certificate extension with the specified object identifier.
+fn find_extension<'a>(der: &'a [u8], oid: &[u8]) -> Option<&'a [u8]> {
+    let (_, cert, _) = read_tlv(der)?;
+    let (_, mut tbs, _) = read_tlv(cert)?;
+
+    let mut extensions = loop {
+        let (tag, value, rest) = read_tlv(tbs)?;
+        if tag == TAG_EXTENSIONS {
+            let (tag, value, _) = read_tlv(value)?;
+            if tag != TAG_SEQUENCE {
+                return None;
+            }
+            break value;
+        }
+        tbs = rest;
+    };
+
+    while !extensions.is_empty() {
+        let (_, mut ext, rest) = read_tlv(extensions)?;
+        extensions = rest;
+
+        let (tag, id, tail) = read_tlv(ext)?;
+        if tag != TAG_OID || id != oid {
+            continue;
+        }
+        ext = tail;
+
+        // Skip the optional `critical` flag.
+        loop {
+            let (tag, value, tail) = read_tlv(ext)?;
+            if tag == TAG_OCTET_STRING {
+                return Some(value);
+            }
+            ext = tail;
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PEM: &[u8] = b"-----BEGIN CERTIFICATE-----
+MIIB0DCCAXegAwIBAgIUPoXgu7oGQPEhNvDD0457Mb9sUmowCgYIKoZIzj0EAwIw
+ETEPMA0GA1UEAwwGY2xpZW50MB4XDTI2MTAxNjAwMzM0NFoXDTM2MTAxMzAwMzM0
+NFowETEPMA0GA1UEAwwGY2xpZW50MFkwEwYHKoZIzj0CAQYIKoZIzj0DAQcDQgAE
+LrhAsHCBbSYHkPJU5d3w7cM0Ln2BMFQShogq76jQ1Lm/fWGJGLkz9TcCQX5meida
+Yx+224vy9J/97sWNmF7HbqOBrDCBqTAdBgNVHQ4EFgQUsU5TjJ2qmOjhcLb3JZOS
+NTbpD+cwHwYDVR0jBBgwFoAUsU5TjJ2qmOjhcLb3JZOSNTbpD+cwDwYDVR0TAQH/
+BAUwAwEB/zBWBgNVHREETzBNggtleGFtcGxlLmNvbYcEfwAAAYcQAAAAAAAAAAAA
+AAAAAAAAAYEQdXNlckBleGFtcGxlLmNvbYYUaHR0cHM6Ly9leGFtcGxlLmNvbS8w
+CgYIKoZIzj0EAwIDRwAwRAIgaBJtmi2jRltzdEQ1g75xUMuvHSXXfJRkxFzxYgX2
+UhYCIAc3w+/LpwUjXsRuTJIUOKhKo/9oOzeyQKZ3N2Sp75oQ
+-----END CERTIFICATE-----
+";
+
+    #[test]
+    fn test_subject_alt_names() {
+        let mut buf = [0u8; PEM.len()];
+        let len = pem_to_der(PEM, &mut buf).unwrap();
+        assert_eq!(len, 468);
+        assert_eq!(&buf[..2], &[0x30, 0x82]);
+
+        let cert = ClientCertificate::from_der(&buf[..len]);
+        let mut names = cert.subject_alt_names();
+
+        assert_eq!(names.next(), Some(SubjectAltName::Dns(b"example.com")));
+        assert_eq!(names.next(), Some(SubjectAltName::Ip(Ipv4Addr::LOCALHOST.into())));
+        assert_eq!(names.next(), Some(SubjectAltName::Ip(Ipv6Addr::LOCALHOST.into())));
+        assert_eq!(names.next(), Some(SubjectAltName::Email(b"user@example.com")));
+        assert_eq!(names.next(), Some(SubjectAltName::Uri(b"https://example.com/")));
+        assert_eq!(names.next(), None);
+    }
+
+    #[test]
+    fn test_invalid() {
+        let mut buf = [0u8; 64];
+        assert_eq!(pem_to_der(b"MIIB", &mut buf), None);
+        assert_eq!(
+            pem_to_der(b"-----BEGIN CERTIFICATE-----\n*\n-----END CERTIFICATE-----", &mut buf),
+            None
+        );
+
+        let cert = ClientCertificate::from_der(&[0x30, 0x03, 0x30, 0x01]);
+        assert_eq!(cert.subject_alt_names().next(), None);
+    }
+}
@@ -0,0 +1,182 @@
+//! Compatibility layer for the differences between the supported nginx versions.
+//!
+//! The helpers hide the changes in the layout of the nginx structures, so the request APIs can
+//! behave identically regardless of the nginx version the module is built against.
+
+use crate::core::NgxStr;
+use crate::ffi::{ngx_http_headers_in_t, ngx_http_upstream_headers_in_t, ngx_table_elt_t};
+use crate::http::Request;
+
+/// Prepares a header entry allocated with `ngx_list_push` for initialization.
+///
+/// The header is not sent until the `hash` is set to a non-zero value.
+pub(crate) fn init_table_elt(h: &mut ngx_table_elt_t) {
+    h.hash = 0;
+    // nginx 1.23.0 links the headers with the same name, see 5cdcc9d2ab2a
+    #[cfg(nginx1_23_0)]
+    {
+        h.next = core::ptr::null_mut();
+    }
+}
+
+/// Resets the cache-related flags of the upstream response headers.
+///
+/// The flags were introduced in nginx 1.23.0 to track the caching directives from multiple
+/// headers; older versions have no such state.
+pub(crate) fn reset_upstream_cache_flags(headers: &mut ngx_http_upstream_headers_in_t) {
+    #[cfg(nginx1_23_0)]
+    {
+        headers.set_no_cache(0);
+        headers.set_expired(0);
+    }
+    #[cfg(not(nginx1_23_0))]
+    let _ = headers;
+}
+
+/// Iterator over the values of a request header that can be repeated.
+///
+/// nginx before 1.23.0 keeps such headers in an array of pointers; newer versions link the
+/// entries in a list with the `next` field.
+pub struct HeaderValues<'a> {
+    #[cfg(nginx1_23_0)]
+    next: *const ngx_table_elt_t,
+    #[cfg(not(nginx1_23_0))]
+    iter: core::slice::Iter<'a, *mut ngx_table_elt_t>,
+    _marker: core::marker::PhantomData<&'a ngx_table_elt_t>,
+}
+
+impl<'a> Iterator for HeaderValues<'a> {
+    type Item = &'a NgxStr;
+
+    #[cfg(nginx1_23_0)]
+    fn next(&mut self) -> Option<Self::Item> {
+        // SAFETY: the header entries are valid for the lifetime of the request.
+        let h = unsafe { self.next.as_ref()? };
+        self.next = h.next;
+        Some(unsafe { NgxStr::from_ngx_str(h.value) })
+    }
+
+    #[cfg(not(nginx1_23_0))]
+    fn next(&mut self) -> Option<Self::Item> {
+        // SAFETY: the header entries are valid for the lifetime of the request.
+        let h = unsafe { self.iter.next()?.as_ref()? };
+        Some(unsafe { NgxStr::from_ngx_str(h.value) })
+    }
+}
+
+macro_rules! header_values {
+    ($headers:expr, $new:ident, $old:ident) => {{
+        let headers: &ngx_http_headers_in_t = $headers;
+
+        #[cfg(nginx1_23_0)]
+        let values = HeaderValues { next: headers.$new, _marker: core::marker::PhantomData };
+
+        // SAFETY: the array is initialized on demand, an empty array has no elements.
+        #[cfg(not(nginx1_23_0))]
+        let values = HeaderValues {
+            iter: if headers.$old.elts.is_null() {
+                [].iter()
+            } else {
+                unsafe { headers.$old.as_slice() }.iter()
+            },
+            _marker: core::marker::PhantomData,
+        };
+
+        values
+    }};
+}
+
+impl Request {
+    /// Returns the values of all the `Cookie` request headers.
+    pub fn cookie_headers(&self) -> HeaderValues<'_> {
+        header_values!(&self.as_ref().headers_in, cookie, cookies)
+    }
+
+    /// Returns the values of all the `X-Forwarded-For` request headers.
+    #[cfg(ngx_feature = "http_x_forwarded_for")]
+    pub fn x_forwarded_for_headers(&self) -> HeaderValues<'_> {
+        header_values!(&self.as_ref().headers_in, x_forwarded_for, x_forwarded_for)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use core::mem;
+    use std::vec::Vec;
+
+    use super::*;
+    use crate::ffi::ngx_str_t;
+
+    fn table_elt(value: &'static [u8]) -> ngx_table_elt_t {
+        // SAFETY: an all-zero header entry is valid.
+        let mut h: ngx_table_elt_t = unsafe { mem::zeroed() };
+        h.hash = 1;
+        h.value = ngx_str_t { len: value.len(), data: value.as_ptr().cast_mut() };
+        h
+    }
+
+    fn collect(values: HeaderValues<'_>) -> Vec<&[u8]> {
+        values.map(NgxStr::as_bytes).collect()
+    }
+
+    #[test]
+    fn test_init_table_elt() {
+        let mut h = table_elt(b"a");
+        #[cfg(nginx1_23_0)]
+        let mut other = table_elt(b"b");
+        #[cfg(nginx1_23_0)]
+        {
+            h.next = &raw mut other;
+        }
+
+        init_table_elt(&mut h);
+        assert_eq!(h.hash, 0);
+        #[cfg(nginx1_23_0)]
+        assert!(h.next.is_null());
+    }
+
+    #[test]
+    #[cfg(nginx1_23_0)]
+    fn test_reset_upstream_cache_flags() {
+        // SAFETY: an all-zero value is valid.
+        let mut headers: ngx_http_upstream_headers_in_t = unsafe { mem::zeroed() };
+        headers.set_no_cache(1);
+        headers.set_expired(1);
+
+        reset_upstream_cache_flags(&mut headers);
+        assert_eq!(headers.no_cache(), 0);
+        assert_eq!(headers.expired(), 0);
+    }
+
+    #[test]
+    fn test_header_values() {
+        // SAFETY: an all-zero value is valid: no headers.
+        let mut headers: ngx_http_headers_in_t = unsafe { mem::zeroed() };
+        assert!(collect(header_values!(&headers, cookie, cookies)).is_empty());
+
+        let mut first = table_elt(b"a=1");
+        let mut second = table_elt(b"b=2");
+
+        // nginx 1.23.0 and newer link the headers with `next`.
+        #[cfg(nginx1_23_0)]
+        {
+            first.next = &raw mut second;
+            headers.cookie = &raw mut first;
+        }
+
+        // Older versions store the headers in an array of pointers.
+        #[cfg(not(nginx1_23_0))]
+        let mut elts = [&raw mut first, &raw mut second];
+        #[cfg(not(nginx1_23_0))]
+        {
+            headers.cookies.elts = elts.as_mut_ptr().cast();
+            headers.cookies.nelts = elts.len();
+            headers.cookies.size = mem::size_of::<*mut ngx_table_elt_t>();
+            headers.cookies.nalloc = elts.len();
+        }
+
+        assert_eq!(collect(header_values!(&headers, cookie, cookies)), [&b"a=1"[..], b"b=2"]);
+    }
+}
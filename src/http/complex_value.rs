@@ -0,0 +1,232 @@
+use core::ffi::{c_char, c_void};
+use core::ptr::{self, NonNull};
+use core::{mem, slice};
+
+use crate::core::{
+    ConfError, ConfUnset, NGX_CONF_DUPLICATE, NGX_CONF_ERROR, NGX_CONF_OK, NgxStr, Pool,
+};
+use crate::ffi::{
+    NGX_OK, ngx_command_t, ngx_conf_t, ngx_http_compile_complex_value,
+    ngx_http_compile_complex_value_t, ngx_http_complex_value_t, ngx_int_t, ngx_str_t,
+};
+use crate::http::{Merge, MergeConfigError, Request};
+
+/// Single directive argument compiled into a [complex value].
+///
+/// The value may contain variables, which are evaluated at request time:
+///
+/// ```no_run
+/// # use ngx::core::Status;
+/// # use ngx::ffi::{ngx_conf_t, ngx_str_t};
+/// # use ngx::http::{ConfComplexValue, Request};
+/// # fn f(cf: &mut ngx_conf_t, arg: &ngx_str_t, r: &Request) -> Result<(), Status> {
+/// // in a directive handler
+/// let key = ConfComplexValue::compile(cf, arg)?;
+///
+/// // in a request handler
+/// let key = key.evaluate(r).ok_or(Status::NGX_ERROR)?;
+/// # Ok(()) }
+/// ```
+///
+/// [complex value]: https://nginx.org/en/docs/dev/development_guide.html#http_complex_values
+#[derive(Clone, Copy, Debug)]
+pub struct ComplexValue(NonNull<ngx_http_complex_value_t>);
+
+/// Name of [`ComplexValue`] for the configuration-time code: `ConfComplexValue::compile`.
+pub type ConfComplexValue = ComplexValue;
+
+impl ComplexValue {
+    /// Compiles the value in the configuration pool.
+    pub fn compile(cf: &mut ngx_conf_t, value: &ngx_str_t) -> Result<ComplexValue, ConfError> {
+        // SAFETY: the configuration pool is valid for the duration of the configuration parsing.
+        let pool = unsafe { Pool::from_ngx_pool(cf.pool) };
+
+        let cv = NonNull::new(pool.calloc_type::<ngx_http_complex_value_t>())
+            .ok_or(ConfError::NoMemory)?;
+        // SAFETY: the value is allocated above.
+        unsafe { Self::compile_into(cf, value, cv.as_ptr()) }?;
+
+        Ok(Self(cv))
+    }
+
+    /// Compiles the value into the memory allocated by the caller.
+    ///
+    /// # Safety
+    ///
+    /// `cv` must point to a complex value allocated from the configuration pool.
+    unsafe fn compile_into(
+        cf: &mut ngx_conf_t,
+        value: &ngx_str_t,
+        cv: *mut ngx_http_complex_value_t,
+    ) -> Result<(), ConfError> {
+        let mut value = *value;
+
+        // SAFETY: an all-zero value is a valid initial state for the compiler, as in nginx.
+        let mut ccv: ngx_http_compile_complex_value_t = unsafe { mem::zeroed() };
+        ccv.cf = cf;
+        ccv.value = &raw mut value;
+        ccv.complex_value = cv;
+
+        if unsafe { ngx_http_compile_complex_value(&raw mut ccv) } != NGX_OK as ngx_int_t {
+            return Err(ConfError::InvalidValue);
+        }
+
+        Ok(())
+    }
+
+    /// Restores a value from the pointer returned by [`ComplexValue::as_ptr`], e.g. from the
+    /// `data` of a variable handler.
+    ///
+    /// # Safety
+    ///
+    /// The pointer must be obtained from [`ComplexValue::as_ptr`], and the configuration it was
+    /// compiled for must be alive.
+    pub unsafe fn from_ptr(ptr: *mut ngx_http_complex_value_t) -> Option<Self> {
+        NonNull::new(ptr).map(Self)
+    }
+
+    /// Returns the pointer to the compiled value.
+    pub fn as_ptr(&self) -> *mut ngx_http_complex_value_t {
+        self.0.as_ptr()
+    }
+
+    /// Returns the value if it does not contain variables.
+    pub fn as_static(&self) -> Option<&NgxStr> {
+        // SAFETY: the value is compiled and allocated from the configuration pool.
+        let cv = unsafe { self.0.as_ref() };
+        cv.lengths.is_null().then(|| unsafe { NgxStr::from_ngx_str(cv.value) })
+    }
+
+    /// Evaluates the value in the context of the request.
+    ///
+    /// Returns `None` if the evaluation failed.
+    pub fn evaluate<'r>(&self, r: &'r Request) -> Option<&'r NgxStr> {
+        // SAFETY: the value is compiled and allocated from the configuration pool.
+        r.get_complex_value(unsafe { self.0.as_ref() })
+    }
+}
+
+/// Directive arguments compiled into [complex values].
+///
+/// Each argument may contain variables, which are evaluated at request time. Arguments without
+/// variables are stored as is and do not incur any evaluation overhead.
+///
+/// Use [`complex_value_args_slot`] as the directive handler to fill a field of this type in the
+/// module configuration.
+///
+/// [complex values]: https://nginx.org/en/docs/dev/development_guide.html#http_complex_values
+#[derive(Clone, Copy, Debug)]
+pub struct ComplexValueArgs {
+    values: *mut ngx_http_complex_value_t,
+    len: usize,
+}
+
+impl Default for ComplexValueArgs {
+    fn default() -> Self {
+        Self::UNSET
+    }
+}
+
+impl ComplexValueArgs {
+    /// Unset value.
+    pub const UNSET: Self = Self { values: ptr::null_mut(), len: 0 };
+
+    /// Compiles the directive arguments in the configuration pool.
+    pub fn compile(cf: &mut ngx_conf_t, args: &[ngx_str_t]) -> Result<Self, ConfError> {
+        // SAFETY: the configuration pool is valid for the duration of the configuration parsing.
+        let pool = unsafe { Pool::from_ngx_pool(cf.pool) };
+
+        let values = pool.alloc(args.len() * mem::size_of::<ngx_http_complex_value_t>());
+        let values = values.cast::<ngx_http_complex_value_t>();
+        if values.is_null() {
+            return Err(ConfError::NoMemory);
+        }
+
+        for (i, arg) in args.iter().enumerate() {
+            // SAFETY: the array of `args.len()` values is allocated above.
+            unsafe { ComplexValue::compile_into(cf, arg, values.add(i)) }?;
+        }
+
+        Ok(Self { values, len: args.len() })
+    }
+
+    /// Returns `true` if the value was not set.
+    pub fn is_unset(&self) -> bool {
+        self.values.is_null()
+    }
+
+    /// Returns the number of arguments.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if there are no arguments.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the compiled arguments.
+    pub fn as_slice(&self) -> &[ngx_http_complex_value_t] {
+        if self.values.is_null() {
+            return &[];
+        }
+        // SAFETY: `values` points to `len` complex values initialized in `compile`.
+        unsafe { slice::from_raw_parts(self.values, self.len) }
+    }
+
+    /// Evaluates the argument at `index` in the context of the request.
+    ///
+    /// Returns `None` if there is no such argument or the evaluation failed.
+    pub fn evaluate<'r>(&self, r: &'r Request, index: usize) -> Option<&'r NgxStr> {
+        let cv = self.as_slice().get(index)?;
+        r.get_complex_value(cv)
+    }
+}
+
+impl Merge for ComplexValueArgs {
+    fn merge(&mut self, prev: &Self) -> Result<(), MergeConfigError> {
+        if self.is_unset() {
+            *self = *prev;
+        }
+        Ok(())
+    }
+}
+
+impl ConfUnset for ComplexValueArgs {
+    fn is_unset(&self) -> bool {
+        ComplexValueArgs::is_unset(self)
+    }
+}
+
+/// Directive handler compiling the arguments into a [`ComplexValueArgs`] field of the
+/// configuration.
+///
+/// The field is located at `offset` of the command, as with the nginx slot functions.
+///
+/// # Safety
+///
+/// The field at `cmd.offset` in `conf` must have the type [`ComplexValueArgs`].
+pub unsafe extern "C" fn complex_value_args_slot(
+    cf: *mut ngx_conf_t,
+    cmd: *mut ngx_command_t,
+    conf: *mut c_void,
+) -> *mut c_char {
+    // SAFETY: nginx passes valid pointers to the directive handlers.
+    let (cf, cmd) = unsafe { (&mut *cf, &*cmd) };
+    let field = unsafe { &mut *conf.byte_add(cmd.offset).cast::<ComplexValueArgs>() };
+
+    if field.is_set() {
+        return NGX_CONF_DUPLICATE;
+    }
+
+    // SAFETY: `cf.args` contains the directive name and arguments.
+    let args: &[ngx_str_t] = unsafe { (*cf.args).as_slice() };
+
+    match ComplexValueArgs::compile(cf, &args[1..]) {
+        Ok(value) => {
+            *field = value;
+            NGX_CONF_OK
+        }
+        Err(_) => NGX_CONF_ERROR,
+    }
+}
@@ -0,0 +1,289 @@
+use crate::ffi::{ngx_parse_http_time, ngx_table_elt_t, time_t};
+use crate::http::{Method, Request};
+
+/// Result of the conditional request evaluation.
+///
+/// See [RFC 9110, Section 13.2.2](https://datatracker.ietf.org/doc/html/rfc9110#section-13.2.2).
+#[allow(clippy::enum_variant_names)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConditionalResponse {
+    /// Send the full representation with the `200 OK` status.
+    ServeFull,
+    /// Send `304 Not Modified` without a body.
+    Serve304,
+    /// Send `412 Precondition Failed`.
+    Serve412,
+    /// Process the `Range` header and send the requested ranges.
+    ServeRange,
+}
+
+/// Validator of the `If-Range` header.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IfRange<'a> {
+    /// Entity tag.
+    ETag(&'a [u8]),
+    /// HTTP date, or `None` if the date cannot be parsed.
+    Date(Option<time_t>),
+}
+
+/// Conditional headers of a request.
+///
+/// Unparsable dates in `If-Modified-Since` and `If-Unmodified-Since` are ignored, as required by
+/// the specification. `If-Unmodified-Since` is also ignored for a resource without a modification
+/// date (RFC 9110, section 13.1.4).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Preconditions<'a> {
+    /// Value of the `If-Match` header.
+    pub if_match: Option<&'a [u8]>,
+    /// Value of the `If-None-Match` header.
+    pub if_none_match: Option<&'a [u8]>,
+    /// Parsed `If-Modified-Since` date.
+    pub if_modified_since: Option<time_t>,
+    /// Parsed `If-Unmodified-Since` date.
+    pub if_unmodified_since: Option<time_t>,
+    /// Parsed `If-Range` validator.
+    pub if_range: Option<IfRange<'a>>,
+    /// The request has a `Range` header.
+    pub range: bool,
+}
+
+/// Validators of the selected representation.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Validators<'a> {
+    /// Entity tag, including the quotes and the weakness indicator.
+    pub etag: Option<&'a [u8]>,
+    /// Last modification time.
+    pub last_modified: Option<time_t>,
+}
+
+impl<'a> Preconditions<'a> {
+    /// Collects the conditional headers of the request.
+    pub fn from_request(r: &'a Request) -> Self {
+        let headers = &r.as_ref().headers_in;
+
+        let if_range = header_value(headers.if_range).map(|value| {
+            if value.starts_with(b"\"") || value.starts_with(b"W/") {
+                IfRange::ETag(value)
+            } else {
+                IfRange::Date(parse_http_time(value))
+            }
+        });
+
+        Self {
+            if_match: header_value(headers.if_match),
+            if_none_match: header_value(headers.if_none_match),
+            if_modified_since: header_value(headers.if_modified_since).and_then(parse_http_time),
+            if_unmodified_since: header_value(headers.if_unmodified_since)
+                .and_then(parse_http_time),
+            if_range,
+            range: !headers.range.is_null(),
+        }
+    }
+
+    /// Evaluates the preconditions for a request with the specified `method`.
+    pub fn evaluate(&self, method: &Method, validators: &Validators) -> ConditionalResponse {
+        let safe = *method == Method::GET || *method == Method::HEAD;
+
+        if let Some(if_match) = self.if_match {
+            if !etag_matches(if_match, validators.etag, false) {
+                return ConditionalResponse::Serve412;
+            }
+        } else if let Some(since) = self.if_unmodified_since {
+            if validators.last_modified.is_some_and(|lm| lm > since) {
+                return ConditionalResponse::Serve412;
+            }
+        }
+
+        if let Some(if_none_match) = self.if_none_match {
+            if etag_matches(if_none_match, validators.etag, true) {
+                return if safe {
+                    ConditionalResponse::Serve304
+                } else {
+                    ConditionalResponse::Serve412
+                };
+            }
+        } else if let (true, Some(since), Some(lm)) =
+            (safe, self.if_modified_since, validators.last_modified)
+        {
+            if lm <= since {
+                return ConditionalResponse::Serve304;
+            }
+        }
+
+        if !self.range || *method != Method::GET {
+            return ConditionalResponse::ServeFull;
+        }
+
+        let range = match self.if_range {
+            None => true,
+            Some(IfRange::ETag(etag)) => etag_matches(etag, validators.etag, false),
+            Some(IfRange::Date(date)) => date.is_some() && date == validators.last_modified,
+        };
+
+        if range { ConditionalResponse::ServeRange } else { ConditionalResponse::ServeFull }
+    }
+}
+
+impl<'a> Validators<'a> {
+    /// Collects the validators from the response headers.
+    pub fn from_response(r: &'a Request) -> Self {
+        let headers = &r.as_ref().headers_out;
+        Self {
+            etag: header_value(headers.etag),
+            last_modified: (headers.last_modified_time != -1).then_some(headers.last_modified_time),
+        }
+    }
+}
+
+impl Request {
+    /// Evaluates the conditional headers of the request against the response `ETag` and
+    /// `Last-Modified` time.
+    ///
+    /// The response validators should be set before calling this method.
+    pub fn evaluate_preconditions(&self) -> ConditionalResponse {
+        Preconditions::from_request(self).evaluate(&self.method(), &Validators::from_response(self))
+    }
+}
+
+fn header_value<'a>(h: *const ngx_table_elt_t) -> Option<&'a [u8]> {
+    // SAFETY: the header pointers in a request are either NULL or point to list elements
+    // allocated from the request pool.
+    unsafe { h.as_ref() }.map(|h| h.value.as_bytes())
+}
+
+fn parse_http_time(value: &[u8]) -> Option<time_t> {
+    let time = unsafe { ngx_parse_http_time(value.as_ptr().cast_mut(), value.len()) };
+    (time != -1).then_some(time)
+}
+
+/// Splits an entity tag into the weakness indicator and the opaque tag.
+fn split_etag(etag: &[u8]) -> Option<(bool, &[u8])> {
+    let (weak, etag) = match etag.strip_prefix(b"W/") {
+        Some(etag) => (true, etag),
+        None => (false, etag),
+    };
+    let etag = etag.strip_prefix(b"\"")?.strip_suffix(b"\"")?;
+    Some((weak, etag))
+}
+
+/// Checks if `etag` matches any of the entity tags in `list`.
+///
+/// See [RFC 9110, Section 8.8.3.2](https://datatracker.ietf.org/doc/html/rfc9110#section-8.8.3.2).
+fn etag_matches(list: &[u8], etag: Option<&[u8]>, weak: bool) -> bool {
+    // `*` matches any current representation, with or without an entity tag.
+    if list.trim_ascii() == b"*" {
+        return true;
+    }
+
+    let Some((etag_weak, etag)) = etag.and_then(split_etag) else {
+        return false;
+    };
+
+    let mut rest = list;
+
+    loop {
+        rest = rest.trim_ascii_start();
+        while let Some(tail) = rest.strip_prefix(b",") {
+            rest = tail.trim_ascii_start();
+        }
+
+        if rest.is_empty() {
+            return false;
+        }
+
+        let (w, tail) = match rest.strip_prefix(b"W/") {
+            Some(tail) => (true, tail),
+            None => (false, rest),
+        };
+
+        let Some(tail) = tail.strip_prefix(b"\"") else {
+            return false;
+        };
+        let Some(end) = tail.iter().position(|&x| x == b'"') else {
+            return false;
+        };
+
+        if &tail[..end] == etag && (weak || !(w || etag_weak)) {
+            return true;
+        }
+
+        rest = &tail[end + 1..];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STRONG: Validators =
+        Validators { etag: Some(b"\"abc\""), last_modified: Some(1_000_000) };
+    const WEAK: Validators = Validators { etag: Some(b"W/\"abc\""), last_modified: None };
+
+    #[test]
+    fn test_etag_matches() {
+        assert!(etag_matches(b"\"abc\"", Some(b"\"abc\""), false));
+        assert!(etag_matches(b"\"x\", \"abc\"", Some(b"\"abc\""), false));
+        assert!(etag_matches(b"*", Some(b"W/\"abc\""), false));
+        assert!(!etag_matches(b"W/\"abc\"", Some(b"\"abc\""), false));
+        assert!(etag_matches(b"W/\"abc\"", Some(b"\"abc\""), true));
+        assert!(etag_matches(b" ,\"x,y\" , W/\"abc\"", Some(b"W/\"abc\""), true));
+        assert!(!etag_matches(b"\"abc\"", None, true));
+        assert!(etag_matches(b" * ", None, false));
+        assert!(!etag_matches(b"abc", Some(b"\"abc\""), true));
+    }
+
+    #[test]
+    fn test_evaluate() {
+        use ConditionalResponse::*;
+
+        let get = &Method::GET;
+        let put = &Method::PUT;
+
+        let pre = Preconditions::default();
+        assert_eq!(pre.evaluate(get, &STRONG), ServeFull);
+
+        let pre = Preconditions { if_match: Some(b"\"xyz\""), ..Default::default() };
+        assert_eq!(pre.evaluate(get, &STRONG), Serve412);
+
+        let pre = Preconditions { if_match: Some(b"\"abc\""), ..Default::default() };
+        assert_eq!(pre.evaluate(put, &STRONG), ServeFull);
+        assert_eq!(pre.evaluate(put, &WEAK), Serve412);
+
+        let pre = Preconditions { if_unmodified_since: Some(999_999), ..Default::default() };
+        assert_eq!(pre.evaluate(put, &STRONG), Serve412);
+        assert_eq!(pre.evaluate(put, &WEAK), ServeFull);
+
+        let pre = Preconditions { if_none_match: Some(b"W/\"abc\""), ..Default::default() };
+        assert_eq!(pre.evaluate(get, &STRONG), Serve304);
+        assert_eq!(pre.evaluate(put, &STRONG), Serve412);
+
+        let pre = Preconditions {
+            if_none_match: Some(b"\"xyz\""),
+            if_modified_since: Some(1_000_000),
+            ..Default::default()
+        };
+        assert_eq!(pre.evaluate(get, &STRONG), ServeFull);
+
+        let pre = Preconditions { if_modified_since: Some(1_000_000), ..Default::default() };
+        assert_eq!(pre.evaluate(get, &STRONG), Serve304);
+        assert_eq!(pre.evaluate(get, &WEAK), ServeFull);
+        assert_eq!(pre.evaluate(put, &STRONG), ServeFull);
+
+        let pre = Preconditions { range: true, ..Default::default() };
+        assert_eq!(pre.evaluate(get, &STRONG), ServeRange);
+        assert_eq!(pre.evaluate(&Method::HEAD, &STRONG), ServeFull);
+
+        let if_range = Some(IfRange::ETag(b"\"abc\""));
+        let pre = Preconditions { range: true, if_range, ..Default::default() };
+        assert_eq!(pre.evaluate(get, &STRONG), ServeRange);
+        assert_eq!(pre.evaluate(get, &WEAK), ServeFull);
+
+        let if_range = Some(IfRange::Date(Some(1_000_000)));
+        let pre = Preconditions { range: true, if_range, ..Default::default() };
+        assert_eq!(pre.evaluate(get, &STRONG), ServeRange);
+
+        let if_range = Some(IfRange::Date(None));
+        let pre = Preconditions { range: true, if_range, ..Default::default() };
+        assert_eq!(pre.evaluate(get, &STRONG), ServeFull);
+    }
+}
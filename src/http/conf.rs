@@ -1,8 +1,11 @@
 use ::core::ptr::NonNull;
 
+use crate::core::NgxStr;
 use crate::ffi::{
-    ngx_http_conf_ctx_t, ngx_http_core_srv_conf_t, ngx_http_request_t,
-    ngx_http_upstream_srv_conf_t, ngx_module_t,
+    NGX_HTTP_LIF_CONF, NGX_HTTP_LMT_CONF, NGX_HTTP_LOC_CONF, NGX_HTTP_MAIN_CONF, NGX_HTTP_MODULE,
+    NGX_HTTP_SIF_CONF, NGX_HTTP_SRV_CONF, NGX_HTTP_UPS_CONF, ngx_conf_t, ngx_http_conf_ctx_t,
+    ngx_http_core_srv_conf_t, ngx_http_request_t, ngx_http_server_name_t,
+    ngx_http_upstream_srv_conf_t, ngx_module_t, ngx_uint_t,
 };
 use crate::http::HttpModule;
 
@@ -205,6 +208,91 @@ pub unsafe trait HttpModuleLocationConf: HttpModule {
     }
 }
 
+/// Configuration context of an HTTP directive.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HttpConfContext {
+    /// `http` block.
+    Main,
+    /// `server` block.
+    Server,
+    /// `if` block in a `server`.
+    ServerIf,
+    /// `location` block.
+    Location,
+    /// `if` block in a `location`.
+    LocationIf,
+    /// `limit_except` block.
+    LimitExcept,
+    /// `upstream` block.
+    Upstream,
+}
+
+impl HttpConfContext {
+    /// Returns the context of the directive currently processed by the configuration parser.
+    ///
+    /// Returns `None` if called outside of the `http` block.
+    pub fn current(cf: &ngx_conf_t) -> Option<Self> {
+        if cf.module_type != NGX_HTTP_MODULE as ngx_uint_t {
+            return None;
+        }
+
+        let context = match cf.cmd_type as u32 {
+            NGX_HTTP_MAIN_CONF => Self::Main,
+            NGX_HTTP_SRV_CONF => Self::Server,
+            NGX_HTTP_SIF_CONF => Self::ServerIf,
+            NGX_HTTP_LOC_CONF => Self::Location,
+            NGX_HTTP_LIF_CONF => Self::LocationIf,
+            NGX_HTTP_LMT_CONF => Self::LimitExcept,
+            NGX_HTTP_UPS_CONF => Self::Upstream,
+            _ => return None,
+        };
+
+        Some(context)
+    }
+
+    /// Returns `true` if the context has a location configuration of its own.
+    pub fn is_location(&self) -> bool {
+        matches!(self, Self::Location | Self::LocationIf | Self::LimitExcept)
+    }
+
+    /// Returns `true` for the `if` and `limit_except` blocks, which are implemented as unnamed
+    /// nested locations.
+    pub fn is_nested(&self) -> bool {
+        matches!(self, Self::ServerIf | Self::LocationIf | Self::LimitExcept)
+    }
+}
+
+/// Returns the name of the enclosing `location` block for the directive currently processed by
+/// the configuration parser.
+///
+/// The name is inherited by the nested `if` and `limit_except` blocks.
+pub fn http_conf_location_name(cf: &ngx_conf_t) -> Option<&NgxStr> {
+    if !HttpConfContext::current(cf)?.is_location() {
+        return None;
+    }
+
+    let clcf = NgxHttpCoreModule::location_conf(cf)?;
+    // SAFETY: the location name is allocated from the configuration pool.
+    Some(unsafe { NgxStr::from_ngx_str(clcf.name) })
+}
+
+/// Returns the names of the enclosing `server` block for the directive currently processed by the
+/// configuration parser.
+///
+/// Only the names specified before the directive are available.
+pub fn http_conf_server_names(cf: &ngx_conf_t) -> impl Iterator<Item = &NgxStr> {
+    let names: &[ngx_http_server_name_t] = match HttpConfContext::current(cf) {
+        Some(HttpConfContext::Main | HttpConfContext::Upstream) | None => &[],
+        // SAFETY: `server_names` is initialized in the server configuration constructor.
+        Some(_) => NgxHttpCoreModule::server_conf(cf)
+            .map(|cscf| unsafe { cscf.server_names.as_slice() })
+            .unwrap_or_default(),
+    };
+
+    // SAFETY: the server names are allocated from the configuration pool.
+    names.iter().map(|x| unsafe { NgxStr::from_ngx_str(x.name) })
+}
+
 mod core {
     use crate::allocator::AllocError;
     use crate::{
@@ -235,6 +323,7 @@ mod core {
     }
 
     /// HTTP phases in which a module can register handlers.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
     #[repr(usize)]
     pub enum HttpPhase {
         /// Post-read phase
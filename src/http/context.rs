@@ -0,0 +1,124 @@
+use core::ffi::c_void;
+use core::marker::PhantomData;
+
+use crate::allocator::AllocError;
+use crate::core::Pool;
+use crate::ffi::ngx_http_request_t;
+use crate::http::{HttpModule, Request};
+
+/// Typed access to the request context of the module `M`, with explicit sharing between the main
+/// request and its subrequests.
+///
+/// nginx allocates a separate array of module contexts for each subrequest, so a context set with
+/// `ngx_http_set_ctx` for a request is not visible to its subrequests, and a context set while
+/// processing a subrequest is not visible to the main request. A module keeping state for the
+/// whole client request, e.g. a counter of the subrequests, should use the context of the main
+/// request with [`get_main`](Self::get_main) and [`set_main`](Self::set_main). A module keeping
+/// state for the request being processed, e.g. a body filter, should use
+/// [`get_current`](Self::get_current) and [`set_current`](Self::set_current).
+///
+/// The contexts are cleared on an internal redirect, as done by nginx.
+///
+/// ```no_run
+/// # use ngx::ffi::ngx_module_t;
+/// # use ngx::http::{HttpModule, Request, RequestContext};
+/// # struct Module;
+/// # impl HttpModule for Module {
+/// #     fn module() -> &'static ngx_module_t { unimplemented!() }
+/// # }
+/// #[derive(Default)]
+/// struct Stats {
+///     subrequests: usize,
+/// }
+///
+/// type StatsCtx = RequestContext<Module, Stats>;
+///
+/// fn handler(r: &mut Request) -> Option<()> {
+///     if StatsCtx::get_main(r).is_none() {
+///         StatsCtx::set_main(r, Stats::default()).ok()?;
+///     }
+///     if !r.is_main() {
+///         StatsCtx::get_main_mut(r)?.subrequests += 1;
+///     }
+///     Some(())
+/// }
+/// ```
+pub struct RequestContext<M, T>(PhantomData<fn() -> (M, T)>);
+
+impl<M: HttpModule, T> RequestContext<M, T> {
+    /// Returns the context of the request being processed, which may be a subrequest.
+    pub fn get_current(r: &Request) -> Option<&T> {
+        // SAFETY: the context is either NULL or set with `set_current`.
+        unsafe { ctx_ptr::<M>(r.as_ref()).cast::<T>().as_ref() }
+    }
+
+    /// Returns the mutable context of the request being processed, which may be a subrequest.
+    pub fn get_current_mut(r: &mut Request) -> Option<&mut T> {
+        // SAFETY: the context is either NULL or set with `set_current`.
+        unsafe { ctx_ptr::<M>(r.as_ref()).cast::<T>().as_mut() }
+    }
+
+    /// Allocates the context for the request being processed, replacing the previous one.
+    ///
+    /// The value is dropped when the request pool is destroyed.
+    pub fn set_current(r: &mut Request, value: T) -> Result<&mut T, AllocError> {
+        // SAFETY: the request is valid and borrowed mutably.
+        unsafe { set_ctx::<M, T>(&r.pool(), r.as_mut(), value) }
+    }
+
+    /// Returns the context of the main request, shared by all its subrequests.
+    pub fn get_main(r: &Request) -> Option<&T> {
+        // SAFETY: the main request outlives all its subrequests, and the context is either NULL
+        // or set with `set_main`.
+        unsafe { ctx_ptr::<M>(r.as_ref().main).cast::<T>().as_ref() }
+    }
+
+    /// Returns the mutable context of the main request, shared by all its subrequests.
+    pub fn get_main_mut(r: &mut Request) -> Option<&mut T> {
+        // SAFETY: the main request outlives all its subrequests, and the context is either NULL
+        // or set with `set_main`.
+        unsafe { ctx_ptr::<M>(r.as_ref().main).cast::<T>().as_mut() }
+    }
+
+    /// Allocates the context for the main request, replacing the previous one.
+    ///
+    /// The value is dropped when the request pool, shared by the main request and the
+    /// subrequests, is destroyed.
+    pub fn set_main(r: &mut Request, value: T) -> Result<&mut T, AllocError> {
+        // SAFETY: the main request outlives all its subrequests and shares the pool with them.
+        unsafe { set_ctx::<M, T>(&r.pool(), r.as_ref().main, value) }
+    }
+}
+
+/// Returns the context of the module `M` for the request.
+///
+/// The context is read through the raw pointer, so that the main request can be accessed while
+/// a reference to a subrequest, or to the main request itself, is held.
+///
+/// # Safety
+///
+/// `r` must point to a valid request.
+unsafe fn ctx_ptr<M: HttpModule>(r: *const ngx_http_request_t) -> *mut c_void {
+    // SAFETY: `ctx` has an entry for each HTTP module.
+    unsafe { *(*r).ctx.add(M::module().ctx_index) }
+}
+
+/// Allocates the context of the module `M` for the request from `pool`.
+///
+/// # Safety
+///
+/// `r` must point to a valid request, and the request must outlive `'a`.
+unsafe fn set_ctx<'a, M: HttpModule, T>(
+    pool: &Pool,
+    r: *mut ngx_http_request_t,
+    value: T,
+) -> Result<&'a mut T, AllocError> {
+    let p = pool.allocate(value);
+    if p.is_null() {
+        return Err(AllocError);
+    }
+    // SAFETY: `ctx` has an entry for each HTTP module.
+    unsafe { *(*r).ctx.add(M::module().ctx_index) = p.cast() };
+    // SAFETY: the value is allocated and initialized above.
+    Ok(unsafe { &mut *p })
+}
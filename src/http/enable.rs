@@ -0,0 +1,148 @@
+use core::ffi::{c_char, c_void};
+
+use crate::core::{ConfError, ConfUnset, NGX_CONF_DUPLICATE, NGX_CONF_ERROR, NGX_CONF_OK, NgxStr};
+use crate::ffi::{NGX_LOG_EMERG, ngx_command_t, ngx_conf_t, ngx_http_complex_value_t, ngx_str_t};
+use crate::http::{ComplexValue, Merge, MergeConfigError, Request};
+use crate::ngx_conf_log_error;
+
+/// Per-location switch for a module: `off`, `on`, or a value with variables, which also enables
+/// the module.
+///
+/// Many modules are configured with a single directive enabling the module in a location, such as
+/// `foo on | off` or `foo <value> | off` for the modules that need a parameter evaluated per
+/// request, e.g. a key built from variables. The type implements the parsing, with [`enable_flag_slot`] as the directive handler, and
+/// the inheritance from the enclosing levels with [`Merge`]. The module is disabled if the
+/// directive is not specified at any level.
+///
+/// The type can be used as the whole location configuration, with the offset 0 in the command, or
+/// as a field of a larger configuration.
+///
+/// ```no_run
+/// # use ngx::core::Status;
+/// # use ngx::http::{EnableFlag, Request};
+/// fn handler(r: &mut Request, conf: &EnableFlag) -> Status {
+///     if !conf.is_enabled() {
+///         return Status::NGX_DECLINED;
+///     }
+///     let _key = conf.value(r);
+///     // ...
+/// #   Status::NGX_OK
+/// }
+/// ```
+#[derive(Clone, Copy, Debug, Default)]
+pub struct EnableFlag(State);
+
+#[derive(Clone, Copy, Debug, Default)]
+enum State {
+    #[default]
+    Unset,
+    Off,
+    On,
+    Value(ComplexValue),
+}
+
+impl EnableFlag {
+    /// Unset value.
+    pub const UNSET: Self = Self(State::Unset);
+
+    /// Parses the directive argument: `on`, `off`, or a value with variables compiled as a complex
+    /// value.
+    ///
+    /// Other values without variables are rejected as invalid, as with `ngx_conf_set_flag_slot`.
+    pub fn compile(cf: &mut ngx_conf_t, value: &ngx_str_t) -> Result<Self, ConfError> {
+        let bytes = value.as_bytes();
+        if bytes.eq_ignore_ascii_case(b"on") {
+            return Ok(Self(State::On));
+        }
+        if bytes.eq_ignore_ascii_case(b"off") {
+            return Ok(Self(State::Off));
+        }
+
+        let cv = ComplexValue::compile(cf, value)?;
+        if cv.as_static().is_some() {
+            ngx_conf_log_error!(
+                NGX_LOG_EMERG,
+                cf,
+                "invalid value \"{value}\", it must be \"on\", \"off\" or contain variables"
+            );
+            return Err(ConfError::InvalidValue);
+        }
+
+        Ok(Self(State::Value(cv)))
+    }
+
+    /// Returns `true` if the directive was not specified.
+    pub fn is_unset(&self) -> bool {
+        matches!(self.0, State::Unset)
+    }
+
+    /// Returns `true` if the module is enabled, without evaluating the value.
+    #[inline]
+    pub fn is_enabled(&self) -> bool {
+        matches!(self.0, State::On | State::Value(_))
+    }
+
+    /// Returns the compiled value, if specified instead of `on`.
+    pub fn complex_value(&self) -> Option<&ngx_http_complex_value_t> {
+        match self.0 {
+            // SAFETY: the value is allocated from the configuration pool in `compile`.
+            State::Value(cv) => Some(unsafe { &*cv.as_ptr() }),
+            _ => None,
+        }
+    }
+
+    /// Evaluates the value in the context of the request.
+    ///
+    /// Returns `None` if the value is not specified or the evaluation failed.
+    pub fn value<'r>(&self, r: &'r Request) -> Option<&'r NgxStr> {
+        r.get_complex_value(self.complex_value()?)
+    }
+}
+
+impl Merge for EnableFlag {
+    fn merge(&mut self, prev: &Self) -> Result<(), MergeConfigError> {
+        if self.is_unset() {
+            *self = *prev;
+        }
+        Ok(())
+    }
+}
+
+impl ConfUnset for EnableFlag {
+    fn is_unset(&self) -> bool {
+        EnableFlag::is_unset(self)
+    }
+}
+
+/// Directive handler parsing the argument into an [`EnableFlag`] field of the configuration.
+///
+/// The field is located at `offset` of the command, as with the nginx slot functions. The command
+/// should be declared with `NGX_CONF_TAKE1`.
+///
+/// # Safety
+///
+/// The field at `cmd.offset` in `conf` must have the type [`EnableFlag`].
+pub unsafe extern "C" fn enable_flag_slot(
+    cf: *mut ngx_conf_t,
+    cmd: *mut ngx_command_t,
+    conf: *mut c_void,
+) -> *mut c_char {
+    // SAFETY: nginx passes valid pointers to the directive handlers.
+    let (cf, cmd) = unsafe { (&mut *cf, &*cmd) };
+    let field = unsafe { &mut *conf.byte_add(cmd.offset).cast::<EnableFlag>() };
+
+    if field.is_set() {
+        return NGX_CONF_DUPLICATE;
+    }
+
+    // SAFETY: `cf.args` contains the directive name and arguments.
+    let args: &[ngx_str_t] = unsafe { (*cf.args).as_slice() };
+
+    match EnableFlag::compile(cf, &args[1]) {
+        Ok(value) => {
+            *field = value;
+            NGX_CONF_OK
+        }
+        Err(_) => NGX_CONF_ERROR,
+    }
+}
@@ -0,0 +1,241 @@
+//! Programmatic bodies for the error responses.
+//!
+//! nginx generates the body of an error response returned from a handler, e.g. `404` or `503`,
+//! in the special response handler: either an HTML page built into nginx or a page configured with
+//! the `error_page` directive. A module may replace the body for a single request, e.g. to return
+//! JSON errors from an API gateway, with [`Request::set_error_body`] once the error body filter is
+//! installed with [`install_error_body_filter`].
+use core::ffi::c_void;
+use core::ptr;
+use core::sync::atomic::{AtomicPtr, Ordering};
+
+use crate::core::Status;
+use crate::ffi::{
+    ngx_buf_t, ngx_chain_t, ngx_conf_t, ngx_cycle, ngx_cycle_t, ngx_pool_cleanup_add, ngx_str_t,
+};
+use crate::http::{
+    BodyFilter, Chain, HeaderFilter, NextBodyFilter, NextHeaderFilter, Request,
+    install_body_filter, install_header_filter,
+};
+
+/// The configuration cycle the filters are installed for.
+///
+/// The filter chain is rebuilt for each configuration cycle, so the filters must be installed
+/// again on reload.
+static INSTALLED: AtomicPtr<ngx_cycle_t> = AtomicPtr::new(ptr::null_mut());
+static NEXT_HEADER_FILTER: NextHeaderFilter = NextHeaderFilter::new();
+static NEXT_BODY_FILTER: NextBodyFilter = NextBodyFilter::new();
+
+/// Error body override, stored in a request pool cleanup.
+struct ErrorBody {
+    content_type: ngx_str_t,
+    body: ngx_str_t,
+    /// The response is an error response with the body replaced.
+    active: bool,
+    /// The replacement body has been passed to the next filter.
+    sent: bool,
+}
+
+/// Installs the filters replacing the error response bodies set with [`Request::set_error_body`].
+///
+/// This function must be called from the module's `postconfiguration()` function on each
+/// configuration load. The filters are installed once per configuration, even if several modules
+/// call the function.
+pub fn install_error_body_filter(cf: &ngx_conf_t) {
+    if !claim_cycle(cf.cycle) {
+        return;
+    }
+    install_header_filter::<ErrorBodyFilter>();
+    install_body_filter::<ErrorBodyFilter>();
+}
+
+/// Records that the filters are installed for the cycle, returning `false` if they already are.
+fn claim_cycle(cycle: *mut ngx_cycle_t) -> bool {
+    INSTALLED.swap(cycle, Ordering::Relaxed) != cycle
+}
+
+/// Returns `true` if the filters are installed for the cycle.
+fn is_installed(cycle: *mut ngx_cycle_t) -> bool {
+    !cycle.is_null() && INSTALLED.load(Ordering::Relaxed) == cycle
+}
+
+impl Request {
+    /// Sets the body of the error response for this request, replacing the body generated by
+    /// nginx.
+    ///
+    /// The body is used if the request is finalized with an error status, `400` or above, and the
+    /// response is generated by the special response handler, e.g. when a handler returns
+    /// `HTTPStatus::SERVICE_UNAVAILABLE`. Pages configured with `error_page` are processed first,
+    /// and the body replaces the response of the error page location. Calling the method again
+    /// replaces the previous override.
+    ///
+    /// Returns `NGX_ERROR` if the memory allocation fails or the error body filter is not
+    /// installed with [`install_error_body_filter`] for the current configuration.
+    ///
+    /// ```no_run
+    /// # use ngx::core::Status;
+    /// # use ngx::http::{HTTPStatus, Request};
+    /// # fn handler(r: &mut Request) -> Status {
+    /// if !r.set_error_body(b"application/json", br#"{"error":"rate limited"}"#).is_ok() {
+    ///     return Status::NGX_ERROR;
+    /// }
+    /// HTTPStatus::TOO_MANY_REQUESTS.into()
+    /// # }
+    /// ```
+    pub fn set_error_body(&mut self, content_type: &[u8], body: &[u8]) -> Status {
+        // SAFETY: the worker cycle is set before any request is processed.
+        if !is_installed(unsafe { ngx_cycle }) {
+            return Status::NGX_ERROR;
+        }
+
+        let pool = self.as_ref().pool;
+        let content_type = unsafe { ngx_str_t::from_bytes(pool, content_type) };
+        let body = unsafe { ngx_str_t::from_bytes(pool, body) };
+        let (Some(content_type), Some(body)) = (content_type, body) else {
+            return Status::NGX_ERROR;
+        };
+
+        if let Some(state) = self.error_body() {
+            state.content_type = content_type;
+            state.body = body;
+            return Status::NGX_OK;
+        }
+
+        let cln = unsafe { ngx_pool_cleanup_add(pool, core::mem::size_of::<ErrorBody>()) };
+        let Some(cln) = (unsafe { cln.as_mut() }) else {
+            return Status::NGX_ERROR;
+        };
+
+        let state = ErrorBody { content_type, body, active: false, sent: false };
+        unsafe { ptr::write(cln.data.cast::<ErrorBody>(), state) };
+        cln.handler = Some(cleanup_error_body);
+        Status::NGX_OK
+    }
+
+    fn error_body(&mut self) -> Option<&mut ErrorBody> {
+        let mut cln = unsafe { (*self.as_ref().pool).cleanup };
+
+        while let Some(c) = unsafe { cln.as_ref() } {
+            if c.handler == Some(cleanup_error_body as unsafe extern "C" fn(_)) {
+                // SAFETY: the cleanup data was written by `set_error_body`.
+                return Some(unsafe { &mut *c.data.cast::<ErrorBody>() });
+            }
+            cln = c.next;
+        }
+
+        None
+    }
+}
+
+/// The cleanup handler only marks the error body in the pool; the data is owned by the pool.
+unsafe extern "C" fn cleanup_error_body(_data: *mut c_void) {}
+
+/// Filter replacing the error response bodies, see [`install_error_body_filter`].
+pub struct ErrorBodyFilter;
+
+impl HeaderFilter for ErrorBodyFilter {
+    type Error = Status;
+
+    fn next() -> &'static NextHeaderFilter {
+        &NEXT_HEADER_FILTER
+    }
+
+    fn filter(r: &mut Request) -> Result<(), Status> {
+        // `err_status` is only set by the special response handler.
+        if r.as_ref().err_status < 400 {
+            return Ok(());
+        }
+
+        let header_only = r.header_only();
+        let Some(state) = r.error_body() else {
+            return Ok(());
+        };
+        state.active = !header_only;
+        let (content_type, len) = (state.content_type, state.body.len);
+
+        if !r.set_content_type(content_type.as_bytes()).is_ok() {
+            return Err(Status::NGX_ERROR);
+        }
+        r.set_content_length_n(len);
+        r.clear_last_modified();
+        r.clear_etag();
+        r.clear_accept_ranges();
+        r.remove_header_out("Content-Encoding");
+        Ok(())
+    }
+}
+
+impl BodyFilter for ErrorBodyFilter {
+    type Output = Status;
+
+    fn next() -> &'static NextBodyFilter {
+        &NEXT_BODY_FILTER
+    }
+
+    fn filter(r: &mut Request, chain: Chain<'_>) -> Status {
+        let main = r.is_main();
+        let Some(state) = r.error_body().filter(|s| s.active) else {
+            return NEXT_BODY_FILTER.call(r, chain);
+        };
+
+        // The body generated by nginx is discarded.
+        for mut b in chain {
+            b.consume();
+        }
+
+        if state.sent {
+            return Status::NGX_OK;
+        }
+        state.sent = true;
+        let body = state.body;
+
+        let pool = r.pool();
+        let b = pool.calloc_type::<ngx_buf_t>();
+        let cl = pool.alloc_type::<ngx_chain_t>();
+        if b.is_null() || cl.is_null() {
+            return Status::NGX_ERROR;
+        }
+
+        // SAFETY: the buffer and the chain link are allocated above; the body is allocated from
+        // the request pool.
+        unsafe {
+            if body.len > 0 {
+                (*b).pos = body.data;
+                (*b).last = body.data.add(body.len);
+                (*b).set_memory(1);
+            }
+            if main {
+                (*b).set_last_buf(1);
+            } else {
+                (*b).set_last_in_chain(1);
+            }
+            cl.write(ngx_chain_t { buf: b, next: ptr::null_mut() });
+        }
+
+        NEXT_BODY_FILTER.call(r, unsafe { Chain::from_raw(cl) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_install_per_cycle() {
+        let first = ptr::dangling_mut::<ngx_cycle_t>();
+        let second = first.wrapping_add(1);
+
+        assert!(!is_installed(first));
+        assert!(claim_cycle(first));
+        assert!(is_installed(first));
+        // Another module calling the function for the same configuration.
+        assert!(!claim_cycle(first));
+
+        // The filters must be installed again for a reloaded configuration.
+        assert!(!is_installed(second));
+        assert!(claim_cycle(second));
+        assert!(is_installed(second));
+        assert!(!is_installed(first));
+        assert!(!is_installed(ptr::null_mut()));
+    }
+}
@@ -0,0 +1,395 @@
+use core::ffi::c_void;
+use core::marker::PhantomData;
+use core::mem;
+use core::ptr;
+use core::sync::atomic::{AtomicPtr, Ordering};
+
+use crate::core::{Buffer, Status};
+use crate::ffi::{
+    NGX_ERROR, ngx_buf_t, ngx_chain_t, ngx_http_output_body_filter_pt,
+    ngx_http_output_header_filter_pt, ngx_http_request_t, ngx_http_top_body_filter,
+    ngx_http_top_header_filter, ngx_int_t,
+};
+use crate::http::filter_chain::{register_body_filter, register_header_filter};
+use crate::http::{IntoHandlerStatus, Request};
+
+/// Chain of buffers passed to a body filter.
+#[derive(Clone, Copy, Debug)]
+pub struct Chain<'a> {
+    cl: *mut ngx_chain_t,
+    _marker: PhantomData<&'a mut ngx_chain_t>,
+}
+
+impl<'a> Chain<'a> {
+    /// Creates a chain from a raw pointer.
+    ///
+    /// # Safety
+    ///
+    /// `cl` must be NULL or a valid chain for the lifetime `'a`.
+    pub unsafe fn from_raw(cl: *mut ngx_chain_t) -> Self {
+        Self { cl, _marker: PhantomData }
+    }
+
+    /// Returns an empty chain.
+    pub fn empty() -> Self {
+        Self { cl: ptr::null_mut(), _marker: PhantomData }
+    }
+
+    /// Returns the raw pointer to the first link.
+    pub fn as_ptr(&self) -> *mut ngx_chain_t {
+        self.cl
+    }
+
+    /// Returns `true` if the chain has no links.
+    pub fn is_empty(&self) -> bool {
+        self.cl.is_null()
+    }
+
+    /// Returns an iterator over the buffers.
+    pub fn iter(&self) -> ChainIter<'a> {
+        ChainIter { cl: self.cl, _marker: PhantomData }
+    }
+
+    /// Returns `true` if the chain contains the last buffer of the response or the subrequest.
+    pub fn has_last_buf(&self) -> bool {
+        self.iter().any(|b| b.is_last())
+    }
+
+    /// Returns the total size of the data in the memory buffers of the chain.
+    pub fn len(&self) -> usize {
+        self.iter().map(|b| b.len()).sum()
+    }
+}
+
+impl<'a> IntoIterator for Chain<'a> {
+    type Item = ChainBuf<'a>;
+    type IntoIter = ChainIter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Iterator over the buffers of a [`Chain`].
+pub struct ChainIter<'a> {
+    cl: *mut ngx_chain_t,
+    _marker: PhantomData<&'a mut ngx_chain_t>,
+}
+
+impl<'a> Iterator for ChainIter<'a> {
+    type Item = ChainBuf<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // SAFETY: the chain is valid for `'a`, see `Chain::from_raw`.
+        let cl = unsafe { self.cl.as_ref()? };
+        self.cl = cl.next;
+        Some(ChainBuf(cl.buf, PhantomData))
+    }
+}
+
+/// Buffer of a [`Chain`].
+pub struct ChainBuf<'a>(*mut ngx_buf_t, PhantomData<&'a mut ngx_buf_t>);
+
+impl ChainBuf<'_> {
+    /// Returns `true` if the buffer data is in memory.
+    pub fn in_memory(&self) -> bool {
+        let b = unsafe { &*self.0 };
+        b.temporary() != 0 || b.memory() != 0 || b.mmap() != 0
+    }
+
+    /// Returns `true` if the buffer is the last one of the response or the subrequest.
+    pub fn is_last(&self) -> bool {
+        let b = unsafe { &*self.0 };
+        b.last_buf() != 0 || b.last_in_chain() != 0
+    }
+
+    /// Returns `true` if the buffer requests flushing the output.
+    pub fn is_flush(&self) -> bool {
+        unsafe { (*self.0).flush() != 0 }
+    }
+
+    /// Returns `true` if the buffer carries no data, only the flags.
+    pub fn is_special(&self) -> bool {
+        let b = unsafe { &*self.0 };
+        (b.flush() != 0 || b.last_buf() != 0 || b.sync() != 0)
+            && !self.in_memory()
+            && b.in_file() == 0
+    }
+
+    /// Marks the buffer data as consumed.
+    pub fn consume(&mut self) {
+        let b = unsafe { &mut *self.0 };
+        b.pos = b.last;
+        if b.in_file() != 0 {
+            b.file_pos = b.file_last;
+        }
+    }
+}
+
+impl Buffer for ChainBuf<'_> {
+    fn as_ngx_buf(&self) -> *const ngx_buf_t {
+        self.0
+    }
+
+    fn as_ngx_buf_mut(&mut self) -> *mut ngx_buf_t {
+        self.0
+    }
+
+    fn as_bytes(&self) -> &[u8] {
+        if self.in_memory() && !unsafe { (*self.0).pos.is_null() } {
+            unsafe { core::slice::from_raw_parts((*self.0).pos, self.len()) }
+        } else {
+            &[]
+        }
+    }
+
+    fn len(&self) -> usize {
+        if self.in_memory() {
+            let b = unsafe { &*self.0 };
+            unsafe { b.last.offset_from(b.pos) as usize }
+        } else {
+            0
+        }
+    }
+}
+
+/// Storage for the next body filter in the chain.
+///
+/// Declared as a `static` for each [`BodyFilter`] implementation and set by
+/// [`install_body_filter`].
+#[derive(Debug)]
+pub struct NextBodyFilter(AtomicPtr<c_void>);
+
+impl NextBodyFilter {
+    /// Creates an empty storage.
+    pub const fn new() -> Self {
+        Self(AtomicPtr::new(ptr::null_mut()))
+    }
+
+    fn set(&self, next: ngx_http_output_body_filter_pt) {
+        let p = next.map_or(ptr::null_mut(), |f| f as *mut c_void);
+        self.0.store(p, Ordering::Relaxed);
+    }
+
+    pub(crate) fn get(&self) -> ngx_http_output_body_filter_pt {
+        let p = self.0.load(Ordering::Relaxed);
+        // SAFETY: the pointer was stored from the same function pointer type.
+        unsafe { mem::transmute::<*mut c_void, ngx_http_output_body_filter_pt>(p) }
+    }
+
+    /// Passes the chain to the next body filter.
+    pub fn call(&self, r: &mut Request, chain: Chain<'_>) -> Status {
+        let Some(next) = self.get() else {
+            return Status::NGX_ERROR;
+        };
+        let r: *mut ngx_http_request_t = r.into();
+        // SAFETY: the next filter was installed by nginx or a module.
+        Status(unsafe { next(r, chain.as_ptr()) })
+    }
+}
+
+impl Default for NextBodyFilter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Trait for a response body filter.
+///
+/// # Example
+///
+/// ```no_run
+/// # use ngx::core::{Buffer, Status};
+/// # use ngx::http::{BodyFilter, Chain, NextBodyFilter, Request};
+/// struct CountingFilter;
+///
+/// static NEXT: NextBodyFilter = NextBodyFilter::new();
+///
+/// impl BodyFilter for CountingFilter {
+///     type Output = Status;
+///
+///     fn next() -> &'static NextBodyFilter {
+///         &NEXT
+///     }
+///
+///     fn filter(r: &mut Request, chain: Chain<'_>) -> Status {
+///         let size: usize = chain.iter().map(|b| b.len()).sum();
+///         ngx::ngx_log_debug_http!(r, "body chunk {size} bytes");
+///         NEXT.call(r, chain)
+///     }
+/// }
+///
+/// // in postconfiguration:
+/// ngx::http::install_body_filter::<CountingFilter>();
+/// ```
+pub trait BodyFilter {
+    /// The return type of the filter.
+    type Output: IntoHandlerStatus;
+
+    /// Returns the storage for the next body filter.
+    fn next() -> &'static NextBodyFilter;
+
+    /// The filter function.
+    ///
+    /// The filter passes the original or a modified chain to the next filter with
+    /// [`NextBodyFilter::call`].
+    fn filter(r: &mut Request, chain: Chain<'_>) -> Self::Output;
+
+    /// Filter name for logging purposes.
+    /// [`core::any::type_name`] is used by default.
+    fn name() -> &'static str {
+        core::any::type_name::<Self>()
+    }
+}
+
+unsafe extern "C" fn raw_body_filter<F>(
+    r: *mut ngx_http_request_t,
+    cl: *mut ngx_chain_t,
+) -> ngx_int_t
+where
+    F: BodyFilter,
+{
+    if r.is_null() {
+        return NGX_ERROR as _;
+    }
+    let r = unsafe { Request::from_ngx_http_request(r) };
+    let chain = unsafe { Chain::from_raw(cl) };
+    F::filter(r, chain).into_handler_status(r)
+}
+
+/// Installs the body filter at the top of the filter chain.
+///
+/// This function must be called from the module's `postconfiguration()` function. The filters
+/// are invoked in the reverse order of installation.
+pub fn install_body_filter<F>()
+where
+    F: BodyFilter,
+{
+    // SAFETY: the configuration is processed in a single thread.
+    unsafe {
+        let top = &raw mut ngx_http_top_body_filter;
+        F::next().set(*top);
+        *top = Some(raw_body_filter::<F>);
+    }
+    register_body_filter(raw_body_filter::<F> as usize, F::name(), F::next());
+}
+
+/// Storage for the next header filter in the chain.
+///
+/// Declared as a `static` for each [`HeaderFilter`] implementation and set by
+/// [`install_header_filter`].
+#[derive(Debug)]
+pub struct NextHeaderFilter(AtomicPtr<c_void>);
+
+impl NextHeaderFilter {
+    /// Creates an empty storage.
+    pub const fn new() -> Self {
+        Self(AtomicPtr::new(ptr::null_mut()))
+    }
+
+    fn set(&self, next: ngx_http_output_header_filter_pt) {
+        let p = next.map_or(ptr::null_mut(), |f| f as *mut c_void);
+        self.0.store(p, Ordering::Relaxed);
+    }
+
+    pub(crate) fn get(&self) -> ngx_http_output_header_filter_pt {
+        let p = self.0.load(Ordering::Relaxed);
+        // SAFETY: the pointer was stored from the same function pointer type.
+        unsafe { mem::transmute::<*mut c_void, ngx_http_output_header_filter_pt>(p) }
+    }
+
+    /// Passes the request to the next header filter.
+    pub fn call(&self, r: &mut Request) -> Status {
+        let Some(next) = self.get() else {
+            return Status::NGX_ERROR;
+        };
+        let r: *mut ngx_http_request_t = r.into();
+        // SAFETY: the next filter was installed by nginx or a module.
+        Status(unsafe { next(r) })
+    }
+}
+
+impl Default for NextHeaderFilter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Trait for a response header filter.
+///
+/// The filter inspects or modifies the response headers before they are sent. On success, the
+/// request is passed to the next header filter; on error, the error is converted to the return
+/// code with [`IntoHandlerStatus`] and the next filters are not called.
+///
+/// # Example
+///
+/// ```no_run
+/// # use ngx::core::Status;
+/// # use ngx::http::{HeaderFilter, NextHeaderFilter, Request};
+/// struct PoweredByFilter;
+///
+/// static NEXT: NextHeaderFilter = NextHeaderFilter::new();
+///
+/// impl HeaderFilter for PoweredByFilter {
+///     type Error = Status;
+///
+///     fn next() -> &'static NextHeaderFilter {
+///         &NEXT
+///     }
+///
+///     fn filter(r: &mut Request) -> Result<(), Status> {
+///         r.remove_header_out("X-Powered-By");
+///         r.add_header_out("X-Served-By", "ngx-rust").ok_or(Status::NGX_ERROR)
+///     }
+/// }
+///
+/// // in postconfiguration:
+/// ngx::http::install_header_filter::<PoweredByFilter>();
+/// ```
+pub trait HeaderFilter {
+    /// The error type of the filter.
+    type Error: IntoHandlerStatus;
+
+    /// Returns the storage for the next header filter.
+    fn next() -> &'static NextHeaderFilter;
+
+    /// The filter function.
+    fn filter(r: &mut Request) -> Result<(), Self::Error>;
+
+    /// Filter name for logging purposes.
+    /// [`core::any::type_name`] is used by default.
+    fn name() -> &'static str {
+        core::any::type_name::<Self>()
+    }
+}
+
+unsafe extern "C" fn raw_header_filter<F>(r: *mut ngx_http_request_t) -> ngx_int_t
+where
+    F: HeaderFilter,
+{
+    if r.is_null() {
+        return NGX_ERROR as _;
+    }
+    let r = unsafe { Request::from_ngx_http_request(r) };
+    match F::filter(r) {
+        Ok(()) => F::next().call(r).0,
+        Err(err) => err.into_handler_status(r),
+    }
+}
+
+/// Installs the header filter at the top of the filter chain.
+///
+/// This function must be called from the module's `postconfiguration()` function. The filters
+/// are invoked in the reverse order of installation.
+pub fn install_header_filter<F>()
+where
+    F: HeaderFilter,
+{
+    // SAFETY: the configuration is processed in a single thread.
+    unsafe {
+        let top = &raw mut ngx_http_top_header_filter;
+        F::next().set(*top);
+        *top = Some(raw_header_filter::<F>);
+    }
+    register_header_filter(raw_header_filter::<F> as usize, F::name(), F::next());
+}
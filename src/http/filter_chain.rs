@@ -0,0 +1,174 @@
+//! Introspection of the response filter chains.
+//!
+//! nginx keeps the filters in a singly linked chain: each filter module stores the previous top of
+//! the chain in a static variable when installing the filter. The pointers stored by the native
+//! modules are not accessible, so the chain can only be followed through the filters installed
+//! with [`install_header_filter`](crate::http::install_header_filter) and
+//! [`install_body_filter`](crate::http::install_body_filter).
+//!
+//! The position of a filter relative to the native filters, e.g. `gzip` or `chunked`, follows the
+//! order of the modules: the filters are installed from the `postconfiguration()` handlers, and a
+//! filter of a module listed later runs earlier. [`http_filter_modules`] lists the native filter
+//! modules in the order of invocation.
+use core::cell::UnsafeCell;
+use core::ffi::CStr;
+
+use crate::ffi::{
+    NGX_HTTP_MODULE, ngx_conf_t, ngx_http_top_body_filter, ngx_http_top_header_filter, ngx_module_t,
+};
+use crate::http::{NextBodyFilter, NextHeaderFilter};
+
+/// Maximum number of the filters tracked for the introspection.
+const MAX_FILTERS: usize = 64;
+
+/// A filter in a response filter chain.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FilterInfo {
+    /// Filter installed with this crate, with the name returned by `HeaderFilter::name()` or
+    /// `BodyFilter::name()`.
+    Rust(&'static str),
+    /// Native filter function, identified by the address.
+    Native(usize),
+}
+
+#[derive(Clone, Copy)]
+enum Next {
+    Header(&'static NextHeaderFilter),
+    Body(&'static NextBodyFilter),
+}
+
+impl Next {
+    fn get(&self) -> usize {
+        match self {
+            Self::Header(next) => next.get().map_or(0, |f| f as usize),
+            Self::Body(next) => next.get().map_or(0, |f| f as usize),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Entry {
+    filter: usize,
+    name: &'static str,
+    next: Next,
+}
+
+struct Registry(UnsafeCell<[Option<Entry>; MAX_FILTERS]>);
+
+// SAFETY: the registry is only modified while processing the configuration, which happens in a
+// single thread.
+unsafe impl Sync for Registry {}
+
+static REGISTRY: Registry = Registry(UnsafeCell::new([None; MAX_FILTERS]));
+
+impl Registry {
+    fn entries(&self) -> &[Option<Entry>; MAX_FILTERS] {
+        // SAFETY: see `unsafe impl Sync`.
+        unsafe { &*self.0.get() }
+    }
+
+    fn find(&self, filter: usize) -> Option<&Entry> {
+        self.entries().iter().flatten().find(|x| x.filter == filter)
+    }
+
+    fn add(&self, entry: Entry) {
+        // SAFETY: see `unsafe impl Sync`.
+        let entries = unsafe { &mut *self.0.get() };
+        // The filters are installed again on each configuration reload.
+        let slot = entries.iter_mut().find(|x| x.is_none_or(|x| x.filter == entry.filter));
+        if let Some(slot) = slot {
+            *slot = Some(entry);
+        }
+    }
+}
+
+pub(crate) fn register_header_filter(
+    filter: usize,
+    name: &'static str,
+    next: &'static NextHeaderFilter,
+) {
+    REGISTRY.add(Entry { filter, name, next: Next::Header(next) });
+}
+
+pub(crate) fn register_body_filter(
+    filter: usize,
+    name: &'static str,
+    next: &'static NextBodyFilter,
+) {
+    REGISTRY.add(Entry { filter, name, next: Next::Body(next) });
+}
+
+/// Iterator over a response filter chain, see [`header_filters`] and [`body_filters`].
+///
+/// The iteration ends after the first native filter, as the following filters are not known.
+#[derive(Clone, Debug)]
+pub struct FilterChainIter {
+    filter: usize,
+}
+
+impl Iterator for FilterChainIter {
+    type Item = FilterInfo;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.filter == 0 {
+            return None;
+        }
+
+        match REGISTRY.find(self.filter) {
+            Some(entry) => {
+                self.filter = entry.next.get();
+                Some(FilterInfo::Rust(entry.name))
+            }
+            None => Some(FilterInfo::Native(core::mem::take(&mut self.filter))),
+        }
+    }
+}
+
+/// Returns an iterator over the header filter chain, starting from the top.
+///
+/// ```no_run
+/// # fn postconfiguration(cf: &mut ngx::ffi::ngx_conf_t) {
+/// for filter in ngx::http::header_filters() {
+///     ngx::ngx_conf_log_error!(ngx::ffi::NGX_LOG_NOTICE, cf, "header filter: {filter:?}");
+/// }
+/// # }
+/// ```
+pub fn header_filters() -> FilterChainIter {
+    // SAFETY: the configuration is processed in a single thread.
+    let top = unsafe { ngx_http_top_header_filter };
+    FilterChainIter { filter: top.map_or(0, |f| f as usize) }
+}
+
+/// Returns an iterator over the body filter chain, starting from the top.
+pub fn body_filters() -> FilterChainIter {
+    // SAFETY: the configuration is processed in a single thread.
+    let top = unsafe { ngx_http_top_body_filter };
+    FilterChainIter { filter: top.map_or(0, |f| f as usize) }
+}
+
+/// Returns the names of the native HTTP filter modules, e.g. `ngx_http_gzip_filter_module`, in the
+/// order the filters are invoked.
+///
+/// Only the modules named with the `_filter_module` suffix, as done by nginx, are included. The
+/// function must be called from a configuration handler, as the list of modules is taken from the
+/// cycle being configured.
+pub fn http_filter_modules(cf: &ngx_conf_t) -> impl Iterator<Item = &'static CStr> + '_ {
+    // SAFETY: the cycle being configured has a valid array of `modules_n` modules.
+    let modules: &[*mut ngx_module_t] = unsafe {
+        let cycle = &*cf.cycle;
+        if cycle.modules.is_null() {
+            &[]
+        } else {
+            core::slice::from_raw_parts(cycle.modules, cycle.modules_n)
+        }
+    };
+
+    modules.iter().rev().filter_map(|&m| {
+        // SAFETY: the module name is either NULL or a static nul-terminated string set by
+        // ngx_preinit_modules() or ngx_add_module().
+        let m = unsafe { &*m };
+        let name = unsafe { m.name.as_ref() }.map(|x| unsafe { CStr::from_ptr(x) })?;
+        (m.type_ == NGX_HTTP_MODULE as usize && name.to_bytes().ends_with(b"_filter_module"))
+            .then_some(name)
+    })
+}
@@ -0,0 +1,182 @@
+use core::fmt;
+
+use crate::core::{ListIter, NgxList, NgxStr};
+use crate::ffi::{ngx_list_t, ngx_table_elt_t, ngx_uint_t};
+use crate::http::Request;
+
+/// Borrowed view of a request or response header.
+#[derive(Clone, Copy)]
+pub struct Header<'a>(&'a ngx_table_elt_t);
+
+impl<'a> Header<'a> {
+    /// Creates a header view from a raw header entry.
+    pub fn from_ngx_table_elt(h: &'a ngx_table_elt_t) -> Self {
+        Self(h)
+    }
+
+    /// Returns the header name, in the original case.
+    pub fn key(&self) -> &'a NgxStr {
+        // SAFETY: the key is allocated from the request pool or is static.
+        unsafe { NgxStr::from_ngx_str(self.0.key) }
+    }
+
+    /// Returns the header value.
+    pub fn value(&self) -> &'a NgxStr {
+        // SAFETY: the value is allocated from the request pool or is static.
+        unsafe { NgxStr::from_ngx_str(self.0.value) }
+    }
+
+    /// Returns the lowercase header name.
+    ///
+    /// The lowercase name is always set for the request headers, but is often missing for the
+    /// response headers.
+    pub fn lowcase_key(&self) -> Option<&'a NgxStr> {
+        if self.0.lowcase_key.is_null() {
+            return None;
+        }
+        // SAFETY: the lowercase key has the same length as the key.
+        let key = unsafe { core::slice::from_raw_parts(self.0.lowcase_key, self.0.key.len) };
+        Some(NgxStr::from_bytes(key))
+    }
+
+    /// Returns the hash of the lowercase header name.
+    ///
+    /// nginx sets the hash of a response header to 0 to remove it from the response.
+    pub fn hash(&self) -> ngx_uint_t {
+        self.0.hash
+    }
+
+    /// Returns `true` if the header name matches `name`, ignoring ASCII case.
+    pub fn is(&self, name: impl AsRef<[u8]>) -> bool {
+        self.key().as_bytes().eq_ignore_ascii_case(name.as_ref())
+    }
+
+    /// Returns a reference to the underlying [`ngx_table_elt_t`].
+    pub fn as_ngx_table_elt(&self) -> &'a ngx_table_elt_t {
+        self.0
+    }
+}
+
+impl fmt::Debug for Header<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Header").field(&self.key()).field(&self.value()).finish()
+    }
+}
+
+impl fmt::Display for Header<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.key(), self.value())
+    }
+}
+
+/// Borrowed view of a list of request or response headers, returned by [`Request::headers_in`]
+/// and [`Request::headers_out`].
+///
+/// The lookup methods compare the names ignoring ASCII case and skip the response headers removed
+/// by setting the hash to 0.
+#[derive(Clone, Copy)]
+pub struct Headers<'a>(&'a NgxList<ngx_table_elt_t>);
+
+impl<'a> Headers<'a> {
+    /// Creates a view of a raw list of headers.
+    ///
+    /// # Safety
+    ///
+    /// The list must be initialized and contain [`ngx_table_elt_t`] elements.
+    pub unsafe fn from_ngx_list(list: &'a ngx_list_t) -> Self {
+        Self(unsafe { NgxList::from_ngx_list(list) })
+    }
+
+    /// Returns an iterator over all the headers, including the removed ones.
+    pub fn iter(&self) -> NgxListIterator<'a> {
+        NgxListIterator(self.0.iter())
+    }
+
+    /// Returns the first header with the name.
+    pub fn get(&self, name: impl AsRef<[u8]>) -> Option<Header<'a>> {
+        self.get_all(name).next()
+    }
+
+    /// Returns an iterator over all the headers with the name.
+    pub fn get_all<N>(&self, name: N) -> impl Iterator<Item = Header<'a>> + 'a
+    where
+        N: AsRef<[u8]> + 'a,
+    {
+        self.iter().filter(move |h| h.hash() != 0 && h.is(name.as_ref()))
+    }
+
+    /// Returns `true` if a header with the name is present.
+    pub fn contains(&self, name: impl AsRef<[u8]>) -> bool {
+        self.get(name).is_some()
+    }
+}
+
+impl<'a> IntoIterator for Headers<'a> {
+    type Item = Header<'a>;
+    type IntoIter = NgxListIterator<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl fmt::Debug for Headers<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+/// Iterator over the headers in an [`ngx_list_t`].
+///
+/// The items borrow the header names and values from the list.
+pub struct NgxListIterator<'a>(ListIter<'a, ngx_table_elt_t>);
+
+/// Creates new HTTP header iterator
+///
+/// # Safety
+///
+/// The caller has provided a valid [`ngx_list_t`] of [`ngx_table_elt_t`] elements.
+pub unsafe fn list_iterator(list: &ngx_list_t) -> NgxListIterator<'_> {
+    NgxListIterator(unsafe { ListIter::from_ngx_list(list) })
+}
+
+impl<'a> Iterator for NgxListIterator<'a> {
+    type Item = Header<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(Header)
+    }
+}
+
+impl Request {
+    /// Returns the request headers.
+    ///
+    /// ```no_run
+    /// # use ngx::http::Request;
+    /// # fn handler(r: &Request) {
+    /// if let Some(auth) = r.headers_in().get("Authorization") {
+    ///     ngx::ngx_log_debug_http!(r, "authorization: {}", auth.value());
+    /// }
+    /// # }
+    /// ```
+    pub fn headers_in(&self) -> Headers<'_> {
+        // SAFETY: `headers_in.headers` is initialized when the request is created.
+        unsafe { Headers::from_ngx_list(&self.as_ref().headers_in.headers) }
+    }
+
+    /// Returns the response headers.
+    pub fn headers_out(&self) -> Headers<'_> {
+        // SAFETY: `headers_out.headers` is initialized when the request is created.
+        unsafe { Headers::from_ngx_list(&self.as_ref().headers_out.headers) }
+    }
+
+    /// Iterate over headers_in
+    pub fn headers_in_iterator(&self) -> NgxListIterator<'_> {
+        self.headers_in().iter()
+    }
+
+    /// Iterate over headers_out
+    pub fn headers_out_iterator(&self) -> NgxListIterator<'_> {
+        self.headers_out().iter()
+    }
+}
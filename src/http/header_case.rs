@@ -0,0 +1,139 @@
+use crate::ffi::{
+    ngx_hash_strlow, ngx_list_part_t, ngx_list_push, ngx_pnalloc, ngx_pool_t, ngx_str_t,
+    ngx_table_elt_t,
+};
+use crate::http::Request;
+use crate::http::compat::init_table_elt;
+
+/// Case of the response header names.
+///
+/// HTTP/1.x header names are sent exactly as stored in the `key` of the header entry, while the
+/// header lookups use the lowercase copy in `lowcase_key`. HTTP/2 and HTTP/3 always send the
+/// names in lowercase, so the case only matters for HTTP/1.x clients.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum HeaderCase {
+    /// Keep the name as is.
+    #[default]
+    Preserve,
+    /// Convert the name to lowercase, e.g. `x-request-id`.
+    Lower,
+    /// Capitalize each dash-separated word, e.g. `X-Request-Id`.
+    Title,
+}
+
+impl HeaderCase {
+    /// Writes `name` converted to this case into `out`.
+    ///
+    /// `out` must have the same length as `name`.
+    pub fn convert(self, name: &[u8], out: &mut [u8]) {
+        debug_assert_eq!(name.len(), out.len());
+
+        let mut start = true;
+        for (dst, &ch) in out.iter_mut().zip(name) {
+            *dst = match self {
+                HeaderCase::Preserve => ch,
+                HeaderCase::Lower => ch.to_ascii_lowercase(),
+                HeaderCase::Title if start => ch.to_ascii_uppercase(),
+                HeaderCase::Title => ch.to_ascii_lowercase(),
+            };
+            start = ch == b'-';
+        }
+    }
+}
+
+impl Request {
+    /// Adds a header to the `headers_out` object, with the name converted to `case`.
+    ///
+    /// The original name is converted when stored as the `key` of the header entry, and the
+    /// `lowcase_key` used for lookups is computed as usual.
+    pub fn add_header_out_cased(&mut self, key: &str, value: &str, case: HeaderCase) -> Option<()> {
+        let pool = self.as_ref().pool;
+
+        // SAFETY: `headers_out.headers` is initialized when the request is created.
+        let h: *mut ngx_table_elt_t =
+            unsafe { ngx_list_push(&raw mut self.as_mut().headers_out.headers).cast() };
+        let h = unsafe { h.as_mut()? };
+
+        // Prevent sending a partially initialized entry on allocation failure.
+        init_table_elt(h);
+
+        // SAFETY: the strings are allocated from the request pool.
+        unsafe {
+            h.key = cased_str(pool, key.as_bytes(), case)?;
+            h.value = ngx_str_t::from_bytes(pool, value.as_bytes())?;
+            h.lowcase_key = ngx_pnalloc(pool, h.key.len).cast();
+            if h.lowcase_key.is_null() {
+                return None;
+            }
+            h.hash = ngx_hash_strlow(h.lowcase_key, h.key.data, h.key.len);
+        }
+
+        Some(())
+    }
+
+    /// Converts the names of all the headers in the `headers_out` list to `case`.
+    ///
+    /// Only affects the headers stored in the list, the headers generated by the nginx header
+    /// filter from the `headers_out` fields, e.g. `Content-Type` or `Date`, are always sent
+    /// capitalized. Should be called before sending the response header.
+    pub fn set_headers_out_case(&mut self, case: HeaderCase) -> Option<()> {
+        if case == HeaderCase::Preserve {
+            return Some(());
+        }
+
+        let pool = self.as_ref().pool;
+        let mut part: *mut ngx_list_part_t = &raw mut self.as_mut().headers_out.headers.part;
+
+        // SAFETY: the list parts and entries are valid for the lifetime of the request.
+        while let Some(p) = unsafe { part.as_mut() } {
+            let elts: *mut ngx_table_elt_t = p.elts.cast();
+            for i in 0..p.nelts {
+                let h = unsafe { &mut *elts.add(i) };
+                if h.hash == 0 {
+                    continue;
+                }
+                // The key may point to static or shared memory, always make a copy.
+                h.key = unsafe { cased_str(pool, h.key.as_bytes(), case)? };
+            }
+            part = p.next;
+        }
+
+        Some(())
+    }
+}
+
+/// Allocates a copy of `name` converted to `case` from the pool.
+unsafe fn cased_str(pool: *mut ngx_pool_t, name: &[u8], case: HeaderCase) -> Option<ngx_str_t> {
+    let data: *mut u8 = unsafe { ngx_pnalloc(pool, name.len()).cast() };
+    if data.is_null() {
+        return None;
+    }
+    // SAFETY: `data` is a fresh allocation of `name.len()` bytes.
+    case.convert(name, unsafe { core::slice::from_raw_parts_mut(data, name.len()) });
+    Some(ngx_str_t { len: name.len(), data })
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use std::string::String;
+    use std::vec;
+
+    use super::*;
+
+    fn convert(case: HeaderCase, name: &str) -> String {
+        let mut out = vec![0; name.len()];
+        case.convert(name.as_bytes(), &mut out);
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_header_case() {
+        assert_eq!(convert(HeaderCase::Preserve, "x-REQUEST-id"), "x-REQUEST-id");
+        assert_eq!(convert(HeaderCase::Lower, "X-Request-ID"), "x-request-id");
+        assert_eq!(convert(HeaderCase::Title, "x-REQUEST-id"), "X-Request-Id");
+        assert_eq!(convert(HeaderCase::Title, "www-authenticate"), "Www-Authenticate");
+        assert_eq!(convert(HeaderCase::Title, "-a--b"), "-A--B");
+    }
+}
@@ -0,0 +1,213 @@
+//! Setters for the response headers with dedicated fields in `ngx_http_headers_out_t`.
+//!
+//! nginx keeps pointers to the entries of some known headers, e.g. `Location` or
+//! `WWW-Authenticate`, and the header filter and other modules use the pointers instead of
+//! searching the list. Appending such a header with [`Request::add_header_out`] leaves the pointer
+//! unset or pointing to a stale entry, and may result in duplicate headers.
+
+use crate::core::{NgxStr, Status};
+use crate::ffi::{add_to_ngx_table, ngx_list_push, ngx_str_t, ngx_table_elt_t};
+use crate::http::Request;
+use crate::http::compat::init_table_elt;
+
+impl Request {
+    /// Sets the response `Location` header, replacing the existing one.
+    ///
+    /// A relative value starting with `/` is converted to an absolute URL by the header filter,
+    /// unless disabled with the `absolute_redirect` directive.
+    pub fn set_location(&mut self, value: &[u8]) -> Status {
+        match self.replace_header_out("Location", value) {
+            Some(h) => {
+                self.as_mut().headers_out.location = h;
+                Status::NGX_OK
+            }
+            None => Status::NGX_ERROR,
+        }
+    }
+
+    /// Sets the response `Content-Type` without the charset.
+    ///
+    /// The value may include parameters, which are sent as is. The charset previously set for the
+    /// response is cleared.
+    pub fn set_content_type(&mut self, value: &[u8]) -> Status {
+        let Some(value) = (unsafe { ngx_str_t::from_bytes(self.as_ref().pool, value) }) else {
+            return Status::NGX_ERROR;
+        };
+
+        self.set_content_type_fields(value, mime_type_len(value.as_bytes()), ngx_str_t::empty());
+        Status::NGX_OK
+    }
+
+    /// Sets the response `Content-Type` with the charset, e.g. `text/html` and `utf-8`.
+    ///
+    /// The charset is kept separately from the media type, as done by nginx, so that the `charset`
+    /// filter can recode the response and the header filter appends `; charset=...` to the value.
+    /// If `mime` includes parameters, the charset parameter is appended to the value instead, as the
+    /// header filter only appends the charset to a bare media type.
+    pub fn set_content_type_with_charset(&mut self, mime: &[u8], charset: &[u8]) -> Status {
+        let mime = mime.trim_ascii();
+        let type_len = mime_type_len(mime);
+        let pool = self.as_ref().pool;
+
+        if charset.is_empty() {
+            return self.set_content_type(mime);
+        }
+
+        if type_len == mime.len() {
+            let value = unsafe { ngx_str_t::from_bytes(pool, mime) };
+            let charset = unsafe { ngx_str_t::from_bytes(pool, charset) };
+            let (Some(value), Some(charset)) = (value, charset) else {
+                return Status::NGX_ERROR;
+            };
+            self.set_content_type_fields(value, type_len, charset);
+            return Status::NGX_OK;
+        }
+
+        let len = mime.len() + b"; charset=".len() + charset.len();
+        let data: *mut u8 = self.pool().alloc_unaligned(len).cast();
+        if data.is_null() {
+            return Status::NGX_ERROR;
+        }
+
+        // SAFETY: `data` points to `len` bytes of uninitialized memory allocated above.
+        let buf = unsafe { core::slice::from_raw_parts_mut(data, len) };
+        let (head, tail) = buf.split_at_mut(mime.len());
+        head.copy_from_slice(mime);
+        let (sep, tail) = tail.split_at_mut(b"; charset=".len());
+        sep.copy_from_slice(b"; charset=");
+        tail.copy_from_slice(charset);
+
+        let value = ngx_str_t { len, data };
+        self.set_content_type_fields(value, type_len, ngx_str_t::empty());
+        Status::NGX_OK
+    }
+
+    /// Returns the response `Content-Type`, without the charset.
+    pub fn content_type(&self) -> &NgxStr {
+        // SAFETY: the value is allocated from the request pool or is static.
+        unsafe { NgxStr::from_ngx_str(self.as_ref().headers_out.content_type) }
+    }
+
+    /// Returns the response charset, if set separately from the `Content-Type`.
+    pub fn charset(&self) -> Option<&NgxStr> {
+        let charset = self.as_ref().headers_out.charset;
+        // SAFETY: the value is allocated from the request pool or is static.
+        (charset.len != 0).then(|| unsafe { NgxStr::from_ngx_str(charset) })
+    }
+
+    fn set_content_type_fields(&mut self, value: ngx_str_t, type_len: usize, charset: ngx_str_t) {
+        let headers = &mut self.as_mut().headers_out;
+        headers.content_type = value;
+        headers.content_type_len = type_len;
+        headers.content_type_lowcase = core::ptr::null_mut();
+        headers.content_type_hash = 0;
+        headers.charset = charset;
+    }
+
+    /// Sets the response `Content-Encoding` header, replacing the existing one.
+    pub fn set_content_encoding(&mut self, value: &[u8]) -> Status {
+        match self.replace_header_out("Content-Encoding", value) {
+            Some(h) => {
+                self.as_mut().headers_out.content_encoding = h;
+                Status::NGX_OK
+            }
+            None => Status::NGX_ERROR,
+        }
+    }
+
+    /// Sets the response `WWW-Authenticate` header, replacing all the existing challenges.
+    pub fn set_www_authenticate(&mut self, challenge: &[u8]) -> Status {
+        match self.replace_header_out("WWW-Authenticate", challenge) {
+            Some(h) => {
+                self.as_mut().headers_out.www_authenticate = h;
+                Status::NGX_OK
+            }
+            None => Status::NGX_ERROR,
+        }
+    }
+
+    /// Adds a challenge to the response `WWW-Authenticate` headers.
+    pub fn add_www_authenticate(&mut self, challenge: &[u8]) -> Status {
+        let Some(h) = self.push_header_out("WWW-Authenticate", challenge) else {
+            return Status::NGX_ERROR;
+        };
+
+        let headers = &mut self.as_mut().headers_out;
+
+        // nginx 1.23.0 links the headers with the same name, see 5cdcc9d2ab2a
+        #[cfg(nginx1_23_0)]
+        {
+            let mut last = headers.www_authenticate;
+            // SAFETY: the linked entries are valid for the lifetime of the request.
+            while let Some(prev) = unsafe { last.as_mut() } {
+                if prev.next.is_null() {
+                    prev.next = h;
+                    return Status::NGX_OK;
+                }
+                last = prev.next;
+            }
+        }
+
+        if headers.www_authenticate.is_null() {
+            headers.www_authenticate = h;
+        }
+        Status::NGX_OK
+    }
+
+    /// Sets the response `Content-Disposition` header, replacing the existing one.
+    ///
+    /// nginx has no dedicated field for the header, but it must not be repeated.
+    pub fn set_content_disposition(&mut self, value: &[u8]) -> Status {
+        match self.replace_header_out("Content-Disposition", value) {
+            Some(_) => Status::NGX_OK,
+            None => Status::NGX_ERROR,
+        }
+    }
+
+    /// Sets the response `Refresh` header, replacing the existing one.
+    pub fn set_refresh(&mut self, value: &[u8]) -> Status {
+        match self.replace_header_out("Refresh", value) {
+            Some(h) => {
+                self.as_mut().headers_out.refresh = h;
+                Status::NGX_OK
+            }
+            None => Status::NGX_ERROR,
+        }
+    }
+
+    /// Removes all the response headers named `key` and appends a new one.
+    fn replace_header_out(&mut self, key: &str, value: &[u8]) -> Option<*mut ngx_table_elt_t> {
+        self.remove_header_out(key);
+        self.push_header_out(key, value)
+    }
+
+    /// Appends a response header, returning the new list entry.
+    fn push_header_out(&mut self, key: &str, value: &[u8]) -> Option<*mut ngx_table_elt_t> {
+        let r = self.as_mut();
+        let h: *mut ngx_table_elt_t =
+            unsafe { ngx_list_push(&raw mut r.headers_out.headers).cast() };
+        // SAFETY: `h` is either NULL or a list element allocated above.
+        init_table_elt(unsafe { h.as_mut()? });
+        // The hash remains 0, i.e. the header is not sent, if the allocation fails.
+        unsafe { add_to_ngx_table(h, r.pool, key, value)? };
+        Some(h)
+    }
+}
+
+/// Returns the length of the media type in a `Content-Type` value, without the parameters.
+fn mime_type_len(value: &[u8]) -> usize {
+    value.iter().position(|&x| x == b';' || x == b' ' || x == b'\t').unwrap_or(value.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::mime_type_len;
+
+    #[test]
+    fn test_mime_type_len() {
+        assert_eq!(mime_type_len(b"text/html"), 9);
+        assert_eq!(mime_type_len(b"text/html; charset=utf-8"), 9);
+        assert_eq!(mime_type_len(b"multipart/form-data;boundary=x"), 19);
+        assert_eq!(mime_type_len(b""), 0);
+    }
+}
@@ -1,10 +1,81 @@
+#[cfg(feature = "alloc")]
+pub mod access;
+mod build_info;
+#[cfg(ngx_feature = "http_cache")]
+mod cache;
+#[cfg(any(ngx_feature = "http_ssl", feature = "feature-stubs"))]
+mod client_cert;
+mod compat;
+mod complex_value;
+mod conditional;
 mod conf;
+mod context;
+mod enable;
+mod error_body;
+mod filter;
+mod filter_chain;
+mod header;
+mod header_case;
+mod headers_out;
 mod module;
+pub mod multipart;
+#[cfg(feature = "alloc")]
+pub mod negotiate;
+mod normalize;
+mod postpone;
+mod protocol;
+mod range;
+mod redirect;
 mod request;
+mod request_body;
+mod responder;
+mod response_writer;
+#[cfg(feature = "alloc")]
+mod runtime_status;
+mod script;
+mod server;
 mod status;
+mod subrequest;
+#[cfg(feature = "alloc")]
+pub mod substitution;
+mod synthetic;
+mod timeout;
 mod upstream;
+mod variable;
 
+pub use build_info::*;
+#[cfg(ngx_feature = "http_cache")]
+pub use cache::*;
+#[cfg(any(ngx_feature = "http_ssl", feature = "feature-stubs"))]
+pub use client_cert::*;
+pub use compat::HeaderValues;
+pub use complex_value::*;
+pub use conditional::*;
 pub use conf::*;
+pub use context::RequestContext;
+pub use enable::{EnableFlag, enable_flag_slot};
+pub use error_body::{ErrorBodyFilter, install_error_body_filter};
+pub use filter::*;
+pub use filter_chain::{
+    FilterChainIter, FilterInfo, body_filters, header_filters, http_filter_modules,
+};
+pub use header::*;
+pub use header_case::*;
 pub use module::*;
+pub use normalize::*;
+pub use postpone::*;
+pub use protocol::HttpVersion;
+pub use range::*;
 pub use request::*;
+pub use request_body::*;
+pub use responder::*;
+pub use response_writer::*;
+#[cfg(feature = "alloc")]
+pub use runtime_status::*;
+pub use script::*;
+pub use server::*;
 pub use status::*;
+pub use subrequest::SubrequestFlags;
+pub use synthetic::*;
+pub use upstream::*;
+pub use variable::*;
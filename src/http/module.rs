@@ -1,46 +1,11 @@
-use core::error;
 use core::ffi::{c_char, c_void};
-use core::fmt;
 use core::ptr;
 
 use crate::core::NGX_CONF_ERROR;
 use crate::core::*;
 use crate::ffi::*;
 
-/// MergeConfigError - configuration cannot be merged with levels above.
-#[derive(Debug)]
-pub enum MergeConfigError {
-    /// No value provided for configuration argument
-    NoValue,
-}
-
-impl error::Error for MergeConfigError {}
-
-impl fmt::Display for MergeConfigError {
-    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
-        match self {
-            MergeConfigError::NoValue => "no value".fmt(fmt),
-        }
-    }
-}
-
-/// The `Merge` trait provides a method for merging configuration down through each level.
-///
-/// A module configuration should implement this trait for setting its configuration throughout
-/// each level.
-pub trait Merge {
-    /// Module merge function.
-    ///
-    /// # Returns
-    /// Result, Ok on success or MergeConfigError on failure.
-    fn merge(&mut self, prev: &Self) -> Result<(), MergeConfigError>;
-}
-
-impl Merge for () {
-    fn merge(&mut self, _prev: &Self) -> Result<(), MergeConfigError> {
-        Ok(())
-    }
-}
+pub use crate::core::{Merge, MergeConfigError};
 
 /// The `HTTPModule` trait provides the NGINX configuration stage interface.
 ///
@@ -171,3 +136,111 @@ pub trait HttpModule {
         }
     }
 }
+
+/// Defines the [`ngx_module_t`] static of an HTTP module.
+///
+/// The macro generates the module context with the [`HttpModule`] handlers of the type, the
+/// module static, and the `ngx_modules` table when building with the `export-modules` feature
+/// of the calling crate. The configuration handlers are set for the levels listed in `conf`:
+/// `main`, `srv` and `loc`, which require the corresponding [`HttpModuleMainConf`],
+/// [`HttpModuleServerConf`] and [`HttpModuleLocationConf`] implementations. The `commands`,
+/// `conf` and `lifecycle` fields are optional, in this order.
+///
+/// ```no_run
+/// # use ngx::core::ProcessLifecycle;
+/// # use ngx::ffi::{ngx_command_t, ngx_module_t};
+/// # use ngx::http::{EnableFlag, HttpModule, HttpModuleLocationConf};
+/// struct Module;
+///
+/// impl HttpModule for Module {
+///     fn module() -> &'static ngx_module_t {
+///         unsafe { &*::core::ptr::addr_of!(ngx_http_foo_module) }
+///     }
+/// }
+///
+/// unsafe impl HttpModuleLocationConf for Module {
+///     type LocationConf = EnableFlag;
+/// }
+///
+/// impl ProcessLifecycle for Module {
+///     type Error = &'static str;
+/// }
+///
+/// static mut NGX_HTTP_FOO_COMMANDS: [ngx_command_t; 1] = [ngx_command_t::empty()];
+///
+/// ngx::ngx_http_module! {
+///     pub static mut ngx_http_foo_module = Module {
+///         commands: NGX_HTTP_FOO_COMMANDS,
+///         conf: [loc],
+///         lifecycle: Module,
+///     }
+/// }
+/// ```
+///
+/// [`HttpModuleMainConf`]: crate::http::HttpModuleMainConf
+/// [`HttpModuleServerConf`]: crate::http::HttpModuleServerConf
+/// [`HttpModuleLocationConf`]: crate::http::HttpModuleLocationConf
+#[macro_export]
+macro_rules! ngx_http_module {
+    (
+        $(#[$attr:meta])*
+        $vis:vis static mut $name:ident = $module:ty {
+            $(commands: $commands:expr,)?
+            $(conf: [$($conf:ident),* $(,)?],)?
+            $(lifecycle: $lifecycle:ty $(,)?)?
+        }
+    ) => {
+        // Generate the `ngx_modules` table with exported modules.
+        // This feature is required to build a 'cdylib' dynamic module outside of the NGINX
+        // buildsystem.
+        #[cfg(feature = "export-modules")]
+        $crate::ngx_modules!($name);
+
+        $(#[$attr])*
+        #[used]
+        #[allow(non_upper_case_globals)]
+        #[cfg_attr(not(feature = "export-modules"), unsafe(no_mangle))]
+        $vis static mut $name: $crate::ffi::ngx_module_t = {
+            static CTX: $crate::ffi::ngx_http_module_t = {
+                #[allow(unused_mut)]
+                let mut ctx = $crate::ffi::ngx_http_module_t {
+                    preconfiguration: Some(<$module as $crate::http::HttpModule>::preconfiguration),
+                    postconfiguration: Some(
+                        <$module as $crate::http::HttpModule>::postconfiguration,
+                    ),
+                    create_main_conf: None,
+                    init_main_conf: None,
+                    create_srv_conf: None,
+                    merge_srv_conf: None,
+                    create_loc_conf: None,
+                    merge_loc_conf: None,
+                };
+                $($( $crate::ngx_http_module!(@conf ctx, $module, $conf); )*)?
+                ctx
+            };
+
+            #[allow(unused_mut)]
+            let mut module = $crate::ffi::ngx_module_t {
+                ctx: &raw const CTX as _,
+                type_: $crate::ffi::NGX_HTTP_MODULE as _,
+                ..$crate::ffi::ngx_module_t::default()
+            };
+            $( module.commands = unsafe { &raw mut $commands[0] }; )?
+            $( module = $crate::core::with_process_lifecycle::<$lifecycle>(module); )?
+            module
+        };
+    };
+
+    (@conf $ctx:ident, $module:ty, main) => {
+        $ctx.create_main_conf = Some(<$module as $crate::http::HttpModule>::create_main_conf);
+        $ctx.init_main_conf = Some(<$module as $crate::http::HttpModule>::init_main_conf);
+    };
+    (@conf $ctx:ident, $module:ty, srv) => {
+        $ctx.create_srv_conf = Some(<$module as $crate::http::HttpModule>::create_srv_conf);
+        $ctx.merge_srv_conf = Some(<$module as $crate::http::HttpModule>::merge_srv_conf);
+    };
+    (@conf $ctx:ident, $module:ty, loc) => {
+        $ctx.create_loc_conf = Some(<$module as $crate::http::HttpModule>::create_loc_conf);
+        $ctx.merge_loc_conf = Some(<$module as $crate::http::HttpModule>::merge_loc_conf);
+    };
+}
@@ -0,0 +1,652 @@
+//! Streaming `multipart/form-data` parser.
+//!
+//! See [RFC 7578](https://datatracker.ietf.org/doc/html/rfc7578) and
+//! [RFC 2046, Section 5.1](https://datatracker.ietf.org/doc/html/rfc2046#section-5.1).
+use core::{error, fmt, mem, ptr, slice};
+
+use crate::core::{Pool, Status};
+use crate::ffi::{
+    NGX_ERROR, NGX_INVALID_FILE, ngx_buf_t, ngx_chain_t, ngx_create_temp_buf, ngx_log_t,
+    ngx_path_t, ngx_temp_file_t, ngx_write_chain_to_temp_file,
+};
+use crate::http::{HttpModuleLocationConf, NgxHttpCoreModule, Request};
+use crate::parse::{StateMachine, Step};
+#[cfg(feature = "alloc")]
+use crate::{
+    allocator::Allocator,
+    parse::{ParseError, Resumable},
+};
+
+/// Maximum length of the boundary parameter, as defined in RFC 2046.
+const MAX_BOUNDARY_LEN: usize = 70;
+
+/// Event produced by [`MultipartParser`].
+#[derive(Debug, PartialEq, Eq)]
+pub enum Event<'a> {
+    /// Start of a new body part.
+    PartStart,
+    /// Header field of the current part.
+    Header {
+        /// Field name.
+        name: &'a [u8],
+        /// Field value, with leading and trailing whitespace removed.
+        value: &'a [u8],
+    },
+    /// End of the header section of the current part.
+    HeadersEnd,
+    /// Chunk of the current part data.
+    Data(&'a [u8]),
+    /// End of the current part.
+    PartEnd,
+    /// End of the multipart body.
+    End,
+}
+
+/// Errors returned by [`MultipartParser`].
+#[derive(Debug, PartialEq, Eq)]
+pub enum MultipartError {
+    /// Unexpected characters after a boundary delimiter.
+    InvalidBoundary,
+    /// Malformed part header field.
+    InvalidHeader,
+    /// Request body buffer is not in memory.
+    BufferInFile,
+}
+
+impl fmt::Display for MultipartError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MultipartError::InvalidBoundary => f.write_str("invalid multipart boundary"),
+            MultipartError::InvalidHeader => f.write_str("invalid multipart header"),
+            MultipartError::BufferInFile => f.write_str("multipart body buffer is not in memory"),
+        }
+    }
+}
+
+impl error::Error for MultipartError {}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum State {
+    Start,
+    Preamble,
+    Boundary,
+    Headers,
+    Data,
+    Epilogue,
+}
+
+/// Incremental `multipart/form-data` parser.
+///
+/// The parser implements [`StateMachine`] and is expected to be used with a driver, such as
+/// [`Resumable`]. Part data is passed through without copying; the driver only needs to buffer
+/// incomplete header lines and boundary delimiters split across buffers.
+#[derive(Debug)]
+pub struct MultipartParser<'b> {
+    boundary: &'b [u8],
+    state: State,
+}
+
+impl<'b> MultipartParser<'b> {
+    /// Creates a parser for the body with the specified `boundary`.
+    pub fn new(boundary: &'b [u8]) -> Self {
+        Self { boundary, state: State::Start }
+    }
+
+    /// Creates a parser for the request body, using the boundary from the `Content-Type` header.
+    ///
+    /// Returns `None` if the request does not have a multipart body.
+    pub fn from_request(r: &'b Request) -> Option<Self> {
+        // SAFETY: `content_type` is either NULL or points to a header list element allocated from
+        // the request pool.
+        let content_type = unsafe { r.as_ref().headers_in.content_type.as_ref()? };
+        multipart_boundary(content_type.value.as_bytes()).map(Self::new)
+    }
+
+    /// Returns `true` if the closing boundary delimiter was processed.
+    pub fn is_complete(&self) -> bool {
+        self.state == State::Epilogue
+    }
+
+    fn delimiter_len(&self) -> usize {
+        4 + self.boundary.len()
+    }
+
+    /// Finds the first complete or partial (at the end of input) `CRLF "--" boundary` delimiter.
+    fn find_delimiter(&self, input: &[u8]) -> Option<(usize, bool)> {
+        let mut pos = 0;
+
+        while let Some(n) = input[pos..].iter().position(|&x| x == b'\r') {
+            let start = pos + n;
+            let rest = &input[start..];
+            let delimiter = b"\r\n--".iter().chain(self.boundary);
+
+            if rest.iter().zip(delimiter).all(|(a, b)| a == b) {
+                return Some((start, rest.len() >= self.delimiter_len()));
+            }
+
+            pos = start + 1;
+        }
+
+        None
+    }
+}
+
+impl StateMachine for MultipartParser<'_> {
+    type Output<'a> = Event<'a>;
+    type Error = MultipartError;
+
+    fn step<'a>(&mut self, input: &'a [u8]) -> Result<Step<Event<'a>>, MultipartError> {
+        match self.state {
+            State::Start => {
+                // The first delimiter is not required to be preceded by CRLF.
+                let dash_boundary = b"--".iter().chain(self.boundary);
+                if !input.iter().zip(dash_boundary).all(|(a, b)| a == b) {
+                    self.state = State::Preamble;
+                    return self.step(input);
+                }
+
+                let len = 2 + self.boundary.len();
+                if input.len() < len {
+                    return Ok(Step::Incomplete);
+                }
+
+                self.state = State::Boundary;
+                Ok(Step::Advance(len))
+            }
+
+            State::Preamble => match self.find_delimiter(input) {
+                Some((0, true)) => {
+                    self.state = State::Boundary;
+                    Ok(Step::Advance(self.delimiter_len()))
+                }
+                Some((0, false)) => Ok(Step::Incomplete),
+                Some((n, _)) => Ok(Step::Advance(n)),
+                None => Ok(Step::Advance(input.len())),
+            },
+
+            State::Boundary => {
+                // Transport padding.
+                let ws = input.iter().take_while(|&&x| x == b' ' || x == b'\t').count();
+                if ws > 0 {
+                    return Ok(Step::Advance(ws));
+                }
+
+                match input {
+                    [b'-', b'-', ..] => {
+                        self.state = State::Epilogue;
+                        Ok(Step::Emit(2, Event::End))
+                    }
+                    [b'\r', b'\n', ..] => {
+                        self.state = State::Headers;
+                        Ok(Step::Emit(2, Event::PartStart))
+                    }
+                    [b'-'] | [b'\r'] => Ok(Step::Incomplete),
+                    _ => Err(MultipartError::InvalidBoundary),
+                }
+            }
+
+            State::Headers => {
+                let Some(n) = input.windows(2).position(|x| x == b"\r\n") else {
+                    return Ok(Step::Incomplete);
+                };
+
+                if n == 0 {
+                    self.state = State::Data;
+                    return Ok(Step::Emit(2, Event::HeadersEnd));
+                }
+
+                let (name, value) = parse_header(&input[..n])?;
+                Ok(Step::Emit(n + 2, Event::Header { name, value }))
+            }
+
+            State::Data => match self.find_delimiter(input) {
+                Some((0, true)) => {
+                    self.state = State::Boundary;
+                    Ok(Step::Emit(self.delimiter_len(), Event::PartEnd))
+                }
+                Some((0, false)) => Ok(Step::Incomplete),
+                Some((n, _)) => Ok(Step::Emit(n, Event::Data(&input[..n]))),
+                None => Ok(Step::Emit(input.len(), Event::Data(input))),
+            },
+
+            State::Epilogue => Ok(Step::Advance(input.len())),
+        }
+    }
+}
+
+/// Splits a header line into the field name and value.
+fn parse_header(line: &[u8]) -> Result<(&[u8], &[u8]), MultipartError> {
+    let colon = line.iter().position(|&x| x == b':').ok_or(MultipartError::InvalidHeader)?;
+    let (name, value) = (&line[..colon], &line[colon + 1..]);
+
+    if name.is_empty() || !name.iter().all(|&x| is_token_char(x)) {
+        return Err(MultipartError::InvalidHeader);
+    }
+
+    Ok((name, value.trim_ascii()))
+}
+
+fn is_token_char(x: u8) -> bool {
+    x.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&x)
+}
+
+/// Finds a parameter in a `;`-separated list of `name=value` pairs.
+///
+/// The returned value is not unescaped if it was a quoted string.
+fn find_param<'a>(params: &'a [u8], name: &[u8]) -> Option<&'a [u8]> {
+    let mut rest = params;
+
+    while !rest.is_empty() {
+        let eq = rest.iter().position(|&x| x == b'=' || x == b';');
+        let (key, tail) = match eq {
+            Some(n) if rest[n] == b'=' => (&rest[..n], &rest[n + 1..]),
+            Some(n) => {
+                rest = &rest[n + 1..];
+                continue;
+            }
+            None => return None,
+        };
+
+        let tail = tail.trim_ascii_start();
+        let (value, next) = if let Some(quoted) = tail.strip_prefix(b"\"") {
+            let mut end = 0;
+            while end < quoted.len() && quoted[end] != b'"' {
+                end += if quoted[end] == b'\\' { 2 } else { 1 };
+            }
+            let end = end.min(quoted.len());
+            let next = quoted.get(end + 1..).unwrap_or_default();
+            (&quoted[..end], next)
+        } else {
+            let end = tail.iter().position(|&x| x == b';').unwrap_or(tail.len());
+            (tail[..end].trim_ascii_end(), &tail[end..])
+        };
+
+        if key.trim_ascii().eq_ignore_ascii_case(name) {
+            return Some(value);
+        }
+
+        rest = match next.iter().position(|&x| x == b';') {
+            Some(n) => &next[n + 1..],
+            None => return None,
+        };
+    }
+
+    None
+}
+
+/// Extracts the boundary parameter from a `multipart/*` media type.
+pub fn multipart_boundary(content_type: &[u8]) -> Option<&[u8]> {
+    let (media_type, params) = match content_type.iter().position(|&x| x == b';') {
+        Some(n) => (&content_type[..n], &content_type[n + 1..]),
+        None => return None,
+    };
+
+    let media_type = media_type.trim_ascii();
+    if media_type.len() < 10 || !media_type[..10].eq_ignore_ascii_case(b"multipart/") {
+        return None;
+    }
+
+    let boundary = find_param(params, b"boundary")?;
+    if boundary.is_empty() || boundary.len() > MAX_BOUNDARY_LEN {
+        return None;
+    }
+
+    Some(boundary)
+}
+
+/// Parsed `Content-Disposition` header of a body part.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ContentDisposition<'a> {
+    /// Disposition type, usually `form-data`.
+    pub kind: &'a [u8],
+    /// Name of the form field.
+    pub name: Option<&'a [u8]>,
+    /// Original name of the uploaded file.
+    pub filename: Option<&'a [u8]>,
+}
+
+impl<'a> ContentDisposition<'a> {
+    /// Parses the value of a `Content-Disposition` header.
+    pub fn parse(value: &'a [u8]) -> Option<Self> {
+        let (kind, params) = match value.iter().position(|&x| x == b';') {
+            Some(n) => (&value[..n], &value[n + 1..]),
+            None => (value, &b""[..]),
+        };
+
+        let kind = kind.trim_ascii();
+        if kind.is_empty() || !kind.iter().all(|&x| is_token_char(x)) {
+            return None;
+        }
+
+        Some(Self {
+            kind,
+            name: find_param(params, b"name"),
+            filename: find_param(params, b"filename"),
+        })
+    }
+}
+
+/// Resumable multipart parser.
+#[cfg(feature = "alloc")]
+pub type Multipart<'b, A> = Resumable<MultipartParser<'b>, A>;
+
+/// Feeds the request body buffers from `chain` to the multipart parser.
+///
+/// Returns `Ok(true)` if the chain contains the last buffer of the request body, and the body was
+/// complete.
+///
+/// All the buffers must be in memory. Use [`ngx_http_read_client_request_body`] with the
+/// `request_body_no_buffering` flag set to receive the body in memory.
+///
+/// [`ngx_http_read_client_request_body`]: crate::ffi::ngx_http_read_client_request_body
+#[cfg(feature = "alloc")]
+pub fn feed_chain<A, F>(
+    parser: &mut Multipart<'_, A>,
+    mut chain: *const ngx_chain_t,
+    mut emit: F,
+) -> Result<bool, ParseError<MultipartError>>
+where
+    A: Allocator + Clone,
+    F: FnMut(Event<'_>),
+{
+    // SAFETY: the chain links and buffers are allocated from the request pool and valid
+    // for the duration of the call.
+    while let Some(cl) = unsafe { chain.as_ref() } {
+        let buf = unsafe { &*cl.buf };
+
+        if buf.in_file() != 0 && buf.temporary() == 0 && buf.memory() == 0 && buf.mmap() == 0 {
+            return Err(ParseError::Invalid(MultipartError::BufferInFile, parser.consumed()));
+        }
+
+        if !buf.pos.is_null() && buf.last > buf.pos {
+            let len = unsafe { buf.last.offset_from(buf.pos) } as usize;
+            let data = unsafe { slice::from_raw_parts(buf.pos, len) };
+            parser.feed(data, &mut emit)?;
+        }
+
+        if buf.last_buf() != 0 {
+            parser.finish()?;
+            if !parser.parser().is_complete() {
+                return Err(ParseError::Incomplete);
+            }
+            return Ok(true);
+        }
+
+        chain = cl.next;
+    }
+
+    Ok(false)
+}
+
+/// Storage for a body part, moving the data to a temporary file once the part grows too large.
+///
+/// The temporary file is created in the `client_body_temp_path` directory and follows the
+/// `client_body_in_file_only` settings of the request.
+pub struct PartBuffer {
+    pool: Pool,
+    log: *mut ngx_log_t,
+    path: *mut ngx_path_t,
+    log_level: u32,
+    persistent: bool,
+    clean: bool,
+    buf: *mut ngx_buf_t,
+    file: *mut ngx_temp_file_t,
+    size: usize,
+}
+
+impl PartBuffer {
+    /// Creates a part storage for the request, keeping up to `threshold` bytes in memory.
+    ///
+    /// Returns `None` if memory allocation fails.
+    pub fn new(r: &Request, threshold: usize) -> Option<Self> {
+        let pool = r.pool();
+        let clcf = NgxHttpCoreModule::location_conf(r.as_ref())?;
+
+        let buf = unsafe { ngx_create_temp_buf(pool.as_ptr(), threshold) };
+        if buf.is_null() {
+            return None;
+        }
+
+        let r = r.as_ref();
+
+        Some(Self {
+            pool,
+            // SAFETY: the request connection is valid for the lifetime of the request.
+            log: unsafe { (*r.connection).log },
+            path: clcf.client_body_temp_path,
+            log_level: r.request_body_file_log_level(),
+            persistent: r.request_body_in_persistent_file() != 0,
+            clean: r.request_body_in_clean_file() != 0,
+            buf,
+            file: ptr::null_mut(),
+            size: 0,
+        })
+    }
+
+    /// Returns the total size of the stored data.
+    pub fn len(&self) -> usize {
+        self.size
+    }
+
+    /// Returns `true` if no data was stored.
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    /// Returns the stored data if it was not moved to a temporary file.
+    pub fn in_memory(&self) -> Option<&[u8]> {
+        if !self.file.is_null() {
+            return None;
+        }
+
+        // SAFETY: `buf` is a valid temporary buffer allocated in the constructor.
+        let buf = unsafe { &*self.buf };
+        let len = unsafe { buf.last.offset_from(buf.pos) } as usize;
+        Some(unsafe { slice::from_raw_parts(buf.pos, len) })
+    }
+
+    /// Returns the temporary file, if the data was moved to one.
+    pub fn temp_file(&self) -> Option<&ngx_temp_file_t> {
+        unsafe { self.file.as_ref() }
+    }
+
+    /// Appends `data` to the part.
+    pub fn write(&mut self, data: &[u8]) -> Result<(), Status> {
+        if data.is_empty() {
+            return Ok(());
+        }
+
+        // SAFETY: `buf` is a valid temporary buffer allocated in the constructor.
+        let buf = unsafe { &mut *self.buf };
+
+        if self.file.is_null() {
+            let avail = unsafe { buf.end.offset_from(buf.last) } as usize;
+            if data.len() <= avail {
+                unsafe {
+                    ptr::copy_nonoverlapping(data.as_ptr(), buf.last, data.len());
+                    buf.last = buf.last.add(data.len());
+                }
+                self.size += data.len();
+                return Ok(());
+            }
+
+            self.file = self.create_temp_file().ok_or(Status::NGX_ERROR)?;
+        }
+
+        // SAFETY: an all-zero `ngx_buf_t` is a valid empty buffer.
+        let mut data_buf: ngx_buf_t = unsafe { mem::zeroed() };
+        data_buf.pos = data.as_ptr().cast_mut();
+        data_buf.last = unsafe { data_buf.pos.add(data.len()) };
+        data_buf.set_memory(1);
+
+        let mut out = ngx_chain_t { buf: &raw mut data_buf, next: ptr::null_mut() };
+        let mut memory = ngx_chain_t { buf: self.buf, next: &raw mut out };
+
+        // Flush the data accumulated in memory before the new data.
+        let chain = if buf.last > buf.pos { &raw mut memory } else { &raw mut out };
+
+        let n = unsafe { ngx_write_chain_to_temp_file(self.file, chain) };
+        if n == NGX_ERROR as _ {
+            return Err(Status::NGX_ERROR);
+        }
+
+        buf.pos = buf.start;
+        buf.last = buf.start;
+        self.size += data.len();
+
+        Ok(())
+    }
+
+    fn create_temp_file(&self) -> Option<*mut ngx_temp_file_t> {
+        let tf = self.pool.calloc_type::<ngx_temp_file_t>();
+        // SAFETY: `tf` was allocated above and zero-initialized.
+        let t = unsafe { tf.as_mut()? };
+
+        t.file.fd = NGX_INVALID_FILE;
+        t.file.log = self.log;
+        t.path = self.path;
+        t.pool = self.pool.as_ptr();
+        t.warn = c"a multipart body part is buffered to a temporary file".as_ptr().cast_mut();
+        t.set_log_level(self.log_level);
+        t.set_persistent(self.persistent.into());
+        t.set_clean(self.clean.into());
+
+        Some(tf)
+    }
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+    use alloc::vec::Vec;
+
+    use super::*;
+    use crate::allocator::Global;
+
+    #[derive(Debug, PartialEq)]
+    enum Owned {
+        PartStart,
+        Header(Vec<u8>, Vec<u8>),
+        HeadersEnd,
+        Data(Vec<u8>),
+        PartEnd,
+        End,
+    }
+
+    fn parse(boundary: &[u8], chunks: &[&[u8]]) -> Result<Vec<Owned>, ParseError<MultipartError>> {
+        let mut events: Vec<Owned> = Vec::new();
+        let mut parser = Multipart::new_in(MultipartParser::new(boundary), 256, Global);
+
+        for chunk in chunks {
+            parser.feed(chunk, |ev| {
+                let ev = match ev {
+                    Event::PartStart => Owned::PartStart,
+                    Event::Header { name, value } => Owned::Header(name.to_vec(), value.to_vec()),
+                    Event::HeadersEnd => Owned::HeadersEnd,
+                    Event::Data(data) => match events.last_mut() {
+                        Some(Owned::Data(prev)) => return prev.extend_from_slice(data),
+                        _ => Owned::Data(data.to_vec()),
+                    },
+                    Event::PartEnd => Owned::PartEnd,
+                    Event::End => Owned::End,
+                };
+                events.push(ev);
+            })?;
+        }
+
+        parser.finish()?;
+        assert!(parser.parser().is_complete());
+        Ok(events)
+    }
+
+    const BODY: &[u8] = b"preamble\r\n--XyZ\r\n\
+        Content-Disposition: form-data; name=\"field\"\r\n\
+        \r\n\
+        value\r\n--XyZ  \r\n\
+        Content-Disposition: form-data; name=\"file\"; filename=\"a.txt\"\r\n\
+        Content-Type: text/plain\r\n\
+        \r\n\
+        line 1\r\n-- XyZ\r\nline 2\r\n--XyZ--\r\nepilogue";
+
+    fn expected() -> Vec<Owned> {
+        let header = |n: &[u8], v: &[u8]| Owned::Header(n.to_vec(), v.to_vec());
+        alloc::vec![
+            Owned::PartStart,
+            header(b"Content-Disposition", b"form-data; name=\"field\""),
+            Owned::HeadersEnd,
+            Owned::Data(b"value".to_vec()),
+            Owned::PartEnd,
+            Owned::PartStart,
+            header(b"Content-Disposition", b"form-data; name=\"file\"; filename=\"a.txt\""),
+            header(b"Content-Type", b"text/plain"),
+            Owned::HeadersEnd,
+            Owned::Data(b"line 1\r\n-- XyZ\r\nline 2".to_vec()),
+            Owned::PartEnd,
+            Owned::End,
+        ]
+    }
+
+    #[test]
+    fn test_multipart() {
+        assert_eq!(parse(b"XyZ", &[BODY]).unwrap(), expected());
+
+        for n in 1..BODY.len() {
+            let (a, b) = BODY.split_at(n);
+            assert_eq!(parse(b"XyZ", &[a, b]).unwrap(), expected(), "split at {n}");
+        }
+
+        let chunks: Vec<&[u8]> = BODY.chunks(1).collect();
+        assert_eq!(parse(b"XyZ", &chunks).unwrap(), expected());
+
+        let body = b"--XyZ\r\n\r\n\r\n--XyZ--";
+        let events = parse(b"XyZ", &[body]).unwrap();
+        assert_eq!(events, [Owned::PartStart, Owned::HeadersEnd, Owned::PartEnd, Owned::End]);
+    }
+
+    #[test]
+    fn test_multipart_errors() {
+        let body = b"--XyZ\r\nContent-Type text/plain\r\n\r\n\r\n--XyZ--";
+        assert!(matches!(
+            parse(b"XyZ", &[body]),
+            Err(ParseError::Invalid(MultipartError::InvalidHeader, 7))
+        ));
+
+        let body = b"--XyZ\r\n\r\ndata\r\n--XyZx\r\n";
+        assert!(matches!(
+            parse(b"XyZ", &[body]),
+            Err(ParseError::Invalid(MultipartError::InvalidBoundary, _))
+        ));
+
+        let body = b"--XyZ\r\n\r\ndata\r\n--Xy";
+        assert!(matches!(parse(b"XyZ", &[body]), Err(ParseError::Incomplete)));
+    }
+
+    #[test]
+    fn test_boundary() {
+        assert_eq!(multipart_boundary(b"multipart/form-data; boundary=abc"), Some(&b"abc"[..]));
+        assert_eq!(
+            multipart_boundary(b"Multipart/Form-Data;charset=utf-8; BOUNDARY=\"a b;c\""),
+            Some(&b"a b;c"[..])
+        );
+        assert_eq!(multipart_boundary(b"multipart/form-data"), None);
+        assert_eq!(multipart_boundary(b"text/plain; boundary=abc"), None);
+        assert_eq!(multipart_boundary(b"multipart/mixed; boundary="), None);
+    }
+
+    #[test]
+    fn test_content_disposition() {
+        let cd = ContentDisposition::parse(b"form-data; name=\"file\"; filename=\"a;b.txt\"");
+        assert_eq!(
+            cd,
+            Some(ContentDisposition {
+                kind: b"form-data",
+                name: Some(b"file"),
+                filename: Some(b"a;b.txt"),
+            })
+        );
+
+        let cd = ContentDisposition::parse(b"form-data; filename=x.bin").unwrap();
+        assert_eq!((cd.name, cd.filename), (None, Some(&b"x.bin"[..])));
+
+        assert_eq!(ContentDisposition::parse(b"; name=x"), None);
+    }
+}
@@ -0,0 +1,343 @@
+//! Content negotiation.
+//!
+//! See [RFC 9110, Section 12](https://datatracker.ietf.org/doc/html/rfc9110#section-12).
+use core::slice;
+
+use crate::allocator::{AllocError, Allocator};
+use crate::collections::Vec;
+use crate::core::Pool;
+use crate::http::Request;
+
+/// Maximum value of the quality parameter, corresponding to `q=1`.
+pub const QVALUE_MAX: u16 = 1000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Kind {
+    MediaType,
+    Language,
+}
+
+/// Element of an `Accept` or `Accept-Language` header.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AcceptItem<'a> {
+    /// Media range or language range, without parameters.
+    pub range: &'a [u8],
+    /// Media type parameters preceding the quality value, e.g. `level=1`, or an empty string.
+    pub params: &'a [u8],
+    /// Quality value, in thousandths.
+    pub q: u16,
+}
+
+/// Parsed list of acceptable media types or languages.
+pub struct Accept<'a, A = Pool>
+where
+    A: Allocator + Clone,
+{
+    kind: Kind,
+    items: Vec<AcceptItem<'a>, A>,
+}
+
+impl<'a> Accept<'a, Pool> {
+    /// Parses the `Accept` headers of the request, allocating the list in the request pool.
+    pub fn media_types(r: &'a Request) -> Result<Self, AllocError> {
+        Self::media_types_in(r.pool()).with_request_headers(r, "Accept")
+    }
+
+    /// Parses the `Accept-Language` headers of the request, allocating the list in the request
+    /// pool.
+    pub fn languages(r: &'a Request) -> Result<Self, AllocError> {
+        Self::languages_in(r.pool()).with_request_headers(r, "Accept-Language")
+    }
+
+    fn with_request_headers(mut self, r: &'a Request, name: &str) -> Result<Self, AllocError> {
+        for h in r.headers_in().get_all(name) {
+            self.add(h.value().as_bytes())?;
+        }
+        Ok(self)
+    }
+}
+
+impl<'a, A> Accept<'a, A>
+where
+    A: Allocator + Clone,
+{
+    /// Creates an empty list of media ranges.
+    pub fn media_types_in(alloc: A) -> Self {
+        Self { kind: Kind::MediaType, items: Vec::new_in(alloc) }
+    }
+
+    /// Creates an empty list of language ranges.
+    pub fn languages_in(alloc: A) -> Self {
+        Self { kind: Kind::Language, items: Vec::new_in(alloc) }
+    }
+
+    /// Parses a header value and appends the elements to the list.
+    ///
+    /// Elements with invalid quality values are ignored.
+    pub fn add(&mut self, value: &'a [u8]) -> Result<(), AllocError> {
+        for element in value.split(|&x| x == b',') {
+            let (range, rest) = split_params(element);
+            if range.is_empty() {
+                continue;
+            }
+
+            // The parameters after the quality value are extensions, and are ignored.
+            let mut q = Some(QVALUE_MAX);
+            let mut params_len = rest.len();
+            let mut offset = 0;
+
+            for param in rest.split(|&x| x == b';') {
+                let trimmed = param.trim_ascii();
+                if let Some(value) = trimmed.strip_prefix(b"q=").or(trimmed.strip_prefix(b"Q=")) {
+                    q = parse_qvalue(value);
+                    params_len = offset.saturating_sub(1);
+                    break;
+                }
+                offset += param.len() + 1;
+            }
+
+            let Some(q) = q else {
+                continue;
+            };
+
+            let params = rest[..params_len].trim_ascii();
+
+            self.items.try_reserve(1).map_err(|_| AllocError)?;
+            self.items.push(AcceptItem { range, params, q });
+        }
+
+        Ok(())
+    }
+
+    /// Returns `true` if the list is empty, i.e. the client did not express any preference.
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Returns an iterator over the list elements, in the order of appearance.
+    pub fn iter(&self) -> slice::Iter<'_, AcceptItem<'a>> {
+        self.items.iter()
+    }
+
+    /// Returns the quality value of the `offered` media type or language.
+    ///
+    /// The value is taken from the most specific matching range. A media range with parameters
+    /// only matches the media types with the same parameters, e.g. `text/html;level=1`, and is
+    /// more specific than the range without parameters. `0` means that the value is not
+    /// acceptable.
+    pub fn quality(&self, offered: &str) -> u16 {
+        if self.items.is_empty() {
+            return QVALUE_MAX;
+        }
+
+        let offered = offered.as_bytes();
+        let mut best: Option<(usize, u16)> = None;
+
+        for item in &self.items {
+            let Some(specificity) = self.specificity(item, offered) else {
+                continue;
+            };
+
+            if best.is_none_or(|(s, _)| specificity > s) {
+                best = Some((specificity, item.q));
+            }
+        }
+
+        best.map_or(0, |(_, q)| q)
+    }
+
+    /// Selects the most preferred of the `offered` values.
+    ///
+    /// Returns `None` if none of the values is acceptable. Ties are resolved in favor of the value
+    /// listed first in `offered`.
+    pub fn negotiate<'o>(&self, offered: &[&'o str]) -> Option<&'o str> {
+        let mut best: Option<(&'o str, u16)> = None;
+
+        for value in offered {
+            let q = self.quality(value);
+            if q > 0 && best.is_none_or(|(_, best)| q > best) {
+                best = Some((value, q));
+            }
+        }
+
+        best.map(|(value, _)| value)
+    }
+
+    /// Returns the specificity of `range` if it matches the `offered` value.
+    fn specificity(&self, item: &AcceptItem<'_>, offered: &[u8]) -> Option<usize> {
+        let range = item.range;
+
+        match self.kind {
+            Kind::MediaType => {
+                let (offered, oparams) = split_params(offered);
+                let (otype, _) = split_media_type(offered)?;
+
+                let specificity = if range == b"*" || range == b"*/*" {
+                    0
+                } else if range.eq_ignore_ascii_case(offered) {
+                    2
+                } else {
+                    let (rtype, rsubtype) = split_media_type(range)?;
+                    if rsubtype != b"*" || !rtype.eq_ignore_ascii_case(otype) {
+                        return None;
+                    }
+                    1
+                };
+
+                // The parameters rank below the type and the subtype.
+                let matched = params_match(item.params, oparams)?;
+                Some(specificity * 16 + matched.min(15))
+            }
+
+            Kind::Language => {
+                if range == b"*" {
+                    Some(0)
+                } else if range.eq_ignore_ascii_case(offered)
+                    || (offered.len() > range.len()
+                        && offered[range.len()] == b'-'
+                        && range.eq_ignore_ascii_case(&offered[..range.len()]))
+                {
+                    Some(range.len())
+                } else {
+                    None
+                }
+            }
+        }
+    }
+}
+
+/// Splits an element into the trimmed value and the parameters following the first `;`.
+fn split_params(element: &[u8]) -> (&[u8], &[u8]) {
+    match element.iter().position(|&x| x == b';') {
+        Some(n) => (element[..n].trim_ascii(), &element[n + 1..]),
+        None => (element.trim_ascii(), &b""[..]),
+    }
+}
+
+/// Returns an iterator over the `name=value` parameters, with the quotes removed from the values.
+fn parse_params(params: &[u8]) -> impl Iterator<Item = (&[u8], &[u8])> {
+    params.split(|&x| x == b';').filter_map(|param| {
+        let eq = param.iter().position(|&x| x == b'=')?;
+        let name = param[..eq].trim_ascii();
+        let value = param[eq + 1..].trim_ascii();
+        let value = value.strip_prefix(b"\"").and_then(|v| v.strip_suffix(b"\"")).unwrap_or(value);
+        (!name.is_empty()).then_some((name, value))
+    })
+}
+
+/// Checks that all the `range` parameters are present in `offered`, and returns their number.
+///
+/// The names and the values are compared ignoring ASCII case.
+fn params_match(range: &[u8], offered: &[u8]) -> Option<usize> {
+    let mut matched = 0;
+
+    for (name, value) in parse_params(range) {
+        parse_params(offered)
+            .any(|(n, v)| n.eq_ignore_ascii_case(name) && v.eq_ignore_ascii_case(value))
+            .then_some(())?;
+        matched += 1;
+    }
+
+    Some(matched)
+}
+
+fn split_media_type(value: &[u8]) -> Option<(&[u8], &[u8])> {
+    let slash = value.iter().position(|&x| x == b'/')?;
+    Some((&value[..slash], &value[slash + 1..]))
+}
+
+/// Parses a quality value, as defined in RFC 9110, Section 12.4.2.
+fn parse_qvalue(value: &[u8]) -> Option<u16> {
+    let (int, frac) = match value.iter().position(|&x| x == b'.') {
+        Some(n) => (&value[..n], &value[n + 1..]),
+        None => (value, &b""[..]),
+    };
+
+    if frac.len() > 3 || !frac.iter().all(u8::is_ascii_digit) {
+        return None;
+    }
+
+    let mut q = match int {
+        b"0" => 0,
+        b"1" => QVALUE_MAX,
+        _ => return None,
+    };
+
+    for (i, &digit) in frac.iter().enumerate() {
+        q += u16::from(digit - b'0') * [100, 10, 1][i];
+    }
+
+    (q <= QVALUE_MAX).then_some(q)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::allocator::Global;
+
+    #[test]
+    fn test_qvalue() {
+        assert_eq!(parse_qvalue(b"1"), Some(1000));
+        assert_eq!(parse_qvalue(b"1.000"), Some(1000));
+        assert_eq!(parse_qvalue(b"0.5"), Some(500));
+        assert_eq!(parse_qvalue(b"0.05"), Some(50));
+        assert_eq!(parse_qvalue(b"0."), Some(0));
+        assert_eq!(parse_qvalue(b"1.001"), None);
+        assert_eq!(parse_qvalue(b"0.0001"), None);
+        assert_eq!(parse_qvalue(b"2"), None);
+        assert_eq!(parse_qvalue(b""), None);
+    }
+
+    #[test]
+    fn test_media_types() {
+        let mut accept = Accept::media_types_in(Global);
+        accept.add(b"text/*;q=0.3, text/html;q=0.7, text/html;level=1, */*;q=0.5").unwrap();
+        accept.add(b"image/png;q=0").unwrap();
+
+        assert_eq!(accept.quality("text/html"), 700);
+        assert_eq!(accept.quality("text/html;level=1"), 1000);
+        assert_eq!(accept.quality("text/html; LEVEL=\"1\""), 1000);
+        assert_eq!(accept.quality("text/html;level=2"), 700);
+        assert_eq!(accept.quality("text/plain"), 300);
+        assert_eq!(accept.quality("application/json"), 500);
+        assert_eq!(accept.quality("image/png"), 0);
+
+        assert_eq!(accept.negotiate(&["image/png", "text/plain"]), Some("text/plain"));
+        assert_eq!(accept.negotiate(&["application/json", "text/html"]), Some("text/html"));
+        assert_eq!(accept.negotiate(&["image/png"]), None);
+
+        let accept = Accept::media_types_in(Global);
+        assert_eq!(accept.negotiate(&["text/html", "application/json"]), Some("text/html"));
+    }
+
+    #[test]
+    fn test_media_type_params() {
+        let mut accept = Accept::media_types_in(Global);
+        accept
+            .add(b"text/plain;q=0.7, text/plain;format=flowed, text/plain;format=fixed;q=0.4;x=y")
+            .unwrap();
+
+        let params: [&[u8]; 3] = [b"", b"format=flowed", b"format=fixed"];
+        assert!(accept.iter().map(|item| item.params).eq(params));
+
+        assert_eq!(accept.quality("text/plain"), 700);
+        assert_eq!(accept.quality("text/plain;format=flowed"), 1000);
+        assert_eq!(accept.quality("text/plain;format=fixed"), 400);
+        assert_eq!(accept.quality("text/plain;charset=utf-8;format=fixed"), 400);
+
+        let offered = ["text/plain;format=fixed", "text/plain;format=flowed"];
+        assert_eq!(accept.negotiate(&offered), Some("text/plain;format=flowed"));
+    }
+
+    #[test]
+    fn test_languages() {
+        let mut accept = Accept::languages_in(Global);
+        accept.add(b"da, en-GB;q=0.8, en;q=0.7, *;q=0.1").unwrap();
+
+        assert_eq!(accept.quality("en-GB"), 800);
+        assert_eq!(accept.quality("en-us"), 700);
+        assert_eq!(accept.quality("english"), 100);
+        assert_eq!(accept.negotiate(&["fr", "en-US", "en-gb"]), Some("en-gb"));
+        assert_eq!(accept.negotiate(&["fr", "de"]), Some("fr"));
+    }
+}
@@ -0,0 +1,355 @@
+use core::fmt;
+use core::ops;
+
+use crate::core::NgxStr;
+use crate::http::Request;
+
+/// Results of the request URI normalization performed by nginx.
+#[derive(Debug)]
+pub struct UriNormalization<'a> {
+    /// Request URI as received from the client, including the arguments.
+    pub raw: &'a NgxStr,
+    /// Decoded and normalized path, used for the location matching.
+    pub path: &'a NgxStr,
+    /// Request arguments, not decoded.
+    pub args: &'a NgxStr,
+    /// The path contained dot segments, repeated slashes or encoded characters.
+    pub complex: bool,
+    /// The path contained percent-encoded characters.
+    pub quoted: bool,
+    /// The path contained the `+` character.
+    pub plus: bool,
+    /// The request line had an absolute URI with an empty path.
+    pub empty_path: bool,
+}
+
+/// Set of suspicious properties of a request URI.
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+pub struct UriFindings(u32);
+
+impl UriFindings {
+    /// No findings.
+    pub const NONE: Self = Self(0);
+    /// Percent sign not followed by two hexadecimal digits.
+    pub const INVALID_ENCODING: Self = Self(1 << 0);
+    /// Encoded percent sign followed by two hexadecimal digits, e.g. `%252e`.
+    pub const DOUBLE_ENCODING: Self = Self(1 << 1);
+    /// Null byte, encoded or not.
+    pub const NULL_BYTE: Self = Self(1 << 2);
+    /// Other control characters, encoded or not.
+    pub const CONTROL_CHAR: Self = Self(1 << 3);
+    /// `..` path segment.
+    pub const TRAVERSAL: Self = Self(1 << 4);
+    /// `..` path segment with percent-encoded characters, e.g. `%2e%2e`.
+    pub const ENCODED_TRAVERSAL: Self = Self(1 << 5);
+    /// Percent-encoded slash or backslash in the path.
+    pub const ENCODED_SLASH: Self = Self(1 << 6);
+    /// Backslash in the path, a path separator on Windows.
+    pub const BACKSLASH: Self = Self(1 << 7);
+    /// Overlong UTF-8 sequence, e.g. `%c0%ae`.
+    pub const OVERLONG_UTF8: Self = Self(1 << 8);
+    /// `..` path segment remaining after the normalization.
+    pub const NORMALIZED_TRAVERSAL: Self = Self(1 << 9);
+
+    const NAMES: [(Self, &'static str); 10] = [
+        (Self::INVALID_ENCODING, "invalid_encoding"),
+        (Self::DOUBLE_ENCODING, "double_encoding"),
+        (Self::NULL_BYTE, "null_byte"),
+        (Self::CONTROL_CHAR, "control_char"),
+        (Self::TRAVERSAL, "traversal"),
+        (Self::ENCODED_TRAVERSAL, "encoded_traversal"),
+        (Self::ENCODED_SLASH, "encoded_slash"),
+        (Self::BACKSLASH, "backslash"),
+        (Self::OVERLONG_UTF8, "overlong_utf8"),
+        (Self::NORMALIZED_TRAVERSAL, "normalized_traversal"),
+    ];
+
+    /// Returns the raw bitmask.
+    pub const fn bits(self) -> u32 {
+        self.0
+    }
+
+    /// Returns `true` if there are no findings.
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Returns `true` if all the findings in `other` are present.
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Returns `true` if any of the findings in `other` is present.
+    pub const fn intersects(self, other: Self) -> bool {
+        self.0 & other.0 != 0
+    }
+}
+
+impl ops::BitOr for UriFindings {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl ops::BitOrAssign for UriFindings {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
+}
+
+impl ops::BitAnd for UriFindings {
+    type Output = Self;
+
+    fn bitand(self, rhs: Self) -> Self {
+        Self(self.0 & rhs.0)
+    }
+}
+
+impl fmt::Debug for UriFindings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "UriFindings({self})")
+    }
+}
+
+impl fmt::Display for UriFindings {
+    /// Formats the findings as a `|`-separated list of names, suitable for logging.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut sep = "";
+        for (flag, name) in Self::NAMES {
+            if self.contains(flag) {
+                write!(f, "{sep}{name}")?;
+                sep = "|";
+            }
+        }
+        if sep.is_empty() {
+            f.write_str("none")?;
+        }
+        Ok(())
+    }
+}
+
+/// Inspects a raw request URI, as received from the client.
+///
+/// The path and the arguments are decoded once; the path is split into segments on decoded
+/// slashes, matching the nginx normalization.
+pub fn inspect_uri(raw: &[u8]) -> UriFindings {
+    let (path, args) = match raw.iter().position(|&x| x == b'?') {
+        Some(pos) => (&raw[..pos], &raw[pos + 1..]),
+        None => (raw, &raw[raw.len()..]),
+    };
+
+    inspect_path(path) | inspect_component(args)
+}
+
+/// Inspects a path after the nginx normalization, e.g. [`Request::path`].
+pub fn inspect_normalized(path: &[u8]) -> UriFindings {
+    let mut findings = UriFindings::NONE;
+
+    for segment in path.split(|&x| x == b'/') {
+        if segment == b".." {
+            findings |= UriFindings::NORMALIZED_TRAVERSAL;
+        }
+    }
+
+    for &ch in path {
+        findings |= classify(ch);
+    }
+
+    findings
+}
+
+fn inspect_path(path: &[u8]) -> UriFindings {
+    let mut findings = UriFindings::NONE;
+    let mut dots = 0;
+    let mut other = false;
+    let mut encoded = false;
+
+    let mut decoder = Decoder::new(path);
+    for (ch, was_encoded) in decoder.by_ref() {
+        match ch {
+            b'/' | b'\\' => {
+                if was_encoded {
+                    findings |= UriFindings::ENCODED_SLASH;
+                } else if ch == b'\\' {
+                    findings |= UriFindings::BACKSLASH;
+                }
+                findings |= dot_segment(dots, other, encoded);
+                (dots, other, encoded) = (0, false, false);
+            }
+            b'.' => {
+                dots += 1;
+                encoded |= was_encoded;
+            }
+            _ => other = true,
+        }
+        findings |= classify(ch);
+    }
+
+    findings | dot_segment(dots, other, encoded) | decoder.findings
+}
+
+fn dot_segment(dots: usize, other: bool, encoded: bool) -> UriFindings {
+    match (dots, other, encoded) {
+        (2, false, false) => UriFindings::TRAVERSAL,
+        (2, false, true) => UriFindings::TRAVERSAL | UriFindings::ENCODED_TRAVERSAL,
+        _ => UriFindings::NONE,
+    }
+}
+
+fn inspect_component(value: &[u8]) -> UriFindings {
+    let mut findings = UriFindings::NONE;
+    let mut decoder = Decoder::new(value);
+    for (ch, _) in decoder.by_ref() {
+        findings |= classify(ch);
+    }
+    findings | decoder.findings
+}
+
+fn classify(ch: u8) -> UriFindings {
+    match ch {
+        0 => UriFindings::NULL_BYTE,
+        0x01..0x20 | 0x7f => UriFindings::CONTROL_CHAR,
+        0xc0 | 0xc1 => UriFindings::OVERLONG_UTF8,
+        _ => UriFindings::NONE,
+    }
+}
+
+/// Single pass percent-decoder, recording the encoding errors.
+struct Decoder<'a> {
+    input: &'a [u8],
+    findings: UriFindings,
+}
+
+impl<'a> Decoder<'a> {
+    fn new(input: &'a [u8]) -> Self {
+        Self { input, findings: UriFindings::NONE }
+    }
+}
+
+impl Iterator for Decoder<'_> {
+    type Item = (u8, bool);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (&ch, rest) = self.input.split_first()?;
+
+        if ch != b'%' {
+            self.input = rest;
+            return Some((ch, false));
+        }
+
+        match decode_hex(rest) {
+            Some(decoded) => {
+                self.input = &rest[2..];
+                if decoded == b'%' && decode_hex(self.input).is_some() {
+                    self.findings |= UriFindings::DOUBLE_ENCODING;
+                }
+                Some((decoded, true))
+            }
+            None => {
+                self.findings |= UriFindings::INVALID_ENCODING;
+                self.input = rest;
+                Some((ch, false))
+            }
+        }
+    }
+}
+
+fn decode_hex(input: &[u8]) -> Option<u8> {
+    let hi = (*input.first()? as char).to_digit(16)?;
+    let lo = (*input.get(1)? as char).to_digit(16)?;
+    Some((hi * 16 + lo) as u8)
+}
+
+impl Request {
+    /// Returns the results of the URI normalization.
+    pub fn uri_normalization(&self) -> UriNormalization<'_> {
+        let r = self.as_ref();
+        // SAFETY: the strings are valid for the lifetime of the request.
+        let args = unsafe { NgxStr::from_ngx_str(r.args) };
+
+        UriNormalization {
+            raw: self.unparsed_uri(),
+            path: self.path(),
+            args,
+            complex: r.complex_uri() != 0,
+            quoted: r.quoted_uri() != 0,
+            plus: r.plus_in_uri() != 0,
+            empty_path: r.empty_path_in_uri() != 0,
+        }
+    }
+
+    /// Inspects the request URI for the suspicious encodings, both as received and after the
+    /// normalization.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use ngx::core::Status;
+    /// # use ngx::http::{HTTPStatus, Request, UriFindings};
+    /// fn access_handler(r: &mut Request) -> Status {
+    ///     let findings = r.uri_findings();
+    ///     if findings.intersects(UriFindings::NULL_BYTE | UriFindings::ENCODED_TRAVERSAL) {
+    ///         return HTTPStatus::FORBIDDEN.into();
+    ///     }
+    ///     Status::NGX_DECLINED
+    /// }
+    /// ```
+    pub fn uri_findings(&self) -> UriFindings {
+        inspect_uri(self.unparsed_uri().as_bytes()) | inspect_normalized(self.path().as_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use std::string::ToString;
+
+    use super::*;
+
+    #[test]
+    fn test_inspect_uri() {
+        assert_eq!(inspect_uri(b"/index.html?a=1&b=%20"), UriFindings::NONE);
+        assert_eq!(inspect_uri(b"/a/./b/...x"), UriFindings::NONE);
+
+        assert_eq!(inspect_uri(b"/a/../b"), UriFindings::TRAVERSAL);
+        assert_eq!(inspect_uri(b"/a/.."), UriFindings::TRAVERSAL);
+        assert_eq!(
+            inspect_uri(b"/a/%2e%2E/b"),
+            UriFindings::TRAVERSAL | UriFindings::ENCODED_TRAVERSAL
+        );
+        assert_eq!(
+            inspect_uri(b"/a%2f..%2fb"),
+            UriFindings::TRAVERSAL | UriFindings::ENCODED_SLASH
+        );
+        assert_eq!(inspect_uri(b"/a\\..\\b"), UriFindings::TRAVERSAL | UriFindings::BACKSLASH);
+
+        assert_eq!(inspect_uri(b"/%252e%252e/"), UriFindings::DOUBLE_ENCODING);
+        assert_eq!(inspect_uri(b"/file%00.txt"), UriFindings::NULL_BYTE);
+        assert_eq!(inspect_uri(b"/?q=%0d%0a"), UriFindings::CONTROL_CHAR);
+        assert_eq!(inspect_uri(b"/%c0%ae%c0%ae/"), UriFindings::OVERLONG_UTF8);
+        assert_eq!(inspect_uri(b"/100%"), UriFindings::INVALID_ENCODING);
+        assert_eq!(inspect_uri(b"/%zz"), UriFindings::INVALID_ENCODING);
+
+        // the `..` in the arguments is not a path segment
+        assert_eq!(inspect_uri(b"/?path=../etc"), UriFindings::NONE);
+    }
+
+    #[test]
+    fn test_inspect_normalized() {
+        assert_eq!(inspect_normalized(b"/a/b"), UriFindings::NONE);
+        assert_eq!(inspect_normalized(b"/a/../b"), UriFindings::NORMALIZED_TRAVERSAL);
+        assert_eq!(inspect_normalized(b"/a\0b"), UriFindings::NULL_BYTE);
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(UriFindings::NONE.to_string(), "none");
+        assert_eq!(
+            (UriFindings::NULL_BYTE | UriFindings::TRAVERSAL).to_string(),
+            "null_byte|traversal"
+        );
+    }
+}
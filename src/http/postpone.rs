@@ -0,0 +1,139 @@
+use core::ptr;
+
+use crate::core::{Buffer, Pool, Status};
+use crate::ffi::{
+    NGX_ERROR, NGX_HTTP_LAST, ngx_chain_t, ngx_http_output_filter, ngx_http_request_t,
+    ngx_http_send_special, ngx_int_t,
+};
+use crate::http::{Request, SubrequestFlags};
+
+/// Emits the response body composed of the request's own output and the output of subrequests,
+/// in the order of the calls.
+///
+/// The output of a subrequest is inserted at the position where the subrequest was created: any
+/// output of the parent request sent after creating the subrequest is held by the postpone filter
+/// until the subrequest completes. This is the mechanism used by SSI `include` and similar
+/// composition modules.
+///
+/// The subrequests are created without `NGX_HTTP_SUBREQUEST_IN_MEMORY`, their responses pass
+/// through the output filters of the parent request. The response header of the parent request
+/// must be sent before using the sequencer.
+///
+/// # Example
+///
+/// ```no_run
+/// # use ngx::core::Status;
+/// # use ngx::http::{OutputSequencer, Request};
+/// fn handler(r: &mut Request) -> Result<Status, Status> {
+///     let mut out = OutputSequencer::new(r);
+///     out.write(b"<header>")?;
+///     out.include("/fragment", None, false)?;
+///     out.write(b"</header>")?;
+///     Ok(out.finish())
+/// }
+/// ```
+pub struct OutputSequencer<'r> {
+    r: &'r mut Request,
+    rc: ngx_int_t,
+}
+
+impl<'r> OutputSequencer<'r> {
+    /// Creates a sequencer for the request output.
+    pub fn new(r: &'r mut Request) -> Self {
+        Self { r, rc: 0 }
+    }
+
+    /// Returns the request.
+    pub fn request(&mut self) -> &mut Request {
+        self.r
+    }
+
+    /// Copies `data` into a request pool buffer and sends it.
+    pub fn write(&mut self, data: &[u8]) -> Result<(), Status> {
+        if data.is_empty() {
+            return Ok(());
+        }
+
+        let pool = self.r.pool();
+        let mut buf = pool.create_buffer(data.len()).ok_or(Status::NGX_ERROR)?;
+        // SAFETY: the buffer was allocated with the required size.
+        unsafe {
+            let b = buf.as_ngx_buf_mut();
+            ptr::copy_nonoverlapping(data.as_ptr(), (*b).pos, data.len());
+            (*b).last = (*b).pos.add(data.len());
+        }
+
+        self.send_buf(&pool, buf.as_ngx_buf_mut())
+    }
+
+    /// Sends a static string without copying.
+    pub fn write_static(&mut self, data: &'static str) -> Result<(), Status> {
+        if data.is_empty() {
+            return Ok(());
+        }
+
+        let pool = self.r.pool();
+        let mut buf = pool.create_buffer_from_static_str(data).ok_or(Status::NGX_ERROR)?;
+        self.send_buf(&pool, buf.as_ngx_buf_mut())
+    }
+
+    /// Sends a chain of buffers.
+    pub fn write_chain(&mut self, cl: &mut ngx_chain_t) -> Result<(), Status> {
+        self.output(cl)
+    }
+
+    /// Creates a subrequest to `uri`, and inserts its response at the current position.
+    ///
+    /// With `wait`, the parent request is not finalized until the subrequest completes, and the
+    /// subrequest variables are available for the rest of the parent output, as with the SSI
+    /// `wait` parameter.
+    pub fn include(&mut self, uri: &str, args: Option<&str>, wait: bool) -> Result<(), Status> {
+        let flags = SubrequestFlags::new().waited(wait);
+        self.r.subrequest_with(uri, args, flags, |_, _| {})?;
+        Ok(())
+    }
+
+    /// Sends the end of the output: the last buffer for the main request, or the last buffer in
+    /// the chain for a subrequest.
+    ///
+    /// Returns the result of the output call, suitable for returning from a content handler or for
+    /// `ngx_http_finalize_request`.
+    pub fn finish(mut self) -> Status {
+        let r = self.as_ptr();
+        // SAFETY: the request is valid for the lifetime of the sequencer.
+        Status(unsafe { ngx_http_send_special(r, NGX_HTTP_LAST as _) })
+    }
+
+    /// Returns the result of the last output call, `NGX_OK` or `NGX_AGAIN`.
+    pub fn last_status(&self) -> Status {
+        Status(self.rc)
+    }
+
+    fn as_ptr(&mut self) -> *mut ngx_http_request_t {
+        ptr::from_mut::<Request>(self.r).cast()
+    }
+
+    fn send_buf(&mut self, pool: &Pool, buf: *mut crate::ffi::ngx_buf_t) -> Result<(), Status> {
+        let cl = pool.alloc_type::<ngx_chain_t>();
+        if cl.is_null() {
+            return Err(Status::NGX_ERROR);
+        }
+
+        // SAFETY: `cl` is a fresh allocation from the request pool.
+        unsafe {
+            cl.write(ngx_chain_t { buf, next: ptr::null_mut() });
+            self.output(&mut *cl)
+        }
+    }
+
+    fn output(&mut self, cl: &mut ngx_chain_t) -> Result<(), Status> {
+        let r = self.as_ptr();
+        // SAFETY: the request is valid for the lifetime of the sequencer.
+        let rc = unsafe { ngx_http_output_filter(r, cl) };
+        if rc == NGX_ERROR as ngx_int_t {
+            return Err(Status(rc));
+        }
+        self.rc = rc;
+        Ok(())
+    }
+}
@@ -0,0 +1,161 @@
+use core::fmt;
+
+use crate::core::Connection;
+use crate::ffi::{
+    NGX_HTTP_VERSION_9, NGX_HTTP_VERSION_10, NGX_HTTP_VERSION_20, NGX_HTTP_VERSION_30,
+    ngx_connection_t, ngx_uint_t,
+};
+use crate::http::Request;
+
+/// HTTP protocol version of a request.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum HttpVersion {
+    /// HTTP/0.9.
+    Http09,
+    /// HTTP/1.0.
+    Http10,
+    /// HTTP/1.1.
+    Http11,
+    /// HTTP/2.
+    Http2,
+    /// HTTP/3.
+    Http3,
+}
+
+impl HttpVersion {
+    /// Converts the `http_version` field of a request, e.g. `NGX_HTTP_VERSION_11`.
+    ///
+    /// The versions above HTTP/1.1 and below HTTP/2, accepted by nginx in the request line, are
+    /// reported as HTTP/1.1.
+    pub fn from_ngx(version: ngx_uint_t) -> Self {
+        match version as u32 {
+            NGX_HTTP_VERSION_9 => Self::Http09,
+            NGX_HTTP_VERSION_10 => Self::Http10,
+            NGX_HTTP_VERSION_20 => Self::Http2,
+            NGX_HTTP_VERSION_30 => Self::Http3,
+            v if v < NGX_HTTP_VERSION_10 => Self::Http09,
+            _ => Self::Http11,
+        }
+    }
+
+    /// Returns the version as in the `$server_protocol` variable, e.g. `HTTP/1.1`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Http09 => "HTTP/0.9",
+            Self::Http10 => "HTTP/1.0",
+            Self::Http11 => "HTTP/1.1",
+            Self::Http2 => "HTTP/2.0",
+            Self::Http3 => "HTTP/3.0",
+        }
+    }
+}
+
+impl fmt::Display for HttpVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Request {
+    /// Returns the HTTP protocol version of the request.
+    pub fn http_version(&self) -> HttpVersion {
+        HttpVersion::from_ngx(self.as_ref().http_version)
+    }
+
+    /// Returns `true` if the request is received over HTTP/2.
+    pub fn is_http2(&self) -> bool {
+        #[cfg(ngx_feature = "http_v2")]
+        {
+            !self.as_ref().stream.is_null()
+        }
+        #[cfg(not(ngx_feature = "http_v2"))]
+        {
+            false
+        }
+    }
+
+    /// Returns `true` if the request is received over HTTP/3.
+    pub fn is_http3(&self) -> bool {
+        self.as_ref().http_version == NGX_HTTP_VERSION_30 as ngx_uint_t
+    }
+
+    /// Returns the HTTP/2 stream identifier of the request.
+    ///
+    /// Returns `None` if the request is not received over HTTP/2.
+    pub fn http2_stream_id(&self) -> Option<u32> {
+        #[cfg(ngx_feature = "http_v2")]
+        {
+            // SAFETY: the stream and its node are valid for the lifetime of the request.
+            let stream = unsafe { self.as_ref().stream.as_ref()? };
+            let node = unsafe { stream.node.as_ref()? };
+            Some(node.id as u32)
+        }
+        #[cfg(not(ngx_feature = "http_v2"))]
+        {
+            None
+        }
+    }
+
+    /// Returns the QUIC stream identifier of an HTTP/3 request.
+    pub fn http3_stream_id(&self) -> Option<u64> {
+        #[cfg(ngx_feature = "http_v3")]
+        {
+            // SAFETY: the connection and the QUIC stream are valid for the lifetime of the
+            // request.
+            let qs = unsafe { (*self.as_ref().connection).quic.as_ref()? };
+            Some(qs.id)
+        }
+        #[cfg(not(ngx_feature = "http_v3"))]
+        {
+            None
+        }
+    }
+
+    /// Returns the client connection: the connection carrying the HTTP/2 or HTTP/3 streams, or
+    /// the request connection for the other protocols.
+    pub fn client_connection(&self) -> &Connection {
+        let c: *mut ngx_connection_t = self.as_ref().connection;
+
+        // SAFETY: the HTTP/2 connection outlives its streams.
+        #[cfg(ngx_feature = "http_v2")]
+        let c = match unsafe { self.as_ref().stream.as_ref() } {
+            Some(stream) => unsafe { (*stream.connection).connection },
+            None => c,
+        };
+
+        // SAFETY: the QUIC connection outlives its streams.
+        #[cfg(ngx_feature = "http_v3")]
+        let c = match unsafe { (*c).quic.as_ref() } {
+            Some(qs) if !qs.parent.is_null() => qs.parent,
+            _ => c,
+        };
+
+        // SAFETY: the connection is valid for the lifetime of the request.
+        unsafe { Connection::from_ptr(c) }
+    }
+
+    /// Returns the application protocol negotiated with ALPN on the client connection, e.g. `h2`
+    /// or `http/1.1`.
+    #[cfg(any(ngx_feature = "ssl", feature = "feature-stubs"))]
+    pub fn alpn_protocol(&self) -> Result<Option<&[u8]>, crate::core::FeatureDisabled> {
+        self.client_connection().alpn_protocol()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ffi::NGX_HTTP_VERSION_11;
+
+    #[test]
+    fn test_http_version() {
+        let v = |x: u32| HttpVersion::from_ngx(x as ngx_uint_t);
+        assert_eq!(v(NGX_HTTP_VERSION_9), HttpVersion::Http09);
+        assert_eq!(v(NGX_HTTP_VERSION_10), HttpVersion::Http10);
+        assert_eq!(v(NGX_HTTP_VERSION_11), HttpVersion::Http11);
+        assert_eq!(v(1005), HttpVersion::Http11);
+        assert_eq!(v(NGX_HTTP_VERSION_20), HttpVersion::Http2);
+        assert_eq!(v(NGX_HTTP_VERSION_30).as_str(), "HTTP/3.0");
+        assert!(HttpVersion::Http10 < HttpVersion::Http2);
+    }
+}
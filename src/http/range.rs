@@ -0,0 +1,416 @@
+use core::error;
+use core::fmt::{self, Write};
+use core::mem;
+
+use crate::core::{Pool, SliceWriter, Status};
+use crate::ffi::{
+    NGX_OK, ngx_hash_key, ngx_int_t, ngx_list_init, ngx_list_push, ngx_str_t, ngx_table_elt_t,
+};
+use crate::http::{Request, SubrequestFlags, list_iterator};
+
+/// Inclusive byte range, as in the `Range: bytes=start-end` request header.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ByteRange {
+    /// Offset of the first byte.
+    pub start: u64,
+    /// Offset of the last byte, inclusive.
+    pub end: u64,
+}
+
+impl ByteRange {
+    /// Creates a range of `len` bytes starting at `start`.
+    ///
+    /// Returns `None` if `len` is zero or the range overflows.
+    pub fn with_len(start: u64, len: u64) -> Option<Self> {
+        let end = start.checked_add(len.checked_sub(1)?)?;
+        Some(Self { start, end })
+    }
+
+    /// Returns the number of bytes in the range.
+    ///
+    /// Returns `None` if the range is invalid, i.e. ends before the start, or the length overflows.
+    pub fn len(&self) -> Option<u64> {
+        self.end.checked_sub(self.start)?.checked_add(1)
+    }
+
+    /// Always `false`: a byte range contains at least one byte.
+    pub fn is_empty(&self) -> bool {
+        false
+    }
+}
+
+/// Formats the range as the value of the `Range` request header.
+impl fmt::Display for ByteRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "bytes={}-{}", self.start, self.end)
+    }
+}
+
+/// Parsed `Content-Range` response header of a `206 Partial Content` response.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ContentRange {
+    /// Range of the bytes in the response body.
+    pub range: ByteRange,
+    /// Complete length of the representation, if known.
+    pub complete_length: Option<u64>,
+}
+
+impl ContentRange {
+    /// Parses a `Content-Range` header value, e.g. `bytes 0-1023/4096` or `bytes 0-1023/*`.
+    pub fn parse(value: &[u8]) -> Result<Self, RangeError> {
+        let value = value.trim_ascii();
+        let rest = value
+            .strip_prefix(b"bytes ")
+            .ok_or(RangeError::InvalidContentRange)?
+            .trim_ascii_start();
+
+        let dash = rest.iter().position(|&x| x == b'-').ok_or(RangeError::InvalidContentRange)?;
+        let slash = rest.iter().position(|&x| x == b'/').ok_or(RangeError::InvalidContentRange)?;
+        if slash < dash {
+            return Err(RangeError::InvalidContentRange);
+        }
+
+        let start = parse_u64(&rest[..dash])?;
+        let end = parse_u64(&rest[dash + 1..slash])?;
+        let complete_length = match &rest[slash + 1..] {
+            b"*" => None,
+            x => Some(parse_u64(x)?),
+        };
+
+        if end < start || complete_length.is_some_and(|len| end >= len) {
+            return Err(RangeError::InvalidContentRange);
+        }
+
+        Ok(Self { range: ByteRange { start, end }, complete_length })
+    }
+
+    /// Returns the `Content-Range` header of the subrequest response.
+    pub fn from_response(sr: &Request) -> Result<Self, RangeError> {
+        let headers_out = &sr.as_ref().headers_out;
+
+        // SAFETY: the header entries are valid for the lifetime of the request.
+        if let Some(h) = unsafe { headers_out.content_range.as_ref() } {
+            if h.hash != 0 {
+                return Self::parse(h.value.as_bytes());
+            }
+        }
+
+        // SAFETY: `headers_out.headers` is initialized when the request is created.
+        unsafe { list_iterator(&headers_out.headers) }
+            .find(|h| h.hash != 0 && h.is("content-range"))
+            .ok_or(RangeError::MissingContentRange)
+            .and_then(|h| Self::parse(h.value().as_bytes()))
+    }
+}
+
+fn parse_u64(value: &[u8]) -> Result<u64, RangeError> {
+    if value.is_empty() || !value.iter().all(u8::is_ascii_digit) {
+        return Err(RangeError::InvalidContentRange);
+    }
+    value.iter().try_fold(0u64, |acc, &x| {
+        acc.checked_mul(10)
+            .and_then(|acc| acc.checked_add(u64::from(x - b'0')))
+            .ok_or(RangeError::InvalidContentRange)
+    })
+}
+
+/// Errors of the range fetching.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RangeError {
+    /// The response has no `Content-Range` header.
+    MissingContentRange,
+    /// The `Content-Range` header cannot be parsed.
+    InvalidContentRange,
+    /// The response range does not start at the expected offset.
+    UnexpectedRange {
+        /// Expected offset of the first byte.
+        expected: u64,
+        /// Actual offset of the first byte.
+        actual: u64,
+    },
+    /// The response range ends after the requested range.
+    UnexpectedEnd {
+        /// Requested offset of the last byte.
+        expected: u64,
+        /// Actual offset of the last byte.
+        actual: u64,
+    },
+    /// The complete length differs from the one reported by the previous responses, which
+    /// usually means that the resource was modified.
+    LengthMismatch,
+}
+
+impl error::Error for RangeError {}
+
+impl fmt::Display for RangeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RangeError::MissingContentRange => f.write_str("missing content range"),
+            RangeError::InvalidContentRange => f.write_str("invalid content range"),
+            RangeError::UnexpectedRange { expected, actual } => {
+                write!(f, "unexpected range start {actual}, expected {expected}")
+            }
+            RangeError::UnexpectedEnd { expected, actual } => {
+                write!(f, "unexpected range end {actual}, expected at most {expected}")
+            }
+            RangeError::LengthMismatch => f.write_str("complete length mismatch"),
+        }
+    }
+}
+
+/// Tracks the progress of fetching a resource as a sequence of adjacent byte ranges.
+///
+/// The stitcher produces the ranges to request, e.g. with [`Request::range_subrequest`], and
+/// validates that the received responses form a contiguous representation of the same length,
+/// as the nginx `slice` module does.
+#[derive(Clone, Copy, Debug)]
+pub struct RangeStitcher {
+    offset: u64,
+    end: Option<u64>,
+    complete_length: Option<u64>,
+    slice_size: u64,
+}
+
+impl RangeStitcher {
+    /// Creates a stitcher fetching the resource from `offset` in slices of `slice_size` bytes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `slice_size` is zero.
+    pub fn new(offset: u64, slice_size: u64) -> Self {
+        assert!(slice_size > 0, "slice size must be positive");
+        Self { offset, end: None, complete_length: None, slice_size }
+    }
+
+    /// Limits the fetch to the bytes up to `end`, inclusive.
+    pub fn until(mut self, end: u64) -> Self {
+        self.end = Some(end);
+        self
+    }
+
+    /// Returns the offset of the next byte to fetch.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Returns the complete length of the resource, if known from the responses.
+    pub fn complete_length(&self) -> Option<u64> {
+        self.complete_length
+    }
+
+    /// Returns `true` if all the requested bytes are received.
+    pub fn is_complete(&self) -> bool {
+        let last = match (self.end, self.complete_length) {
+            (_, Some(0)) => return true,
+            (Some(end), Some(len)) => end.min(len - 1),
+            (Some(end), None) => end,
+            (None, Some(len)) => len - 1,
+            (None, None) => return false,
+        };
+        self.offset > last
+    }
+
+    /// Returns the next range to request, or `None` if the fetch is complete.
+    pub fn next_range(&self) -> Option<ByteRange> {
+        if self.is_complete() {
+            return None;
+        }
+
+        // Align the slices to the slice size, as the slice module does for the cache efficiency.
+        let slice_end = (self.offset / self.slice_size)
+            .checked_add(1)
+            .and_then(|x| x.checked_mul(self.slice_size))
+            .map_or(u64::MAX, |x| x - 1);
+
+        let mut end = slice_end;
+        if let Some(limit) = self.end {
+            end = end.min(limit);
+        }
+        if let Some(len) = self.complete_length {
+            end = end.min(len - 1);
+        }
+
+        Some(ByteRange { start: self.offset, end })
+    }
+
+    /// Validates the `Content-Range` of a received response and advances the offset.
+    ///
+    /// The response must start at the current offset and must not end after the range returned
+    /// by [`Self::next_range`].
+    pub fn accept(&mut self, cr: &ContentRange) -> Result<(), RangeError> {
+        let Some(expected) = self.next_range().filter(|x| x.start == cr.range.start) else {
+            return Err(RangeError::UnexpectedRange {
+                expected: self.offset,
+                actual: cr.range.start,
+            });
+        };
+
+        if cr.range.end > expected.end {
+            return Err(RangeError::UnexpectedEnd { expected: expected.end, actual: cr.range.end });
+        }
+
+        // The range ending at the last representable offset cannot be followed by another one.
+        let offset = cr.range.end.checked_add(1).ok_or(RangeError::InvalidContentRange)?;
+
+        match (self.complete_length, cr.complete_length) {
+            (Some(known), Some(len)) if known != len => return Err(RangeError::LengthMismatch),
+            (None, Some(len)) => self.complete_length = Some(len),
+            _ => {}
+        }
+
+        self.offset = offset;
+        Ok(())
+    }
+}
+
+impl Request {
+    /// Creates a subrequest fetching `range` of `uri`, and calls `handler` when it is finalized.
+    ///
+    /// The subrequest is a clone of the current request, as in the `slice` module, with the `Range`
+    /// request header replaced. The response passes through the output filters; use
+    /// [`ContentRange::from_response`] in the handler to validate the response. See
+    /// [`Request::subrequest_with`] for the handler semantics.
+    pub fn range_subrequest<F>(
+        &mut self,
+        uri: &str,
+        args: Option<&str>,
+        range: ByteRange,
+        handler: F,
+    ) -> Result<&mut Request, Status>
+    where
+        F: FnOnce(&mut Request, Status) + 'static,
+    {
+        let pool = self.pool();
+        let flags = SubrequestFlags::new().clone_request(true);
+
+        let sr = self.subrequest_with(uri, args, flags, handler)?;
+        set_range_header(sr, &pool, range)?;
+        Ok(sr)
+    }
+}
+
+/// Replaces the `Range` header of a subrequest.
+///
+/// The subrequest shares the header list with the parent request, so a new list is created
+/// without the existing `Range` headers.
+fn set_range_header(sr: &mut Request, pool: &Pool, range: ByteRange) -> Result<(), Status> {
+    let headers_in = &mut sr.as_mut().headers_in;
+    let parent = headers_in.headers;
+
+    // SAFETY: the list is allocated from the request pool.
+    if unsafe {
+        ngx_list_init(&mut headers_in.headers, pool.as_ptr(), 8, mem::size_of::<ngx_table_elt_t>())
+    } != NGX_OK as ngx_int_t
+    {
+        return Err(Status::NGX_ERROR);
+    }
+
+    // SAFETY: the parent list and entries are valid for the lifetime of the request.
+    let mut part = &raw const parent.part;
+    while let Some(p) = unsafe { part.as_ref() } {
+        let elts: *const ngx_table_elt_t = p.elts.cast();
+        for i in 0..p.nelts {
+            let h = unsafe { &*elts.add(i) };
+            if h.hash == 0 || h.key.as_bytes().eq_ignore_ascii_case(b"range") {
+                continue;
+            }
+            let dst: *mut ngx_table_elt_t =
+                unsafe { ngx_list_push(&mut headers_in.headers).cast() };
+            if dst.is_null() {
+                return Err(Status::NGX_ERROR);
+            }
+            unsafe { dst.write(*h) };
+        }
+        part = p.next;
+    }
+
+    let mut buf = [0u8; 48];
+    let mut w = SliceWriter::new(&mut buf);
+    write!(w, "{range}").map_err(|_| Status::NGX_ERROR)?;
+    let len = w.pos;
+
+    let h: *mut ngx_table_elt_t = unsafe { ngx_list_push(&mut headers_in.headers).cast() };
+    // SAFETY: the entry is allocated from the request pool.
+    let h = unsafe { h.as_mut() }.ok_or(Status::NGX_ERROR)?;
+    crate::http::compat::init_table_elt(h);
+    h.key = ngx_str_t { len: 5, data: c"Range".as_ptr().cast_mut().cast() };
+    h.lowcase_key = c"range".as_ptr().cast_mut().cast();
+    h.value = pool.copy_str(&buf[..len]).ok_or(Status::NGX_ERROR)?;
+    h.hash = unsafe { ngx_hash_key(h.lowcase_key, 5) };
+
+    headers_in.range = h;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_content_range() {
+        let cr = ContentRange::parse(b"bytes 0-1023/4096").unwrap();
+        assert_eq!(cr.range, ByteRange { start: 0, end: 1023 });
+        assert_eq!(cr.complete_length, Some(4096));
+
+        let cr = ContentRange::parse(b" bytes 10-19/* ").unwrap();
+        assert_eq!(cr.range.len(), Some(10));
+        assert_eq!(cr.complete_length, None);
+
+        for bad in
+            [&b"bytes 5-4/10"[..], b"bytes 0-10/10", b"bytes */10", b"items 0-1/2", b"bytes 0-/2"]
+        {
+            assert_eq!(ContentRange::parse(bad), Err(RangeError::InvalidContentRange));
+        }
+    }
+
+    #[test]
+    fn test_stitcher() {
+        let mut st = RangeStitcher::new(0, 1000);
+        assert_eq!(st.next_range(), Some(ByteRange { start: 0, end: 999 }));
+
+        st.accept(&ContentRange::parse(b"bytes 0-999/2500").unwrap()).unwrap();
+        assert_eq!(st.next_range(), Some(ByteRange { start: 1000, end: 1999 }));
+
+        assert_eq!(
+            st.accept(&ContentRange::parse(b"bytes 1500-1999/2500").unwrap()),
+            Err(RangeError::UnexpectedRange { expected: 1000, actual: 1500 })
+        );
+        assert_eq!(
+            st.accept(&ContentRange::parse(b"bytes 1000-1999/3000").unwrap()),
+            Err(RangeError::LengthMismatch)
+        );
+
+        st.accept(&ContentRange::parse(b"bytes 1000-1999/2500").unwrap()).unwrap();
+        assert_eq!(st.next_range(), Some(ByteRange { start: 2000, end: 2499 }));
+        st.accept(&ContentRange::parse(b"bytes 2000-2499/2500").unwrap()).unwrap();
+        assert!(st.is_complete());
+        assert_eq!(st.next_range(), None);
+
+        let mut st = RangeStitcher::new(1500, 1000).until(1700);
+        assert_eq!(st.next_range(), Some(ByteRange { start: 1500, end: 1700 }));
+        assert_eq!(
+            st.accept(&ContentRange::parse(b"bytes 1500-1999/*").unwrap()),
+            Err(RangeError::UnexpectedEnd { expected: 1700, actual: 1999 })
+        );
+        assert_eq!(st.offset(), 1500);
+    }
+
+    #[test]
+    fn test_overflow() {
+        let cr = ContentRange::parse(b"bytes 0-18446744073709551615/*").unwrap();
+        assert_eq!(cr.range.len(), None);
+        assert_eq!(ByteRange { start: 1, end: 0 }.len(), None);
+
+        let mut st = RangeStitcher::new(0, 1000);
+        assert_eq!(
+            st.accept(&cr),
+            Err(RangeError::UnexpectedEnd { expected: 999, actual: u64::MAX })
+        );
+
+        let mut st = RangeStitcher::new(u64::MAX, 1000);
+        assert_eq!(st.next_range(), Some(ByteRange { start: u64::MAX, end: u64::MAX }));
+        let cr = ContentRange::parse(b"bytes 18446744073709551615-18446744073709551615/*").unwrap();
+        assert_eq!(st.accept(&cr), Err(RangeError::InvalidContentRange));
+        assert_eq!(st.offset(), u64::MAX);
+    }
+}
@@ -0,0 +1,180 @@
+use core::slice;
+
+use crate::core::Status;
+use crate::http::{HTTPStatus, Request};
+
+impl Request {
+    /// Prepares a redirect response to `url` with the `status`, e.g. `302 Found`.
+    ///
+    /// The characters not allowed in a URL are percent-encoded. A relative reference is resolved
+    /// against the current URI, and an absolute path is converted to an absolute URL by the
+    /// header filter, unless disabled with the `absolute_redirect` directive. The headers
+    /// describing the original response, such as `Content-Length`, are cleared.
+    ///
+    /// Returns the status to be returned from the handler: the redirect status, so that nginx
+    /// generates the response body, or `NGX_ERROR` if `status` is not a redirect status.
+    ///
+    /// ```no_run
+    /// # use ngx::core::Status;
+    /// # use ngx::http::{HTTPStatus, Request};
+    /// # fn handler(r: &mut Request) -> Status {
+    /// r.redirect_to(b"/login?next=/account", HTTPStatus::MOVED_TEMPORARILY)
+    /// # }
+    /// ```
+    pub fn redirect_to(&mut self, url: &[u8], status: HTTPStatus) -> Status {
+        if !matches!(status.0, 301..=303 | 307 | 308) {
+            return Status::NGX_ERROR;
+        }
+
+        let base = match reference_kind(url) {
+            Reference::Absolute => &[][..],
+            Reference::Relative => base_path(self.path().as_bytes()),
+            Reference::Query => self.path().as_bytes(),
+        };
+
+        // The current URI is percent-decoded and may contain any bytes, including CR and LF.
+        let base_len = escaped_len(base, needs_escape_path);
+        let len = base_len + escaped_len(url, needs_escape);
+        let data: *mut u8 = self.pool().alloc_unaligned(len).cast();
+        if data.is_null() {
+            return Status::NGX_ERROR;
+        }
+
+        // SAFETY: `data` points to `len` bytes of uninitialized memory allocated above.
+        let buf = unsafe { slice::from_raw_parts_mut(data, len) };
+        let (head, tail) = buf.split_at_mut(base_len);
+        escape_url(base, head, needs_escape_path);
+        escape_url(url, tail, needs_escape);
+
+        if !self.set_location(buf).is_ok() {
+            return Status::NGX_ERROR;
+        }
+
+        self.clear_content_length();
+        self.clear_last_modified();
+        self.clear_etag();
+        self.clear_accept_ranges();
+        self.remove_header_out("Refresh");
+
+        status.into()
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Reference {
+    /// Absolute URL, network-path or absolute-path reference.
+    Absolute,
+    /// Relative-path reference, resolved against the directory of the current URI.
+    Relative,
+    /// Query or fragment, appended to the current URI.
+    Query,
+}
+
+fn reference_kind(url: &[u8]) -> Reference {
+    match url.first() {
+        Some(b'/') => return Reference::Absolute,
+        Some(b'?' | b'#') => return Reference::Query,
+        _ => {}
+    }
+
+    // RFC 3986, Section 3.1: scheme = ALPHA *( ALPHA / DIGIT / "+" / "-" / "." )
+    let scheme = url.iter().position(|&x| !(x.is_ascii_alphanumeric() || b"+-.".contains(&x)));
+    match scheme {
+        Some(n) if n > 0 && url[0].is_ascii_alphabetic() && url[n] == b':' => Reference::Absolute,
+        _ => Reference::Relative,
+    }
+}
+
+/// Returns the URI up to and including the last slash.
+fn base_path(uri: &[u8]) -> &[u8] {
+    match uri.iter().rposition(|&x| x == b'/') {
+        Some(n) => &uri[..=n],
+        None => b"/",
+    }
+}
+
+fn needs_escape(x: u8) -> bool {
+    x <= b' ' || x >= 0x7f || b"\"<>\\^`{|}".contains(&x)
+}
+
+/// Characters of a decoded path that must be escaped to keep their literal meaning.
+fn needs_escape_path(x: u8) -> bool {
+    needs_escape(x) || b"%?#".contains(&x)
+}
+
+fn escaped_len(url: &[u8], escape: fn(u8) -> bool) -> usize {
+    url.iter().map(|&x| if escape(x) { 3 } else { 1 }).sum()
+}
+
+/// Percent-encodes the characters matching `escape`. `out` must be `escaped_len(url, escape)`
+/// bytes.
+fn escape_url(url: &[u8], out: &mut [u8], escape: fn(u8) -> bool) {
+    const HEX: &[u8; 16] = b"0123456789ABCDEF";
+
+    let mut i = 0;
+    for &x in url {
+        if escape(x) {
+            out[i] = b'%';
+            out[i + 1] = HEX[(x >> 4) as usize];
+            out[i + 2] = HEX[(x & 0xf) as usize];
+            i += 3;
+        } else {
+            out[i] = x;
+            i += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+    use std::vec::Vec;
+
+    use super::*;
+
+    fn escape_with(url: &[u8], escape: fn(u8) -> bool) -> Vec<u8> {
+        let mut out = std::vec![0; escaped_len(url, escape)];
+        escape_url(url, &mut out, escape);
+        out
+    }
+
+    fn escape(url: &[u8]) -> Vec<u8> {
+        escape_with(url, needs_escape)
+    }
+
+    #[test]
+    fn test_escape_url() {
+        assert_eq!(escape(b"/a b?c=\"d\""), b"/a%20b?c=%22d%22");
+        assert_eq!(escape(b"/caf\xc3\xa9"), b"/caf%C3%A9");
+        assert_eq!(escape(b"/x\r\nSet-Cookie: a"), b"/x%0D%0ASet-Cookie:%20a");
+        assert_eq!(escape(b"/already%20escaped"), b"/already%20escaped");
+    }
+
+    #[test]
+    fn test_escape_path() {
+        let base = base_path(b"/a\r\nSet-Cookie:x/page");
+        assert_eq!(escape_with(base, needs_escape_path), b"/a%0D%0ASet-Cookie:x/");
+        assert_eq!(escape_with(b"/100%/a?b#c/", needs_escape_path), b"/100%25/a%3Fb%23c/");
+    }
+
+    #[test]
+    fn test_reference_kind() {
+        assert_eq!(reference_kind(b"https://example.com/"), Reference::Absolute);
+        assert_eq!(reference_kind(b"//example.com/"), Reference::Absolute);
+        assert_eq!(reference_kind(b"/path"), Reference::Absolute);
+        assert_eq!(reference_kind(b"mailto:a@b"), Reference::Absolute);
+        assert_eq!(reference_kind(b"page.html"), Reference::Relative);
+        assert_eq!(reference_kind(b"../up"), Reference::Relative);
+        assert_eq!(reference_kind(b"a/b:c"), Reference::Relative);
+        assert_eq!(reference_kind(b"1a:b"), Reference::Relative);
+        assert_eq!(reference_kind(b"?q=1"), Reference::Query);
+        assert_eq!(reference_kind(b""), Reference::Relative);
+    }
+
+    #[test]
+    fn test_base_path() {
+        assert_eq!(base_path(b"/a/b/c"), b"/a/b/");
+        assert_eq!(base_path(b"/a/"), b"/a/");
+        assert_eq!(base_path(b""), b"/");
+    }
+}
@@ -85,7 +85,34 @@ macro_rules! http_variable_get {
 /// in the `into_handler_status` method.
 ///
 /// There are predefined implementations for `ngx_int_t`, [`Status`], [`HTTPStatus`],
-/// [`Option`] with value type implementing [`IntoHandlerStatus`].
+/// [`Option`] with value type implementing [`IntoHandlerStatus`], and [`Result`] with value type
+/// convertible to [`Status`] and error type convertible to [`HTTPStatus`].
+///
+/// The latter allows mapping the errors of a handler to the HTTP responses with a [`From`]
+/// implementation:
+///
+/// ```
+/// # use ngx::core::Status;
+/// # use ngx::http::{HTTPStatus, Request};
+/// enum ApiError {
+///     NotFound,
+///     Backend,
+/// }
+///
+/// impl From<ApiError> for HTTPStatus {
+///     fn from(err: ApiError) -> Self {
+///         match err {
+///             ApiError::NotFound => HTTPStatus::NOT_FOUND,
+///             ApiError::Backend => HTTPStatus::BAD_GATEWAY,
+///         }
+///     }
+/// }
+///
+/// fn handler(_r: &mut Request) -> Result<Status, ApiError> {
+///     // ...
+/// #   Err(ApiError::NotFound)
+/// }
+/// ```
 pub trait IntoHandlerStatus
 where
     Self: Sized,
@@ -104,6 +131,20 @@ where
     }
 }
 
+impl<T, E> IntoHandlerStatus for Result<T, E>
+where
+    T: Into<Status>,
+    E: Into<HTTPStatus>,
+{
+    #[inline]
+    fn into_handler_status(self, _r: &Request) -> ngx_int_t {
+        match self {
+            Ok(val) => val.into().0,
+            Err(err) => err.into().0 as _,
+        }
+    }
+}
+
 impl IntoHandlerStatus for ngx_int_t {
     #[inline]
     fn into_handler_status(self, _r: &Request) -> ngx_int_t {
@@ -150,7 +191,32 @@ where
     H: HttpRequestHandler,
 {
     let r = unsafe { Request::from_ngx_http_request(r) };
-    H::handler(r).into_handler_status(r)
+
+    #[cfg(feature = "handler-trace")]
+    let start = std::time::Instant::now();
+
+    let rc = H::handler(r).into_handler_status(r);
+
+    #[cfg(feature = "handler-trace")]
+    trace_handler::<H>(r, rc, start.elapsed());
+
+    rc
+}
+
+/// Logs the handler invocation at the `debug` level.
+#[cfg(feature = "handler-trace")]
+fn trace_handler<H: HttpRequestHandler>(r: &Request, rc: ngx_int_t, elapsed: core::time::Duration) {
+    let log = r.log();
+    crate::ngx_log_error!(
+        NGX_LOG_DEBUG,
+        log,
+        "http handler {} phase:{:?} rc:{} {}us{}",
+        H::name(),
+        H::PHASE,
+        rc,
+        elapsed.as_micros(),
+        if r.is_main() { "" } else { " subrequest" },
+    );
 }
 
 /// Wrapper struct for an [`ngx_http_request_t`] pointer, providing methods for working with HTTP
@@ -221,18 +287,20 @@ impl Request {
         Some(self.0.upstream)
     }
 
-    /// Pointer to a [`ngx_connection_t`] client connection object.
+    /// Returns the [`Connection`] the request is received on.
     ///
-    /// [`ngx_connection_t`]: https://nginx.org/en/docs/dev/development_guide.html#connection
-    pub fn connection(&self) -> *mut ngx_connection_t {
-        self.0.connection
+    /// For HTTP/2 and HTTP/3 requests, this is the connection object of the stream; see
+    /// [`Request::client_connection`] for the client connection.
+    pub fn connection(&mut self) -> &mut Connection {
+        // SAFETY: the connection is valid for the lifetime of the request.
+        unsafe { Connection::from_ptr_mut(self.0.connection) }
     }
 
     /// Pointer to a [`ngx_log_t`].
     ///
     /// [`ngx_log_t`]: https://nginx.org/en/docs/dev/development_guide.html#logging
     pub fn log(&self) -> *mut ngx_log_t {
-        unsafe { (*self.connection()).log }
+        unsafe { (*self.0.connection).log }
     }
 
     /// Get Module context pointer
@@ -241,6 +309,9 @@ impl Request {
     }
 
     /// Get Module context
+    ///
+    /// The context belongs to this request only, and is not shared between the main request and
+    /// its subrequests. See [`RequestContext`](crate::http::RequestContext) for explicit control.
     pub fn get_module_ctx<T>(&self, module: &ngx_module_t) -> Option<&T> {
         let ctx = self.get_module_ctx_ptr(module).cast::<T>();
         // SAFETY: ctx is either NULL or allocated with ngx_p(c)alloc and
@@ -259,6 +330,8 @@ impl Request {
 
     /// Get the value of a [complex value].
     ///
+    /// The value is not guaranteed to be valid UTF-8 if it contains variables.
+    ///
     /// [complex value]: https://nginx.org/en/docs/dev/development_guide.html#http_complex_values
     pub fn get_complex_value(&self, cv: &ngx_http_complex_value_t) -> Option<&NgxStr> {
         let r = (self as *const Request as *mut Request).cast();
@@ -283,6 +356,8 @@ impl Request {
 
     /// Client HTTP [User-Agent].
     ///
+    /// The value is sent by the client and is not guaranteed to be valid UTF-8.
+    ///
     /// [User-Agent]: https://developer.mozilla.org/en-US/docs/Web/HTTP/Headers/User-Agent
     pub fn user_agent(&self) -> Option<&NgxStr> {
         if !self.0.headers_in.user_agent.is_null() {
@@ -315,6 +390,60 @@ impl Request {
         unsafe { add_to_ngx_table(table, self.0.pool, key, value) }
     }
 
+    /// Returns the response status.
+    pub fn status(&self) -> HTTPStatus {
+        HTTPStatus(self.0.headers_out.status)
+    }
+
+    /// Returns the value of the first response header with the name, ignoring case.
+    ///
+    /// Removed headers are skipped. The value is not guaranteed to be valid UTF-8.
+    pub fn header_out(&self, key: &str) -> Option<&NgxStr> {
+        // SAFETY: the list entries are valid for the lifetime of the request.
+        headers_out_entries(&self.0.headers_out.headers)
+            .map(|h| unsafe { &*h })
+            .find(|h| h.hash != 0 && h.key.as_bytes().eq_ignore_ascii_case(key.as_bytes()))
+            .map(|h| unsafe { NgxStr::from_ngx_str(h.value) })
+    }
+
+    /// Removes all the response headers with the name, ignoring case.
+    ///
+    /// Returns `true` if any header was removed. The headers with dedicated fields in
+    /// `headers_out`, such as `Content-Length` or `ETag`, are cleared as well.
+    pub fn remove_header_out(&mut self, key: &str) -> bool {
+        let key = key.as_bytes();
+        let mut removed = false;
+
+        for h in headers_out_entries(&self.0.headers_out.headers) {
+            // SAFETY: the list entries are valid for the lifetime of the request.
+            let h = unsafe { &mut *h };
+            if h.hash != 0 && h.key.as_bytes().eq_ignore_ascii_case(key) {
+                h.hash = 0;
+                removed = true;
+            }
+        }
+
+        if key.eq_ignore_ascii_case(b"Content-Length") {
+            self.clear_content_length();
+        } else if key.eq_ignore_ascii_case(b"ETag") {
+            self.clear_etag();
+        } else if key.eq_ignore_ascii_case(b"Last-Modified") {
+            self.clear_last_modified();
+        } else if key.eq_ignore_ascii_case(b"Accept-Ranges") {
+            self.clear_accept_ranges();
+        } else if key.eq_ignore_ascii_case(b"Location") {
+            self.0.headers_out.location = core::ptr::null_mut();
+        } else if key.eq_ignore_ascii_case(b"Content-Encoding") {
+            self.0.headers_out.content_encoding = core::ptr::null_mut();
+        } else if key.eq_ignore_ascii_case(b"WWW-Authenticate") {
+            self.0.headers_out.www_authenticate = core::ptr::null_mut();
+        } else if key.eq_ignore_ascii_case(b"Refresh") {
+            self.0.headers_out.refresh = core::ptr::null_mut();
+        }
+
+        removed
+    }
+
     /// Set response body [Content-Length].
     ///
     /// [Content-Length]: https://developer.mozilla.org/en-US/docs/Web/HTTP/Headers/Content-Length
@@ -322,6 +451,128 @@ impl Request {
         self.0.headers_out.content_length_n = n as off_t;
     }
 
+    /// Set response [Last-Modified] time.
+    ///
+    /// [Last-Modified]: https://developer.mozilla.org/en-US/docs/Web/HTTP/Headers/Last-Modified
+    pub fn set_last_modified(&mut self, time: time_t) {
+        self.0.headers_out.last_modified_time = time;
+    }
+
+    /// Set the default nginx [ETag], derived from the response `Last-Modified` time and
+    /// `Content-Length`.
+    ///
+    /// Both values must be set before calling this method. The header is not added if disabled
+    /// with the `etag` directive.
+    ///
+    /// [ETag]: https://developer.mozilla.org/en-US/docs/Web/HTTP/Headers/ETag
+    pub fn set_etag(&mut self) -> Status {
+        unsafe { Status(ngx_http_set_etag(&raw mut self.0)) }
+    }
+
+    /// Set a strong [ETag] with the hex-encoded `digest` of the response body.
+    ///
+    /// Replaces the previously set ETag. The header is not added if disabled with the `etag`
+    /// directive.
+    ///
+    /// [ETag]: https://developer.mozilla.org/en-US/docs/Web/HTTP/Headers/ETag
+    pub fn set_strong_etag(&mut self, digest: &[u8]) -> Status {
+        const HEX: &[u8; 16] = b"0123456789abcdef";
+
+        if let Some(clcf) = crate::http::NgxHttpCoreModule::location_conf(self) {
+            if clcf.etag == 0 {
+                return Status::NGX_OK;
+            }
+        }
+
+        let len = 2 * digest.len() + 2;
+        let data: *mut u8 = self.pool().alloc_unaligned(len).cast();
+        if data.is_null() {
+            return Status::NGX_ERROR;
+        }
+
+        // SAFETY: `data` points to `len` bytes of uninitialized memory allocated above.
+        let value = unsafe { slice::from_raw_parts_mut(data, len) };
+        value[0] = b'"';
+        for (i, x) in digest.iter().enumerate() {
+            value[1 + 2 * i] = HEX[(x >> 4) as usize];
+            value[2 + 2 * i] = HEX[(x & 0xf) as usize];
+        }
+        value[len - 1] = b'"';
+
+        let etag: *mut ngx_table_elt_t =
+            unsafe { ngx_list_push(&raw mut self.0.headers_out.headers).cast() };
+        if etag.is_null() {
+            return Status::NGX_ERROR;
+        }
+
+        self.clear_etag();
+
+        // SAFETY: `etag` is a valid list element allocated above.
+        unsafe {
+            etag.write_bytes(0, 1);
+            (*etag).hash = 1;
+            (*etag).key = crate::ngx_string!("ETag");
+            (*etag).value = ngx_str_t { len, data };
+        }
+        self.0.headers_out.etag = etag;
+
+        Status::NGX_OK
+    }
+
+    /// Convert the response [ETag] to a weak one.
+    ///
+    /// Should be called by filters modifying the response body.
+    ///
+    /// [ETag]: https://developer.mozilla.org/en-US/docs/Web/HTTP/Headers/ETag
+    pub fn weaken_etag(&mut self) {
+        unsafe { ngx_http_weak_etag(&raw mut self.0) }
+    }
+
+    /// Remove the response [ETag].
+    ///
+    /// [ETag]: https://developer.mozilla.org/en-US/docs/Web/HTTP/Headers/ETag
+    pub fn clear_etag(&mut self) {
+        if let Some(etag) = unsafe { self.0.headers_out.etag.as_mut() } {
+            etag.hash = 0;
+            self.0.headers_out.etag = core::ptr::null_mut();
+        }
+    }
+
+    /// Remove the response [Content-Length].
+    ///
+    /// Should be called by filters changing the length of the response body.
+    ///
+    /// [Content-Length]: https://developer.mozilla.org/en-US/docs/Web/HTTP/Headers/Content-Length
+    pub fn clear_content_length(&mut self) {
+        self.0.headers_out.content_length_n = -1;
+        if let Some(h) = unsafe { self.0.headers_out.content_length.as_mut() } {
+            h.hash = 0;
+            self.0.headers_out.content_length = core::ptr::null_mut();
+        }
+    }
+
+    /// Remove the response [Accept-Ranges] and disable the range requests.
+    ///
+    /// [Accept-Ranges]: https://developer.mozilla.org/en-US/docs/Web/HTTP/Headers/Accept-Ranges
+    pub fn clear_accept_ranges(&mut self) {
+        self.0.set_allow_ranges(0);
+        if let Some(h) = unsafe { self.0.headers_out.accept_ranges.as_mut() } {
+            h.hash = 0;
+            self.0.headers_out.accept_ranges = core::ptr::null_mut();
+        }
+    }
+
+    /// Remove the response [Last-Modified].
+    ///
+    /// [Last-Modified]: https://developer.mozilla.org/en-US/docs/Web/HTTP/Headers/Last-Modified
+    pub fn clear_last_modified(&mut self) {
+        self.0.headers_out.last_modified_time = -1;
+        if let Some(h) = unsafe { self.0.headers_out.last_modified.as_mut() } {
+            h.hash = 0;
+            self.0.headers_out.last_modified = core::ptr::null_mut();
+        }
+    }
+
     /// Send the output header.
     ///
     /// Do not call this function until all output headers are set.
@@ -341,12 +592,16 @@ impl Request {
         Method::from_ngx(self.0.method)
     }
 
-    /// path part of request only
+    /// Path part of the request URI, percent-decoded and normalized.
+    ///
+    /// The decoding may produce any bytes, and the path is not guaranteed to be valid UTF-8.
     pub fn path(&self) -> &NgxStr {
         unsafe { NgxStr::from_ngx_str(self.0.uri) }
     }
 
-    /// full uri - containing path and args
+    /// Request URI as sent by the client, including the arguments.
+    ///
+    /// The URI is not decoded, and is not guaranteed to be valid UTF-8.
     pub fn unparsed_uri(&self) -> &NgxStr {
         unsafe { NgxStr::from_ngx_str(self.0.unparsed_uri) }
     }
@@ -438,18 +693,6 @@ impl Request {
         sr.set_header_only(1 as _);
         Status(r)
     }
-
-    /// Iterate over headers_in
-    /// each header item is (&str, &str) (borrowed)
-    pub fn headers_in_iterator(&self) -> NgxListIterator<'_> {
-        unsafe { list_iterator(&self.0.headers_in.headers) }
-    }
-
-    /// Iterate over headers_out
-    /// each header item is (&str, &str) (borrowed)
-    pub fn headers_out_iterator(&self) -> NgxListIterator<'_> {
-        unsafe { list_iterator(&self.0.headers_out.headers) }
-    }
 }
 
 impl crate::http::HttpModuleConfExt for Request {
@@ -491,61 +734,26 @@ impl fmt::Debug for Request {
     }
 }
 
-/// Iterator for [`ngx_list_t`] types.
-///
-/// Implementes the core::iter::Iterator trait.
-pub struct NgxListIterator<'a> {
-    part: Option<ListPart<'a>>,
-    i: ngx_uint_t,
-}
-struct ListPart<'a> {
-    raw: &'a ngx_list_part_t,
-    arr: &'a [ngx_table_elt_t],
-}
-impl<'a> From<&'a ngx_list_part_t> for ListPart<'a> {
-    fn from(raw: &'a ngx_list_part_t) -> Self {
-        let arr = if raw.nelts != 0 {
-            unsafe { slice::from_raw_parts(raw.elts.cast(), raw.nelts) }
-        } else {
-            &[]
-        };
-        Self { raw, arr }
-    }
-}
+/// Returns the raw pointers to the elements of a list of headers.
+fn headers_out_entries(list: &ngx_list_t) -> impl Iterator<Item = *mut ngx_table_elt_t> + '_ {
+    let mut part: *const ngx_list_part_t = &list.part;
+    let mut i = 0;
 
-/// Creates new HTTP header iterator
-///
-/// # Safety
-///
-/// The caller has provided a valid [`ngx_str_t`] which can be dereferenced validly.
-pub unsafe fn list_iterator(list: &ngx_list_t) -> NgxListIterator<'_> {
-    NgxListIterator { part: Some((&list.part).into()), i: 0 }
-}
-
-// iterator for ngx_list_t
-impl<'a> Iterator for NgxListIterator<'a> {
-    // TODO: try to use struct instead of &str pair
-    // something like pub struct Header(ngx_table_elt_t);
-    // then header would have key and value
-
-    type Item = (&'a NgxStr, &'a NgxStr);
-
-    fn next(&mut self) -> Option<Self::Item> {
-        let part = self.part.as_mut()?;
-        if self.i >= part.arr.len() {
-            if let Some(next_part_raw) = unsafe { part.raw.next.as_ref() } {
-                // loop back
-                *part = next_part_raw.into();
-                self.i = 0;
-            } else {
-                self.part = None;
-                return None;
+    core::iter::from_fn(move || {
+        // SAFETY: the list parts are valid for the lifetime of the list.
+        unsafe {
+            while i >= (*part).nelts {
+                part = (*part).next;
+                if part.is_null() {
+                    return None;
+                }
+                i = 0;
             }
+            let h = (*part).elts.cast::<ngx_table_elt_t>().add(i);
+            i += 1;
+            Some(h)
         }
-        let header = &part.arr[self.i];
-        self.i += 1;
-        unsafe { Some((NgxStr::from_ngx_str(header.key), NgxStr::from_ngx_str(header.value))) }
-    }
+    })
 }
 
 /// A possible error value when converting `Method`
@@ -660,6 +868,29 @@ impl Method {
             _ => Method(MethodInner::Unknown),
         }
     }
+
+    pub(crate) fn to_ngx(&self) -> ngx_uint_t {
+        (match self.0 {
+            MethodInner::Get => crate::ffi::NGX_HTTP_GET,
+            MethodInner::Head => crate::ffi::NGX_HTTP_HEAD,
+            MethodInner::Post => crate::ffi::NGX_HTTP_POST,
+            MethodInner::Put => crate::ffi::NGX_HTTP_PUT,
+            MethodInner::Delete => crate::ffi::NGX_HTTP_DELETE,
+            MethodInner::Mkcol => crate::ffi::NGX_HTTP_MKCOL,
+            MethodInner::Copy => crate::ffi::NGX_HTTP_COPY,
+            MethodInner::Move => crate::ffi::NGX_HTTP_MOVE,
+            MethodInner::Options => crate::ffi::NGX_HTTP_OPTIONS,
+            MethodInner::Propfind => crate::ffi::NGX_HTTP_PROPFIND,
+            MethodInner::Proppatch => crate::ffi::NGX_HTTP_PROPPATCH,
+            MethodInner::Lock => crate::ffi::NGX_HTTP_LOCK,
+            MethodInner::Unlock => crate::ffi::NGX_HTTP_UNLOCK,
+            MethodInner::Patch => crate::ffi::NGX_HTTP_PATCH,
+            MethodInner::Trace => crate::ffi::NGX_HTTP_TRACE,
+            #[cfg(nginx1_21_1)]
+            MethodInner::Connect => crate::ffi::NGX_HTTP_CONNECT,
+            _ => crate::ffi::NGX_HTTP_UNKNOWN,
+        }) as ngx_uint_t
+    }
 }
 
 impl AsRef<str> for Method {
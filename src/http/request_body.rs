@@ -0,0 +1,515 @@
+use core::ptr;
+use core::slice;
+
+use crate::core::{Buffer, Status};
+use crate::ffi::{
+    NGX_ERROR, NGX_HTTP_SPECIAL_RESPONSE, ngx_buf_t, ngx_http_finalize_request,
+    ngx_http_read_client_request_body, ngx_http_request_body_t, ngx_http_request_t, ngx_read_file,
+};
+use crate::http::{Chain, ChainBuf, ChainIter, IntoHandlerStatus, Request};
+
+/// Trait for a handler invoked when the request body is read.
+///
+/// # Example
+///
+/// ```no_run
+/// # use ngx::core::Status;
+/// # use ngx::http::{HTTPStatus, ReadBodyHandler, Request};
+/// struct EchoLength;
+///
+/// impl ReadBodyHandler for EchoLength {
+///     type Output = Status;
+///
+///     fn handler(r: &mut Request) -> Status {
+///         let len = r.request_body().map_or(0, |body| body.len());
+///         ngx::ngx_log_debug_http!(r, "request body: {len} bytes");
+///         r.set_status(HTTPStatus::NO_CONTENT);
+///         r.set_content_length_n(0);
+///         r.send_header()
+///     }
+/// }
+///
+/// // in the content handler:
+/// # fn content(r: &mut Request) -> Status {
+/// r.read_body::<EchoLength>()
+/// # }
+/// ```
+pub trait ReadBodyHandler {
+    /// The return type of the handler.
+    type Output: IntoHandlerStatus;
+
+    /// The handler function.
+    ///
+    /// The returned status finalizes the request, as `ngx_http_finalize_request` does in the
+    /// body handlers of the C modules.
+    fn handler(r: &mut Request) -> Self::Output;
+}
+
+unsafe extern "C" fn raw_read_body_handler<H>(r: *mut ngx_http_request_t)
+where
+    H: ReadBodyHandler,
+{
+    let req = unsafe { Request::from_ngx_http_request(r) };
+    let rc = H::handler(req).into_handler_status(req);
+    unsafe { ngx_http_finalize_request(r, rc) };
+}
+
+impl Request {
+    /// Reads the [request body] and invokes `H` once the whole body is received.
+    ///
+    /// The handler may be invoked before this method returns. The result is intended to be
+    /// returned from the content handler: the error statuses as is, and [`Status::NGX_DONE`]
+    /// otherwise.
+    ///
+    /// [request body]: https://nginx.org/en/docs/dev/development_guide.html#http_request_body
+    pub fn read_body<H>(&mut self) -> Status
+    where
+        H: ReadBodyHandler,
+    {
+        let rc = unsafe {
+            ngx_http_read_client_request_body(self.into(), Some(raw_read_body_handler::<H>))
+        };
+
+        if rc >= NGX_HTTP_SPECIAL_RESPONSE as _ {
+            return Status(rc);
+        }
+
+        Status::NGX_DONE
+    }
+
+    /// Returns the request body received with [`Request::read_body`].
+    pub fn request_body(&self) -> Option<RequestBody<'_>> {
+        let rb = unsafe { self.as_ref().request_body.as_ref()? };
+        Some(RequestBody { r: self, rb })
+    }
+}
+
+/// Request body received with [`Request::read_body`].
+///
+/// Depending on the configuration and the body size, the data is kept in memory buffers, in a
+/// temporary file, or both.
+pub struct RequestBody<'a> {
+    r: &'a Request,
+    rb: &'a ngx_http_request_body_t,
+}
+
+impl<'a> RequestBody<'a> {
+    /// Returns the chain of the body buffers.
+    pub fn chain(&self) -> Chain<'a> {
+        unsafe { Chain::from_raw(self.rb.bufs) }
+    }
+
+    /// Returns an iterator over the body buffers.
+    ///
+    /// The buffers written to the temporary file are not in memory, see
+    /// [`ChainBuf::in_memory`].
+    pub fn iter(&self) -> ChainIter<'a> {
+        self.chain().into_iter()
+    }
+
+    /// Returns `true` if any part of the body is written to a temporary file.
+    pub fn in_file(&self) -> bool {
+        !self.rb.temp_file.is_null()
+    }
+
+    /// Returns the body size in bytes.
+    pub fn len(&self) -> usize {
+        self.iter().map(|b| unsafe { buf_size(&*b.as_ngx_buf()) }).sum()
+    }
+
+    /// Returns `true` if the body is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Collects the body into a contiguous slice allocated from the request pool.
+    ///
+    /// The data in the temporary file is read back into memory. If the body is a single memory
+    /// buffer, the buffer data is returned without copying.
+    ///
+    /// Returns `None` if the allocation or the file read fails; the read errors are logged.
+    pub fn collect(&self) -> Option<&'a [u8]> {
+        let mut bufs = self.iter();
+
+        match (bufs.next(), bufs.next()) {
+            (None, _) => return Some(&[]),
+            (Some(b), None) if b.in_memory() => return Some(b.as_bytes()),
+            _ => {}
+        }
+
+        let size = self.len();
+        if size == 0 {
+            return Some(&[]);
+        }
+
+        let data = self.r.pool().alloc_unaligned(size).cast::<u8>();
+        if data.is_null() {
+            return None;
+        }
+
+        let mut p = data;
+        for b in self.iter() {
+            let b = unsafe { &*b.as_ngx_buf() };
+            let n = buf_size(b);
+
+            if b.in_file() != 0 && !(b.temporary() != 0 || b.memory() != 0 || b.mmap() != 0) {
+                let rc = unsafe { ngx_read_file(b.file, p, n, b.file_pos) };
+                if rc == NGX_ERROR as isize || rc as usize != n {
+                    return None;
+                }
+            } else if n > 0 {
+                unsafe { ptr::copy_nonoverlapping(b.pos, p, n) };
+            }
+
+            p = unsafe { p.add(n) };
+        }
+
+        Some(unsafe { slice::from_raw_parts(data, size) })
+    }
+}
+
+impl<'a> IntoIterator for &RequestBody<'a> {
+    type Item = ChainBuf<'a>;
+    type IntoIter = ChainIter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.chain().into_iter()
+    }
+}
+
+/// Returns the size of the buffer data, in memory or in file.
+fn buf_size(b: &ngx_buf_t) -> usize {
+    if b.temporary() != 0 || b.memory() != 0 || b.mmap() != 0 {
+        unsafe { b.last.offset_from(b.pos) as usize }
+    } else if b.in_file() != 0 {
+        (b.file_last - b.file_pos) as usize
+    } else {
+        0
+    }
+}
+
+#[cfg(feature = "async")]
+pub use self::read_body_async::ReadBody;
+
+#[cfg(feature = "async")]
+mod read_body_async {
+    use core::ffi::c_void;
+    use core::future::Future;
+    use core::mem;
+    use core::pin::Pin;
+    use core::ptr::{self, NonNull};
+    use core::task::{self, Poll, Waker};
+
+    use crate::core::Status;
+    use crate::ffi::{
+        NGX_HTTP_SPECIAL_RESPONSE, ngx_http_read_client_request_body, ngx_http_request_t,
+        ngx_pool_cleanup_add,
+    };
+    use crate::http::Request;
+
+    /// Future returned by [`Request::read_body_async`].
+    pub struct ReadBody {
+        r: NonNull<ngx_http_request_t>,
+        state: *mut ReadBodyState,
+    }
+
+    struct ReadBodyState {
+        done: bool,
+        waker: Option<Waker>,
+    }
+
+    impl Request {
+        /// Reads the [request body] asynchronously.
+        ///
+        /// The future resolves once the whole body is received and available with
+        /// [`Request::request_body`], or to the error status that should finalize the request.
+        /// As with [`Request::read_body`], the request is referenced until it is finalized, and
+        /// the content handler should return [`Status::NGX_DONE`] after spawning the task.
+        ///
+        /// The future must be polled from the NGINX event loop, e.g. in a task started with
+        /// [`crate::async_::spawn`].
+        ///
+        /// [request body]: https://nginx.org/en/docs/dev/development_guide.html#http_request_body
+        pub fn read_body_async(&mut self) -> ReadBody {
+            ReadBody { r: NonNull::from(self.as_mut()), state: ptr::null_mut() }
+        }
+    }
+
+    impl Future for ReadBody {
+        type Output = Result<(), Status>;
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<Self::Output> {
+            if self.state.is_null() {
+                let r = self.r.as_ptr();
+                // SAFETY: the request outlives the body reading, as it is referenced until
+                // finalized.
+                let state = unsafe { add_state(r) };
+                if state.is_null() {
+                    return Poll::Ready(Err(Status::NGX_ERROR));
+                }
+                self.state = state;
+
+                let rc = unsafe { ngx_http_read_client_request_body(r, Some(read_body_handler)) };
+                if rc >= NGX_HTTP_SPECIAL_RESPONSE as _ {
+                    return Poll::Ready(Err(Status(rc)));
+                }
+            }
+
+            let state = unsafe { &mut *self.state };
+            if state.done {
+                return Poll::Ready(Ok(()));
+            }
+
+            state.waker = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+
+    /// Allocates the state in a request pool cleanup, so that the body handler can find it.
+    unsafe fn add_state(r: *mut ngx_http_request_t) -> *mut ReadBodyState {
+        let cln = unsafe { ngx_pool_cleanup_add((*r).pool, mem::size_of::<ReadBodyState>()) };
+        if cln.is_null() {
+            return ptr::null_mut();
+        }
+
+        unsafe {
+            let state = (*cln).data.cast::<ReadBodyState>();
+            ptr::write(state, ReadBodyState { done: false, waker: None });
+            (*cln).handler = Some(cleanup_state);
+            state
+        }
+    }
+
+    unsafe extern "C" fn cleanup_state(data: *mut c_void) {
+        unsafe { ptr::drop_in_place(data.cast::<ReadBodyState>()) };
+    }
+
+    unsafe extern "C" fn read_body_handler(r: *mut ngx_http_request_t) {
+        let mut cln = unsafe { (*(*r).pool).cleanup };
+
+        while let Some(c) = unsafe { cln.as_ref() } {
+            if c.handler == Some(cleanup_state as unsafe extern "C" fn(_)) {
+                let state = unsafe { &mut *c.data.cast::<ReadBodyState>() };
+                state.done = true;
+                if let Some(waker) = state.waker.take() {
+                    waker.wake();
+                }
+                return;
+            }
+            cln = c.next;
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+pub use self::body_stream::{BodyChunk, BodyStream};
+
+#[cfg(feature = "async")]
+mod body_stream {
+    use core::ffi::c_void;
+    use core::future::{self, Future};
+    use core::mem;
+    use core::pin::Pin;
+    use core::ptr::{self, NonNull};
+    use core::task::{self, Poll, Waker};
+
+    use futures_core::Stream;
+
+    use crate::core::Status;
+    use crate::ffi::{
+        NGX_HTTP_SPECIAL_RESPONSE, ngx_chain_t, ngx_http_read_client_request_body,
+        ngx_http_read_unbuffered_request_body, ngx_http_request_t, ngx_pool_cleanup_add,
+    };
+    use crate::http::{Chain, ChainIter, Request};
+
+    /// Stream of the request body chunks returned by [`Request::body_stream`].
+    pub struct BodyStream {
+        r: NonNull<ngx_http_request_t>,
+        state: *mut BodyStreamState,
+        done: bool,
+    }
+
+    struct BodyStreamState {
+        waker: Option<Waker>,
+    }
+
+    /// Chunk of the request body, as received from the client.
+    ///
+    /// The buffers are marked as consumed when the chunk is dropped, which allows nginx to reuse
+    /// the memory for reading more data. The chunks should be processed promptly: nginx stops
+    /// reading the body while the buffer is full of unconsumed data.
+    pub struct BodyChunk {
+        cl: *mut ngx_chain_t,
+    }
+
+    impl BodyChunk {
+        /// Returns the chain of the buffers.
+        pub fn chain(&self) -> Chain<'_> {
+            // SAFETY: the chain is allocated from the request pool and is not modified by nginx
+            // until the buffers are consumed.
+            unsafe { Chain::from_raw(self.cl) }
+        }
+
+        /// Returns an iterator over the buffers.
+        pub fn iter(&self) -> ChainIter<'_> {
+            self.chain().iter()
+        }
+
+        /// Returns the size of the chunk.
+        pub fn len(&self) -> usize {
+            self.chain().len()
+        }
+
+        /// Returns `true` if the chunk has no data.
+        pub fn is_empty(&self) -> bool {
+            self.len() == 0
+        }
+    }
+
+    impl Drop for BodyChunk {
+        fn drop(&mut self) {
+            for mut b in self.chain() {
+                b.consume();
+            }
+        }
+    }
+
+    impl Request {
+        /// Reads the [request body] as a stream of chunks, without buffering the whole body.
+        ///
+        /// The body is read with `request_body_no_buffering`: the chunks are returned as soon as
+        /// the data is received from the client, and are not saved to a temporary file. The
+        /// stream ends when the whole body is read, or yields the error status that should
+        /// finalize the request. As with [`Request::read_body_async`], the request is referenced
+        /// until it is finalized, and the content handler should return [`Status::NGX_DONE`]
+        /// after spawning the task.
+        ///
+        /// The method must be called before the body is read by any other means. The stream must
+        /// be polled from the NGINX event loop, e.g. in a task started with
+        /// [`crate::async_::spawn`].
+        ///
+        /// ```no_run
+        /// # use ngx::core::Status;
+        /// # use ngx::http::Request;
+        /// async fn count(r: &mut Request) -> Result<usize, Status> {
+        ///     let mut body = r.body_stream();
+        ///     let mut total = 0;
+        ///     while let Some(chunk) = body.next().await {
+        ///         total += chunk?.len();
+        ///     }
+        ///     Ok(total)
+        /// }
+        /// ```
+        ///
+        /// [request body]: https://nginx.org/en/docs/dev/development_guide.html#http_request_body
+        pub fn body_stream(&mut self) -> BodyStream {
+            BodyStream { r: NonNull::from(self.as_mut()), state: ptr::null_mut(), done: false }
+        }
+    }
+
+    impl BodyStream {
+        /// Returns the next chunk of the body, or `None` once the whole body is read.
+        pub fn next(&mut self) -> impl Future<Output = Option<Result<BodyChunk, Status>>> + '_ {
+            future::poll_fn(|cx| Pin::new(&mut *self).poll_next(cx))
+        }
+    }
+
+    impl Stream for BodyStream {
+        type Item = Result<BodyChunk, Status>;
+
+        fn poll_next(
+            mut self: Pin<&mut Self>,
+            cx: &mut task::Context<'_>,
+        ) -> Poll<Option<Self::Item>> {
+            if self.done {
+                return Poll::Ready(None);
+            }
+
+            let r = self.r.as_ptr();
+
+            let rc = if self.state.is_null() {
+                // SAFETY: the request outlives the body reading, as it is referenced until
+                // finalized.
+                let state = unsafe { add_state(r) };
+                if state.is_null() {
+                    self.done = true;
+                    return Poll::Ready(Some(Err(Status::NGX_ERROR)));
+                }
+                self.state = state;
+
+                unsafe {
+                    (*r).set_request_body_no_buffering(1);
+                    ngx_http_read_client_request_body(r, Some(body_stream_handler))
+                }
+            } else if unsafe { (*r).reading_body() } != 0 {
+                // The previous chunks may have been consumed since the last read, read more.
+                unsafe { ngx_http_read_unbuffered_request_body(r) }
+            } else {
+                0
+            };
+
+            if rc >= NGX_HTTP_SPECIAL_RESPONSE as _ {
+                self.done = true;
+                return Poll::Ready(Some(Err(Status(rc))));
+            }
+
+            // SAFETY: the request body is allocated from the request pool.
+            let Some(rb) = (unsafe { (*r).request_body.as_mut() }) else {
+                self.done = true;
+                return Poll::Ready(None);
+            };
+
+            // The buffers are removed from the request body, as done by the upstream module.
+            let cl = mem::replace(&mut rb.bufs, ptr::null_mut());
+            if !cl.is_null() {
+                return Poll::Ready(Some(Ok(BodyChunk { cl })));
+            }
+
+            if unsafe { (*r).reading_body() } == 0 {
+                self.done = true;
+                return Poll::Ready(None);
+            }
+
+            unsafe {
+                (*r).read_event_handler = Some(body_stream_handler);
+                (*self.state).waker = Some(cx.waker().clone());
+            }
+            Poll::Pending
+        }
+    }
+
+    /// Allocates the state in a request pool cleanup, so that the event handler can find it.
+    unsafe fn add_state(r: *mut ngx_http_request_t) -> *mut BodyStreamState {
+        let cln = unsafe { ngx_pool_cleanup_add((*r).pool, mem::size_of::<BodyStreamState>()) };
+        if cln.is_null() {
+            return ptr::null_mut();
+        }
+
+        unsafe {
+            let state = (*cln).data.cast::<BodyStreamState>();
+            ptr::write(state, BodyStreamState { waker: None });
+            (*cln).handler = Some(cleanup_state);
+            state
+        }
+    }
+
+    unsafe extern "C" fn cleanup_state(data: *mut c_void) {
+        unsafe { ptr::drop_in_place(data.cast::<BodyStreamState>()) };
+    }
+
+    /// Body handler and read event handler: wakes the task to read the available data.
+    unsafe extern "C" fn body_stream_handler(r: *mut ngx_http_request_t) {
+        let mut cln = unsafe { (*(*r).pool).cleanup };
+
+        while let Some(c) = unsafe { cln.as_ref() } {
+            if c.handler == Some(cleanup_state as unsafe extern "C" fn(_)) {
+                let state = unsafe { &mut *c.data.cast::<BodyStreamState>() };
+                if let Some(waker) = state.waker.take() {
+                    waker.wake();
+                }
+                return;
+            }
+            cln = c.next;
+        }
+    }
+}
@@ -0,0 +1,211 @@
+use core::ptr;
+
+use crate::core::{Buffer, Status};
+use crate::ffi::{
+    NGX_OK, ngx_buf_t, ngx_chain_t, ngx_http_complex_value_t, ngx_http_send_response,
+    ngx_http_set_content_type, ngx_int_t, ngx_str_t,
+};
+use crate::http::{HTTPStatus, Request};
+
+/// Trait for the values that can be sent as a response to a request.
+///
+/// A content handler builds the response, e.g. a [`Response`], and passes it to
+/// [`Request::respond`], which sends the header and the body and returns the status the handler
+/// should return to nginx.
+///
+/// There are predefined implementations for [`Response`], [`HTTPStatus`] and [`Status`], returned
+/// to nginx as is to generate a special response, and [`Result`] with the error type convertible
+/// to [`HTTPStatus`].
+pub trait Responder {
+    /// Sends the response.
+    fn respond(self, r: &mut Request) -> Status;
+}
+
+impl Responder for Status {
+    #[inline]
+    fn respond(self, _r: &mut Request) -> Status {
+        self
+    }
+}
+
+impl Responder for HTTPStatus {
+    #[inline]
+    fn respond(self, _r: &mut Request) -> Status {
+        self.into()
+    }
+}
+
+impl<T, E> Responder for Result<T, E>
+where
+    T: Responder,
+    E: Into<HTTPStatus>,
+{
+    #[inline]
+    fn respond(self, r: &mut Request) -> Status {
+        match self {
+            Ok(val) => val.respond(r),
+            Err(err) => err.into().into(),
+        }
+    }
+}
+
+/// A response with a status, a content type and a body.
+///
+/// The body is copied to the request pool when the response is sent.
+///
+/// ```no_run
+/// # use ngx::core::Status;
+/// # use ngx::http::{HTTPStatus, Request, Response};
+/// fn handler(r: &mut Request) -> Status {
+///     r.respond(Response::new(HTTPStatus::OK).content_type(b"text/plain").body(b"hello\n"))
+/// }
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct Response<'a> {
+    status: HTTPStatus,
+    content_type: Option<&'a [u8]>,
+    body: &'a [u8],
+}
+
+impl<'a> Response<'a> {
+    /// Creates a response with the `status` and an empty body.
+    pub fn new(status: HTTPStatus) -> Self {
+        Self { status, content_type: None, body: b"" }
+    }
+
+    /// Sets the content type. The default type of the location is used otherwise.
+    pub fn content_type(mut self, content_type: &'a [u8]) -> Self {
+        self.content_type = Some(content_type);
+        self
+    }
+
+    /// Sets the body.
+    pub fn body(mut self, body: &'a [u8]) -> Self {
+        self.body = body;
+        self
+    }
+}
+
+impl Responder for Response<'_> {
+    fn respond(self, r: &mut Request) -> Status {
+        let rc = r.discard_request_body();
+        if !rc.is_ok() {
+            return rc;
+        }
+
+        r.set_status(self.status);
+        r.set_content_length_n(self.body.len());
+
+        let rc = match self.content_type {
+            Some(content_type) => r.set_content_type(content_type),
+            // SAFETY: the request is valid.
+            None => match unsafe { ngx_http_set_content_type(r.as_mut()) } {
+                rc if rc == NGX_OK as ngx_int_t => Status::NGX_OK,
+                _ => Status::NGX_ERROR,
+            },
+        };
+        if !rc.is_ok() {
+            return Status::NGX_ERROR;
+        }
+
+        let rc = r.send_header();
+        if rc == Status::NGX_ERROR || rc > Status::NGX_OK || r.header_only() {
+            return rc;
+        }
+
+        let cl = match body_chain(r, self.body) {
+            Some(cl) => cl,
+            None => return Status::NGX_ERROR,
+        };
+
+        // SAFETY: the chain link is allocated from the request pool.
+        r.output_filter(unsafe { &mut *cl })
+    }
+}
+
+impl Request {
+    /// Sends the `response` and returns the status to be returned from the content handler.
+    pub fn respond(&mut self, response: impl Responder) -> Status {
+        response.respond(self)
+    }
+
+    /// Sends a response with the `status`, the `content_type` and the `body`, as
+    /// `ngx_http_send_response` does for the `return` directive.
+    ///
+    /// The content type and the body are copied to the request pool. For the redirect statuses,
+    /// `301`-`303`, `307` and `308`, the body is used as the `Location` header value instead. The
+    /// result should be returned from the content handler.
+    ///
+    /// ```no_run
+    /// # use ngx::core::Status;
+    /// # use ngx::http::{HTTPStatus, Request};
+    /// fn handler(r: &mut Request) -> Status {
+    ///     r.send_response(HTTPStatus::OK, b"application/json", br#"{"ok":true}"#)
+    /// }
+    /// ```
+    pub fn send_response(
+        &mut self,
+        status: HTTPStatus,
+        content_type: &[u8],
+        body: &[u8],
+    ) -> Status {
+        let pool = self.as_ref().pool;
+        // SAFETY: the strings are copied to the request pool.
+        let content_type = unsafe { ngx_str_t::from_bytes(pool, content_type) };
+        let body = unsafe { ngx_str_t::from_bytes(pool, body) };
+        let (Some(mut content_type), Some(body)) = (content_type, body) else {
+            return Status::NGX_ERROR;
+        };
+
+        // A complex value without the `lengths` is a constant string.
+        // SAFETY: an all-zero value is a valid empty complex value.
+        let mut cv: ngx_http_complex_value_t = unsafe { core::mem::zeroed() };
+        cv.value = body;
+
+        // SAFETY: the complex value is evaluated immediately, and the strings referenced by the
+        // response are allocated from the request pool.
+        Status(unsafe {
+            ngx_http_send_response(self.as_mut(), status.0, &raw mut content_type, &raw mut cv)
+        })
+    }
+}
+
+/// Copies the body into a single buffer marked as the last one.
+fn body_chain(r: &mut Request, body: &[u8]) -> Option<*mut ngx_chain_t> {
+    let main = r.is_main();
+    let pool = r.pool();
+
+    let b = if body.is_empty() {
+        let b = pool.calloc_type::<ngx_buf_t>();
+        if b.is_null() {
+            return None;
+        }
+        b
+    } else {
+        let mut buf = pool.create_buffer(body.len())?;
+        let b = buf.as_ngx_buf_mut();
+        // SAFETY: the buffer was allocated with the required size.
+        unsafe {
+            ptr::copy_nonoverlapping(body.as_ptr(), (*b).pos, body.len());
+            (*b).last = (*b).pos.add(body.len());
+        }
+        b
+    };
+
+    let cl = pool.alloc_type::<ngx_chain_t>();
+    if cl.is_null() {
+        return None;
+    }
+
+    // SAFETY: the buffer and the chain link are allocated above.
+    unsafe {
+        if main {
+            (*b).set_last_buf(1);
+        } else {
+            (*b).set_last_in_chain(1);
+        }
+        cl.write(ngx_chain_t { buf: b, next: ptr::null_mut() });
+    }
+
+    Some(cl)
+}
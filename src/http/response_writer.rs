@@ -0,0 +1,172 @@
+use core::fmt;
+use core::ptr;
+
+use crate::core::{Buffer, Status};
+use crate::ffi::{ngx_buf_t, ngx_chain_t, ngx_int_t};
+use crate::http::Request;
+
+/// Default size of the buffers allocated by [`ResponseWriter`].
+pub const RESPONSE_WRITER_BUFFER_SIZE: usize = 4096;
+
+/// Writer sending the response body through the output filters.
+///
+/// The data is copied into buffers allocated from the request pool, and each buffer is passed to
+/// the output filters once full. [`ResponseWriter::flush`] sends the buffered data immediately,
+/// and [`ResponseWriter::finish`] sends the rest of the data with the `last_buf` flag. The
+/// response header must be sent before writing the body.
+///
+/// The memory of the buffers is not reused and is freed with the request.
+///
+/// ```no_run
+/// # use core::fmt::Write;
+/// # use ngx::core::Status;
+/// # use ngx::http::{HTTPStatus, Request};
+/// fn handler(r: &mut Request) -> Result<Status, Status> {
+///     r.set_status(HTTPStatus::OK);
+///     let rc = r.send_header();
+///     if rc == Status::NGX_ERROR || rc > Status::NGX_OK || r.header_only() {
+///         return Ok(rc);
+///     }
+///
+///     let mut w = r.response_writer();
+///     for i in 0..10 {
+///         writeln!(w, "line {i}").map_err(|_| Status::NGX_ERROR)?;
+///     }
+///     Ok(w.finish())
+/// }
+/// ```
+pub struct ResponseWriter<'r> {
+    r: &'r mut Request,
+    buf: *mut ngx_buf_t,
+    capacity: usize,
+    rc: ngx_int_t,
+}
+
+impl Request {
+    /// Returns a writer for the response body, see [`ResponseWriter`].
+    pub fn response_writer(&mut self) -> ResponseWriter<'_> {
+        ResponseWriter::with_capacity(self, RESPONSE_WRITER_BUFFER_SIZE)
+    }
+}
+
+impl<'r> ResponseWriter<'r> {
+    /// Creates a writer allocating buffers of `capacity` bytes.
+    pub fn with_capacity(r: &'r mut Request, capacity: usize) -> Self {
+        Self { r, buf: ptr::null_mut(), capacity: capacity.max(1), rc: 0 }
+    }
+
+    /// Returns the request.
+    pub fn request(&mut self) -> &mut Request {
+        self.r
+    }
+
+    /// Copies `data` into the buffers, sending the buffers that are full.
+    pub fn write_bytes(&mut self, mut data: &[u8]) -> Result<(), Status> {
+        while !data.is_empty() {
+            if self.buf.is_null() {
+                let mut buf =
+                    self.r.pool().create_buffer(self.capacity).ok_or(Status::NGX_ERROR)?;
+                self.buf = buf.as_ngx_buf_mut();
+            }
+
+            // SAFETY: the buffer is allocated above with `capacity` bytes.
+            let b = unsafe { &mut *self.buf };
+            let free = unsafe { b.end.offset_from(b.last) } as usize;
+            let n = free.min(data.len());
+            unsafe {
+                ptr::copy_nonoverlapping(data.as_ptr(), b.last, n);
+                b.last = b.last.add(n);
+            }
+            data = &data[n..];
+
+            if n == free {
+                self.send(false, false)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Sends the buffered data with the `flush` flag.
+    pub fn flush(&mut self) -> Result<(), Status> {
+        self.send(true, false)
+    }
+
+    /// Sends the rest of the data and marks the end of the response: the last buffer for the main
+    /// request, or the last buffer in the chain for a subrequest.
+    ///
+    /// Returns the result of the output call, suitable for returning from a content handler or for
+    /// `ngx_http_finalize_request`.
+    pub fn finish(mut self) -> Status {
+        match self.send(false, true) {
+            Ok(()) => Status(self.rc),
+            Err(rc) => rc,
+        }
+    }
+
+    /// Returns the result of the last output call, `NGX_OK` or `NGX_AGAIN`.
+    pub fn last_status(&self) -> Status {
+        Status(self.rc)
+    }
+
+    fn send(&mut self, flush: bool, last: bool) -> Result<(), Status> {
+        let pool = self.r.pool();
+
+        if self.buf.is_null() {
+            if !flush && !last {
+                return Ok(());
+            }
+            self.buf = pool.calloc_type::<ngx_buf_t>();
+            if self.buf.is_null() {
+                return Err(Status::NGX_ERROR);
+            }
+        }
+
+        let cl = pool.alloc_type::<ngx_chain_t>();
+        if cl.is_null() {
+            return Err(Status::NGX_ERROR);
+        }
+
+        let b = core::mem::replace(&mut self.buf, ptr::null_mut());
+        // SAFETY: the buffer and the chain link are allocated from the request pool.
+        unsafe {
+            if flush {
+                (*b).set_flush(1);
+            }
+            if last {
+                if self.r.is_main() {
+                    (*b).set_last_buf(1);
+                } else {
+                    (*b).set_last_in_chain(1);
+                }
+            }
+            cl.write(ngx_chain_t { buf: b, next: ptr::null_mut() });
+        }
+
+        // SAFETY: the chain link is allocated above.
+        let rc = self.r.output_filter(unsafe { &mut *cl });
+        if rc == Status::NGX_ERROR {
+            return Err(rc);
+        }
+        self.rc = rc.0;
+        Ok(())
+    }
+}
+
+impl fmt::Write for ResponseWriter<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.write_bytes(s.as_bytes()).map_err(|_| fmt::Error)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::io::Write for ResponseWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.write_bytes(buf).map_err(|_| std::io::Error::other("output filter error"))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        ResponseWriter::flush(self).map_err(|_| std::io::Error::other("output filter error"))
+    }
+}
@@ -0,0 +1,209 @@
+use core::ffi::CStr;
+use core::ptr;
+
+use crate::allocator::AllocError;
+use crate::collections::Vec;
+use crate::core::{JsonError, JsonWriter, NgxStr, Pool, SlabPool, Status};
+use crate::ffi::{
+    NGX_HTTP_VAR_CHANGEABLE, NGX_HTTP_VAR_NOCACHEABLE, NGX_LOG_EMERG, ngx_conf_t, ngx_cycle,
+    ngx_http_add_variable, ngx_http_request_t, ngx_int_t, ngx_module_t, ngx_pagesize,
+    ngx_shm_zone_t, ngx_str_t, ngx_uint_t, ngx_variable_value_t,
+};
+use crate::http::{HttpModule, ModuleBuildInfo, Request};
+use crate::ngx_conf_log_error;
+
+/// Prefix of the runtime status variable names.
+pub const RUNTIME_STATUS_VARIABLE: &str = "ngx_rust_status_";
+
+#[derive(Clone, Copy)]
+struct StatusModule {
+    module: *const ngx_module_t,
+    info: &'static ModuleBuildInfo,
+}
+
+/// Adds the `$ngx_rust_status_<module>` variable reporting the status of the module.
+///
+/// The variable evaluates to a JSON object with the version of the `ngx` crate, the module, the
+/// statistics of the async runtime (with the `async` feature), and the usage of the shared zones
+/// owned by the module. E.g. `$ngx_rust_status_ngx_http_foo_module`:
+///
+/// ```json
+/// {"version":"0.5.0","module":{"name":"ngx_http_foo_module","crate":"foo","version":"1.0.0",
+/// "build":""},"async":{"spawned":10,"active":1,"queued":0},
+/// "zones":[{"name":"foo","size":1048576,"free":1040384}]}
+/// ```
+///
+/// The zones are attributed to the module by the `tag` passed to `ngx_shared_memory_add`, which
+/// must be the address of the module. The free space is not reported for the zones without a slab
+/// allocator.
+///
+/// Each module gets its own variable: separately built dynamic modules may link different
+/// versions of the `ngx` crate and cannot share the variable state. It must be called from the
+/// module's `preconfiguration()` function.
+pub fn add_runtime_status_variable<M>(
+    cf: &mut ngx_conf_t,
+    info: &'static ModuleBuildInfo,
+) -> Result<(), AllocError>
+where
+    M: HttpModule,
+{
+    let module = M::module();
+    // SAFETY: the module name is either NULL or a static nul-terminated string.
+    let Some(module_name) = (unsafe { module.name.as_ref() }) else {
+        ngx_conf_log_error!(NGX_LOG_EMERG, cf, "module name is not set");
+        return Err(AllocError);
+    };
+    let module_name = unsafe { CStr::from_ptr(module_name) }.to_bytes();
+
+    let pool = unsafe { Pool::from_ngx_pool(cf.pool) };
+    let mut buf = Vec::new_in(pool.clone());
+    buf.try_reserve_exact(RUNTIME_STATUS_VARIABLE.len() + module_name.len())
+        .map_err(|_| AllocError)?;
+    buf.extend_from_slice(RUNTIME_STATUS_VARIABLE.as_bytes());
+    buf.extend_from_slice(module_name);
+    let buf = buf.leak();
+
+    let mut name = ngx_str_t { data: buf.as_mut_ptr(), len: buf.len() };
+    let flags = (NGX_HTTP_VAR_CHANGEABLE | NGX_HTTP_VAR_NOCACHEABLE) as ngx_uint_t;
+
+    let var = unsafe { ngx_http_add_variable(cf, &raw mut name, flags).as_mut() };
+    let Some(var) = var else {
+        ngx_conf_log_error!(NGX_LOG_EMERG, cf, "failed to add variable \"{name}\"");
+        return Err(AllocError);
+    };
+
+    let status = pool.alloc_type::<StatusModule>();
+    if status.is_null() {
+        return Err(AllocError);
+    }
+    unsafe { status.write(StatusModule { module, info }) };
+
+    var.get_handler = Some(runtime_status_variable);
+    var.data = status as usize;
+
+    Ok(())
+}
+
+fn write_status(json: &mut JsonWriter<Vec<u8, Pool>>, m: &StatusModule) -> Result<(), JsonError> {
+    json.begin_object()?;
+    json.key("version")?;
+    json.string(env!("CARGO_PKG_VERSION"))?;
+
+    json.key("module")?;
+    json.begin_object()?;
+    json.key("name")?;
+    // SAFETY: the module name is either NULL or a static nul-terminated string.
+    match unsafe { (*m.module).name.as_ref() } {
+        Some(name) => json.bytes(unsafe { CStr::from_ptr(name) }.to_bytes())?,
+        None => json.null()?,
+    }
+    json.key("crate")?;
+    json.string(m.info.name)?;
+    json.key("version")?;
+    json.string(m.info.version)?;
+    json.key("build")?;
+    json.string(m.info.build)?;
+    json.end_object()?;
+
+    #[cfg(feature = "async")]
+    {
+        let stats = crate::async_::runtime_stats();
+        json.key("async")?;
+        json.begin_object()?;
+        json.key("spawned")?;
+        json.uint(stats.spawned as _)?;
+        json.key("active")?;
+        json.uint(stats.active as _)?;
+        json.key("queued")?;
+        json.uint(stats.queued as _)?;
+        json.end_object()?;
+    }
+
+    json.key("zones")?;
+    json.begin_array()?;
+    for zone in shared_zones() {
+        if !ptr::addr_eq(m.module, zone.tag) {
+            continue;
+        }
+
+        json.begin_object()?;
+        json.key("name")?;
+        json.ngx_str(unsafe { NgxStr::from_ngx_str(zone.shm.name) })?;
+        json.key("size")?;
+        json.uint(zone.shm.size as _)?;
+        json.key("free")?;
+        match unsafe { zone_free(zone) } {
+            Some(free) => json.uint(free as _)?,
+            None => json.null()?,
+        }
+        json.end_object()?;
+    }
+    json.end_array()?;
+
+    json.end_object()
+}
+
+/// Returns the shared zones of the current cycle.
+fn shared_zones() -> impl Iterator<Item = &'static ngx_shm_zone_t> {
+    // SAFETY: the cycle and its shared memory list are valid while the worker is running.
+    let mut part = unsafe { &raw const (*ngx_cycle).shared_memory.part };
+    let mut i = 0;
+
+    core::iter::from_fn(move || {
+        loop {
+            let p = unsafe { &*part };
+            if i < p.nelts {
+                i += 1;
+                return Some(unsafe { &*p.elts.cast::<ngx_shm_zone_t>().add(i - 1) });
+            }
+            if p.next.is_null() {
+                return None;
+            }
+            part = p.next;
+            i = 0;
+        }
+    })
+}
+
+/// Returns the free space in a zone with the slab allocator.
+///
+/// # Safety
+///
+/// The zone must be initialized.
+unsafe fn zone_free(zone: &ngx_shm_zone_t) -> Option<usize> {
+    if zone.noslab() != 0 || zone.shm.addr.is_null() {
+        return None;
+    }
+
+    let pool = unsafe { SlabPool::from_shm_zone(zone) }?;
+    let _lock = pool.lock();
+    Some(pool.as_ref().pfree * unsafe { ngx_pagesize })
+}
+
+unsafe extern "C" fn runtime_status_variable(
+    r: *mut ngx_http_request_t,
+    v: *mut ngx_variable_value_t,
+    data: usize,
+) -> ngx_int_t {
+    let r = unsafe { Request::from_ngx_http_request(r) };
+    // SAFETY: `data` is set by add_runtime_status_variable to a StatusModule.
+    let module = unsafe { &*(data as *const StatusModule) };
+
+    let mut json = JsonWriter::new(Vec::new_in(r.pool()));
+    if write_status(&mut json, module).is_err() {
+        return Status::NGX_ERROR.into();
+    }
+    let Ok(value) = json.into_inner() else {
+        return Status::NGX_ERROR.into();
+    };
+    let value = value.leak();
+
+    let v = unsafe { &mut *v };
+    v.data = value.as_mut_ptr();
+    v.set_len(value.len() as _);
+    v.set_valid(1);
+    v.set_no_cacheable(1);
+    v.set_not_found(0);
+
+    Status::NGX_OK.into()
+}
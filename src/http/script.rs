@@ -0,0 +1,102 @@
+use core::mem;
+use core::ptr;
+
+use crate::core::{NgxStr, Status};
+use crate::ffi::{
+    NGX_OK, ngx_array_t, ngx_conf_t, ngx_http_script_compile, ngx_http_script_compile_t,
+    ngx_http_script_run, ngx_http_script_variables_count, ngx_int_t, ngx_str_t,
+};
+use crate::http::Request;
+
+/// Compiled nginx script expression.
+///
+/// Scripts are evaluated by the same engine as the `set` and `rewrite` directives, and, unlike
+/// complex values, can refer to the regex captures (`$1`, `$2`, ...) of the last successful
+/// match in the request.
+///
+/// See <https://nginx.org/en/docs/dev/development_guide.html#http_complex_values>
+#[derive(Clone, Copy, Debug)]
+pub struct Script {
+    source: ngx_str_t,
+    lengths: *mut ngx_array_t,
+    values: *mut ngx_array_t,
+}
+
+impl Default for Script {
+    fn default() -> Self {
+        Self { source: ngx_str_t::default(), lengths: ptr::null_mut(), values: ptr::null_mut() }
+    }
+}
+
+impl Script {
+    /// Compiles the expression in `source`.
+    ///
+    /// This function should be called from a directive handler. The compiled code is allocated
+    /// from the configuration pool, and `source` must outlive the configuration.
+    pub fn compile(cf: &mut ngx_conf_t, source: &ngx_str_t) -> Result<Self, Status> {
+        let mut script = Self { source: *source, ..Default::default() };
+
+        let mut value = *source;
+        let variables = unsafe { ngx_http_script_variables_count(&raw mut value) };
+        if variables == 0 {
+            return Ok(script);
+        }
+
+        // SAFETY: an all-zero value is a valid initial state for the compiler, as in nginx.
+        let mut sc: ngx_http_script_compile_t = unsafe { mem::zeroed() };
+        sc.cf = cf;
+        sc.source = &raw mut value;
+        sc.lengths = &raw mut script.lengths;
+        sc.values = &raw mut script.values;
+        sc.variables = variables;
+        sc.set_complete_lengths(1);
+        sc.set_complete_values(1);
+
+        if unsafe { ngx_http_script_compile(&raw mut sc) } != NGX_OK as ngx_int_t {
+            return Err(Status::NGX_ERROR);
+        }
+
+        Ok(script)
+    }
+
+    /// Returns the source of the expression.
+    pub fn source(&self) -> &NgxStr {
+        // SAFETY: the source is required to outlive the configuration.
+        unsafe { NgxStr::from_ngx_str(self.source) }
+    }
+
+    /// Returns `true` if the expression does not contain any variables.
+    pub fn is_static(&self) -> bool {
+        self.lengths.is_null()
+    }
+
+    /// Evaluates the expression in the context of the request.
+    ///
+    /// The result is allocated from the request pool. Returns `None` if the evaluation failed.
+    pub fn run<'r>(&self, r: &'r mut Request) -> Option<&'r NgxStr> {
+        if self.is_static() {
+            // SAFETY: the source is required to outlive the configuration.
+            return Some(unsafe { NgxStr::from_ngx_str(self.source) });
+        }
+
+        let mut value = ngx_str_t::default();
+        // SAFETY: `lengths` and `values` contain the code compiled in `compile`, terminated with
+        // NULL, as required by the script engine.
+        let p = unsafe {
+            ngx_http_script_run(
+                r.into(),
+                &raw mut value,
+                (*self.lengths).elts,
+                0,
+                (*self.values).elts,
+            )
+        };
+
+        if p.is_null() {
+            return None;
+        }
+
+        // SAFETY: the value is allocated from the request pool.
+        Some(unsafe { NgxStr::from_ngx_str(value) })
+    }
+}
@@ -0,0 +1,159 @@
+use crate::core::NgxStr;
+use crate::ffi::{
+    ngx_http_addr_conf_t, ngx_http_connection_t, ngx_http_core_srv_conf_t, ngx_http_server_name_t,
+    ngx_listening_t,
+};
+use crate::http::{HttpModuleServerConf, NgxHttpCoreModule, Request};
+
+/// Virtual server selected for a request.
+#[derive(Clone, Copy)]
+pub struct VirtualServer<'a> {
+    r: &'a Request,
+    cscf: &'a ngx_http_core_srv_conf_t,
+}
+
+impl<'a> VirtualServer<'a> {
+    /// Returns the core module configuration of the server.
+    pub fn srv_conf(&self) -> &'a ngx_http_core_srv_conf_t {
+        self.cscf
+    }
+
+    /// Returns the primary name of the server, the first name in the `server_name` directive.
+    pub fn server_name(&self) -> &'a NgxStr {
+        // SAFETY: the server name is allocated from the configuration pool.
+        unsafe { NgxStr::from_ngx_str(self.cscf.server_name) }
+    }
+
+    /// Returns all the names of the server, including the wildcard and regex names.
+    pub fn server_names(&self) -> impl Iterator<Item = &'a NgxStr> + 'a {
+        // SAFETY: `server_names` is initialized in the server configuration constructor.
+        let names: &[ngx_http_server_name_t] = unsafe { self.cscf.server_names.as_slice() };
+        // SAFETY: the server names are allocated from the configuration pool.
+        names.iter().map(|x| unsafe { NgxStr::from_ngx_str(x.name) })
+    }
+
+    /// Returns the host name used to select the server, taken from the request line, the `Host`
+    /// header or the TLS SNI extension.
+    ///
+    /// Returns `None` if the server was selected by the address only.
+    pub fn host(&self) -> Option<&'a NgxStr> {
+        let host = self.r.as_ref().headers_in.server;
+        if host.len == 0 {
+            return None;
+        }
+        // SAFETY: the host is allocated from the request pool or the client header buffer.
+        Some(unsafe { NgxStr::from_ngx_str(host) })
+    }
+
+    /// Returns `true` if the server is the default server for the listening address.
+    pub fn is_default_server(&self) -> bool {
+        self.r
+            .listen()
+            .is_some_and(|listen| core::ptr::eq(listen.addr_conf.default_server, self.cscf))
+    }
+}
+
+/// Listening socket that accepted the request connection.
+#[derive(Clone, Copy)]
+pub struct ListenInfo<'a> {
+    ls: &'a ngx_listening_t,
+    addr_conf: &'a ngx_http_addr_conf_t,
+}
+
+impl<'a> ListenInfo<'a> {
+    /// Returns the listening socket.
+    pub fn listening(&self) -> &'a ngx_listening_t {
+        self.ls
+    }
+
+    /// Returns the address configuration of the `listen` directive.
+    pub fn addr_conf(&self) -> &'a ngx_http_addr_conf_t {
+        self.addr_conf
+    }
+
+    /// Returns the text representation of the listening address.
+    pub fn address(&self) -> &'a NgxStr {
+        // SAFETY: the address text is allocated from the cycle pool.
+        unsafe { NgxStr::from_ngx_str(self.ls.addr_text) }
+    }
+
+    /// Returns `true` if the `ssl` parameter is set.
+    pub fn ssl(&self) -> bool {
+        #[cfg(ngx_feature = "http_ssl")]
+        {
+            self.addr_conf.ssl() != 0
+        }
+        #[cfg(not(ngx_feature = "http_ssl"))]
+        {
+            false
+        }
+    }
+
+    /// Returns `true` if HTTP/2 is enabled on the address.
+    pub fn http2(&self) -> bool {
+        #[cfg(ngx_feature = "http_v2")]
+        {
+            self.addr_conf.http2() != 0
+        }
+        #[cfg(not(ngx_feature = "http_v2"))]
+        {
+            false
+        }
+    }
+
+    /// Returns `true` if the `quic` parameter is set.
+    pub fn quic(&self) -> bool {
+        #[cfg(ngx_feature = "http_v3")]
+        {
+            self.addr_conf.quic() != 0
+        }
+        #[cfg(not(ngx_feature = "http_v3"))]
+        {
+            false
+        }
+    }
+
+    /// Returns `true` if the `proxy_protocol` parameter is set.
+    pub fn proxy_protocol(&self) -> bool {
+        self.addr_conf.proxy_protocol() != 0
+    }
+
+    /// Returns the `backlog` parameter.
+    pub fn backlog(&self) -> i32 {
+        self.ls.backlog
+    }
+
+    /// Returns the `rcvbuf` parameter, or -1 if not set.
+    pub fn rcvbuf(&self) -> i32 {
+        self.ls.rcvbuf
+    }
+
+    /// Returns the `sndbuf` parameter, or -1 if not set.
+    pub fn sndbuf(&self) -> i32 {
+        self.ls.sndbuf
+    }
+
+    /// Returns the `so_keepalive` parameter: 0 if not set, 1 for `on` and 2 for `off`.
+    pub fn so_keepalive(&self) -> u32 {
+        self.ls.so_keepalive()
+    }
+}
+
+impl Request {
+    /// Returns the virtual server selected for the request.
+    pub fn virtual_server(&self) -> Option<VirtualServer<'_>> {
+        let cscf = NgxHttpCoreModule::server_conf(self)?;
+        Some(VirtualServer { r: self, cscf })
+    }
+
+    /// Returns the listening socket that accepted the request connection.
+    pub fn listen(&self) -> Option<ListenInfo<'_>> {
+        // SAFETY: `http_connection` is either NULL or allocated from the connection pool.
+        let hc: &ngx_http_connection_t = unsafe { self.as_ref().http_connection.as_ref()? };
+        // SAFETY: the connection is valid for the lifetime of the request, and the listening
+        // sockets and the address configuration are valid for the lifetime of the cycle.
+        let ls = unsafe { (*self.as_ref().connection).listening.as_ref()? };
+        let addr_conf = unsafe { hc.addr_conf.as_ref()? };
+        Some(ListenInfo { ls, addr_conf })
+    }
+}
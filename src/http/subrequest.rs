@@ -0,0 +1,157 @@
+use core::ffi::c_void;
+use core::ptr;
+
+use crate::core::Status;
+use crate::ffi::{
+    NGX_HTTP_SUBREQUEST_BACKGROUND, NGX_HTTP_SUBREQUEST_CLONE, NGX_HTTP_SUBREQUEST_IN_MEMORY,
+    NGX_HTTP_SUBREQUEST_WAITED, NGX_OK, ngx_http_post_subrequest_t, ngx_http_request_t,
+    ngx_http_subrequest, ngx_int_t, ngx_uint_t,
+};
+use crate::http::Request;
+
+/// Flags of a subrequest created with [`Request::subrequest_with`].
+///
+/// ```
+/// # use ngx::http::SubrequestFlags;
+/// let flags = SubrequestFlags::new().in_memory(true).waited(true);
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SubrequestFlags(ngx_uint_t);
+
+impl SubrequestFlags {
+    /// Creates an empty set of flags.
+    pub const fn new() -> Self {
+        Self(0)
+    }
+
+    /// `NGX_HTTP_SUBREQUEST_IN_MEMORY`: the response is stored in `r->out` instead of being sent
+    /// to the client. Only supported by some modules, e.g. the upstream modules.
+    pub const fn in_memory(self, on: bool) -> Self {
+        self.set(NGX_HTTP_SUBREQUEST_IN_MEMORY, on)
+    }
+
+    /// `NGX_HTTP_SUBREQUEST_WAITED`: the subrequest is waited for by the parent request, and its
+    /// `done` flag is set even if it is finalized before becoming active.
+    pub const fn waited(self, on: bool) -> Self {
+        self.set(NGX_HTTP_SUBREQUEST_WAITED, on)
+    }
+
+    /// `NGX_HTTP_SUBREQUEST_CLONE`: the subrequest is started in the same location and phase as
+    /// the parent request.
+    pub const fn clone_request(self, on: bool) -> Self {
+        self.set(NGX_HTTP_SUBREQUEST_CLONE, on)
+    }
+
+    /// `NGX_HTTP_SUBREQUEST_BACKGROUND`: the subrequest does not produce output and does not
+    /// block the parent request, e.g. a cache update.
+    pub const fn background(self, on: bool) -> Self {
+        self.set(NGX_HTTP_SUBREQUEST_BACKGROUND, on)
+    }
+
+    /// Returns the raw flags for `ngx_http_subrequest`.
+    pub const fn bits(&self) -> ngx_uint_t {
+        self.0
+    }
+
+    const fn set(self, flag: u32, on: bool) -> Self {
+        if on { Self(self.0 | flag as ngx_uint_t) } else { Self(self.0 & !(flag as ngx_uint_t)) }
+    }
+}
+
+impl Request {
+    /// Creates a subrequest to `uri` and calls `handler` when it is finalized.
+    ///
+    /// The handler receives the subrequest and the status it is finalized with, and is called at
+    /// most once. The closure is allocated from the request pool and is dropped with the pool if
+    /// the subrequest is never finalized.
+    ///
+    /// Returns the subrequest on success.
+    ///
+    /// ```no_run
+    /// # use ngx::core::Status;
+    /// # use ngx::http::{Request, SubrequestFlags};
+    /// # fn handler(r: &mut Request) -> Status {
+    /// let flags = SubrequestFlags::new().in_memory(true).waited(true);
+    /// match r.subrequest_with("/auth", None, flags, |sr, rc| {
+    ///     ngx::ngx_log_debug_http!(sr, "auth subrequest done: {:?} {:?}", rc, sr.status());
+    /// }) {
+    ///     Ok(_) => Status::NGX_AGAIN,
+    ///     Err(rc) => rc,
+    /// }
+    /// # }
+    /// ```
+    pub fn subrequest_with<F>(
+        &mut self,
+        uri: &str,
+        args: Option<&str>,
+        flags: SubrequestFlags,
+        handler: F,
+    ) -> Result<&mut Request, Status>
+    where
+        F: FnOnce(&mut Request, Status) + 'static,
+    {
+        let pool = self.pool();
+
+        let mut uri = pool.copy_str(uri).ok_or(Status::NGX_ERROR)?;
+        let mut args = match args {
+            Some(args) => Some(pool.copy_str(args).ok_or(Status::NGX_ERROR)?),
+            None => None,
+        };
+        let args = args.as_mut().map_or(ptr::null_mut(), |x| ptr::from_mut(x));
+
+        let data = pool.allocate(Some(handler));
+        let ps = pool.calloc_type::<ngx_http_post_subrequest_t>();
+        if data.is_null() || ps.is_null() {
+            return Err(Status::NGX_ERROR);
+        }
+        // SAFETY: `ps` is a fresh allocation from the request pool.
+        unsafe {
+            (*ps).handler = Some(post_subrequest_handler::<F>);
+            (*ps).data = data.cast();
+        }
+
+        let r: *mut ngx_http_request_t = self.into();
+        let mut sr: *mut ngx_http_request_t = ptr::null_mut();
+        // SAFETY: the arguments are allocated from the request pool.
+        let rc = unsafe { ngx_http_subrequest(r, &mut uri, args, &mut sr, ps, flags.bits()) };
+        if rc != NGX_OK as ngx_int_t {
+            return Err(Status(rc));
+        }
+
+        // SAFETY: ngx_http_subrequest returns a valid subrequest on success.
+        Ok(unsafe { Request::from_ngx_http_request(sr) })
+    }
+}
+
+unsafe extern "C" fn post_subrequest_handler<F>(
+    r: *mut ngx_http_request_t,
+    data: *mut c_void,
+    rc: ngx_int_t,
+) -> ngx_int_t
+where
+    F: FnOnce(&mut Request, Status),
+{
+    // SAFETY: `data` is the closure slot allocated from the request pool in `subrequest_with`.
+    let slot = unsafe { &mut *data.cast::<Option<F>>() };
+    if let Some(handler) = slot.take() {
+        // SAFETY: nginx calls the handler with the subrequest being finalized.
+        handler(unsafe { Request::from_ngx_http_request(r) }, Status(rc));
+    }
+    rc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_subrequest_flags() {
+        let flags = SubrequestFlags::new().in_memory(true).waited(true);
+        assert_eq!(
+            flags.bits(),
+            (NGX_HTTP_SUBREQUEST_IN_MEMORY | NGX_HTTP_SUBREQUEST_WAITED) as ngx_uint_t
+        );
+        assert_eq!(flags.in_memory(false).bits(), NGX_HTTP_SUBREQUEST_WAITED as ngx_uint_t);
+        assert_eq!(SubrequestFlags::default(), SubrequestFlags::new());
+    }
+}
@@ -0,0 +1,437 @@
+//! Streaming search and replace in the response body.
+//!
+//! The [`Substitution`] state machine finds the matches across the buffer boundaries by holding
+//! back the tail of the data that may start a match, until the next buffer arrives. The
+//! [`Matcher`] trait abstracts the search: [`Literal`] finds a fixed string, [`RegexMatcher`]
+//! finds a [`Regex`](crate::regex::Regex), and [`Windowed`] adapts any search function, assuming a
+//! maximum match length.
+//!
+//! [`SubstitutionFilter`] connects the state machine to the body filter chain:
+//!
+//! ```no_run
+//! # use ngx::core::Status;
+//! # use ngx::ffi::ngx_chain_t;
+//! # use ngx::http::Request;
+//! # use ngx::http::substitution::{self, Literal, SubstitutionFilter};
+//! // in the header filter
+//! fn header_filter(r: &mut Request) -> Status {
+//!     substitution::prepare(r);
+//!     // save a `SubstitutionFilter::new(r, Literal::new(b"foo").unwrap())` in the module ctx
+//!     Status::NGX_OK
+//! }
+//!
+//! // in the body filter
+//! fn body_filter(
+//!     r: &Request,
+//!     sf: &mut SubstitutionFilter<Literal<'static>>,
+//!     body: *mut ngx_chain_t,
+//! ) -> Result<*mut ngx_chain_t, Status> {
+//!     unsafe { sf.process(r, body, |_, out| out.extend(b"bar")) }
+//! }
+//! ```
+use core::ops::Range;
+use core::ptr;
+
+use crate::allocator::{AllocError, Allocator};
+use crate::collections::Vec;
+use crate::core::{Buffer, Pool, Status};
+use crate::ffi::{ngx_buf_t, ngx_chain_t};
+use crate::http::Request;
+
+/// Result of a [`Matcher`] search.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Search {
+    /// A complete match.
+    Found(Range<usize>),
+    /// No complete match; the data starting at the offset may begin a match.
+    Partial(usize),
+    /// No match.
+    None,
+}
+
+/// Search strategy for a [`Substitution`].
+pub trait Matcher {
+    /// Searches for the first match in `data`; `last` is set at the end of the body.
+    ///
+    /// A match must not be empty.
+    fn search(&mut self, data: &[u8], last: bool) -> Search;
+}
+
+/// Fixed string matcher.
+#[derive(Clone, Copy, Debug)]
+pub struct Literal<'a> {
+    pattern: &'a [u8],
+    ignore_case: bool,
+}
+
+impl<'a> Literal<'a> {
+    /// Creates a case-sensitive matcher for `pattern`.
+    ///
+    /// Returns `None` if the pattern is empty.
+    pub fn new(pattern: &'a [u8]) -> Option<Self> {
+        (!pattern.is_empty()).then_some(Self { pattern, ignore_case: false })
+    }
+
+    /// Makes the matcher ASCII case-insensitive.
+    pub fn ignore_case(mut self) -> Self {
+        self.ignore_case = true;
+        self
+    }
+
+    fn eq(&self, a: &[u8], b: &[u8]) -> bool {
+        if self.ignore_case { a.eq_ignore_ascii_case(b) } else { a == b }
+    }
+}
+
+impl Matcher for Literal<'_> {
+    fn search(&mut self, data: &[u8], _last: bool) -> Search {
+        let n = self.pattern.len();
+
+        if let Some(pos) = data.windows(n).position(|x| self.eq(x, self.pattern)) {
+            return Search::Found(pos..pos + n);
+        }
+
+        let tail = data.len().saturating_sub(n - 1);
+        match (tail..data.len()).find(|&i| self.eq(&data[i..], &self.pattern[..data.len() - i])) {
+            Some(pos) => Search::Partial(pos),
+            None => Search::None,
+        }
+    }
+}
+
+/// Matcher adapter for the search functions that cannot report partial matches.
+///
+/// A match is assumed to be at most `max_len` bytes long; the last `max_len - 1` bytes of the
+/// data are held back until more data arrives or the end of the body.
+pub struct Windowed<F> {
+    find: F,
+    max_len: usize,
+}
+
+impl<F> Windowed<F>
+where
+    F: FnMut(&[u8]) -> Option<Range<usize>>,
+{
+    /// Creates a matcher with the search function and the maximum match length.
+    pub fn new(max_len: usize, find: F) -> Self {
+        Self { find, max_len: max_len.max(1) }
+    }
+}
+
+impl<F> Matcher for Windowed<F>
+where
+    F: FnMut(&[u8]) -> Option<Range<usize>>,
+{
+    fn search(&mut self, data: &[u8], last: bool) -> Search {
+        match (self.find)(data) {
+            // A match reaching the end of the data may be longer with more data.
+            Some(m) if last || m.end < data.len() || m.len() >= self.max_len => Search::Found(m),
+            Some(m) => Search::Partial(m.start),
+            None if last => Search::None,
+            None if data.len() >= self.max_len => Search::Partial(data.len() - self.max_len + 1),
+            None => Search::Partial(0),
+        }
+    }
+}
+
+/// Regular expression matcher.
+///
+/// The expression is matched with [`Windowed`], and a match is assumed to be at most `max_len`
+/// bytes long. An empty match is treated as no match.
+#[cfg(ngx_feature = "pcre2")]
+#[derive(Clone, Copy, Debug)]
+pub struct RegexMatcher {
+    regex: crate::regex::Regex,
+    max_len: usize,
+}
+
+#[cfg(ngx_feature = "pcre2")]
+impl RegexMatcher {
+    /// Creates a matcher for the compiled expression and the maximum match length.
+    pub fn new(regex: crate::regex::Regex, max_len: usize) -> Self {
+        Self { regex, max_len }
+    }
+}
+
+#[cfg(ngx_feature = "pcre2")]
+impl Matcher for RegexMatcher {
+    fn search(&mut self, data: &[u8], last: bool) -> Search {
+        let regex = self.regex;
+        Windowed::new(self.max_len, |data: &[u8]| regex.find(data).filter(|m| !m.is_empty()))
+            .search(data, last)
+    }
+}
+
+/// Output of a [`Substitution`].
+pub struct Output<'a, A: Allocator>(&'a mut Vec<u8, A>);
+
+impl<A: Allocator> Output<'_, A> {
+    /// Appends the data to the output.
+    pub fn extend(&mut self, data: &[u8]) -> Result<(), AllocError> {
+        self.0.try_reserve(data.len()).map_err(|_| AllocError)?;
+        self.0.extend_from_slice(data);
+        Ok(())
+    }
+}
+
+/// Streaming search and replace state.
+pub struct Substitution<M, A: Allocator = Pool> {
+    matcher: M,
+    pending: Vec<u8, A>,
+    once: bool,
+    done: bool,
+}
+
+impl<M: Matcher, A: Allocator> Substitution<M, A> {
+    /// Creates the state for the matcher.
+    pub fn new_in(matcher: M, alloc: A) -> Self {
+        Self { matcher, pending: Vec::new_in(alloc), once: false, done: false }
+    }
+
+    /// Replaces only the first match.
+    pub fn once(mut self) -> Self {
+        self.once = true;
+        self
+    }
+
+    /// Returns the number of bytes held back as a possible start of a match.
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Processes the next part of the data, appending the result to `out`.
+    ///
+    /// The `replace` callback receives each match and writes the replacement. With `last`, the
+    /// held back data is flushed to the output.
+    pub fn feed<F>(
+        &mut self,
+        data: &[u8],
+        last: bool,
+        out: &mut Vec<u8, A>,
+        mut replace: F,
+    ) -> Result<(), AllocError>
+    where
+        F: FnMut(&[u8], &mut Output<'_, A>) -> Result<(), AllocError>,
+    {
+        let mut out = Output(out);
+
+        if self.done && self.pending.is_empty() {
+            return out.extend(data);
+        }
+
+        self.pending.try_reserve(data.len()).map_err(|_| AllocError)?;
+        self.pending.extend_from_slice(data);
+
+        let mut pos = 0;
+
+        while pos < self.pending.len() {
+            let rest = &self.pending[pos..];
+
+            let search = if self.done { Search::None } else { self.matcher.search(rest, last) };
+
+            match search {
+                Search::Found(m) if !m.is_empty() => {
+                    out.extend(&rest[..m.start])?;
+                    replace(&rest[m.clone()], &mut out)?;
+                    pos += m.end;
+                    self.done = self.once;
+                }
+                Search::Partial(start) if !last => {
+                    out.extend(&rest[..start])?;
+                    pos += start;
+                    break;
+                }
+                _ => {
+                    out.extend(rest)?;
+                    pos = self.pending.len();
+                }
+            }
+        }
+
+        self.pending.drain(..pos);
+        Ok(())
+    }
+}
+
+/// Body filter helper applying a [`Substitution`] to the response.
+pub struct SubstitutionFilter<M> {
+    state: Substitution<M, Pool>,
+}
+
+impl<M: Matcher> SubstitutionFilter<M> {
+    /// Creates the filter state for the request.
+    pub fn new(r: &Request, matcher: M) -> Self {
+        Self { state: Substitution::new_in(matcher, r.pool()) }
+    }
+
+    /// Replaces only the first match.
+    pub fn once(self) -> Self {
+        Self { state: self.state.once() }
+    }
+
+    /// Processes the buffers of the body filter input, returning a chain of the modified
+    /// buffers to pass to the next body filter.
+    ///
+    /// The input buffers are marked as consumed.
+    ///
+    /// # Safety
+    ///
+    /// `body` must be a valid chain of the request output.
+    pub unsafe fn process<F>(
+        &mut self,
+        r: &Request,
+        body: *mut ngx_chain_t,
+        mut replace: F,
+    ) -> Result<*mut ngx_chain_t, Status>
+    where
+        F: FnMut(&[u8], &mut Output<'_, Pool>) -> Result<(), AllocError>,
+    {
+        let pool = r.pool();
+        let mut out: *mut ngx_chain_t = ptr::null_mut();
+        let mut ll: *mut *mut ngx_chain_t = &mut out;
+
+        let mut cl = body;
+        while let Some(link) = unsafe { cl.as_mut() } {
+            cl = link.next;
+
+            let b = unsafe { &mut *link.buf };
+            let in_memory = b.temporary() != 0 || b.memory() != 0 || b.mmap() != 0;
+            if b.in_file() != 0 && !in_memory {
+                return Err(Status::NGX_ERROR);
+            }
+
+            let data = if in_memory && !b.pos.is_null() {
+                unsafe { core::slice::from_raw_parts(b.pos, b.last.offset_from(b.pos) as usize) }
+            } else {
+                &[]
+            };
+
+            let last = b.last_buf() != 0 || b.last_in_chain() != 0;
+            let mut buf = Vec::new_in(pool.clone());
+            self.state.feed(data, last, &mut buf, &mut replace).map_err(|_| Status::NGX_ERROR)?;
+
+            b.pos = b.last;
+            if b.in_file() != 0 {
+                b.file_pos = b.file_last;
+            }
+
+            let special = last || b.flush() != 0 || b.sync() != 0;
+            if buf.is_empty() && !special {
+                continue;
+            }
+
+            let nb = output_buf(&pool, &buf).ok_or(Status::NGX_ERROR)?;
+            unsafe {
+                (*nb).set_last_buf(b.last_buf());
+                (*nb).set_last_in_chain(b.last_in_chain());
+                (*nb).set_flush(b.flush());
+                (*nb).set_sync(if buf.is_empty() { 1 } else { 0 });
+
+                let link = pool.alloc_type::<ngx_chain_t>();
+                if link.is_null() {
+                    return Err(Status::NGX_ERROR);
+                }
+                link.write(ngx_chain_t { buf: nb, next: ptr::null_mut() });
+                *ll = link;
+                ll = &mut (*link).next;
+            }
+        }
+
+        Ok(out)
+    }
+}
+
+/// Prepares the response headers for a modified body; called from the header filter.
+///
+/// Requests the body in memory, removes `Content-Length`, `Accept-Ranges` and `Last-Modified` as
+/// the body length and content change, and weakens the `ETag`.
+pub fn prepare(r: &mut Request) {
+    r.as_mut().set_filter_need_in_memory(1);
+    if r.is_main() {
+        r.clear_content_length();
+        r.clear_accept_ranges();
+        r.clear_last_modified();
+        r.weaken_etag();
+    }
+}
+
+fn output_buf(pool: &Pool, data: &[u8]) -> Option<*mut ngx_buf_t> {
+    if data.is_empty() {
+        let b = pool.calloc_type::<ngx_buf_t>();
+        return (!b.is_null()).then_some(b);
+    }
+
+    let mut buf = pool.create_buffer(data.len())?;
+    let b = buf.as_ngx_buf_mut();
+    // SAFETY: the buffer was allocated with the required size.
+    unsafe {
+        ptr::copy_nonoverlapping(data.as_ptr(), (*b).pos, data.len());
+        (*b).last = (*b).pos.add(data.len());
+    }
+    Some(b)
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use std::vec::Vec as StdVec;
+
+    use crate::allocator::Global;
+
+    use super::*;
+
+    fn run<M: Matcher>(matcher: M, parts: &[&[u8]], once: bool) -> StdVec<u8> {
+        let mut s = Substitution::new_in(matcher, Global);
+        if once {
+            s = s.once();
+        }
+
+        let mut result = StdVec::new();
+        for (i, part) in parts.iter().enumerate() {
+            let mut out = Vec::new_in(Global);
+            s.feed(part, i + 1 == parts.len(), &mut out, |m, out| {
+                out.extend(b"[")?;
+                out.extend(m)?;
+                out.extend(b"]")
+            })
+            .unwrap();
+            result.extend_from_slice(&out);
+        }
+        assert_eq!(s.pending(), 0);
+        result
+    }
+
+    #[test]
+    fn test_literal() {
+        let lit = Literal::new(b"foo").unwrap();
+        assert_eq!(run(lit, &[b"a foo b foo"], false), b"a [foo] b [foo]");
+        assert_eq!(run(lit, &[b"a f", b"o", b"o b fo"], false), b"a [foo] b fo");
+        assert_eq!(run(lit, &[b"ffoo", b"f", b"foo"], false), b"f[foo]f[foo]");
+        assert_eq!(run(lit, &[b"foo foo"], true), b"[foo] foo");
+        assert_eq!(run(lit.ignore_case(), &[b"FoO"], false), b"[FoO]");
+        assert!(Literal::new(b"").is_none());
+    }
+
+    #[test]
+    fn test_literal_partial() {
+        let mut lit = Literal::new(b"abc").unwrap();
+        assert_eq!(lit.search(b"xxab", false), Search::Partial(2));
+        assert_eq!(lit.search(b"xxa", false), Search::Partial(2));
+        assert_eq!(lit.search(b"xxb", false), Search::None);
+        assert_eq!(lit.search(b"xabcx", false), Search::Found(1..4));
+    }
+
+    #[test]
+    fn test_windowed() {
+        // digits, up to 4 characters
+        let find = |data: &[u8]| {
+            let start = data.iter().position(u8::is_ascii_digit)?;
+            let len = data[start..].iter().take(4).take_while(|x| x.is_ascii_digit()).count();
+            Some(start..start + len)
+        };
+
+        let w = Windowed::new(4, find);
+        assert_eq!(run(w, &[b"a 12", b"34 b 5"], false), b"a [1234] b [5]");
+    }
+}
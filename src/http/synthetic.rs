@@ -0,0 +1,335 @@
+use core::ffi::c_void;
+use core::mem;
+use core::ops::{Deref, DerefMut};
+use core::ptr::{self, NonNull};
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::core::{Pool, Status, del_timer};
+use crate::ffi::*;
+use crate::http::{HttpModuleMainConf, Method, NgxHttpCoreModule, Request};
+
+/// Builder for a synthetic request, not associated with any client connection.
+///
+/// Synthetic requests allow a module to invoke its own request handlers at the times when there is
+/// no client request, e.g. from a timer to warm up a cache or to perform a self-check.
+///
+/// The request is created with a fake connection and uses the configuration of the selected
+/// virtual server, the first server in the `http` block by default. The location is not
+/// resolved, the server-level location configuration is used instead. The response is discarded.
+///
+/// Synthetic requests can only be created in a worker process, after the module initialization.
+#[derive(Clone)]
+pub struct SyntheticRequestBuilder<'a> {
+    method: Method,
+    uri: &'a str,
+    args: &'a str,
+    server: Option<&'a ngx_http_core_srv_conf_t>,
+}
+
+/// Synthetic request created by [`SyntheticRequestBuilder`].
+///
+/// The request, the fake connection and the associated memory pool are destroyed when this object
+/// is dropped.
+pub struct SyntheticRequest(NonNull<Request>);
+
+impl<'a> SyntheticRequestBuilder<'a> {
+    /// Creates a builder for a `GET` request to `uri`.
+    pub fn new(uri: &'a str) -> Self {
+        Self { method: Method::GET, uri, args: "", server: None }
+    }
+
+    /// Sets the request method.
+    pub fn method(mut self, method: Method) -> Self {
+        self.method = method;
+        self
+    }
+
+    /// Sets the request arguments, the part of the request line after `?`.
+    pub fn args(mut self, args: &'a str) -> Self {
+        self.args = args;
+        self
+    }
+
+    /// Selects the virtual server for the request.
+    pub fn server(mut self, cscf: &'a ngx_http_core_srv_conf_t) -> Self {
+        self.server = Some(cscf);
+        self
+    }
+
+    /// Creates the request.
+    pub fn build(self) -> Result<SyntheticRequest, Status> {
+        // SAFETY: the cycle is initialized before any module code can run.
+        let cycle = unsafe { &*ngx_cycle };
+        let cmcf = NgxHttpCoreModule::main_conf(cycle).ok_or(Status::NGX_ERROR)?;
+
+        let cscf = match self.server {
+            Some(cscf) => cscf,
+            None => {
+                // SAFETY: `servers` contains pointers to the server configurations.
+                let servers: &[*mut ngx_http_core_srv_conf_t] = unsafe { cmcf.servers.as_slice() };
+                unsafe { servers.first().ok_or(Status::NGX_DECLINED)?.as_ref() }
+                    .ok_or(Status::NGX_ERROR)?
+            }
+        };
+
+        let c = unsafe { create_connection(cycle.log) }.ok_or(Status::NGX_ERROR)?;
+
+        // SAFETY: `c` is a valid connection with a pool created by create_connection.
+        let pool = unsafe { Pool::from_ngx_pool((*c.as_ptr()).pool) };
+
+        let r = pool.calloc_type::<ngx_http_request_t>();
+        let hc = pool.calloc_type::<ngx_http_connection_t>();
+        if r.is_null() || hc.is_null() {
+            unsafe { close_connection(c.as_ptr()) };
+            return Err(Status::NGX_ERROR);
+        }
+
+        unsafe { (*r).connection = c.as_ptr() };
+        // SAFETY: `r` is a zeroed request allocated from the connection pool. From this point
+        // the connection is released when the returned object is dropped.
+        let request = SyntheticRequest(unsafe { NonNull::new_unchecked(r.cast()) });
+
+        unsafe {
+            (*c.as_ptr()).data = r.cast();
+            (*hc).conf_ctx = cscf.ctx;
+
+            let r = &mut *r;
+            r.signature = NGX_HTTP_MODULE as _;
+            r.http_connection = hc;
+            r.pool = pool.as_ptr();
+            r.main = &raw mut *r;
+            r.read_event_handler = Some(ngx_http_block_reading);
+            r.write_event_handler = Some(ngx_http_request_empty_handler);
+            r.set_count(1);
+            r.set_internal(1);
+            r.set_subrequests((NGX_HTTP_MAX_SUBREQUESTS + 1) as _);
+            r.set_uri_changes((NGX_HTTP_MAX_URI_CHANGES + 1) as _);
+            r.http_version = NGX_HTTP_VERSION_11 as _;
+            r.method = self.method.to_ngx();
+
+            let ctx = &*cscf.ctx;
+            r.main_conf = ctx.main_conf;
+            r.srv_conf = ctx.srv_conf;
+            r.loc_conf = ctx.loc_conf;
+
+            r.ctx = pool.calloc(mem::size_of::<*mut c_void>() * ngx_http_max_module).cast();
+            r.variables = pool
+                .calloc(mem::size_of::<ngx_http_variable_value_t>() * cmcf.variables.nelts)
+                .cast();
+            if r.ctx.is_null() || r.variables.is_null() {
+                return Err(Status::NGX_ERROR);
+            }
+
+            if ngx_list_init(
+                &raw mut r.headers_in.headers,
+                r.pool,
+                2,
+                mem::size_of::<ngx_table_elt_t>(),
+            ) != NGX_OK as ngx_int_t
+                || ngx_list_init(
+                    &raw mut r.headers_out.headers,
+                    r.pool,
+                    2,
+                    mem::size_of::<ngx_table_elt_t>(),
+                ) != NGX_OK as ngx_int_t
+            {
+                return Err(Status::NGX_ERROR);
+            }
+
+            r.headers_in.content_length_n = -1;
+            r.headers_in.keep_alive_n = -1;
+            r.headers_out.content_length_n = -1;
+            r.headers_out.last_modified_time = -1;
+
+            r.method_name = pool.copy_str(self.method.as_str()).ok_or(Status::NGX_ERROR)?;
+            r.uri = pool.copy_str(self.uri).ok_or(Status::NGX_ERROR)?;
+            r.args = pool.copy_str(self.args).ok_or(Status::NGX_ERROR)?;
+            r.unparsed_uri = r.uri;
+            r.request_line = r.uri;
+
+            let tp = ngx_timeofday();
+            r.start_sec = tp.sec;
+            r.start_msec = tp.msec;
+
+            ngx_http_update_location_config(r);
+        }
+
+        Ok(request)
+    }
+}
+
+impl SyntheticRequest {
+    /// Runs `handler` as the content handler of the request and finalizes the request with its
+    /// result, the same as the content phase of a client request.
+    ///
+    /// The subrequests created by the handler and the other posted requests are run before
+    /// returning. A handler returning [`Status::NGX_DONE`] may continue the processing
+    /// asynchronously, e.g. with an upstream subrequest, and must finalize the request itself
+    /// when done; the request must then be kept alive until the finalization.
+    ///
+    /// The request can only be run once. Returns the status of the handler, or
+    /// [`Status::NGX_DECLINED`] if the request was already run.
+    pub fn run<F>(&mut self, handler: F) -> Status
+    where
+        F: FnOnce(&mut Request) -> Status,
+    {
+        let r: *mut ngx_http_request_t = self.0.as_ptr().cast();
+
+        unsafe {
+            if (*r).done() != 0 || (*r).blocked() != 0 {
+                return Status::NGX_DECLINED;
+            }
+
+            // The request and the connection are owned by this object and are released in
+            // `Drop`: hold an extra reference, and block the request so that neither the
+            // finalization nor the termination of the request frees them.
+            (*r).set_count((*r).count() + 1);
+            (*r).set_blocked((*r).blocked() + 1);
+        }
+
+        let rc = handler(self);
+
+        // SAFETY: the request and the connection are valid until the object is dropped.
+        unsafe {
+            ngx_http_finalize_request(r, rc.into());
+            ngx_http_run_posted_requests((*r).connection);
+        }
+
+        rc
+    }
+}
+
+impl Deref for SyntheticRequest {
+    type Target = Request;
+
+    fn deref(&self) -> &Self::Target {
+        // SAFETY: the request is valid until the object is dropped.
+        unsafe { self.0.as_ref() }
+    }
+}
+
+impl DerefMut for SyntheticRequest {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        // SAFETY: the request is valid until the object is dropped.
+        unsafe { self.0.as_mut() }
+    }
+}
+
+impl Drop for SyntheticRequest {
+    fn drop(&mut self) {
+        let r: *mut ngx_http_request_t = self.0.as_ptr().cast();
+
+        unsafe {
+            let mut cln = (*r).cleanup;
+            (*r).cleanup = ptr::null_mut();
+            while let Some(x) = cln.as_ref() {
+                if let Some(handler) = x.handler {
+                    handler(x.data);
+                }
+                cln = x.next;
+            }
+
+            close_connection((*r).connection);
+        }
+    }
+}
+
+/// Creates a fake connection without a socket, with a dedicated memory pool.
+///
+/// The connection has no descriptor, which is only supported by the event methods not indexing
+/// the connections by the descriptor (`cycle->files`), i.e. all but `/dev/poll`.
+unsafe fn create_connection(log: *mut ngx_log_t) -> Option<NonNull<ngx_connection_t>> {
+    unsafe {
+        if !(*ngx_cycle).files.is_null() {
+            return None;
+        }
+
+        let c = ngx_get_connection(-1, log).as_mut()?;
+
+        c.pool = ngx_create_pool(NGX_DEFAULT_POOL_SIZE as _, log);
+        if c.pool.is_null() {
+            close_connection(c);
+            return None;
+        }
+
+        let clog = ngx_pcalloc(c.pool, mem::size_of::<ngx_log_t>()).cast::<ngx_log_t>();
+        if clog.is_null() {
+            close_connection(c);
+            return None;
+        }
+        *clog = *log;
+        c.log = clog;
+        (*c.read).log = clog;
+        (*c.write).log = clog;
+
+        c.recv = Some(fake_recv);
+        c.send = Some(fake_send);
+        c.send_chain = Some(fake_send_chain);
+        // SAFETY: the counter is allocated in the shared memory before the worker starts.
+        c.number = AtomicUsize::from_ptr(ngx_connection_counter.cast())
+            .fetch_add(1, Ordering::Relaxed) as _;
+
+        NonNull::new(c)
+    }
+}
+
+unsafe fn close_connection(c: *mut ngx_connection_t) {
+    unsafe {
+        let c = &mut *c;
+        let pool = c.pool;
+
+        if (*c.read).timer_set() != 0 {
+            del_timer(c.read);
+        }
+        if (*c.write).timer_set() != 0 {
+            del_timer(c.write);
+        }
+        if (*c.read).posted() != 0 {
+            ngx_delete_posted_event(c.read);
+        }
+        if (*c.write).posted() != 0 {
+            ngx_delete_posted_event(c.write);
+        }
+
+        c.set_destroyed(1);
+        ngx_free_connection(c);
+
+        if !pool.is_null() {
+            ngx_destroy_pool(pool);
+        }
+    }
+}
+
+unsafe extern "C" fn fake_recv(
+    _c: *mut ngx_connection_t,
+    _buf: *mut u_char,
+    _size: usize,
+) -> isize {
+    0
+}
+
+unsafe extern "C" fn fake_send(_c: *mut ngx_connection_t, _buf: *mut u_char, size: usize) -> isize {
+    size as isize
+}
+
+unsafe extern "C" fn fake_send_chain(
+    c: *mut ngx_connection_t,
+    mut cl: *mut ngx_chain_t,
+    _limit: off_t,
+) -> *mut ngx_chain_t {
+    unsafe {
+        while let Some(link) = cl.as_mut() {
+            if let Some(b) = link.buf.as_mut() {
+                let mut size = b.last.offset_from(b.pos) as off_t;
+                b.pos = b.last;
+                if b.in_file() != 0 {
+                    size = b.file_last - b.file_pos;
+                    b.file_pos = b.file_last;
+                }
+                (*c).sent += size;
+            }
+            cl = link.next;
+        }
+    }
+    ptr::null_mut()
+}
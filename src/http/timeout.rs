@@ -0,0 +1,151 @@
+use core::ffi::c_void;
+use core::mem;
+use core::time::Duration;
+
+use crate::allocator::AllocError;
+use crate::core::add_timer;
+use crate::ffi::{
+    ngx_http_core_loc_conf_t, ngx_http_max_module, ngx_msec_t, ngx_pool_cleanup_add, ngx_pool_t,
+};
+use crate::http::{HttpModule, HttpModuleLocationConf, NgxHttpCoreModule, Request};
+
+impl Request {
+    /// Returns the `client_body_timeout` of the request.
+    pub fn client_body_timeout(&self) -> Duration {
+        self.timeout(|clcf| clcf.client_body_timeout)
+    }
+
+    /// Returns the `send_timeout` of the request.
+    pub fn send_timeout(&self) -> Duration {
+        self.timeout(|clcf| clcf.send_timeout)
+    }
+
+    /// Returns the `keepalive_timeout` of the request.
+    pub fn keepalive_timeout(&self) -> Duration {
+        self.timeout(|clcf| clcf.keepalive_timeout)
+    }
+
+    /// Overrides the `client_body_timeout` for the request.
+    ///
+    /// The timer is re-armed with the new value if the request body is being read.
+    ///
+    /// The overrides of the timeouts are attached to the location configuration of the request,
+    /// and are lost if the location changes, e.g. on an internal redirect.
+    pub fn set_client_body_timeout(&mut self, timeout: Duration) -> Result<(), AllocError> {
+        let msec = to_msec(timeout);
+        self.private_core_loc_conf()?.client_body_timeout = msec;
+
+        let r = self.as_ref();
+        // SAFETY: the connection and its events are valid while the request is alive.
+        unsafe {
+            let rev = (*r.connection).read;
+            if r.reading_body() != 0 && (*rev).timer_set() != 0 {
+                add_timer(rev, msec);
+            }
+        }
+        Ok(())
+    }
+
+    /// Overrides the `send_timeout` for the request.
+    ///
+    /// The timer is re-armed with the new value if the response is being sent, unless the
+    /// sending is delayed by the rate limiting.
+    ///
+    /// See [`Request::set_client_body_timeout`] for the lifetime of the override.
+    pub fn set_send_timeout(&mut self, timeout: Duration) -> Result<(), AllocError> {
+        let msec = to_msec(timeout);
+        self.private_core_loc_conf()?.send_timeout = msec;
+
+        let r = self.as_ref();
+        // SAFETY: the connection and its events are valid while the request is alive.
+        unsafe {
+            let wev = (*r.connection).write;
+            if (*wev).timer_set() != 0 && (*wev).delayed() == 0 {
+                add_timer(wev, msec);
+            }
+        }
+        Ok(())
+    }
+
+    /// Overrides the `keepalive_timeout` for the request, applied once the request is
+    /// finalized. A zero timeout disables the keepalive for the connection.
+    ///
+    /// See [`Request::set_client_body_timeout`] for the lifetime of the override.
+    pub fn set_keepalive_timeout(&mut self, timeout: Duration) -> Result<(), AllocError> {
+        let clcf = self.private_core_loc_conf()?;
+        clcf.keepalive_timeout = to_msec(timeout);
+        if timeout.is_zero() {
+            self.as_mut().set_keepalive(0);
+        }
+        Ok(())
+    }
+
+    fn timeout(&self, f: impl FnOnce(&ngx_http_core_loc_conf_t) -> ngx_msec_t) -> Duration {
+        NgxHttpCoreModule::location_conf(self)
+            .map_or(Duration::ZERO, |clcf| Duration::from_millis(f(clcf) as u64))
+    }
+
+    /// Returns the core location configuration of the request, copied for the request on the
+    /// first call to keep the changes private.
+    fn private_core_loc_conf(&mut self) -> Result<&mut ngx_http_core_loc_conf_t, AllocError> {
+        let index = NgxHttpCoreModule::module().ctx_index;
+        let pool = self.pool();
+        let r = self.as_mut();
+
+        if !is_private(r.pool, r.loc_conf) {
+            // SAFETY: the location configuration array contains a pointer for each HTTP module.
+            let clcf = unsafe { *r.loc_conf.add(index) }.cast::<ngx_http_core_loc_conf_t>();
+            let clcf = pool.allocate(unsafe { *clcf });
+            if clcf.is_null() {
+                return Err(AllocError);
+            }
+
+            // SAFETY: `ngx_http_max_module` is the number of the HTTP modules.
+            let n = unsafe { ngx_http_max_module };
+            let conf = pool.alloc(n * mem::size_of::<*mut c_void>()).cast::<*mut c_void>();
+            if conf.is_null() {
+                return Err(AllocError);
+            }
+
+            // SAFETY: both arrays have `ngx_http_max_module` elements.
+            unsafe {
+                conf.copy_from_nonoverlapping(r.loc_conf, n);
+                *conf.add(index) = clcf.cast();
+            }
+            mark_private(r.pool, conf)?;
+            r.loc_conf = conf;
+        }
+
+        // SAFETY: the configuration is allocated from the request pool above.
+        Ok(unsafe { &mut *(*r.loc_conf.add(index)).cast() })
+    }
+}
+
+fn to_msec(timeout: Duration) -> ngx_msec_t {
+    timeout.as_millis().min(ngx_msec_t::MAX as u128) as ngx_msec_t
+}
+
+fn is_private(pool: *mut ngx_pool_t, conf: *mut *mut c_void) -> bool {
+    // SAFETY: the cleanup list is owned by the request pool.
+    let mut cln = unsafe { (*pool).cleanup };
+    while let Some(c) = unsafe { cln.as_ref() } {
+        if c.handler == Some(private_loc_conf_cleanup as unsafe extern "C" fn(_))
+            && c.data == conf.cast()
+        {
+            return true;
+        }
+        cln = c.next;
+    }
+    false
+}
+
+fn mark_private(pool: *mut ngx_pool_t, conf: *mut *mut c_void) -> Result<(), AllocError> {
+    // SAFETY: the request pool is valid while the request is alive.
+    let cln = unsafe { ngx_pool_cleanup_add(pool, 0).as_mut() }.ok_or(AllocError)?;
+    cln.data = conf.cast();
+    cln.handler = Some(private_loc_conf_cleanup);
+    Ok(())
+}
+
+/// Marks the location configuration copied for a request.
+unsafe extern "C" fn private_loc_conf_cleanup(_data: *mut c_void) {}
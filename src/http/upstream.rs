@@ -1,3 +1,15 @@
+use core::ffi::c_void;
+use core::ptr::NonNull;
+
+use crate::core::Status;
+use crate::ffi::{
+    ngx_conf_t, ngx_http_request_t, ngx_http_upstream_free_round_robin_peer,
+    ngx_http_upstream_get_round_robin_peer, ngx_http_upstream_init_round_robin,
+    ngx_http_upstream_init_round_robin_peer, ngx_http_upstream_rr_peer_data_t,
+    ngx_http_upstream_srv_conf_t, ngx_int_t, ngx_peer_connection_t, ngx_uint_t,
+};
+use crate::http::Request;
+
 /// Define a static upstream peer initializer
 ///
 /// Initializes the upstream 'get', 'free', and 'session' callbacks and gives the module writer an
@@ -22,3 +34,187 @@ macro_rules! http_upstream_init_peer_pt {
         }
     };
 }
+
+/// Trait for a load balancer built on top of the round-robin balancer.
+///
+/// The peers of the upstream block are initialized with the round-robin balancer, and the
+/// implementation decides for each connection attempt whether to pick a peer itself or to
+/// delegate the choice to [`RoundRobinPeers`]. This is the structure of most load balancing
+/// modules in NGINX, e.g. `hash`, `ip_hash` or `least_conn`.
+///
+/// Install the balancer with [`set_upstream_peer`] from a directive handler of the `upstream`
+/// block.
+///
+/// Load Balancing: <https://nginx.org/en/docs/dev/development_guide.html#http_load_balancing>
+pub trait UpstreamPeer: Sized {
+    /// Creates the balancer state for a request.
+    fn init(r: &mut Request, us: &ngx_http_upstream_srv_conf_t) -> Result<Self, Status>;
+
+    /// Selects a peer for a connection attempt.
+    ///
+    /// On success, the implementation sets `pc.sockaddr`, `pc.socklen` and `pc.name` and returns
+    /// `NGX_OK`. `NGX_BUSY` reports that no peers are available.
+    fn get(&mut self, pc: &mut ngx_peer_connection_t, rr: &mut RoundRobinPeers) -> Status {
+        rr.get(pc)
+    }
+
+    /// Releases the peer selected with [`UpstreamPeer::get`].
+    ///
+    /// `state` is a combination of `NGX_PEER_FAILED` and `NGX_PEER_NEXT` flags, or `0` for a
+    /// successful connection.
+    fn free(
+        &mut self,
+        pc: &mut ngx_peer_connection_t,
+        rr: &mut RoundRobinPeers,
+        state: ngx_uint_t,
+    ) {
+        rr.free(pc, state)
+    }
+}
+
+/// Round-robin balancer state of a request.
+pub struct RoundRobinPeers(NonNull<ngx_http_upstream_rr_peer_data_t>);
+
+impl RoundRobinPeers {
+    /// Returns the underlying [`ngx_http_upstream_rr_peer_data_t`].
+    pub fn as_ptr(&self) -> *mut ngx_http_upstream_rr_peer_data_t {
+        self.0.as_ptr()
+    }
+
+    /// Returns a mutable reference to the round-robin state, e.g. to iterate the peers or to set
+    /// `current` for a peer selected by the caller.
+    pub fn data(&mut self) -> &mut ngx_http_upstream_rr_peer_data_t {
+        // SAFETY: the state is allocated from the request pool in the peer initialization.
+        unsafe { self.0.as_mut() }
+    }
+
+    /// Selects a peer with the round-robin algorithm.
+    pub fn get(&mut self, pc: &mut ngx_peer_connection_t) -> Status {
+        Status(unsafe { ngx_http_upstream_get_round_robin_peer(pc, self.as_ptr().cast()) })
+    }
+
+    /// Releases the peer selected with [`RoundRobinPeers::get`] or set in `current`.
+    pub fn free(&mut self, pc: &mut ngx_peer_connection_t, state: ngx_uint_t) {
+        unsafe { ngx_http_upstream_free_round_robin_peer(pc, self.as_ptr().cast(), state) }
+    }
+}
+
+struct PeerData<P> {
+    rr: NonNull<ngx_http_upstream_rr_peer_data_t>,
+    peer: P,
+}
+
+/// Sets the load balancer of the upstream block to `P`.
+///
+/// Overrides the balancer installed by the previous directives of the block. Typically called
+/// from a directive handler with the `NGX_HTTP_UPS_CONF` context.
+pub fn set_upstream_peer<P>(us: &mut ngx_http_upstream_srv_conf_t)
+where
+    P: UpstreamPeer,
+{
+    us.peer.init_upstream = Some(raw_init_upstream::<P>);
+}
+
+unsafe extern "C" fn raw_init_upstream<P>(
+    cf: *mut ngx_conf_t,
+    us: *mut ngx_http_upstream_srv_conf_t,
+) -> ngx_int_t
+where
+    P: UpstreamPeer,
+{
+    let rc = unsafe { ngx_http_upstream_init_round_robin(cf, us) };
+    if rc != Status::NGX_OK.into() {
+        return rc;
+    }
+
+    unsafe { (*us).peer.init = Some(raw_init_peer::<P>) };
+    Status::NGX_OK.into()
+}
+
+unsafe extern "C" fn raw_init_peer<P>(
+    r: *mut ngx_http_request_t,
+    us: *mut ngx_http_upstream_srv_conf_t,
+) -> ngx_int_t
+where
+    P: UpstreamPeer,
+{
+    let rc = unsafe { ngx_http_upstream_init_round_robin_peer(r, us) };
+    if rc != Status::NGX_OK.into() {
+        return rc;
+    }
+
+    let request = unsafe { Request::from_ngx_http_request(r) };
+    let peer = match P::init(request, unsafe { &*us }) {
+        Ok(peer) => peer,
+        Err(status) => return status.into(),
+    };
+
+    // SAFETY: the upstream and the round-robin state are set by the round-robin initialization.
+    let u = unsafe { &mut *(*r).upstream };
+    let Some(rr) = NonNull::new(u.peer.data.cast()) else {
+        return Status::NGX_ERROR.into();
+    };
+
+    let data = request.pool().allocate(PeerData { rr, peer });
+    if data.is_null() {
+        return Status::NGX_ERROR.into();
+    }
+
+    u.peer.data = data.cast();
+    u.peer.get = Some(raw_get_peer::<P>);
+    u.peer.free = Some(raw_free_peer::<P>);
+
+    #[cfg(ngx_feature = "http_ssl")]
+    {
+        u.peer.set_session = Some(raw_set_session::<P>);
+        u.peer.save_session = Some(raw_save_session::<P>);
+    }
+
+    Status::NGX_OK.into()
+}
+
+unsafe extern "C" fn raw_get_peer<P>(pc: *mut ngx_peer_connection_t, data: *mut c_void) -> ngx_int_t
+where
+    P: UpstreamPeer,
+{
+    let data = unsafe { &mut *data.cast::<PeerData<P>>() };
+    let mut rr = RoundRobinPeers(data.rr);
+    data.peer.get(unsafe { &mut *pc }, &mut rr).into()
+}
+
+unsafe extern "C" fn raw_free_peer<P>(
+    pc: *mut ngx_peer_connection_t,
+    data: *mut c_void,
+    state: ngx_uint_t,
+) where
+    P: UpstreamPeer,
+{
+    let data = unsafe { &mut *data.cast::<PeerData<P>>() };
+    let mut rr = RoundRobinPeers(data.rr);
+    data.peer.free(unsafe { &mut *pc }, &mut rr, state)
+}
+
+#[cfg(ngx_feature = "http_ssl")]
+unsafe extern "C" fn raw_set_session<P>(
+    pc: *mut ngx_peer_connection_t,
+    data: *mut c_void,
+) -> ngx_int_t
+where
+    P: UpstreamPeer,
+{
+    let data = unsafe { &*data.cast::<PeerData<P>>() };
+    unsafe {
+        crate::ffi::ngx_http_upstream_set_round_robin_peer_session(pc, data.rr.as_ptr().cast())
+    }
+}
+
+#[cfg(ngx_feature = "http_ssl")]
+unsafe extern "C" fn raw_save_session<P>(pc: *mut ngx_peer_connection_t, data: *mut c_void)
+where
+    P: UpstreamPeer,
+{
+    let data = unsafe { &*data.cast::<PeerData<P>>() };
+    unsafe {
+        crate::ffi::ngx_http_upstream_save_round_robin_peer_session(pc, data.rr.as_ptr().cast())
+    }
+}
@@ -0,0 +1,400 @@
+use core::fmt::{self, Write};
+use core::ops;
+use core::ptr;
+use core::slice;
+
+use crate::allocator::AllocError;
+use crate::core::{FloatBuffer, IntBuffer, Integer, LenCounter, NgxStr, SliceWriter, Status};
+use crate::ffi::{
+    NGX_ERROR, NGX_HTTP_VAR_CHANGEABLE, NGX_HTTP_VAR_NOCACHEABLE, NGX_HTTP_VAR_NOHASH,
+    NGX_HTTP_VAR_WEAK, NGX_LOG_EMERG, ngx_conf_t, ngx_hash_key, ngx_http_add_variable,
+    ngx_http_get_indexed_variable, ngx_http_get_variable, ngx_http_get_variable_index,
+    ngx_http_request_t, ngx_http_variable_t, ngx_int_t, ngx_str_t, ngx_uint_t,
+    ngx_variable_value_t,
+};
+use crate::http::Request;
+use crate::ngx_conf_log_error;
+
+/// Flags of an HTTP variable.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct VariableFlags(ngx_uint_t);
+
+impl VariableFlags {
+    /// No flags.
+    pub const NONE: Self = Self(0);
+    /// The variable can be redefined with the `set` directive.
+    pub const CHANGEABLE: Self = Self(NGX_HTTP_VAR_CHANGEABLE as _);
+    /// The value is never cached and is evaluated on each access.
+    pub const NO_CACHEABLE: Self = Self(NGX_HTTP_VAR_NOCACHEABLE as _);
+    /// The variable is only accessible by index, not by name.
+    pub const NO_HASH: Self = Self(NGX_HTTP_VAR_NOHASH as _);
+    /// The variable may be redefined by another module.
+    pub const WEAK: Self = Self(NGX_HTTP_VAR_WEAK as _);
+
+    /// Returns the raw flags value.
+    pub const fn bits(&self) -> ngx_uint_t {
+        self.0
+    }
+
+    /// Returns `true` if all the flags in `other` are set.
+    pub const fn contains(&self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl ops::BitOr for VariableFlags {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+/// Value of an HTTP variable.
+///
+/// The data must outlive the request: it is either static, owned by the request, or allocated
+/// from the request pool with [`VariableValue::copy`] or [`VariableValue::format`].
+#[derive(Clone, Copy, Debug)]
+pub struct VariableValue<'r> {
+    data: &'r [u8],
+    cacheable: bool,
+}
+
+impl<'r> VariableValue<'r> {
+    /// Creates a value referencing the data.
+    pub const fn new(data: &'r [u8]) -> Self {
+        Self { data, cacheable: true }
+    }
+
+    /// Creates a value with a copy of the data allocated from the request pool.
+    pub fn copy(r: &'r Request, data: &[u8]) -> Option<Self> {
+        let p = alloc_value(r, data.len())?;
+        p.copy_from_slice(data);
+        Some(Self::new(p))
+    }
+
+    /// Creates a value by formatting `args` into the request pool.
+    ///
+    /// ```no_run
+    /// # use ngx::http::{Request, VariableValue};
+    /// # fn get(r: &mut Request) -> Option<VariableValue<'_>> {
+    /// let len = r.path().len();
+    /// VariableValue::format(r, format_args!("{len}"))
+    /// # }
+    /// ```
+    pub fn format(r: &'r Request, args: fmt::Arguments<'_>) -> Option<Self> {
+        if let Some(s) = args.as_str() {
+            return Self::copy(r, s.as_bytes());
+        }
+
+        let mut counter = LenCounter(0);
+        counter.write_fmt(args).ok()?;
+
+        let p = alloc_value(r, counter.0)?;
+        let mut w = SliceWriter::new(&mut *p);
+        w.write_fmt(args).ok()?;
+        let len = w.pos;
+        Some(Self::new(&p[..len]))
+    }
+
+    /// Creates a value with the decimal representation of `n` allocated from the request pool.
+    pub fn from_int<T: Integer>(r: &'r Request, n: T) -> Option<Self> {
+        Self::copy(r, IntBuffer::new().format(n).as_bytes())
+    }
+
+    /// Creates a value with `f` formatted with `precision` decimal places, allocated from the
+    /// request pool. See [`FloatBuffer::format_fixed`].
+    pub fn from_fixed(r: &'r Request, f: f64, precision: usize) -> Option<Self> {
+        Self::copy(r, FloatBuffer::new().format_fixed(f, precision).as_bytes())
+    }
+
+    /// Marks the value as not cacheable: it will be evaluated again on the next access within the
+    /// same request.
+    pub const fn no_cacheable(mut self) -> Self {
+        self.cacheable = false;
+        self
+    }
+
+    /// Returns the value data.
+    pub fn as_bytes(&self) -> &'r [u8] {
+        self.data
+    }
+}
+
+impl<'r> From<&'r NgxStr> for VariableValue<'r> {
+    fn from(value: &'r NgxStr) -> Self {
+        Self::new(value.as_bytes())
+    }
+}
+
+impl From<&'static str> for VariableValue<'static> {
+    fn from(value: &'static str) -> Self {
+        Self::new(value.as_bytes())
+    }
+}
+
+/// Allocates a value buffer from the request pool.
+///
+/// The buffer borrows the request: the pool memory remains valid until the request is finalized.
+fn alloc_value<'r>(r: &'r Request, len: usize) -> Option<&'r mut [u8]> {
+    if len == 0 {
+        return Some(&mut []);
+    }
+    let p = r.pool().alloc_unaligned(len).cast::<u8>();
+    if p.is_null() {
+        return None;
+    }
+    // SAFETY: the memory is a fresh allocation from the request pool, which outlives `'r`. It is
+    // zeroed before use.
+    unsafe {
+        ptr::write_bytes(p, 0, len);
+        Some(slice::from_raw_parts_mut(p, len))
+    }
+}
+
+/// Trait for an HTTP variable defined by a module.
+///
+/// # Example
+///
+/// ```no_run
+/// # use ngx::http::{Request, Variable, VariableValue};
+/// struct UriLength;
+///
+/// impl Variable for UriLength {
+///     const NAME: &'static str = "uri_length";
+///
+///     fn get(r: &mut Request) -> Option<VariableValue<'_>> {
+///         let len = r.path().len();
+///         VariableValue::from_int(r, len)
+///     }
+/// }
+///
+/// // in preconfiguration:
+/// # fn preconfiguration(cf: &mut ngx::ffi::ngx_conf_t) -> Result<(), ngx::allocator::AllocError> {
+/// ngx::http::Variables::new(cf).add::<UriLength>()?;
+/// # Ok(())
+/// # }
+/// ```
+pub trait Variable {
+    /// Variable name, without the `$` prefix.
+    const NAME: &'static str;
+
+    /// Variable flags.
+    const FLAGS: VariableFlags = VariableFlags::NONE;
+
+    /// Evaluates the variable.
+    ///
+    /// Returns `None` if the variable is not found, i.e. evaluates to an empty string in the
+    /// configuration.
+    fn get(r: &mut Request) -> Option<VariableValue<'_>>;
+}
+
+/// Trait for an HTTP variable that can be redefined with the `set` directive.
+pub trait SettableVariable: Variable {
+    /// Handles the `set` directive for the variable.
+    fn set(r: &mut Request, value: &[u8]);
+}
+
+/// Registers the module variables.
+///
+/// Must be used from the module's `preconfiguration()` function.
+pub struct Variables<'a> {
+    cf: &'a mut ngx_conf_t,
+}
+
+impl<'a> Variables<'a> {
+    /// Creates a registration builder for the configuration.
+    pub fn new(cf: &'a mut ngx_conf_t) -> Self {
+        Self { cf }
+    }
+
+    /// Adds the variable `V`.
+    pub fn add<V: Variable>(&mut self) -> Result<&mut Self, AllocError> {
+        let var = self.add_raw(V::NAME, V::FLAGS)?;
+        var.get_handler = Some(raw_get_variable::<V>);
+        Ok(self)
+    }
+
+    /// Adds the variable `V` that can be redefined with the `set` directive.
+    pub fn add_settable<V: SettableVariable>(&mut self) -> Result<&mut Self, AllocError> {
+        let var = self.add_raw(V::NAME, V::FLAGS | VariableFlags::CHANGEABLE)?;
+        var.get_handler = Some(raw_get_variable::<V>);
+        var.set_handler = Some(raw_set_variable::<V>);
+        Ok(self)
+    }
+
+    fn add_raw(
+        &mut self,
+        name: &'static str,
+        flags: VariableFlags,
+    ) -> Result<&mut crate::ffi::ngx_http_variable_t, AllocError> {
+        let mut name = ngx_str_t { data: name.as_ptr().cast_mut(), len: name.len() };
+        let var = unsafe { ngx_http_add_variable(self.cf, &raw mut name, flags.bits()).as_mut() };
+        match var {
+            Some(var) => Ok(var),
+            None => {
+                ngx_conf_log_error!(NGX_LOG_EMERG, self.cf, "failed to add variable \"{name}\"");
+                Err(AllocError)
+            }
+        }
+    }
+}
+
+unsafe extern "C" fn raw_get_variable<V: Variable>(
+    r: *mut ngx_http_request_t,
+    v: *mut ngx_variable_value_t,
+    _data: usize,
+) -> ngx_int_t {
+    let r = unsafe { Request::from_ngx_http_request(r) };
+    let v = unsafe { &mut *v };
+
+    match V::get(r) {
+        Some(value) => {
+            v.data = value.data.as_ptr().cast_mut();
+            v.set_len(value.data.len() as _);
+            v.set_valid(1);
+            v.set_no_cacheable(!value.cacheable as _);
+            v.set_not_found(0);
+        }
+        None => v.set_not_found(1),
+    }
+
+    Status::NGX_OK.into()
+}
+
+unsafe extern "C" fn raw_set_variable<V: SettableVariable>(
+    r: *mut ngx_http_request_t,
+    v: *mut ngx_variable_value_t,
+    _data: usize,
+) {
+    let r = unsafe { Request::from_ngx_http_request(r) };
+    let v = unsafe { &*v };
+    let value = if v.data.is_null() {
+        &[][..]
+    } else {
+        unsafe { slice::from_raw_parts(v.data, v.len() as usize) }
+    };
+    V::set(r, value)
+}
+
+/// Registers a static table of variables and returns their indexes, in the order of the table.
+///
+/// This is the equivalent of the `ngx_http_variable_t` tables of the C modules, combined with
+/// [`VariableIndex::new`] for each entry: the indexes can be stored in the module configuration
+/// for the fast access with [`Request::indexed_variable`]. The `index` field of the entries is
+/// ignored.
+///
+/// Must be used from the module's `preconfiguration()` function.
+///
+/// ```no_run
+/// # use ngx::ffi::{ngx_conf_t, ngx_http_request_t, ngx_http_variable_t, ngx_int_t};
+/// # use ngx::ffi::ngx_variable_value_t;
+/// # use ngx::http::{VariableIndex, register_variables};
+/// # use ngx::ngx_string;
+/// # unsafe extern "C" fn get_foo(
+/// #     _r: *mut ngx_http_request_t,
+/// #     _v: *mut ngx_variable_value_t,
+/// #     _data: usize,
+/// # ) -> ngx_int_t {
+/// #     0
+/// # }
+/// static mut VARIABLES: [ngx_http_variable_t; 1] = [ngx_http_variable_t {
+///     name: ngx_string!("foo"),
+///     set_handler: None,
+///     get_handler: Some(get_foo),
+///     data: 0,
+///     flags: 0,
+///     index: 0,
+/// }];
+///
+/// struct MainConf {
+///     indexes: [VariableIndex; 1],
+/// }
+///
+/// # fn preconfiguration(cf: &mut ngx_conf_t, conf: &mut MainConf) -> Option<()> {
+/// conf.indexes = register_variables(cf, unsafe { &*&raw const VARIABLES }).ok()?;
+/// # Some(())
+/// # }
+/// ```
+pub fn register_variables<const N: usize>(
+    cf: &mut ngx_conf_t,
+    table: &[ngx_http_variable_t; N],
+) -> Result<[VariableIndex; N], AllocError> {
+    let mut indexes = [VariableIndex(0); N];
+
+    for (def, index) in table.iter().zip(indexes.iter_mut()) {
+        let mut name = def.name;
+        let var = unsafe { ngx_http_add_variable(cf, &raw mut name, def.flags).as_mut() };
+        let Some(var) = var else {
+            ngx_conf_log_error!(NGX_LOG_EMERG, cf, "failed to add variable \"{name}\"");
+            return Err(AllocError);
+        };
+        var.get_handler = def.get_handler;
+        var.set_handler = def.set_handler;
+        var.data = def.data;
+
+        *index = VariableIndex::from_ngx_str(cf, &mut name).ok_or(AllocError)?;
+    }
+
+    Ok(indexes)
+}
+
+/// Index of an HTTP variable, obtained at the configuration time.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct VariableIndex(ngx_uint_t);
+
+impl VariableIndex {
+    /// Returns the index of the variable `name`, registering it for indexed access.
+    ///
+    /// Must be called during the configuration parsing.
+    pub fn new(cf: &mut ngx_conf_t, name: &str) -> Option<Self> {
+        let mut name = ngx_str_t { data: name.as_ptr().cast_mut(), len: name.len() };
+        Self::from_ngx_str(cf, &mut name)
+    }
+
+    fn from_ngx_str(cf: &mut ngx_conf_t, name: &mut ngx_str_t) -> Option<Self> {
+        let index = unsafe { ngx_http_get_variable_index(cf, name) };
+        if index == NGX_ERROR as ngx_int_t {
+            return None;
+        }
+        Some(Self(index as _))
+    }
+
+    /// Returns the raw index.
+    pub fn get(&self) -> ngx_uint_t {
+        self.0
+    }
+}
+
+impl Request {
+    /// Evaluates an indexed variable.
+    ///
+    /// Returns `None` if the variable is not found or the evaluation fails.
+    pub fn indexed_variable(&mut self, index: VariableIndex) -> Option<&NgxStr> {
+        let v = unsafe { ngx_http_get_indexed_variable(self.into(), index.0).as_ref()? };
+        variable_value(v)
+    }
+
+    /// Evaluates a variable by its name, which must be in lowercase.
+    ///
+    /// Prefer [`Request::indexed_variable`] for the variables known at the configuration time:
+    /// the lookup by name is slower, and the variables not used in the configuration may be
+    /// unavailable.
+    pub fn variable(&mut self, name: &str) -> Option<&NgxStr> {
+        let mut name = ngx_str_t { data: name.as_ptr().cast_mut(), len: name.len() };
+        let key = unsafe { ngx_hash_key(name.data, name.len) };
+        let v = unsafe { ngx_http_get_variable(self.into(), &raw mut name, key).as_ref()? };
+        variable_value(v)
+    }
+}
+
+fn variable_value(v: &ngx_variable_value_t) -> Option<&NgxStr> {
+    if v.not_found() != 0 || v.valid() == 0 {
+        return None;
+    }
+    if v.data.is_null() {
+        return Some(NgxStr::from_bytes(&[]));
+    }
+    // SAFETY: the value is valid for the lifetime of the request.
+    Some(NgxStr::from_bytes(unsafe { slice::from_raw_parts(v.data, v.len() as usize) }))
+}
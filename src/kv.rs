@@ -0,0 +1,721 @@
+//! Key-value store in a shared memory zone.
+//!
+//! [`SharedKvZone`] is a string to string map shared between the worker processes, with optional
+//! per-key expiration and eviction of the least recently used entries when the zone is full. It is
+//! registered in a directive handler, and accessed either directly or via the HTTP variables
+//! added with [`add_variable`]: reading the variable returns the value of the key, assigning to it
+//! with `set` stores the value, and assigning to it in a `DELETE` request removes the key.
+//!
+//! ```no_run
+//! # use core::time::Duration;
+//! # use ngx::core::Status;
+//! # use ngx::ffi::{ngx_conf_t, ngx_module_t, ngx_str_t};
+//! # use ngx::kv::SharedKvZone;
+//! # fn f(cf: &mut ngx_conf_t, name: &ngx_str_t, module: &ngx_module_t) -> Result<(), Status> {
+//! // in a directive handler
+//! let zone = SharedKvZone::add(cf, name, 1024 * 1024, module)?;
+//!
+//! // in a request handler
+//! zone.set(b"session", b"data", Some(Duration::from_secs(60))).ok();
+//! let len = zone.get_with(b"session", |value| value.as_bytes().len());
+//! # Ok(()) }
+//! ```
+use core::alloc::Layout;
+use core::fmt;
+use core::mem;
+use core::ptr::NonNull;
+use core::time::Duration;
+
+use nginx_sys::{
+    ngx_queue_data, ngx_queue_empty, ngx_queue_init, ngx_queue_insert_after, ngx_queue_remove,
+    ngx_queue_t,
+};
+
+use crate::allocator::{self, AllocError, Allocator};
+use crate::collections::RbTreeMap;
+use crate::collections::expiring::deadline;
+use crate::core::{NgxStr, NgxString, SharedZone, SlabPool, Status, time};
+use crate::ffi::{ngx_conf_t, ngx_module_t, ngx_str_t, time_t};
+use crate::sync::RwLock;
+
+/// Errors of the [`SharedKvZone`] operations.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KvError {
+    /// The zone is not mapped yet, i.e. the store is accessed during the configuration parsing.
+    NotMapped,
+    /// The entry does not fit into the zone even after the eviction of all the other entries.
+    NoMemory,
+}
+
+impl fmt::Display for KvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KvError::NotMapped => f.write_str("zone is not mapped"),
+            KvError::NoMemory => f.write_str("no memory in zone"),
+        }
+    }
+}
+
+impl core::error::Error for KvError {}
+
+impl From<AllocError> for KvError {
+    fn from(_: AllocError) -> Self {
+        KvError::NoMemory
+    }
+}
+
+/// Stored value.
+#[derive(Debug)]
+struct Entry<A>
+where
+    A: Allocator + Clone,
+{
+    /// Link in the LRU queue of the store.
+    link: ngx_queue_t,
+    value: NgxString<A>,
+    /// Expiration time, or 0 if the entry does not expire.
+    deadline: time_t,
+}
+
+impl<A> Entry<A>
+where
+    A: Allocator + Clone,
+{
+    fn is_live(&self, now: time_t) -> bool {
+        self.deadline == 0 || self.deadline > now
+    }
+
+    /// Returns the remaining time to live, or `Some(None)` if the entry does not expire.
+    fn ttl(&self, now: time_t) -> Option<Option<Duration>> {
+        if !self.is_live(now) {
+            return None;
+        }
+        Some((self.deadline != 0).then(|| Duration::from_secs((self.deadline - now) as u64)))
+    }
+}
+
+// SAFETY: the link is embedded into the entry.
+unsafe impl<A> LruNode for Entry<A>
+where
+    A: Allocator + Clone,
+{
+    fn link(&mut self) -> &mut ngx_queue_t {
+        &mut self.link
+    }
+
+    unsafe fn from_link(link: *mut ngx_queue_t) -> NonNull<Self> {
+        // SAFETY: the link is embedded into an entry, as required by the caller.
+        unsafe { NonNull::new_unchecked(ngx_queue_data!(link, Self, link)) }
+    }
+}
+
+/// Element of an [`LruList`].
+///
+/// # Safety
+///
+/// The link must be embedded into the element, and [`Self::from_link`] must return the element
+/// embedding the link.
+unsafe trait LruNode {
+    /// Returns the link of the element.
+    fn link(&mut self) -> &mut ngx_queue_t;
+
+    /// Returns the element embedding the link.
+    ///
+    /// # Safety
+    ///
+    /// The link must be embedded into an element of this type.
+    unsafe fn from_link(link: *mut ngx_queue_t) -> NonNull<Self>;
+}
+
+/// Queue of the elements in the order of use, with the most recently used element first.
+///
+/// The queue does not own the elements: an element must be removed from the queue before it is
+/// moved or dropped.
+#[derive(Debug)]
+struct LruList {
+    /// Allocated separately, as the links of the elements must stay valid when the owner of the
+    /// queue is moved.
+    head: NonNull<ngx_queue_t>,
+}
+
+impl LruList {
+    /// Allocates an empty queue. The head must be released with [`Self::deallocate`].
+    fn try_new_in<A: Allocator>(alloc: &A) -> Result<Self, AllocError> {
+        // SAFETY: a zeroed queue head is valid and is initialized below.
+        let head = allocator::allocate(unsafe { mem::zeroed::<ngx_queue_t>() }, alloc)?;
+        unsafe { ngx_queue_init(head.as_ptr()) };
+        Ok(Self { head })
+    }
+
+    /// Releases the head of the queue.
+    ///
+    /// # Safety
+    ///
+    /// The queue must be allocated with `alloc` and must not be used afterwards.
+    unsafe fn deallocate<A: Allocator>(&mut self, alloc: &A) {
+        unsafe { alloc.deallocate(self.head.cast(), Layout::new::<ngx_queue_t>()) };
+    }
+
+    /// Unlinks all the elements.
+    fn clear(&mut self) {
+        // SAFETY: the head is owned by the queue.
+        unsafe { ngx_queue_init(self.head.as_ptr()) };
+    }
+
+    /// Inserts the element as the most recently used one.
+    ///
+    /// # Safety
+    ///
+    /// The element must not be linked into a queue, and must stay at the same address until it is
+    /// removed.
+    unsafe fn push<T: LruNode>(&mut self, node: &mut T) {
+        unsafe { ngx_queue_insert_after(self.head.as_ptr(), node.link()) };
+    }
+
+    /// Marks the element as the most recently used one.
+    ///
+    /// # Safety
+    ///
+    /// The element must be linked into this queue.
+    unsafe fn touch<T: LruNode>(&mut self, node: &mut T) {
+        unsafe {
+            ngx_queue_remove(node.link());
+            ngx_queue_insert_after(self.head.as_ptr(), node.link());
+        }
+    }
+
+    /// Unlinks the element.
+    ///
+    /// # Safety
+    ///
+    /// The element must be linked into this queue.
+    unsafe fn remove<T: LruNode>(&mut self, node: &mut T) {
+        unsafe { ngx_queue_remove(node.link()) };
+    }
+
+    /// Unlinks and returns the least recently used element.
+    ///
+    /// # Safety
+    ///
+    /// All the elements linked into the queue must be of type `T`.
+    unsafe fn pop_last<T: LruNode>(&mut self) -> Option<NonNull<T>> {
+        let head = self.head.as_ptr();
+        // SAFETY: the head is owned by the queue and the queue links the elements of type `T`.
+        unsafe {
+            if ngx_queue_empty(head) {
+                return None;
+            }
+            let link = (*head).prev;
+            ngx_queue_remove(link);
+            Some(T::from_link(link))
+        }
+    }
+}
+
+/// String to string map allocated from the slab pool of a shared zone.
+///
+/// The entries are kept in the order of use, so that the least recently used one is evicted when
+/// the zone is full. The store is usually accessed via [`SharedKvZone`], which takes the zone
+/// lock.
+#[derive(Debug)]
+pub struct KvStore<A = SlabPool>
+where
+    A: Allocator + Clone,
+{
+    map: RbTreeMap<NgxString<A>, Entry<A>, A>,
+    /// Queue linking the stored entries.
+    lru: LruList,
+}
+
+// SAFETY: the queue links only point to the queue head and the entries owned by the store.
+unsafe impl<A> Send for KvStore<A> where A: Allocator + Clone + Send {}
+// SAFETY: the queue links are only updated via `&mut KvStore`.
+unsafe impl<A> Sync for KvStore<A> where A: Allocator + Clone + Sync {}
+
+impl<A> KvStore<A>
+where
+    A: Allocator + Clone,
+{
+    /// Attempts to create an empty store in the allocator, usually the slab pool of a zone.
+    pub fn try_new_in(alloc: A) -> Result<Self, AllocError> {
+        let map = RbTreeMap::try_new_in(alloc)?;
+        let lru = LruList::try_new_in(map.allocator())?;
+        Ok(Self { map, lru })
+    }
+
+    /// Returns true if the store contains no entries, including the expired ones.
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// Returns the value of the key, if not expired, and marks the entry as recently used.
+    ///
+    /// The expired entry is removed.
+    pub fn get(&mut self, key: &[u8]) -> Option<&NgxStr> {
+        if !self.map.get(key)?.is_live(time()) {
+            self.remove(key);
+            return None;
+        }
+
+        let entry = self.map.get_mut(key)?;
+        // SAFETY: the stored entries are linked into the queue.
+        unsafe { self.lru.touch(entry) };
+        Some(&*entry.value)
+    }
+
+    /// Returns the remaining time to live of the key, or `Some(None)` if the entry does not
+    /// expire.
+    pub fn ttl(&self, key: &[u8]) -> Option<Option<Duration>> {
+        self.map.get(key)?.ttl(time())
+    }
+
+    /// Returns an iterator over the entries that are not expired, in an unspecified order.
+    pub fn iter(&self) -> impl Iterator<Item = (&NgxStr, &NgxStr)> + '_ {
+        let now = time();
+        self.map
+            .iter()
+            .filter(move |(_, entry)| entry.is_live(now))
+            .map(|(key, entry)| (&**key, &*entry.value))
+    }
+
+    /// Stores the value of the key, expiring after `ttl` if specified.
+    ///
+    /// The time to live is rounded up to whole seconds. If the zone is full, the least recently
+    /// used entries are evicted one by one until the entry fits.
+    pub fn set(
+        &mut self,
+        key: &[u8],
+        value: &[u8],
+        ttl: Option<Duration>,
+    ) -> Result<(), AllocError> {
+        let deadline = ttl.map_or(0, |ttl| deadline(time(), ttl));
+        loop {
+            match self.insert(key, value, deadline) {
+                Ok(()) => return Ok(()),
+                Err(AllocError) if self.evict() => {}
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    /// Stores the value of the key like [`Self::set`], but fails instead of evicting the other
+    /// entries if the zone is full, e.g. when loading the saved entries.
+    pub fn try_set(
+        &mut self,
+        key: &[u8],
+        value: &[u8],
+        ttl: Option<Duration>,
+    ) -> Result<(), AllocError> {
+        let deadline = ttl.map_or(0, |ttl| deadline(time(), ttl));
+        self.insert(key, value, deadline)
+    }
+
+    fn insert(&mut self, key: &[u8], value: &[u8], deadline: time_t) -> Result<(), AllocError> {
+        let alloc = self.map.allocator().clone();
+        let value = NgxString::try_from_bytes_in(value, alloc.clone()).map_err(|_| AllocError)?;
+
+        if let Some(entry) = self.map.get_mut(key) {
+            entry.value = value;
+            entry.deadline = deadline;
+            // SAFETY: the stored entries are linked into the queue.
+            unsafe { self.lru.touch(entry) };
+            return Ok(());
+        }
+
+        let key = NgxString::try_from_bytes_in(key, alloc).map_err(|_| AllocError)?;
+        // SAFETY: a zeroed link is valid and is initialized below.
+        let entry = Entry { link: unsafe { mem::zeroed() }, value, deadline };
+        let entry = self.map.try_insert(key, entry)?;
+        // SAFETY: the entry stays at the same address until it is removed from the map.
+        unsafe { self.lru.push(entry) };
+        Ok(())
+    }
+
+    /// Removes the key, returning true if it was stored and not expired.
+    pub fn delete(&mut self, key: &[u8]) -> bool {
+        self.remove(key).is_some_and(|entry| entry.is_live(time()))
+    }
+
+    fn remove(&mut self, key: &[u8]) -> Option<Entry<A>> {
+        let entry = self.map.get_mut(key)?;
+        // SAFETY: the stored entries are linked into the queue.
+        unsafe { self.lru.remove(entry) };
+        self.map.remove(key)
+    }
+
+    /// Removes all the entries.
+    pub fn clear(&mut self) {
+        self.map.clear();
+        self.lru.clear();
+    }
+
+    /// Removes the expired entries, returning the number of removed entries.
+    pub fn sweep(&mut self) -> usize {
+        let now = time();
+        let mut removed = 0;
+        self.map.retain(|_, entry| {
+            if entry.is_live(now) {
+                return true;
+            }
+            // SAFETY: the stored entries are linked into the queue.
+            unsafe { self.lru.remove(entry) };
+            removed += 1;
+            false
+        });
+        removed
+    }
+
+    /// Removes the least recently used entry to free space in the zone, returning false if the
+    /// store is empty.
+    pub fn evict(&mut self) -> bool {
+        // SAFETY: the queue links the stored entries.
+        let Some(entry) = (unsafe { self.lru.pop_last::<Entry<A>>() }) else {
+            return false;
+        };
+        // SAFETY: the entry is stored in the map.
+        unsafe { self.map.remove_value(entry) };
+        true
+    }
+}
+
+impl<A> Drop for KvStore<A>
+where
+    A: Allocator + Clone,
+{
+    fn drop(&mut self) {
+        // SAFETY: the queue is allocated in `try_new_in`; the entries are dropped with the map and do
+        // not touch the queue.
+        unsafe { self.lru.deallocate(self.map.allocator()) };
+    }
+}
+
+/// Key-value store shared between the worker processes.
+pub type SharedKv = RwLock<KvStore>;
+
+/// Shared memory zone with a [`KvStore`].
+///
+/// The store is kept across configuration reloads if the zone is reused with the same name and
+/// size.
+#[derive(Clone, Copy, Debug)]
+pub struct SharedKvZone(SharedZone<SharedKv>);
+
+impl SharedKvZone {
+    /// Registers a zone `name` of `size` bytes owned by `module`.
+    ///
+    /// Must be called from a configuration directive handler.
+    pub fn add(
+        cf: &mut ngx_conf_t,
+        name: &ngx_str_t,
+        size: usize,
+        module: &ngx_module_t,
+    ) -> Result<Self, Status> {
+        Self::add_with(cf, name, size, module, |_| {})
+    }
+
+    /// Registers a zone, calling `init` with the new empty store once the zone is mapped, e.g. to
+    /// load the saved entries.
+    ///
+    /// `init` is not called if the zone keeps the store of the previous configuration.
+    pub fn add_with<F>(
+        cf: &mut ngx_conf_t,
+        name: &ngx_str_t,
+        size: usize,
+        module: &ngx_module_t,
+        init: F,
+    ) -> Result<Self, Status>
+    where
+        F: Fn(&mut KvStore) + 'static,
+    {
+        let zone = SharedZone::add(cf, name, size, module, move |alloc| {
+            let mut store = KvStore::try_new_in(alloc)?;
+            init(&mut store);
+            Ok(RwLock::new(store))
+        })?;
+        Ok(Self(zone))
+    }
+
+    /// Returns the zone name.
+    pub fn name(&self) -> &NgxStr {
+        self.0.name()
+    }
+
+    /// Returns the underlying shared zone.
+    pub fn zone(&self) -> &SharedZone<SharedKv> {
+        &self.0
+    }
+
+    /// Returns the locked store, for the operations not covered by the methods below, e.g.
+    /// iteration.
+    pub fn store(&self) -> Result<&SharedKv, KvError> {
+        self.0.get().ok_or(KvError::NotMapped)
+    }
+
+    /// Calls `f` with the value of the key.
+    ///
+    /// Takes the write lock, as the entry is moved to the head of the LRU queue. Returns `None` if
+    /// the key is not found or expired, or if the zone is not mapped.
+    pub fn get_with<R>(&self, key: &[u8], f: impl FnOnce(&NgxStr) -> R) -> Option<R> {
+        let mut store = self.store().ok()?.write();
+        store.get(key).map(f)
+    }
+
+    /// Stores the value of the key, expiring after `ttl` if specified.
+    ///
+    /// See [`KvStore::set`].
+    pub fn set(&self, key: &[u8], value: &[u8], ttl: Option<Duration>) -> Result<(), KvError> {
+        Ok(self.store()?.write().set(key, value, ttl)?)
+    }
+
+    /// Removes the key, returning true if it was stored and not expired.
+    pub fn delete(&self, key: &[u8]) -> Result<bool, KvError> {
+        Ok(self.store()?.write().delete(key))
+    }
+
+    /// Removes all the entries.
+    pub fn clear(&self) -> Result<(), KvError> {
+        self.store()?.write().clear();
+        Ok(())
+    }
+
+    /// Removes the expired entries, e.g. from a [`Timer`](crate::core::Timer), returning the number
+    /// of removed entries.
+    pub fn sweep(&self) -> Result<usize, KvError> {
+        Ok(self.store()?.write().sweep())
+    }
+}
+
+#[cfg(ngx_feature = "http")]
+pub use self::http::add_variable;
+
+#[cfg(ngx_feature = "http")]
+mod http {
+    use core::time::Duration;
+
+    use super::SharedKvZone;
+    use crate::core::{Pool, Status};
+    use crate::ffi::{
+        NGX_HTTP_VAR_CHANGEABLE, NGX_HTTP_VAR_NOCACHEABLE, NGX_LOG_EMERG, NGX_LOG_ERR, ngx_conf_t,
+        ngx_http_add_variable, ngx_http_request_t, ngx_int_t, ngx_str_t, ngx_uint_t,
+        ngx_variable_value_t,
+    };
+    use crate::http::{ComplexValue, Method, Request};
+    use crate::{ngx_conf_log_error, ngx_log_debug, ngx_log_error};
+
+    struct KvVariable {
+        zone: SharedKvZone,
+        key: ComplexValue,
+        ttl: Option<Duration>,
+    }
+
+    /// Adds the HTTP variable `name`, with the `$` prefix, bound to the key of the store.
+    ///
+    /// The `key` may contain variables and is evaluated on each access. Reading the variable
+    /// returns the value of the key, or the variable is not found if the key is not stored.
+    /// Assigning to the variable with the `set` directive stores the value with the specified
+    /// `ttl`, or removes the key if the request method is `DELETE`.
+    ///
+    /// Must be called from a configuration directive handler.
+    ///
+    /// ```no_run
+    /// # use ngx::core::Status;
+    /// # use ngx::ffi::{ngx_conf_t, ngx_str_t};
+    /// # use ngx::http::ComplexValue;
+    /// # use ngx::kv::{SharedKvZone, add_variable};
+    /// # fn f(cf: &mut ngx_conf_t, zone: SharedKvZone, args: &[ngx_str_t]) -> Result<(), Status> {
+    /// // kv $arg_key $kv_value;
+    /// let key = ComplexValue::compile(cf, &args[1])?;
+    /// add_variable(cf, zone, key, &args[2], None)?;
+    /// # Ok(()) }
+    /// ```
+    pub fn add_variable(
+        cf: &mut ngx_conf_t,
+        zone: SharedKvZone,
+        key: ComplexValue,
+        name: &ngx_str_t,
+        ttl: Option<Duration>,
+    ) -> Result<(), Status> {
+        let Some(stripped) = name.as_bytes().strip_prefix(b"$") else {
+            ngx_conf_log_error!(NGX_LOG_EMERG, cf, "invalid variable name \"{name}\"");
+            return Err(Status::NGX_ERROR);
+        };
+        let mut name = ngx_str_t { data: stripped.as_ptr().cast_mut(), len: stripped.len() };
+
+        let flags = NGX_HTTP_VAR_CHANGEABLE | NGX_HTTP_VAR_NOCACHEABLE;
+        // SAFETY: the configuration is valid in a directive handler.
+        let var = unsafe { ngx_http_add_variable(cf, &raw mut name, flags as ngx_uint_t) };
+        let Some(var) = (unsafe { var.as_mut() }) else {
+            return Err(Status::NGX_ERROR);
+        };
+
+        // SAFETY: the configuration pool outlives the variables.
+        let pool = unsafe { Pool::from_ngx_pool(cf.pool) };
+        let data = pool.allocate(KvVariable { zone, key, ttl });
+        if data.is_null() {
+            return Err(Status::NGX_ERROR);
+        }
+
+        var.get_handler = Some(kv_get_variable);
+        var.set_handler = Some(kv_set_variable);
+        var.data = data as usize;
+        Ok(())
+    }
+
+    unsafe extern "C" fn kv_get_variable(
+        r: *mut ngx_http_request_t,
+        v: *mut ngx_variable_value_t,
+        data: usize,
+    ) -> ngx_int_t {
+        // SAFETY: nginx calls the handler with a valid request and the data set by `add_variable`.
+        let r = unsafe { Request::from_ngx_http_request(r) };
+        let v = unsafe { &mut *v };
+        let var = unsafe { &*(data as *const KvVariable) };
+
+        let Some(key) = var.key.evaluate(r) else {
+            return Status::NGX_ERROR.into();
+        };
+
+        let pool = r.as_ref().pool;
+        // SAFETY: the request pool is valid for the lifetime of the request.
+        let value = var.zone.get_with(key.as_bytes(), |value| unsafe {
+            ngx_str_t::from_bytes(pool, value.as_bytes())
+        });
+
+        ngx_log_debug!(
+            r.log(),
+            "kv: get \"{}\" in \"{}\" -> {}",
+            key,
+            var.zone.name(),
+            value.is_some()
+        );
+
+        match value {
+            Some(Some(value)) => {
+                v.data = value.data;
+                v.set_len(value.len as _);
+                v.set_valid(1);
+                v.set_no_cacheable(0);
+                v.set_not_found(0);
+            }
+            Some(None) => return Status::NGX_ERROR.into(),
+            None => v.set_not_found(1),
+        }
+
+        Status::NGX_OK.into()
+    }
+
+    unsafe extern "C" fn kv_set_variable(
+        r: *mut ngx_http_request_t,
+        v: *mut ngx_variable_value_t,
+        data: usize,
+    ) {
+        // SAFETY: nginx calls the handler with a valid request and the data set by `add_variable`.
+        let r = unsafe { Request::from_ngx_http_request(r) };
+        let v = unsafe { &*v };
+        let var = unsafe { &*(data as *const KvVariable) };
+
+        let Some(key) = var.key.evaluate(r) else {
+            return;
+        };
+
+        let rc = if r.method() == Method::DELETE {
+            ngx_log_debug!(r.log(), "kv: delete \"{}\" in \"{}\"", key, var.zone.name());
+            var.zone.delete(key.as_bytes()).map(|_| ())
+        } else {
+            ngx_log_debug!(r.log(), "kv: set \"{}\" in \"{}\"", key, var.zone.name());
+            var.zone.set(key.as_bytes(), v.as_bytes(), var.ttl)
+        };
+
+        if let Err(err) = rc {
+            ngx_log_error!(NGX_LOG_ERR, r.log(), "kv: \"{}\": {}", var.zone.name(), err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use std::boxed::Box;
+    use std::vec::Vec;
+
+    use super::*;
+    use crate::allocator::Global;
+
+    struct Node {
+        link: ngx_queue_t,
+        id: usize,
+    }
+
+    unsafe impl LruNode for Node {
+        fn link(&mut self) -> &mut ngx_queue_t {
+            &mut self.link
+        }
+
+        unsafe fn from_link(link: *mut ngx_queue_t) -> NonNull<Self> {
+            unsafe { NonNull::new_unchecked(ngx_queue_data!(link, Self, link)) }
+        }
+    }
+
+    fn entry(deadline: time_t) -> Entry<Global> {
+        // SAFETY: the link is not used.
+        Entry { link: unsafe { mem::zeroed() }, value: NgxString::new_in(Global), deadline }
+    }
+
+    #[test]
+    fn test_entry_ttl() {
+        assert_eq!(entry(0).ttl(1000), Some(None));
+        assert_eq!(entry(1010).ttl(1000), Some(Some(Duration::from_secs(10))));
+        assert_eq!(entry(1001).ttl(1000), Some(Some(Duration::from_secs(1))));
+        assert_eq!(entry(1000).ttl(1000), None);
+        assert_eq!(entry(999).ttl(1000), None);
+
+        // the time to live is rounded up to whole seconds
+        let deadline = deadline(1000, Duration::from_millis(1500));
+        assert_eq!(entry(deadline).ttl(1000), Some(Some(Duration::from_secs(2))));
+        assert!(entry(deadline).is_live(1001));
+        assert!(!entry(deadline).is_live(1002));
+    }
+
+    #[test]
+    fn test_eviction_order() {
+        let mut lru = LruList::try_new_in(&Global).unwrap();
+        let mut nodes: Vec<_> = (0..5)
+            // SAFETY: the link is initialized when the node is pushed.
+            .map(|id| Box::new(Node { link: unsafe { mem::zeroed() }, id }))
+            .collect();
+
+        let evict = |lru: &mut LruList| {
+            // SAFETY: the queue links the nodes only.
+            unsafe { lru.pop_last::<Node>() }.map(|node| unsafe { node.as_ref() }.id)
+        };
+
+        assert_eq!(evict(&mut lru), None);
+
+        // SAFETY: the boxed nodes stay at the same address and outlive the queue.
+        unsafe {
+            for node in nodes.iter_mut() {
+                lru.push(&mut **node);
+            }
+            lru.touch(&mut *nodes[0]);
+            lru.remove(&mut *nodes[2]);
+        }
+
+        assert_eq!(evict(&mut lru), Some(1));
+
+        // a touched node becomes the most recently used one
+        unsafe { lru.touch(&mut *nodes[3]) };
+        assert_eq!(evict(&mut lru), Some(4));
+        assert_eq!(evict(&mut lru), Some(0));
+
+        unsafe { lru.push(&mut *nodes[2]) };
+        assert_eq!(evict(&mut lru), Some(3));
+        assert_eq!(evict(&mut lru), Some(2));
+        assert_eq!(evict(&mut lru), None);
+
+        unsafe { lru.push(&mut *nodes[1]) };
+        lru.clear();
+        assert_eq!(evict(&mut lru), None);
+
+        unsafe { lru.deallocate(&Global) };
+    }
+}
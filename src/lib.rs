@@ -70,10 +70,25 @@
 //! - `alloc` - **Enabled** by default. This provides APIs that require allocations
 //!   via the `alloc` crate.
 //! - `async` - Enables a minimal async runtime built on top of the NGINX event loop.
+//! - `cbor` - Enables [`core::cbor`], a compact binary encoding for the structured values
+//!   stored in shared memory.
+//! - `feature-stubs` - Provides stub implementations for some of the APIs depending on
+//!   optional NGINX features (`ssl`, `http_ssl`, `http_v2`, `quic`, `threads`), so that the
+//!   same module source can be built against NGINX configurations with and without these
+//!   features. The stubs fail with [`core::FeatureDisabled`].
+//! - `handler-trace` - Logs every invocation of the phase handlers registered with
+//!   [`http::add_phase_handler`], with the phase, the return code and the execution time,
+//!   at the `debug` level. Does not require an NGINX build with `--with-debug`.
 //! - `serde` - Enables serialization support for some of the provided and
 //!   re-exported types.
+//! - `stream` - Enables [`stream`], the APIs for the stream (TCP/UDP) modules. Requires an
+//!   NGINX build with the stream module.
 //! - `std` - **Enabled** by default. This provides APIs that require the standard
 //!   library.
+//! - `task-registry` - Records the live tasks of the async runtime and the requests they
+//!   were spawned for, to diagnose leaked futures. See [`async_::live_tasks`].
+//! - `testing` - Replaces the cached NGINX time and the event loop timers with a mock
+//!   clock controlled by the unit tests. See [`testing`].
 //! - `vendored`: Enables the build scripts to build a copy of nginx source and link
 //!   against it. See the [nginx-src] crate documentation for additional details.
 //!
@@ -120,6 +135,8 @@
 #![no_std]
 #[cfg(feature = "alloc")]
 extern crate alloc;
+#[cfg(feature = "std")]
+extern crate std;
 
 pub mod allocator;
 #[cfg(feature = "async")]
@@ -144,13 +161,36 @@ pub mod ffi;
 /// configuration access, and statuses.
 #[cfg(ngx_feature = "http")]
 pub mod http;
+#[cfg(feature = "alloc")]
+pub mod kv;
 
 /// The log module.
 ///
 /// This module provides an interface into the NGINX logger framework.
 pub mod log;
 
+pub mod parse;
+pub mod prelude;
+#[cfg(ngx_feature = "pcre2")]
+pub mod regex;
+
+/// The ssl module.
+///
+/// This module provides information about the TLS connections, such as the negotiated protocol
+/// and the peer certificate.
+#[cfg(ngx_feature = "ssl")]
+pub mod ssl;
+
+/// The stream module.
+///
+/// This module provides wrappers and utilities to NGINX stream (TCP/UDP) APIs, such as sessions,
+/// configuration access, and phase handlers.
+#[cfg(all(feature = "stream", ngx_feature = "stream"))]
+pub mod stream;
 pub mod sync;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+pub mod time;
 
 /// Define modules exported by this library.
 ///
@@ -153,7 +153,7 @@ macro_rules! ngx_log_debug {
 #[macro_export]
 macro_rules! ngx_log_debug_http {
     ( $request:expr, $($arg:tt)+ ) => {
-        let log = unsafe { (*$request.connection()).log };
+        let log = $request.log();
         $crate::ngx_log_debug!(mask: $crate::log::DebugMask::Http, log, $($arg)+);
     }
 }
@@ -191,6 +191,49 @@ macro_rules! ngx_log_debug_mask {
     };
 }
 
+/// Write a message with key-value pairs to logger at a specified level.
+///
+/// The pairs are appended to the message in the `logfmt` style: `message key=value key="a value"`.
+/// The values are formatted with [`fmt::Display`], and are quoted and escaped when they contain
+/// spaces, quotes, `=` or control characters. With the `json:` prefix the message is written as a
+/// JSON object instead: `{"msg":"message","key":"value"}`, with all the values as strings.
+///
+/// The output is truncated to [`LOG_BUFFER_SIZE`] bytes.
+///
+/// ```no_run
+/// # use ngx::ffi::{NGX_LOG_INFO, ngx_log_t};
+/// # fn f(log: *mut ngx_log_t, status: u16, uri: &str) {
+/// ngx::ngx_log_kv!(log, NGX_LOG_INFO, "request done", status = status, uri = uri);
+/// ngx::ngx_log_kv!(json: log, NGX_LOG_INFO, "request done", status = status, uri = uri);
+/// # }
+/// ```
+#[macro_export]
+macro_rules! ngx_log_kv {
+    ( @format $format:expr, $log:expr, $level:expr, $msg:literal $(, $key:ident = $value:expr)* ) => {
+        let log = $log;
+        let level = $level as $crate::ffi::ngx_uint_t;
+        if level <= unsafe { (*log).log_level } {
+            let mut buf =
+                [const { ::core::mem::MaybeUninit::<u8>::uninit() }; $crate::log::LOG_BUFFER_SIZE];
+            #[allow(unused_mut)]
+            let mut writer = $crate::log::KvWriter::new(&mut buf, $format, format_args!($msg));
+            $( writer.pair(stringify!($key), &$value); )*
+            let message = writer.finish();
+            unsafe { $crate::log::log_error(level, log, 0, message) };
+        }
+    };
+    ( json: $log:expr, $level:expr, $msg:literal $(, $key:ident = $value:expr)* $(,)? ) => {
+        $crate::ngx_log_kv!(
+            @format $crate::log::KvFormat::Json, $log, $level, $msg $(, $key = $value)*
+        );
+    };
+    ( $log:expr, $level:expr, $msg:literal $(, $key:ident = $value:expr)* $(,)? ) => {
+        $crate::ngx_log_kv!(
+            @format $crate::log::KvFormat::Logfmt, $log, $level, $msg $(, $key = $value)*
+        );
+    };
+}
+
 /// Debug masks for use with [`ngx_log_debug_mask`], these represent the only accepted values for
 /// the mask.
 #[derive(Debug)]
@@ -246,10 +289,167 @@ impl From<DebugMask> for u32 {
     }
 }
 
+/// Output format of [`ngx_log_kv`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KvFormat {
+    /// `message key=value key="a value"`
+    Logfmt,
+    /// `{"msg":"message","key":"value"}`
+    Json,
+}
+
+/// Formats a message with key-value pairs into a provided buffer, see [`ngx_log_kv`].
+///
+/// The JSON output stays valid when truncated: the last string is closed, and a pair that does not
+/// fit up to the start of the value is dropped.
+pub struct KvWriter<'a> {
+    buf: LogBuf<'a>,
+    format: KvFormat,
+    /// The JSON output is truncated and the following pairs are dropped.
+    truncated: bool,
+    /// The truncated JSON output ends inside a string.
+    open: bool,
+}
+
+impl<'a> KvWriter<'a> {
+    /// Room for the end of the truncated JSON object, `"}`.
+    const JSON_RESERVE: usize = 2;
+
+    /// Creates a writer and formats the message.
+    pub fn new(buf: &'a mut [MaybeUninit<u8>], format: KvFormat, msg: fmt::Arguments<'_>) -> Self {
+        let mut this = Self { buf: LogBuf::from(buf), format, truncated: false, open: false };
+        match format {
+            KvFormat::Logfmt => {
+                let _ = this.buf.write_fmt(msg);
+            }
+            KvFormat::Json => {
+                this.buf.reserve(Self::JSON_RESERVE);
+                if !this.buf.try_append(b"{\"msg\":\"")
+                    || JsonEscape(&mut this.buf).write_fmt(msg).is_err()
+                    || !this.buf.try_append(b"\"")
+                {
+                    this.truncated = true;
+                    this.open = true;
+                }
+            }
+        }
+        this
+    }
+
+    /// Appends a key-value pair.
+    pub fn pair(&mut self, key: &str, value: &dyn fmt::Display) -> &mut Self {
+        match self.format {
+            KvFormat::Logfmt => {
+                self.buf.append(b" ").append(key.as_bytes()).append(b"=\"");
+
+                // The value is written once, and the opening quote is removed if not needed.
+                let start = self.buf.filled;
+                let mut escape = LogfmtEscape { buf: &mut self.buf, quote: false };
+                let _ = write!(escape, "{value}");
+                let quote = escape.quote;
+                if quote || self.buf.filled == start {
+                    self.buf.append(b"\"");
+                } else {
+                    self.buf.remove(start - 1);
+                }
+            }
+            KvFormat::Json if self.truncated => {}
+            KvFormat::Json => {
+                let start = self.buf.filled;
+                if !self.buf.try_append(b",\"")
+                    || JsonEscape(&mut self.buf).write_str(key).is_err()
+                    || !self.buf.try_append(b"\":\"")
+                {
+                    self.buf.truncate(start);
+                    self.truncated = true;
+                    return self;
+                }
+
+                if write!(JsonEscape(&mut self.buf), "{value}").is_err()
+                    || !self.buf.try_append(b"\"")
+                {
+                    self.truncated = true;
+                    self.open = true;
+                }
+            }
+        }
+        self
+    }
+
+    /// Completes the output and returns the formatted bytes.
+    pub fn finish(mut self) -> &'a [u8] {
+        if self.format == KvFormat::Json {
+            self.buf.reserve(0);
+            if self.open {
+                self.buf.append(b"\"");
+            }
+            self.buf.append(b"}");
+        }
+        self.buf.filled()
+    }
+}
+
+/// Escapes a `logfmt` value and detects whether the value needs quotes.
+struct LogfmtEscape<'a, 'b> {
+    buf: &'a mut LogBuf<'b>,
+    quote: bool,
+}
+
+impl fmt::Write for LogfmtEscape<'_, '_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for c in s.chars() {
+            self.quote |= matches!(c, ' ' | '"' | '=' | '\\') || c.is_ascii_control();
+            match c {
+                '"' => self.buf.append(b"\\\""),
+                '\\' => self.buf.append(b"\\\\"),
+                '\n' => self.buf.append(b"\\n"),
+                c if c.is_ascii_control() => self.buf.append(b"\\x").append(&hex_byte(c as u8)),
+                c => self.buf.append(c.encode_utf8(&mut [0; 4]).as_bytes()),
+            };
+        }
+        Ok(())
+    }
+}
+
+/// Escapes a JSON string, failing instead of writing a partial escape sequence or character.
+struct JsonEscape<'a, 'b>(&'a mut LogBuf<'b>);
+
+impl fmt::Write for JsonEscape<'_, '_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for c in s.chars() {
+            let mut tmp = [0u8; 6];
+            let bytes: &[u8] = match c {
+                '"' => b"\\\"",
+                '\\' => b"\\\\",
+                '\n' => b"\\n",
+                '\r' => b"\\r",
+                '\t' => b"\\t",
+                c if (c as u32) < 0x20 => {
+                    let [hi, lo] = hex_byte(c as u8);
+                    tmp = [b'\\', b'u', b'0', b'0', hi, lo];
+                    &tmp
+                }
+                c => c.encode_utf8(&mut tmp).as_bytes(),
+            };
+            if !self.0.try_append(bytes) {
+                return Err(fmt::Error);
+            }
+        }
+        Ok(())
+    }
+}
+
+fn hex_byte(b: u8) -> [u8; 2] {
+    const HEX: &[u8; 16] = b"0123456789abcdef";
+    [HEX[(b >> 4) as usize], HEX[(b & 0xf) as usize]]
+}
+
 /// Minimal subset of unstable core::io::{BorrowedBuf,BorrowedCursor}
 struct LogBuf<'data> {
     buf: &'data mut [MaybeUninit<u8>],
     filled: usize,
+    /// End of the writable part of the buffer.
+    limit: usize,
 }
 
 impl<'data> LogBuf<'data> {
@@ -263,7 +463,7 @@ impl<'data> LogBuf<'data> {
     }
 
     pub fn append(&mut self, buf: &[u8]) -> &mut Self {
-        let n = cmp::min(self.buf.len() - self.filled, buf.len());
+        let n = cmp::min(self.limit.saturating_sub(self.filled), buf.len());
         unsafe {
             // SAFETY: The source buf has at least n bytes
             let src = buf.get_unchecked(..n);
@@ -275,11 +475,38 @@ impl<'data> LogBuf<'data> {
         self.filled += n;
         self
     }
+
+    /// Appends the whole `buf`, or nothing if it does not fit.
+    pub fn try_append(&mut self, buf: &[u8]) -> bool {
+        if self.limit.saturating_sub(self.filled) < buf.len() {
+            return false;
+        }
+        self.append(buf);
+        true
+    }
+
+    /// Keeps the last `n` bytes of the buffer for the final [`append`](Self::append), or makes
+    /// them available again with `n` equal to 0.
+    pub fn reserve(&mut self, n: usize) {
+        self.limit = self.buf.len().saturating_sub(n);
+    }
+
+    /// Removes the byte at `pos`, shifting the following bytes.
+    pub fn remove(&mut self, pos: usize) {
+        self.buf.copy_within(pos + 1..self.filled, pos);
+        self.filled -= 1;
+    }
+
+    /// Discards the bytes after `len`.
+    pub fn truncate(&mut self, len: usize) {
+        self.filled = cmp::min(self.filled, len);
+    }
 }
 
 impl<'data> From<&'data mut [MaybeUninit<u8>]> for LogBuf<'data> {
     fn from(buf: &'data mut [MaybeUninit<u8>]) -> Self {
-        Self { buf, filled: 0 }
+        let limit = buf.len();
+        Self { buf, filled: 0, limit }
     }
 }
 
@@ -292,6 +519,9 @@ impl fmt::Write for LogBuf<'_> {
 
 #[cfg(test)]
 mod tests {
+    extern crate std;
+
+    use std::string::String;
 
     use super::*;
 
@@ -319,6 +549,51 @@ mod tests {
         assert!(!r);
     }
 
+    #[test]
+    fn kv_writer() {
+        use core::str;
+
+        let mut buf = [const { MaybeUninit::<u8>::uninit() }; 128];
+        let mut w = KvWriter::new(&mut buf, KvFormat::Logfmt, format_args!("done"));
+        w.pair("status", &200).pair("uri", &"/a b").pair("empty", &"");
+        assert_eq!(str::from_utf8(w.finish()), Ok(r#"done status=200 uri="/a b" empty="""#));
+
+        let mut buf = [const { MaybeUninit::<u8>::uninit() }; 128];
+        let mut w = KvWriter::new(&mut buf, KvFormat::Json, format_args!("do\"ne"));
+        w.pair("status", &200).pair("uri", &"/a\nb");
+        assert_eq!(
+            str::from_utf8(w.finish()),
+            Ok(r#"{"msg":"do\"ne","status":"200","uri":"/a\nb"}"#)
+        );
+    }
+
+    #[test]
+    fn kv_writer_truncated() {
+        use core::str;
+
+        let json = |len: usize| {
+            let mut buf = [const { MaybeUninit::<u8>::uninit() }; 64];
+            let mut w = KvWriter::new(&mut buf[..len], KvFormat::Json, format_args!("done"));
+            w.pair("uri", &"/a\nb").pair("status", &200);
+            String::from_utf8(w.finish().to_vec()).unwrap()
+        };
+
+        assert_eq!(json(64), r#"{"msg":"done","uri":"/a\nb","status":"200"}"#);
+        // the value is cut before the escape sequence and the string is closed
+        assert_eq!(json(26), r#"{"msg":"done","uri":"/a"}"#);
+        assert_eq!(json(27), r#"{"msg":"done","uri":"/a\n"}"#);
+        assert_eq!(json(28), r#"{"msg":"done","uri":"/a\nb"}"#);
+        // the pair without the room for the value is dropped
+        assert_eq!(json(36), r#"{"msg":"done","uri":"/a\nb"}"#);
+        assert_eq!(json(10), r#"{"msg":""}"#);
+        assert_eq!(json(16), r#"{"msg":"done"}"#);
+
+        let mut buf = [const { MaybeUninit::<u8>::uninit() }; 32];
+        let mut w = KvWriter::new(&mut buf, KvFormat::Logfmt, format_args!("done"));
+        w.pair("a", &"b c").pair("d", &"e");
+        assert_eq!(str::from_utf8(w.finish()), Ok(r#"done a="b c" d=e"#));
+    }
+
     #[test]
     fn log_buffer() {
         use core::str;
@@ -0,0 +1,369 @@
+//! HTTP/1.1 chunked transfer coding.
+//!
+//! The decoder is a wrapper over the nginx chunked parser, `ngx_http_parse_chunked`, and requires
+//! nginx built with the HTTP module.
+//!
+//! See [RFC 9112, Section 7.1](https://datatracker.ietf.org/doc/html/rfc9112#section-7.1).
+use core::{cmp, error, fmt, ptr};
+
+use crate::allocator::AllocError;
+use crate::core::Pool;
+use crate::ffi::{ngx_alloc_chain_link, ngx_buf_t, ngx_chain_t, ngx_create_temp_buf};
+
+/// Maximum length of a chunk header produced by [`chunk_header`].
+pub const CHUNK_HEADER_MAX: usize = 2 * size_of::<u64>() + 2;
+
+/// Last chunk with an empty trailer section.
+pub const LAST_CHUNK: &[u8] = b"0\r\n\r\n";
+
+/// Errors returned by [`ChunkedDecoder`].
+#[derive(Debug, PartialEq, Eq)]
+pub enum ChunkedError {
+    /// Invalid chunk size or framing, as reported by the nginx parser.
+    Invalid,
+    /// Data after the last chunk.
+    ExtraData,
+    /// Memory allocation failed.
+    Alloc,
+}
+
+impl fmt::Display for ChunkedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChunkedError::Invalid => f.write_str("invalid chunked body"),
+            ChunkedError::ExtraData => f.write_str("unexpected data after the last chunk"),
+            ChunkedError::Alloc => f.write_str("allocation failed"),
+        }
+    }
+}
+
+impl error::Error for ChunkedError {}
+
+impl From<AllocError> for ChunkedError {
+    fn from(_: AllocError) -> Self {
+        ChunkedError::Alloc
+    }
+}
+
+#[cfg(ngx_feature = "http")]
+pub use self::decoder::ChunkedDecoder;
+
+#[cfg(ngx_feature = "http")]
+mod decoder {
+    use core::{cmp, fmt, mem, ptr, slice};
+
+    use super::{ChunkedError, append_buf, buf_in_memory};
+    use crate::ffi::{
+        NGX_AGAIN, NGX_DONE, NGX_OK, ngx_buf_t, ngx_chain_t, ngx_http_chunked_t,
+        ngx_http_parse_chunked, ngx_http_request_t, ngx_int_t,
+    };
+    use crate::http::Request;
+
+    /// Incremental decoder for the chunked transfer coding.
+    ///
+    /// The decoder keeps the `ngx_http_chunked_t` state of the nginx parser, so the input does not
+    /// need to be buffered and can be passed as it arrives. Chunk extensions and trailer fields are
+    /// skipped by the parser. The request is only used for logging.
+    #[derive(Clone)]
+    pub struct ChunkedDecoder {
+        ctx: ngx_http_chunked_t,
+        complete: bool,
+    }
+
+    impl Default for ChunkedDecoder {
+        fn default() -> Self {
+            // SAFETY: the parser state is a set of integers, starting with zeroes.
+            Self { ctx: unsafe { mem::zeroed() }, complete: false }
+        }
+    }
+
+    impl fmt::Debug for ChunkedDecoder {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.debug_struct("ChunkedDecoder")
+                .field("state", &self.ctx.state)
+                .field("size", &self.ctx.size)
+                .field("complete", &self.complete)
+                .finish()
+        }
+    }
+
+    impl ChunkedDecoder {
+        /// Creates a new decoder.
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        /// Returns `true` if the last chunk and the trailer section were processed.
+        pub fn is_complete(&self) -> bool {
+            self.complete
+        }
+
+        /// Returns the number of bytes left in the current chunk.
+        pub fn remaining(&self) -> u64 {
+            self.ctx.size.max(0) as u64
+        }
+
+        /// Returns the minimal number of bytes needed to complete the body, as estimated by the
+        /// parser.
+        pub fn length(&self) -> u64 {
+            self.ctx.length.max(0) as u64
+        }
+
+        /// Decodes a part of the chunked body, invoking `emit` for each fragment of chunk data.
+        ///
+        /// Returns the number of consumed bytes. The value is less than the length of `input`
+        /// only if the body was complete and `input` contains data after the last chunk.
+        pub fn decode<'a, F>(
+            &mut self,
+            r: &mut Request,
+            input: &'a [u8],
+            mut emit: F,
+        ) -> Result<usize, ChunkedError>
+        where
+            F: FnMut(&'a [u8]),
+        {
+            // SAFETY: a zeroed buffer is valid, and the parser only reads the data.
+            let mut b: ngx_buf_t = unsafe { mem::zeroed() };
+            b.start = input.as_ptr().cast_mut();
+            b.pos = b.start;
+            b.last = b.start.wrapping_add(input.len());
+            b.end = b.last;
+            b.set_memory(1);
+
+            let r: *mut ngx_http_request_t = r.into();
+
+            while !self.complete {
+                // SAFETY: the buffer points to `input`.
+                let rc = unsafe { ngx_http_parse_chunked(r, &mut b, &mut self.ctx) };
+
+                match rc {
+                    // The chunk data starts at `b.pos`.
+                    rc if rc == NGX_OK as ngx_int_t => {
+                        let pos = offset(&b, input);
+                        let n = cmp::min(self.remaining(), (input.len() - pos) as u64) as usize;
+                        emit(&input[pos..pos + n]);
+                        b.pos = b.pos.wrapping_add(n);
+                        self.ctx.size -= n as _;
+                    }
+                    rc if rc == NGX_DONE as ngx_int_t => self.complete = true,
+                    rc if rc == NGX_AGAIN as ngx_int_t => break,
+                    _ => return Err(ChunkedError::Invalid),
+                }
+            }
+
+            Ok(offset(&b, input))
+        }
+
+        /// Decodes the in-memory buffers of `input` into a new chain allocated from the request
+        /// pool.
+        ///
+        /// The buffers of the returned chain point to the chunk data in the input buffers, and
+        /// the last one has the `last_buf` flag set once the body is complete. Returns a null
+        /// pointer if there was no chunk data in `input`.
+        ///
+        /// # Safety
+        ///
+        /// `input` must be a null pointer or a valid chain. The input buffers must remain valid
+        /// while the returned chain is in use.
+        pub unsafe fn decode_chain(
+            &mut self,
+            r: &mut Request,
+            mut input: *const ngx_chain_t,
+        ) -> Result<*mut ngx_chain_t, ChunkedError> {
+            let pool = r.pool();
+            let mut out: *mut ngx_chain_t = ptr::null_mut();
+            let mut ll = &raw mut out;
+            let mut last: *mut ngx_buf_t = ptr::null_mut();
+
+            while let Some(cl) = unsafe { input.as_ref() } {
+                let buf = unsafe { &*cl.buf };
+                input = cl.next;
+
+                if !buf_in_memory(buf) || buf.last <= buf.pos {
+                    continue;
+                }
+
+                let len = unsafe { buf.last.offset_from(buf.pos) } as usize;
+                let data = unsafe { slice::from_raw_parts(buf.pos, len) };
+
+                let mut result = Ok(());
+                let n = self.decode(r, data, |data| {
+                    if result.is_ok() {
+                        result = unsafe { append_buf(&pool, &mut ll, data.as_ptr(), data.len()) }
+                            .map(|b| last = b);
+                    }
+                })?;
+                result?;
+
+                if n < data.len() {
+                    return Err(ChunkedError::ExtraData);
+                }
+            }
+
+            if self.complete {
+                if last.is_null() {
+                    last = unsafe { append_buf(&pool, &mut ll, ptr::null(), 0)? };
+                }
+                unsafe { (*last).set_last_buf(1) };
+            }
+
+            Ok(out)
+        }
+    }
+
+    /// Returns the position of the parser buffer within `input`.
+    fn offset(b: &ngx_buf_t, input: &[u8]) -> usize {
+        (b.pos as usize) - (input.as_ptr() as usize)
+    }
+}
+
+fn buf_in_memory(buf: &ngx_buf_t) -> bool {
+    buf.temporary() != 0 || buf.memory() != 0 || buf.mmap() != 0
+}
+
+fn buf_size(buf: &ngx_buf_t) -> u64 {
+    if buf_in_memory(buf) {
+        (buf.last as usize).wrapping_sub(buf.pos as usize) as u64
+    } else {
+        (buf.file_last - buf.file_pos) as u64
+    }
+}
+
+/// Allocates a memory buffer pointing to `len` bytes at `data` and appends it to the chain.
+unsafe fn append_buf(
+    pool: &Pool,
+    ll: &mut *mut *mut ngx_chain_t,
+    data: *const u8,
+    len: usize,
+) -> Result<*mut ngx_buf_t, AllocError> {
+    let b = pool.calloc_type::<ngx_buf_t>();
+    if b.is_null() {
+        return Err(AllocError);
+    }
+
+    unsafe {
+        let data = data.cast_mut();
+        (*b).start = data;
+        (*b).pos = data;
+        (*b).last = data.wrapping_add(len);
+        (*b).end = (*b).last;
+        (*b).set_memory(1);
+
+        append_link(pool, ll, b)?;
+    }
+
+    Ok(b)
+}
+
+/// Appends a new chain link with `buf` to the chain.
+unsafe fn append_link(
+    pool: &Pool,
+    ll: &mut *mut *mut ngx_chain_t,
+    buf: *mut ngx_buf_t,
+) -> Result<(), AllocError> {
+    let cl = unsafe { ngx_alloc_chain_link(pool.as_ptr()) };
+    if cl.is_null() {
+        return Err(AllocError);
+    }
+
+    unsafe {
+        (*cl).buf = buf;
+        (*cl).next = ptr::null_mut();
+        **ll = cl;
+        *ll = &raw mut (*cl).next;
+    }
+
+    Ok(())
+}
+
+/// Formats a chunk header for a chunk of `size` bytes.
+pub fn chunk_header(size: u64, buf: &mut [u8; CHUNK_HEADER_MAX]) -> &[u8] {
+    let digits = cmp::max(1, (u64::BITS - size.leading_zeros()).div_ceil(4)) as usize;
+
+    for (i, x) in buf[..digits].iter_mut().rev().enumerate() {
+        *x = b"0123456789abcdef"[((size >> (i * 4)) & 0xf) as usize];
+    }
+
+    buf[digits..digits + 2].copy_from_slice(b"\r\n");
+    &buf[..digits + 2]
+}
+
+/// Encodes the buffers of `input` as a single chunk.
+///
+/// Returns a new chain with the chunk header, the input buffers and the chunk terminator. If one
+/// of the input buffers has the `last_buf` flag, the flag is moved to the last chunk appended to
+/// the output.
+///
+/// # Safety
+///
+/// `input` must be a null pointer or a valid chain.
+pub unsafe fn encode_chain(
+    pool: &Pool,
+    input: *mut ngx_chain_t,
+) -> Result<*mut ngx_chain_t, AllocError> {
+    let mut out: *mut ngx_chain_t = ptr::null_mut();
+    let mut ll = &raw mut out;
+    let mut size = 0;
+    let mut last = false;
+
+    let mut cl = input;
+    while let Some(link) = unsafe { cl.as_mut() } {
+        let buf = unsafe { &mut *link.buf };
+        size += buf_size(buf);
+        if buf.last_buf() != 0 {
+            buf.set_last_buf(0);
+            last = true;
+        }
+        cl = link.next;
+    }
+
+    if size > 0 {
+        let mut header = [0u8; CHUNK_HEADER_MAX];
+        let header = chunk_header(size, &mut header);
+
+        let b = unsafe { ngx_create_temp_buf(pool.as_ptr(), header.len()) };
+        if b.is_null() {
+            return Err(AllocError);
+        }
+
+        unsafe {
+            ptr::copy_nonoverlapping(header.as_ptr(), (*b).last, header.len());
+            (*b).last = (*b).last.add(header.len());
+            append_link(pool, &mut ll, b)?;
+        }
+    }
+
+    let mut cl = input;
+    while let Some(link) = unsafe { cl.as_ref() } {
+        unsafe { append_link(pool, &mut ll, link.buf)? };
+        cl = link.next;
+    }
+
+    let tail: &'static [u8] = match (size > 0, last) {
+        (true, true) => b"\r\n0\r\n\r\n",
+        (true, false) => b"\r\n",
+        (false, true) => LAST_CHUNK,
+        (false, false) => return Ok(out),
+    };
+
+    let b = unsafe { append_buf(pool, &mut ll, tail.as_ptr(), tail.len())? };
+    if last {
+        unsafe { (*b).set_last_buf(1) };
+    }
+
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chunk_header() {
+        let mut buf = [0u8; CHUNK_HEADER_MAX];
+        assert_eq!(chunk_header(0, &mut buf), b"0\r\n");
+        assert_eq!(chunk_header(0x1a, &mut buf), b"1a\r\n");
+        assert_eq!(chunk_header(4096, &mut buf), b"1000\r\n");
+        assert_eq!(chunk_header(u64::MAX, &mut buf), b"ffffffffffffffff\r\n");
+    }
+}
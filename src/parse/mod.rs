@@ -0,0 +1,46 @@
+//! Incremental parsing utilities.
+//!
+//! Data in nginx arrives in buffers of arbitrary size, and any token can be split across buffer
+//! boundaries. This module provides a framework for writing resumable parsers as explicit state
+//! machines, and a driver that feeds the buffers to the state machine, tracks the number of
+//! consumed bytes and keeps the incomplete tokens in allocator-backed storage until more data is
+//! available.
+#[cfg(feature = "alloc")]
+pub use self::resumable::{ParseError, Resumable};
+
+pub mod chunked;
+#[cfg(feature = "alloc")]
+mod resumable;
+
+/// Result of a single [`StateMachine::step`].
+#[derive(Debug, PartialEq, Eq)]
+pub enum Step<T> {
+    /// The parser consumed the specified number of bytes without producing any output.
+    ///
+    /// The number must be greater than zero.
+    Advance(usize),
+    /// The parser consumed the specified number of bytes and produced an output.
+    ///
+    /// The number can be zero if the parser state was changed by the step.
+    Emit(usize, T),
+    /// The input is not sufficient to make progress.
+    ///
+    /// The parser will be called again with the same input extended with the next buffer.
+    Incomplete,
+}
+
+/// Resumable parser state machine.
+///
+/// The implementation is expected to keep the current state (usually an enum) and advance it with
+/// each call to [`step`](StateMachine::step). Outputs may borrow from the input.
+pub trait StateMachine {
+    /// Output produced by the parser.
+    type Output<'a>;
+    /// Parser error.
+    type Error;
+
+    /// Processes a prefix of the `input` and advances the parser state.
+    ///
+    /// `input` is never empty.
+    fn step<'a>(&mut self, input: &'a [u8]) -> Result<Step<Self::Output<'a>>, Self::Error>;
+}
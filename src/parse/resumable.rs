@@ -0,0 +1,241 @@
+use core::{cmp, error, fmt};
+
+use super::{StateMachine, Step};
+use crate::allocator::{AllocError, Allocator};
+use crate::collections::Vec;
+
+/// Errors returned by [`Resumable`].
+#[derive(Debug, PartialEq, Eq)]
+pub enum ParseError<E> {
+    /// The state machine rejected the input at the specified offset.
+    Invalid(E, usize),
+    /// An incomplete token exceeds the configured limit.
+    TooLarge,
+    /// The input ended with an incomplete token.
+    Incomplete,
+    /// Memory allocation failed.
+    Alloc,
+}
+
+impl<E: fmt::Display> fmt::Display for ParseError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::Invalid(err, offset) => write!(f, "{err} at offset {offset}"),
+            ParseError::TooLarge => f.write_str("token too large"),
+            ParseError::Incomplete => f.write_str("unexpected end of input"),
+            ParseError::Alloc => f.write_str("allocation failed"),
+        }
+    }
+}
+
+impl<E: error::Error> error::Error for ParseError<E> {}
+
+impl<E> From<AllocError> for ParseError<E> {
+    fn from(_: AllocError) -> Self {
+        ParseError::Alloc
+    }
+}
+
+/// Driver for a [`StateMachine`] accepting the input in arbitrary chunks.
+///
+/// The input is passed to the state machine directly whenever possible. If the state machine
+/// cannot make progress with the remaining bytes of a chunk, these bytes are saved into a buffer
+/// allocated with `A` and prepended to the next chunk. The size of the buffer is bounded by the
+/// `limit` argument.
+pub struct Resumable<P, A>
+where
+    A: Allocator + Clone,
+{
+    parser: P,
+    partial: Vec<u8, A>,
+    limit: usize,
+    consumed: usize,
+}
+
+impl<P, A> Resumable<P, A>
+where
+    P: StateMachine,
+    A: Allocator + Clone,
+{
+    /// Creates a new driver for `parser`, keeping up to `limit` bytes of incomplete input in the
+    /// memory allocated with `alloc`.
+    pub fn new_in(parser: P, limit: usize, alloc: A) -> Self {
+        Self { parser, partial: Vec::new_in(alloc), limit, consumed: 0 }
+    }
+
+    /// Returns a reference to the state machine.
+    pub fn parser(&self) -> &P {
+        &self.parser
+    }
+
+    /// Returns a mutable reference to the state machine.
+    pub fn parser_mut(&mut self) -> &mut P {
+        &mut self.parser
+    }
+
+    /// Returns the total number of bytes consumed by the state machine.
+    pub fn consumed(&self) -> usize {
+        self.consumed
+    }
+
+    /// Returns the number of buffered bytes waiting for more input.
+    pub fn pending(&self) -> usize {
+        self.partial.len()
+    }
+
+    /// Consumes the driver, returning the state machine.
+    pub fn into_inner(self) -> P {
+        self.parser
+    }
+
+    /// Feeds the next chunk of input to the state machine.
+    ///
+    /// The `emit` callback is invoked for each output produced by the state machine.
+    pub fn feed<F>(&mut self, mut input: &[u8], mut emit: F) -> Result<(), ParseError<P::Error>>
+    where
+        F: FnMut(P::Output<'_>),
+    {
+        // Complete the token saved from the previous chunk.
+        while !self.partial.is_empty() {
+            let take = cmp::min(input.len(), self.limit.saturating_sub(self.partial.len()));
+            if take == 0 {
+                if input.is_empty() {
+                    return Ok(());
+                }
+                return Err(ParseError::TooLarge);
+            }
+
+            self.partial.try_reserve(take).map_err(|_| ParseError::Alloc)?;
+            self.partial.extend_from_slice(&input[..take]);
+            input = &input[take..];
+
+            let n = drive(&mut self.parser, &self.partial, &mut self.consumed, &mut emit)?;
+            self.partial.drain(..n);
+        }
+
+        let n = drive(&mut self.parser, input, &mut self.consumed, &mut emit)?;
+        let rest = &input[n..];
+
+        if rest.len() > self.limit {
+            return Err(ParseError::TooLarge);
+        }
+
+        self.partial.try_reserve(rest.len()).map_err(|_| ParseError::Alloc)?;
+        self.partial.extend_from_slice(rest);
+        Ok(())
+    }
+
+    /// Signals the end of input.
+    ///
+    /// Returns an error if there is an incomplete token left in the buffer.
+    pub fn finish(&mut self) -> Result<(), ParseError<P::Error>> {
+        if self.partial.is_empty() { Ok(()) } else { Err(ParseError::Incomplete) }
+    }
+}
+
+/// Runs the state machine over `input` until it runs out of data or requests more.
+///
+/// Returns the number of consumed bytes.
+fn drive<P, F>(
+    parser: &mut P,
+    input: &[u8],
+    consumed: &mut usize,
+    emit: &mut F,
+) -> Result<usize, ParseError<P::Error>>
+where
+    P: StateMachine,
+    F: FnMut(P::Output<'_>),
+{
+    let mut pos = 0;
+
+    while pos < input.len() {
+        match parser.step(&input[pos..]) {
+            Ok(Step::Advance(n)) => {
+                debug_assert!(n > 0, "state machine must consume input on Step::Advance");
+                pos += n;
+                *consumed += n;
+            }
+            Ok(Step::Emit(n, out)) => {
+                pos += n;
+                *consumed += n;
+                emit(out);
+            }
+            Ok(Step::Incomplete) => break,
+            Err(err) => return Err(ParseError::Invalid(err, *consumed)),
+        }
+    }
+
+    Ok(pos)
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec as StdVec;
+
+    use super::*;
+    use crate::allocator::Global;
+
+    /// Splits the input into newline-terminated lines.
+    struct Lines;
+
+    impl StateMachine for Lines {
+        type Output<'a> = &'a [u8];
+        type Error = &'static str;
+
+        fn step<'a>(&mut self, input: &'a [u8]) -> Result<Step<&'a [u8]>, Self::Error> {
+            if input[0] == 0 {
+                return Err("nul byte");
+            }
+            match input.iter().position(|&x| x == b'\n') {
+                Some(n) => Ok(Step::Emit(n + 1, &input[..n])),
+                None => Ok(Step::Incomplete),
+            }
+        }
+    }
+
+    fn feed_all(
+        chunks: &[&[u8]],
+        limit: usize,
+    ) -> Result<StdVec<StdVec<u8>>, ParseError<&'static str>> {
+        let mut lines = StdVec::new();
+        let mut parser = Resumable::new_in(Lines, limit, Global);
+        for chunk in chunks {
+            parser.feed(chunk, |line| lines.push(line.to_vec()))?;
+        }
+        parser.finish()?;
+        assert_eq!(parser.consumed(), chunks.iter().map(|x| x.len()).sum::<usize>());
+        Ok(lines)
+    }
+
+    #[test]
+    fn test_split_tokens() {
+        let expected: &[&[u8]] = &[b"first", b"second", b"", b"third"];
+
+        let chunks: &[&[u8]] = &[b"first\nsecond\n\nthird\n"];
+        assert_eq!(feed_all(chunks, 16).unwrap(), expected);
+
+        let chunks: &[&[u8]] = &[b"fi", b"rst\nsec", b"o", b"nd\n", b"\nthird", b"\n"];
+        assert_eq!(feed_all(chunks, 16).unwrap(), expected);
+
+        let chunks: &[&[u8]] = &[b"first\nsecond\n\nthird\n", b""];
+        assert_eq!(feed_all(chunks, 0).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_errors() {
+        let chunks: &[&[u8]] = &[b"first\nsec", b"ond"];
+        assert_eq!(feed_all(chunks, 16), Err(ParseError::Incomplete));
+
+        let chunks: &[&[u8]] = &[b"first\nsecond-line-is-too-long\n"];
+        assert_eq!(feed_all(chunks, 8).unwrap().len(), 2);
+
+        let chunks: &[&[u8]] = &[b"first\nsecond-line-is-", b"too-long\n"];
+        assert_eq!(feed_all(chunks, 8), Err(ParseError::TooLarge));
+
+        let chunks: &[&[u8]] = &[b"first\nsecond", b"-line-is-too-long\n"];
+        assert_eq!(feed_all(chunks, 8), Err(ParseError::TooLarge));
+
+        let chunks: &[&[u8]] = &[b"first\n\0"];
+        assert_eq!(feed_all(chunks, 8), Err(ParseError::Invalid("nul byte", 6)));
+    }
+}
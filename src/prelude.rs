@@ -0,0 +1,48 @@
+//! Commonly used traits, macros and types.
+//!
+//! The prelude is meant to be glob-imported at the top of a module source file:
+//!
+//! ```
+//! use ngx::prelude::*;
+//! ```
+//!
+//! It brings in the traits needed to define a module and its configuration, the status types,
+//! the logging and command macros, and the [`ffi`](crate::ffi) items used by most modules, so that
+//! the module code does not need to depend on `nginx-sys` directly. Less common items are still
+//! available from their own modules.
+
+#[cfg(feature = "alloc")]
+pub use crate::core::NgxString;
+pub use crate::core::{
+    ConfUnset, CoreModule, CoreModuleConfExt, Merge, MergeConfigError, NgxStr, Pool,
+    ProcessLifecycle, Status,
+};
+pub use crate::{
+    ngx_commands, ngx_conf_log_error, ngx_container_of, ngx_log_debug, ngx_log_debug_mask,
+    ngx_log_error, ngx_modules, ngx_string,
+};
+
+#[cfg(ngx_feature = "http")]
+pub use crate::http::{
+    HTTPStatus, HttpModule, HttpModuleConfExt, HttpModuleLocationConf, HttpModuleMainConf,
+    HttpModuleServerConf, HttpPhase, HttpRequestHandler, IntoHandlerStatus, NgxHttpCoreModule,
+    Request, RequestContext,
+};
+#[cfg(ngx_feature = "http")]
+pub use crate::{ngx_http_module, ngx_log_debug_http};
+
+#[cfg(all(feature = "stream", ngx_feature = "stream"))]
+pub use crate::stream::{StreamHandler, StreamModule, StreamModuleConfExt};
+
+// The FFI items used by most modules.
+pub use crate::ffi::{
+    NGX_CONF_1MORE, NGX_CONF_FLAG, NGX_CONF_NOARGS, NGX_CONF_TAKE1, NGX_CONF_TAKE2, NGX_LOG_ALERT,
+    NGX_LOG_CRIT, NGX_LOG_EMERG, NGX_LOG_ERR, NGX_LOG_INFO, NGX_LOG_NOTICE, NGX_LOG_WARN,
+    ngx_command_t, ngx_conf_t, ngx_cycle_t, ngx_int_t, ngx_log_t, ngx_module_t, ngx_str_t,
+    ngx_uint_t,
+};
+#[cfg(ngx_feature = "http")]
+pub use crate::ffi::{
+    NGX_HTTP_LOC_CONF, NGX_HTTP_LOC_CONF_OFFSET, NGX_HTTP_MAIN_CONF, NGX_HTTP_MAIN_CONF_OFFSET,
+    NGX_HTTP_SRV_CONF, NGX_HTTP_SRV_CONF_OFFSET, ngx_http_module_t, ngx_http_request_t,
+};
@@ -0,0 +1,272 @@
+//! Regular expressions compiled with the PCRE library linked into nginx.
+//!
+//! [`Regex`] wraps `ngx_regex_compile` and `ngx_regex_exec`, and is compiled once in a directive
+//! handler and then matched in the request handlers, with the same syntax and options as the
+//! regular expressions in the nginx configuration:
+//!
+//! ```no_run
+//! # use ngx::core::{NgxStr, Pool, Status};
+//! # use ngx::ffi::{ngx_conf_t, ngx_str_t};
+//! # use ngx::regex::{Regex, RegexOptions};
+//! # fn f(cf: &mut ngx_conf_t, pattern: &ngx_str_t, pool: &Pool, uri: &NgxStr) -> Result<(), Status> {
+//! // in a directive handler
+//! let re = Regex::compile(cf, pattern, RegexOptions::CASELESS)?;
+//!
+//! // in a request handler
+//! if let Some(caps) = re.captures(uri, pool) {
+//!     let id = caps.name("id");
+//! }
+//! # Ok(()) }
+//! ```
+//!
+//! [`HttpRegex`] additionally sets the numbered (`$1`) and the named captures as the request
+//! variables, the same as the regular expressions in the `location` and `map` directives.
+//!
+//! The module requires nginx built with PCRE2, the default since nginx 1.21.5.
+use core::ffi::{CStr, c_char, c_int};
+use core::ops;
+use core::ptr::{self, NonNull};
+use core::slice;
+
+use crate::core::{NgxStr, Pool, Status};
+use crate::ffi::{
+    NGX_LOG_EMERG, NGX_MAX_CONF_ERRSTR, NGX_OK, NGX_REGEX_CASELESS, ngx_conf_t, ngx_int_t,
+    ngx_regex_compile, ngx_regex_compile_t, ngx_regex_exec, ngx_regex_t, ngx_str_t, ngx_uint_t,
+    u_char,
+};
+use crate::ngx_conf_log_error;
+
+/// Compilation options of a [`Regex`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RegexOptions(ngx_uint_t);
+
+impl RegexOptions {
+    /// No options.
+    pub const NONE: Self = Self(0);
+    /// Case-insensitive matching, as with the `~*` operator.
+    pub const CASELESS: Self = Self(NGX_REGEX_CASELESS as _);
+}
+
+impl ops::BitOr for RegexOptions {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+/// Compiled regular expression.
+///
+/// The expression is allocated from the configuration pool and remains valid for the lifetime of
+/// the configuration cycle.
+#[derive(Clone, Copy, Debug)]
+pub struct Regex {
+    regex: NonNull<ngx_regex_t>,
+    captures: usize,
+    names: *const u_char,
+    named_captures: usize,
+    name_size: usize,
+}
+
+impl Regex {
+    /// Compiles the `pattern` in a directive handler.
+    ///
+    /// The compilation error is logged with the location in the configuration file.
+    pub fn compile(
+        cf: &mut ngx_conf_t,
+        pattern: &ngx_str_t,
+        options: RegexOptions,
+    ) -> Result<Self, Status> {
+        let mut errstr = [0u8; NGX_MAX_CONF_ERRSTR as usize];
+        let mut rc = compile_options(cf, pattern, options, &mut errstr);
+
+        if unsafe { ngx_regex_compile(&mut rc) } != NGX_OK as ngx_int_t {
+            log_compile_error(cf, &rc);
+            return Err(Status::NGX_ERROR);
+        }
+
+        Ok(Self {
+            regex: NonNull::new(rc.regex).ok_or(Status::NGX_ERROR)?,
+            captures: rc.captures as usize,
+            names: rc.names,
+            named_captures: rc.named_captures as usize,
+            name_size: rc.name_size as usize,
+        })
+    }
+
+    /// Returns the number of the capture groups, not including the whole match.
+    pub fn captures_len(&self) -> usize {
+        self.captures
+    }
+
+    /// Returns an iterator over the names of the named capture groups and their indices.
+    pub fn capture_names(&self) -> impl Iterator<Item = (&NgxStr, usize)> + '_ {
+        (0..self.named_captures).map(|i| {
+            // SAFETY: the name table has `named_captures` entries of `name_size` bytes: a 16-bit
+            // big-endian group index followed by the NUL-terminated name.
+            unsafe {
+                let p = self.names.add(i * self.name_size);
+                let index = usize::from(u16::from_be_bytes([*p, *p.add(1)]));
+                let name = CStr::from_ptr(p.add(2).cast::<c_char>());
+                (NgxStr::from_bytes(name.to_bytes()), index)
+            }
+        })
+    }
+
+    /// Returns `true` if the expression matches the string.
+    pub fn is_match(&self, s: &NgxStr) -> bool {
+        let mut subject =
+            ngx_str_t { len: s.as_bytes().len(), data: s.as_bytes().as_ptr().cast_mut() };
+        // SAFETY: the expression is valid for the configuration lifetime.
+        let rc = unsafe { ngx_regex_exec(self.regex.as_ptr(), &mut subject, ptr::null_mut(), 0) };
+        rc >= 0
+    }
+
+    /// Returns the position of the first match in the data.
+    pub fn find(&self, data: &[u8]) -> Option<ops::Range<usize>> {
+        let mut ovector: [c_int; 3] = [-1; 3];
+        let mut subject = ngx_str_t { len: data.len(), data: data.as_ptr().cast_mut() };
+        // SAFETY: the array has space for the whole match; the groups are not reported.
+        let rc =
+            unsafe { ngx_regex_exec(self.regex.as_ptr(), &mut subject, ovector.as_mut_ptr(), 3) };
+        if rc < 0 || ovector[0] < 0 {
+            return None;
+        }
+        Some(ovector[0] as usize..ovector[1] as usize)
+    }
+
+    /// Matches the expression against the string, and returns the capture groups.
+    ///
+    /// The positions of the groups are stored in an array allocated from the pool. Returns `None`
+    /// if the expression does not match or the allocation fails.
+    pub fn captures<'a>(&self, s: &'a NgxStr, pool: &'a Pool) -> Option<Captures<'a>> {
+        let size = (self.captures + 1) * 3;
+        let ovector = pool.alloc(size * size_of::<c_int>()).cast::<c_int>();
+        if ovector.is_null() {
+            return None;
+        }
+
+        let mut subject =
+            ngx_str_t { len: s.as_bytes().len(), data: s.as_bytes().as_ptr().cast_mut() };
+        // SAFETY: the array has space for the whole match and all the capture groups.
+        let rc = unsafe { ngx_regex_exec(self.regex.as_ptr(), &mut subject, ovector, size as _) };
+        if rc < 0 {
+            return None;
+        }
+
+        Some(Captures {
+            subject: s.as_bytes(),
+            // SAFETY: `ngx_regex_exec` initialized a pair of offsets for each group.
+            ovector: unsafe { slice::from_raw_parts(ovector, (self.captures + 1) * 2) },
+            matched: rc as usize,
+            regex: *self,
+        })
+    }
+}
+
+/// Capture groups of a successful [`Regex::captures`] match.
+#[derive(Clone, Copy, Debug)]
+pub struct Captures<'a> {
+    subject: &'a [u8],
+    ovector: &'a [c_int],
+    matched: usize,
+    regex: Regex,
+}
+
+impl<'a> Captures<'a> {
+    /// Returns the capture group with the index, where 0 is the whole match.
+    ///
+    /// Returns `None` if the group did not participate in the match.
+    pub fn get(&self, i: usize) -> Option<&'a NgxStr> {
+        if i >= self.matched {
+            return None;
+        }
+        let start = self.ovector[2 * i];
+        let end = self.ovector[2 * i + 1];
+        if start < 0 {
+            return None;
+        }
+        Some(NgxStr::from_bytes(&self.subject[start as usize..end as usize]))
+    }
+
+    /// Returns the named capture group.
+    pub fn name(&self, name: &str) -> Option<&'a NgxStr> {
+        let (_, i) = self.regex.capture_names().find(|(n, _)| *n == name)?;
+        self.get(i)
+    }
+
+    /// Returns the number of the groups, including the whole match.
+    pub fn len(&self) -> usize {
+        self.regex.captures + 1
+    }
+
+    /// Always returns `false`: a match has at least the group 0.
+    pub fn is_empty(&self) -> bool {
+        false
+    }
+}
+
+/// Regular expression setting the request variables from the capture groups.
+///
+/// The numbered captures are available as `$1`, `$2`, ... to the scripts and the complex values
+/// evaluated after the match, and the named captures are set as the variables with the same
+/// names, which are added to the configuration on compilation.
+#[cfg(ngx_feature = "http")]
+#[derive(Clone, Copy, Debug)]
+pub struct HttpRegex(NonNull<crate::ffi::ngx_http_regex_t>);
+
+#[cfg(ngx_feature = "http")]
+impl HttpRegex {
+    /// Compiles the `pattern` with `ngx_http_regex_compile` in a directive handler.
+    ///
+    /// The compilation error is logged by nginx with the location in the configuration file.
+    pub fn compile(
+        cf: &mut ngx_conf_t,
+        pattern: &ngx_str_t,
+        options: RegexOptions,
+    ) -> Result<Self, Status> {
+        let mut errstr = [0u8; NGX_MAX_CONF_ERRSTR as usize];
+        let mut rc = compile_options(cf, pattern, options, &mut errstr);
+
+        // SAFETY: the configuration is valid in a directive handler.
+        let re = unsafe { crate::ffi::ngx_http_regex_compile(cf, &mut rc) };
+        NonNull::new(re).map(Self).ok_or(Status::NGX_ERROR)
+    }
+
+    /// Matches the expression against the string and sets the capture variables of the request.
+    ///
+    /// Returns `Ok(false)` if the expression does not match.
+    pub fn exec(&self, r: &mut crate::http::Request, s: &NgxStr) -> Result<bool, Status> {
+        let mut subject =
+            ngx_str_t { len: s.as_bytes().len(), data: s.as_bytes().as_ptr().cast_mut() };
+        // SAFETY: the expression is valid for the configuration lifetime.
+        let rc =
+            unsafe { crate::ffi::ngx_http_regex_exec(r.as_mut(), self.0.as_ptr(), &mut subject) };
+        match Status(rc) {
+            Status::NGX_OK => Ok(true),
+            Status::NGX_DECLINED => Ok(false),
+            status => Err(status),
+        }
+    }
+}
+
+fn compile_options(
+    cf: &mut ngx_conf_t,
+    pattern: &ngx_str_t,
+    options: RegexOptions,
+    errstr: &mut [u8],
+) -> ngx_regex_compile_t {
+    // SAFETY: an all-zero value is a valid initial state, as in nginx.
+    let mut rc: ngx_regex_compile_t = unsafe { core::mem::zeroed() };
+    rc.pattern = *pattern;
+    rc.pool = cf.pool;
+    rc.options = options.0;
+    rc.err = ngx_str_t { len: errstr.len(), data: errstr.as_mut_ptr() };
+    rc
+}
+
+fn log_compile_error(cf: &mut ngx_conf_t, rc: &ngx_regex_compile_t) {
+    // SAFETY: the error buffer is filled by `ngx_regex_compile`.
+    let err = unsafe { NgxStr::from_ngx_str(rc.err) };
+    ngx_conf_log_error!(NGX_LOG_EMERG, cf, "{err}");
+}
@@ -0,0 +1,148 @@
+//! Information about the TLS connections.
+//!
+//! The values are obtained with the same functions as the `$ssl_*` variables of the HTTP and
+//! stream modules, and have the same format. Most of the values are allocated from the
+//! specified pool, e.g. the request pool.
+//!
+//! ```no_run
+//! # use ngx::http::Request;
+//! # use ngx::ssl::VerifyResult;
+//! fn client_is_verified(r: &mut Request) -> bool {
+//!     let pool = r.pool();
+//!     let Some(ssl) = r.connection().ssl() else {
+//!         return false;
+//!     };
+//!     matches!(ssl.verify_result(&pool), VerifyResult::Success)
+//! }
+//! ```
+use core::slice;
+
+use crate::core::{Connection, NgxStr, Pool, pem_to_der};
+use crate::ffi::{
+    NGX_OK, ngx_connection_t, ngx_int_t, ngx_pool_t, ngx_ssl_get_cipher_name,
+    ngx_ssl_get_client_verify, ngx_ssl_get_protocol, ngx_ssl_get_raw_certificate,
+    ngx_ssl_get_server_name, ngx_str_t,
+};
+
+/// TLS state of a connection, see [`Connection::ssl`].
+#[derive(Clone, Copy)]
+pub struct SslInfo<'a>(&'a Connection);
+
+/// Result of the peer certificate verification, as in the `$ssl_client_verify` variable.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VerifyResult<'a> {
+    /// The certificate is verified.
+    Success,
+    /// The verification failed, with the reason, e.g. `certificate has expired`.
+    Failed(&'a NgxStr),
+    /// The peer did not present a certificate.
+    None,
+}
+
+impl Connection {
+    /// Returns the TLS state of the connection, if TLS is used.
+    pub fn ssl(&self) -> Option<SslInfo<'_>> {
+        self.ssl_connection().map(|_| SslInfo(self))
+    }
+}
+
+type Getter =
+    unsafe extern "C" fn(*mut ngx_connection_t, *mut ngx_pool_t, *mut ngx_str_t) -> ngx_int_t;
+
+impl<'a> SslInfo<'a> {
+    /// Returns the connection.
+    pub fn connection(&self) -> &'a Connection {
+        self.0
+    }
+
+    /// Returns the server name requested with SNI, as in the `$ssl_server_name` variable.
+    pub fn server_name<'p>(&self, pool: &'p Pool) -> Option<&'p NgxStr> {
+        self.get(pool, ngx_ssl_get_server_name)
+    }
+
+    /// Returns the protocol of the connection, e.g. `TLSv1.3`.
+    pub fn protocol<'p>(&self, pool: &'p Pool) -> Option<&'p NgxStr> {
+        self.get(pool, ngx_ssl_get_protocol)
+    }
+
+    /// Returns the name of the cipher used, e.g. `TLS_AES_256_GCM_SHA384`.
+    pub fn cipher<'p>(&self, pool: &'p Pool) -> Option<&'p NgxStr> {
+        self.get(pool, ngx_ssl_get_cipher_name)
+    }
+
+    /// Returns the result of the peer certificate verification.
+    ///
+    /// The result is only meaningful if the certificate verification is enabled, e.g. with the
+    /// `ssl_verify_client` directive. An allocation failure is reported as
+    /// [`VerifyResult::None`].
+    pub fn verify_result<'p>(&self, pool: &'p Pool) -> VerifyResult<'p> {
+        match self.get(pool, ngx_ssl_get_client_verify) {
+            Some(s) => VerifyResult::parse(s),
+            None => VerifyResult::None,
+        }
+    }
+
+    /// Returns the PEM-encoded peer certificate, as in the `$ssl_client_raw_cert` variable.
+    ///
+    /// Returns `None` if the peer did not present a certificate.
+    pub fn peer_certificate_pem<'p>(&self, pool: &'p Pool) -> Option<&'p NgxStr> {
+        self.get(pool, ngx_ssl_get_raw_certificate)
+    }
+
+    /// Returns the DER-encoded peer certificate.
+    ///
+    /// Returns `None` if the peer did not present a certificate.
+    pub fn peer_certificate_der<'p>(&self, pool: &'p Pool) -> Option<&'p [u8]> {
+        let pem = self.peer_certificate_pem(pool)?;
+
+        let buf = pool.alloc_unaligned(pem.len()).cast::<u8>();
+        if buf.is_null() {
+            return None;
+        }
+
+        // SAFETY: the buffer is large enough for the decoded PEM body.
+        let buf = unsafe { slice::from_raw_parts_mut(buf, pem.len()) };
+        let len = pem_to_der(pem.as_bytes(), buf)?;
+        Some(&buf[..len])
+    }
+
+    fn get<'p>(&self, pool: &'p Pool, get: Getter) -> Option<&'p NgxStr> {
+        let mut s = ngx_str_t::default();
+        // SAFETY: the connection uses TLS, and the value is allocated from `pool`.
+        let rc = unsafe { get(self.0.as_ptr(), pool.as_ptr(), &raw mut s) };
+        if rc != NGX_OK as ngx_int_t || s.len == 0 {
+            return None;
+        }
+        Some(unsafe { NgxStr::from_ngx_str(s) })
+    }
+}
+
+impl<'a> VerifyResult<'a> {
+    /// Parses the value of the `$ssl_client_verify` variable.
+    pub fn parse(s: &'a NgxStr) -> Self {
+        match s.as_bytes() {
+            b"SUCCESS" => Self::Success,
+            b"NONE" => Self::None,
+            x => {
+                let reason = x.strip_prefix(b"FAILED:").unwrap_or(x);
+                Self::Failed(NgxStr::from_bytes(reason))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verify_result() {
+        let parse = |x: &'static [u8]| VerifyResult::parse(NgxStr::from_bytes(x));
+        assert_eq!(parse(b"SUCCESS"), VerifyResult::Success);
+        assert_eq!(parse(b"NONE"), VerifyResult::None);
+        assert_eq!(
+            parse(b"FAILED:certificate has expired"),
+            VerifyResult::Failed(NgxStr::from_bytes(b"certificate has expired"))
+        );
+    }
+}
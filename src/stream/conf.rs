@@ -0,0 +1,179 @@
+use ::core::ptr::NonNull;
+
+use crate::ffi::{
+    ngx_conf_t, ngx_cycle_t, ngx_module_t, ngx_stream_conf_ctx_t, ngx_stream_module,
+    ngx_stream_session_t,
+};
+use crate::stream::StreamModule;
+
+/// Utility trait for types containing stream module configuration
+pub trait StreamModuleConfExt {
+    /// Get a non-null reference to the main configuration structure for stream module
+    ///
+    /// # Safety
+    /// Caller must ensure that type `T` matches the configuration type for the specified module.
+    #[inline]
+    unsafe fn stream_main_conf_unchecked<T>(&self, _module: &ngx_module_t) -> Option<NonNull<T>> {
+        None
+    }
+
+    /// Get a non-null reference to the server configuration structure for stream module
+    ///
+    /// # Safety
+    /// Caller must ensure that type `T` matches the configuration type for the specified module.
+    #[inline]
+    unsafe fn stream_server_conf_unchecked<T>(&self, _module: &ngx_module_t) -> Option<NonNull<T>> {
+        None
+    }
+}
+
+impl StreamModuleConfExt for ngx_stream_conf_ctx_t {
+    #[inline]
+    unsafe fn stream_main_conf_unchecked<T>(&self, module: &ngx_module_t) -> Option<NonNull<T>> {
+        NonNull::new(unsafe { *self.main_conf.add(module.ctx_index) }.cast())
+    }
+
+    #[inline]
+    unsafe fn stream_server_conf_unchecked<T>(&self, module: &ngx_module_t) -> Option<NonNull<T>> {
+        NonNull::new(unsafe { *self.srv_conf.add(module.ctx_index) }.cast())
+    }
+}
+
+impl StreamModuleConfExt for ngx_cycle_t {
+    #[inline]
+    unsafe fn stream_main_conf_unchecked<T>(&self, module: &ngx_module_t) -> Option<NonNull<T>> {
+        let stream_conf = unsafe { self.conf_ctx.add(ngx_stream_module.index).as_ref()? };
+        let conf_ctx = (*stream_conf).cast::<ngx_stream_conf_ctx_t>();
+        unsafe { conf_ctx.as_ref()?.stream_main_conf_unchecked(module) }
+    }
+}
+
+impl StreamModuleConfExt for ngx_conf_t {
+    #[inline]
+    unsafe fn stream_main_conf_unchecked<T>(&self, module: &ngx_module_t) -> Option<NonNull<T>> {
+        let conf_ctx = self.ctx.cast::<ngx_stream_conf_ctx_t>();
+        unsafe { conf_ctx.as_ref()?.stream_main_conf_unchecked(module) }
+    }
+
+    #[inline]
+    unsafe fn stream_server_conf_unchecked<T>(&self, module: &ngx_module_t) -> Option<NonNull<T>> {
+        let conf_ctx = self.ctx.cast::<ngx_stream_conf_ctx_t>();
+        unsafe { conf_ctx.as_ref()?.stream_server_conf_unchecked(module) }
+    }
+}
+
+impl StreamModuleConfExt for ngx_stream_session_t {
+    #[inline]
+    unsafe fn stream_main_conf_unchecked<T>(&self, module: &ngx_module_t) -> Option<NonNull<T>> {
+        NonNull::new(unsafe { *self.main_conf.add(module.ctx_index) }.cast())
+    }
+
+    #[inline]
+    unsafe fn stream_server_conf_unchecked<T>(&self, module: &ngx_module_t) -> Option<NonNull<T>> {
+        NonNull::new(unsafe { *self.srv_conf.add(module.ctx_index) }.cast())
+    }
+}
+
+/// Trait to define and access main stream module configuration
+///
+/// # Safety
+/// Caller must ensure that type `StreamModuleMainConf::MainConf` matches the configuration type
+/// for the specified module.
+pub unsafe trait StreamModuleMainConf: StreamModule {
+    /// Type for main module configuration
+    type MainConf;
+    /// Get reference to main module configuration
+    fn main_conf(o: &impl StreamModuleConfExt) -> Option<&'static Self::MainConf> {
+        unsafe { Some(o.stream_main_conf_unchecked(Self::module())?.as_ref()) }
+    }
+    /// Get mutable reference to main module configuration
+    fn main_conf_mut(o: &impl StreamModuleConfExt) -> Option<&'static mut Self::MainConf> {
+        unsafe { Some(o.stream_main_conf_unchecked(Self::module())?.as_mut()) }
+    }
+}
+
+/// Trait to define and access server-specific stream module configuration
+///
+/// # Safety
+/// Caller must ensure that type `StreamModuleServerConf::ServerConf` matches the configuration
+/// type for the specified module.
+pub unsafe trait StreamModuleServerConf: StreamModule {
+    /// Type for server-specific module configuration
+    type ServerConf;
+    /// Get reference to server-specific module configuration
+    fn server_conf(o: &impl StreamModuleConfExt) -> Option<&'static Self::ServerConf> {
+        unsafe { Some(o.stream_server_conf_unchecked(Self::module())?.as_ref()) }
+    }
+    /// Get mutable reference to server-specific module configuration
+    fn server_conf_mut(o: &impl StreamModuleConfExt) -> Option<&'static mut Self::ServerConf> {
+        unsafe { Some(o.stream_server_conf_unchecked(Self::module())?.as_mut()) }
+    }
+}
+
+mod core {
+    use crate::allocator::AllocError;
+    use crate::ffi::{
+        NGX_LOG_EMERG, ngx_array_push, ngx_conf_t, ngx_module_t, ngx_stream_core_main_conf_t,
+        ngx_stream_core_module, ngx_stream_core_srv_conf_t, ngx_stream_handler_pt,
+    };
+    use crate::ngx_conf_log_error;
+    use crate::stream::{StreamHandler, StreamModule, StreamModuleMainConf};
+
+    /// Auxiliary structure to access `ngx_stream_core_module` configuration.
+    pub struct NgxStreamCoreModule;
+
+    impl StreamModule for NgxStreamCoreModule {
+        fn module() -> &'static ngx_module_t {
+            unsafe { &*::core::ptr::addr_of!(ngx_stream_core_module) }
+        }
+    }
+    unsafe impl crate::stream::StreamModuleMainConf for NgxStreamCoreModule {
+        type MainConf = ngx_stream_core_main_conf_t;
+    }
+    unsafe impl crate::stream::StreamModuleServerConf for NgxStreamCoreModule {
+        type ServerConf = ngx_stream_core_srv_conf_t;
+    }
+
+    /// Stream phases in which a module can register handlers.
+    ///
+    /// The content phase is not listed, as its handler is set in the server configuration by the
+    /// directive of the content module, e.g. `proxy_pass`.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    #[repr(usize)]
+    pub enum StreamPhase {
+        /// Post-accept phase
+        PostAccept = crate::ffi::ngx_stream_phases_NGX_STREAM_POST_ACCEPT_PHASE as _,
+        /// Pre-access phase
+        Preaccess = crate::ffi::ngx_stream_phases_NGX_STREAM_PREACCESS_PHASE as _,
+        /// Access phase
+        Access = crate::ffi::ngx_stream_phases_NGX_STREAM_ACCESS_PHASE as _,
+        /// SSL phase
+        Ssl = crate::ffi::ngx_stream_phases_NGX_STREAM_SSL_PHASE as _,
+        /// Preread phase
+        Preread = crate::ffi::ngx_stream_phases_NGX_STREAM_PREREAD_PHASE as _,
+        /// Log phase
+        Log = crate::ffi::ngx_stream_phases_NGX_STREAM_LOG_PHASE as _,
+    }
+
+    /// Register a session handler for a specified phase.
+    /// This function must be called from the module's `postconfiguration()` function.
+    pub fn add_phase_handler<H>(cf: &mut ngx_conf_t) -> Result<(), AllocError>
+    where
+        H: StreamHandler,
+    {
+        let cmcf = NgxStreamCoreModule::main_conf_mut(cf).expect("stream core main conf");
+        let h: *mut ngx_stream_handler_pt =
+            unsafe { ngx_array_push(&raw mut cmcf.phases[H::PHASE as usize].handlers).cast() };
+        if h.is_null() {
+            ngx_conf_log_error!(NGX_LOG_EMERG, cf, "failed to register {} handler", H::name());
+            return Err(AllocError);
+        }
+        // set an H::PHASE phase handler
+        unsafe {
+            *h = Some(crate::stream::raw_handler::<H>);
+        }
+        Ok(())
+    }
+}
+
+pub use core::{NgxStreamCoreModule, StreamPhase, add_phase_handler};
@@ -0,0 +1,170 @@
+use core::ffi::c_void;
+use core::fmt;
+
+use crate::core::{Pool, Status};
+use crate::ffi::*;
+use crate::stream::{StreamModuleConfExt, StreamPhase};
+
+/// Define a static stream session handler.
+///
+/// Handlers are expected to take a single [`Session`] argument and return a [`Status`]:
+/// - `NGX_OK` to proceed to the next phase,
+/// - `NGX_DECLINED` to proceed to the next handler of the phase,
+/// - `NGX_AGAIN` or `NGX_DONE` to suspend the processing until the session is resumed,
+/// - `NGX_ERROR` or a stream status code, e.g. `NGX_STREAM_FORBIDDEN`, to finalize the session.
+pub trait StreamHandler {
+    /// The phase in which the handler is invoked.
+    const PHASE: StreamPhase;
+    /// The handler function.
+    fn handler(session: &mut Session) -> Status;
+    /// Handler name for logging purposes.
+    /// [`core::any::type_name`] is used by default.
+    fn name() -> &'static str {
+        core::any::type_name::<Self>()
+    }
+}
+
+/// The C-compatible handler wrapper function.
+///
+/// # Safety
+///
+/// The caller has provided a valid non-null pointer to an [`ngx_stream_session_t`].
+pub(crate) unsafe extern "C" fn raw_handler<H>(s: *mut ngx_stream_session_t) -> ngx_int_t
+where
+    H: StreamHandler,
+{
+    let s = unsafe { Session::from_ngx_stream_session(s) };
+    H::handler(s).into()
+}
+
+/// Wrapper struct for an [`ngx_stream_session_t`] pointer, providing methods for working with
+/// TCP and UDP sessions.
+///
+/// See <https://nginx.org/en/docs/dev/development_guide.html#stream>
+#[repr(transparent)]
+pub struct Session(ngx_stream_session_t);
+
+impl<'a> From<&'a Session> for *const ngx_stream_session_t {
+    fn from(s: &'a Session) -> Self {
+        &s.0 as *const _
+    }
+}
+
+impl<'a> From<&'a mut Session> for *mut ngx_stream_session_t {
+    fn from(s: &'a mut Session) -> Self {
+        &s.0 as *const _ as *mut _
+    }
+}
+
+impl AsRef<ngx_stream_session_t> for Session {
+    fn as_ref(&self) -> &ngx_stream_session_t {
+        &self.0
+    }
+}
+
+impl AsMut<ngx_stream_session_t> for Session {
+    fn as_mut(&mut self) -> &mut ngx_stream_session_t {
+        &mut self.0
+    }
+}
+
+impl Session {
+    /// Create a [`Session`] from an [`ngx_stream_session_t`].
+    ///
+    /// # Safety
+    ///
+    /// The caller has provided a valid non-null pointer to a valid `ngx_stream_session_t`
+    /// which shares the same representation as `Session`.
+    pub unsafe fn from_ngx_stream_session<'a>(s: *mut ngx_stream_session_t) -> &'a mut Session {
+        unsafe { &mut *s.cast::<Session>() }
+    }
+
+    /// Client connection.
+    pub fn connection(&self) -> *mut ngx_connection_t {
+        self.0.connection
+    }
+
+    /// Session pool, which is the pool of the client connection.
+    pub fn pool(&self) -> Pool {
+        // SAFETY: the connection and its pool are valid for the lifetime of the session.
+        unsafe { Pool::from_ngx_pool((*self.0.connection).pool) }
+    }
+
+    /// Pointer to a [`ngx_log_t`].
+    pub fn log(&self) -> *mut ngx_log_t {
+        unsafe { (*self.0.connection).log }
+    }
+
+    /// Returns `true` if the session is over UDP.
+    pub fn is_udp(&self) -> bool {
+        // SAFETY: the connection is valid for the lifetime of the session.
+        unsafe { (*self.0.connection).type_ == SOCK_DGRAM as i32 }
+    }
+
+    /// Number of bytes received from the client.
+    pub fn received(&self) -> u64 {
+        self.0.received as _
+    }
+
+    /// Session status, as reported by the `$status` variable.
+    pub fn status(&self) -> ngx_uint_t {
+        self.0.status
+    }
+
+    /// Sets the session status, as reported by the `$status` variable.
+    pub fn set_status(&mut self, status: ngx_uint_t) {
+        self.0.status = status;
+    }
+
+    /// Module context pointer, if any.
+    pub fn get_module_ctx<T>(&self, module: &ngx_module_t) -> Option<&T> {
+        let ctx = unsafe { *self.0.ctx.add(module.ctx_index) }.cast::<T>();
+        unsafe { ctx.as_ref() }
+    }
+
+    /// Sets the value as the module's context.
+    pub fn set_module_ctx(&self, value: *mut c_void, module: &ngx_module_t) {
+        unsafe {
+            *self.0.ctx.add(module.ctx_index) = value;
+        };
+    }
+
+    /// Resumes the processing of a session suspended with `NGX_AGAIN` or `NGX_DONE`.
+    pub fn run_phases(&mut self) {
+        unsafe { ngx_stream_core_run_phases(&raw mut self.0) }
+    }
+
+    /// Finalizes the session with the status, e.g. `NGX_STREAM_OK` or
+    /// `NGX_STREAM_INTERNAL_SERVER_ERROR`.
+    pub fn finalize(&mut self, status: ngx_uint_t) {
+        unsafe { ngx_stream_finalize_session(&raw mut self.0, status) }
+    }
+}
+
+impl StreamModuleConfExt for Session {
+    #[inline]
+    unsafe fn stream_main_conf_unchecked<T>(
+        &self,
+        module: &ngx_module_t,
+    ) -> Option<core::ptr::NonNull<T>> {
+        unsafe { self.0.stream_main_conf_unchecked(module) }
+    }
+
+    #[inline]
+    unsafe fn stream_server_conf_unchecked<T>(
+        &self,
+        module: &ngx_module_t,
+    ) -> Option<core::ptr::NonNull<T>> {
+        unsafe { self.0.stream_server_conf_unchecked(module) }
+    }
+}
+
+impl fmt::Debug for Session {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Session")
+            .field("udp", &self.is_udp())
+            .field("received", &self.received())
+            .field("status", &self.status())
+            .finish()
+    }
+}
@@ -0,0 +1,256 @@
+//! Deterministic time and timers for the unit tests of the modules.
+//!
+//! Once a test calls [`enable`], the crate reads the time from a mock clock instead of the cached
+//! nginx time, and the timers of [`Timer`](crate::core::Timer),
+//! [`async_::sleep`](crate::async_::sleep) and [`async_::interval`](crate::async_::interval) are
+//! scheduled on the mock clock instead of the event loop. The timers fire only when the test
+//! advances the clock, so the rate limiters, the caches with expiring entries and the code
+//! sleeping in async tasks can be tested without real sleeps and without a running nginx.
+//!
+//! The clock and the timers are thread-local: each test starts with the mock clock disabled, at
+//! zero and with no timers, as long as it runs on its own thread. The threads that never enable
+//! the mock clock, including the nginx workers of a module built with the `testing` feature, use
+//! the nginx time and the timers of the event loop.
+//!
+//! The feature is meant to be enabled in the `dev-dependencies` of a module:
+//!
+//! ```toml
+//! [dev-dependencies]
+//! ngx = { version = "0.5", features = ["testing"] }
+//! ```
+//!
+//! ```no_run
+//! # use core::time::Duration;
+//! # use ngx::testing;
+//! testing::enable();
+//! testing::set_time(Duration::from_secs(1_700_000_000));
+//! // arm a timer or start a sleep
+//! testing::advance(Duration::from_secs(60)); // fires the timers due within a minute
+//! assert_eq!(testing::pending_timers(), 0);
+//! ```
+use core::cell::RefCell;
+use core::time::Duration;
+
+extern crate std;
+use std::thread_local;
+use std::vec::Vec;
+
+use crate::ffi::{ngx_event_t, ngx_msec_t, time_t};
+
+struct MockTimer {
+    deadline: Duration,
+    event: *mut ngx_event_t,
+}
+
+#[derive(Default)]
+struct MockClock {
+    enabled: bool,
+    now: Duration,
+    /// Pending timers, ordered by the deadline and then by the order of arming.
+    timers: Vec<MockTimer>,
+}
+
+thread_local! {
+    static CLOCK: RefCell<MockClock> = RefCell::default();
+}
+
+/// Enables the mock clock on the current thread.
+pub fn enable() {
+    CLOCK.with_borrow_mut(|clock| clock.enabled = true)
+}
+
+/// Returns `true` if the mock clock is enabled on the current thread.
+pub fn is_enabled() -> bool {
+    CLOCK.with_borrow(|clock| clock.enabled)
+}
+
+/// Returns the current time of the mock clock, since the Unix epoch.
+pub fn now() -> Duration {
+    CLOCK.with_borrow(|clock| clock.now)
+}
+
+/// Sets the time of the mock clock, since the Unix epoch.
+///
+/// The timers are not fired, even if the time moves past their deadlines; see [`fire_timers`].
+pub fn set_time(now: Duration) {
+    CLOCK.with_borrow_mut(|clock| clock.now = now)
+}
+
+/// Advances the mock clock, firing the timers in the order of their deadlines.
+///
+/// The clock is set to the deadline of each timer before its handler is called, so the handler
+/// observes the time it was scheduled for. The timers armed by the handlers fire within the same
+/// call if they are due before the end of the interval.
+pub fn advance(duration: Duration) {
+    let end = now().saturating_add(duration);
+    while let Some(event) = pop_timer(end) {
+        fire(event);
+    }
+    set_time(end);
+}
+
+/// Fires the timers due at the current time of the mock clock, and returns the number of the
+/// fired timers.
+pub fn fire_timers() -> usize {
+    let now = now();
+    let mut n = 0;
+    while let Some(event) = pop_timer(now) {
+        fire(event);
+        n += 1;
+    }
+    n
+}
+
+/// Returns the number of the pending timers.
+pub fn pending_timers() -> usize {
+    CLOCK.with_borrow(|clock| clock.timers.len())
+}
+
+/// Returns the time until the earliest pending timer.
+pub fn next_timer() -> Option<Duration> {
+    CLOCK.with_borrow(|clock| {
+        clock.timers.first().map(|timer| timer.deadline.saturating_sub(clock.now))
+    })
+}
+
+/// Disables the mock clock, resets it to zero and drops the pending timers without firing them.
+pub fn reset() {
+    CLOCK.with_borrow_mut(|clock| *clock = MockClock::default())
+}
+
+fn pop_timer(until: Duration) -> Option<*mut ngx_event_t> {
+    CLOCK.with_borrow_mut(|clock| {
+        if clock.timers.first()?.deadline > until {
+            return None;
+        }
+        let timer = clock.timers.remove(0);
+        clock.now = clock.now.max(timer.deadline);
+        Some(timer.event)
+    })
+}
+
+fn fire(ev: *mut ngx_event_t) {
+    // SAFETY: the event stays valid while the timer is set, see `add_timer`.
+    let ev = unsafe { &mut *ev };
+    ev.set_timer_set(0);
+    ev.set_timedout(1);
+    if let Some(handler) = ev.handler {
+        // SAFETY: the handler is set by the owner of the event.
+        unsafe { handler(ev) };
+    }
+}
+
+pub(crate) fn mock_now() -> Option<Duration> {
+    CLOCK.with_borrow(|clock| clock.enabled.then_some(clock.now))
+}
+
+pub(crate) fn mock_current_msec() -> Option<ngx_msec_t> {
+    mock_now().map(|now| now.as_millis() as ngx_msec_t)
+}
+
+pub(crate) fn mock_time() -> Option<time_t> {
+    mock_now().map(|now| now.as_secs() as time_t)
+}
+
+/// Schedules the event on the mock clock, replacing the previous timer of the event, and returns
+/// `false` if the mock clock is disabled.
+///
+/// # Safety
+///
+/// The event must stay valid until the timer fires or is deleted.
+pub(crate) unsafe fn mock_add_timer(ev: *mut ngx_event_t, msec: ngx_msec_t) -> bool {
+    let enabled = CLOCK.with_borrow_mut(|clock| {
+        if !clock.enabled {
+            return false;
+        }
+        clock.timers.retain(|timer| timer.event != ev);
+        let deadline = clock.now.saturating_add(Duration::from_millis(msec as u64));
+        let at = clock.timers.partition_point(|timer| timer.deadline <= deadline);
+        clock.timers.insert(at, MockTimer { deadline, event: ev });
+        true
+    });
+    if enabled {
+        // SAFETY: the event is valid, as required by the caller.
+        unsafe { (*ev).set_timer_set(1) };
+    }
+    enabled
+}
+
+/// Removes the event from the mock clock, and returns `false` if the mock clock is disabled.
+///
+/// # Safety
+///
+/// The event must be valid.
+pub(crate) unsafe fn mock_del_timer(ev: *mut ngx_event_t) -> bool {
+    let enabled = CLOCK.with_borrow_mut(|clock| {
+        clock.timers.retain(|timer| timer.event != ev);
+        clock.enabled
+    });
+    if enabled {
+        // SAFETY: the event is valid, as required by the caller.
+        unsafe { (*ev).set_timer_set(0) };
+    }
+    enabled
+}
+
+#[cfg(test)]
+mod tests {
+    use core::mem;
+
+    use super::*;
+
+    thread_local! {
+        static FIRED: RefCell<Vec<usize>> = const { RefCell::new(Vec::new()) };
+    }
+
+    unsafe extern "C" fn handler(ev: *mut ngx_event_t) {
+        let id = unsafe { (*ev).data } as usize;
+        FIRED.with_borrow_mut(|fired| fired.push(id));
+    }
+
+    fn event(id: usize) -> ngx_event_t {
+        let mut ev: ngx_event_t = unsafe { mem::zeroed() };
+        ev.data = id as _;
+        ev.handler = Some(handler);
+        ev
+    }
+
+    #[test]
+    fn test_timers() {
+        let mut a = event(1);
+        let mut b = event(2);
+        let mut c = event(3);
+
+        assert_eq!(mock_time(), None);
+        assert!(unsafe { !mock_add_timer(&mut a, 3000) });
+        assert_eq!(a.timer_set(), 0);
+
+        enable();
+        set_time(Duration::from_secs(100));
+        assert_eq!(mock_time(), Some(100));
+        assert_eq!(mock_current_msec(), Some(100_000));
+
+        unsafe {
+            assert!(mock_add_timer(&mut a, 3000));
+            mock_add_timer(&mut b, 1000);
+            mock_add_timer(&mut c, 2000);
+            mock_del_timer(&mut c);
+        }
+        assert_eq!(pending_timers(), 2);
+        assert_eq!(next_timer(), Some(Duration::from_secs(1)));
+        assert_eq!(c.timer_set(), 0);
+
+        advance(Duration::from_millis(999));
+        assert_eq!(fire_timers(), 0);
+
+        advance(Duration::from_secs(5));
+        assert_eq!(FIRED.with_borrow(Vec::clone), [2, 1]);
+        assert_eq!(a.timedout(), 1);
+        assert_eq!(a.timer_set(), 0);
+        assert_eq!(now(), Duration::from_millis(105_999));
+        assert_eq!(pending_timers(), 0);
+
+        reset();
+        assert!(!is_enabled());
+    }
+}
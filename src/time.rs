@@ -0,0 +1,139 @@
+//! Cached time of the nginx event loop.
+//!
+//! nginx caches the current time at the start of each iteration of the event loop, and all the
+//! timestamps within an iteration, e.g. in the access log or in the `Date` header, are the same.
+//! The functions here read the cached time, which is cheaper than a system call and consistent
+//! with the rest of nginx.
+//!
+//! The time is replaced with the mock clock of [`testing`](crate::testing) when the `testing`
+//! feature is enabled.
+//!
+//! ```no_run
+//! # use ngx::core::Pool;
+//! # fn f(pool: &Pool) {
+//! let now = ngx::time::now();
+//! let date = ngx::time::http_time(now.as_secs() as _, pool);
+//! # }
+//! ```
+use core::slice;
+use core::time::Duration;
+
+use crate::core::{NgxStr, Pool};
+use crate::ffi::{
+    NGX_ERROR, ngx_http_cookie_time, ngx_http_time, ngx_msec_t, ngx_parse_http_time, time_t,
+};
+
+/// Length of a date formatted with [`http_time`], e.g. `Mon, 28 Sep 1970 06:00:00 GMT`.
+pub const HTTP_TIME_LEN: usize = "Mon, 28 Sep 1970 06:00:00 GMT".len();
+
+/// Length of a date formatted with [`cookie_time`], e.g. `Thu, 31-Dec-2037 23:55:55 GMT`.
+pub const COOKIE_TIME_LEN: usize = "Mon, 28-Sep-1970 06:00:00 GMT".len();
+
+/// Latest time that can be formatted as an HTTP date, `Fri, 31 Dec 9999 23:59:59 GMT`.
+///
+/// The formatting functions clamp the time to the range from the Unix epoch to this value, as the
+/// nginx functions print a four-digit year.
+pub const MAX_HTTP_TIME: time_t =
+    if size_of::<time_t>() < 8 { time_t::MAX } else { 253_402_300_799_i64 as time_t };
+
+/// Returns the cached wall clock time, since the Unix epoch, with the millisecond resolution.
+pub fn now() -> Duration {
+    crate::core::timeofday()
+}
+
+/// Returns the cached wall clock time as [`SystemTime`](std::time::SystemTime).
+#[cfg(feature = "std")]
+pub fn system_time() -> std::time::SystemTime {
+    std::time::UNIX_EPOCH + now()
+}
+
+/// Returns the cached wall clock time in seconds since the Unix epoch, same as `ngx_time()`.
+pub fn unix_time() -> time_t {
+    crate::core::time()
+}
+
+/// Returns the cached monotonic time in milliseconds, same as `ngx_current_msec`.
+///
+/// The value has an arbitrary origin and wraps around, so it is only suitable for measuring the
+/// intervals, see [`elapsed_since`].
+pub fn current_msec() -> ngx_msec_t {
+    crate::core::current_msec()
+}
+
+/// Returns the time elapsed since the `msec` obtained with [`current_msec`].
+pub fn elapsed_since(msec: ngx_msec_t) -> Duration {
+    Duration::from_millis(current_msec().wrapping_sub(msec) as u64)
+}
+
+/// Formats the time as an HTTP date, e.g. for the `Last-Modified` or `Expires` headers, using
+/// `ngx_http_time`.
+///
+/// The time is clamped to the range from 0 to [`MAX_HTTP_TIME`].
+pub fn format_http_time(t: time_t, buf: &mut [u8; HTTP_TIME_LEN]) -> &NgxStr {
+    // SAFETY: `ngx_http_time` writes exactly `HTTP_TIME_LEN` bytes for a clamped time.
+    unsafe { ngx_http_time(buf.as_mut_ptr(), clamp(t)) };
+    NgxStr::from_bytes(buf)
+}
+
+/// Formats the time as an HTTP date in a string allocated from the pool.
+///
+/// The time is clamped to the range from 0 to [`MAX_HTTP_TIME`]. Returns `None` if the allocation
+/// fails.
+pub fn http_time(t: time_t, pool: &Pool) -> Option<&NgxStr> {
+    let p = alloc(pool, HTTP_TIME_LEN)?;
+    // SAFETY: `ngx_http_time` writes exactly `HTTP_TIME_LEN` bytes to the allocated buffer for a
+    // clamped time.
+    unsafe {
+        ngx_http_time(p, clamp(t));
+        Some(NgxStr::from_bytes(slice::from_raw_parts(p, HTTP_TIME_LEN)))
+    }
+}
+
+/// Formats the time as the `expires` attribute of a cookie in a string allocated from the pool,
+/// using `ngx_http_cookie_time`.
+///
+/// The time is clamped to the range from 0 to [`MAX_HTTP_TIME`]. Returns `None` if the allocation
+/// fails.
+pub fn cookie_time(t: time_t, pool: &Pool) -> Option<&NgxStr> {
+    let p = alloc(pool, COOKIE_TIME_LEN)?;
+    // SAFETY: `ngx_http_cookie_time` writes at most `COOKIE_TIME_LEN` bytes to the allocated
+    // buffer for a clamped time, and returns the end of the written data.
+    unsafe {
+        let end = ngx_http_cookie_time(p, clamp(t));
+        let len = end.offset_from(p) as usize;
+        Some(NgxStr::from_bytes(slice::from_raw_parts(p, len)))
+    }
+}
+
+/// Parses an HTTP date in any of the formats accepted by nginx, using `ngx_parse_http_time`.
+///
+/// Returns `None` if the date is not valid.
+pub fn parse_http_time(value: impl AsRef<[u8]>) -> Option<time_t> {
+    let value = value.as_ref();
+    // SAFETY: `ngx_parse_http_time` only reads `value.len()` bytes.
+    let t = unsafe { ngx_parse_http_time(value.as_ptr().cast_mut(), value.len()) };
+    (t != NGX_ERROR as time_t).then_some(t)
+}
+
+/// Limits the time to the years 1970 to 9999 printed by nginx with the fixed width.
+fn clamp(t: time_t) -> time_t {
+    t.clamp(0, MAX_HTTP_TIME)
+}
+
+fn alloc(pool: &Pool, len: usize) -> Option<*mut u8> {
+    let p = pool.alloc_unaligned(len).cast::<u8>();
+    (!p.is_null()).then_some(p)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clamp() {
+        assert_eq!(clamp(-1), 0);
+        assert_eq!(clamp(0), 0);
+        assert_eq!(clamp(1_700_000_000), 1_700_000_000);
+        assert_eq!(clamp(time_t::MAX), MAX_HTTP_TIME);
+    }
+}